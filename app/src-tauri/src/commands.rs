@@ -1,15 +1,22 @@
 //! Tauri Commands
 
-use flowsight_analysis::Analyzer;
-use flowsight_index::SymbolIndex;
-use flowsight_parser::get_parser;
-use flowsight_parser::parallel::{ParallelParser, ProgressPhase};
+use flowsight_analysis::async_tracker::AsyncTracker;
+use flowsight_analysis::funcptr::FuncPtrResolver;
+use flowsight_analysis::{Analyzer, FlowProgress};
+use flowsight_index::{IgnoreRules, SymbolIndex};
+use flowsight_knowledge::KnowledgeBase;
+use flowsight_parser::{get_parser, ParseResult};
+use flowsight_parser::cache::{PersistentCache, DEFAULT_CACHE_DIR};
+use flowsight_parser::parallel::{FileStats, ParallelParser, ProgressPhase, TimingSummary};
+use flowsight_parser::preprocessor::HeaderResolver;
+use flowsight_query::{mechanism_name, SearchMode, SymbolMatcher};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
 use tauri::Emitter;
-use walkdir::WalkDir;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AnalysisResult {
@@ -31,28 +38,82 @@ pub struct FunctionInfo {
     pub calls: Vec<String>,
 }
 
-/// Analyze a source file
-#[tauri::command]
-pub async fn analyze_file(path: String) -> Result<AnalysisResult, String> {
-    let path = PathBuf::from(&path);
+/// Parse and analysis of one file
+struct FileAnalysis {
+    parse_result: ParseResult,
+    analysis: flowsight_analysis::AnalysisResult,
+}
 
-    let parser = get_parser();
-    let mut parse_result = parser.parse_file(&path).map_err(|e| e.to_string())?;
+/// The last file analyzed, so several panels inspecting the same file share
+/// one parse
+///
+/// Keyed by path and modification time; a change to the file misses the
+/// cache and replaces the entry.
+#[derive(Default)]
+struct AnalysisCache {
+    key: Option<(PathBuf, SystemTime)>,
+    entry: Option<Arc<FileAnalysis>>,
+}
+
+static ANALYSIS_CACHE: Lazy<Mutex<AnalysisCache>> = Lazy::new(|| Mutex::new(AnalysisCache::default()));
 
-    let source = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+/// Parse and analyze `path`, reusing the cached result while the file is unchanged
+///
+/// `progress` hears about each flow tree built; a cache hit builds none.
+fn analyze_cached(path: &Path, progress: impl FnMut(FlowProgress)) -> Result<Arc<FileAnalysis>, String> {
+    let modified = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map_err(|e| e.to_string())?;
+    let key = (path.to_path_buf(), modified);
+    {
+        let cache = ANALYSIS_CACHE.lock().map_err(|e| e.to_string())?;
+        if cache.key.as_ref() == Some(&key) {
+            if let Some(entry) = &cache.entry {
+                return Ok(entry.clone());
+            }
+        }
+    }
 
+    // Analyze without holding the lock; a concurrent miss just does the work twice
+    let parser = get_parser();
+    let mut parse_result = parser.parse_file(path).map_err(|e| e.to_string())?;
+    let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
     let mut analyzer = Analyzer::new();
     let analysis = analyzer
-        .analyze(&source, &mut parse_result)
+        .analyze_with_progress(&source, &mut parse_result, progress)
         .map_err(|e| e.to_string())?;
 
+    let entry = Arc::new(FileAnalysis { parse_result, analysis });
+    let mut cache = ANALYSIS_CACHE.lock().map_err(|e| e.to_string())?;
+    *cache = AnalysisCache {
+        key: Some(key),
+        entry: Some(entry.clone()),
+    };
+    Ok(entry)
+}
+
+/// Analyze a source file
+///
+/// Emits `flow-progress` as the flow tree of each entry point is built.
+#[tauri::command]
+pub async fn analyze_file(path: String, app_handle: tauri::AppHandle) -> Result<AnalysisResult, String> {
+    let path = PathBuf::from(&path);
+    let cached = analyze_cached(&path, |progress| {
+        let _ = app_handle.emit("flow-progress", serde_json::json!({
+            "current": progress.current,
+            "total": progress.total,
+            "message": format!("Building flow {}/{}: {}", progress.current, progress.total, progress.entry)
+        }));
+    })?;
+    let (parse_result, analysis) = (&cached.parse_result, &cached.analysis);
+
     Ok(AnalysisResult {
         file: path.to_string_lossy().to_string(),
         functions_count: parse_result.functions.len(),
         structs_count: parse_result.structs.len(),
         async_handlers_count: analysis.async_bindings.len(),
-        entry_points: analysis.entry_points,
-        flow_trees: analysis.flow_trees,
+        entry_points: analysis.entry_points.clone(),
+        flow_trees: analysis.flow_trees.clone(),
     })
 }
 
@@ -60,27 +121,19 @@ pub async fn analyze_file(path: String) -> Result<AnalysisResult, String> {
 #[tauri::command]
 pub async fn get_functions(path: String) -> Result<Vec<FunctionInfo>, String> {
     let path = PathBuf::from(&path);
+    let cached = analyze_cached(&path, |_| {})?;
 
-    let parser = get_parser();
-    let mut parse_result = parser.parse_file(&path).map_err(|e| e.to_string())?;
-
-    let source = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
-
-    let mut analyzer = Analyzer::new();
-    let _ = analyzer
-        .analyze(&source, &mut parse_result)
-        .map_err(|e| e.to_string())?;
-
-    let functions: Vec<FunctionInfo> = parse_result
+    let functions: Vec<FunctionInfo> = cached
+        .parse_result
         .functions
-        .into_iter()
+        .iter()
         .map(|(name, func)| FunctionInfo {
-            name,
-            return_type: func.return_type,
-            line: func.location.map(|l| l.line).unwrap_or(0),
+            name: name.clone(),
+            return_type: func.return_type.clone(),
+            line: func.location.as_ref().map(|l| l.line).unwrap_or(0),
             is_callback: func.is_callback,
-            callback_context: func.callback_context,
-            calls: func.calls,
+            callback_context: func.callback_context.clone(),
+            calls: func.calls.clone(),
         })
         .collect();
 
@@ -96,6 +149,11 @@ pub async fn read_file(path: String) -> Result<String, String> {
 /// Global index state
 static INDEX: Lazy<Mutex<SymbolIndex>> = Lazy::new(|| Mutex::new(SymbolIndex::new()));
 
+/// Cancel flag of the running indexer, if any
+///
+/// Lock this before `INDEX` when holding both.
+static INDEXING: Lazy<Mutex<Option<Arc<AtomicBool>>>> = Lazy::new(|| Mutex::new(None));
+
 /// Project information
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProjectInfo {
@@ -116,17 +174,40 @@ pub struct SearchResult {
     pub is_callback: bool,
 }
 
+/// One page of search results
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchPage {
+    pub results: Vec<SearchResult>,
+    /// Number of matches across all pages
+    pub total: usize,
+}
+
+/// Page size used when the caller doesn't ask for one
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+
 /// Open a project directory - returns immediately, indexing happens in background
+///
+/// Paths matching `ignore` or the project's `.flowsightignore` are not indexed.
 #[tauri::command]
-pub async fn open_project(path: String, app_handle: tauri::AppHandle) -> Result<ProjectInfo, String> {
+pub async fn open_project(
+    path: String,
+    ignore: Option<Vec<String>>,
+    app_handle: tauri::AppHandle,
+) -> Result<ProjectInfo, String> {
     let project_path = PathBuf::from(&path);
 
     if !project_path.is_dir() {
         return Err("Path is not a directory".into());
     }
+    let ignore = IgnoreRules::for_project(&project_path, &ignore.unwrap_or_default()).map_err(|e| e.to_string())?;
 
-    // Clear previous index
+    // Cancel any indexer still running and clear previous index
+    let cancel = Arc::new(AtomicBool::new(false));
     {
+        let mut indexing = INDEXING.lock().map_err(|e| e.to_string())?;
+        if let Some(previous) = indexing.replace(cancel.clone()) {
+            previous.store(true, Ordering::Relaxed);
+        }
         let mut index = INDEX.lock().map_err(|e| e.to_string())?;
         *index = SymbolIndex::new();
     }
@@ -137,7 +218,7 @@ pub async fn open_project(path: String, app_handle: tauri::AppHandle) -> Result<
         .stack_size(8 * 1024 * 1024)
         .name("indexer".into())
         .spawn(move || {
-            index_project_background(project_path, app_handle);
+            index_project_background(project_path, ignore, app_handle, cancel);
         })
         .ok();
 
@@ -151,8 +232,27 @@ pub async fn open_project(path: String, app_handle: tauri::AppHandle) -> Result<
     })
 }
 
+/// Stop the running indexer; the index is left empty
+#[tauri::command]
+pub async fn cancel_indexing() -> Result<(), String> {
+    let indexing = INDEXING.lock().map_err(|e| e.to_string())?;
+    if let Some(cancel) = indexing.as_ref() {
+        cancel.store(true, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
 /// Background indexing function
-fn index_project_background(project_path: PathBuf, app_handle: tauri::AppHandle) {
+///
+/// The index is built aside and only published if `cancel` is still unset,
+/// so a cancelled or superseded run never leaves a partial index behind.
+fn index_project_background(
+    project_path: PathBuf,
+    ignore: IgnoreRules,
+    app_handle: tauri::AppHandle,
+    cancel: Arc<AtomicBool>,
+) {
+    let started = Instant::now();
     let _ = app_handle.emit("index-progress", serde_json::json!({
         "phase": "scanning",
         "current": 0,
@@ -162,7 +262,10 @@ fn index_project_background(project_path: PathBuf, app_handle: tauri::AppHandle)
 
     // Scan files
     let mut c_files: Vec<PathBuf> = Vec::new();
-    for entry in WalkDir::new(&project_path).into_iter().filter_map(|e| e.ok()) {
+    for entry in ignore.walk(&project_path) {
+        if cancel.load(Ordering::Relaxed) {
+            return finish_cancelled(&app_handle, &cancel);
+        }
         if entry.path().extension().map(|ext| ext == "c" || ext == "h").unwrap_or(false) {
             c_files.push(entry.path().to_path_buf());
             if c_files.len() % 2000 == 0 {
@@ -184,9 +287,18 @@ fn index_project_background(project_path: PathBuf, app_handle: tauri::AppHandle)
         "message": format!("Parsing {} files...", total)
     }));
 
-    // Parse in parallel
-    let parallel_parser = ParallelParser::new();
-    let results = parallel_parser.parse_files(&c_files);
+    // Parse in parallel, reusing results for files unchanged since the last run
+    let mut parallel_parser = ParallelParser::new();
+    if let Ok(cache) = PersistentCache::new(project_path.join(DEFAULT_CACHE_DIR)) {
+        parallel_parser = parallel_parser.with_persistent_cache(cache);
+    }
+    parallel_parser = parallel_parser
+        .with_header_resolver(HeaderResolver::for_project(&project_path))
+        .with_cancel_flag(cancel.clone());
+    let results = parallel_parser.parse_files_timed(&c_files);
+    if parallel_parser.is_cancelled() {
+        return finish_cancelled(&app_handle, &cancel);
+    }
 
     let _ = app_handle.emit("index-progress", serde_json::json!({
         "phase": "indexing",
@@ -196,50 +308,128 @@ fn index_project_background(project_path: PathBuf, app_handle: tauri::AppHandle)
     }));
 
     // Build index
-    if let Ok(mut index) = INDEX.lock() {
-        for (i, (file, result)) in results.iter().enumerate() {
-            if let Ok(parse_result) = result {
-                for (_, func) in &parse_result.functions {
-                    index.add_function(func.clone(), file);
-                }
-                for (_, st) in &parse_result.structs {
-                    index.add_struct(st.clone());
-                }
+    let async_tracker = AsyncTracker::with_knowledge(&KnowledgeBase::builtin());
+    let funcptr_resolver = FuncPtrResolver::new();
+    let mut index = SymbolIndex::with_root(&project_path);
+    let mut file_stats = Vec::with_capacity(results.len());
+    for (i, (file, parse_result, stats)) in results.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            return finish_cancelled(&app_handle, &cancel);
+        }
+        let start = Instant::now();
+        for (_, func) in &parse_result.functions {
+            index.add_function(func.clone(), file);
+        }
+        for (_, st) in &parse_result.structs {
+            index.add_struct(st.clone());
+        }
+        for occurrence in &parse_result.occurrences {
+            index.add_occurrence(occurrence.clone());
+        }
+        if let Ok(source) = std::fs::read_to_string(file) {
+            for binding in async_tracker.analyze(&source, &parse_result.functions) {
+                let registered_by = binding
+                    .bind_location
+                    .as_ref()
+                    .and_then(|loc| enclosing_function(&parse_result.functions, loc.line));
+                index.add_async_binding(binding, registered_by);
             }
-            if i % 2000 == 0 && i > 0 {
-                let _ = app_handle.emit("index-progress", serde_json::json!({
-                    "phase": "indexing",
-                    "current": i,
-                    "total": total,
-                    "message": format!("Indexed {}/{}", i, total)
-                }));
+            let file_name = file.to_string_lossy();
+            for assignment in funcptr_resolver.find_ops_assignments(&source, &file_name) {
+                index.add_ops_assignment(assignment);
             }
         }
+        let analyze_ms = start.elapsed().as_secs_f64() * 1000.0;
+        file_stats.push((file.clone(), FileStats { analyze_ms, ..*stats }));
+        if i % 2000 == 0 && i > 0 {
+            let _ = app_handle.emit("index-progress", serde_json::json!({
+                "phase": "indexing",
+                "current": i,
+                "total": total,
+                "message": format!("Indexed {}/{}", i, total)
+            }));
+        }
+    }
+    for (file, headers) in parallel_parser.include_map() {
+        index.set_includes(&file, headers);
+    }
 
-        let stats = index.stats();
-        let _ = app_handle.emit("index-progress", serde_json::json!({
-            "phase": "done",
-            "current": total,
-            "total": total,
-            "files": total,
-            "functions": stats.total_functions,
-            "structs": stats.total_structs,
-            "message": format!("Done! {} files, {} functions", total, stats.total_functions)
-        }));
+    // Slowest files, so pathological ones (huge generated headers) can be excluded
+    let timing = TimingSummary::new(file_stats.iter().map(|(path, stats)| (path, stats)), 10);
+
+    // Publish under the job lock so a newer open_project can't be overwritten
+    let stats = index.stats();
+    {
+        let Ok(mut indexing) = INDEXING.lock() else {
+            return;
+        };
+        if cancel.load(Ordering::Relaxed) {
+            drop(indexing);
+            return finish_cancelled(&app_handle, &cancel);
+        }
+        if let Ok(mut global) = INDEX.lock() {
+            *global = index;
+        }
+        *indexing = None;
+    }
+
+    let _ = app_handle.emit("index-progress", serde_json::json!({
+        "phase": "done",
+        "current": total,
+        "total": total,
+        "files": total,
+        "functions": stats.total_functions,
+        "structs": stats.total_structs,
+        "timing": timing,
+        "message": format!(
+            "Done! {} files, {} functions in {:.1}s",
+            total,
+            stats.total_functions,
+            started.elapsed().as_secs_f64()
+        )
+    }));
+}
+
+/// Report a cancelled run, unless a newer one has already replaced it
+fn finish_cancelled(app_handle: &tauri::AppHandle, cancel: &Arc<AtomicBool>) {
+    let Ok(mut indexing) = INDEXING.lock() else {
+        return;
+    };
+    if !indexing.as_ref().is_some_and(|current| Arc::ptr_eq(current, cancel)) {
+        return;
     }
+    *indexing = None;
+    if let Ok(mut index) = INDEX.lock() {
+        *index = SymbolIndex::new();
+    }
+    let _ = app_handle.emit("index-progress", serde_json::json!({
+        "phase": "cancelled",
+        "current": 0,
+        "total": 0,
+        "message": "Indexing cancelled"
+    }));
 }
 
 /// Search for symbols in the index
 #[tauri::command]
-pub async fn search_symbols(query: String) -> Result<Vec<SearchResult>, String> {
+pub async fn search_symbols(
+    query: String,
+    mode: Option<SearchMode>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<SearchPage, String> {
+    // Substring search stays case-insensitive; regex and glob are exact unless the pattern says otherwise
+    let mode = mode.unwrap_or_default();
+    let matcher = SymbolMatcher::with_case(&query, mode, mode == SearchMode::Substring).map_err(|e| e.to_string())?;
     let index = INDEX.lock().map_err(|e| e.to_string())?;
-    let query_lower = query.to_lowercase();
 
     let mut results = Vec::new();
 
-    // Search functions
-    for (name, func) in &index.functions {
-        if name.to_lowercase().contains(&query_lower) {
+    // Search functions, then structs, each by name so pages are stable
+    let mut functions: Vec<_> = index.functions.iter().collect();
+    functions.sort_by_key(|(name, _)| *name);
+    for (name, func) in functions {
+        if matcher.is_match(name) {
             results.push(SearchResult {
                 name: name.clone(),
                 kind: "function".into(),
@@ -250,9 +440,10 @@ pub async fn search_symbols(query: String) -> Result<Vec<SearchResult>, String>
         }
     }
 
-    // Search structs
-    for (name, st) in &index.structs {
-        if name.to_lowercase().contains(&query_lower) {
+    let mut structs: Vec<_> = index.structs.iter().collect();
+    structs.sort_by_key(|(name, _)| *name);
+    for (name, st) in structs {
+        if matcher.is_match(name) {
             results.push(SearchResult {
                 name: name.clone(),
                 kind: "struct".into(),
@@ -263,10 +454,14 @@ pub async fn search_symbols(query: String) -> Result<Vec<SearchResult>, String>
         }
     }
 
-    // Limit results
-    results.truncate(50);
+    let total = results.len();
+    let results = results
+        .into_iter()
+        .skip(offset.unwrap_or(0))
+        .take(limit.unwrap_or(DEFAULT_SEARCH_LIMIT))
+        .collect();
 
-    Ok(results)
+    Ok(SearchPage { results, total })
 }
 
 /// Get index statistics
@@ -279,6 +474,9 @@ pub async fn get_index_stats() -> Result<IndexStats, String> {
         functions: stats.total_functions,
         structs: stats.total_structs,
         files: stats.total_files,
+        async_handlers: stats.async_handlers,
+        callbacks: stats.callbacks,
+        async_by_mechanism: stats.async_by_mechanism,
     })
 }
 
@@ -287,6 +485,10 @@ pub struct IndexStats {
     pub functions: usize,
     pub structs: usize,
     pub files: usize,
+    pub async_handlers: usize,
+    pub callbacks: usize,
+    /// Async handlers per mechanism family, e.g. "timer" → 3
+    pub async_by_mechanism: std::collections::BTreeMap<String, usize>,
 }
 
 /// Function detail with location info
@@ -321,7 +523,7 @@ pub async fn get_function_detail(name: String) -> Result<Option<FunctionDetail>,
             return_type: func.return_type.clone(),
             file: func.location.as_ref().map(|l| l.file.clone()),
             line: func.location.as_ref().map(|l| l.line).unwrap_or(0),
-            end_line: func.location.as_ref().map(|l| l.line + 10).unwrap_or(0), // Approximate
+            end_line: func.location.as_ref().map(|l| l.end_line).unwrap_or(0),
             is_callback: func.is_callback,
             callback_context: func.callback_context.clone(),
             calls: func.calls.clone(),
@@ -344,22 +546,14 @@ pub async fn get_function_detail(name: String) -> Result<Option<FunctionDetail>,
 #[tauri::command]
 pub async fn get_function_locations(path: String) -> Result<Vec<FunctionLocation>, String> {
     let path = PathBuf::from(&path);
+    let cached = analyze_cached(&path, |_| {})?;
 
-    let parser = get_parser();
-    let mut parse_result = parser.parse_file(&path).map_err(|e| e.to_string())?;
-
-    let source = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
-
-    let mut analyzer = Analyzer::new();
-    let _ = analyzer
-        .analyze(&source, &mut parse_result)
-        .map_err(|e| e.to_string())?;
-
-    let locations: Vec<FunctionLocation> = parse_result
+    let locations: Vec<FunctionLocation> = cached
+        .parse_result
         .functions
-        .into_iter()
+        .iter()
         .map(|(name, func)| FunctionLocation {
-            name,
+            name: name.clone(),
             line: func.location.as_ref().map(|l| l.line).unwrap_or(0),
             column: func.location.as_ref().map(|l| l.column).unwrap_or(0),
             is_callback: func.is_callback,
@@ -540,6 +734,22 @@ pub async fn delete_file_or_dir(path: String) -> Result<(), String> {
     }
 }
 
+/// Find the function whose body spans `line`
+fn enclosing_function(
+    functions: &std::collections::HashMap<String, flowsight_core::FunctionDef>,
+    line: u32,
+) -> Option<String> {
+    functions
+        .values()
+        .find(|f| {
+            f.location
+                .as_ref()
+                .map(|l| l.line <= line && line <= l.end_line)
+                .unwrap_or(false)
+        })
+        .map(|f| f.name.clone())
+}
+
 /// Caller information
 #[derive(Debug, Serialize)]
 pub struct CallerInfo {
@@ -564,24 +774,30 @@ pub async fn get_function_callers(
     for (name, func) in &index.functions {
         // Check if this function calls the target
         if func.calls.contains(&function_name) {
-            let call_type = if func.is_callback {
-                "async"
-            } else {
-                "direct"
-            };
-            
             callers.push(CallerInfo {
                 name: name.clone(),
                 file: func.location.as_ref().map(|l| l.file.clone()).unwrap_or_default(),
                 line: func.location.as_ref().map(|l| l.line).unwrap_or(0),
-                call_type: call_type.to_string(),
-                async_mechanism: func.callback_context.clone(),
+                call_type: "direct".to_string(),
+                async_mechanism: None,
             });
         }
     }
     
-    // Also check async bindings for indirect callers
-    // This would require tracking async bindings in the index
+    // Functions registering the target as an async handler (INIT_WORK, request_irq, ...)
+    for entry in index.get_async_bindings(&function_name) {
+        let Some(registrar) = &entry.registered_by else {
+            continue;
+        };
+        let loc = entry.binding.bind_location.as_ref();
+        callers.push(CallerInfo {
+            name: registrar.clone(),
+            file: loc.map(|l| l.file.clone()).unwrap_or_default(),
+            line: loc.map(|l| l.line).unwrap_or(0),
+            call_type: "async".to_string(),
+            async_mechanism: Some(mechanism_name(&entry.binding.mechanism)),
+        });
+    }
     
     let mut result = std::collections::HashMap::new();
     result.insert("callers".to_string(), callers);
@@ -619,6 +835,8 @@ pub struct ScenarioResult {
     pub path: Vec<ScenarioState>,
     pub annotated_flow_tree: Option<flowsight_core::FlowNode>,
     pub error: Option<String>,
+    /// Phases of a flow that crosses an async boundary (e.g. IRQ top half, workqueue)
+    pub timeline: Vec<flowsight_analysis::scenario::TimelineSegment>,
 }
 
 #[derive(Debug, Serialize)]
@@ -628,28 +846,65 @@ pub struct ScenarioState {
     pub variables: std::collections::HashMap<String, String>,
 }
 
+impl ScenarioRequest {
+    fn into_scenario(self) -> flowsight_analysis::scenario::Scenario {
+        use flowsight_analysis::scenario::{Scenario, ScenarioOptions, SymbolicValue, ValueBinding};
+
+        let bindings = self.bindings.iter()
+            .map(|b| ValueBinding {
+                path: b.path.clone(),
+                value: SymbolicValue::parse(&b.value, &b.value_type),
+            })
+            .collect();
+        let options = self.options.as_ref().map(|o| ScenarioOptions {
+            follow_async: o.follow_async.unwrap_or(true),
+            show_kernel_api: o.show_kernel_api.unwrap_or(true),
+            max_depth: o.max_depth.unwrap_or(10),
+        }).unwrap_or_default();
+
+        Scenario {
+            name: self.name,
+            entry_function: self.entry_function,
+            bindings,
+            options,
+        }
+    }
+}
+
+impl From<flowsight_analysis::scenario::ExecutionPath> for ScenarioResult {
+    fn from(result: flowsight_analysis::scenario::ExecutionPath) -> Self {
+        let path = result.states.iter()
+            .map(|s| ScenarioState {
+                function: s.function.clone(),
+                line: s.location.line,
+                variables: s.variables.iter()
+                    .map(|(k, v)| (k.clone(), v.display()))
+                    .collect(),
+            })
+            .collect();
+        Self {
+            success: result.completed,
+            path,
+            annotated_flow_tree: result.flow_tree,
+            error: result.termination_reason,
+            timeline: result.timeline,
+        }
+    }
+}
+
 /// Execute scenario-based symbolic analysis
 #[tauri::command]
 pub async fn execute_scenario(
     file_path: String,
     scenario: ScenarioRequest,
 ) -> Result<ScenarioResult, String> {
-    use flowsight_analysis::scenario::{Scenario, ScenarioExecutor, ScenarioOptions, SymbolicValue, ValueBinding};
+    use flowsight_analysis::scenario::ScenarioExecutor;
     
     let path = PathBuf::from(&file_path);
     
-    // Parse file
-    let parser = get_parser();
-    let mut parse_result = parser.parse_file(&path).map_err(|e| e.to_string())?;
-    
-    // Read source for analysis
-    let source = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
-    
-    // Run analysis to get flow trees
-    let mut analyzer = Analyzer::new();
-    let analysis = analyzer
-        .analyze(&source, &mut parse_result)
-        .map_err(|e| e.to_string())?;
+    // Parse and analyze (or reuse) to get flow trees
+    let cached = analyze_cached(&path, |_| {})?;
+    let (parse_result, analysis) = (&cached.parse_result, &cached.analysis);
     
     // Find the flow tree for the entry function
     let entry_tree = analysis.flow_trees.iter()
@@ -661,50 +916,70 @@ pub async fn execute_scenario(
             path: vec![],
             annotated_flow_tree: None,
             error: Some(format!("Entry function '{}' not found in flow trees", scenario.entry_function)),
+            timeline: vec![],
         });
     };
     
-    // Convert bindings
-    let bindings: Vec<ValueBinding> = scenario.bindings.iter()
-        .map(|b| ValueBinding {
-            path: b.path.clone(),
-            value: SymbolicValue::parse(&b.value, &b.value_type),
-        })
-        .collect();
-    
-    // Build scenario
-    let options = scenario.options.as_ref().map(|o| ScenarioOptions {
-        follow_async: o.follow_async.unwrap_or(true),
-        show_kernel_api: o.show_kernel_api.unwrap_or(true),
-        max_depth: o.max_depth.unwrap_or(10),
-    }).unwrap_or_default();
-    
-    let scenario_config = Scenario {
-        name: scenario.name,
-        entry_function: scenario.entry_function,
-        bindings,
-        options: options.clone(),
-    };
+    let scenario_config = scenario.into_scenario();
+    let options = scenario_config.options.clone();
     
     // Execute scenario
-    let mut executor = ScenarioExecutor::new(options);
+    let kb = KnowledgeBase::builtin();
+    let mut executor = ScenarioExecutor::new(options)
+        .with_async_timelines(&kb)
+        .with_constants(kb.constants)
+        .with_defines(&parse_result.defines)
+        .with_functions(&parse_result.functions);
     let result = executor.execute(&scenario_config, entry_tree);
     
-    // Convert states
-    let states: Vec<ScenarioState> = result.states.iter()
-        .map(|s| ScenarioState {
-            function: s.function.clone(),
-            line: s.location.line,
-            variables: s.variables.iter()
-                .map(|(k, v)| (k.clone(), v.display()))
-                .collect(),
+    Ok(ScenarioResult::from(result))
+}
+
+/// Result of one scenario in a collection run
+#[derive(Debug, Serialize)]
+pub struct NamedScenarioResult {
+    pub name: String,
+    pub result: ScenarioResult,
+}
+
+/// Execute several scenarios against one file, e.g. to compare which branches
+/// each input set reaches
+///
+/// The file is parsed and analyzed once; the executor options are taken from
+/// the first scenario.
+#[tauri::command]
+pub async fn execute_scenario_collection(
+    file_path: String,
+    scenarios: Vec<ScenarioRequest>,
+) -> Result<Vec<NamedScenarioResult>, String> {
+    use flowsight_analysis::scenario::{ScenarioCollection, ScenarioExecutor};
+
+    let path = PathBuf::from(&file_path);
+    let cached = analyze_cached(&path, |_| {})?;
+    let (parse_result, analysis) = (&cached.parse_result, &cached.analysis);
+
+    let mut collection = ScenarioCollection::new(&file_path);
+    for scenario in scenarios {
+        collection.add(scenario.into_scenario());
+    }
+    let options = collection
+        .scenarios
+        .first()
+        .map(|s| s.options.clone())
+        .unwrap_or_default();
+
+    let kb = KnowledgeBase::builtin();
+    let mut executor = ScenarioExecutor::new(options)
+        .with_async_timelines(&kb)
+        .with_constants(kb.constants)
+        .with_defines(&parse_result.defines)
+        .with_functions(&parse_result.functions);
+    Ok(executor
+        .execute_collection(&collection, &analysis.flow_trees)
+        .into_iter()
+        .map(|(name, result)| NamedScenarioResult {
+            name,
+            result: ScenarioResult::from(result),
         })
-        .collect();
-    
-    Ok(ScenarioResult {
-        success: true,
-        path: states,
-        annotated_flow_tree: result.flow_tree,
-        error: None,
-    })
+        .collect())
 }
@@ -23,6 +23,7 @@ pub fn run() {
             commands::get_functions,
             commands::read_file,
             commands::open_project,
+            commands::cancel_indexing,
             commands::search_symbols,
             commands::get_index_stats,
             commands::get_function_detail,
@@ -31,6 +32,7 @@ pub fn run() {
             commands::expand_directory,
             commands::export_flow_text,
             commands::execute_scenario,
+            commands::execute_scenario_collection,
             commands::get_function_callers,
             commands::create_file,
             commands::create_directory,
@@ -19,7 +19,7 @@ import { Settings, defaultSettings, type AppSettings } from './components/Settin
 import { FindReplace, type FindMatch } from './components/FindReplace'
 import { KeyboardShortcuts } from './components/KeyboardShortcuts'
 import { ScenarioPanel } from './components/ScenarioPanel'
-import { ScenarioResults } from './components/ScenarioResults'
+import { ScenarioResults, type TimelineSegment } from './components/ScenarioResults'
 import { CallersView } from './components/CallersView'
 import { GoToLine } from './components/GoToLine'
 import { ToastContainer, useToast } from './components/Toast'
@@ -32,6 +32,7 @@ import {
   FlowTreeNode, 
   ProjectInfo, 
   SearchResult, 
+  SearchPage,
   IndexStats,
   FunctionDetail 
 } from './types'
@@ -39,6 +40,22 @@ import {
 type ViewMode = 'flow' | 'code' | 'split'
 type FlowDisplayMode = 'graph' | 'text'  // 执行流显示模式：图形 vs 文本
 
+// 索引耗时统计 (后端 TimingSummary)
+interface IndexTiming {
+  files: number
+  parse_ms: number
+  analyze_ms: number
+  slowest: [string, { parse_ms: number; analyze_ms: number; bytes: number; functions: number }][]
+}
+
+// 最慢文件列表，用于进度提示
+function formatSlowest(timing: IndexTiming): string {
+  const lines = timing.slowest.map(([path, s]) =>
+    `${path}: 解析 ${s.parse_ms.toFixed(1)}ms, 分析 ${s.analyze_ms.toFixed(1)}ms (${s.functions} 函数)`
+  )
+  return ['最慢的文件:', ...lines].join('\n')
+}
+
 // 导航历史记录项
 interface NavigationEntry {
   filePath: string
@@ -71,6 +88,7 @@ function App() {
   const [project, setProject] = useState<ProjectInfo | null>(null)
   const [searchQuery, setSearchQuery] = useState('')
   const [searchResults, setSearchResults] = useState<SearchResult[]>([])
+  const [searchTotal, setSearchTotal] = useState(0)
   const [indexStats, setIndexStats] = useState<IndexStats | null>(null)
   const [functionDetail, setFunctionDetail] = useState<FunctionDetail | null>(null)
   const [fileTree, setFileTree] = useState<FileNode[]>([])
@@ -106,6 +124,7 @@ function App() {
       message: string
       files?: number
       functions?: number
+      timing?: IndexTiming
     }>('index-progress', (event) => {
       setIndexProgress(event.payload)
       if (event.payload.phase === 'done') {
@@ -115,8 +134,13 @@ function App() {
           functions: event.payload.functions || 0,
           structs: 0,
         })
+        // 异步处理函数等统计只在后端有
+        invoke<IndexStats>('get_index_stats').then(setIndexStats).catch(() => {})
         // 3秒后清除进度
         setTimeout(() => setIndexProgress(null), 3000)
+      } else if (event.payload.phase === 'cancelled') {
+        setIndexStats(null)
+        setTimeout(() => setIndexProgress(null), 3000)
       }
     })
     return () => { unlisten.then(fn => fn()) }
@@ -155,6 +179,7 @@ function App() {
   const [scenarioResults, setScenarioResults] = useState<{
     path: string[]
     states: { location: string; variables: Record<string, string> }[]
+    timeline: TimelineSegment[]
   } | null>(null)
   
   // 调用者分析状态
@@ -167,6 +192,7 @@ function App() {
     current: number
     total: number
     message: string
+    timing?: IndexTiming
   } | null>(null)
   
   // 拖放文件处理
@@ -509,13 +535,15 @@ function App() {
   useEffect(() => {
     if (searchQuery.length < 2) {
       setSearchResults([])
+      setSearchTotal(0)
       return
     }
 
     const timer = setTimeout(async () => {
       try {
-        const results = await invoke<SearchResult[]>('search_symbols', { query: searchQuery })
-        setSearchResults(results)
+        const page = await invoke<SearchPage>('search_symbols', { query: searchQuery, offset: 0 })
+        setSearchResults(page.results)
+        setSearchTotal(page.total)
       } catch (e) {
         console.error('Search error:', e)
       }
@@ -524,6 +552,20 @@ function App() {
     return () => clearTimeout(timer)
   }, [searchQuery])
 
+  // 加载下一页搜索结果
+  const loadMoreSearchResults = async () => {
+    try {
+      const page = await invoke<SearchPage>('search_symbols', {
+        query: searchQuery,
+        offset: searchResults.length,
+      })
+      setSearchResults(prev => [...prev, ...page.results])
+      setSearchTotal(page.total)
+    } catch (e) {
+      console.error('Search error:', e)
+    }
+  }
+
   // === 标签页管理 ===
   
   // 生成唯一 Tab ID
@@ -1124,6 +1166,11 @@ function App() {
                       )}
                     </div>
                   ))}
+                  {searchResults.length < searchTotal && (
+                    <div className="search-item search-more" onClick={loadMoreSearchResults}>
+                      显示 {searchResults.length} / {searchTotal}，加载更多...
+                    </div>
+                  )}
                 </div>
               )}
             </div>
@@ -1191,13 +1238,39 @@ function App() {
                       <span>{indexStats?.files || 0} 文件</span>
                       <span>•</span>
                       <span>{indexStats?.functions || 0} 函数</span>
+                      {indexStats?.async_handlers ? (
+                        <>
+                          <span>•</span>
+                          <span
+                            title={Object.entries(indexStats.async_by_mechanism || {})
+                              .map(([mechanism, count]) => `${mechanism}: ${count}`)
+                              .join('\n')}
+                          >
+                            {indexStats.async_handlers} 异步
+                          </span>
+                        </>
+                      ) : null}
                     </div>
                   </div>
 
                   {/* 索引进度条 */}
                   {indexProgress && (
                     <div className="index-progress">
-                      <div className="progress-message">{indexProgress.message}</div>
+                      <div
+                        className="progress-message"
+                        title={indexProgress.timing ? formatSlowest(indexProgress.timing) : undefined}
+                      >
+                        {indexProgress.message}
+                        {indexProgress.phase !== 'done' && indexProgress.phase !== 'cancelled' && (
+                          <button
+                            className="progress-cancel"
+                            onClick={() => invoke('cancel_indexing')}
+                            title="取消索引"
+                          >
+                            ✕
+                          </button>
+                        )}
+                      </div>
                       {indexProgress.total > 0 && (
                         <div className="progress-bar">
                           <div
@@ -1672,6 +1745,7 @@ function App() {
           scenarioName={currentScenarioName}
           path={scenarioResults.path}
           states={scenarioResults.states}
+          timeline={scenarioResults.timeline}
           onNodeClick={(funcName) => {
             // 跳转到对应函数
             handleNodeClick('', funcName)
@@ -1711,6 +1785,7 @@ function App() {
               path: { function: string; line: number; variables: Record<string, string> }[]
               annotated_flow_tree: FlowTreeNode | null
               error: string | null
+              timeline: TimelineSegment[]
             }>('execute_scenario', {
               filePath: filePath,
               scenario: {
@@ -1739,6 +1814,7 @@ function App() {
                   location: `${p.function}:${p.line}`,
                   variables: p.variables,
                 })),
+                timeline: scenarioResult.timeline,
               })
               setCurrentScenarioName(scenario.name)
               setScenarioResultsOpen(true)
@@ -598,6 +598,7 @@ function FlowViewInner({ flowTrees, onNodeClick, selectedFunction }: FlowViewPro
       icon: getNodeIcon(node.node_type),
       file: node.location?.file,
       line: node.location?.line,
+      expandedFrom: node.location?.expanded_from,
       description: node.description,
       confidence: node.confidence,
       asyncLabel: getAsyncLabel(node.node_type),
@@ -707,6 +708,11 @@ function FlowViewInner({ flowTrees, onNodeClick, selectedFunction }: FlowViewPro
               📍 {hoverData.file?.split('/').pop()}:{hoverData.line}
             </div>
           )}
+          {hoverData.expandedFrom && (
+            <div className="preview-location">
+              🧩 由宏 {hoverData.expandedFrom} 生成
+            </div>
+          )}
           {hoverData.description && (
             <div className="preview-description">{hoverData.description}</div>
           )}
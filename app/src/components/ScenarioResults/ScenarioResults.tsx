@@ -5,6 +5,7 @@
  * - 显示执行路径
  * - 显示每个节点的变量状态
  * - 高亮当前选中的节点
+ * - 跨异步边界时分阶段显示 (如中断上半部 → WorkQueue)
  */
 
 import { useState, useMemo } from 'react'
@@ -15,12 +16,28 @@ interface ScenarioState {
   variables: Record<string, string>
 }
 
+/** 跨异步边界的执行阶段 */
+export interface TimelineSegment {
+  name: string
+  context: 'Process' | 'SoftIrq' | 'HardIrq' | 'Unknown'
+  functions: string[]
+  separation: string | null
+}
+
+const CONTEXT_LABELS: Record<TimelineSegment['context'], string> = {
+  Process: '📦 进程上下文',
+  SoftIrq: '🔄 软中断上下文',
+  HardIrq: '⚡ 硬中断上下文',
+  Unknown: '❓ 未知上下文',
+}
+
 interface ScenarioResultsProps {
   isOpen: boolean
   onClose: () => void
   scenarioName?: string
   path: string[]
   states: ScenarioState[]
+  timeline?: TimelineSegment[]
   onNodeClick?: (funcName: string) => void
 }
 
@@ -30,6 +47,7 @@ export function ScenarioResults({
   scenarioName,
   path,
   states,
+  timeline = [],
   onNodeClick,
 }: ScenarioResultsProps) {
   const [selectedIndex, setSelectedIndex] = useState<number | null>(null)
@@ -78,6 +96,39 @@ export function ScenarioResults({
         </div>
         
         <div className="results-content">
+          {/* 异步阶段 */}
+          {timeline.length > 1 && (
+            <div className="async-phases">
+              {timeline.map((segment, index) => (
+                <div key={index} className="async-phase-group">
+                  {index > 0 && (
+                    <div className="async-separation">
+                      <span className="separation-arrow">⇣</span>
+                      <span>{segment.separation || '异步执行'}</span>
+                    </div>
+                  )}
+                  <div className={`async-phase ${segment.context.toLowerCase()}`}>
+                    <div className="phase-header">
+                      <span className="phase-name">{segment.name}</span>
+                      <span className="phase-context">{CONTEXT_LABELS[segment.context]}</span>
+                    </div>
+                    <div className="phase-functions">
+                      {segment.functions.map(func => (
+                        <code
+                          key={func}
+                          className="phase-function"
+                          onClick={() => onNodeClick?.(func)}
+                        >
+                          {func}()
+                        </code>
+                      ))}
+                    </div>
+                  </div>
+                </div>
+              ))}
+            </div>
+          )}
+
           {/* 执行路径时间线 */}
           <div className="execution-timeline">
             {states.map((state, index) => {
@@ -196,6 +247,7 @@ export function ScenarioResults({
               scenario: scenarioName,
               path,
               states,
+              timeline,
             }
             const blob = new Blob([JSON.stringify(data, null, 2)], { type: 'application/json' })
             const url = URL.createObjectURL(blob)
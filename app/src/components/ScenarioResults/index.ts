@@ -1,2 +1,2 @@
-export { ScenarioResults } from './ScenarioResults'
+export { ScenarioResults, type TimelineSegment } from './ScenarioResults'
 
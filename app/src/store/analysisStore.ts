@@ -8,6 +8,7 @@ import {
   AnalysisResult,
   FlowTreeNode,
   FunctionDetail,
+  SearchPage,
   SearchResult,
 } from '../types'
 import { OutlineItem } from '../components/Outline/Outline'
@@ -107,8 +108,8 @@ export const useAnalysisStore = create<AnalysisState>((set, get) => ({
   // 搜索符号
   searchSymbols: async (query) => {
     try {
-      const results = await invoke<SearchResult[]>('search_symbols', { query })
-      return results
+      const page = await invoke<SearchPage>('search_symbols', { query })
+      return page.results
     } catch (e) {
       console.error('搜索失败:', e)
       return []
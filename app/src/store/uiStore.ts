@@ -4,6 +4,7 @@
 
 import { create } from 'zustand'
 import { AppSettings, defaultSettings } from '../components/Settings'
+import type { TimelineSegment } from '../components/ScenarioResults'
 
 // 视图模式
 export type ViewMode = 'flow' | 'code' | 'split'
@@ -65,6 +66,7 @@ interface UIState {
   scenarioResults: {
     path: string[]
     states: { location: string; variables: Record<string, string> }[]
+    timeline?: TimelineSegment[]
   } | null
 
   // 索引进度
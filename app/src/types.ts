@@ -7,6 +7,8 @@ export interface Location {
   file: string
   line: number
   column: number
+  /** 生成此处代码的宏 (如 DEFINE_SIMPLE_ATTRIBUTE) */
+  expanded_from?: string
 }
 
 // 流程节点类型
@@ -80,11 +82,20 @@ export interface SearchResult {
   is_callback: boolean
 }
 
+export interface SearchPage {
+  results: SearchResult[]
+  total: number
+}
+
 // 索引统计
 export interface IndexStats {
   functions: number
   structs: number
   files: number
+  // 异步处理函数总数及按机制分类（如 "timer" → 3）
+  async_handlers?: number
+  callbacks?: number
+  async_by_mechanism?: Record<string, number>
 }
 
 // 函数详情
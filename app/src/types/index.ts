@@ -38,6 +38,8 @@ export interface Location {
   file: string
   line: number
   column: number
+  /** 生成此处代码的宏 (如 DEFINE_SIMPLE_ATTRIBUTE) */
+  expanded_from?: string
 }
 
 // 分析结果
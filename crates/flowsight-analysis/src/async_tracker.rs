@@ -6,22 +6,56 @@
 //! - Interrupts (request_irq)
 //! - Tasklets (tasklet_init)
 //! - Kernel threads (kthread_run)
+//! - Notifier chains (blocking/atomic_notifier_chain_register, register_reboot_notifier)
+//! - DMA completion callbacks (`desc->callback = handler`, dmaengine_submit)
+//!
+//! More patterns can come from a knowledge base's `async_patterns`, so
+//! subsystem-specific APIs are recognized without code changes.
 
+use crate::callgraph::core_context;
 use flowsight_core::{AsyncBinding, AsyncMechanism, ExecutionContext, FunctionDef, Location};
+use flowsight_knowledge::KnowledgeBase;
 use regex::Regex;
 use std::collections::HashMap;
 
+/// `AsyncMechanism::Custom` name of DMA engine completion callbacks
+pub const DMA_CALLBACK: &str = "dma_callback";
+
 /// Pattern definition for async mechanism
 struct AsyncPattern {
     mechanism: AsyncMechanism,
     context: ExecutionContext,
     bind_patterns: Vec<Regex>,
     trigger_patterns: Vec<Regex>,
+    /// Loaded from a knowledge base; yields to a builtin binding on the same line
+    from_knowledge: bool,
+}
+
+/// Registration of a `notifier_block` on a notifier chain
+///
+/// The last capture is the block; the first is the chain unless `chain` is fixed.
+struct NotifierPattern {
+    register: Regex,
+    chain: Option<&'static str>,
+    context: ExecutionContext,
 }
 
 /// Async mechanism tracker
 pub struct AsyncTracker {
     patterns: Vec<AsyncPattern>,
+    notifier_patterns: Vec<NotifierPattern>,
+    /// `struct notifier_block nb = { ... }`
+    notifier_init_re: Regex,
+    /// `.notifier_call = handler` inside an initializer
+    notifier_field_re: Regex,
+    /// `priv->nb.notifier_call = handler;`
+    notifier_assign_re: Regex,
+    /// `*_notifier_call_chain(&chain, ...)`
+    notifier_trigger_re: Regex,
+    /// `desc->callback = handler;` on a `dma_async_tx_descriptor`
+    dma_callback_re: Regex,
+    /// `dmaengine_submit(desc)`
+    dma_submit_re: Regex,
 }
 
 impl AsyncTracker {
@@ -29,9 +63,86 @@ impl AsyncTracker {
     pub fn new() -> Self {
         Self {
             patterns: Self::default_patterns(),
+            notifier_patterns: Self::notifier_patterns(),
+            notifier_init_re: Regex::new(r"struct\s+notifier_block\s+(\w+)\s*=\s*\{([^}]*)\}").unwrap(),
+            notifier_field_re: Regex::new(r"\.notifier_call\s*=\s*(\w+)").unwrap(),
+            notifier_assign_re: Regex::new(r"([\w\.\->]+)\.notifier_call\s*=\s*(\w+)\s*;").unwrap(),
+            notifier_trigger_re: Regex::new(
+                r"(?:blocking|atomic|srcu|raw)_notifier_call_chain\s*\(\s*&?([\w\.\->]+)",
+            )
+            .unwrap(),
+            dma_callback_re: Regex::new(r"([\w\.\->]+)(?:->|\.)callback(?:_result)?\s*=\s*(\w+)\s*;").unwrap(),
+            dma_submit_re: Regex::new(r"dmaengine_submit\s*\(\s*([\w\.\->]+)\s*\)").unwrap(),
         }
     }
 
+    /// Create a tracker with the default patterns plus the `async_patterns`
+    /// of `kb`
+    ///
+    /// Each pattern's regexes bind and trigger like the builtins and its
+    /// bindings take the pattern's declared context. Regexes that don't
+    /// compile are skipped.
+    pub fn with_knowledge(kb: &KnowledgeBase) -> Self {
+        let mut tracker = Self::new();
+        let mut names: Vec<&String> = kb.async_patterns.keys().collect();
+        names.sort();
+        for name in names {
+            let pattern = &kb.async_patterns[name];
+            let compile = |sources: &[String]| -> Vec<Regex> {
+                sources
+                    .iter()
+                    .filter_map(|source| match Regex::new(source) {
+                        Ok(re) => Some(re),
+                        Err(e) => {
+                            tracing::warn!("Skipping async pattern '{}' regex {:?}: {}", name, source, e);
+                            None
+                        }
+                    })
+                    .collect()
+            };
+            tracker.patterns.push(AsyncPattern {
+                mechanism: mechanism_for_pattern(name),
+                context: core_context(&pattern.context),
+                bind_patterns: compile(&pattern.bind_patterns),
+                trigger_patterns: compile(&pattern.trigger_patterns),
+                from_knowledge: true,
+            });
+        }
+        tracker
+    }
+
+    fn notifier_patterns() -> Vec<NotifierPattern> {
+        let chain_register = |family: &str, context| NotifierPattern {
+            register: Regex::new(&format!(
+                r"\b{}_notifier_chain_register\s*\(\s*&?([\w\.\->]+)\s*,\s*&?([\w\.\->]+)\s*\)",
+                family
+            ))
+            .unwrap(),
+            chain: None,
+            context,
+        };
+        let fixed = |api: &str, chain, context| NotifierPattern {
+            register: Regex::new(&format!(r"\b{}\s*\(\s*&?([\w\.\->]+)\s*\)", api)).unwrap(),
+            chain: Some(chain),
+            context,
+        };
+
+        vec![
+            chain_register("blocking", ExecutionContext::Process),
+            chain_register("srcu", ExecutionContext::Process),
+            // Atomic chains may be called from interrupt context and must not sleep
+            chain_register("atomic", ExecutionContext::HardIrq),
+            // Raw chains leave locking (and context) to the caller
+            chain_register("raw", ExecutionContext::Unknown),
+            fixed("register_reboot_notifier", "reboot_notifier_list", ExecutionContext::Process),
+            fixed("register_netdevice_notifier", "netdev_chain", ExecutionContext::Process),
+            fixed("register_pm_notifier", "pm_chain_head", ExecutionContext::Process),
+            fixed("register_inetaddr_notifier", "inetaddr_chain", ExecutionContext::Process),
+            fixed("register_die_notifier", "die_chain", ExecutionContext::HardIrq),
+            fixed("register_keyboard_notifier", "keyboard_notifier_list", ExecutionContext::HardIrq),
+        ]
+    }
+
     fn default_patterns() -> Vec<AsyncPattern> {
         vec![
             // Work queue
@@ -46,6 +157,7 @@ impl AsyncTracker {
                     Regex::new(r"schedule_work\s*\(\s*&?([\w\.\->]+)\s*\)").unwrap(),
                     Regex::new(r"queue_work\s*\([^,]+,\s*&?([\w\.\->]+)\s*\)").unwrap(),
                 ],
+                from_knowledge: false,
             },
             // Delayed work
             AsyncPattern {
@@ -59,6 +171,7 @@ impl AsyncTracker {
                     r"schedule_delayed_work\s*\(\s*&?([\w\.\->]+)\s*,",
                 )
                 .unwrap()],
+                from_knowledge: false,
             },
             // Timer
             AsyncPattern {
@@ -74,6 +187,7 @@ impl AsyncTracker {
                     Regex::new(r"mod_timer\s*\(\s*&?([\w\.\->]+)\s*,").unwrap(),
                     Regex::new(r"add_timer\s*\(\s*&?([\w\.\->]+)\s*\)").unwrap(),
                 ],
+                from_knowledge: false,
             },
             // High-resolution timer
             AsyncPattern {
@@ -85,26 +199,32 @@ impl AsyncTracker {
                 trigger_patterns: vec![
                     Regex::new(r"hrtimer_start\s*\(\s*&?([\w\.\->]+)\s*,").unwrap()
                 ],
+                from_knowledge: false,
             },
-            // Interrupt
+            // Interrupt, including the primary handler of a threaded interrupt
             AsyncPattern {
                 mechanism: AsyncMechanism::Interrupt { threaded: false },
                 context: ExecutionContext::HardIrq,
                 bind_patterns: vec![
                     Regex::new(r"request_irq\s*\([^,]+,\s*(\w+)\s*,").unwrap(),
                     Regex::new(r"devm_request_irq\s*\([^,]+,\s*[^,]+,\s*(\w+)\s*,").unwrap(),
+                    Regex::new(r"\brequest_threaded_irq\s*\([^,]+,\s*(\w+)\s*,").unwrap(),
+                    Regex::new(r"\bdevm_request_threaded_irq\s*\([^,]+,\s*[^,]+,\s*(\w+)\s*,").unwrap(),
                 ],
                 trigger_patterns: vec![],
+                from_knowledge: false,
             },
-            // Threaded interrupt
+            // Threaded interrupt handler, run by the irq/N kernel thread
             AsyncPattern {
                 mechanism: AsyncMechanism::Interrupt { threaded: true },
                 context: ExecutionContext::Process,
-                bind_patterns: vec![Regex::new(
-                    r"request_threaded_irq\s*\([^,]+,\s*\w+\s*,\s*(\w+)\s*,",
-                )
-                .unwrap()],
+                bind_patterns: vec![
+                    Regex::new(r"\brequest_threaded_irq\s*\([^,]+,\s*\w+\s*,\s*(\w+)\s*,").unwrap(),
+                    Regex::new(r"\bdevm_request_threaded_irq\s*\([^,]+,\s*[^,]+,\s*\w+\s*,\s*(\w+)\s*,")
+                        .unwrap(),
+                ],
                 trigger_patterns: vec![],
+                from_knowledge: false,
             },
             // Tasklet
             AsyncPattern {
@@ -117,6 +237,7 @@ impl AsyncTracker {
                 trigger_patterns: vec![
                     Regex::new(r"tasklet_schedule\s*\(\s*&?([\w\.\->]+)\s*\)").unwrap()
                 ],
+                from_knowledge: false,
             },
             // Kernel thread
             AsyncPattern {
@@ -127,6 +248,7 @@ impl AsyncTracker {
                     Regex::new(r"kthread_create\s*\(\s*(\w+)\s*,").unwrap(),
                 ],
                 trigger_patterns: vec![Regex::new(r"wake_up_process\s*\(").unwrap()],
+                from_knowledge: false,
             },
             // RCU callback
             AsyncPattern {
@@ -137,22 +259,7 @@ impl AsyncTracker {
                     Regex::new(r"call_rcu_sched\s*\(\s*&?([\w\.\->]+)\s*,\s*(\w+)\s*\)").unwrap(),
                 ],
                 trigger_patterns: vec![],
-            },
-            // Notifier chain
-            AsyncPattern {
-                mechanism: AsyncMechanism::Notifier,
-                context: ExecutionContext::Process,
-                bind_patterns: vec![
-                    Regex::new(r"register_reboot_notifier\s*\(\s*&?([\w\.\->]+)\s*\)").unwrap(),
-                    Regex::new(r"register_netdevice_notifier\s*\(\s*&?([\w\.\->]+)\s*\)").unwrap(),
-                    Regex::new(
-                        r"blocking_notifier_chain_register\s*\([^,]+,\s*&?([\w\.\->]+)\s*\)",
-                    )
-                    .unwrap(),
-                    Regex::new(r"atomic_notifier_chain_register\s*\([^,]+,\s*&?([\w\.\->]+)\s*\)")
-                        .unwrap(),
-                ],
-                trigger_patterns: vec![],
+                from_knowledge: false,
             },
             // Softirq
             AsyncPattern {
@@ -165,6 +272,7 @@ impl AsyncTracker {
                     Regex::new(r"raise_softirq\s*\(").unwrap(),
                     Regex::new(r"raise_softirq_irqoff\s*\(").unwrap(),
                 ],
+                from_knowledge: false,
             },
             // Completion (synchronization but often used with async)
             AsyncPattern {
@@ -179,6 +287,7 @@ impl AsyncTracker {
                     Regex::new(r"complete\s*\(\s*&?([\w\.\->]+)\s*\)").unwrap(),
                     Regex::new(r"complete_all\s*\(\s*&?([\w\.\->]+)\s*\)").unwrap(),
                 ],
+                from_knowledge: false,
             },
             // Wait queue
             AsyncPattern {
@@ -193,6 +302,7 @@ impl AsyncTracker {
                     Regex::new(r"wake_up_interruptible\s*\(\s*&?([\w\.\->]+)\s*\)").unwrap(),
                     Regex::new(r"wake_up_all\s*\(\s*&?([\w\.\->]+)\s*\)").unwrap(),
                 ],
+                from_knowledge: false,
             },
             // Deferred work (system_wq)
             AsyncPattern {
@@ -207,6 +317,7 @@ impl AsyncTracker {
                     Regex::new(r"queue_work_on\s*\([^,]+,\s*[^,]+,\s*&?([\w\.\->]+)\s*\)").unwrap(),
                     Regex::new(r"flush_work\s*\(\s*&?([\w\.\->]+)\s*\)").unwrap(),
                 ],
+                from_knowledge: false,
             },
             // IRQ work (runs in IRQ context but deferred)
             AsyncPattern {
@@ -219,6 +330,7 @@ impl AsyncTracker {
                 trigger_patterns: vec![
                     Regex::new(r"irq_work_queue\s*\(\s*&?([\w\.\->]+)\s*\)").unwrap()
                 ],
+                from_knowledge: false,
             },
         ]
     }
@@ -251,9 +363,16 @@ impl AsyncTracker {
                             String::new()
                         };
 
+                        let bind_line = (line_num + 1) as u32;
+                        let known = || {
+                            bindings.iter().any(|b: &AsyncBinding| {
+                                b.handler == handler && b.bind_location.as_ref().is_some_and(|l| l.line == bind_line)
+                            })
+                        };
                         if !handler.is_empty()
                             && handler != "NULL"
                             && functions.contains_key(&handler)
+                            && !(pattern.from_knowledge && known())
                         {
                             // Find trigger locations
                             let trigger_locations =
@@ -263,7 +382,7 @@ impl AsyncTracker {
                                 mechanism: pattern.mechanism.clone(),
                                 variable,
                                 handler,
-                                bind_location: Some(Location::new("", (line_num + 1) as u32, 0)),
+                                bind_location: Some(Location::new("", bind_line, 0)),
                                 trigger_locations,
                                 context: pattern.context.clone(),
                             });
@@ -273,6 +392,85 @@ impl AsyncTracker {
             }
         }
 
+        bindings.extend(self.analyze_notifiers(source, functions));
+        bindings.extend(self.analyze_dma_callbacks(source, functions));
+        bindings
+    }
+
+    /// DMA completion callbacks set on a descriptor that is then submitted
+    ///
+    /// `callback` is a common field name, so an assignment only counts when
+    /// the same descriptor is passed to `dmaengine_submit()`. Controllers
+    /// call it from their completion tasklet, so it must not sleep.
+    fn analyze_dma_callbacks(&self, source: &str, functions: &HashMap<String, FunctionDef>) -> Vec<AsyncBinding> {
+        let mut bindings = Vec::new();
+        for (line_num, line) in source.lines().enumerate() {
+            let Some(caps) = self.dma_callback_re.captures(line) else {
+                continue;
+            };
+            let (descriptor, handler) = (&caps[1], &caps[2]);
+            if !functions.contains_key(handler) {
+                continue;
+            }
+            let trigger_locations = self.find_triggers(source, std::slice::from_ref(&self.dma_submit_re), descriptor);
+            if trigger_locations.is_empty() {
+                continue;
+            }
+            bindings.push(AsyncBinding {
+                mechanism: AsyncMechanism::Custom(DMA_CALLBACK.to_string()),
+                variable: descriptor.to_string(),
+                handler: handler.to_string(),
+                bind_location: Some(Location::new("", (line_num + 1) as u32, 0)),
+                trigger_locations,
+                context: ExecutionContext::SoftIrq,
+            });
+        }
+        bindings
+    }
+
+    /// Notifier registrations, bound to the block's `.notifier_call`
+    ///
+    /// The binding's variable is the chain, so `*_notifier_call_chain()` on
+    /// the same chain counts as a trigger.
+    fn analyze_notifiers(&self, source: &str, functions: &HashMap<String, FunctionDef>) -> Vec<AsyncBinding> {
+        let normalize = |s: &str| s.replace('&', "").replace("->", ".").replace(' ', "");
+
+        // notifier_block -> handler
+        let mut handlers: HashMap<String, String> = HashMap::new();
+        for caps in self.notifier_init_re.captures_iter(source) {
+            if let Some(handler) = self.notifier_field_re.captures(&caps[2]) {
+                handlers.insert(normalize(&caps[1]), handler[1].to_string());
+            }
+        }
+        for caps in self.notifier_assign_re.captures_iter(source) {
+            handlers.insert(normalize(&caps[1]), caps[2].to_string());
+        }
+
+        let mut bindings = Vec::new();
+        for pattern in &self.notifier_patterns {
+            for (line_num, line) in source.lines().enumerate() {
+                let Some(caps) = pattern.register.captures(line) else {
+                    continue;
+                };
+                let block = &caps[caps.len() - 1];
+                let Some(handler) = handlers.get(&normalize(block)) else {
+                    continue;
+                };
+                if !functions.contains_key(handler) {
+                    continue;
+                }
+                let chain = pattern.chain.map(str::to_string).unwrap_or_else(|| caps[1].to_string());
+
+                bindings.push(AsyncBinding {
+                    mechanism: AsyncMechanism::Notifier,
+                    trigger_locations: self.find_triggers(source, std::slice::from_ref(&self.notifier_trigger_re), &chain),
+                    variable: chain,
+                    handler: handler.clone(),
+                    bind_location: Some(Location::new("", (line_num + 1) as u32, 0)),
+                    context: pattern.context.clone(),
+                });
+            }
+        }
         bindings
     }
 
@@ -306,6 +504,22 @@ impl AsyncTracker {
     }
 }
 
+/// Mechanism of a knowledge base async pattern, by the kernel type it is named after
+fn mechanism_for_pattern(name: &str) -> AsyncMechanism {
+    match name {
+        "work_struct" => AsyncMechanism::WorkQueue { delayed: false },
+        "delayed_work" => AsyncMechanism::WorkQueue { delayed: true },
+        "timer_list" => AsyncMechanism::Timer { high_resolution: false },
+        "hrtimer" => AsyncMechanism::Timer { high_resolution: true },
+        "tasklet_struct" | "tasklet" => AsyncMechanism::Tasklet,
+        "notifier_block" | "atomic_notifier" => AsyncMechanism::Notifier,
+        "rcu_head" => AsyncMechanism::RcuCallback,
+        "irq" => AsyncMechanism::Interrupt { threaded: false },
+        "threaded_irq" => AsyncMechanism::Interrupt { threaded: true },
+        name => AsyncMechanism::Custom(name.to_string()),
+    }
+}
+
 impl Default for AsyncTracker {
     fn default() -> Self {
         Self::new()
@@ -337,13 +551,7 @@ static int my_probe(void) {
             FunctionDef {
                 name: "my_work_handler".to_string(),
                 return_type: "void".to_string(),
-                params: vec![],
-                location: None,
-                calls: vec![],
-                called_by: vec![],
-                is_callback: false,
-                callback_context: None,
-                attributes: vec![],
+                ..Default::default()
             },
         );
 
@@ -351,4 +559,174 @@ static int my_probe(void) {
         assert_eq!(bindings.len(), 1);
         assert_eq!(bindings[0].handler, "my_work_handler");
     }
+
+    #[test]
+    fn test_notifier_detection() {
+        let tracker = AsyncTracker::new();
+        let source = r#"
+static int my_reboot(struct notifier_block *nb, unsigned long action, void *data) { return 0; }
+static int my_event(struct notifier_block *nb, unsigned long action, void *data) { return 0; }
+
+static struct notifier_block my_reboot_nb = {
+    .notifier_call = my_reboot,
+    .priority = 0,
+};
+
+static int my_probe(struct my_dev *priv) {
+    register_reboot_notifier(&my_reboot_nb);
+    priv->nb.notifier_call = my_event;
+    atomic_notifier_chain_register(&my_chain, &priv->nb);
+    atomic_notifier_call_chain(&my_chain, 0, NULL);
+    return 0;
+}
+
+static void my_remove(struct my_dev *priv) {
+    unregister_reboot_notifier(&my_reboot_nb);
+}
+"#;
+        let functions: HashMap<String, FunctionDef> = ["my_reboot", "my_event"]
+            .iter()
+            .map(|name| {
+                let func = FunctionDef {
+                    name: name.to_string(),
+                    return_type: "int".to_string(),
+                    ..Default::default()
+                };
+                (name.to_string(), func)
+            })
+            .collect();
+
+        let bindings = tracker.analyze(source, &functions);
+        assert_eq!(bindings.len(), 2, "{:?}", bindings);
+
+        let reboot = bindings.iter().find(|b| b.handler == "my_reboot").unwrap();
+        assert!(matches!(reboot.mechanism, AsyncMechanism::Notifier));
+        assert_eq!(reboot.variable, "reboot_notifier_list");
+        assert!(reboot.context.can_sleep());
+        assert_eq!(reboot.bind_location.as_ref().unwrap().line, 11);
+
+        let event = bindings.iter().find(|b| b.handler == "my_event").unwrap();
+        assert_eq!(event.variable, "my_chain");
+        assert!(!event.context.can_sleep());
+        assert_eq!(event.trigger_locations.len(), 1);
+        assert_eq!(event.trigger_locations[0].line, 14);
+    }
+
+    #[test]
+    fn test_knowledge_patterns() {
+        let mut kb = KnowledgeBase::builtin();
+        kb.async_patterns.insert(
+            "my_deferred".into(),
+            flowsight_knowledge::AsyncPattern {
+                description: "Driver-private deferral".into(),
+                context: flowsight_knowledge::ExecutionContext::SoftIrq,
+                bind_patterns: vec![r"my_defer_init\s*\(\s*&?([\w\.\->]+)\s*,\s*(\w+)\s*\)".into()],
+                trigger_patterns: vec![r"my_defer_kick\s*\(\s*&?([\w\.\->]+)\s*\)".into(), "(".into()],
+                handler_signature: None,
+                timeline: None,
+                handler_call_chain: None,
+            },
+        );
+        let tracker = AsyncTracker::with_knowledge(&kb);
+        let source = r#"
+static void my_handler(struct my_defer *d) {
 }
+
+static void my_work(struct work_struct *work) {
+}
+
+static int my_probe(void) {
+    my_defer_init(&dev->defer, my_handler);
+    INIT_WORK(&dev->work, my_work);
+    my_defer_kick(&dev->defer);
+    return 0;
+}
+"#;
+        let mut parser = flowsight_parser::treesitter::TreeSitterParser::new();
+        let functions = parser.parse_source(source, "test.c").unwrap().functions;
+        let bindings = tracker.analyze(source, &functions);
+
+        let custom = bindings.iter().find(|b| b.handler == "my_handler").unwrap();
+        assert!(matches!(&custom.mechanism, AsyncMechanism::Custom(name) if name == "my_deferred"));
+        assert!(matches!(custom.context, ExecutionContext::SoftIrq));
+        assert_eq!(custom.variable, "dev->defer");
+        assert_eq!(custom.trigger_locations.iter().map(|l| l.line).collect::<Vec<_>>(), vec![11]);
+
+        // The knowledge base's work_struct pattern matches INIT_WORK too; the builtin binding wins
+        let work: Vec<&AsyncBinding> = bindings.iter().filter(|b| b.handler == "my_work").collect();
+        assert_eq!(work.len(), 1);
+        assert!(matches!(work[0].mechanism, AsyncMechanism::WorkQueue { delayed: false }));
+    }
+    #[test]
+    fn test_threaded_irq_split() {
+        let tracker = AsyncTracker::new();
+        let source = r#"
+static irqreturn_t my_hardirq(int irq, void *data) {
+    return IRQ_WAKE_THREAD;
+}
+
+static irqreturn_t my_thread_fn(int irq, void *data) {
+    return IRQ_HANDLED;
+}
+
+static irqreturn_t other_thread_fn(int irq, void *data) {
+    return IRQ_HANDLED;
+}
+
+static int my_probe(struct device *dev) {
+    request_threaded_irq(irq, my_hardirq, my_thread_fn, IRQF_ONESHOT, "my", dev);
+    devm_request_threaded_irq(dev, irq2, NULL, other_thread_fn, IRQF_ONESHOT, "other", dev);
+    return 0;
+}
+"#;
+        let mut parser = flowsight_parser::treesitter::TreeSitterParser::new();
+        let functions = parser.parse_source(source, "test.c").unwrap().functions;
+        let bindings = tracker.analyze(source, &functions);
+        assert_eq!(bindings.len(), 3, "{:?}", bindings);
+
+        let primary = bindings.iter().find(|b| b.handler == "my_hardirq").unwrap();
+        assert!(matches!(primary.mechanism, AsyncMechanism::Interrupt { threaded: false }));
+        assert!(matches!(primary.context, ExecutionContext::HardIrq));
+
+        // A NULL primary handler leaves only the threaded half
+        for name in ["my_thread_fn", "other_thread_fn"] {
+            let threaded = bindings.iter().find(|b| b.handler == name).unwrap();
+            assert!(matches!(threaded.mechanism, AsyncMechanism::Interrupt { threaded: true }));
+            assert!(threaded.context.can_sleep());
+        }
+    }
+
+    #[test]
+    fn test_dma_callback_detection() {
+        let tracker = AsyncTracker::new();
+        let source = r#"
+static void my_dma_done(void *param) {
+}
+
+static void my_event(struct my_dev *dev) {
+}
+
+static int my_start(struct my_dev *dev) {
+    desc = dmaengine_prep_slave_sg(dev->chan, dev->sgl, dev->nents, DMA_DEV_TO_MEM, 0);
+    desc->callback = my_dma_done;
+    desc->callback_param = dev;
+    dev->ops.callback = my_event;
+    dmaengine_submit(desc);
+    return 0;
+}
+"#;
+        let mut parser = flowsight_parser::treesitter::TreeSitterParser::new();
+        let functions = parser.parse_source(source, "test.c").unwrap().functions;
+        let bindings = tracker.analyze(source, &functions);
+
+        // Only the descriptor handed to dmaengine_submit() counts
+        assert_eq!(bindings.len(), 1, "{:?}", bindings);
+        let dma = &bindings[0];
+        assert_eq!(dma.handler, "my_dma_done");
+        assert!(matches!(&dma.mechanism, AsyncMechanism::Custom(name) if name == DMA_CALLBACK));
+        assert!(!dma.context.can_sleep());
+        assert_eq!(dma.variable, "desc");
+        assert_eq!(dma.bind_location.as_ref().unwrap().line, 10);
+        assert_eq!(dma.trigger_locations[0].line, 13);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,384 @@
+//! Sleeping functions reachable from atomic context
+//!
+//! [`IrqChecker`](crate::irq_check::IrqChecker) follows hard IRQ handlers
+//! only. This check works over the whole program: every function is given
+//! the contexts it is reached in across all entry points (softirq handlers
+//! such as timers and tasklets, framework callbacks the knowledge base marks
+//! atomic, and calls made while a spinlock is held), and a function calling
+//! a sleeping API is reported when one of those contexts cannot sleep. Such
+//! a function is usually fine on its other callers' paths, which is why the
+//! report carries the atomic path that reaches it and, if there is one, a
+//! path on which it may sleep.
+//!
+//! Hard IRQ handlers and DMA callbacks are left to the IRQ checker so the
+//! same call is not reported twice.
+//!
+//! Held locks are tracked through the call sites of a function in source
+//! order, without following branches: an unlock on an early-return branch
+//! ends the region early, so calls after it are missed rather than
+//! misreported. Sleeping APIs are the knowledge base entries marked
+//! `can_sleep`; a call passing `GFP_ATOMIC` or `GFP_NOWAIT` is not counted.
+
+use crate::async_tracker::DMA_CALLBACK;
+use crate::callgraph::core_context;
+use flowsight_core::{AsyncBinding, AsyncMechanism, CallSite, ExecutionContext, FunctionDef, OpsAssignment};
+use flowsight_knowledge::KnowledgeBase;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+/// Calls that enter atomic context, and the calls that leave it
+const ATOMIC_SECTIONS: &[(&str, &str)] = &[
+    ("spin_lock", "spin_unlock"),
+    ("spin_lock_bh", "spin_unlock_bh"),
+    ("spin_lock_irq", "spin_unlock_irq"),
+    ("spin_lock_irqsave", "spin_unlock_irqrestore"),
+    ("raw_spin_lock", "raw_spin_unlock"),
+    ("raw_spin_lock_irq", "raw_spin_unlock_irq"),
+    ("raw_spin_lock_irqsave", "raw_spin_unlock_irqrestore"),
+    ("read_lock", "read_unlock"),
+    ("read_lock_bh", "read_unlock_bh"),
+    ("read_lock_irqsave", "read_unlock_irqrestore"),
+    ("write_lock", "write_unlock"),
+    ("write_lock_bh", "write_unlock_bh"),
+    ("write_lock_irqsave", "write_unlock_irqrestore"),
+    ("rcu_read_lock", "rcu_read_unlock"),
+    ("preempt_disable", "preempt_enable"),
+    ("local_irq_disable", "local_irq_enable"),
+    ("local_irq_save", "local_irq_restore"),
+    ("local_bh_disable", "local_bh_enable"),
+];
+
+/// A sleeping call on a path that cannot sleep
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AtomicSleep {
+    /// Function making the sleeping call
+    pub function: String,
+    /// Sleeping API called
+    pub api: String,
+    /// Line of the call (1-based)
+    pub line: u32,
+    /// Why the first function of `path` cannot sleep
+    pub origin: AtomicOrigin,
+    /// Call path from the atomic entry to `function`, both included
+    pub path: Vec<String>,
+    /// A path from an entry that may sleep to `function`, if it has one
+    pub sleepable_path: Option<Vec<String>>,
+}
+
+/// Where the atomic context of a path starts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AtomicOrigin {
+    /// The first function is a handler or callback running in `context`
+    Entry { context: ExecutionContext },
+    /// The first function holds `lock`, taken at `line`, while making the
+    /// next call of the path (or the sleeping call itself)
+    LockHeld { lock: String, line: u32 },
+}
+
+/// Whole-program check for sleeping calls in atomic context
+pub struct AtomicChecker;
+
+impl AtomicChecker {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Check `functions`, entered through the handlers of `bindings` and the
+    /// callbacks of `assignments`
+    ///
+    /// Functions whose `execution_context` is already set (see
+    /// [`Analyzer`](crate::Analyzer)) are entry points as well.
+    pub fn check(
+        &self,
+        functions: &HashMap<String, FunctionDef>,
+        bindings: &[AsyncBinding],
+        assignments: &[OpsAssignment],
+        kb: &KnowledgeBase,
+    ) -> Vec<AtomicSleep> {
+        let entries = entry_contexts(functions, bindings, assignments, kb);
+        let sleeps = |site: &CallSite| {
+            kb.get_api(&site.callee).is_some_and(|api| api.can_sleep)
+                && !site
+                    .arguments
+                    .iter()
+                    .any(|arg| arg.contains("GFP_ATOMIC") || arg.contains("GFP_NOWAIT"))
+        };
+
+        // Atomic reachability: entries that cannot sleep, then the callees of
+        // calls made under a lock, breadth first so each path is short
+        let mut atomic: HashMap<&str, (Vec<String>, AtomicOrigin)> = HashMap::new();
+        let mut queue = VecDeque::new();
+        for (name, context) in &entries {
+            if matches!(context, ExecutionContext::SoftIrq | ExecutionContext::HardIrq) {
+                atomic.insert(*name, (vec![name.to_string()], AtomicOrigin::Entry { context: context.clone() }));
+                queue.push_back(*name);
+            }
+        }
+        let mut findings = Vec::new();
+        let mut names: Vec<&String> = functions.keys().collect();
+        names.sort();
+        for name in names {
+            for (site, (lock, line)) in locked_calls(&functions[name]) {
+                let origin = AtomicOrigin::LockHeld { lock, line };
+                if let Some((callee, _)) = functions.get_key_value(&site.callee) {
+                    if !atomic.contains_key(callee.as_str()) {
+                        atomic.insert(callee.as_str(), (vec![name.clone(), callee.clone()], origin));
+                        queue.push_back(callee.as_str());
+                    }
+                } else if sleeps(site) {
+                    findings.push(AtomicSleep {
+                        function: name.clone(),
+                        api: site.callee.clone(),
+                        line: site.line,
+                        origin,
+                        path: vec![name.clone()],
+                        sleepable_path: None,
+                    });
+                }
+            }
+        }
+        while let Some(name) = queue.pop_front() {
+            let path = atomic[name].0.clone();
+            let origin = atomic[name].1.clone();
+            for callee in &functions[name].calls {
+                if let Some((callee, _)) = functions.get_key_value(callee) {
+                    if !atomic.contains_key(callee.as_str()) {
+                        let mut path = path.clone();
+                        path.push(callee.clone());
+                        atomic.insert(callee.as_str(), (path, origin.clone()));
+                        queue.push_back(callee.as_str());
+                    }
+                }
+            }
+        }
+
+        // Every function reached from an atomic entry: each sleeping API it
+        // calls, at its first call
+        let mut reached: Vec<(&&str, &(Vec<String>, AtomicOrigin))> = atomic.iter().collect();
+        reached.sort_by_key(|(name, _)| **name);
+        for (name, (path, origin)) in reached {
+            let mut apis = HashSet::new();
+            for site in functions[*name].call_sites.iter().filter(|&s| sleeps(s)) {
+                if apis.insert(site.callee.as_str()) {
+                    findings.push(AtomicSleep {
+                        function: name.to_string(),
+                        api: site.callee.clone(),
+                        line: site.line,
+                        origin: origin.clone(),
+                        path: path.clone(),
+                        sleepable_path: None,
+                    });
+                }
+            }
+        }
+
+        let sleepable = sleepable_paths(functions, &entries);
+        for finding in &mut findings {
+            finding.sleepable_path = sleepable.get(finding.function.as_str()).cloned();
+        }
+        findings.sort_by(|a, b| (&a.function, a.line, &a.api).cmp(&(&b.function, b.line, &b.api)));
+        findings.dedup_by(|a, b| a.function == b.function && a.line == b.line && a.api == b.api);
+        findings
+    }
+}
+
+impl Default for AtomicChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Entry points of `functions` and the context each runs in, by name
+fn entry_contexts<'a>(
+    functions: &'a HashMap<String, FunctionDef>,
+    bindings: &[AsyncBinding],
+    assignments: &[OpsAssignment],
+    kb: &KnowledgeBase,
+) -> BTreeMap<&'a str, ExecutionContext> {
+    let mut entries = BTreeMap::new();
+    let mut add = |name: &str, context: ExecutionContext| {
+        if let Some((name, _)) = functions.get_key_value(name) {
+            entries.entry(name.as_str()).or_insert(context);
+        }
+    };
+    for func in functions.values() {
+        if let Some(context) = &func.execution_context {
+            add(&func.name, context.clone());
+        }
+    }
+    for binding in bindings {
+        let checked_by_irq = match &binding.mechanism {
+            AsyncMechanism::Interrupt { threaded } => !threaded,
+            AsyncMechanism::Custom(name) => name == DMA_CALLBACK,
+            _ => false,
+        };
+        if !checked_by_irq {
+            add(&binding.handler, binding.context.clone());
+        }
+    }
+    for assignment in assignments {
+        if let Some(callback) = kb.get_callback(&assignment.ops_type, &assignment.field) {
+            add(&assignment.function, core_context(&callback.context));
+        }
+    }
+    entries
+}
+
+/// Shortest path from an entry running in process context to each function
+fn sleepable_paths<'a>(
+    functions: &'a HashMap<String, FunctionDef>,
+    entries: &BTreeMap<&'a str, ExecutionContext>,
+) -> HashMap<&'a str, Vec<String>> {
+    let mut paths: HashMap<&str, Vec<String>> = HashMap::new();
+    let mut queue = VecDeque::new();
+    for (name, context) in entries {
+        if *context == ExecutionContext::Process {
+            paths.insert(*name, vec![name.to_string()]);
+            queue.push_back(*name);
+        }
+    }
+    while let Some(name) = queue.pop_front() {
+        let path = paths[name].clone();
+        // Calls made under a lock do not make the callee sleepable
+        let locked: HashSet<&str> = locked_calls(&functions[name])
+            .into_iter()
+            .map(|(site, _)| site.callee.as_str())
+            .collect();
+        for callee in &functions[name].calls {
+            if locked.contains(callee.as_str()) {
+                continue;
+            }
+            if let Some((callee, _)) = functions.get_key_value(callee) {
+                if !paths.contains_key(callee.as_str()) {
+                    let mut path = path.clone();
+                    path.push(callee.clone());
+                    paths.insert(callee.as_str(), path);
+                    queue.push_back(callee.as_str());
+                }
+            }
+        }
+    }
+    paths
+}
+
+/// Call sites of `func` made inside an atomic section, with the innermost
+/// section's lock and the line it was entered on
+fn locked_calls(func: &FunctionDef) -> Vec<(&CallSite, (String, u32))> {
+    let mut sites: Vec<&CallSite> = func.call_sites.iter().collect();
+    sites.sort_by_key(|s| (s.line, s.column));
+
+    // (acquire call, lock, line)
+    let mut held: Vec<(&str, String, u32)> = Vec::new();
+    let mut calls = Vec::new();
+    for site in sites {
+        if let Some((acquire, _)) = ATOMIC_SECTIONS.iter().find(|(acquire, _)| *acquire == site.callee) {
+            held.push((*acquire, lock_name(site), site.line));
+        } else if let Some((acquire, _)) = ATOMIC_SECTIONS.iter().find(|(_, release)| *release == site.callee) {
+            let lock = lock_name(site);
+            if let Some(i) = held.iter().rposition(|(a, l, _)| a == acquire && *l == lock) {
+                held.remove(i);
+            }
+        } else if let Some((_, lock, line)) = held.last() {
+            calls.push((site, (lock.clone(), *line)));
+        }
+    }
+    calls
+}
+
+/// The lock a section call works on, e.g. `dev->lock` for
+/// `spin_lock(&dev->lock)`, or the call itself for `rcu_read_lock()`
+fn lock_name(site: &CallSite) -> String {
+    match site.arguments.first() {
+        Some(arg) => arg.trim().trim_start_matches('&').trim().to_string(),
+        None => site.callee.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::async_tracker::AsyncTracker;
+    use crate::funcptr::FuncPtrResolver;
+    use flowsight_parser::treesitter::TreeSitterParser;
+
+    #[test]
+    fn test_atomic_sleep() {
+        let source = r#"
+static void update_stats(struct my_dev *dev)
+{
+    mutex_lock(&dev->stats_lock);
+    dev->count++;
+    mutex_unlock(&dev->stats_lock);
+}
+
+static void refill(struct my_dev *dev)
+{
+    void *buf = kmalloc(64, GFP_ATOMIC);
+    update_stats(dev);
+}
+
+static void poll_timer(struct timer_list *t)
+{
+    struct my_dev *dev = from_timer(dev, t, timer);
+    refill(dev);
+}
+
+static int my_open(struct inode *inode, struct file *file)
+{
+    struct my_dev *dev = inode->i_private;
+    update_stats(dev);
+    spin_lock(&dev->lock);
+    msleep(10);
+    spin_unlock(&dev->lock);
+    msleep(10);
+    return 0;
+}
+
+static int my_probe(struct platform_device *pdev)
+{
+    struct my_dev *dev = platform_get_drvdata(pdev);
+    timer_setup(&dev->timer, poll_timer, 0);
+    return 0;
+}
+
+static const struct file_operations my_fops = {
+    .open = my_open,
+};
+"#;
+        let mut parser = TreeSitterParser::new();
+        let result = parser.parse_source(source, "test.c").unwrap();
+        let kb = KnowledgeBase::builtin();
+        let bindings = AsyncTracker::with_knowledge(&kb).analyze(source, &result.functions);
+        let assignments = FuncPtrResolver::new().find_ops_assignments(source, "test.c");
+        let findings = AtomicChecker::new().check(&result.functions, &bindings, &assignments, &kb);
+
+        // The GFP_ATOMIC allocation and the msleep() after the unlock are fine
+        assert_eq!(
+            findings,
+            vec![
+                AtomicSleep {
+                    function: "my_open".into(),
+                    api: "msleep".into(),
+                    line: 26,
+                    origin: AtomicOrigin::LockHeld {
+                        lock: "dev->lock".into(),
+                        line: 25,
+                    },
+                    path: vec!["my_open".into()],
+                    sleepable_path: Some(vec!["my_open".into()]),
+                },
+                AtomicSleep {
+                    function: "update_stats".into(),
+                    api: "mutex_lock".into(),
+                    line: 4,
+                    origin: AtomicOrigin::Entry {
+                        context: ExecutionContext::SoftIrq,
+                    },
+                    path: vec!["poll_timer".into(), "refill".into(), "update_stats".into()],
+                    sleepable_path: Some(vec!["my_open".into(), "update_stats".into()]),
+                },
+            ]
+        );
+    }
+}
@@ -5,30 +5,197 @@
 //! 当检测到入口点函数（如 probe, work handler）时，
 //! 自动注入完整的内核调用链，让用户看到真正的执行流程。
 
-use flowsight_core::{AsyncBinding, AsyncMechanism, CallEdge, CallType, FlowNode, FlowNodeType, CallConfidence, ConfidenceLevel};
+use flowsight_core::{
+    AsyncBinding, AsyncMechanism, CallConfidence, CallEdge, CallKind, CallType, CaseBranch, Confidence,
+    ConfidenceLevel, FlowNode, FlowNodeType, OccurrenceKind, Result,
+};
 use flowsight_knowledge::{KnowledgeBase, CallChain};
+use flowsight_parser::treesitter::mask_comments_and_literals;
 use flowsight_parser::ParseResult;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::async_tracker::DMA_CALLBACK;
+use crate::classification::{self, ResultClassifier};
+use crate::constraint::ConstraintCollector;
+use crate::funcptr::{self, FuncPtrBinding};
+use crate::pointer::AndersenSolver;
+use crate::AnalysisConfig;
+
+/// Lowercase kernel macros that are commonly written like function calls
+const KNOWN_MACROS: &[&str] = &[
+    "container_of",
+    "offsetof",
+    "likely",
+    "unlikely",
+    "min",
+    "max",
+    "min_t",
+    "max_t",
+    "clamp",
+    "clamp_t",
+    "list_entry",
+    "list_first_entry",
+    "list_for_each",
+    "list_for_each_entry",
+    "list_for_each_entry_safe",
+    "hlist_for_each_entry",
+    "for_each_possible_cpu",
+    "for_each_online_cpu",
+    "from_timer",
+    "from_work",
+    "module_param",
+    "pr_info",
+    "pr_err",
+    "pr_warn",
+    "pr_debug",
+    "dev_info",
+    "dev_err",
+    "dev_warn",
+    "dev_dbg",
+];
+
+/// Whether a call-like name is a macro: all-uppercase (e.g. `INIT_WORK`) or a known macro
+pub fn is_macro_call(name: &str) -> bool {
+    let all_upper = name.chars().any(|c| c.is_ascii_uppercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+    all_upper || KNOWN_MACROS.contains(&name)
+}
+
+/// Call kind of a flow tree node, as seen from its parent
+pub fn flow_node_call_kind(node: &FlowNode) -> CallKind {
+    match node.node_type {
+        FlowNodeType::AsyncCallback { .. } => CallKind::Async,
+        _ if is_macro_call(&node.name) => CallKind::Macro,
+        _ => CallKind::Direct,
+    }
+}
 
 /// Build call edges from parse result
+///
+/// `funcptr_bindings` (from [`funcptr::FuncPtrResolver::resolve_all`]) turn
+/// `x->field(...)` / `x.field(...)` calls in `source` into indirect edges.
+/// A call keeps the binding's confidence when `x` is the bound variable or
+/// is declared with the ops table's struct type; a call matched on the
+/// field name alone is at most `Medium`, and none is made when `x` is
+/// declared with a different struct type. Comments and strings are ignored.
 pub fn build_call_edges(
     parse_result: &ParseResult,
     async_bindings: &[AsyncBinding],
+    funcptr_bindings: &[FuncPtrBinding],
+    source: &str,
 ) -> Vec<CallEdge> {
     let mut edges = Vec::new();
 
-    // Direct calls
+    // Direct calls, one edge per call site when positions are known
     for (caller_name, caller) in &parse_result.functions {
-        for callee_name in &caller.calls {
+        let sites: Vec<(&String, Option<flowsight_core::Location>)> = if caller.call_sites.is_empty() {
+            caller.calls.iter().map(|callee| (callee, caller.location.clone())).collect()
+        } else {
+            let file = caller.location.as_ref().map(|l| l.file.as_str()).unwrap_or_default();
+            caller
+                .call_sites
+                .iter()
+                .map(|site| (&site.callee, Some(flowsight_core::Location::new(file, site.line, site.column))))
+                .collect()
+        };
+        for (callee_name, location) in sites {
             edges.push(CallEdge {
                 caller: caller_name.clone(),
                 callee: callee_name.clone(),
-                location: caller.location.clone(),
+                location,
                 call_type: CallType::Direct,
+                call_kind: if is_macro_call(callee_name) {
+                    CallKind::Macro
+                } else {
+                    CallKind::Direct
+                },
             });
         }
     }
 
+    // Indirect calls through resolved function pointer fields
+    let code = mask_comments_and_literals(source);
+    let lines: Vec<&str> = code.lines().collect();
+    // `receiver->field(` / `receiver.field(`, one pattern per field
+    let mut call_res: HashMap<&str, regex::Regex> = HashMap::new();
+    for binding in funcptr_bindings {
+        call_res.entry(&binding.field).or_insert_with(|| {
+            let pattern = format!(r"(\w+)\s*(?:->|\.)\s*{}\s*\(", regex::escape(&binding.field));
+            regex::Regex::new(&pattern).expect("escaped field is a valid pattern")
+        });
+    }
+    // `struct type *name` in parameter lists and declarations
+    let decl_re = regex::Regex::new(r"\bstruct\s+(\w+)[\s*]+(?:const\s+)?(\w+)\s*[;=,)\[]").unwrap();
+    let declared_types = |text: &str| -> HashMap<String, String> {
+        decl_re
+            .captures_iter(text)
+            .map(|caps| (caps[2].to_string(), caps[1].to_string()))
+            .collect()
+    };
+    let file_types = if funcptr_bindings.is_empty() {
+        HashMap::new()
+    } else {
+        declared_types(&code)
+    };
+    for (caller_name, caller) in &parse_result.functions {
+        let Some(loc) = &caller.location else {
+            continue;
+        };
+        let start = (loc.line as usize).saturating_sub(1);
+        let end = (loc.end_line as usize).min(lines.len());
+        if start >= end || funcptr_bindings.is_empty() {
+            continue;
+        }
+        let body = lines[start..end].join("\n");
+        // Locals and parameters shadow file-level declarations
+        let local_types = declared_types(&body);
+        let struct_type = |name: &str| local_types.get(name).or_else(|| file_types.get(name)).map(String::as_str);
+        for binding in funcptr_bindings {
+            let variable = binding
+                .source
+                .rsplit(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .find(|s| !s.is_empty());
+            let mut bound = None;
+            let mut name_only = None;
+            for caps in call_res[binding.field.as_str()].captures_iter(&body) {
+                let (Some(call), Some(receiver)) = (caps.get(0), caps.get(1)) else {
+                    continue;
+                };
+                let receiver_type = struct_type(receiver.as_str());
+                let same_type = binding.struct_type.as_deref().zip(receiver_type).map(|(a, b)| a == b);
+                if Some(receiver.as_str()) == variable || same_type == Some(true) {
+                    bound = Some(call.start());
+                    break;
+                }
+                if same_type.is_none() && name_only.is_none() {
+                    name_only = Some(call.start());
+                }
+            }
+            let confidence = match (binding.confidence, bound.is_some()) {
+                (funcptr::Confidence::High, true) => Confidence::High,
+                (funcptr::Confidence::High | funcptr::Confidence::Medium, _) => Confidence::Medium,
+                (funcptr::Confidence::Low, _) => Confidence::Low,
+            };
+            let Some(offset) = bound.or(name_only) else {
+                continue;
+            };
+            let line = loc.line + body[..offset].matches('\n').count() as u32;
+            edges.push(CallEdge {
+                caller: caller_name.clone(),
+                callee: binding.function.clone(),
+                location: Some(flowsight_core::Location::new(&loc.file, line, 0)),
+                call_type: CallType::Indirect { confidence },
+                call_kind: CallKind::Indirect,
+            });
+        }
+    }
+
+    // Calls through function pointer variables
+    edges.extend(pointer_variable_edges(parse_result, source));
+
     // Async calls
     for binding in async_bindings {
         // Find functions that contain trigger patterns
@@ -37,7 +204,7 @@ pub fn build_call_edges(
             for trigger_loc in &binding.trigger_locations {
                 if let Some(func_loc) = &func.location {
                     // Simple check: trigger is within function range
-                    if trigger_loc.line >= func_loc.line && trigger_loc.line <= func_loc.end_line {
+                    if location_within(trigger_loc, func_loc) {
                         edges.push(CallEdge {
                             caller: func_name.clone(),
                             callee: binding.handler.clone(),
@@ -45,6 +212,7 @@ pub fn build_call_edges(
                             call_type: CallType::Async {
                                 mechanism: binding.mechanism.clone(),
                             },
+                            call_kind: CallKind::Async,
                         });
                     }
                 }
@@ -55,17 +223,83 @@ pub fn build_call_edges(
     edges
 }
 
+/// Edges for calls through function pointer variables (`fp()`)
+///
+/// Targets come from points-to analysis and are classified by
+/// [`ResultClassifier::classify_pointer_call`], so a pointer assigned in
+/// if/else branches gets a `Medium` edge to each branch's target.
+fn pointer_variable_edges(parse_result: &ParseResult, source: &str) -> Vec<CallEdge> {
+    let functions = &parse_result.functions;
+    let calls: Vec<_> = functions
+        .values()
+        .flat_map(|f| f.call_sites.iter().map(move |site| (f, site)))
+        .filter(|(_, site)| !functions.contains_key(&site.callee) && !is_macro_call(&site.callee))
+        .collect();
+    if calls.is_empty() {
+        return Vec::new();
+    }
+
+    let mut collector = ConstraintCollector::new();
+    collector.set_functions(functions.keys().cloned());
+    let mut solver = AndersenSolver::new();
+    solver.add_constraints(collector.collect(source));
+    let points_to = solver.solve();
+
+    let classifier = ResultClassifier::new();
+    let mut edges = Vec::new();
+    for (caller, site) in calls {
+        if points_to.get_function_targets(&site.callee).is_empty() {
+            continue;
+        }
+        let expr = format!("{}()", site.callee);
+        let classified = classifier.classify_pointer_call(&caller.name, &expr, &site.callee, &points_to, site.line);
+        let file = caller.location.as_ref().map(|l| l.file.as_str()).unwrap_or_default();
+        for target in classified.targets {
+            let confidence = match target.confidence {
+                classification::Confidence::Certain => Confidence::High,
+                classification::Confidence::Possible => Confidence::Medium,
+                classification::Confidence::Unknown => Confidence::Low,
+            };
+            edges.push(CallEdge {
+                caller: caller.name.clone(),
+                callee: target.name,
+                location: Some(flowsight_core::Location::new(file, site.line, site.column)),
+                call_type: CallType::Indirect { confidence },
+                call_kind: CallKind::Indirect,
+            });
+        }
+    }
+    edges
+}
+
 /// Build execution flow tree for an entry point
+///
+/// Node ids are derived from each node's path from the root (see
+/// [`assign_stable_ids`]), so they survive re-analysis.
 pub fn build_flow_tree(
     entry: &str,
     parse_result: &ParseResult,
     async_bindings: &[AsyncBinding],
     visited: &mut HashSet<String>,
     depth: usize,
+    config: &AnalysisConfig,
 ) -> Option<FlowNode> {
-    const MAX_DEPTH: usize = 20;
+    let mut tree = build_flow_node(entry, parse_result, async_bindings, visited, depth, config)?;
+    if depth == 0 {
+        assign_stable_ids(&mut tree);
+    }
+    Some(tree)
+}
 
-    if depth > MAX_DEPTH {
+fn build_flow_node(
+    entry: &str,
+    parse_result: &ParseResult,
+    async_bindings: &[AsyncBinding],
+    visited: &mut HashSet<String>,
+    depth: usize,
+    config: &AnalysisConfig,
+) -> Option<FlowNode> {
+    if depth > config.max_flow_depth {
         return None;
     }
 
@@ -76,15 +310,9 @@ pub fn build_flow_tree(
             id: format!("{}-ref-{}", entry, depth),
             name: entry.to_string(),
             display_name: format!("↩️ {}() [递归]", entry),
-            location: None,
             node_type: FlowNodeType::Function,
-            children: vec![],
             description: Some("递归调用，点击入口点查看完整树".to_string()),
-            confidence: None,
-            execution_context: None,
-            can_sleep: None,
-            source_file: None,
-            is_kernel_internal: false,
+            ..Default::default()
         });
     }
 
@@ -99,22 +327,311 @@ pub fn build_flow_tree(
                 id: entry.to_string(),
                 name: entry.to_string(),
                 display_name: format!("📦 {}()", entry),
-                location: None,
                 node_type: FlowNodeType::External,
-                children: vec![],
                 description: Some("External function".to_string()),
                 confidence: Some(CallConfidence {
                     level: ConfidenceLevel::Unknown,
                     reason: "External function - definition not found".to_string(),
                 }),
-                execution_context: None,
-                can_sleep: None,
-                source_file: None,
-                is_kernel_internal: false,
+                ..Default::default()
             });
         }
     };
 
+    let (node_type, display_name) = function_node_kind(entry, func, async_bindings);
+
+    // Async handlers triggered by this function
+    let triggered: Vec<&str> = async_bindings
+        .iter()
+        .flat_map(|binding| {
+            binding
+                .trigger_locations
+                .iter()
+                .filter(|trigger_loc| {
+                    func.location
+                        .as_ref()
+                        .is_some_and(|func_loc| location_within(trigger_loc, func_loc))
+                })
+                .map(move |_| binding.handler.as_str())
+        })
+        .collect();
+
+    // Calls made only after a `goto err_*`, or only in switch arms, are
+    // shown under those branches instead
+    let unwind = func.unwind_labels();
+    let on_normal_path = |callee: &str| {
+        (unwind.is_empty() || !unwind_only(callee, func, &unwind, parse_result)) && !switch_only(callee, func)
+    };
+
+    // Build children, summarizing whatever falls outside the configured limits
+    let mut children = Vec::new();
+    let mut omitted = 0;
+    let expand = depth < config.max_flow_depth;
+    for callee in func.calls.iter().filter(|c| on_normal_path(c)) {
+        if !expand || children.len() >= config.max_children_per_node {
+            omitted += 1;
+        } else if let Some(child) =
+            callee_node(entry, callee, parse_result, async_bindings, visited, depth, config)
+        {
+            children.push(child);
+        }
+    }
+
+    // ⭐ 错误回滚路径：每个被 goto 的回滚标签作为一个条件分支
+    for label in unwind.iter().filter(|l| !l.gotos.is_empty()) {
+        if !expand || children.len() >= config.max_children_per_node {
+            omitted += 1;
+            continue;
+        }
+        let calls: Vec<FlowNode> = func
+            .unwind_path(&label.name)
+            .into_iter()
+            .filter_map(|callee| {
+                callee_node(entry, callee, parse_result, async_bindings, visited, depth, config)
+            })
+            .collect();
+        let lines: Vec<String> = label.gotos.iter().map(|l| l.to_string()).collect();
+        children.push(FlowNode {
+            id: format!("{}-goto-{}", entry, label.name),
+            name: format!("goto {}", label.name),
+            display_name: format!("⤵️ goto {}", label.name),
+            location: func
+                .location
+                .as_ref()
+                .map(|loc| flowsight_core::Location::new(&loc.file, label.line, 0)),
+            node_type: FlowNodeType::Function,
+            children: calls,
+            description: Some(format!("错误回滚路径 (goto at line {})", lines.join(", "))),
+            confidence: Some(CallConfidence {
+                level: ConfidenceLevel::Possible,
+                reason: "Taken only when an earlier step fails".to_string(),
+            }),
+            ..Default::default()
+        });
+    }
+
+    // switch 分发：每个 case 作为一个条件分支
+    for switch in &func.switches {
+        if !expand || children.len() >= config.max_children_per_node {
+            omitted += 1;
+            continue;
+        }
+        children.push(switch_node(entry, func, switch, parse_result, async_bindings, visited, depth, config));
+    }
+
+    for handler in triggered {
+        if !expand || children.len() >= config.max_children_per_node {
+            omitted += 1;
+        } else if let Some(async_child) =
+            build_flow_node(handler, parse_result, async_bindings, visited, depth + 1, config)
+        {
+            children.push(async_child);
+        }
+    }
+
+    if omitted > 0 {
+        children.push(truncated_node(entry, omitted));
+    }
+
+    visited.remove(entry);
+
+    // Set confidence based on node type
+    let confidence = match &node_type {
+        FlowNodeType::EntryPoint => Some(CallConfidence {
+            level: ConfidenceLevel::Certain,
+            reason: "Callback entry point from ops table".to_string(),
+        }),
+        FlowNodeType::AsyncCallback { .. } => Some(CallConfidence {
+            level: ConfidenceLevel::Certain,
+            reason: "Async callback with known binding".to_string(),
+        }),
+        FlowNodeType::Function => None, // Direct call - certain by default
+        FlowNodeType::KernelApi => Some(CallConfidence {
+            level: ConfidenceLevel::Certain,
+            reason: "Kernel API call".to_string(),
+        }),
+        FlowNodeType::External => Some(CallConfidence {
+            level: ConfidenceLevel::Unknown,
+            reason: "External function - definition not available".to_string(),
+        }),
+    };
+
+    // Section annotations (__init, __irq_entry, ...) override the default
+    let exec_ctx = func
+        .annotated_context()
+        .unwrap_or(flowsight_core::ExecutionContext::Process);
+
+    Some(FlowNode {
+        id: entry.to_string(),
+        name: entry.to_string(),
+        display_name,
+        location: func.location.clone(),
+        node_type,
+        children,
+        description: func.callback_context.clone(),
+        confidence,
+        can_sleep: Some(exec_ctx.can_sleep()),
+        execution_context: Some(exec_ctx),
+        ..Default::default()
+    })
+}
+
+/// Child node for a call from `entry` at `depth`
+fn callee_node(
+    entry: &str,
+    callee: &str,
+    parse_result: &ParseResult,
+    async_bindings: &[AsyncBinding],
+    visited: &mut HashSet<String>,
+    depth: usize,
+    config: &AnalysisConfig,
+) -> Option<FlowNode> {
+    if parse_result.functions.contains_key(callee) {
+        // Recurse for internal functions
+        return build_flow_node(callee, parse_result, async_bindings, visited, depth + 1, config);
+    }
+
+    // External/kernel API
+    Some(FlowNode {
+        id: format!("{}-{}", entry, callee),
+        name: callee.to_string(),
+        display_name: format!("{}()", callee),
+        node_type: FlowNodeType::KernelApi,
+        confidence: Some(CallConfidence {
+            level: ConfidenceLevel::Certain,
+            reason: "Direct call to kernel API".to_string(),
+        }),
+        ..Default::default()
+    })
+}
+
+/// Branch node for `switch`, with one child per arm
+#[allow(clippy::too_many_arguments)]
+fn switch_node(
+    entry: &str,
+    func: &flowsight_core::FunctionDef,
+    switch: &flowsight_core::SwitchDispatch,
+    parse_result: &ParseResult,
+    async_bindings: &[AsyncBinding],
+    visited: &mut HashSet<String>,
+    depth: usize,
+    config: &AnalysisConfig,
+) -> FlowNode {
+    let at_line = |line: u32| {
+        func.location
+            .as_ref()
+            .map(|loc| flowsight_core::Location::new(&loc.file, line, 0))
+    };
+    let cases = switch
+        .cases
+        .iter()
+        .enumerate()
+        .map(|(i, arm)| {
+            let calls: Vec<FlowNode> = switch
+                .arm_calls(i)
+                .into_iter()
+                .filter_map(|callee| {
+                    callee_node(entry, callee, parse_result, async_bindings, visited, depth, config)
+                })
+                .collect();
+            let (name, reason) = match &arm.value {
+                Some(value) => (
+                    format!("case {}", value),
+                    format!("Taken when {} == {}", switch.discriminant, value),
+                ),
+                None => ("default".to_string(), "Taken when no case matches".to_string()),
+            };
+            FlowNode {
+                id: format!("{}-case-{}", entry, arm.line),
+                display_name: format!("↳ {}", name),
+                name,
+                location: at_line(arm.line),
+                node_type: FlowNodeType::Function,
+                children: calls,
+                description: arm.fallthrough.then(|| "fallthrough from previous case".to_string()),
+                confidence: Some(CallConfidence {
+                    level: ConfidenceLevel::Possible,
+                    reason,
+                }),
+                case: Some(CaseBranch {
+                    discriminant: switch.discriminant.clone(),
+                    value: arm.value.clone(),
+                }),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    FlowNode {
+        id: format!("{}-switch-{}", entry, switch.line),
+        name: format!("switch ({})", switch.discriminant),
+        display_name: format!("🔀 switch ({})", switch.discriminant),
+        location: at_line(switch.line),
+        node_type: FlowNodeType::Function,
+        children: cases,
+        description: Some(format!("{} 个分支", switch.cases.len())),
+        ..Default::default()
+    }
+}
+
+/// Whether every call of `callee` in `func` sits in an arm of one of its switches
+///
+/// Needs the call sites; without them nothing is moved off the normal path.
+fn switch_only(callee: &str, func: &flowsight_core::FunctionDef) -> bool {
+    if func.switches.is_empty() {
+        return false;
+    }
+    let mut sites = func.call_sites.iter().filter(|site| site.callee == callee).peekable();
+    sites.peek().is_some()
+        && sites.all(|site| {
+            func.switches
+                .iter()
+                .flat_map(|switch| &switch.cases)
+                .any(|arm| site.line >= arm.line && site.line <= arm.end_line)
+        })
+}
+
+/// Whether every call of `callee` in `func` sits in an error-unwind block
+///
+/// Needs the call occurrences; without them nothing is moved off the normal path.
+fn unwind_only(
+    callee: &str,
+    func: &flowsight_core::FunctionDef,
+    unwind: &[&flowsight_core::GotoLabel],
+    parse_result: &ParseResult,
+) -> bool {
+    let Some(func_loc) = func.location.as_ref() else {
+        return false;
+    };
+    // Line range of each unwind block: up to the next label or the end of the function
+    let blocks: Vec<(u32, u32)> = unwind
+        .iter()
+        .map(|label| {
+            let end = func
+                .labels
+                .iter()
+                .find(|l| l.line > label.line)
+                .map_or(func_loc.end_line, |next| next.line - 1);
+            (label.line, end)
+        })
+        .collect();
+
+    let mut calls = parse_result
+        .occurrences
+        .iter()
+        .filter(|o| o.kind == OccurrenceKind::Call && o.name == callee && o.file == func_loc.file)
+        .filter(|o| o.line >= func_loc.line && o.line <= func_loc.end_line)
+        .peekable();
+    calls.peek().is_some()
+        && calls.all(|o| blocks.iter().any(|&(start, end)| o.line >= start && o.line <= end))
+}
+
+/// Node type and display name for a user function
+fn function_node_kind(
+    entry: &str,
+    func: &flowsight_core::FunctionDef,
+    async_bindings: &[AsyncBinding],
+) -> (FlowNodeType, String) {
     let node_type = if func.is_callback {
         if let Some(ctx) = &func.callback_context {
             if ctx.starts_with("async_") {
@@ -141,110 +658,281 @@ pub fn build_flow_tree(
             format!("🔌 [{}] {}()", ctx, entry)
         }
         FlowNodeType::AsyncCallback { mechanism } => {
-            let icon = match mechanism {
-                AsyncMechanism::WorkQueue { .. } => "⚙️",
-                AsyncMechanism::Timer { .. } => "⏲️",
-                AsyncMechanism::Interrupt { .. } => "⚡",
-                AsyncMechanism::Tasklet => "🔄",
-                AsyncMechanism::KThread => "🧵",
-                _ => "📍",
-            };
-            format!("{} {}()", icon, entry)
+            format!("{} {}()", mechanism_icon(mechanism), entry)
         }
         _ => format!("{}()", entry),
     };
 
-    // Build children
-    let mut children = Vec::new();
-    for callee in &func.calls {
-        if parse_result.functions.contains_key(callee) {
-            // Recurse for internal functions
-            if let Some(child) =
-                build_flow_tree(callee, parse_result, async_bindings, visited, depth + 1)
-            {
-                children.push(child);
-            }
+    (node_type, display_name)
+}
+
+fn mechanism_icon(mechanism: &AsyncMechanism) -> &'static str {
+    match mechanism {
+        AsyncMechanism::WorkQueue { .. } => "⚙️",
+        AsyncMechanism::Timer { .. } => "⏲️",
+        AsyncMechanism::Interrupt { .. } => "⚡",
+        AsyncMechanism::Tasklet => "🔄",
+        AsyncMechanism::KThread => "🧵",
+        AsyncMechanism::Notifier => "🔔",
+        _ => "📍",
+    }
+}
+
+/// Give every node an id derived from its path of names from the root
+///
+/// Same-named siblings are told apart by their order among each other, so
+/// an unrelated source change leaves ids (and UI state keyed on them) intact.
+pub fn assign_stable_ids(tree: &mut FlowNode) {
+    assign_path_ids(tree, fnv1a(FNV_OFFSET, tree.name.as_bytes()));
+}
+
+fn assign_path_ids(node: &mut FlowNode, hash: u64) {
+    node.id = format!("{}-{:016x}", node.name, hash);
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for child in &mut node.children {
+        let nth = seen.entry(child.name.clone()).or_default();
+        let segment = format!("/{}#{}", child.name, nth);
+        *nth += 1;
+        assign_path_ids(child, fnv1a(hash, segment.as_bytes()));
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// FNV-1a, chosen because its output is fixed across Rust releases
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Placeholder for children cut off by [`AnalysisConfig`] limits
+fn truncated_node(parent: &str, omitted: usize) -> FlowNode {
+    FlowNode {
+        id: format!("{}-more", parent),
+        name: "...".to_string(),
+        display_name: format!("... {} more", omitted),
+        node_type: FlowNodeType::External,
+        description: Some("Truncated by flow tree depth/width limits".to_string()),
+        ..Default::default()
+    }
+}
+
+/// Build one tree for a whole module, rooted at a synthetic node
+///
+/// The roots are `entry_points` (module_init first, then callbacks) followed
+/// by any async handler not already among them. A function expanded once is
+/// not expanded again: later occurrences are left as leaves pointing back.
+pub fn build_forest(
+    entry_points: &[String],
+    parse_result: &ParseResult,
+    async_bindings: &[AsyncBinding],
+    config: &AnalysisConfig,
+) -> FlowNode {
+    let mut roots: Vec<&str> = entry_points.iter().map(String::as_str).collect();
+    let mut handlers: Vec<(&str, u32)> = async_bindings
+        .iter()
+        .map(|b| b.handler.as_str())
+        .filter(|h| !roots.contains(h) && parse_result.functions.contains_key(*h))
+        .map(|h| (h, parse_result.functions[h].location.as_ref().map_or(u32::MAX, |l| l.line)))
+        .collect();
+    handlers.sort_by_key(|&(name, line)| (line, name));
+    handlers.dedup();
+    roots.extend(handlers.into_iter().map(|(name, _)| name));
+
+    let mut expanded = HashSet::new();
+    let children = roots
+        .into_iter()
+        .filter_map(|entry| {
+            let mut tree = build_flow_node(entry, parse_result, async_bindings, &mut HashSet::new(), 0, config)?;
+            collapse_expanded(&mut tree, parse_result, &mut expanded);
+            Some(tree)
+        })
+        .collect();
+
+    let mut forest = FlowNode {
+        id: "module".to_string(),
+        name: "module".to_string(),
+        display_name: "🌲 module".to_string(),
+        node_type: FlowNodeType::Function,
+        children,
+        description: Some("All entry points and async handlers of the module".to_string()),
+        ..Default::default()
+    };
+    propagate_execution_context(&mut forest, async_bindings);
+    assign_stable_ids(&mut forest);
+    forest
+}
+
+/// Drop the subtree of every function already in `expanded`, recording the rest
+fn collapse_expanded(node: &mut FlowNode, parse_result: &ParseResult, expanded: &mut HashSet<String>) {
+    let is_function = parse_result.functions.contains_key(&node.name);
+    if is_function && !node.children.is_empty() && !expanded.insert(node.name.clone()) {
+        node.children.clear();
+        node.display_name = format!("↪️ {}() [见上文]", node.name);
+        node.description = Some("Already expanded earlier in the module tree".to_string());
+        return;
+    }
+    for child in &mut node.children {
+        collapse_expanded(child, parse_result, expanded);
+    }
+}
+
+/// Build a backward tree of every path that can reach `target`
+///
+/// A node's children are its callers and, when it is an async handler, the
+/// functions that trigger it (e.g. `schedule_work`) and register it (e.g.
+/// `INIT_WORK`, `request_irq`). Ops callbacks and handlers without a known
+/// caller end up as leaves.
+pub fn build_reverse_flow_tree(
+    target: &str,
+    parse_result: &ParseResult,
+    async_bindings: &[AsyncBinding],
+    visited: &mut HashSet<String>,
+    depth: usize,
+    config: &AnalysisConfig,
+) -> Option<FlowNode> {
+    let mut tree = build_reverse_node(target, parse_result, async_bindings, visited, depth, config)?;
+    if depth == 0 {
+        assign_stable_ids(&mut tree);
+    }
+    Some(tree)
+}
+
+fn build_reverse_node(
+    target: &str,
+    parse_result: &ParseResult,
+    async_bindings: &[AsyncBinding],
+    visited: &mut HashSet<String>,
+    depth: usize,
+    config: &AnalysisConfig,
+) -> Option<FlowNode> {
+    if depth > config.max_flow_depth {
+        return None;
+    }
+    let func = parse_result.functions.get(target)?;
+
+    if visited.contains(target) {
+        return Some(FlowNode {
+            id: format!("{}-ref-{}", target, depth),
+            name: target.to_string(),
+            display_name: format!("↩️ {}() [递归]", target),
+            node_type: FlowNodeType::Function,
+            description: Some("递归调用".to_string()),
+            ..Default::default()
+        });
+    }
+    visited.insert(target.to_string());
+
+    let (node_type, display_name) = function_node_kind(target, func, async_bindings);
+
+    // Functions leading here; async links carry a description of the link
+    let mut callers: Vec<&str> = parse_result
+        .functions
+        .values()
+        .filter(|f| f.calls.iter().any(|c| c == target))
+        .map(|f| f.name.as_str())
+        .collect();
+    callers.sort_unstable();
+    let mut reachers: Vec<(&str, Vec<String>)> = callers.into_iter().map(|c| (c, Vec::new())).collect();
+    for binding in async_bindings.iter().filter(|b| b.handler == target) {
+        let via = if binding.variable.is_empty() {
+            String::new()
         } else {
-            // External/kernel API
-            children.push(FlowNode {
-                id: format!("{}-{}", entry, callee),
-                name: callee.clone(),
-                display_name: format!("{}()", callee),
-                location: None,
-                node_type: FlowNodeType::KernelApi,
-                children: vec![],
-                description: None,
-                confidence: Some(CallConfidence {
-                    level: ConfidenceLevel::Certain,
-                    reason: "Direct call to kernel API".to_string(),
-                }),
-                execution_context: None,
-                can_sleep: None,
-                source_file: None,
-                is_kernel_internal: false,
-            });
+            format!(" via {}", binding.variable)
+        };
+        let links = binding
+            .trigger_locations
+            .iter()
+            .map(|loc| (loc, format!("triggers {}(){}", target, via)))
+            .chain(
+                binding
+                    .bind_location
+                    .iter()
+                    .map(|loc| (loc, format!("registers {}(){}", target, via))),
+            );
+        for (loc, link) in links {
+            let Some(reacher) = enclosing_function(parse_result, loc) else {
+                continue;
+            };
+            match reachers.iter_mut().find(|(name, _)| *name == reacher) {
+                Some((_, existing)) if !existing.contains(&link) => existing.push(link),
+                Some(_) => {}
+                None => reachers.push((reacher, vec![link])),
+            }
         }
     }
 
-    // Add async handlers triggered by this function
-    for binding in async_bindings {
-        for trigger_loc in &binding.trigger_locations {
-            if let Some(func_loc) = &func.location {
-                if trigger_loc.line >= func_loc.line && trigger_loc.line <= func_loc.end_line {
-                    // This function triggers an async handler
-                    if let Some(async_child) = build_flow_tree(
-                        &binding.handler,
-                        parse_result,
-                        async_bindings,
-                        visited,
-                        depth + 1,
-                    ) {
-                        children.push(async_child);
-                    }
-                }
-            }
+    let mut children = Vec::new();
+    let mut omitted = 0;
+    let expand = depth < config.max_flow_depth;
+    for (reacher, links) in reachers {
+        if !expand || children.len() >= config.max_children_per_node {
+            omitted += 1;
+            continue;
+        }
+        let Some(mut child) =
+            build_reverse_node(reacher, parse_result, async_bindings, visited, depth + 1, config)
+        else {
+            continue;
+        };
+        if !links.is_empty() {
+            child.display_name = format!("{} [{}]", child.display_name, links.join("; "));
+            child.confidence = Some(CallConfidence {
+                level: ConfidenceLevel::Certain,
+                reason: "Async binding".to_string(),
+            });
         }
+        children.push(child);
+    }
+    if omitted > 0 {
+        children.push(truncated_node(target, omitted));
     }
 
-    visited.remove(entry);
+    visited.remove(target);
 
-    // Set confidence based on node type
-    let confidence = match &node_type {
-        FlowNodeType::EntryPoint => Some(CallConfidence {
-            level: ConfidenceLevel::Certain,
-            reason: "Callback entry point from ops table".to_string(),
-        }),
-        FlowNodeType::AsyncCallback { .. } => Some(CallConfidence {
-            level: ConfidenceLevel::Certain,
-            reason: "Async callback with known binding".to_string(),
-        }),
-        FlowNodeType::Function => None, // Direct call - certain by default
-        FlowNodeType::KernelApi => Some(CallConfidence {
-            level: ConfidenceLevel::Certain,
-            reason: "Kernel API call".to_string(),
-        }),
-        FlowNodeType::External => Some(CallConfidence {
-            level: ConfidenceLevel::Unknown,
-            reason: "External function - definition not available".to_string(),
-        }),
-    };
+    let exec_ctx = func
+        .annotated_context()
+        .unwrap_or(flowsight_core::ExecutionContext::Process);
 
     Some(FlowNode {
-        id: entry.to_string(),
-        name: entry.to_string(),
+        id: target.to_string(),
+        name: target.to_string(),
         display_name,
         location: func.location.clone(),
         node_type,
         children,
         description: func.callback_context.clone(),
-        confidence,
-        execution_context: Some(flowsight_core::ExecutionContext::Process),
-        can_sleep: Some(true),
-        source_file: None,
-        is_kernel_internal: false,
+        can_sleep: Some(exec_ctx.can_sleep()),
+        execution_context: Some(exec_ctx),
+        ..Default::default()
     })
 }
 
+/// Name of the function whose body contains `loc`
+fn enclosing_function<'a>(parse_result: &'a ParseResult, loc: &flowsight_core::Location) -> Option<&'a str> {
+    parse_result
+        .functions
+        .values()
+        .find(|f| {
+            f.location
+                .as_ref()
+                .is_some_and(|func_loc| location_within(loc, func_loc))
+        })
+        .map(|f| f.name.as_str())
+}
+
+/// Whether `loc` falls inside the function spanning `func_loc`
+///
+/// An empty file name (single-file analysis) matches any file.
+fn location_within(loc: &flowsight_core::Location, func_loc: &flowsight_core::Location) -> bool {
+    (loc.file.is_empty() || func_loc.file.is_empty() || loc.file == func_loc.file)
+        && loc.line >= func_loc.line
+        && loc.line <= func_loc.end_line
+}
+
 /// ⭐ 构建带完整内核调用链的执行流树
 /// 
 /// 当检测到入口点函数时，自动在前面注入内核调用链，
@@ -254,33 +942,42 @@ pub fn build_full_flow_tree(
     parse_result: &ParseResult,
     async_bindings: &[AsyncBinding],
     kb: &KnowledgeBase,
+    config: &AnalysisConfig,
 ) -> Option<FlowNode> {
     let mut visited = HashSet::new();
     
     // 首先构建用户代码的流树
-    let user_tree = build_flow_tree(entry, parse_result, async_bindings, &mut visited, 0)?;
+    let user_tree = build_flow_tree(entry, parse_result, async_bindings, &mut visited, 0, config)?;
     
-    // 检查是否有关联的内核调用链
+    // 检查是否有关联的内核调用链，将用户函数作为最后一个节点注入
+    match kernel_call_chain(entry, parse_result, async_bindings, kb) {
+        Some(call_chain) => Some(inject_kernel_chain(&call_chain, user_tree)),
+        None => Some(user_tree),
+    }
+}
+
+/// Kernel call chain leading to `entry`, from its callback context or async binding
+pub fn kernel_call_chain(
+    entry: &str,
+    parse_result: &ParseResult,
+    async_bindings: &[AsyncBinding],
+    kb: &KnowledgeBase,
+) -> Option<CallChain> {
     let func = parse_result.functions.get(entry)?;
-    
-    if let Some(ctx) = &func.callback_context {
-        // 尝试查找对应的内核调用链
-        if let Some(ref call_chain) = find_call_chain_for_context(ctx, kb) {
-            // 注入内核调用链，将用户函数作为最后一个节点
-            return Some(inject_kernel_chain(call_chain, user_tree));
-        }
+
+    if let Some(chain) = func
+        .callback_context
+        .as_deref()
+        .and_then(|ctx| find_call_chain_for_context(ctx, kb))
+    {
+        return Some(chain);
     }
-    
+
     // 检查是否是异步 handler
-    for binding in async_bindings {
-        if binding.handler == entry {
-            if let Some(ref call_chain) = get_async_handler_chain(&binding.mechanism, kb) {
-                return Some(inject_kernel_chain(call_chain, user_tree));
-            }
-        }
-    }
-    
-    Some(user_tree)
+    async_bindings
+        .iter()
+        .filter(|binding| binding.handler == entry)
+        .find_map(|binding| get_async_handler_chain(binding, kb))
 }
 
 /// 根据 callback context 查找对应的内核调用链
@@ -309,15 +1006,33 @@ fn find_call_chain_for_context(ctx: &str, kb: &KnowledgeBase) -> Option<CallChai
 }
 
 /// 获取异步机制的 handler 调用链
-fn get_async_handler_chain(mechanism: &AsyncMechanism, kb: &KnowledgeBase) -> Option<CallChain> {
+fn get_async_handler_chain(binding: &AsyncBinding, kb: &KnowledgeBase) -> Option<CallChain> {
+    let pattern = async_pattern_name(&binding.mechanism, binding.context.can_sleep())?;
+    kb.get_async_handler_chain(pattern).cloned()
+}
+
+/// 异步机制在知识库中对应的 `AsyncPattern` 名称
+pub(crate) fn async_pattern_name(mechanism: &AsyncMechanism, can_sleep: bool) -> Option<&'static str> {
     match mechanism {
-        AsyncMechanism::WorkQueue { .. } => {
-            kb.get_async_handler_chain("work_struct").cloned()
-        }
-        AsyncMechanism::Timer { .. } => {
-            kb.get_async_handler_chain("timer_list").cloned()
-        }
-        _ => None
+        AsyncMechanism::WorkQueue { .. } => Some("work_struct"),
+        AsyncMechanism::Timer { .. } => Some("timer_list"),
+        AsyncMechanism::Notifier if can_sleep => Some("notifier_block"),
+        AsyncMechanism::Notifier => Some("atomic_notifier"),
+        AsyncMechanism::Interrupt { threaded: false } => Some("irq"),
+        AsyncMechanism::Interrupt { threaded: true } => Some("threaded_irq"),
+        AsyncMechanism::Custom(name) if name == DMA_CALLBACK => Some(DMA_CALLBACK),
+        _ => None,
+    }
+}
+
+/// 将知识库的执行上下文转换为 core 类型 (用户空间视为进程上下文)
+pub(crate) fn core_context(ctx: &flowsight_knowledge::ExecutionContext) -> flowsight_core::ExecutionContext {
+    match ctx {
+        flowsight_knowledge::ExecutionContext::Process => flowsight_core::ExecutionContext::Process,
+        flowsight_knowledge::ExecutionContext::SoftIrq => flowsight_core::ExecutionContext::SoftIrq,
+        flowsight_knowledge::ExecutionContext::HardIrq => flowsight_core::ExecutionContext::HardIrq,
+        flowsight_knowledge::ExecutionContext::User => flowsight_core::ExecutionContext::Process,
+        flowsight_knowledge::ExecutionContext::Unknown => flowsight_core::ExecutionContext::Unknown,
     }
 }
 
@@ -332,7 +1047,6 @@ fn inject_kernel_chain(call_chain: &CallChain, user_tree: FlowNode) -> FlowNode
         id: "trigger-source".to_string(),
         name: call_chain.trigger_source.clone(),
         display_name: format!("🎯 {}", call_chain.trigger_source),
-        location: None,
         node_type: FlowNodeType::External,
         children: vec![build_kernel_chain_tree(&call_chain.nodes, user_tree, 0)],
         description: Some(call_chain.name.clone()),
@@ -340,10 +1054,8 @@ fn inject_kernel_chain(call_chain: &CallChain, user_tree: FlowNode) -> FlowNode
             level: ConfidenceLevel::Certain,
             reason: "Kernel call chain trigger".to_string(),
         }),
-        execution_context: None,
-        can_sleep: None,
-        source_file: None,
         is_kernel_internal: true,
+        ..Default::default()
     };
 
     trigger_node
@@ -359,13 +1071,7 @@ fn build_kernel_chain_tree(nodes: &[flowsight_knowledge::CallChainNode], user_tr
     let node = &nodes[idx];
 
     // 转换执行上下文类型
-    let exec_ctx = match node.context {
-        flowsight_knowledge::ExecutionContext::Process => flowsight_core::ExecutionContext::Process,
-        flowsight_knowledge::ExecutionContext::SoftIrq => flowsight_core::ExecutionContext::SoftIrq,
-        flowsight_knowledge::ExecutionContext::HardIrq => flowsight_core::ExecutionContext::HardIrq,
-        flowsight_knowledge::ExecutionContext::User => flowsight_core::ExecutionContext::Process,
-        flowsight_knowledge::ExecutionContext::Unknown => flowsight_core::ExecutionContext::Unknown,
-    };
+    let exec_ctx = core_context(&node.context);
     let can_sleep = exec_ctx.can_sleep();
 
     // 如果是用户入口点，替换为实际的用户树
@@ -375,7 +1081,6 @@ fn build_kernel_chain_tree(nodes: &[flowsight_knowledge::CallChainNode], user_tr
             id: format!("kernel-{}", idx),
             name: node.function.clone(),
             display_name: format!("🔗 {} → {}", node.function, user_tree.name),
-            location: None,
             node_type: FlowNodeType::KernelApi,
             children: vec![user_tree],
             description: node.description.clone(),
@@ -387,6 +1092,7 @@ fn build_kernel_chain_tree(nodes: &[flowsight_knowledge::CallChainNode], user_tr
             can_sleep: Some(can_sleep),
             source_file: node.file.clone(),
             is_kernel_internal: true,
+            ..Default::default()
         };
     }
 
@@ -405,7 +1111,6 @@ fn build_kernel_chain_tree(nodes: &[flowsight_knowledge::CallChainNode], user_tr
         id: format!("kernel-{}", idx),
         name: node.function.clone(),
         display_name: format!("{} {}()", icon, node.function),
-        location: None,
         node_type: FlowNodeType::KernelApi,
         children: vec![child],
         description: Some(format!(
@@ -421,5 +1126,73 @@ fn build_kernel_chain_tree(nodes: &[flowsight_knowledge::CallChainNode], user_tr
         can_sleep: Some(can_sleep),
         source_file: node.file.clone(),
         is_kernel_internal: true,
+        ..Default::default()
     }
 }
+
+/// Propagate execution context from the root of a flow tree down to its leaves
+///
+/// A callee runs in its caller's context; the context only changes at an
+/// async callback, which takes the context of its binding (a work handler
+/// queued from an interrupt still runs in process context). Kernel chain
+/// nodes keep the context the knowledge base gives them.
+pub fn propagate_execution_context(tree: &mut FlowNode, async_bindings: &[AsyncBinding]) {
+    propagate_context(tree, None, async_bindings);
+}
+
+fn propagate_context(
+    node: &mut FlowNode,
+    inherited: Option<&flowsight_core::ExecutionContext>,
+    async_bindings: &[AsyncBinding],
+) {
+    let ctx = match &node.node_type {
+        _ if node.is_kernel_internal => node.execution_context.clone(),
+        FlowNodeType::AsyncCallback { .. } => async_bindings
+            .iter()
+            .find(|b| b.handler == node.name)
+            .map(|b| b.context.clone())
+            .or_else(|| node.execution_context.clone()),
+        _ => inherited.cloned().or_else(|| node.execution_context.clone()),
+    };
+    node.can_sleep = ctx.as_ref().map(|c| c.can_sleep());
+    node.execution_context = ctx;
+
+    for child in &mut node.children {
+        propagate_context(child, node.execution_context.as_ref(), async_bindings);
+    }
+}
+
+/// Annotate every node of a flow tree with its execution count, if known
+pub fn apply_weights(tree: &mut FlowNode, weights: &HashMap<String, u64>) {
+    tree.weight = weights.get(&tree.name).copied();
+    for child in &mut tree.children {
+        apply_weights(child, weights);
+    }
+}
+
+/// Parse `function,count` lines (e.g. from a perf/ftrace report)
+///
+/// Blank lines, `#` comments and lines whose count isn't a number (such as
+/// a header) are skipped; repeated functions are summed.
+pub fn parse_weights_csv(content: &str) -> HashMap<String, u64> {
+    let mut weights = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((name, count)) = line.split_once(',') else {
+            continue;
+        };
+        if let Ok(count) = count.trim().parse::<u64>() {
+            *weights.entry(name.trim().to_string()).or_insert(0) += count;
+        }
+    }
+    weights
+}
+
+/// Load a `function,count` CSV file
+pub fn load_weights_csv(path: &Path) -> Result<HashMap<String, u64>> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(parse_weights_csv(&content))
+}
@@ -9,7 +9,10 @@
 //! Better to say "Unknown" than give wrong information.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+
+use crate::pointer::PointsToResult;
+use flowsight_core::{ClassifiedCall, ConfidenceLevel, FlowNode, FunctionDef};
 
 /// Confidence level of an analysis result
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -49,6 +52,39 @@ impl Confidence {
             Confidence::Unknown => "dotted",
         }
     }
+
+    /// Whether this is `min` or more certain than it
+    pub fn is_at_least(&self, min: Confidence) -> bool {
+        self.rank() >= min.rank()
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Confidence::Unknown => 0,
+            Confidence::Possible => 1,
+            Confidence::Certain => 2,
+        }
+    }
+}
+
+impl From<ConfidenceLevel> for Confidence {
+    fn from(level: ConfidenceLevel) -> Self {
+        match level {
+            ConfidenceLevel::Certain => Confidence::Certain,
+            ConfidenceLevel::Possible => Confidence::Possible,
+            ConfidenceLevel::Unknown => Confidence::Unknown,
+        }
+    }
+}
+
+impl From<Confidence> for ConfidenceLevel {
+    fn from(confidence: Confidence) -> Self {
+        match confidence {
+            Confidence::Certain => ConfidenceLevel::Certain,
+            Confidence::Possible => ConfidenceLevel::Possible,
+            Confidence::Unknown => ConfidenceLevel::Unknown,
+        }
+    }
 }
 
 impl std::fmt::Display for Confidence {
@@ -118,6 +154,21 @@ impl ClassifiedEdge {
         }
     }
 
+    /// Index rows for this edge, one per target
+    pub fn to_calls(&self, file: &str) -> Vec<ClassifiedCall> {
+        self.targets
+            .iter()
+            .map(|target| ClassifiedCall {
+                caller: self.caller.clone(),
+                call_site: self.call_site.clone(),
+                target: target.name.clone(),
+                confidence: target.confidence.into(),
+                reason: target.reason.clone(),
+                file: file.to_string(),
+            })
+            .collect()
+    }
+
     /// Create an unknown edge
     pub fn unknown(caller: &str, call_site: &str, reason: &str) -> Self {
         Self {
@@ -316,6 +367,43 @@ impl ResultClassifier {
         ClassifiedEdge::possible(caller, &format!("L{}: {}", line, expr), target_list)
     }
 
+    /// Classify a call through `ptr` using the pointer analysis result
+    ///
+    /// Pointers assigned inside if/else branches are `Possible`, listing every
+    /// branch target, even when only one target was found.
+    pub fn classify_pointer_call(
+        &self,
+        caller: &str,
+        expr: &str,
+        ptr: &str,
+        points_to: &PointsToResult,
+        line: u32,
+    ) -> ClassifiedEdge {
+        let mut targets = points_to.get_function_targets(ptr);
+        targets.sort();
+
+        if targets.is_empty() || !points_to.is_conditional(ptr) {
+            return self.classify_funcptr_call(caller, expr, &targets, line);
+        }
+
+        let reason = ClassificationReason::ConditionalAssignment;
+        let target_list: Vec<_> = targets
+            .iter()
+            .map(|t| ClassifiedTarget {
+                name: t.clone(),
+                confidence: reason.default_confidence(),
+                reason: reason.description(),
+            })
+            .collect();
+
+        ClassifiedEdge {
+            caller: caller.to_string(),
+            call_site: format!("L{}: {}", line, expr),
+            targets: target_list,
+            overall_confidence: reason.default_confidence(),
+        }
+    }
+
     /// Classify an ops table callback
     pub fn classify_ops_callback(
         &self,
@@ -378,13 +466,124 @@ impl ResultClassifier {
         }
 
         let total = edges.len();
+        // Nothing to doubt in a flow without edges
+        let weighted_score = if total == 0 {
+            1.0
+        } else {
+            (certain_count as f64 + possible_count as f64 * 0.5) / total as f64
+        };
         ClassificationSummary {
             total,
             certain_count,
             possible_count,
             unknown_count,
-            certain_percentage: if total > 0 { certain_count * 100 / total } else { 0 },
+            certain_percentage: (certain_count * 100).checked_div(total).unwrap_or(0),
+            weighted_score,
+        }
+    }
+
+    /// Classify every parent -> child edge of a flow tree and summarize them
+    ///
+    /// Edges without a recorded confidence are direct calls.
+    pub fn summarize_tree(&self, tree: &FlowNode) -> ClassificationSummary {
+        let mut edges = Vec::new();
+        collect_tree_edges(tree, &mut edges);
+        self.summarize(&edges)
+    }
+
+    /// Classify every call in `trees` once per call site, e.g. for the index
+    ///
+    /// Call sites are the caller's call lines (`L42`). Unlike
+    /// [`summarize_tree`](Self::summarize_tree), unknown edges keep the
+    /// callee's name as their target, and only calls made by `functions` count.
+    pub fn classify_flow_edges(
+        &self,
+        trees: &[FlowNode],
+        functions: &HashMap<String, FunctionDef>,
+    ) -> Vec<ClassifiedEdge> {
+        let mut seen = HashSet::new();
+        let mut edges = Vec::new();
+        for tree in trees {
+            collect_call_sites(tree, functions, &mut seen, &mut edges);
+        }
+        edges
+    }
+}
+
+fn collect_tree_edges(node: &FlowNode, edges: &mut Vec<ClassifiedEdge>) {
+    for child in &node.children {
+        let (level, reason) = match &child.confidence {
+            Some(c) => (c.level, c.reason.as_str()),
+            None => (ConfidenceLevel::Certain, "Direct function call"),
+        };
+        let call_site = child.location.as_ref().map(|l| format!("L{}", l.line)).unwrap_or_default();
+        let edge = match level {
+            ConfidenceLevel::Certain => ClassifiedEdge::certain(&node.name, &call_site, &child.name, reason),
+            ConfidenceLevel::Possible => ClassifiedEdge::possible(&node.name, &call_site, vec![(&child.name, reason)]),
+            ConfidenceLevel::Unknown => ClassifiedEdge::unknown(&node.name, &call_site, reason),
+        };
+        edges.push(edge);
+        collect_tree_edges(child, edges);
+    }
+}
+
+fn collect_call_sites(
+    node: &FlowNode,
+    functions: &HashMap<String, FunctionDef>,
+    seen: &mut HashSet<(String, String, String)>,
+    edges: &mut Vec<ClassifiedEdge>,
+) {
+    // Kernel chain nodes are not calls in the analyzed code
+    let caller = functions.get(&node.name).filter(|_| !node.is_kernel_internal);
+    classify_children(caller, &node.children, functions, seen, edges);
+}
+
+fn classify_children(
+    caller: Option<&FunctionDef>,
+    children: &[FlowNode],
+    functions: &HashMap<String, FunctionDef>,
+    seen: &mut HashSet<(String, String, String)>,
+    edges: &mut Vec<ClassifiedEdge>,
+) {
+    for child in children {
+        // A `goto` branch groups calls made by the caller itself
+        if child.name.starts_with("goto ") {
+            classify_children(caller, &child.children, functions, seen, edges);
+            continue;
+        }
+        // "..." stands for children cut by the tree limits
+        if let Some(caller) = caller.filter(|_| child.name != "...") {
+            let (level, reason) = match &child.confidence {
+                Some(c) => (c.level, c.reason.as_str()),
+                None => (ConfidenceLevel::Certain, "Direct function call"),
+            };
+            let mut call_sites: Vec<String> = caller
+                .call_sites
+                .iter()
+                .filter(|site| site.callee == child.name)
+                .map(|site| format!("L{}", site.line))
+                .collect();
+            if call_sites.is_empty() {
+                call_sites.push(child.location.as_ref().map(|l| format!("L{}", l.line)).unwrap_or_default());
+            }
+
+            let confidence = Confidence::from(level);
+            for call_site in call_sites {
+                if seen.insert((caller.name.clone(), call_site.clone(), child.name.clone())) {
+                    edges.push(ClassifiedEdge {
+                        caller: caller.name.clone(),
+                        call_site,
+                        targets: vec![ClassifiedTarget {
+                            name: child.name.clone(),
+                            confidence,
+                            reason: reason.to_string(),
+                        }],
+                        overall_confidence: confidence,
+                    });
+                }
+            }
         }
+        collect_call_sites(child, functions, seen, edges);
     }
 }
 
@@ -407,18 +606,22 @@ pub struct ClassificationSummary {
     pub unknown_count: usize,
     /// Percentage of certain results
     pub certain_percentage: usize,
+    /// Trustworthiness from 0.0 to 1.0: certain edges count 1, possible 0.5, unknown 0
+    #[serde(default)]
+    pub weighted_score: f64,
 }
 
 impl std::fmt::Display for ClassificationSummary {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Analysis: {} edges ({} certain [{}%], {} possible, {} unknown)",
+            "Analysis: {} edges ({} certain [{}%], {} possible, {} unknown), score {:.2}",
             self.total,
             self.certain_count,
             self.certain_percentage,
             self.possible_count,
-            self.unknown_count
+            self.unknown_count,
+            self.weighted_score
         )
     }
 }
@@ -476,6 +679,47 @@ mod tests {
         assert_eq!(edge.targets.len(), 2);
     }
 
+    #[test]
+    fn test_classify_conditional_pointer_call() {
+        use crate::constraint::ConstraintCollector;
+        use crate::pointer::AndersenSolver;
+
+        let source = r#"
+void handler_a(void) {}
+void handler_b(void) {}
+void handler_c(void) {}
+
+void run(int x) {
+    if (x) fp = handler_a; else fp = handler_b;
+    fp();
+    gp = handler_c;
+    gp();
+}
+"#;
+        let mut collector = ConstraintCollector::new();
+        collector.set_functions(vec![
+            "handler_a".to_string(),
+            "handler_b".to_string(),
+            "handler_c".to_string(),
+        ]);
+        let mut solver = AndersenSolver::new();
+        solver.add_constraints(collector.collect(source));
+        let points_to = solver.solve();
+
+        let classifier = ResultClassifier::new();
+        let edge = classifier.classify_pointer_call("run", "fp()", "fp", &points_to, 8);
+        assert_eq!(edge.overall_confidence, Confidence::Possible);
+        let names: Vec<_> = edge.targets.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["handler_a", "handler_b"]);
+        assert_eq!(
+            edge.targets[0].reason,
+            ClassificationReason::ConditionalAssignment.description()
+        );
+
+        let edge = classifier.classify_pointer_call("run", "gp()", "gp", &points_to, 10);
+        assert_eq!(edge.overall_confidence, Confidence::Certain);
+    }
+
     #[test]
     fn test_classify_ops_callback() {
         let classifier = ResultClassifier::new();
@@ -523,5 +767,89 @@ mod tests {
         assert_eq!(summary.possible_count, 1);
         assert_eq!(summary.unknown_count, 1);
         assert_eq!(summary.certain_percentage, 50);
+        assert_eq!(summary.weighted_score, 0.625);
+        assert_eq!(classifier.summarize(&[]).weighted_score, 1.0);
+    }
+
+    #[test]
+    fn test_summarize_tree() {
+        use flowsight_core::{CallConfidence, FlowNodeType};
+
+        let node = |name: &str, level: Option<ConfidenceLevel>, children: Vec<FlowNode>| FlowNode {
+            id: name.into(),
+            name: name.into(),
+            display_name: name.into(),
+            node_type: FlowNodeType::Function,
+            children,
+            confidence: level.map(|level| CallConfidence {
+                level,
+                reason: "test".into(),
+            }),
+            ..Default::default()
+        };
+        let tree = node(
+            "probe",
+            None,
+            vec![
+                node("helper", None, vec![node("fp_target", Some(ConfidenceLevel::Possible), vec![])]),
+                node("<unknown>", Some(ConfidenceLevel::Unknown), vec![]),
+            ],
+        );
+
+        let summary = ResultClassifier::new().summarize_tree(&tree);
+        assert_eq!(summary.total, 3);
+        assert_eq!(
+            (summary.certain_count, summary.possible_count, summary.unknown_count),
+            (1, 1, 1)
+        );
+        assert_eq!(summary.weighted_score, 0.5);
+    }
+
+    #[test]
+    fn test_classify_flow_edges() {
+        use flowsight_parser::treesitter::TreeSitterParser;
+
+        let source = r#"
+static int my_open(struct inode *inode, struct file *file) {
+    int ret = helper(inode);
+    if (ret)
+        goto err;
+    helper(inode);
+    return 0;
+err:
+    cleanup(inode);
+    return ret;
+}
+
+static int helper(struct inode *inode) { return 0; }
+static void cleanup(struct inode *inode) { }
+
+static struct file_operations my_fops = {
+    .open = my_open,
+};
+"#;
+        let mut parser = TreeSitterParser::new();
+        let mut parse_result = parser.parse_source(source, "drv.c").unwrap();
+        let analysis = crate::Analyzer::new().analyze(source, &mut parse_result).unwrap();
+
+        let edges = ResultClassifier::new().classify_flow_edges(&analysis.flow_trees, &parse_result.functions);
+        let mut found: Vec<(&str, &str, &str, Confidence)> = edges
+            .iter()
+            .map(|e| (e.caller.as_str(), e.call_site.as_str(), e.targets[0].name.as_str(), e.overall_confidence))
+            .collect();
+        found.sort_by_key(|&(_, site, _, _)| site);
+        assert_eq!(
+            found,
+            vec![
+                ("my_open", "L3", "helper", Confidence::Certain),
+                ("my_open", "L6", "helper", Confidence::Certain),
+                ("my_open", "L9", "cleanup", Confidence::Certain),
+            ]
+        );
+
+        let rows = edges[0].to_calls("drv.c");
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].confidence, ConfidenceLevel::Certain);
+        assert_eq!(rows[0].file, "drv.c");
     }
 }
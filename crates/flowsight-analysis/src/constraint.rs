@@ -2,10 +2,11 @@
 //!
 //! Collects pointer constraints from C source code AST for Andersen analysis.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use tree_sitter::{Node, Parser as TSParser};
 
 use super::pointer::{Constraint, Location};
+use super::types::TypeDatabase;
 
 /// Collects pointer constraints from C source code
 pub struct ConstraintCollector {
@@ -17,6 +18,12 @@ pub struct ConstraintCollector {
     current_function: Option<String>,
     /// Array declarations with their element types (array_name -> is_function_pointer_array)
     arrays: HashMap<String, bool>,
+    /// Conditions of the enclosing if/else branches
+    conditions: Vec<String>,
+    /// Struct each function (or function pointer field) returns a pointer to
+    returned_structs: HashMap<String, String>,
+    /// Functions compatible with each `struct.field` function pointer type
+    field_candidates: HashMap<String, HashSet<String>>,
 }
 
 impl ConstraintCollector {
@@ -27,6 +34,9 @@ impl ConstraintCollector {
             functions: HashMap::new(),
             current_function: None,
             arrays: HashMap::new(),
+            conditions: Vec::new(),
+            returned_structs: HashMap::new(),
+            field_candidates: HashMap::new(),
         }
     }
 
@@ -35,9 +45,19 @@ impl ConstraintCollector {
         self.functions = functions.into_iter().map(|f| (f, true)).collect();
     }
 
+    /// Resolve calls through the fields of a struct reached via a function's
+    /// return value (`ops = dev->get_ops(dev); ops->handler(dev);`) to the
+    /// functions `types` finds compatible with the field
+    ///
+    /// Without it such a struct's fields only point to what is stored in them.
+    pub fn set_type_database(&mut self, types: &TypeDatabase) {
+        self.field_candidates = types.compatible_funcs.clone();
+    }
+
     /// Collect constraints from source code
     pub fn collect(&mut self, source: &str) -> Vec<Constraint> {
         self.constraints.clear();
+        self.returned_structs.clear();
 
         let mut parser = TSParser::new();
         parser
@@ -45,12 +65,96 @@ impl ConstraintCollector {
             .expect("Failed to load C grammar");
 
         if let Some(tree) = parser.parse(source, None) {
+            self.collect_returned_structs(tree.root_node(), source);
             self.visit_node(tree.root_node(), source);
         }
+        self.link_returned_fields();
 
         std::mem::take(&mut self.constraints)
     }
 
+    /// Record functions and function pointer fields declared as returning a
+    /// `struct T *`
+    fn collect_returned_structs(&mut self, node: Node, source: &str) {
+        if matches!(node.kind(), "function_definition" | "declaration" | "field_declaration") {
+            let struct_type = node
+                .child_by_field_name("type")
+                .filter(|t| t.kind() == "struct_specifier")
+                .and_then(|t| t.child_by_field_name("name"))
+                .map(|name| self.node_text(name, source));
+            if let Some(struct_type) = struct_type {
+                let mut cursor = node.walk();
+                for declarator in node.children_by_field_name("declarator", &mut cursor) {
+                    if let Some(name) = self.function_returning_pointer(declarator, source) {
+                        self.returned_structs.insert(name, struct_type.clone());
+                    }
+                }
+            }
+        }
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.collect_returned_structs(child, source);
+        }
+    }
+
+    /// Name declared by `declarator` when it is a function, or a pointer to
+    /// one, returning a single pointer: `*get_ops(...)`, `*(*get_ops)(...)`
+    fn function_returning_pointer(&self, declarator: Node, source: &str) -> Option<String> {
+        if declarator.kind() != "pointer_declarator" {
+            return None;
+        }
+        let function = declarator.child_by_field_name("declarator")?;
+        if function.kind() != "function_declarator" {
+            return None;
+        }
+        let mut name = function.child_by_field_name("declarator")?;
+        loop {
+            match name.kind() {
+                "identifier" | "field_identifier" => return Some(self.node_text(name, source)),
+                "parenthesized_declarator" => name = name.named_child(0)?,
+                "pointer_declarator" => name = name.child_by_field_name("declarator")?,
+                _ => return None,
+            }
+        }
+    }
+
+    /// Struct `call` returns a pointer to, if its callee is a known function
+    /// or function pointer field
+    fn call_returned_struct(&self, call: Node, source: &str) -> Option<String> {
+        let callee = call.child_by_field_name("function")?;
+        let name = match callee.kind() {
+            "identifier" => self.node_text(callee, source),
+            "field_expression" => self.node_text(callee.child_by_field_name("field")?, source),
+            _ => return None,
+        };
+        self.returned_structs.get(&name).cloned()
+    }
+
+    /// Point the fields of structs reached through return values at the
+    /// functions compatible with them
+    fn link_returned_fields(&mut self) {
+        let structs: HashSet<&str> = self.returned_structs.values().map(String::as_str).collect();
+        let mut links: Vec<(&str, &str, &String)> = self
+            .field_candidates
+            .iter()
+            .filter_map(|(type_name, funcs)| {
+                let (struct_type, field) = type_name.split_once('.')?;
+                structs.contains(struct_type).then_some((struct_type, field, funcs))
+            })
+            .flat_map(|(struct_type, field, funcs)| funcs.iter().map(move |func| (struct_type, field, func)))
+            .collect();
+        links.sort();
+        let constraints: Vec<Constraint> = links
+            .into_iter()
+            .map(|(struct_type, field, func)| Constraint::AddressOf {
+                pointer: Location::field(&format!("alloc:{}", struct_type), field),
+                target: Location::func(func),
+            })
+            .collect();
+        self.constraints.extend(constraints);
+    }
+
     /// Visit AST node and collect constraints
     fn visit_node(&mut self, node: Node, source: &str) {
         match node.kind() {
@@ -65,6 +169,9 @@ impl ConstraintCollector {
             "init_declarator" => {
                 self.handle_init_declarator(node, source);
             }
+            "if_statement" => {
+                self.handle_if(node, source);
+            }
             "assignment_expression" => {
                 self.handle_assignment(node, source);
             }
@@ -247,10 +354,110 @@ impl ConstraintCollector {
         }
 
         if let (Some(var), Some(init)) = (var_name, init_value) {
+            // `struct T *p = f(...)`: p points to a T object
+            if init.kind() == "call_expression" && self.container_of_args(init, source).is_none() {
+                if let Some(struct_type) = self.declared_struct_pointer(node, source) {
+                    let constraint = self.address_of(Location::var(&var), Location::Alloc(struct_type));
+                    self.constraints.push(constraint);
+                }
+            }
             self.collect_assignment_constraint(&var, init, source);
         }
     }
 
+    /// `T` when `declarator` declares a `struct T *`
+    fn declared_struct_pointer(&self, declarator: Node, source: &str) -> Option<String> {
+        let declaration = declarator.parent()?;
+        let type_node = declaration.child_by_field_name("type")?;
+        let is_pointer = declarator
+            .child_by_field_name("declarator")
+            .is_some_and(|d| d.kind() == "pointer_declarator");
+        if type_node.kind() != "struct_specifier" || !is_pointer {
+            return None;
+        }
+        type_node
+            .child_by_field_name("name")
+            .map(|name| self.node_text(name, source))
+    }
+
+    /// `(ptr, T, member)` of `container_of(ptr, struct T, member)` (or `list_entry`)
+    fn container_of_args(&self, call: Node, source: &str) -> Option<(String, String, String)> {
+        let callee = call.child_by_field_name("function")?;
+        if !matches!(self.node_text(callee, source).as_str(), "container_of" | "list_entry") {
+            return None;
+        }
+        // The type argument isn't an expression, so split the text instead of the tree
+        let args = call.child_by_field_name("arguments")?;
+        let text = self.node_text(args, source);
+        let inner = text.trim().strip_prefix('(')?.strip_suffix(')')?;
+        let mut parts = Vec::new();
+        let (mut depth, mut start) = (0usize, 0);
+        for (i, c) in inner.char_indices() {
+            match c {
+                '(' | '[' => depth += 1,
+                ')' | ']' => depth = depth.saturating_sub(1),
+                ',' if depth == 0 => {
+                    parts.push(inner[start..i].trim());
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        parts.push(inner[start..].trim());
+        let [ptr, type_name, member] = parts[..] else {
+            return None;
+        };
+        let struct_type = type_name.strip_prefix("struct ").unwrap_or(type_name).trim();
+        Some((
+            ptr.trim_start_matches('&').trim().to_string(),
+            struct_type.to_string(),
+            member.to_string(),
+        ))
+    }
+
+    /// Handle if/else, recording the branch condition for nested assignments
+    fn handle_if(&mut self, node: Node, source: &str) {
+        let condition = node
+            .child_by_field_name("condition")
+            .map(|c| {
+                let text = self.node_text(c, source);
+                let text = text.trim();
+                text.strip_prefix('(')
+                    .and_then(|t| t.strip_suffix(')'))
+                    .unwrap_or(text)
+                    .trim()
+                    .to_string()
+            })
+            .unwrap_or_default();
+
+        if let Some(cond) = node.child_by_field_name("condition") {
+            self.visit_node(cond, source);
+        }
+        if let Some(consequence) = node.child_by_field_name("consequence") {
+            self.conditions.push(condition.clone());
+            self.visit_node(consequence, source);
+            self.conditions.pop();
+        }
+        if let Some(alternative) = node.child_by_field_name("alternative") {
+            self.conditions.push(format!("!({})", condition));
+            self.visit_node(alternative, source);
+            self.conditions.pop();
+        }
+    }
+
+    /// AddressOf, or ConditionalAddressOf when inside an if/else branch
+    fn address_of(&self, pointer: Location, target: Location) -> Constraint {
+        if self.conditions.is_empty() {
+            Constraint::AddressOf { pointer, target }
+        } else {
+            Constraint::ConditionalAddressOf {
+                pointer,
+                target,
+                condition: self.conditions.join(" && "),
+            }
+        }
+    }
+
     /// Handle assignment: lhs = rhs;
     fn handle_assignment(&mut self, node: Node, source: &str) {
         let mut lhs = None;
@@ -302,8 +509,28 @@ impl ConstraintCollector {
     fn collect_assignment_constraint(&mut self, lhs: &str, rhs: Node, source: &str) {
         let rhs_text = self.node_text(rhs, source);
 
+        // Recover the enclosing struct: p = container_of(q, struct T, member)
+        if rhs.kind() == "call_expression" {
+            if let Some((ptr, struct_type, member)) = self.container_of_args(rhs, source) {
+                self.constraints.push(Constraint::ContainerOf {
+                    dest: self.parse_location(lhs),
+                    member_ptr: self.parse_location(&ptr),
+                    struct_type,
+                    member,
+                });
+                return;
+            }
+            // p = get_ops(...) with `struct T *get_ops(...)`: p points to a T
+            if let Some(struct_type) = self.call_returned_struct(rhs, source) {
+                let constraint = self.address_of(self.parse_location(lhs), Location::Alloc(struct_type));
+                self.constraints.push(constraint);
+                return;
+            }
+        }
+
         // Check for address-of: p = &x
-        if rhs.kind() == "unary_expression" {
+        // tree-sitter-c parses `&x` as a pointer_expression
+        if rhs.kind() == "unary_expression" || rhs.kind() == "pointer_expression" {
             let mut cursor = rhs.walk();
             let children: Vec<_> = rhs.children(&mut cursor).collect();
             if children.len() >= 2 && self.node_text(children[0], source) == "&" {
@@ -314,20 +541,25 @@ impl ConstraintCollector {
                     Location::var(&target)
                 };
 
-                self.constraints.push(Constraint::AddressOf {
-                    pointer: self.parse_location(lhs),
-                    target: target_loc,
-                });
+                let constraint = self.address_of(self.parse_location(lhs), target_loc);
+                self.constraints.push(constraint);
                 return;
             }
         }
 
         // Check for function name (implicit address-of)
         if self.functions.contains_key(&rhs_text) {
-            self.constraints.push(Constraint::AddressOf {
-                pointer: self.parse_location(lhs),
-                target: Location::func(&rhs_text),
-            });
+            let constraint =
+                self.address_of(self.parse_location(lhs), Location::func(&rhs_text));
+            self.constraints.push(constraint);
+            // p->field = func also stores into whatever p points to
+            if let (base, Some(field)) = self.parse_field_str(lhs) {
+                self.constraints.push(Constraint::FieldStore {
+                    base_ptr: Location::var(&base),
+                    field,
+                    src: Location::func(&rhs_text),
+                });
+            }
             return;
         }
 
@@ -415,6 +647,19 @@ impl ConstraintCollector {
             }
         }
 
+        // Handle field-based indirect calls: dev->callback(...)
+        if callee.kind() == "field_expression" {
+            let (base, field) = self.parse_field_expression(callee, source);
+            if let Some(field) = field {
+                let call_target = format!("__call_{}", self.node_text(callee, source));
+                self.constraints.push(Constraint::FieldLoad {
+                    dest: Location::var(&call_target),
+                    base_ptr: Location::var(&base),
+                    field,
+                });
+            }
+        }
+
         let func_name = self.node_text(callee, source);
 
         // Handle common callback registration patterns
@@ -452,7 +697,7 @@ impl ConstraintCollector {
                 }
             }
             // request_irq(irq, handler, ...)
-            "request_irq" | "request_threaded_irq" => {
+            "request_irq" => {
                 if let Some(args) = self.get_call_args(node, source) {
                     if args.len() >= 2 {
                         let handler = &args[1];
@@ -465,6 +710,23 @@ impl ConstraintCollector {
                     }
                 }
             }
+            // request_threaded_irq(irq, handler, thread_fn, ...): the primary
+            // handler runs in hardirq context, thread_fn in the irq thread
+            "request_threaded_irq" => {
+                if let Some(args) = self.get_call_args(node, source) {
+                    if args.len() >= 3 {
+                        let halves = [("irq", &args[1]), ("irq_thread", &args[2])];
+                        for (prefix, handler) in halves {
+                            if self.functions.contains_key(handler) {
+                                self.constraints.push(Constraint::AddressOf {
+                                    pointer: Location::var(&format!("{}_{}", prefix, args[0])),
+                                    target: Location::func(handler),
+                                });
+                            }
+                        }
+                    }
+                }
+            }
             _ => {}
         }
 
@@ -514,11 +776,10 @@ impl ConstraintCollector {
                             Some(text.trim_start_matches('.').to_string())
                         });
                 }
-                "identifier" => {
-                    if field_name.is_some() {
+                "identifier"
+                    if field_name.is_some() => {
                         value = Some(self.node_text(child, source));
                     }
-                }
                 _ => {}
             }
         }
@@ -743,4 +1004,120 @@ void dispatch(int cmd) {
         }).collect();
         assert_eq!(array_loads.len(), 1);
     }
+
+    #[test]
+    fn test_conditional_assignment() {
+        let source = r#"
+void fast_path(void) {}
+void slow_path(void) {}
+
+void setup(int fast) {
+    if (fast)
+        fp = fast_path;
+    else
+        fp = &slow_path;
+    gp = fast_path;
+}
+"#;
+        let mut collector = ConstraintCollector::new();
+        collector.set_functions(vec!["fast_path".to_string(), "slow_path".to_string()]);
+        let constraints = collector.collect(source);
+
+        let conditional: Vec<_> = constraints
+            .iter()
+            .filter_map(|c| match c {
+                Constraint::ConditionalAddressOf { target, condition, .. } => {
+                    Some((target.clone(), condition.as_str()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            conditional,
+            vec![
+                (Location::func("fast_path"), "fast"),
+                (Location::func("slow_path"), "!(fast)"),
+            ]
+        );
+        assert!(constraints.iter().any(|c| matches!(c,
+            Constraint::AddressOf { pointer, .. } if *pointer == Location::var("gp")
+        )));
+    }
+
+    #[test]
+    fn test_container_of() {
+        let source = r#"
+void my_callback(struct my_dev *dev) {}
+
+void my_work_handler(struct work_struct *work) {
+    struct my_dev *dev = container_of(work, struct my_dev, work);
+    dev->callback(dev);
+}
+
+int my_probe(void) {
+    struct my_dev *priv = kzalloc(sizeof(*priv), GFP_KERNEL);
+    priv->callback = my_callback;
+    INIT_WORK(&priv->work, my_work_handler);
+    return 0;
+}
+"#;
+        let mut collector = ConstraintCollector::new();
+        collector.set_functions(vec!["my_callback".to_string(), "my_work_handler".to_string()]);
+        let constraints = collector.collect(source);
+
+        assert!(constraints.iter().any(|c| matches!(c,
+            Constraint::ContainerOf { dest, member_ptr, struct_type, member }
+                if *dest == Location::var("dev")
+                    && *member_ptr == Location::var("work")
+                    && struct_type == "my_dev"
+                    && member == "work"
+        )));
+
+        let mut solver = crate::pointer::AndersenSolver::new();
+        solver.add_constraints(constraints);
+        let result = solver.solve();
+        assert!(result.get_targets("dev").unwrap().contains("alloc:my_dev"));
+        assert!(result.get_targets("work").unwrap().contains("alloc:my_dev.work"));
+        assert_eq!(result.get_function_targets("__call_dev->callback"), vec!["my_callback"]);
+    }
+
+    #[test]
+    fn test_returned_struct_fields() {
+        let source = r#"
+struct my_ops {
+    int (*handler)(struct my_dev *dev);
+};
+
+struct my_dev {
+    const struct my_ops *(*get_ops)(struct my_dev *dev);
+};
+
+static int fast_handler(struct my_dev *dev) { return 0; }
+static int slow_handler(struct my_dev *dev) { return 1; }
+static void unrelated(int x) {}
+
+void run(struct my_dev *dev) {
+    const struct my_ops *ops;
+    ops = dev->get_ops(dev);
+    ops->handler(dev);
+}
+"#;
+        let mut types = crate::types::TypeAnalyzer::new();
+        let mut collector = ConstraintCollector::new();
+        collector.set_functions(
+            ["fast_handler", "slow_handler", "unrelated", "run"]
+                .into_iter()
+                .map(String::from),
+        );
+        collector.set_type_database(types.analyze(source));
+        let constraints = collector.collect(source);
+
+        let mut solver = crate::pointer::AndersenSolver::new();
+        solver.add_constraints(constraints);
+        let result = solver.solve();
+        assert!(result.get_targets("ops").unwrap().contains("alloc:my_ops"));
+        let mut targets = result.get_function_targets("__call_ops->handler");
+        targets.sort();
+        assert_eq!(targets, vec!["fast_handler", "slow_handler"]);
+    }
 }
@@ -0,0 +1,237 @@
+//! Control-flow correctness checks
+//!
+//! Flags `for (;;)`, `while (1)` and `do { } while (1)` loops that nothing can
+//! leave: no `break` out of the loop itself, no `return`, no `goto` to a label
+//! outside the body and no call to a function that never returns. In driver
+//! code such a loop is a hang, and in a handler it takes the CPU with it.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tree_sitter::{Node, Parser as TSParser};
+
+/// Calls that never return, so a loop calling them is not stuck
+const NORETURN: &[&str] = &["panic", "BUG", "do_exit", "kthread_exit", "machine_halt", "emergency_restart"];
+
+/// A loop with no way out
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InfiniteLoop {
+    /// Function containing the loop
+    pub function: String,
+    /// Line of the loop statement (1-based)
+    pub line: u32,
+}
+
+/// Checker for loops without an exit
+pub struct ControlFlowChecker {
+    /// Only check these functions
+    focus: Option<HashSet<String>>,
+}
+
+impl ControlFlowChecker {
+    pub fn new() -> Self {
+        Self { focus: None }
+    }
+
+    /// Only check `functions` (e.g. the ones touched by a change)
+    pub fn with_focus(mut self, functions: HashSet<String>) -> Self {
+        self.focus = Some(functions);
+        self
+    }
+
+    /// Find every loop in `source` that can never be left
+    pub fn find_infinite_loops(&self, source: &str) -> Vec<InfiniteLoop> {
+        let mut parser = TSParser::new();
+        parser
+            .set_language(&tree_sitter_c::language())
+            .expect("Failed to load C grammar");
+
+        let mut loops = Vec::new();
+        if let Some(tree) = parser.parse(source, None) {
+            self.visit(tree.root_node(), source, None, &mut loops);
+        }
+        loops
+    }
+
+    fn visit(&self, node: Node, source: &str, function: Option<&str>, loops: &mut Vec<InfiniteLoop>) {
+        let name;
+        let mut function = function;
+        if node.kind() == "function_definition" {
+            name = node
+                .child_by_field_name("declarator")
+                .and_then(|d| declared_name(d, source));
+            function = name.as_deref();
+        }
+
+        let focused = |func: &str| self.focus.as_ref().is_none_or(|focus| focus.contains(func));
+        if let Some(func) = function.filter(|f| focused(f)) {
+            if is_infinite_loop(node, source) && !has_exit(node, source) {
+                loops.push(InfiniteLoop {
+                    function: func.to_string(),
+                    line: node.start_position().row as u32 + 1,
+                });
+            }
+        }
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.visit(child, source, function, loops);
+        }
+    }
+}
+
+impl Default for ControlFlowChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) fn text<'a>(node: Node, source: &'a str) -> &'a str {
+    &source[node.byte_range()]
+}
+
+/// Name declared by a (possibly pointer-returning) function declarator
+pub(crate) fn declared_name(node: Node, source: &str) -> Option<String> {
+    match node.kind() {
+        "identifier" => Some(text(node, source).to_string()),
+        _ => declared_name(node.child_by_field_name("declarator")?, source),
+    }
+}
+
+fn is_loop(node: Node) -> bool {
+    matches!(node.kind(), "for_statement" | "while_statement" | "do_statement")
+}
+
+/// Loop whose condition is missing or a non-zero constant
+fn is_infinite_loop(node: Node, source: &str) -> bool {
+    if !is_loop(node) {
+        return false;
+    }
+    let Some(condition) = node.child_by_field_name("condition") else {
+        return node.kind() == "for_statement";
+    };
+    let cond = text(condition, source).trim_matches(|c: char| c == '(' || c == ')' || c.is_whitespace());
+    cond == "true" || cond.parse::<u64>().is_ok_and(|n| n != 0)
+}
+
+/// Whether anything in `loop_node`'s body leaves the loop
+fn has_exit(loop_node: Node, source: &str) -> bool {
+    let Some(body) = loop_node.child_by_field_name("body") else {
+        return false;
+    };
+    let mut labels = HashSet::new();
+    collect_labels(body, source, &mut labels);
+    exits(body, source, &labels, true)
+}
+
+fn collect_labels<'a>(node: Node, source: &'a str, labels: &mut HashSet<&'a str>) {
+    if node.kind() == "labeled_statement" {
+        if let Some(label) = node.child_by_field_name("label") {
+            labels.insert(text(label, source));
+        }
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_labels(child, source, labels);
+    }
+}
+
+/// `breaks_outer`: a `break` here still leaves the loop being checked
+fn exits(node: Node, source: &str, labels: &HashSet<&str>, breaks_outer: bool) -> bool {
+    match node.kind() {
+        "return_statement" => return true,
+        "break_statement" if breaks_outer => return true,
+        "goto_statement" => {
+            return node
+                .child_by_field_name("label")
+                .is_some_and(|label| !labels.contains(text(label, source)));
+        }
+        "call_expression" => {
+            let callee = node.child_by_field_name("function").map(|f| text(f, source));
+            if callee.is_some_and(|c| NORETURN.contains(&c)) {
+                return true;
+            }
+        }
+        _ => {}
+    }
+
+    let breaks_outer = breaks_outer && !is_loop(node) && node.kind() != "switch_statement";
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if exits(child, source, labels, breaks_outer) {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_infinite_loops() {
+        let source = r#"
+static int poll_ready(struct my_dev *dev) {
+    while (1) {
+        if (readl(dev->regs) & READY)
+            break;
+        cpu_relax();
+    }
+    return 0;
+}
+
+static void stuck(struct my_dev *dev) {
+    for (;;) {
+        switch (readl(dev->regs)) {
+        case 0:
+            break;
+        }
+        while (readl(dev->regs) & BUSY)
+            break;
+    }
+}
+
+static int retry(struct my_dev *dev) {
+again:
+    for (;;) {
+        if (readl(dev->regs))
+            goto out;
+        if (dev->reset)
+            goto again;
+    }
+out:
+    return 0;
+}
+
+static void spin(void) {
+    do {
+retry:
+        cpu_relax();
+        goto retry;
+    } while (1);
+}
+
+static void halt(void) {
+    while (1)
+        panic("halted");
+}
+
+static int bounded(int n) {
+    int i;
+    for (i = 0; i < n; i++)
+        ;
+    while (n--)
+        ;
+    return i;
+}
+"#;
+        let loops = ControlFlowChecker::new().find_infinite_loops(source);
+        assert_eq!(
+            loops,
+            vec![
+                InfiniteLoop { function: "stuck".into(), line: 12 },
+                InfiniteLoop { function: "spin".into(), line: 35 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! Driver callback pairing checks
+//!
+//! A driver table that sets an acquire-time callback (`.probe`, `.open`)
+//! without its release-time counterpart (`.remove`, `.release`) usually
+//! leaks whatever the first one set up once the device is unbound or the
+//! file closed.
+//!
+//! Which tables belong to a framework, and which callbacks that framework
+//! has, comes from the knowledge base: a pair is only checked when the
+//! framework declares the release-time callback.
+
+use flowsight_core::OpsAssignment;
+use flowsight_knowledge::KnowledgeBase;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Acquire-time callbacks and the release-time callbacks that undo them
+const CALLBACK_PAIRS: &[(&str, &[&str])] = &[
+    ("probe", &["remove", "remove_new", "disconnect"]),
+    ("open", &["release"]),
+    ("connect", &["disconnect"]),
+    ("attach", &["detach"]),
+];
+
+/// A driver table missing the callback that undoes one it sets
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MissingCallback {
+    /// Framework of the table, e.g. "usb_driver"
+    pub framework: String,
+    /// Variable holding the table, e.g. "my_usb_driver"
+    pub variable: String,
+    /// Release-time callback that is not set, e.g. "disconnect"
+    pub callback: String,
+    /// Acquire-time callback that is set, e.g. "probe"
+    pub paired_with: String,
+    /// Function assigned to `paired_with`
+    pub function: String,
+    /// Line of the `paired_with` assignment (1-based)
+    pub line: u32,
+}
+
+/// Check every framework table among `assignments`
+pub fn find_missing_callbacks(assignments: &[OpsAssignment], kb: &KnowledgeBase) -> Vec<MissingCallback> {
+    // (framework, table) -> assignments, in a stable order
+    let mut tables: BTreeMap<(&str, &str), Vec<&OpsAssignment>> = BTreeMap::new();
+    for assignment in assignments {
+        tables
+            .entry((assignment.ops_type.as_str(), assignment.variable.as_str()))
+            .or_default()
+            .push(assignment);
+    }
+
+    let mut missing = Vec::new();
+    for ((framework, variable), fields) in tables {
+        let Some(definition) = kb.frameworks.get(framework) else {
+            continue;
+        };
+        let sets = |field: &str| fields.iter().find(|a| a.field == field);
+        for (acquire, releases) in CALLBACK_PAIRS {
+            let Some(acquired) = sets(acquire) else {
+                continue;
+            };
+            let Some(expected) = releases.iter().find(|r| definition.callbacks.contains_key(**r)) else {
+                continue;
+            };
+            if releases.iter().any(|r| sets(r).is_some()) {
+                continue;
+            }
+            missing.push(MissingCallback {
+                framework: framework.to_string(),
+                variable: variable.to_string(),
+                callback: expected.to_string(),
+                paired_with: acquire.to_string(),
+                function: acquired.function.clone(),
+                line: acquired.location.as_ref().map_or(0, |loc| loc.line),
+            });
+        }
+    }
+    missing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::funcptr::FuncPtrResolver;
+
+    #[test]
+    fn test_missing_callbacks() {
+        let source = r#"
+static struct usb_driver leaky_driver = {
+    .name = "leaky",
+    .probe = leaky_probe,
+    .id_table = leaky_ids,
+};
+
+static struct usb_driver tidy_driver = {
+    .name = "tidy",
+    .probe = tidy_probe,
+    .disconnect = tidy_disconnect,
+};
+
+static const struct file_operations my_fops = {
+    .open = my_open,
+    .read = my_read,
+};
+"#;
+        let assignments = FuncPtrResolver::new().find_ops_assignments(source, "drv.c");
+        let missing = find_missing_callbacks(&assignments, &KnowledgeBase::builtin());
+
+        // file_operations declares no release in the builtin knowledge base
+        assert_eq!(
+            missing,
+            vec![MissingCallback {
+                framework: "usb_driver".into(),
+                variable: "leaky_driver".into(),
+                callback: "disconnect".into(),
+                paired_with: "probe".into(),
+                function: "leaky_probe".into(),
+                line: 4,
+            }]
+        );
+    }
+}
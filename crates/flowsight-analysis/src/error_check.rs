@@ -0,0 +1,528 @@
+//! Error-path checking for failable kernel APIs
+//!
+//! Flags results of `can_fail` APIs (kzalloc, ioremap, clk_get, ...) that are
+//! dereferenced before any NULL / IS_ERR check. The checker is deliberately
+//! conservative: it only reports when a dereference is seen first, and gives
+//! up on a variable once it is reassigned or returned.
+//!
+//! User copies (copy_to_user, get_user, ...) are the opposite case: they
+//! return the number of bytes *not* copied (or -EFAULT), so what matters is
+//! that the result is looked at at all.
+//!
+//! A finding can be suppressed with a `flowsight:ignore` comment on the
+//! assignment line or the line above it.
+//!
+//! Both checks work line by line: a check or dereference is only seen on the
+//! line holding the variable, so a condition wrapped onto the next line
+//! (`if (priv ==` / `NULL)`) is missed.
+
+use flowsight_core::FunctionDef;
+use flowsight_knowledge::KnowledgeBase;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Comment marker that suppresses a finding
+pub const SUPPRESS_MARKER: &str = "flowsight:ignore";
+
+/// A failable API result used without a preceding check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct UncheckedAllocation {
+    /// API whose result is unchecked, e.g. "kzalloc"
+    pub api: String,
+    /// Variable holding the result, e.g. "priv" or "dev->regs"
+    pub variable: String,
+    /// Function containing the assignment
+    pub function: String,
+    /// Line of the assignment (1-based)
+    pub line: u32,
+    /// Line of the first unchecked dereference (1-based)
+    pub use_line: u32,
+}
+
+/// APIs copying between user and kernel space; non-zero means the copy failed
+const USER_COPY_APIS: &[&str] = &[
+    "copy_to_user",
+    "copy_from_user",
+    "get_user",
+    "put_user",
+    "__copy_to_user",
+    "__copy_from_user",
+    "__get_user",
+    "__put_user",
+];
+
+/// A user copy whose result is never looked at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct UncheckedUserCopy {
+    /// API whose result is ignored, e.g. "copy_from_user"
+    pub api: String,
+    /// Function containing the call
+    pub function: String,
+    /// Line of the call (1-based)
+    pub line: u32,
+}
+
+/// Checker for unchecked failable API results
+pub struct ErrorChecker {
+    assign_re: Regex,
+    user_copy_re: Regex,
+    /// Only check these functions
+    focus: Option<HashSet<String>>,
+}
+
+/// What a line does with the tracked variable
+enum Use {
+    Checked,
+    Escaped,
+    Dereferenced,
+    None,
+}
+
+impl ErrorChecker {
+    pub fn new() -> Self {
+        Self {
+            // p = kzalloc(...)  /  dev->regs = (void __iomem *)ioremap(...)
+            assign_re: Regex::new(
+                r"([A-Za-z_]\w*(?:(?:->|\.)\w+)*)\s*=\s*(?:\([^()]*\)\s*)?([A-Za-z_]\w*)\s*\(",
+            )
+            .unwrap(),
+            user_copy_re: Regex::new(&format!(r"\b({})\s*\(", USER_COPY_APIS.join("|"))).unwrap(),
+            focus: None,
+        }
+    }
+
+    /// Only check `functions` (e.g. the ones touched by a change)
+    pub fn with_focus(mut self, functions: HashSet<String>) -> Self {
+        self.focus = Some(functions);
+        self
+    }
+
+    /// Check every function in `functions` against `source`
+    pub fn check(
+        &self,
+        source: &str,
+        functions: &HashMap<String, FunctionDef>,
+        kb: &KnowledgeBase,
+    ) -> Vec<UncheckedAllocation> {
+        let lines: Vec<&str> = source.lines().collect();
+        let mut findings = Vec::new();
+
+        for (func, body, first_line) in function_bodies(&lines, functions, self.focus.as_ref()) {
+            findings.extend(self.check_body(&func.name, body, first_line, kb));
+        }
+
+        findings.sort_by(|a, b| (a.line, &a.variable).cmp(&(b.line, &b.variable)));
+        findings
+    }
+
+    /// Find user copies in `functions` whose result is discarded, or stored
+    /// and then never read
+    pub fn check_user_copies(
+        &self,
+        source: &str,
+        functions: &HashMap<String, FunctionDef>,
+    ) -> Vec<UncheckedUserCopy> {
+        let lines: Vec<&str> = source.lines().collect();
+        let mut findings = Vec::new();
+
+        for (func, body, first_line) in function_bodies(&lines, functions, self.focus.as_ref()) {
+            for (i, line) in body.iter().enumerate() {
+                let code = line.trim_start();
+                if code.starts_with("//") || code.starts_with('*') || code.starts_with("/*") {
+                    continue;
+                }
+                if line.contains(SUPPRESS_MARKER) || (i > 0 && body[i - 1].contains(SUPPRESS_MARKER)) {
+                    continue;
+                }
+                for caps in self.user_copy_re.captures_iter(line) {
+                    let call = caps.get(0).unwrap();
+                    let prefix = line[..call.start()].trim();
+                    let ignored = match result_use(prefix) {
+                        ResultUse::Discarded => starts_statement(&body[..i]),
+                        ResultUse::Stored(variable) => !is_read_later(&body[i + 1..], variable),
+                        ResultUse::Used => false,
+                    };
+                    if ignored {
+                        findings.push(UncheckedUserCopy {
+                            api: caps[1].to_string(),
+                            function: func.name.clone(),
+                            line: first_line + i as u32,
+                        });
+                    }
+                }
+            }
+        }
+
+        findings.sort_by(|a, b| (a.line, &a.api).cmp(&(b.line, &b.api)));
+        findings
+    }
+
+    fn check_body(
+        &self,
+        function: &str,
+        body: &[&str],
+        first_line: u32,
+        kb: &KnowledgeBase,
+    ) -> Vec<UncheckedAllocation> {
+        let mut findings = Vec::new();
+
+        for (i, line) in body.iter().enumerate() {
+            let Some(caps) = self.assign_re.captures(line) else {
+                continue;
+            };
+            let (variable, api) = (&caps[1], &caps[2]);
+            if !kb.get_api(api).is_some_and(|a| a.can_fail) {
+                continue;
+            }
+            // Skip `==` comparisons that happen to match
+            if line[caps.get(1).unwrap().end()..].trim_start().starts_with("==") {
+                continue;
+            }
+            if line.contains(SUPPRESS_MARKER)
+                || (i > 0 && body[i - 1].contains(SUPPRESS_MARKER))
+            {
+                continue;
+            }
+            // Checked in the same statement, e.g. `if (!(p = kzalloc(...)))`
+            if line.trim_start().starts_with("if") && matches!(classify_use(line, variable), Use::Checked) {
+                continue;
+            }
+
+            for (offset, later) in body[i + 1..].iter().enumerate() {
+                match classify_use(later, variable) {
+                    Use::Checked | Use::Escaped => break,
+                    Use::Dereferenced => {
+                        findings.push(UncheckedAllocation {
+                            api: api.to_string(),
+                            variable: variable.to_string(),
+                            function: function.to_string(),
+                            line: first_line + i as u32,
+                            use_line: first_line + (i + 1 + offset) as u32,
+                        });
+                        break;
+                    }
+                    Use::None => {}
+                }
+            }
+        }
+
+        findings
+    }
+}
+
+impl Default for ErrorChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Each function of `functions` found in `lines`, with its body and first line
+fn function_bodies<'a>(
+    lines: &'a [&'a str],
+    functions: &'a HashMap<String, FunctionDef>,
+    focus: Option<&'a HashSet<String>>,
+) -> impl Iterator<Item = (&'a FunctionDef, &'a [&'a str], u32)> {
+    functions.values().filter_map(move |func| {
+        if focus.is_some_and(|focus| !focus.contains(&func.name)) {
+            return None;
+        }
+        let loc = func.location.as_ref()?;
+        let start = (loc.line as usize).saturating_sub(1);
+        let end = (loc.end_line as usize).min(lines.len());
+        (start < end).then(|| (func, &lines[start..end], loc.line))
+    })
+}
+
+/// What the code before a call on its line does with the call's result
+enum ResultUse<'a> {
+    /// Nothing, or an explicit `(void)` cast
+    Discarded,
+    /// Assigned to a variable
+    Stored(&'a str),
+    /// Tested, returned, passed on or otherwise part of an expression
+    Used,
+}
+
+fn result_use(prefix: &str) -> ResultUse<'_> {
+    let prefix = prefix.trim_end_matches(|c: char| c == '(' || c.is_whitespace());
+    if prefix.is_empty() || prefix == "(void)" {
+        return ResultUse::Discarded;
+    }
+    // `ret = ` or `int ret = `; comparisons and `ret |= ` count as uses
+    if let Some(lhs) = prefix.strip_suffix('=') {
+        let variable = lhs.trim_end().rsplit([' ', '\t', '*']).next().unwrap_or("");
+        if is_lvalue(variable) {
+            return ResultUse::Stored(variable);
+        }
+    }
+    ResultUse::Used
+}
+
+/// Whether `s` is a variable or a member path like `dev->stats.count`
+fn is_lvalue(s: &str) -> bool {
+    let mut parts = s.split("->").flat_map(|part| part.split('.'));
+    let first = parts.next().unwrap_or_default();
+    first.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && first.chars().all(is_word)
+        && parts.all(|part| !part.is_empty() && part.chars().all(is_word))
+}
+
+/// Whether a call with nothing before it on its line starts a statement,
+/// rather than continuing an expression from an earlier line
+fn starts_statement(before: &[&str]) -> bool {
+    let previous = before.iter().rev().map(|l| l.trim()).find(|l| !l.is_empty());
+    match previous {
+        None => true,
+        Some(line) => {
+            line.ends_with([';', '{', '}', ':'])
+                || line.ends_with("*/")
+                || line.starts_with("//")
+                || line.starts_with('#')
+                || line == "else"
+        }
+    }
+}
+
+/// Whether `variable` is read in `rest` before being overwritten
+fn is_read_later(rest: &[&str], variable: &str) -> bool {
+    for line in rest {
+        let (mentions, overwrites) = occurrences(line, variable)
+            .fold((0, 0), |(m, o), (_, after)| (m + 1, o + is_overwrite(after) as usize));
+        if mentions == 0 {
+            continue;
+        }
+        // `ret = foo(ret)` still reads it; a bare overwrite does not
+        return mentions > overwrites;
+    }
+    false
+}
+
+/// Classify how `line` uses `var`; checks win over dereferences on the same line
+fn classify_use(line: &str, var: &str) -> Use {
+    if !line.contains(var) {
+        return Use::None;
+    }
+    let uses: Vec<(&str, &str)> = occurrences(line, var).collect();
+
+    if uses.iter().any(|&(before, after)| is_check(before, after)) {
+        return Use::Checked;
+    }
+    // Reassigned or handed back to the caller: stop tracking
+    if uses.iter().any(|&(before, after)| is_overwrite(after) || is_return(before, after)) {
+        return Use::Escaped;
+    }
+    if uses.iter().any(|&(before, after)| is_deref(before, after)) {
+        return Use::Dereferenced;
+    }
+
+    Use::None
+}
+
+/// Text before and after each mention of `var` in `line` that is neither
+/// part of a longer name nor a member of something else (`x->var`, `x.var`)
+fn occurrences<'a>(line: &'a str, var: &'a str) -> impl Iterator<Item = (&'a str, &'a str)> + 'a {
+    line.match_indices(var).filter_map(move |(start, _)| {
+        let (before, after) = (&line[..start], &line[start + var.len()..]);
+        let root = !before.ends_with(|c: char| is_word(c) || c == '>' || c == '.');
+        (root && !after.starts_with(is_word)).then_some((before, after))
+    })
+}
+
+/// `!var`, `var == NULL`, `NULL != var`, `IS_ERR(var)`, `if (likely(var))`...
+fn is_check(before: &str, after: &str) -> bool {
+    let before = before.trim_end();
+    let after = after.trim_start();
+    let opened = before.strip_suffix('(').map(str::trim_end);
+    let negated = before.ends_with('!') || opened.is_some_and(|b| b.ends_with('!'));
+    let null_after = after
+        .strip_prefix("==")
+        .or_else(|| after.strip_prefix("!="))
+        .is_some_and(|rest| strip_word_prefix(rest.trim_start(), "NULL").is_some());
+    let null_before = before
+        .strip_suffix("==")
+        .or_else(|| before.strip_suffix("!="))
+        .is_some_and(|rest| strip_word_suffix(rest.trim_end(), "NULL").is_some());
+    let wrapped = after.starts_with(')')
+        && opened.is_some_and(|b| {
+            let is_if = |b: &str| strip_word_suffix(b, "if").is_some();
+            ["IS_ERR", "IS_ERR_OR_NULL", "PTR_ERR_OR_ZERO", "PTR_ERR"]
+                .iter()
+                .any(|api| strip_word_suffix(b, api).is_some())
+                || is_if(b)
+                || strip_word_suffix(b, "likely")
+                    .and_then(|b| b.trim_end().strip_suffix('('))
+                    .is_some_and(|b| is_if(b.trim_end()))
+        });
+    negated || null_after || null_before || wrapped
+}
+
+/// `var = ...`, but not `var == ...`
+fn is_overwrite(after: &str) -> bool {
+    after
+        .trim_start()
+        .strip_prefix('=')
+        .is_some_and(|rest| rest.chars().next().is_some_and(|c| c != '='))
+}
+
+/// `return var;` or `return (var);`
+fn is_return(before: &str, after: &str) -> bool {
+    let before = before.trim_end();
+    let before = before.strip_suffix('(').map_or(before, str::trim_end);
+    let after = after.trim_start();
+    let after = after.strip_prefix(')').map_or(after, str::trim_start);
+    strip_word_suffix(before, "return").is_some() && after.starts_with(';')
+}
+
+/// `var->x`, `var[i]`, `var.x` or `*var`
+fn is_deref(before: &str, after: &str) -> bool {
+    let member = after.trim_start().starts_with("->")
+        || after.trim_start().starts_with('[')
+        || after.strip_prefix('.').is_some_and(|rest| rest.starts_with(is_word));
+    let star = before
+        .trim_end()
+        .strip_suffix('*')
+        .is_some_and(|rest| !rest.ends_with(|c: char| is_word(c) || c == ')'));
+    member || star
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// `s` without a leading whole word `word`
+fn strip_word_prefix<'a>(s: &'a str, word: &str) -> Option<&'a str> {
+    s.strip_prefix(word).filter(|rest| !rest.starts_with(is_word))
+}
+
+/// `s` without a trailing whole word `word`
+fn strip_word_suffix<'a>(s: &'a str, word: &str) -> Option<&'a str> {
+    s.strip_suffix(word).filter(|rest| !rest.ends_with(is_word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowsight_parser::treesitter::TreeSitterParser;
+
+    fn check(source: &str) -> Vec<UncheckedAllocation> {
+        let mut parser = TreeSitterParser::new();
+        let result = parser.parse_source(source, "test.c").unwrap();
+        ErrorChecker::new().check(source, &result.functions, &KnowledgeBase::builtin())
+    }
+
+    #[test]
+    fn test_unchecked_allocation() {
+        let source = r#"
+static int probe(struct device *dev) {
+    struct my_priv *priv = kzalloc(sizeof(*priv), GFP_KERNEL);
+    priv->dev = dev;
+    return 0;
+}
+"#;
+        let findings = check(source);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].api, "kzalloc");
+        assert_eq!(findings[0].variable, "priv");
+        assert_eq!(findings[0].function, "probe");
+        assert_eq!(findings[0].line, 3);
+        assert_eq!(findings[0].use_line, 4);
+    }
+
+    #[test]
+    fn test_checked_and_suppressed() {
+        let source = r#"
+static int probe(struct device *dev) {
+    struct my_priv *priv = kzalloc(sizeof(*priv), GFP_KERNEL);
+    if (!priv)
+        return -ENOMEM;
+    priv->regs = ioremap(0x1000, 0x100);
+    if (priv->regs == NULL)
+        return -EIO;
+    priv->clk = clk_get(dev, "core");
+    if (IS_ERR(priv->clk))
+        return PTR_ERR(priv->clk);
+    /* flowsight:ignore - never fails at boot */
+    priv->buf = kmalloc(64, GFP_KERNEL);
+    priv->buf[0] = 0;
+    return 0;
+}
+
+static void *alloc_helper(void) {
+    void *p = kmalloc(16, GFP_KERNEL);
+    return p;
+}
+"#;
+        assert!(check(source).is_empty());
+    }
+
+    #[test]
+    fn test_classify_use() {
+        let kind = |line: &str, var: &str| match classify_use(line, var) {
+            Use::Checked => "checked",
+            Use::Escaped => "escaped",
+            Use::Dereferenced => "deref",
+            Use::None => "none",
+        };
+        assert_eq!(kind("if (!(priv = kzalloc(n, GFP_KERNEL)))", "priv"), "checked");
+        assert_eq!(kind("if (NULL == priv->regs) {", "priv->regs"), "checked");
+        assert_eq!(kind("if (unlikely(IS_ERR( clk )))", "clk"), "checked");
+        assert_eq!(kind("if (likely(priv))", "priv"), "checked");
+        assert_eq!(kind("return (priv);", "priv"), "escaped");
+        assert_eq!(kind("priv = NULL;", "priv"), "escaped");
+        assert_eq!(kind("memset(*priv, 0, n); priv->x = 1;", "priv"), "deref");
+        assert_eq!(kind("dev->priv->x = 1;", "priv"), "none");
+        assert_eq!(kind("private->x = 1; if (priv == 0) {}", "priv"), "none");
+    }
+
+    #[test]
+    fn test_is_lvalue() {
+        assert!(is_lvalue("ret"));
+        assert!(is_lvalue("dev->stats.rx_0"));
+        assert!(!is_lvalue("1ret"));
+        assert!(!is_lvalue("a - b"));
+        assert!(!is_lvalue("dev->"));
+        assert!(!is_lvalue(""));
+    }
+
+    #[test]
+    fn test_unchecked_user_copy() {
+        let source = r#"
+static long my_ioctl(struct file *f, unsigned int cmd, unsigned long arg) {
+    struct my_req req;
+    int ret;
+    int val;
+
+    copy_from_user(&req, (void __user *)arg, sizeof(req));
+    ret = copy_to_user((void __user *)arg, &req, sizeof(req));
+    ret = get_user(val, (int __user *)arg);
+    if (ret)
+        return -EFAULT;
+    (void)put_user(val, (int __user *)arg);
+    if (copy_from_user(&req, (void __user *)arg,
+                       sizeof(req)))
+        return -EFAULT;
+    ret |= put_user(0, (int __user *)arg);
+    /* flowsight:ignore - best effort */
+    copy_to_user((void __user *)arg, &req, 1);
+    return ret ? -EFAULT : 0;
+}
+"#;
+        let mut parser = TreeSitterParser::new();
+        let result = parser.parse_source(source, "test.c").unwrap();
+        let found: Vec<(String, u32)> = ErrorChecker::new()
+            .check_user_copies(source, &result.functions)
+            .into_iter()
+            .map(|c| (c.api, c.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("copy_from_user".to_string(), 7),
+                ("copy_to_user".to_string(), 8),
+                ("put_user".to_string(), 12),
+            ]
+        );
+    }
+}
@@ -59,17 +59,62 @@ impl From<&SymbolicValue> for EvalResult {
 pub struct Evaluator {
     /// Variable bindings
     bindings: HashMap<String, SymbolicValue>,
+    /// Named constants (e.g. GFP_KERNEL), consulted after variables
+    constants: HashMap<String, i64>,
 }
 
 impl Evaluator {
     pub fn new() -> Self {
         Self {
             bindings: HashMap::new(),
+            constants: HashMap::new(),
         }
     }
 
     pub fn with_bindings(bindings: HashMap<String, SymbolicValue>) -> Self {
-        Self { bindings }
+        Self {
+            bindings,
+            constants: HashMap::new(),
+        }
+    }
+
+    /// Use a constant table, typically `KnowledgeBase::constants`
+    pub fn with_constants(mut self, constants: HashMap<String, i64>) -> Self {
+        self.constants = constants;
+        self
+    }
+
+    /// Replace the constant table
+    pub fn set_constants(&mut self, constants: HashMap<String, i64>) {
+        self.constants = constants;
+    }
+
+    /// Add object-like macros (`#define MAX_BUF 256`) to the constant table
+    ///
+    /// Each value is evaluated as an expression and may use other macros;
+    /// those that don't come out as integers are left out. Macros override
+    /// constants of the same name.
+    pub fn add_defines(&mut self, defines: &HashMap<String, String>) {
+        let mut pending: Vec<(&String, &String)> = defines.iter().collect();
+        // Resolve in rounds so a macro may use one defined after it
+        loop {
+            let before = pending.len();
+            pending.retain(|(name, value)| match self.eval(value).to_i64() {
+                Some(n) => {
+                    self.constants.insert(name.to_string(), n);
+                    false
+                }
+                None => true,
+            });
+            if pending.is_empty() || pending.len() == before {
+                break;
+            }
+        }
+    }
+
+    /// Get the constant table
+    pub fn constants(&self) -> &HashMap<String, i64> {
+        &self.constants
     }
 
     /// Set a variable binding
@@ -77,6 +122,11 @@ impl Evaluator {
         self.bindings.insert(name.to_string(), value);
     }
 
+    /// Drop all variable bindings, keeping the constant table
+    pub fn clear_bindings(&mut self) {
+        self.bindings.clear();
+    }
+
     /// Get a variable value
     pub fn get(&self, name: &str) -> Option<&SymbolicValue> {
         self.bindings.get(name)
@@ -112,7 +162,7 @@ impl Evaluator {
         // This must be done BEFORE operator parsing to avoid -> being parsed as >
         if self.is_valid_identifier(expr) {
             if let Some(val) = self.lookup_variable(expr) {
-                return EvalResult::from(val);
+                return self.value_result(val);
             }
         }
 
@@ -199,15 +249,15 @@ impl Evaluator {
         }
 
         // Try unary operators
-        if expr.starts_with('!') {
-            let inner = self.eval_expr(&expr[1..]);
+        if let Some(rest) = expr.strip_prefix('!') {
+            let inner = self.eval_expr(rest);
             return match inner.is_truthy() {
                 Some(b) => EvalResult::Bool(!b),
                 None => EvalResult::Unknown,
             };
         }
-        if expr.starts_with('~') {
-            let inner = self.eval_expr(&expr[1..]);
+        if let Some(rest) = expr.strip_prefix('~') {
+            let inner = self.eval_expr(rest);
             return match inner.to_i64() {
                 Some(n) => EvalResult::Integer(!n),
                 None => EvalResult::Unknown,
@@ -234,7 +284,7 @@ impl Evaluator {
         let bytes = expr.as_bytes();
         let op_bytes = op.as_bytes();
 
-        for i in (0..expr.len().saturating_sub(op.len() - 1)).rev() {
+        for i in (0..expr.len()).rev() {
             match bytes[i] {
                 b')' => depth += 1,
                 b'(' => depth -= 1,
@@ -327,7 +377,7 @@ impl Evaluator {
         let bytes = expr.as_bytes();
         let op_bytes = op.as_bytes();
 
-        for i in (0..expr.len().saturating_sub(1)).rev() {
+        for i in (0..expr.len()).rev() {
             match bytes[i] {
                 b')' => depth += 1,
                 b'(' => depth -= 1,
@@ -388,7 +438,7 @@ impl Evaluator {
         let bytes = expr.as_bytes();
         let op_bytes = op.as_bytes();
 
-        for i in (0..expr.len().saturating_sub(op.len() - 1)).rev() {
+        for i in (0..expr.len()).rev() {
             match bytes[i] {
                 b')' => depth += 1,
                 b'(' => depth -= 1,
@@ -500,22 +550,25 @@ impl Evaluator {
             return EvalResult::Bool(false);
         }
 
+        // Integer literals may carry a type suffix (256U, 0x10UL)
+        let number = expr.trim_end_matches(['u', 'U', 'l', 'L']);
+
         // Try hex number
-        if expr.starts_with("0x") || expr.starts_with("0X") {
-            if let Ok(n) = i64::from_str_radix(&expr[2..], 16) {
+        if number.starts_with("0x") || number.starts_with("0X") {
+            if let Ok(n) = i64::from_str_radix(&number[2..], 16) {
                 return EvalResult::Integer(n);
             }
         }
 
         // Try binary number
-        if expr.starts_with("0b") || expr.starts_with("0B") {
-            if let Ok(n) = i64::from_str_radix(&expr[2..], 2) {
+        if number.starts_with("0b") || number.starts_with("0B") {
+            if let Ok(n) = i64::from_str_radix(&number[2..], 2) {
                 return EvalResult::Integer(n);
             }
         }
 
         // Try decimal number
-        if let Ok(n) = expr.parse::<i64>() {
+        if let Ok(n) = number.parse::<i64>() {
             return EvalResult::Integer(n);
         }
 
@@ -527,12 +580,49 @@ impl Evaluator {
         // Try variable lookup
         // Handle member access: id->idVendor, dev.name, etc.
         if let Some(val) = self.lookup_variable(expr) {
-            return EvalResult::from(val);
+            return self.value_result(val);
+        }
+
+        // Try named constant
+        if let Some(n) = self.constants.get(expr) {
+            return EvalResult::Integer(*n);
         }
 
         EvalResult::Unknown
     }
 
+    /// Convert a bound value, decoding flag strings like "GFP_KERNEL | __GFP_ZERO"
+    fn value_result(&self, val: &SymbolicValue) -> EvalResult {
+        if let SymbolicValue::String(s) = val {
+            if let Some(n) = self.decode_flags(s) {
+                return EvalResult::Integer(n);
+            }
+        }
+        EvalResult::from(val)
+    }
+
+    /// OR together `|`-separated constants and integer literals
+    fn decode_flags(&self, s: &str) -> Option<i64> {
+        if self.constants.is_empty() {
+            return None;
+        }
+        s.split('|').try_fold(0i64, |acc, part| {
+            let part = part.trim();
+            let n = match self.constants.get(part) {
+                Some(n) => *n,
+                None => self.parse_int_literal(part)?,
+            };
+            Some(acc | n)
+        })
+    }
+
+    fn parse_int_literal(&self, s: &str) -> Option<i64> {
+        if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            return i64::from_str_radix(hex, 16).ok();
+        }
+        s.parse().ok()
+    }
+
     fn lookup_variable(&self, path: &str) -> Option<&SymbolicValue> {
         // Direct lookup
         if let Some(val) = self.bindings.get(path) {
@@ -633,6 +723,10 @@ mod tests {
         assert_eq!(eval.eval("5 > 3").is_truthy(), Some(true));
         assert_eq!(eval.eval("5 <= 5").is_truthy(), Some(true));
         assert_eq!(eval.eval("5 >= 5").is_truthy(), Some(true));
+        // A parenthesized right operand, as in `cmd == (FOO_IOC_RESET)`
+        assert_eq!(eval.eval("5 == (5)").is_truthy(), Some(true));
+        assert_eq!(eval.eval("1 && (0)").is_truthy(), Some(false));
+        assert_eq!(eval.eval("(1) << (4)").to_i64(), Some(16));
     }
 
     #[test]
@@ -692,4 +786,38 @@ mod tests {
         assert_eq!(eval.eval("a > 0 && b > 0").is_truthy(), Some(true));
         assert_eq!(eval.eval("a == 5 || b == 10").is_truthy(), Some(true));
     }
+
+    #[test]
+    fn test_named_constants() {
+        let constants = HashMap::from([
+            ("GFP_KERNEL".to_string(), 0xcc0),
+            ("__GFP_ZERO".to_string(), 0x100),
+            ("__GFP_HIGHMEM".to_string(), 0x02),
+        ]);
+        let mut eval = Evaluator::new().with_constants(constants);
+        eval.set("flags", SymbolicValue::String("GFP_KERNEL | __GFP_ZERO".into()));
+
+        assert_eq!(eval.eval("GFP_KERNEL").to_i64(), Some(0xcc0));
+        assert_eq!(eval.eval("flags & __GFP_ZERO").to_i64(), Some(0x100));
+        assert_eq!(eval.eval("flags & __GFP_HIGHMEM").is_truthy(), Some(false));
+        assert_eq!(eval.eval("(flags & __GFP_ZERO) != 0").is_truthy(), Some(true));
+        assert!(matches!(eval.eval("UNKNOWN_FLAG"), EvalResult::Unknown));
+    }
+
+    #[test]
+    fn test_defines() {
+        // HDR_LEN uses a macro that only resolves in a later round
+        let defines = HashMap::from([
+            ("HDR_LEN".to_string(), "(MAX_BUF / 4)".to_string()),
+            ("MAX_BUF".to_string(), "256U".to_string()),
+            ("NAME".to_string(), "\"mydev\"".to_string()),
+        ]);
+        let mut eval = Evaluator::new();
+        eval.add_defines(&defines);
+        eval.set("len", SymbolicValue::Integer(300));
+
+        assert_eq!(eval.constants().get("HDR_LEN"), Some(&64));
+        assert!(!eval.constants().contains_key("NAME"));
+        assert_eq!(eval.eval("len > MAX_BUF").is_truthy(), Some(true));
+    }
 }
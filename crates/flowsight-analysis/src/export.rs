@@ -0,0 +1,482 @@
+//! Graph export for flow trees and call graphs
+//!
+//! Renders a `FlowNode` tree as Graphviz DOT, a Mermaid flowchart or a
+//! standalone SVG, and a list of `CallEdge`s or file dependencies as
+//! Graphviz DOT.
+//! When nodes carry a `weight` (see `callgraph::apply_weights`), edges are
+//! drawn thicker in proportion to the callee's execution count. Edge style
+//! reflects the [`CallKind`]: solid direct, dashed indirect, dotted async,
+//! gray macro.
+
+use crate::callgraph::flow_node_call_kind;
+use flowsight_core::{CallEdge, CallKind, FlowNode, FlowNodeType};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Thinnest and thickest edge widths
+const MIN_WIDTH: f64 = 1.0;
+const MAX_WIDTH: f64 = 8.0;
+
+/// SVG layout, in pixels: row height, box height, indent per level,
+/// margin and the width of one monospace character
+const SVG_ROW: usize = 30;
+const SVG_BOX: usize = 22;
+const SVG_INDENT: usize = 28;
+const SVG_MARGIN: usize = 10;
+const SVG_CHAR: usize = 8;
+
+/// Render a flow tree as a Graphviz digraph
+pub fn flow_tree_to_dot(tree: &FlowNode) -> String {
+    let max_weight = max_weight(tree);
+    let mut out = String::new();
+    out.push_str("digraph flow {\n");
+    out.push_str("    rankdir=TB;\n");
+    out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+    let mut next_id = 0;
+    write_dot_node(tree, max_weight, &mut next_id, &mut out);
+
+    out.push_str("}\n");
+    out
+}
+
+fn write_dot_node(node: &FlowNode, max_weight: u64, next_id: &mut usize, out: &mut String) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+
+    let style = if node.is_kernel_internal {
+        ", style=dashed"
+    } else {
+        ""
+    };
+    let _ = writeln!(
+        out,
+        "    n{} [label=\"{}\"{}];",
+        id,
+        escape_dot(&node_label(node)),
+        style
+    );
+
+    for child in &node.children {
+        let child_id = write_dot_node(child, max_weight, next_id, out);
+        let mut attrs = Vec::new();
+        if let Some(w) = child.weight {
+            attrs.push(format!("penwidth={:.1}", edge_width(w, max_weight)));
+        }
+        if let Some(style) = dot_edge_style(flow_node_call_kind(child)) {
+            attrs.push(style.to_string());
+        }
+        write_dot_edge(out, &format!("n{}", id), &format!("n{}", child_id), &attrs);
+    }
+
+    id
+}
+
+fn write_dot_edge(out: &mut String, from: &str, to: &str, attrs: &[String]) {
+    if attrs.is_empty() {
+        let _ = writeln!(out, "    {} -> {};", from, to);
+    } else {
+        let _ = writeln!(out, "    {} -> {} [{}];", from, to, attrs.join(", "));
+    }
+}
+
+fn dot_edge_style(kind: CallKind) -> Option<&'static str> {
+    match kind {
+        CallKind::Direct => None,
+        CallKind::Indirect => Some("style=dashed"),
+        CallKind::Async => Some("style=dotted"),
+        CallKind::Macro => Some("color=gray"),
+    }
+}
+
+/// Render call edges as a Graphviz digraph, one node per function
+pub fn call_edges_to_dot(edges: &[CallEdge]) -> String {
+    let mut out = String::new();
+    out.push_str("digraph calls {\n");
+    out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+    // Stable node ids in first-seen order
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    for edge in edges {
+        for name in [edge.caller.as_str(), edge.callee.as_str()] {
+            if !ids.contains_key(name) {
+                let id = ids.len();
+                ids.insert(name, id);
+                let _ = writeln!(out, "    n{} [label=\"{}()\"];", id, escape_dot(name));
+            }
+        }
+    }
+
+    // Repeated calls share one edge, labelled with every call line
+    let mut merged: Vec<((usize, usize, CallKind), Vec<u32>)> = Vec::new();
+    for edge in edges {
+        let key = (ids[edge.caller.as_str()], ids[edge.callee.as_str()], edge.call_kind);
+        let i = match merged.iter().position(|(k, _)| *k == key) {
+            Some(i) => i,
+            None => {
+                merged.push((key, Vec::new()));
+                merged.len() - 1
+            }
+        };
+        let lines = &mut merged[i].1;
+        if let Some(loc) = &edge.location {
+            if !lines.contains(&loc.line) {
+                lines.push(loc.line);
+            }
+        }
+    }
+    for ((from, to, kind), lines) in merged {
+        let mut attrs: Vec<String> = dot_edge_style(kind).map(|s| vec![s.to_string()]).unwrap_or_default();
+        if !lines.is_empty() {
+            let lines: Vec<String> = lines.iter().map(|l| format!("L{}", l)).collect();
+            attrs.push(format!("label=\"{}\"", lines.join(", ")));
+        }
+        write_dot_edge(&mut out, &format!("n{}", from), &format!("n{}", to), &attrs);
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+/// Render file dependencies `(from, to)` as a Graphviz digraph, one node per file
+///
+/// Files are grouped into one cluster per directory, labelled with it.
+pub fn file_graph_to_dot(edges: &[(String, String)]) -> String {
+    let mut out = String::new();
+    out.push_str("digraph deps {\n");
+    out.push_str("    rankdir=LR;\n");
+    out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+    // Stable node ids in first-seen order, grouped by directory
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    let mut dirs: Vec<(&str, Vec<(usize, &str)>)> = Vec::new();
+    for (from, to) in edges {
+        for file in [from.as_str(), to.as_str()] {
+            if ids.contains_key(file) {
+                continue;
+            }
+            let id = ids.len();
+            ids.insert(file, id);
+            let (dir, name) = file.rsplit_once('/').unwrap_or(("", file));
+            match dirs.iter_mut().find(|(d, _)| *d == dir) {
+                Some((_, files)) => files.push((id, name)),
+                None => dirs.push((dir, vec![(id, name)])),
+            }
+        }
+    }
+    for (i, (dir, files)) in dirs.iter().enumerate() {
+        let indent = if dir.is_empty() { "    " } else { "        " };
+        if !dir.is_empty() {
+            let _ = writeln!(out, "    subgraph cluster_{} {{", i);
+            let _ = writeln!(out, "        label=\"{}\";", escape_dot(dir));
+        }
+        for (id, name) in files {
+            let _ = writeln!(out, "{}n{} [label=\"{}\"];", indent, id, escape_dot(name));
+        }
+        if !dir.is_empty() {
+            out.push_str("    }\n");
+        }
+    }
+
+    for (from, to) in edges {
+        write_dot_edge(&mut out, &format!("n{}", ids[from.as_str()]), &format!("n{}", ids[to.as_str()]), &[]);
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Render a flow tree as a Mermaid flowchart
+pub fn flow_tree_to_mermaid(tree: &FlowNode) -> String {
+    let max_weight = max_weight(tree);
+    let mut out = String::from("flowchart TD\n");
+    let mut next_id = 0;
+    let mut link_styles = Vec::new();
+    let mut link_index = 0;
+    write_mermaid_node(
+        tree,
+        max_weight,
+        &mut next_id,
+        &mut link_index,
+        &mut link_styles,
+        &mut out,
+    );
+    for style in link_styles {
+        out.push_str(&style);
+    }
+    out
+}
+
+fn write_mermaid_node(
+    node: &FlowNode,
+    max_weight: u64,
+    next_id: &mut usize,
+    link_index: &mut usize,
+    link_styles: &mut Vec<String>,
+    out: &mut String,
+) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+    let _ = writeln!(out, "    n{}[\"{}\"]", id, escape_mermaid(&node_label(node)));
+
+    for child in &node.children {
+        let child_id = write_mermaid_node(child, max_weight, next_id, link_index, link_styles, out);
+        let arrow = match flow_node_call_kind(child) {
+            CallKind::Direct => "-->",
+            CallKind::Indirect => "-.->",
+            CallKind::Async => "-.->|async|",
+            CallKind::Macro => "-->|macro|",
+        };
+        let _ = writeln!(out, "    n{} {} n{}", id, arrow, child_id);
+        if let Some(w) = child.weight {
+            link_styles.push(format!(
+                "    linkStyle {} stroke-width:{:.1}px\n",
+                link_index,
+                edge_width(w, max_weight)
+            ));
+        }
+        *link_index += 1;
+    }
+
+    id
+}
+
+/// Render a flow tree as a standalone SVG image
+///
+/// Nodes are laid out as an indented tree, one per row, so the image needs
+/// no layout engine to view. Async callbacks are shaded and kernel-internal
+/// nodes drawn with a dashed border.
+pub fn flow_tree_to_svg(tree: &FlowNode) -> String {
+    // (depth, node, parent row) in drawing order
+    let mut rows: Vec<(usize, &FlowNode, Option<usize>)> = Vec::new();
+    collect_svg_rows(tree, 0, None, &mut rows);
+
+    let max_weight = max_weight(tree);
+    let box_width = |node: &FlowNode| node_label(node).chars().count() * SVG_CHAR + 2 * SVG_CHAR;
+    let x = |depth: usize| SVG_MARGIN + depth * SVG_INDENT;
+    let y = |row: usize| SVG_MARGIN + row * SVG_ROW;
+    let width = rows
+        .iter()
+        .map(|(depth, node, _)| x(*depth) + box_width(node))
+        .max()
+        .unwrap_or(0)
+        + SVG_MARGIN;
+    let height = y(rows.len()) - (SVG_ROW - SVG_BOX) + SVG_MARGIN;
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" \
+         font-family=\"monospace\" font-size=\"13\">",
+        w = width,
+        h = height
+    );
+    for (row, (depth, node, parent)) in rows.iter().enumerate() {
+        let (left, top) = (x(*depth), y(row));
+        if let Some(parent) = parent {
+            // Elbow from below the parent's box to the left edge of this one
+            let from_x = x(rows[*parent].0) + SVG_INDENT / 2;
+            let from_y = y(*parent) + SVG_BOX;
+            let mut attrs = match node.weight {
+                Some(w) => format!(" stroke-width=\"{:.1}\"", edge_width(w, max_weight)),
+                None => String::new(),
+            };
+            attrs.push_str(match flow_node_call_kind(node) {
+                CallKind::Direct => "",
+                CallKind::Indirect => " stroke-dasharray=\"6 3\"",
+                CallKind::Async => " stroke-dasharray=\"2 3\"",
+                CallKind::Macro => " stroke=\"#aaa\"",
+            });
+            let _ = writeln!(
+                out,
+                "  <path d=\"M{} {} V{} H{}\" fill=\"none\" stroke=\"#666\"{}/>",
+                from_x,
+                from_y,
+                top + SVG_BOX / 2,
+                left,
+                attrs
+            );
+        }
+        let fill = match node.node_type {
+            FlowNodeType::AsyncCallback { .. } => "#fdf0d5",
+            FlowNodeType::EntryPoint => "#dcebfa",
+            FlowNodeType::KernelApi | FlowNodeType::External => "#f2f2f2",
+            FlowNodeType::Function => "#ffffff",
+        };
+        let dash = if node.is_kernel_internal { " stroke-dasharray=\"4 2\"" } else { "" };
+        let _ = writeln!(
+            out,
+            "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"4\" fill=\"{}\" stroke=\"#555\"{}/>",
+            left,
+            top,
+            box_width(node),
+            SVG_BOX,
+            fill,
+            dash
+        );
+        let _ = writeln!(
+            out,
+            "  <text x=\"{}\" y=\"{}\">{}</text>",
+            left + SVG_CHAR,
+            top + SVG_BOX - 7,
+            escape_xml(&node_label(node))
+        );
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+fn collect_svg_rows<'a>(
+    node: &'a FlowNode,
+    depth: usize,
+    parent: Option<usize>,
+    rows: &mut Vec<(usize, &'a FlowNode, Option<usize>)>,
+) {
+    let row = rows.len();
+    rows.push((depth, node, parent));
+    for child in &node.children {
+        collect_svg_rows(child, depth + 1, Some(row), rows);
+    }
+}
+
+fn node_label(node: &FlowNode) -> String {
+    match node.weight {
+        Some(w) => format!("{} ({})", node.display_name, w),
+        None => node.display_name.clone(),
+    }
+}
+
+fn max_weight(node: &FlowNode) -> u64 {
+    node.children
+        .iter()
+        .map(max_weight)
+        .fold(node.weight.unwrap_or(0), u64::max)
+}
+
+fn edge_width(weight: u64, max_weight: u64) -> f64 {
+    if max_weight == 0 {
+        return MIN_WIDTH;
+    }
+    MIN_WIDTH + (MAX_WIDTH - MIN_WIDTH) * weight as f64 / max_weight as f64
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_mermaid(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
+
+/// Escape text for XML and HTML content or attribute values
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::callgraph::{apply_weights, parse_weights_csv};
+
+    fn node(name: &str, children: Vec<FlowNode>) -> FlowNode {
+        FlowNode {
+            id: name.into(),
+            name: name.into(),
+            display_name: format!("{}()", name),
+            node_type: FlowNodeType::Function,
+            children,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_weighted_export() {
+        let mut tree = node("probe", vec![node("hot", vec![]), node("cold", vec![]), node("unknown", vec![])]);
+        let weights = parse_weights_csv("function,count\n# perf\nprobe,100\nhot,100\ncold,0\n");
+        apply_weights(&mut tree, &weights);
+
+        assert_eq!(tree.weight, Some(100));
+        assert_eq!(tree.children[2].weight, None);
+
+        let dot = flow_tree_to_dot(&tree);
+        assert!(dot.contains("n0 -> n1 [penwidth=8.0];"), "{}", dot);
+        assert!(dot.contains("n0 -> n2 [penwidth=1.0];"), "{}", dot);
+        assert!(dot.contains("n0 -> n3;"), "{}", dot);
+        assert!(dot.contains("label=\"hot() (100)\""), "{}", dot);
+
+        let mermaid = flow_tree_to_mermaid(&tree);
+        assert!(mermaid.starts_with("flowchart TD\n"));
+        assert!(mermaid.contains("linkStyle 0 stroke-width:8.0px"), "{}", mermaid);
+        assert!(!mermaid.contains("linkStyle 2"), "{}", mermaid);
+    }
+
+    #[test]
+    fn test_file_graph_to_dot() {
+        let edge = |from: &str, to: &str| (from.to_string(), to.to_string());
+        let dot = file_graph_to_dot(&[
+            edge("drivers/foo/main.c", "drivers/foo/hw.c"),
+            edge("drivers/foo/main.c", "include/foo.h"),
+            edge("drivers/foo/hw.c", "util.c"),
+        ]);
+        assert!(dot.starts_with("digraph deps {\n"), "{}", dot);
+        assert!(dot.contains("    subgraph cluster_0 {\n        label=\"drivers/foo\";\n"), "{}", dot);
+        assert!(dot.contains("        n0 [label=\"main.c\"];\n        n1 [label=\"hw.c\"];\n"), "{}", dot);
+        assert!(dot.contains("label=\"include\""), "{}", dot);
+        assert!(dot.contains("\n    n3 [label=\"util.c\"];\n"), "{}", dot);
+        assert!(dot.contains("    n0 -> n1;\n    n0 -> n2;\n    n1 -> n3;\n"), "{}", dot);
+    }
+
+    #[test]
+    fn test_svg_export() {
+        let mut tree = node("probe", vec![node("alloc", vec![]), node("INIT_WORK", vec![node("a<b>", vec![])])]);
+        tree.children[1].node_type = FlowNodeType::AsyncCallback {
+            mechanism: flowsight_core::AsyncMechanism::WorkQueue { delayed: false },
+        };
+        let svg = flow_tree_to_svg(&tree);
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""), "{}", svg);
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<rect").count(), 4);
+        assert_eq!(svg.matches("<path").count(), 3);
+        // Rows are indented by depth; async edges are dotted
+        assert!(svg.contains("<text x=\"74\" y=\"115\">a&lt;b&gt;()</text>"), "{}", svg);
+        assert!(svg.contains("M24 32 V81 H38\" fill=\"none\" stroke=\"#666\" stroke-dasharray=\"2 3\""), "{}", svg);
+        assert!(svg.contains("fill=\"#fdf0d5\""));
+    }
+
+    #[test]
+    fn test_call_kind_styles() {
+        let tree = node("probe", vec![node("helper", vec![]), node("INIT_WORK", vec![])]);
+        let dot = flow_tree_to_dot(&tree);
+        assert!(dot.contains("n0 -> n1;"), "{}", dot);
+        assert!(dot.contains("n0 -> n2 [color=gray];"), "{}", dot);
+        assert!(flow_tree_to_mermaid(&tree).contains("n0 -->|macro| n2"));
+
+        let edge = |callee: &str, call_kind| CallEdge {
+            caller: "probe".into(),
+            callee: callee.into(),
+            location: None,
+            call_type: flowsight_core::CallType::Direct,
+            call_kind,
+        };
+        let dot = call_edges_to_dot(&[
+            edge("helper", CallKind::Direct),
+            edge("my_read", CallKind::Indirect),
+            edge("my_work", CallKind::Async),
+        ]);
+        assert!(dot.contains("n0 -> n1;"), "{}", dot);
+        assert!(dot.contains("n0 -> n2 [style=dashed];"), "{}", dot);
+        assert!(dot.contains("n0 -> n3 [style=dotted];"), "{}", dot);
+
+        // Repeated calls collapse into one edge listing their lines
+        let at = |line| CallEdge {
+            location: Some(flowsight_core::Location::new("test.c", line, 4)),
+            ..edge("writel", CallKind::Direct)
+        };
+        let dot = call_edges_to_dot(&[at(3), at(5)]);
+        assert!(dot.contains("n0 -> n1 [label=\"L3, L5\"];"), "{}", dot);
+        assert_eq!(dot.matches("->").count(), 1, "{}", dot);
+    }
+}
@@ -0,0 +1,472 @@
+//! Checker findings
+//!
+//! A common shape for everything the checkers report, so reporters (text,
+//! JSON, SARIF) don't need to know each checker's own result type.
+
+use crate::atomic_check::{AtomicOrigin, AtomicSleep};
+use crate::control_flow::InfiniteLoop;
+use crate::driver_check::MissingCallback;
+use crate::error_check::{UncheckedAllocation, UncheckedUserCopy};
+use crate::irq_check::{IrqReport, IrqViolation};
+use crate::lock_balance::{Imbalance, UnbalancedLock};
+use flowsight_core::{FunctionDef, Location};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How serious a finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+/// A checker rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    /// Stable id, e.g. `unchecked-result`
+    pub id: &'static str,
+    /// One-line description
+    pub description: &'static str,
+    pub severity: Severity,
+}
+
+/// Failable API result dereferenced without a NULL / IS_ERR check
+pub const UNCHECKED_RESULT: Rule = Rule {
+    id: "unchecked-result",
+    description: "Result of a failable kernel API is dereferenced before it is checked",
+    severity: Severity::Warning,
+};
+
+/// Loop that no break, return or goto can leave
+pub const INFINITE_LOOP: Rule = Rule {
+    id: "infinite-loop",
+    description: "Loop has no break, return or goto out of its body",
+    severity: Severity::Warning,
+};
+
+/// User copy whose bytes-not-copied result is ignored
+pub const UNCHECKED_USER_COPY: Rule = Rule {
+    id: "unchecked-user-copy",
+    description: "Result of copy_to_user/copy_from_user/get_user/put_user is not checked",
+    severity: Severity::Error,
+};
+
+/// Sleeping call reachable from a hard IRQ handler
+pub const IRQ_SLEEP: Rule = Rule {
+    id: "irq-sleep",
+    description: "Function that may sleep is called from a hard IRQ handler",
+    severity: Severity::Error,
+};
+
+/// Recursion reachable from a hard IRQ handler
+pub const IRQ_RECURSION: Rule = Rule {
+    id: "irq-recursion",
+    description: "Hard IRQ handler reaches a recursive call",
+    severity: Severity::Warning,
+};
+
+/// Sleeping call reachable from softirq context or under a spinlock
+pub const ATOMIC_SLEEP: Rule = Rule {
+    id: "atomic-sleep",
+    description: "Function that may sleep is reachable from atomic context",
+    severity: Severity::Warning,
+};
+
+/// Lock still held when a path leaves the function, or released twice
+pub const UNBALANCED_LOCK: Rule = Rule {
+    id: "unbalanced-lock",
+    description: "Lock is not released on every path, or released without being held",
+    severity: Severity::Error,
+};
+
+/// Driver table sets `.probe`/`.open` but not the callback that undoes it
+pub const MISSING_CALLBACK: Rule = Rule {
+    id: "missing-callback",
+    description: "Driver table sets an acquire callback but not its matching release callback",
+    severity: Severity::Warning,
+};
+
+/// Every rule a finding can report
+pub const RULES: &[Rule] = &[
+    UNCHECKED_RESULT,
+    INFINITE_LOOP,
+    UNCHECKED_USER_COPY,
+    IRQ_SLEEP,
+    IRQ_RECURSION,
+    ATOMIC_SLEEP,
+    UNBALANCED_LOCK,
+    MISSING_CALLBACK,
+];
+
+/// A problem reported by one of the checkers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Finding {
+    UncheckedResult {
+        file: String,
+        #[serde(flatten)]
+        allocation: UncheckedAllocation,
+    },
+    InfiniteLoop {
+        file: String,
+        #[serde(flatten)]
+        infinite_loop: InfiniteLoop,
+    },
+    UncheckedUserCopy {
+        file: String,
+        #[serde(flatten)]
+        copy: UncheckedUserCopy,
+    },
+    /// `path` (handler first) calls the sleeping `api` at `file`:`line`
+    IrqSleep {
+        file: String,
+        line: u32,
+        handler: String,
+        api: String,
+        path: Vec<String>,
+    },
+    /// `path` (handler first) calls back into its last entry at `file`:`line`
+    IrqRecursion {
+        file: String,
+        line: u32,
+        handler: String,
+        path: Vec<String>,
+    },
+    AtomicSleep {
+        file: String,
+        #[serde(flatten)]
+        sleep: AtomicSleep,
+    },
+    UnbalancedLock {
+        file: String,
+        #[serde(flatten)]
+        unbalanced: UnbalancedLock,
+    },
+    MissingCallback {
+        file: String,
+        #[serde(flatten)]
+        missing: MissingCallback,
+    },
+}
+
+impl Finding {
+    /// Wrap error-path checker results for `file`
+    pub fn from_unchecked(file: &str, allocations: Vec<UncheckedAllocation>) -> Vec<Finding> {
+        allocations
+            .into_iter()
+            .map(|allocation| Finding::UncheckedResult {
+                file: file.to_string(),
+                allocation,
+            })
+            .collect()
+    }
+
+    /// Wrap control-flow checker results for `file`
+    pub fn from_infinite_loops(file: &str, loops: Vec<InfiniteLoop>) -> Vec<Finding> {
+        loops
+            .into_iter()
+            .map(|infinite_loop| Finding::InfiniteLoop {
+                file: file.to_string(),
+                infinite_loop,
+            })
+            .collect()
+    }
+
+    /// Wrap lock balance checker results for `file`
+    pub fn from_unbalanced_locks(file: &str, locks: Vec<UnbalancedLock>) -> Vec<Finding> {
+        locks
+            .into_iter()
+            .map(|unbalanced| Finding::UnbalancedLock {
+                file: file.to_string(),
+                unbalanced,
+            })
+            .collect()
+    }
+
+    /// Wrap unchecked user copies for `file`
+    pub fn from_user_copies(file: &str, copies: Vec<UncheckedUserCopy>) -> Vec<Finding> {
+        copies
+            .into_iter()
+            .map(|copy| Finding::UncheckedUserCopy {
+                file: file.to_string(),
+                copy,
+            })
+            .collect()
+    }
+
+    /// Wrap driver callback pairing results for `file`
+    pub fn from_missing_callbacks(file: &str, missing: Vec<MissingCallback>) -> Vec<Finding> {
+        missing
+            .into_iter()
+            .map(|missing| Finding::MissingCallback {
+                file: file.to_string(),
+                missing,
+            })
+            .collect()
+    }
+
+    /// Wrap IRQ checker results for handlers registered in `file`
+    ///
+    /// Each finding points at the function on the path where the offending
+    /// call is made, which may be in another file of `functions`.
+    pub fn from_irq_reports(
+        file: &str,
+        reports: Vec<IrqReport>,
+        functions: &HashMap<String, FunctionDef>,
+    ) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for report in reports {
+            for violation in report.violations {
+                let caller = match &violation {
+                    IrqViolation::SleepingCall { path, .. } => path.last(),
+                    IrqViolation::Recursion { path } => path.iter().rev().nth(1),
+                };
+                let caller = caller.and_then(|name| functions.get(name)).and_then(|f| f.location.as_ref());
+                let (site_file, site_line) = match caller {
+                    Some(loc) => (loc.file.clone(), loc.line),
+                    None => (file.to_string(), report.registered_at.unwrap_or(0)),
+                };
+                findings.push(match violation {
+                    IrqViolation::SleepingCall { api, path, line } => Finding::IrqSleep {
+                        file: site_file,
+                        line: line.unwrap_or(site_line),
+                        handler: report.handler.clone(),
+                        api,
+                        path,
+                    },
+                    IrqViolation::Recursion { path } => Finding::IrqRecursion {
+                        file: site_file,
+                        line: site_line,
+                        handler: report.handler.clone(),
+                        path,
+                    },
+                });
+            }
+        }
+        findings
+    }
+
+    /// Wrap atomic-context checker results, each in the file defining its
+    /// function (`file` if that is unknown)
+    pub fn from_atomic_sleeps(
+        file: &str,
+        sleeps: Vec<AtomicSleep>,
+        functions: &HashMap<String, FunctionDef>,
+    ) -> Vec<Finding> {
+        sleeps
+            .into_iter()
+            .map(|sleep| Finding::AtomicSleep {
+                file: functions
+                    .get(&sleep.function)
+                    .and_then(|f| f.location.as_ref())
+                    .map_or_else(|| file.to_string(), |loc| loc.file.clone()),
+                sleep,
+            })
+            .collect()
+    }
+
+    /// Function the finding is in
+    pub fn function(&self) -> &str {
+        match self {
+            Finding::UncheckedResult { allocation, .. } => &allocation.function,
+            Finding::InfiniteLoop { infinite_loop, .. } => &infinite_loop.function,
+            Finding::UnbalancedLock { unbalanced, .. } => &unbalanced.function,
+            Finding::UncheckedUserCopy { copy, .. } => &copy.function,
+            Finding::IrqSleep { path, handler, .. } => path.last().unwrap_or(handler),
+            Finding::IrqRecursion { path, handler, .. } => path.iter().rev().nth(1).unwrap_or(handler),
+            Finding::AtomicSleep { sleep, .. } => &sleep.function,
+            Finding::MissingCallback { missing, .. } => &missing.function,
+        }
+    }
+
+    /// Rule this finding violates
+    pub fn rule(&self) -> &'static Rule {
+        match self {
+            Finding::UncheckedResult { .. } => &UNCHECKED_RESULT,
+            Finding::InfiniteLoop { .. } => &INFINITE_LOOP,
+            Finding::UncheckedUserCopy { .. } => &UNCHECKED_USER_COPY,
+            Finding::IrqSleep { .. } => &IRQ_SLEEP,
+            Finding::IrqRecursion { .. } => &IRQ_RECURSION,
+            Finding::AtomicSleep { .. } => &ATOMIC_SLEEP,
+            Finding::UnbalancedLock { .. } => &UNBALANCED_LOCK,
+            Finding::MissingCallback { .. } => &MISSING_CALLBACK,
+        }
+    }
+
+    /// Human-readable description
+    pub fn message(&self) -> String {
+        match self {
+            Finding::UncheckedResult { allocation: a, .. } => format!(
+                "{}: result of {}() in `{}` is dereferenced at line {} without a check",
+                a.function, a.api, a.variable, a.use_line
+            ),
+            Finding::InfiniteLoop { infinite_loop: l, .. } => {
+                format!("{}: loop never exits", l.function)
+            }
+            Finding::UncheckedUserCopy { copy: c, .. } => format!(
+                "{}: result of {}() is not checked; a non-zero value means the copy failed",
+                c.function, c.api
+            ),
+            Finding::IrqSleep { handler, api, path, .. } => format!(
+                "{}: IRQ handler calls {}() which may sleep: {} → {}()",
+                handler,
+                api,
+                path.join(" → "),
+                api
+            ),
+            Finding::IrqRecursion { handler, path, .. } => {
+                format!("{}: IRQ handler recurses: {}", handler, path.join(" → "))
+            }
+            Finding::AtomicSleep { sleep: s, .. } => {
+                let atomic = match &s.origin {
+                    AtomicOrigin::Entry { context } => format!("{:?} context", context),
+                    AtomicOrigin::LockHeld { lock, .. } => format!("`{}` held", lock),
+                };
+                let mut message = format!(
+                    "{}: calls {}() which may sleep, but is reached with {}: {} → {}()",
+                    s.function,
+                    s.api,
+                    atomic,
+                    s.path.join(" → "),
+                    s.api
+                );
+                if let Some(path) = &s.sleepable_path {
+                    message.push_str(&format!(" (also called where sleeping is fine: {})", path.join(" → ")));
+                }
+                message
+            }
+            Finding::UnbalancedLock { unbalanced: u, .. } => match u.imbalance {
+                Imbalance::MissingUnlock => format!(
+                    "{}: `{}` locked here is still held when the function exits at line {}",
+                    u.function,
+                    u.lock,
+                    u.missing_unlock_path.last().copied().unwrap_or(u.lock_site)
+                ),
+                Imbalance::UnlockWithoutLock => {
+                    format!("{}: `{}` is unlocked here on a path that does not hold it", u.function, u.lock)
+                }
+            },
+            Finding::MissingCallback { missing: m, .. } => format!(
+                "{}: {} `{}` sets .{} but no .{}",
+                m.function, m.framework, m.variable, m.paired_with, m.callback
+            ),
+        }
+    }
+
+    /// Canned advice on how to fix the finding, if the rule has any
+    pub fn suggestion(&self) -> Option<String> {
+        let suggestion = match self {
+            Finding::UncheckedResult { allocation: a, .. } if returns_err_ptr(&a.api) => format!(
+                "add `if (IS_ERR({v})) return PTR_ERR({v});` after the call to {}()",
+                a.api,
+                v = a.variable
+            ),
+            Finding::UncheckedResult { allocation: a, .. } => format!(
+                "add `if (!{}) return -ENOMEM;` after the call to {}()",
+                a.variable, a.api
+            ),
+            Finding::InfiniteLoop { .. } => {
+                "add a break or return on the exit condition, or bound the loop with a timeout".to_string()
+            }
+            Finding::UncheckedUserCopy { copy: c, .. } => {
+                format!("return -EFAULT when {}() returns non-zero", c.api)
+            }
+            Finding::IrqSleep { api, .. } if api.contains("alloc") => {
+                format!("use GFP_ATOMIC instead of GFP_KERNEL for {}() in hard IRQ context", api)
+            }
+            Finding::IrqSleep { api, .. } => format!(
+                "move the {}() call to a threaded handler (request_threaded_irq) or a workqueue",
+                api
+            ),
+            Finding::IrqRecursion { .. } => {
+                "break the recursion or defer the work out of the IRQ handler".to_string()
+            }
+            Finding::AtomicSleep { sleep: s, .. } => match &s.origin {
+                AtomicOrigin::LockHeld { lock, .. } if s.path.len() == 1 => {
+                    format!("call {}() after releasing `{}`, or use a mutex instead", s.api, lock)
+                }
+                AtomicOrigin::LockHeld { lock, .. } => format!(
+                    "do not call {}() while holding `{}`, or give it a variant that does not sleep",
+                    s.path[1], lock
+                ),
+                AtomicOrigin::Entry { .. } => format!(
+                    "defer the {}() call to a workqueue, or give {}() a variant for atomic callers",
+                    s.api, s.function
+                ),
+            },
+            Finding::UnbalancedLock { unbalanced: u, .. } => match u.imbalance {
+                Imbalance::MissingUnlock => {
+                    format!("release `{}` before returning, e.g. via a common unlock label", u.lock)
+                }
+                Imbalance::UnlockWithoutLock => {
+                    format!("drop the extra unlock or take `{}` on this path first", u.lock)
+                }
+            },
+            Finding::MissingCallback { missing: m, .. } => format!(
+                "add `.{} = ...` to {} that undoes what {}() sets up",
+                m.callback, m.variable, m.function
+            ),
+        };
+        Some(suggestion)
+    }
+
+    /// Where the finding should be fixed
+    pub fn location(&self) -> Location {
+        match self {
+            Finding::UncheckedResult { file, allocation } => Location::new(file.as_str(), allocation.line, 0),
+            Finding::InfiniteLoop { file, infinite_loop } => Location::new(file.as_str(), infinite_loop.line, 0),
+            Finding::UncheckedUserCopy { file, copy } => Location::new(file.as_str(), copy.line, 0),
+            Finding::IrqSleep { file, line, .. } | Finding::IrqRecursion { file, line, .. } => {
+                Location::new(file.as_str(), *line, 0)
+            }
+            Finding::AtomicSleep { file, sleep } => Location::new(file.as_str(), sleep.line, 0),
+            Finding::UnbalancedLock { file, unbalanced } => Location::new(file.as_str(), unbalanced.lock_site, 0),
+            Finding::MissingCallback { file, missing } => Location::new(file.as_str(), missing.line, 0),
+        }
+    }
+
+    /// Other places involved, e.g. the unchecked dereference
+    pub fn related_locations(&self) -> Vec<(Location, String)> {
+        match self {
+            Finding::UncheckedResult { file, allocation } => vec![(
+                Location::new(file.as_str(), allocation.use_line, 0),
+                format!("`{}` dereferenced here", allocation.variable),
+            )],
+            Finding::UnbalancedLock { file, unbalanced } => unbalanced
+                .missing_unlock_path
+                .iter()
+                .filter(|&&line| line != unbalanced.lock_site)
+                .map(|&line| {
+                    let note = match unbalanced.missing_unlock_path.last() == Some(&line) {
+                        true => format!("leaves the function holding `{}`", unbalanced.lock),
+                        false => "path jumps here".to_string(),
+                    };
+                    (Location::new(file.as_str(), line, 0), note)
+                })
+                .collect(),
+            Finding::AtomicSleep { file, sleep } => match &sleep.origin {
+                AtomicOrigin::LockHeld { lock, line } if sleep.path.len() == 1 => {
+                    vec![(Location::new(file.as_str(), *line, 0), format!("`{}` taken here", lock))]
+                }
+                _ => Vec::new(),
+            },
+            Finding::InfiniteLoop { .. }
+            | Finding::UncheckedUserCopy { .. }
+            | Finding::IrqSleep { .. }
+            | Finding::IrqRecursion { .. }
+            | Finding::MissingCallback { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Whether `api` reports failure with an ERR_PTR rather than NULL
+fn returns_err_ptr(api: &str) -> bool {
+    api.ends_with("_get")
+        || api.ends_with("ioremap_resource")
+        || matches!(
+            api,
+            "kthread_create" | "kthread_run" | "filp_open" | "class_create" | "device_create" | "memdup_user"
+        )
+}
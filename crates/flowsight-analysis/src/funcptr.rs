@@ -5,7 +5,7 @@
 //! - Direct variable assignments
 //! - Callback registration patterns
 
-use flowsight_core::FunctionDef;
+use flowsight_core::{FunctionDef, Location, OpsAssignment};
 use regex::Regex;
 use std::collections::HashMap;
 
@@ -28,6 +28,9 @@ struct OpsTablePattern {
 pub struct FuncPtrBinding {
     /// The ops table or variable name
     pub source: String,
+    /// Struct type of the ops table (e.g. "file_operations"), `None` for
+    /// direct assignments
+    pub struct_type: Option<String>,
     /// The field or context
     pub field: String,
     /// The resolved function name
@@ -142,7 +145,20 @@ impl FuncPtrResolver {
         source: &str,
         functions: &HashMap<String, FunctionDef>,
     ) -> Vec<(String, String)> {
-        let mut mappings = Vec::new();
+        self.ops_table_entries(source, functions)
+            .into_iter()
+            .map(|(_, var_name, field, func_name)| (format!("{}.{}", var_name, field), func_name))
+            .collect()
+    }
+
+    /// Known callback fields of known ops tables as
+    /// `(struct type, table variable, field, function)`
+    fn ops_table_entries(
+        &self,
+        source: &str,
+        functions: &HashMap<String, FunctionDef>,
+    ) -> Vec<(String, String, String, String)> {
+        let mut entries = Vec::new();
 
         // Pattern to match struct initializers like:
         // static struct file_operations my_fops = {
@@ -176,15 +192,55 @@ impl FuncPtrResolver {
                         if pattern.field_mappings.contains_key(field)
                             && functions.contains_key(func_name)
                         {
-                            let context = format!("{}.{}", var_name, field);
-                            mappings.push((context, func_name.to_string()));
+                            entries.push((
+                                struct_type.to_string(),
+                                var_name.to_string(),
+                                field.to_string(),
+                                func_name.to_string(),
+                            ));
                         }
                     }
                 }
             }
         }
 
-        mappings
+        entries
+    }
+
+    /// Collect every `.field = identifier` of every static struct initializer
+    ///
+    /// Unlike [`Self::analyze_ops_tables`] this is not limited to known ops
+    /// types or to functions defined in `source`, so the result can be
+    /// indexed and matched against functions from other files.
+    pub fn find_ops_assignments(&self, source: &str, file: &str) -> Vec<OpsAssignment> {
+        let struct_init_re =
+            Regex::new(r"(?s)static\s+(?:const\s+)?struct\s+(\w+)\s+(\w+)\s*=\s*\{([^}]+)\}")
+                .unwrap();
+        let field_assign_re = Regex::new(r"\.(\w+)\s*=\s*&?(\w+)").unwrap();
+
+        let mut assignments = Vec::new();
+        for caps in struct_init_re.captures_iter(source) {
+            let (Some(ops_type), Some(variable), Some(body)) = (caps.get(1), caps.get(2), caps.get(3))
+            else {
+                continue;
+            };
+            for field_caps in field_assign_re.captures_iter(body.as_str()) {
+                let (Some(field), Some(function)) = (field_caps.get(1), field_caps.get(2)) else {
+                    continue;
+                };
+                let offset = body.start() + field.start() - 1;
+                let line = source[..offset].matches('\n').count() as u32 + 1;
+                let column = (offset - source[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0)) as u32;
+                assignments.push(OpsAssignment {
+                    ops_type: ops_type.as_str().to_string(),
+                    variable: variable.as_str().to_string(),
+                    field: field.as_str().to_string(),
+                    function: function.as_str().to_string(),
+                    location: Some(Location::new(file, line, column)),
+                });
+            }
+        }
+        assignments
     }
 
     /// Analyze direct function pointer assignments
@@ -206,11 +262,12 @@ impl FuncPtrResolver {
             if functions.contains_key(func_name) {
                 // Look for patterns that suggest function pointer assignment
                 if target.contains(".") || target.contains("->") {
-                    let parts: Vec<&str> = target.split(|c| c == '.' || c == '>').collect();
+                    let parts: Vec<&str> = target.split(['.', '>']).collect();
                     if let Some(field) = parts.last() {
                         if Self::looks_like_callback_field(field) {
                             bindings.push(FuncPtrBinding {
                                 source: parts[..parts.len() - 1].join("."),
+                                struct_type: None,
                                 field: field.to_string(),
                                 function: func_name.to_string(),
                                 confidence: Confidence::Medium,
@@ -244,11 +301,11 @@ impl FuncPtrResolver {
         let mut bindings = Vec::new();
 
         // Get ops table bindings (high confidence)
-        for (context, func_name) in self.analyze_ops_tables(source, functions) {
-            let parts: Vec<&str> = context.split('.').collect();
+        for (struct_type, var_name, field, func_name) in self.ops_table_entries(source, functions) {
             bindings.push(FuncPtrBinding {
-                source: parts.get(0).unwrap_or(&"").to_string(),
-                field: parts.get(1).unwrap_or(&"").to_string(),
+                source: var_name,
+                struct_type: Some(struct_type),
+                field,
                 function: func_name,
                 confidence: Confidence::High,
             });
@@ -270,19 +327,13 @@ impl Default for FuncPtrResolver {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use flowsight_core::Location;
 
     fn make_func(name: &str) -> FunctionDef {
         FunctionDef {
             name: name.to_string(),
             return_type: "int".to_string(),
-            params: vec![],
             location: Some(Location::new("test.c", 1, 0)),
-            calls: vec![],
-            called_by: vec![],
-            is_callback: false,
-            callback_context: None,
-            attributes: vec![],
+            ..Default::default()
         }
     }
 
@@ -334,4 +385,24 @@ static struct usb_driver my_driver = {
 
         assert_eq!(mappings.len(), 2);
     }
+
+    #[test]
+    fn test_find_ops_assignments() {
+        let source = r#"
+static const struct file_operations my_fops = {
+    .owner = THIS_MODULE,
+    .read = my_read,
+};
+"#;
+        let resolver = FuncPtrResolver::new();
+        let assignments = resolver.find_ops_assignments(source, "drv.c");
+
+        assert_eq!(assignments.len(), 2);
+        let read = &assignments[1];
+        assert_eq!(read.ops_type, "file_operations");
+        assert_eq!(read.variable, "my_fops");
+        assert_eq!(read.field, "read");
+        assert_eq!(read.function, "my_read");
+        assert_eq!(read.location, Some(Location::new("drv.c", 4, 4)));
+    }
 }
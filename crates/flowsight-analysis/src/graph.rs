@@ -0,0 +1,270 @@
+//! In-memory call graph for graph algorithms
+//!
+//! Reachability, recursion (strongly connected components), dominators and
+//! shortest call paths all run on one [`CallGraph`] built from `CallEdge`s.
+//! Functions are nodes of a petgraph [`DiGraph`]; the API only speaks in
+//! function names.
+//!
+//! Results are ordered by name (after call distance where there is one), so
+//! every traversal, and every tie between equally short paths, comes out the
+//! same from run to run.
+
+use flowsight_core::CallEdge;
+use petgraph::algo::{dijkstra, dominators, tarjan_scc};
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::Reversed;
+use petgraph::Direction;
+use std::collections::HashMap;
+
+/// Directed call graph over function names
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    graph: DiGraph<String, ()>,
+    ids: HashMap<String, NodeIndex>,
+}
+
+impl CallGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Graph of `edges`; repeated caller/callee pairs become one edge
+    pub fn from_edges(edges: &[CallEdge]) -> Self {
+        let mut graph = Self::new();
+        for edge in edges {
+            graph.add_edge(&edge.caller, &edge.callee);
+        }
+        graph
+    }
+
+    /// Add a function without any calls
+    pub fn add_function(&mut self, name: &str) {
+        self.intern(name);
+    }
+
+    /// Add a call from `caller` to `callee`, adding either function if new
+    pub fn add_edge(&mut self, caller: &str, callee: &str) {
+        let (from, to) = (self.intern(caller), self.intern(callee));
+        self.graph.update_edge(from, to, ());
+    }
+
+    /// Number of functions
+    pub fn len(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.graph.node_count() == 0
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.ids.contains_key(name)
+    }
+
+    /// Functions `name` calls directly, sorted by name
+    pub fn callees(&self, name: &str) -> Vec<&str> {
+        self.neighbours(name, Direction::Outgoing)
+    }
+
+    /// Functions calling `name` directly, sorted by name
+    pub fn callers(&self, name: &str) -> Vec<&str> {
+        self.neighbours(name, Direction::Incoming)
+    }
+
+    /// Every function reachable from `name` through calls, `name` first,
+    /// then by call distance and name
+    pub fn reachable_from(&self, name: &str) -> Vec<&str> {
+        let Some(&start) = self.ids.get(name) else {
+            return Vec::new();
+        };
+        self.by_distance(dijkstra(&self.graph, start, None, |_| 1usize))
+    }
+
+    /// Every function that can reach `name` through calls, `name` first,
+    /// then by call distance and name
+    pub fn reaching(&self, name: &str) -> Vec<&str> {
+        let Some(&start) = self.ids.get(name) else {
+            return Vec::new();
+        };
+        self.by_distance(dijkstra(Reversed(&self.graph), start, None, |_| 1usize))
+    }
+
+    /// Fewest-calls path from `from` to `to`, both included
+    ///
+    /// Among equally short paths the one through alphabetically earlier
+    /// functions wins. `None` if `to` can't be reached.
+    pub fn shortest_path(&self, from: &str, to: &str) -> Option<Vec<&str>> {
+        let (&start, &goal) = (self.ids.get(from)?, self.ids.get(to)?);
+        // Calls left to `to` from every function that can reach it; walking
+        // forward through the earliest callee one call closer picks the path
+        let remaining = dijkstra(Reversed(&self.graph), goal, Some(start), |_| 1usize);
+        let mut left = *remaining.get(&start)?;
+        let mut path = vec![self.graph[start].as_str()];
+        let mut current = start;
+        while current != goal {
+            left -= 1;
+            current = self
+                .graph
+                .neighbors_directed(current, Direction::Outgoing)
+                .filter(|next| remaining.get(next) == Some(&left))
+                .min_by(|a, b| self.graph[*a].cmp(&self.graph[*b]))?;
+            path.push(self.graph[current].as_str());
+        }
+        Some(path)
+    }
+
+    /// Strongly connected components (Tarjan), each sorted by name
+    ///
+    /// Components come callees first: a component is listed before any
+    /// component that calls into it. A component of more than one function,
+    /// or of one that calls itself, is a recursion.
+    pub fn sccs(&self) -> Vec<Vec<&str>> {
+        tarjan_scc(&self.graph)
+            .into_iter()
+            .map(|component| {
+                let mut names: Vec<&str> = component.into_iter().map(|id| self.graph[id].as_str()).collect();
+                names.sort_unstable();
+                names
+            })
+            .collect()
+    }
+
+    /// Whether `name` can end up calling itself
+    pub fn is_recursive(&self, name: &str) -> bool {
+        self.callees(name)
+            .into_iter()
+            .any(|callee| self.reachable_from(callee).contains(&name))
+    }
+
+    /// Immediate dominator of every function reachable from `root`
+    ///
+    /// Every call path from `root` to a function passes through its
+    /// dominator. `root` itself has none and is left out.
+    pub fn dominators(&self, root: &str) -> HashMap<&str, &str> {
+        let Some(&root) = self.ids.get(root) else {
+            return HashMap::new();
+        };
+        let dominators = dominators::simple_fast(&self.graph, root);
+        self.graph
+            .node_indices()
+            .filter_map(|node| {
+                let idom = dominators.immediate_dominator(node)?;
+                Some((self.graph[node].as_str(), self.graph[idom].as_str()))
+            })
+            .collect()
+    }
+
+    fn intern(&mut self, name: &str) -> NodeIndex {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.graph.add_node(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        id
+    }
+
+    fn neighbours(&self, name: &str, direction: Direction) -> Vec<&str> {
+        let Some(&id) = self.ids.get(name) else {
+            return Vec::new();
+        };
+        let mut names: Vec<&str> = self
+            .graph
+            .neighbors_directed(id, direction)
+            .map(|next| self.graph[next].as_str())
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Names of the nodes in `distances`, nearest first, ties by name
+    fn by_distance(&self, distances: HashMap<NodeIndex, usize>) -> Vec<&str> {
+        let mut nodes: Vec<(usize, &str)> = distances
+            .into_iter()
+            .map(|(id, distance)| (distance, self.graph[id].as_str()))
+            .collect();
+        nodes.sort_unstable();
+        nodes.into_iter().map(|(_, name)| name).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(&str, &str)]) -> CallGraph {
+        let mut graph = CallGraph::new();
+        for (caller, callee) in edges {
+            graph.add_edge(caller, callee);
+        }
+        graph
+    }
+
+    #[test]
+    fn test_reachability_and_paths() {
+        // probe -> setup -> hw_init -> write_reg
+        //       \-> alloc ---------------/
+        let g = graph(&[
+            ("probe", "setup"),
+            ("probe", "alloc"),
+            ("setup", "hw_init"),
+            ("hw_init", "write_reg"),
+            ("alloc", "write_reg"),
+            ("probe", "setup"),
+            ("remove", "write_reg"),
+        ]);
+        assert_eq!(g.len(), 6);
+        assert_eq!(g.callees("probe"), vec!["alloc", "setup"]);
+        assert_eq!(g.callers("write_reg"), vec!["alloc", "hw_init", "remove"]);
+        assert_eq!(g.reachable_from("probe"), vec!["probe", "alloc", "setup", "hw_init", "write_reg"]);
+        assert_eq!(g.reaching("hw_init"), vec!["hw_init", "setup", "probe"]);
+        assert!(g.reachable_from("missing").is_empty());
+
+        assert_eq!(g.shortest_path("probe", "write_reg"), Some(vec!["probe", "alloc", "write_reg"]));
+        assert_eq!(g.shortest_path("probe", "probe"), Some(vec!["probe"]));
+        assert_eq!(g.shortest_path("remove", "probe"), None);
+    }
+
+    #[test]
+    fn test_sccs() {
+        let g = graph(&[
+            ("main", "a"),
+            ("a", "b"),
+            ("b", "a"),
+            ("b", "leaf"),
+            ("walk", "walk"),
+        ]);
+        let sccs = g.sccs();
+        let position = |name: &str| sccs.iter().position(|c| c.contains(&name)).unwrap();
+        assert!(sccs.contains(&vec!["a", "b"]));
+        assert!(position("leaf") < position("a"));
+        assert!(position("a") < position("main"));
+        assert_eq!(sccs.len(), 4);
+
+        assert!(g.is_recursive("a"));
+        assert!(g.is_recursive("walk"));
+        assert!(!g.is_recursive("main"));
+        assert!(!g.is_recursive("leaf"));
+    }
+
+    #[test]
+    fn test_dominators() {
+        // entry -> check -> {fast, slow} -> done; entry -> log
+        let g = graph(&[
+            ("entry", "check"),
+            ("entry", "log"),
+            ("check", "fast"),
+            ("check", "slow"),
+            ("fast", "done"),
+            ("slow", "done"),
+            ("other", "done"),
+        ]);
+        let idom = g.dominators("entry");
+        assert_eq!(idom.len(), 5);
+        assert_eq!(idom["check"], "entry");
+        assert_eq!(idom["log"], "entry");
+        assert_eq!(idom["fast"], "check");
+        assert_eq!(idom["done"], "check");
+        assert!(!idom.contains_key("other"));
+        assert!(!idom.contains_key("entry"));
+    }
+}
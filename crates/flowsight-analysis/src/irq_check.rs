@@ -0,0 +1,438 @@
+//! Interrupt handler checking
+//!
+//! Hard IRQ handlers registered with `request_irq` / `devm_request_irq` run
+//! in atomic context: nothing they reach may sleep, and they must not recurse
+//! since the IRQ stack is small. DMA completion callbacks run from the
+//! controller's tasklet and are held to the same rules. The checker walks
+//! every function reachable from each handler and reports the call path to
+//! each violation.
+//!
+//! Sleeping APIs are the knowledge base entries marked `can_sleep`; a call
+//! passing `GFP_ATOMIC` or `GFP_NOWAIT` anywhere in its arguments is not
+//! counted, and a function calling such an API several times is reported at
+//! its first call without them.
+//!
+//! The functions walked may come from other files (e.g. a project index);
+//! their bodies are then read from disk where a call must be inspected.
+
+use crate::async_tracker::{AsyncTracker, DMA_CALLBACK};
+use flowsight_core::{AsyncBinding, AsyncMechanism, FunctionDef};
+use flowsight_knowledge::KnowledgeBase;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Result of checking one interrupt handler
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IrqReport {
+    /// Handler function
+    pub handler: String,
+    /// Line of the `request_irq` call (1-based)
+    pub registered_at: Option<u32>,
+    pub violations: Vec<IrqViolation>,
+}
+
+impl IrqReport {
+    /// Whether nothing reachable from the handler is a problem
+    pub fn is_clean(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// Something an interrupt handler must not do
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IrqViolation {
+    /// `path` (handler first) ends in a call to the sleeping `api`
+    SleepingCall {
+        api: String,
+        path: Vec<String>,
+        /// Line of the call in the last function of `path`
+        line: Option<u32>,
+    },
+    /// `path` calls back into a function already on it (its last entry)
+    Recursion { path: Vec<String> },
+}
+
+/// Checker for hard IRQ handlers
+pub struct IrqChecker {
+    tracker: AsyncTracker,
+    /// Only report violations whose call path runs through one of these
+    focus: Option<HashSet<String>>,
+}
+
+impl IrqChecker {
+    pub fn new() -> Self {
+        Self {
+            tracker: AsyncTracker::new(),
+            focus: None,
+        }
+    }
+
+    /// Only report violations whose call path runs through `functions`
+    /// (e.g. the ones touched by a change); other handlers are left out
+    pub fn with_focus(mut self, functions: HashSet<String>) -> Self {
+        self.focus = Some(functions);
+        self
+    }
+
+    /// Check every hard IRQ handler registered in `source`
+    ///
+    /// All of `functions` are taken to be defined in `source`.
+    pub fn check(
+        &self,
+        source: &str,
+        functions: &HashMap<String, FunctionDef>,
+        kb: &KnowledgeBase,
+    ) -> Vec<IrqReport> {
+        let lines: Vec<&str> = source.lines().collect();
+        let bindings = self.tracker.analyze(source, functions);
+        self.check_handlers(bindings, Bodies::Source(&lines), functions, kb)
+    }
+
+    /// Check every hard IRQ handler registered in `source`, the contents of
+    /// `file`, walking `functions` from any file
+    pub fn check_file(
+        &self,
+        file: &str,
+        source: &str,
+        functions: &HashMap<String, FunctionDef>,
+        kb: &KnowledgeBase,
+    ) -> Vec<IrqReport> {
+        let lines: Vec<&str> = source.lines().collect();
+        let bindings = self.tracker.analyze(source, functions);
+        self.check_handlers(bindings, Bodies::File(file, &lines), functions, kb)
+    }
+
+    /// Check the hard IRQ handlers of `bindings` found elsewhere (e.g. in an
+    /// index); function bodies are read from disk
+    pub fn check_bindings(
+        &self,
+        bindings: Vec<AsyncBinding>,
+        functions: &HashMap<String, FunctionDef>,
+        kb: &KnowledgeBase,
+    ) -> Vec<IrqReport> {
+        self.check_handlers(bindings, Bodies::File("", &[]), functions, kb)
+    }
+
+    fn check_handlers(
+        &self,
+        bindings: Vec<AsyncBinding>,
+        bodies: Bodies,
+        functions: &HashMap<String, FunctionDef>,
+        kb: &KnowledgeBase,
+    ) -> Vec<IrqReport> {
+        let mut walk = Walk {
+            functions,
+            kb,
+            bodies,
+            other_files: HashMap::new(),
+            path: Vec::new(),
+            done: HashSet::new(),
+            violations: Vec::new(),
+        };
+        let mut reports: Vec<IrqReport> = bindings
+            .into_iter()
+            .filter(|b| match &b.mechanism {
+                AsyncMechanism::Interrupt { threaded } => !threaded,
+                AsyncMechanism::Custom(name) => name == DMA_CALLBACK,
+                _ => false,
+            })
+            .map(|binding| {
+                walk.done.clear();
+                walk.visit(&binding.handler);
+                IrqReport {
+                    handler: binding.handler,
+                    registered_at: binding.bind_location.map(|l| l.line),
+                    violations: std::mem::take(&mut walk.violations),
+                }
+            })
+            .collect();
+
+        if let Some(focus) = &self.focus {
+            for report in &mut reports {
+                report.violations.retain(|v| v.path().iter().any(|f| focus.contains(f)));
+            }
+            reports.retain(|r| focus.contains(&r.handler) || !r.is_clean());
+        }
+        reports.sort_by(|a, b| (a.registered_at, &a.handler).cmp(&(b.registered_at, &b.handler)));
+        reports.dedup_by(|a, b| a.handler == b.handler && a.registered_at == b.registered_at);
+        reports
+    }
+}
+
+impl IrqViolation {
+    /// Call path of the violation, handler first
+    pub fn path(&self) -> &[String] {
+        match self {
+            IrqViolation::SleepingCall { path, .. } | IrqViolation::Recursion { path } => path,
+        }
+    }
+}
+
+/// Where the bodies of walked functions are read from
+enum Bodies<'a> {
+    /// Every function is in this source
+    Source(&'a [&'a str]),
+    /// Functions of this file are in this source; others are read from disk
+    File(&'a str, &'a [&'a str]),
+}
+
+impl Default for IrqChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Depth-first walk over the functions reachable from one handler
+struct Walk<'a> {
+    functions: &'a HashMap<String, FunctionDef>,
+    kb: &'a KnowledgeBase,
+    bodies: Bodies<'a>,
+    /// Lines of other files read so far; empty if unreadable
+    other_files: HashMap<String, Vec<String>>,
+    /// Current call path, handler first
+    path: Vec<String>,
+    /// Functions whose callees were already walked
+    done: HashSet<String>,
+    violations: Vec<IrqViolation>,
+}
+
+impl Walk<'_> {
+    fn visit(&mut self, name: &str) {
+        if self.path.iter().any(|f| f == name) {
+            let mut path = self.path.clone();
+            path.push(name.to_string());
+            self.violations.push(IrqViolation::Recursion { path });
+            return;
+        }
+        let Some(func) = self.functions.get(name) else {
+            return;
+        };
+        if !self.done.insert(name.to_string()) {
+            return;
+        }
+
+        self.path.push(name.to_string());
+        for callee in &func.calls {
+            if self.functions.contains_key(callee) {
+                self.visit(callee);
+            } else if self.kb.get_api(callee).is_some_and(|api| api.can_sleep) {
+                // A call that can't be found in the source is still reported
+                let calls = self.calls(func, callee);
+                let sleeping = calls.iter().find(|(_, text)| !text.contains("GFP_ATOMIC") && !text.contains("GFP_NOWAIT"));
+                if calls.is_empty() || sleeping.is_some() {
+                    self.violations.push(IrqViolation::SleepingCall {
+                        api: callee.clone(),
+                        path: self.path.clone(),
+                        line: sleeping.map(|(line, _)| *line),
+                    });
+                }
+            }
+        }
+        self.path.pop();
+    }
+
+    /// Every call to `callee` in `func`'s body: its line and its text from
+    /// the callee name to the closing parenthesis
+    fn calls(&mut self, func: &FunctionDef, callee: &str) -> Vec<(u32, String)> {
+        let mut starts: Vec<(u32, usize)> = func
+            .call_sites
+            .iter()
+            .filter(|site| site.callee == callee)
+            .map(|site| (site.line, site.column as usize))
+            .collect();
+        // No call sites recorded (e.g. an older index): look for the calls
+        if starts.is_empty() {
+            if let Some(loc) = &func.location {
+                for line in loc.line..=loc.end_line {
+                    let Some(text) = self.line(func, line) else {
+                        break;
+                    };
+                    starts.extend(
+                        text.match_indices(callee)
+                            .filter(|&(at, _)| {
+                                !text[..at].ends_with(|c: char| c.is_alphanumeric() || c == '_')
+                                    && text[at + callee.len()..].trim_start().starts_with('(')
+                            })
+                            .map(|(at, _)| (line, at)),
+                    );
+                }
+            }
+        }
+        starts
+            .into_iter()
+            .map(|(line, column)| (line, self.call_text(func, line, column)))
+            .collect()
+    }
+
+    /// Text from `column` of `line` up to the parenthesis closing the
+    /// argument list, across lines, within `func`
+    fn call_text(&mut self, func: &FunctionDef, line: u32, column: usize) -> String {
+        let end_line = func.location.as_ref().map_or(line, |loc| loc.end_line.max(line));
+        let mut text = String::new();
+        let mut depth = 0;
+        for current in line..=end_line {
+            let Some(content) = self.line(func, current) else {
+                break;
+            };
+            let content = match current == line {
+                true => content.get(column..).unwrap_or(&content),
+                false => &content,
+            };
+            for c in content.chars() {
+                text.push(c);
+                match c {
+                    '(' => depth += 1,
+                    ')' if depth == 1 => return text,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Text of 1-based `line` in the file defining `func`
+    fn line(&mut self, func: &FunctionDef, line: u32) -> Option<String> {
+        let index = (line as usize).checked_sub(1)?;
+        let file = func.location.as_ref().map(|l| l.file.as_str()).unwrap_or_default();
+        match self.bodies {
+            Bodies::Source(lines) => return lines.get(index).map(|l| l.to_string()),
+            Bodies::File(own, lines) if own == file => return lines.get(index).map(|l| l.to_string()),
+            Bodies::File(..) => {}
+        }
+        let lines = self.other_files.entry(file.to_string()).or_insert_with(|| {
+            std::fs::read_to_string(file)
+                .map(|text| text.lines().map(String::from).collect())
+                .unwrap_or_default()
+        });
+        lines.get(index).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowsight_parser::treesitter::TreeSitterParser;
+
+    #[test]
+    fn test_irq_handler_check() {
+        let source = r#"
+static void log_it(struct my_dev *dev) {
+    mutex_lock(&dev->lock);
+    mutex_unlock(&dev->lock);
+}
+
+static void walk(struct node *n) {
+    if (n)
+        walk(n->next);
+}
+
+static irqreturn_t bad_irq(int irq, void *data) {
+    void *buf = kzalloc(16, GFP_ATOMIC);
+    void *big = kmalloc(sizeof(struct big_buffer),
+                        GFP_ATOMIC | __GFP_NOWARN);
+    void *more = kzalloc(64, GFP_KERNEL);
+    log_it(data);
+    walk(data);
+    return IRQ_HANDLED;
+}
+
+static irqreturn_t good_irq(int irq, void *data) {
+    spin_lock(&lock);
+    spin_unlock(&lock);
+    return IRQ_HANDLED;
+}
+
+static irqreturn_t thread_fn(int irq, void *data) {
+    msleep(10);
+    return IRQ_HANDLED;
+}
+
+static int my_probe(struct device *dev) {
+    request_irq(irq, bad_irq, 0, "bad", dev);
+    devm_request_irq(dev, irq2, good_irq, 0, "good", dev);
+    request_threaded_irq(irq3, NULL, thread_fn, 0, "thr", dev);
+    return 0;
+}
+"#;
+        let mut parser = TreeSitterParser::new();
+        let result = parser.parse_source(source, "test.c").unwrap();
+        let reports = IrqChecker::new().check(source, &result.functions, &KnowledgeBase::builtin());
+
+        // The threaded handler may sleep and is not checked
+        let handlers: Vec<&str> = reports.iter().map(|r| r.handler.as_str()).collect();
+        assert_eq!(handlers, vec!["bad_irq", "good_irq"]);
+
+        let bad = &reports[0];
+        assert_eq!(bad.registered_at, Some(34));
+        assert_eq!(
+            bad.violations,
+            vec![
+                IrqViolation::SleepingCall {
+                    api: "kzalloc".into(),
+                    path: vec!["bad_irq".into()],
+                    line: Some(16),
+                },
+                IrqViolation::SleepingCall {
+                    api: "mutex_lock".into(),
+                    path: vec!["bad_irq".into(), "log_it".into()],
+                    line: Some(3),
+                },
+                IrqViolation::Recursion {
+                    path: vec!["bad_irq".into(), "walk".into(), "walk".into()],
+                },
+            ]
+        );
+        assert!(reports[1].is_clean());
+
+        // Focused on `walk`, only the paths running through it are reported
+        let focused = IrqChecker::new()
+            .with_focus(["walk".to_string()].into())
+            .check(source, &result.functions, &KnowledgeBase::builtin());
+        assert_eq!(focused.len(), 1);
+        assert_eq!(
+            focused[0].violations,
+            vec![IrqViolation::Recursion {
+                path: vec!["bad_irq".into(), "walk".into(), "walk".into()],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_dma_callback_check() {
+        let source = r#"
+static void my_dma_done(void *param) {
+    struct my_dev *dev = param;
+    mutex_lock(&dev->lock);
+    mutex_unlock(&dev->lock);
+}
+
+static int my_start_dma(struct my_dev *dev) {
+    struct dma_async_tx_descriptor *desc;
+    desc = dmaengine_prep_slave_single(dev->chan, dev->buf, dev->len, DMA_MEM_TO_DEV, 0);
+    desc->callback = my_dma_done;
+    desc->callback_param = dev;
+    dmaengine_submit(desc);
+    dma_async_issue_pending(dev->chan);
+    return 0;
+}
+"#;
+        let mut parser = TreeSitterParser::new();
+        let result = parser.parse_source(source, "test.c").unwrap();
+        let reports = IrqChecker::new().check(source, &result.functions, &KnowledgeBase::builtin());
+
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].handler, "my_dma_done");
+        assert_eq!(reports[0].registered_at, Some(11));
+        assert_eq!(
+            reports[0].violations,
+            vec![IrqViolation::SleepingCall {
+                api: "mutex_lock".into(),
+                path: vec!["my_dma_done".into()],
+                line: Some(4),
+            }]
+        );
+    }
+}
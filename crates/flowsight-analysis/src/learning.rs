@@ -158,9 +158,7 @@ impl UserLearningStore {
     /// Merge another store into this one
     pub fn merge(&mut self, other: UserLearningStore) {
         for (id, annotation) in other.annotations {
-            if !self.annotations.contains_key(&id) {
-                self.annotations.insert(id, annotation);
-            }
+            self.annotations.entry(id).or_insert(annotation);
         }
         for query in other.pending_queries {
             self.add_query(query);
@@ -216,7 +214,7 @@ impl<'a> CandidateFinder<'a> {
         }
 
         // Sort by score descending
-        candidates.sort_by(|a, b| b.score.cmp(&a.score));
+        candidates.sort_by_key(|c| std::cmp::Reverse(c.score));
         candidates
     }
 
@@ -239,7 +237,7 @@ impl<'a> CandidateFinder<'a> {
             }
         }
 
-        candidates.sort_by(|a, b| b.score.cmp(&a.score));
+        candidates.sort_by_key(|c| std::cmp::Reverse(c.score));
         candidates
     }
 
@@ -3,34 +3,61 @@
 //! Provides advanced code analysis capabilities:
 //! - Async mechanism tracking (work queues, timers, interrupts)
 //! - Function pointer resolution
+//! - Callbacks registered through macros (`DEFINE_*_ATTRIBUTE`, `*_PM_OPS`, ...)
 //! - Andersen-style pointer analysis
 //! - Call graph construction
+//! - Flow tree export (DOT, Mermaid, SVG) and text rendering
 //! - Scenario-based symbolic execution
 //! - Expression evaluation
 //! - Data flow analysis
+//! - Unchecked failable-API results (error paths)
+//! - Loops without an exit
+//! - Locks left held (or released twice) on some path
+//! - Sleeping calls reachable from atomic context
+//! - Checker findings as SARIF for CI
 //! - Result classification (Certain/Possible/Unknown)
 //! - User-assisted learning for uncertain cases
+//! - `CONFIG_*` variant comparison
+//! - Multi-file (module-wide) analysis
+//! - JSON Schemas for the JSON output
 
 pub mod async_tracker;
+pub mod atomic_check;
 pub mod callback;
 pub mod callgraph;
 pub mod classification;
 pub mod constraint;
+pub mod control_flow;
+pub mod driver_check;
+pub mod error_check;
 pub mod evaluator;
+pub mod export;
+pub mod finding;
 pub mod funcptr;
+pub mod graph;
+pub mod irq_check;
 pub mod learning;
+pub mod lock_balance;
+pub mod macro_ops;
+pub mod module;
 pub mod pointer;
 pub mod propagation;
+pub mod render;
+pub mod sarif;
 pub mod scenario;
+pub mod schema;
 pub mod types;
+pub mod variants;
 
 use flowsight_core::{AsyncBinding, CallEdge, FlowNode, FunctionDef, Result};
 use flowsight_knowledge::KnowledgeBase;
 use flowsight_parser::ParseResult;
+use schemars::JsonSchema;
+use serde::Serialize;
 use std::collections::HashMap;
 
 /// Analysis result
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, JsonSchema)]
 pub struct AnalysisResult {
     /// Async bindings found
     pub async_bindings: Vec<AsyncBinding>,
@@ -40,6 +67,41 @@ pub struct AnalysisResult {
     pub entry_points: Vec<String>,
     /// Execution flow trees (with kernel call chain injection)
     pub flow_trees: Vec<FlowNode>,
+    /// Failable API results dereferenced without a check
+    pub unchecked_allocations: Vec<error_check::UncheckedAllocation>,
+    /// copy_to_user/copy_from_user/get_user/put_user results never looked at
+    pub unchecked_user_copies: Vec<error_check::UncheckedUserCopy>,
+    /// Entry points known only from registration macros
+    pub macro_callbacks: Vec<macro_ops::MacroCallback>,
+}
+
+/// Limits applied while building flow trees
+#[derive(Debug, Clone)]
+pub struct AnalysisConfig {
+    /// Maximum call depth below an entry point; deeper calls are summarized
+    pub max_flow_depth: usize,
+    /// Maximum children expanded per node; the rest are summarized
+    pub max_children_per_node: usize,
+}
+
+impl Default for AnalysisConfig {
+    fn default() -> Self {
+        Self {
+            max_flow_depth: 20,
+            max_children_per_node: 64,
+        }
+    }
+}
+
+/// Flow tree construction progress, reported once per entry point
+#[derive(Debug, Clone, Copy)]
+pub struct FlowProgress<'a> {
+    /// Entry points processed so far, this one included
+    pub current: usize,
+    /// Entry points to process
+    pub total: usize,
+    /// Entry point just processed
+    pub entry: &'a str,
 }
 
 /// Main analyzer
@@ -49,6 +111,7 @@ pub struct AnalysisResult {
 pub struct Analyzer {
     async_tracker: async_tracker::AsyncTracker,
     funcptr_resolver: funcptr::FuncPtrResolver,
+    macro_ops: macro_ops::MacroOpsRecognizer,
     /// 知识库，包含内核调用链等信息
     knowledge_base: KnowledgeBase,
 }
@@ -56,40 +119,82 @@ pub struct Analyzer {
 impl Analyzer {
     /// Create a new analyzer with built-in knowledge base
     pub fn new() -> Self {
-        Self {
-            async_tracker: async_tracker::AsyncTracker::new(),
-            funcptr_resolver: funcptr::FuncPtrResolver::new(),
-            knowledge_base: KnowledgeBase::builtin(),
-        }
+        Self::with_knowledge_base(KnowledgeBase::builtin())
     }
 
     /// Create a new analyzer with custom knowledge base
     pub fn with_knowledge_base(kb: KnowledgeBase) -> Self {
         Self {
-            async_tracker: async_tracker::AsyncTracker::new(),
+            async_tracker: async_tracker::AsyncTracker::with_knowledge(&kb),
             funcptr_resolver: funcptr::FuncPtrResolver::new(),
+            macro_ops: macro_ops::MacroOpsRecognizer::new(),
             knowledge_base: kb,
         }
     }
 
-    /// Analyze parsed code
+    /// Analyze parsed code with the default [`AnalysisConfig`]
     pub fn analyze(
         &mut self,
         source: &str,
         parse_result: &mut ParseResult,
     ) -> Result<AnalysisResult> {
-        let mut result = AnalysisResult::default();
+        self.analyze_with_config(source, parse_result, &AnalysisConfig::default())
+    }
+
+    /// Analyze parsed code, bounding flow trees by `config`
+    pub fn analyze_with_config(
+        &mut self,
+        source: &str,
+        parse_result: &mut ParseResult,
+        config: &AnalysisConfig,
+    ) -> Result<AnalysisResult> {
+        Ok(self.analyze_file(source, parse_result, config, &mut |_| {}))
+    }
+
+    /// Analyze parsed code like [`Self::analyze`], calling `progress` as the
+    /// flow tree of each entry point is built
+    ///
+    /// Flow trees dominate the time spent on files with many callbacks.
+    pub fn analyze_with_progress(
+        &mut self,
+        source: &str,
+        parse_result: &mut ParseResult,
+        mut progress: impl FnMut(FlowProgress),
+    ) -> Result<AnalysisResult> {
+        Ok(self.analyze_file(source, parse_result, &AnalysisConfig::default(), &mut progress))
+    }
+
+    /// Analyze many parsed files in parallel, one result per file in order
+    ///
+    /// Each file is analyzed on its own, exactly as by [`Self::analyze`];
+    /// callback marks are written back into each `ParseResult`.
+    #[cfg(feature = "parallel")]
+    pub fn analyze_many(&self, files: &mut [(String, ParseResult)]) -> Vec<AnalysisResult> {
+        use rayon::prelude::*;
+
+        let config = AnalysisConfig::default();
+        files
+            .par_iter_mut()
+            .map(|(source, parse_result)| self.analyze_file(source, parse_result, &config, &mut |_| {}))
+            .collect()
+    }
 
+    /// The analysis passes only read the analyzer, so files can share it across threads
+    fn analyze_file(
+        &self,
+        source: &str,
+        parse_result: &mut ParseResult,
+        config: &AnalysisConfig,
+        progress: &mut dyn FnMut(FlowProgress),
+    ) -> AnalysisResult {
         // Track async mechanisms
-        result.async_bindings = self.async_tracker.analyze(source, &parse_result.functions);
+        let mut result = AnalysisResult {
+            async_bindings: self.async_tracker.analyze(source, &parse_result.functions),
+            ..Default::default()
+        };
 
         // Mark async handlers as callbacks
-        for binding in &result.async_bindings {
-            if let Some(func) = parse_result.functions.get_mut(&binding.handler) {
-                func.is_callback = true;
-                func.callback_context = Some(format!("async_{:?}", binding.mechanism));
-            }
-        }
+        mark_async_handlers(&mut parse_result.functions, &result.async_bindings);
 
         // Resolve function pointers from ops tables
         let ops_mappings = self
@@ -101,18 +206,43 @@ impl Analyzer {
                 func.callback_context = Some(context.clone());
             }
         }
+        self.mark_ops_contexts(source, &mut parse_result.functions);
+
+        // Callbacks hidden behind registration macros
+        result.macro_callbacks = self.mark_macro_callbacks(source, &mut parse_result.functions);
 
         // Find entry points
         result.entry_points = self.find_entry_points(source, &parse_result.functions);
 
         // Build call graph
-        result.call_edges = callgraph::build_call_edges(parse_result, &result.async_bindings);
+        let funcptr_bindings = self
+            .funcptr_resolver
+            .resolve_all(source, &parse_result.functions);
+        result.call_edges = callgraph::build_call_edges(
+            parse_result,
+            &result.async_bindings,
+            &funcptr_bindings,
+            source,
+        );
+
+        // Flag failable API results used without a NULL/IS_ERR check
+        let checker = error_check::ErrorChecker::new();
+        result.unchecked_allocations = checker.check(source, &parse_result.functions, &self.knowledge_base);
+        result.unchecked_user_copies = checker.check_user_copies(source, &parse_result.functions);
 
         // Build flow trees for entry points
-        result.flow_trees =
-            self.build_flow_trees(&result.entry_points, parse_result, &result.async_bindings);
+        result.flow_trees = self.build_flow_trees(
+            &result.entry_points,
+            parse_result,
+            &result.async_bindings,
+            config,
+            progress,
+        );
+        for tree in &mut result.flow_trees {
+            macro_ops::mark_macro_entries(tree, &result.macro_callbacks);
+        }
 
-        Ok(result)
+        result
     }
 
     fn find_entry_points(
@@ -192,22 +322,75 @@ impl Analyzer {
         entries
     }
 
+    /// Record the context the knowledge base gives each ops-table slot on
+    /// the function assigned to it, unless an async binding already set one
+    fn mark_ops_contexts(&self, source: &str, functions: &mut HashMap<String, FunctionDef>) {
+        for assignment in self.funcptr_resolver.find_ops_assignments(source, "") {
+            let Some(callback) = self.knowledge_base.get_callback(&assignment.ops_type, &assignment.field) else {
+                continue;
+            };
+            if let Some(func) = functions.get_mut(&assignment.function) {
+                func.execution_context
+                    .get_or_insert_with(|| callgraph::core_context(&callback.context));
+            }
+        }
+    }
+
+    /// Mark functions registered only through macros as callbacks
+    ///
+    /// Functions already known as callbacks keep their context.
+    fn mark_macro_callbacks(
+        &self,
+        source: &str,
+        functions: &mut HashMap<String, FunctionDef>,
+    ) -> Vec<macro_ops::MacroCallback> {
+        let mut marked = Vec::new();
+        for callback in self.macro_ops.find(source, functions) {
+            let Some(func) = functions.get_mut(&callback.function) else {
+                continue;
+            };
+            if func.is_callback && !marked.iter().any(|m: &macro_ops::MacroCallback| m.function == callback.function) {
+                continue;
+            }
+            func.is_callback = true;
+            func.callback_context.get_or_insert_with(|| callback.context.clone());
+            marked.push(callback);
+        }
+        marked
+    }
+
     fn build_flow_trees(
         &self,
         entry_points: &[String],
         parse_result: &ParseResult,
         async_bindings: &[AsyncBinding],
+        config: &AnalysisConfig,
+        progress: &mut dyn FnMut(FlowProgress),
     ) -> Vec<FlowNode> {
+        let total = entry_points.len();
         entry_points
             .iter()
-            .filter_map(|entry| {
+            .enumerate()
+            .filter_map(|(i, entry)| {
                 // 使用带内核调用链注入的完整执行流构建
-                callgraph::build_full_flow_tree(
+                let tree = callgraph::build_full_flow_tree(
                     entry,
                     parse_result,
                     async_bindings,
                     &self.knowledge_base,
-                )
+                    config,
+                );
+                progress(FlowProgress {
+                    current: i + 1,
+                    total,
+                    entry,
+                });
+                tree
+            })
+            .map(|mut tree| {
+                // Callees inherit the entry's context instead of defaulting to process
+                callgraph::propagate_execution_context(&mut tree, async_bindings);
+                tree
             })
             .collect()
     }
@@ -219,5 +402,17 @@ impl Default for Analyzer {
     }
 }
 
+/// Mark the handlers of `bindings` as callbacks, with their mechanism and context
+fn mark_async_handlers(functions: &mut HashMap<String, FunctionDef>, bindings: &[AsyncBinding]) {
+    for binding in bindings {
+        if let Some(func) = functions.get_mut(&binding.handler) {
+            func.is_callback = true;
+            func.callback_context = Some(format!("async_{:?}", binding.mechanism));
+            func.async_mechanism = Some(binding.mechanism.clone());
+            func.execution_context = Some(binding.context.clone());
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,461 @@
+//! Lock balance checks
+//!
+//! Walks each function's control flow and tracks which locks are held along
+//! every path. A path that returns with a lock still held, or releases a lock
+//! it never took, is reported — the classic case is an error path that does
+//! `goto out` past the `spin_unlock`.
+//!
+//! Only locks the function both takes and releases are considered, so helpers
+//! that lock for their caller (or unlock on its behalf) stay quiet.
+
+use crate::control_flow::{declared_name, text};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tree_sitter::{Node, Parser as TSParser};
+
+/// Acquire / release pairs; the lock is the first argument, or the acquire
+/// function itself for argument-less locks like `rcu_read_lock()`
+const LOCKS: &[(&str, &str)] = &[
+    ("spin_lock", "spin_unlock"),
+    ("spin_lock_bh", "spin_unlock_bh"),
+    ("spin_lock_irq", "spin_unlock_irq"),
+    ("spin_lock_irqsave", "spin_unlock_irqrestore"),
+    ("raw_spin_lock", "raw_spin_unlock"),
+    ("raw_spin_lock_irq", "raw_spin_unlock_irq"),
+    ("raw_spin_lock_irqsave", "raw_spin_unlock_irqrestore"),
+    ("read_lock", "read_unlock"),
+    ("read_lock_bh", "read_unlock_bh"),
+    ("read_lock_irqsave", "read_unlock_irqrestore"),
+    ("write_lock", "write_unlock"),
+    ("write_lock_bh", "write_unlock_bh"),
+    ("write_lock_irqsave", "write_unlock_irqrestore"),
+    ("mutex_lock", "mutex_unlock"),
+    ("mutex_lock_nested", "mutex_unlock"),
+    ("down_read", "up_read"),
+    ("down_write", "up_write"),
+    ("rcu_read_lock", "rcu_read_unlock"),
+    ("rcu_read_lock_bh", "rcu_read_unlock_bh"),
+    ("local_irq_disable", "local_irq_enable"),
+    ("preempt_disable", "preempt_enable"),
+];
+
+/// Paths kept per program point before equivalent ones are merged away
+const MAX_STATES: usize = 64;
+
+/// Which way a lock is out of balance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Imbalance {
+    /// A path leaves the function with the lock held
+    MissingUnlock,
+    /// A path releases the lock without holding it
+    UnlockWithoutLock,
+}
+
+/// A lock that is not released (or released twice) on some path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnbalancedLock {
+    /// Function containing the lock
+    pub function: String,
+    /// Lock expression, e.g. `dev->lock`
+    pub lock: String,
+    /// Line of the lock call (of the unlock for `UnlockWithoutLock`)
+    pub lock_site: u32,
+    /// Lines of the gotos, breaks and returns the bad path goes through,
+    /// ending where it leaves the function (or at the extra unlock)
+    pub missing_unlock_path: Vec<u32>,
+    pub imbalance: Imbalance,
+}
+
+/// Checker for locks left held (or released twice) on some path
+pub struct LockBalanceChecker {
+    /// Only check these functions
+    focus: Option<HashSet<String>>,
+}
+
+impl LockBalanceChecker {
+    pub fn new() -> Self {
+        Self { focus: None }
+    }
+
+    /// Only check `functions` (e.g. the ones touched by a change)
+    pub fn with_focus(mut self, functions: HashSet<String>) -> Self {
+        self.focus = Some(functions);
+        self
+    }
+
+    /// Find every unbalanced lock in the functions of `source`
+    pub fn find_unbalanced(&self, source: &str) -> Vec<UnbalancedLock> {
+        let mut parser = TSParser::new();
+        parser
+            .set_language(&tree_sitter_c::language())
+            .expect("Failed to load C grammar");
+
+        let mut found = Vec::new();
+        if let Some(tree) = parser.parse(source, None) {
+            self.visit(tree.root_node(), source, &mut found);
+        }
+        found
+    }
+
+    fn visit(&self, node: Node, source: &str, found: &mut Vec<UnbalancedLock>) {
+        if node.kind() == "function_definition" {
+            let name = node
+                .child_by_field_name("declarator")
+                .and_then(|d| declared_name(d, source));
+            let focused = |func: &str| self.focus.as_ref().is_none_or(|focus| focus.contains(func));
+            if let (Some(name), Some(body)) = (name.filter(|n| focused(n)), node.child_by_field_name("body")) {
+                found.extend(check_function(&name, body, source));
+            }
+            return;
+        }
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.visit(child, source, found);
+        }
+    }
+}
+
+impl Default for LockBalanceChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Locks held on one path through a function
+#[derive(Debug, Clone, Default)]
+struct State {
+    /// (lock, line it was taken), in acquisition order
+    held: Vec<(String, u32)>,
+    /// Jumps taken so far
+    path: Vec<u32>,
+}
+
+/// Path-sensitive walk over one function body
+struct Walk<'a> {
+    source: &'a str,
+    /// States waiting at a label a `goto` jumped forward to
+    pending: HashMap<&'a str, Vec<State>>,
+    /// States leaving each enclosing loop / switch via `break` or `continue`
+    breaks: Vec<Vec<State>>,
+    /// States leaving the function
+    exits: Vec<State>,
+    /// (lock, line, path) of releases with nothing held
+    extra_unlocks: Vec<(String, u32, Vec<u32>)>,
+    locked: HashSet<String>,
+    unlocked: HashSet<String>,
+}
+
+fn check_function(function: &str, body: Node, source: &str) -> Vec<UnbalancedLock> {
+    let mut walk = Walk {
+        source,
+        pending: HashMap::new(),
+        breaks: Vec::new(),
+        exits: Vec::new(),
+        extra_unlocks: Vec::new(),
+        locked: HashSet::new(),
+        unlocked: HashSet::new(),
+    };
+    let end = walk.exec(body, vec![State::default()]);
+    let closing = body.end_position().row as u32 + 1;
+    walk.exits.extend(end.into_iter().map(|mut state| {
+        state.path.push(closing);
+        state
+    }));
+
+    let balanced_here = |lock: &String| walk.locked.contains(lock) && walk.unlocked.contains(lock);
+    let mut reported = HashSet::new();
+    let mut found = Vec::new();
+    for state in &walk.exits {
+        for (lock, line) in &state.held {
+            if balanced_here(lock) && reported.insert((lock.clone(), *line)) {
+                found.push(UnbalancedLock {
+                    function: function.to_string(),
+                    lock: lock.clone(),
+                    lock_site: *line,
+                    missing_unlock_path: state.path.clone(),
+                    imbalance: Imbalance::MissingUnlock,
+                });
+            }
+        }
+    }
+    for (lock, line, path) in &walk.extra_unlocks {
+        if balanced_here(lock) && reported.insert((lock.clone(), *line)) {
+            found.push(UnbalancedLock {
+                function: function.to_string(),
+                lock: lock.clone(),
+                lock_site: *line,
+                missing_unlock_path: path.clone(),
+                imbalance: Imbalance::UnlockWithoutLock,
+            });
+        }
+    }
+    found.sort_by_key(|u| u.lock_site);
+    found
+}
+
+impl<'a> Walk<'a> {
+    /// Run `states` through `node`, returning the states that fall out the end
+    fn exec(&mut self, node: Node<'a>, mut states: Vec<State>) -> Vec<State> {
+        let line = node.start_position().row as u32 + 1;
+        match node.kind() {
+            "compound_statement" => {
+                let mut cursor = node.walk();
+                for child in node.named_children(&mut cursor) {
+                    states = self.exec(child, states);
+                }
+                states
+            }
+            "labeled_statement" => {
+                if let Some(label) = node.child_by_field_name("label") {
+                    states.extend(self.pending.remove(text(label, self.source)).unwrap_or_default());
+                }
+                let states = merge(states);
+                match node.named_child(node.named_child_count().saturating_sub(1)) {
+                    Some(statement) if statement.kind() != "statement_identifier" => self.exec(statement, states),
+                    _ => states,
+                }
+            }
+            "goto_statement" => {
+                // Backward gotos are loops; the forward pass has already seen the label
+                if let Some(label) = node.child_by_field_name("label") {
+                    let label = text(label, self.source);
+                    let jumped = states.into_iter().map(|mut state| {
+                        state.path.push(line);
+                        state
+                    });
+                    self.pending.entry(label).or_default().extend(jumped);
+                }
+                Vec::new()
+            }
+            "return_statement" => {
+                let states = self.apply_calls(node, states);
+                self.exits.extend(states.into_iter().map(|mut state| {
+                    state.path.push(line);
+                    state
+                }));
+                Vec::new()
+            }
+            "break_statement" | "continue_statement" => {
+                if let Some(out) = self.breaks.last_mut() {
+                    out.extend(states.into_iter().map(|mut state| {
+                        state.path.push(line);
+                        state
+                    }));
+                }
+                Vec::new()
+            }
+            "if_statement" => {
+                if let Some(condition) = node.child_by_field_name("condition") {
+                    states = self.apply_calls(condition, states);
+                }
+                let mut out = match node.child_by_field_name("consequence") {
+                    Some(then) => self.exec(then, states.clone()),
+                    None => states.clone(),
+                };
+                match node.child_by_field_name("alternative") {
+                    Some(alternative) => out.extend(self.exec(alternative, states)),
+                    None => out.extend(states),
+                }
+                merge(out)
+            }
+            "else_clause" => {
+                let mut cursor = node.walk();
+                let children: Vec<_> = node.named_children(&mut cursor).collect();
+                for child in children {
+                    states = self.exec(child, states);
+                }
+                states
+            }
+            "while_statement" | "for_statement" | "do_statement" => {
+                if let Some(condition) = node.child_by_field_name("condition") {
+                    states = self.apply_calls(condition, states);
+                }
+                self.breaks.push(Vec::new());
+                let mut out = match node.child_by_field_name("body") {
+                    Some(body) => self.exec(body, states.clone()),
+                    None => Vec::new(),
+                };
+                out.extend(self.breaks.pop().unwrap_or_default());
+                if node.kind() != "do_statement" {
+                    out.extend(states);
+                }
+                merge(out)
+            }
+            "switch_statement" => {
+                if let Some(condition) = node.child_by_field_name("condition") {
+                    states = self.apply_calls(condition, states);
+                }
+                self.breaks.push(Vec::new());
+                let mut current = Vec::new();
+                let mut has_default = false;
+                if let Some(body) = node.child_by_field_name("body") {
+                    let mut cursor = body.walk();
+                    for case in body.named_children(&mut cursor) {
+                        if case.kind() != "case_statement" {
+                            current = self.exec(case, current);
+                            continue;
+                        }
+                        has_default |= case.child_by_field_name("value").is_none();
+                        current.extend(states.iter().cloned());
+                        current = merge(current);
+                        let mut case_cursor = case.walk();
+                        for statement in case.named_children(&mut case_cursor) {
+                            if Some(statement) != case.child_by_field_name("value") {
+                                current = self.exec(statement, current);
+                            }
+                        }
+                    }
+                }
+                current.extend(self.breaks.pop().unwrap_or_default());
+                if !has_default {
+                    current.extend(states);
+                }
+                merge(current)
+            }
+            _ => self.apply_calls(node, states),
+        }
+    }
+
+    /// Apply the lock and unlock calls inside `node`, in source order
+    fn apply_calls(&mut self, node: Node<'a>, mut states: Vec<State>) -> Vec<State> {
+        if node.kind() == "call_expression" {
+            let callee = node.child_by_field_name("function").map(|f| text(f, self.source));
+            let first_arg = node
+                .child_by_field_name("arguments")
+                .and_then(|args| args.named_child(0))
+                .map(|arg| text(arg, self.source).trim_start_matches('&').split_whitespace().collect::<String>());
+            let line = node.start_position().row as u32 + 1;
+            if let Some(&(acquire, _)) = LOCKS.iter().find(|(acquire, _)| Some(*acquire) == callee) {
+                let lock = first_arg.unwrap_or_else(|| acquire.to_string());
+                self.locked.insert(lock.clone());
+                for state in &mut states {
+                    state.held.push((lock.clone(), line));
+                }
+            } else if let Some(&(acquire, _)) = LOCKS.iter().find(|(_, release)| Some(*release) == callee) {
+                let lock = first_arg.unwrap_or_else(|| acquire.to_string());
+                self.unlocked.insert(lock.clone());
+                for state in &mut states {
+                    match state.held.iter().rposition(|(held, _)| *held == lock) {
+                        Some(pos) => {
+                            state.held.remove(pos);
+                        }
+                        None => {
+                            let mut path = state.path.clone();
+                            path.push(line);
+                            self.extra_unlocks.push((lock.clone(), line, path));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut cursor = node.walk();
+        let children: Vec<_> = node.children(&mut cursor).collect();
+        for child in children {
+            states = self.apply_calls(child, states);
+        }
+        states
+    }
+}
+
+/// Drop paths holding the same locks as an earlier one
+fn merge(states: Vec<State>) -> Vec<State> {
+    let mut seen = HashSet::new();
+    let mut merged: Vec<State> = states
+        .into_iter()
+        .filter(|state| seen.insert(state.held.clone()))
+        .collect();
+    merged.truncate(MAX_STATES);
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_unbalanced() {
+        let source = r#"
+static int my_write(struct my_dev *dev, int val) {
+    int ret = 0;
+
+    spin_lock(&dev->lock);
+    if (val < 0) {
+        ret = -EINVAL;
+        goto out;
+    }
+    if (!dev->ready)
+        return -EBUSY;
+    writel(val, dev->regs);
+out:
+    spin_unlock(&dev->lock);
+    return ret;
+}
+
+static void my_reset(struct my_dev *dev) {
+    unsigned long flags;
+
+    spin_lock_irqsave(&dev->lock, flags);
+    switch (dev->state) {
+    case 0:
+        break;
+    default:
+        dev->state = 0;
+        break;
+    }
+    spin_unlock_irqrestore(&dev->lock, flags);
+}
+
+static void my_poll(struct my_dev *dev) {
+    int i;
+
+    for (i = 0; i < 10; i++) {
+        mutex_lock(&dev->mutex);
+        if (readl(dev->regs))
+            continue;
+        mutex_unlock(&dev->mutex);
+    }
+}
+
+static void my_release(struct my_dev *dev) {
+    if (dev->busy)
+        mutex_unlock(&dev->mutex);
+    mutex_lock(&dev->mutex);
+    dev->busy = 0;
+    mutex_unlock(&dev->mutex);
+}
+
+static void my_lock(struct my_dev *dev) {
+    spin_lock(&dev->lock);
+}
+"#;
+        let found = LockBalanceChecker::new().find_unbalanced(source);
+        assert_eq!(
+            found,
+            vec![
+                UnbalancedLock {
+                    function: "my_write".into(),
+                    lock: "dev->lock".into(),
+                    lock_site: 5,
+                    missing_unlock_path: vec![11],
+                    imbalance: Imbalance::MissingUnlock,
+                },
+                UnbalancedLock {
+                    function: "my_poll".into(),
+                    lock: "dev->mutex".into(),
+                    lock_site: 36,
+                    missing_unlock_path: vec![38, 41],
+                    imbalance: Imbalance::MissingUnlock,
+                },
+                UnbalancedLock {
+                    function: "my_release".into(),
+                    lock: "dev->mutex".into(),
+                    lock_site: 45,
+                    missing_unlock_path: vec![45],
+                    imbalance: Imbalance::UnlockWithoutLock,
+                },
+            ]
+        );
+    }
+}
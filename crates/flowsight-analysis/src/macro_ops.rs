@@ -0,0 +1,234 @@
+//! Callbacks registered through macros
+//!
+//! `DEFINE_SHOW_ATTRIBUTE(foo)`, `SIMPLE_DEV_PM_OPS(pm, suspend, resume)` or
+//! `module_platform_driver(drv)` expand to the functions and registrations
+//! that make a driver's callbacks reachable, none of which the parser sees.
+//! A pattern table maps each macro's arguments to the callbacks it implies.
+//! The expansion is assumed rather than seen, so these entry points are only
+//! `Possible`.
+
+use crate::funcptr::FuncPtrResolver;
+use flowsight_core::{CallConfidence, ConfidenceLevel, FlowNode, FunctionDef};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Callbacks implied by each macro, as `(function, context)`
+///
+/// `{0}`, `{1}`, ... in a function template stand for the macro's arguments.
+const CALLBACK_MACROS: &[(&str, &[(&str, &str)])] = &[
+    ("DEFINE_SHOW_ATTRIBUTE", &[("{0}_show", "seq_file.show")]),
+    (
+        "DEFINE_SHOW_STORE_ATTRIBUTE",
+        &[("{0}_show", "seq_file.show"), ("{0}_write", "file_operations.write")],
+    ),
+    ("DEFINE_SIMPLE_ATTRIBUTE", &[("{1}", "simple_attr.get"), ("{2}", "simple_attr.set")]),
+    ("DEFINE_DEBUGFS_ATTRIBUTE", &[("{1}", "simple_attr.get"), ("{2}", "simple_attr.set")]),
+    ("DEFINE_DEBUGFS_ATTRIBUTE_SIGNED", &[("{1}", "simple_attr.get"), ("{2}", "simple_attr.set")]),
+    (
+        "DEVICE_ATTR_RW",
+        &[("{0}_show", "device_attribute.show"), ("{0}_store", "device_attribute.store")],
+    ),
+    ("DEVICE_ATTR_RO", &[("{0}_show", "device_attribute.show")]),
+    ("DEVICE_ATTR_WO", &[("{0}_store", "device_attribute.store")]),
+    ("SIMPLE_DEV_PM_OPS", &[("{1}", "dev_pm_ops.suspend"), ("{2}", "dev_pm_ops.resume")]),
+    ("DEFINE_SIMPLE_DEV_PM_OPS", &[("{1}", "dev_pm_ops.suspend"), ("{2}", "dev_pm_ops.resume")]),
+    ("SET_SYSTEM_SLEEP_PM_OPS", &[("{0}", "dev_pm_ops.suspend"), ("{1}", "dev_pm_ops.resume")]),
+    ("SYSTEM_SLEEP_PM_OPS", &[("{0}", "dev_pm_ops.suspend"), ("{1}", "dev_pm_ops.resume")]),
+    (
+        "SET_RUNTIME_PM_OPS",
+        &[
+            ("{0}", "dev_pm_ops.runtime_suspend"),
+            ("{1}", "dev_pm_ops.runtime_resume"),
+            ("{2}", "dev_pm_ops.runtime_idle"),
+        ],
+    ),
+    (
+        "RUNTIME_PM_OPS",
+        &[
+            ("{0}", "dev_pm_ops.runtime_suspend"),
+            ("{1}", "dev_pm_ops.runtime_resume"),
+            ("{2}", "dev_pm_ops.runtime_idle"),
+        ],
+    ),
+    (
+        "DEFINE_RUNTIME_DEV_PM_OPS",
+        &[
+            ("{1}", "dev_pm_ops.runtime_suspend"),
+            ("{2}", "dev_pm_ops.runtime_resume"),
+            ("{3}", "dev_pm_ops.runtime_idle"),
+        ],
+    ),
+    (
+        "UNIVERSAL_DEV_PM_OPS",
+        &[
+            ("{1}", "dev_pm_ops.suspend"),
+            ("{2}", "dev_pm_ops.resume"),
+            ("{3}", "dev_pm_ops.runtime_idle"),
+        ],
+    ),
+];
+
+/// A callback implied by a registration macro
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct MacroCallback {
+    /// Macro that implies it (e.g. "SIMPLE_DEV_PM_OPS")
+    pub macro_name: String,
+    pub function: String,
+    /// Callback slot, e.g. "dev_pm_ops.suspend" or "my_driver.probe"
+    pub context: String,
+    /// Line of the macro invocation
+    pub line: u32,
+}
+
+/// Recognizer for callback registration macros
+pub struct MacroOpsRecognizer {
+    /// Any macro of [`CALLBACK_MACROS`] with its argument list
+    callback_re: Regex,
+    /// `module_platform_driver(drv)`, `builtin_i2c_driver(drv)`, `module_driver(drv, ...)`
+    driver_re: Regex,
+}
+
+impl MacroOpsRecognizer {
+    pub fn new() -> Self {
+        let names: Vec<&str> = CALLBACK_MACROS.iter().map(|(name, _)| *name).collect();
+        Self {
+            callback_re: Regex::new(&format!(r"\b({})\s*\(([^()]*)\)", names.join("|"))).unwrap(),
+            driver_re: Regex::new(r"\b((?:module|builtin)_(?:\w+_)?driver)\s*\(\s*(\w+)").unwrap(),
+        }
+    }
+
+    /// Callbacks implied by the macros in `source` that name a function in `functions`
+    pub fn find(&self, source: &str, functions: &HashMap<String, FunctionDef>) -> Vec<MacroCallback> {
+        let line_of = |offset: usize| source[..offset].matches('\n').count() as u32 + 1;
+        let mut callbacks = Vec::new();
+
+        for caps in self.callback_re.captures_iter(source) {
+            let macro_name = &caps[1];
+            let args: Vec<&str> = caps[2].split(',').map(str::trim).collect();
+            let Some((_, implied)) = CALLBACK_MACROS.iter().find(|(name, _)| *name == macro_name) else {
+                continue;
+            };
+            for (template, context) in implied.iter() {
+                let mut function = template.to_string();
+                for (i, arg) in args.iter().enumerate() {
+                    function = function.replace(&format!("{{{}}}", i), arg);
+                }
+                if functions.contains_key(&function) {
+                    callbacks.push(MacroCallback {
+                        macro_name: macro_name.to_string(),
+                        function,
+                        context: context.to_string(),
+                        line: line_of(caps.get(0).unwrap().start()),
+                    });
+                }
+            }
+        }
+
+        // The driver table itself is a plain initializer; the macro makes it live
+        let assignments = FuncPtrResolver::new().find_ops_assignments(source, "");
+        for caps in self.driver_re.captures_iter(source) {
+            let driver = &caps[2];
+            for assignment in assignments.iter().filter(|a| a.variable == driver) {
+                if functions.contains_key(&assignment.function) {
+                    callbacks.push(MacroCallback {
+                        macro_name: caps[1].to_string(),
+                        function: assignment.function.clone(),
+                        context: format!("{}.{}", driver, assignment.field),
+                        line: line_of(caps.get(0).unwrap().start()),
+                    });
+                }
+            }
+        }
+
+        callbacks
+    }
+}
+
+impl Default for MacroOpsRecognizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Mark the nodes of macro-registered entry points in `tree` as `Possible`
+pub fn mark_macro_entries(tree: &mut FlowNode, callbacks: &[MacroCallback]) {
+    if let Some(callback) = callbacks.iter().find(|c| c.function == tree.name) {
+        tree.confidence = Some(CallConfidence {
+            level: ConfidenceLevel::Possible,
+            reason: format!("Registered by {} (macro expansion not parsed)", callback.macro_name),
+        });
+        return;
+    }
+    // The entry may sit below an injected kernel call chain
+    if tree.is_kernel_internal {
+        for child in &mut tree.children {
+            mark_macro_entries(child, callbacks);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowsight_parser::treesitter::TreeSitterParser;
+
+    #[test]
+    fn test_find_macro_callbacks() {
+        let source = r#"
+static int foo_show(struct seq_file *s, void *data) { return 0; }
+DEFINE_SHOW_ATTRIBUTE(foo);
+
+static int rate_get(void *data, u64 *val) { return 0; }
+DEFINE_DEBUGFS_ATTRIBUTE(rate_fops, rate_get, NULL, "%llu\n");
+
+static int my_suspend(struct device *dev) { return 0; }
+static int my_resume(struct device *dev) { return 0; }
+static SIMPLE_DEV_PM_OPS(my_pm, my_suspend, my_resume);
+
+static int my_probe(struct platform_device *pdev) { return 0; }
+static struct platform_driver my_driver = {
+    .probe = my_probe,
+    .driver = { .name = "my" },
+};
+module_platform_driver(my_driver);
+"#;
+        let mut parser = TreeSitterParser::new();
+        let result = parser.parse_source(source, "drv.c").unwrap();
+
+        let found: Vec<(String, String, String, u32)> = MacroOpsRecognizer::new()
+            .find(source, &result.functions)
+            .into_iter()
+            .map(|c| (c.macro_name, c.function, c.context, c.line))
+            .collect();
+        let expected = [
+            ("DEFINE_SHOW_ATTRIBUTE", "foo_show", "seq_file.show", 3),
+            ("DEFINE_DEBUGFS_ATTRIBUTE", "rate_get", "simple_attr.get", 6),
+            ("SIMPLE_DEV_PM_OPS", "my_suspend", "dev_pm_ops.suspend", 10),
+            ("SIMPLE_DEV_PM_OPS", "my_resume", "dev_pm_ops.resume", 10),
+            ("module_platform_driver", "my_probe", "my_driver.probe", 17),
+        ];
+        let expected: Vec<(String, String, String, u32)> = expected
+            .iter()
+            .map(|&(m, f, c, l)| (m.to_string(), f.to_string(), c.to_string(), l))
+            .collect();
+        assert_eq!(found, expected);
+
+        // Only the callbacks nothing else registers are downgraded
+        let mut parse_result = result;
+        let analysis = crate::Analyzer::new().analyze(source, &mut parse_result).unwrap();
+        let level = |name: &str| {
+            analysis
+                .flow_trees
+                .iter()
+                .find(|t| t.name == name)
+                .and_then(|t| t.confidence.as_ref())
+                .map(|c| c.level)
+        };
+        assert_eq!(level("my_suspend"), Some(ConfidenceLevel::Possible));
+        assert_eq!(level("foo_show"), Some(ConfidenceLevel::Possible));
+        assert_eq!(level("my_probe"), Some(ConfidenceLevel::Certain));
+        assert_eq!(parse_result.functions["rate_get"].callback_context.as_deref(), Some("simple_attr.get"));
+    }
+}
@@ -0,0 +1,98 @@
+//! Multi-file analysis
+//!
+//! Parses every file of a driver, merges the results, and runs the
+//! text-based passes per file against the merged functions, so flow trees
+//! follow calls and async bindings across files.
+
+use crate::{callgraph, error_check, mark_async_handlers, AnalysisConfig, AnalysisResult, Analyzer};
+use flowsight_core::Result;
+use flowsight_parser::{get_parser_for, ParseResult};
+use std::path::PathBuf;
+
+/// Merged parse and analysis results for a set of files
+#[derive(Debug, Default)]
+pub struct ModuleAnalysis {
+    pub parse_result: ParseResult,
+    pub analysis: AnalysisResult,
+}
+
+impl Analyzer {
+    /// Analyze `files` as one module
+    ///
+    /// A callee or async handler defined in another file is expanded in the
+    /// flow trees instead of being shown as external.
+    pub fn analyze_files(&mut self, files: &[PathBuf], config: &AnalysisConfig) -> Result<ModuleAnalysis> {
+        let mut sources = Vec::with_capacity(files.len());
+        let mut merged = ParseResult::default();
+        for file in files {
+            let source = std::fs::read_to_string(file)?;
+            let filename = file.to_string_lossy().into_owned();
+            merged.merge(get_parser_for(file)?.parse(&source, &filename)?);
+            sources.push((filename, source));
+        }
+
+        let mut result = AnalysisResult::default();
+
+        // Bindings and ops tables may name functions from any file
+        for (filename, source) in &sources {
+            for mut binding in self.async_tracker.analyze(source, &merged.functions) {
+                // Tracker locations only carry lines; tie them to their file
+                for loc in binding.bind_location.iter_mut().chain(binding.trigger_locations.iter_mut()) {
+                    loc.file = filename.clone();
+                }
+                result.async_bindings.push(binding);
+            }
+            for (context, func_name) in self.funcptr_resolver.analyze_ops_tables(source, &merged.functions) {
+                if let Some(func) = merged.functions.get_mut(&func_name) {
+                    func.is_callback = true;
+                    func.callback_context = Some(context);
+                }
+            }
+            result.macro_callbacks.extend(self.mark_macro_callbacks(source, &mut merged.functions));
+        }
+        mark_async_handlers(&mut merged.functions, &result.async_bindings);
+        for (_, source) in &sources {
+            self.mark_ops_contexts(source, &mut merged.functions);
+        }
+
+        // Passes that slice function bodies out of the source see only that file
+        let checker = error_check::ErrorChecker::new();
+        for (filename, source) in &sources {
+            let own = ParseResult {
+                functions: merged
+                    .functions
+                    .iter()
+                    .filter(|(_, f)| f.location.as_ref().is_some_and(|l| &l.file == filename))
+                    .map(|(name, f)| (name.clone(), f.clone()))
+                    .collect(),
+                ..Default::default()
+            };
+
+            for entry in self.find_entry_points(source, &own.functions) {
+                if !result.entry_points.contains(&entry) {
+                    result.entry_points.push(entry);
+                }
+            }
+            let funcptr_bindings = self.funcptr_resolver.resolve_all(source, &own.functions);
+            result.call_edges.extend(callgraph::build_call_edges(
+                &own,
+                &result.async_bindings,
+                &funcptr_bindings,
+                source,
+            ));
+            result.unchecked_allocations.extend(checker.check(source, &own.functions, &self.knowledge_base));
+            result.unchecked_user_copies.extend(checker.check_user_copies(source, &own.functions));
+        }
+
+        result.flow_trees =
+            self.build_flow_trees(&result.entry_points, &merged, &result.async_bindings, config, &mut |_| {});
+        for tree in &mut result.flow_trees {
+            crate::macro_ops::mark_macro_entries(tree, &result.macro_callbacks);
+        }
+
+        Ok(ModuleAnalysis {
+            parse_result: merged,
+            analysis: result,
+        })
+    }
+}
@@ -7,6 +7,7 @@
 //!
 //! 1. Collect constraints from source code:
 //!    - AddressOf: `p = &x` → x ∈ pts(p)
+//!    - ConditionalAddressOf: `if (c) p = &x` → x ∈ pts(p), p marked conditional
 //!    - Copy: `p = q` → pts(q) ⊆ pts(p)
 //!    - Load: `p = *q` → ∀o ∈ pts(q): pts(o) ⊆ pts(p)
 //!    - Store: `*p = q` → ∀o ∈ pts(p): pts(q) ⊆ pts(o)
@@ -55,6 +56,13 @@ pub enum Constraint {
         pointer: Location,
         target: Location,
     },
+    /// if (cond) p = &x: x is added to pts(p), but only on some paths
+    ConditionalAddressOf {
+        pointer: Location,
+        target: Location,
+        /// Branch condition guarding the assignment (e.g. "x", "!(x)")
+        condition: String,
+    },
     /// p = q: pts(q) ⊆ pts(p)
     Copy {
         dest: Location,
@@ -92,6 +100,14 @@ pub enum Constraint {
         dest: Location,
         array: String,
     },
+    /// p = container_of(q, struct T, member): p points to a T whose
+    /// `member` field is what q points to
+    ContainerOf {
+        dest: Location,
+        member_ptr: Location,
+        struct_type: String,
+        member: String,
+    },
 }
 
 /// Result of pointer analysis
@@ -101,6 +117,8 @@ pub struct PointsToResult {
     pub points_to: HashMap<String, HashSet<String>>,
     /// Function pointer targets: call_site -> possible functions
     pub func_ptr_targets: HashMap<String, HashSet<String>>,
+    /// Targets assigned under a branch condition: pointer -> targets
+    pub conditional_targets: HashMap<String, HashSet<String>>,
 }
 
 impl PointsToResult {
@@ -109,6 +127,11 @@ impl PointsToResult {
         self.points_to.get(ptr)
     }
 
+    /// Whether any target of `ptr` was assigned under a branch condition
+    pub fn is_conditional(&self, ptr: &str) -> bool {
+        self.conditional_targets.contains_key(ptr)
+    }
+
     /// Get all function names that a pointer might point to
     pub fn get_function_targets(&self, ptr: &str) -> Vec<String> {
         self.points_to
@@ -170,7 +193,9 @@ impl AndersenSolver {
     /// Initialize points-to sets from AddressOf constraints
     fn initialize(&mut self) {
         for constraint in &self.constraints {
-            if let Constraint::AddressOf { pointer, target } = constraint {
+            if let Constraint::AddressOf { pointer, target }
+            | Constraint::ConditionalAddressOf { pointer, target, .. } = constraint
+            {
                 let ptr_key = Self::loc_key(pointer);
                 let tgt_key = Self::loc_key(target);
 
@@ -227,7 +252,7 @@ impl AndersenSolver {
             // Process each constraint
             for constraint in self.constraints.clone() {
                 match constraint {
-                    Constraint::AddressOf { .. } => {
+                    Constraint::AddressOf { .. } | Constraint::ConditionalAddressOf { .. } => {
                         // Already handled in initialize
                     }
                     Constraint::Copy { dest, src } => {
@@ -273,11 +298,19 @@ impl AndersenSolver {
                         let base_ptr_key = Self::loc_key(&base_ptr);
                         let src_key = Self::loc_key(&src);
 
-                        // For each o in pts(base_ptr), union pts(src) into pts(o.field)
+                        // For each o in pts(base_ptr), union pts(src) into pts(o.field);
+                        // a function is stored as itself, as in ArrayStore
                         if let Some(bases) = self.pts.get(&base_ptr_key).cloned() {
                             for base in bases {
                                 let field_key = format!("{}.{}", base, field);
-                                self.union_pts(&field_key, &src_key);
+                                match &src {
+                                    Location::Function(_) => {
+                                        self.add_to_pts(&field_key, &src_key);
+                                    }
+                                    _ => {
+                                        self.union_pts(&field_key, &src_key);
+                                    }
+                                }
                             }
                         }
                     }
@@ -304,6 +337,29 @@ impl AndersenSolver {
                         let array_key = format!("{}[]", array);
                         self.union_pts(&dest_key, &array_key);
                     }
+                    Constraint::ContainerOf {
+                        dest,
+                        member_ptr,
+                        struct_type,
+                        member,
+                    } => {
+                        // Without an allocation site, the object is the type itself
+                        let dest_key = Self::loc_key(&dest);
+                        let member_ptr_key = Self::loc_key(&member_ptr);
+                        let object = Self::loc_key(&Location::Alloc(struct_type));
+                        self.add_to_pts(&dest_key, &object);
+                        self.add_to_pts(&member_ptr_key, &format!("{}.{}", object, member));
+
+                        // q already points into a known object: p points to that object
+                        let suffix = format!(".{}", member);
+                        if let Some(targets) = self.pts.get(&member_ptr_key).cloned() {
+                            for target in targets {
+                                if let Some(container) = target.strip_suffix(&suffix) {
+                                    self.add_to_pts(&dest_key, container);
+                                }
+                            }
+                        }
+                    }
                 }
             }
 
@@ -314,10 +370,21 @@ impl AndersenSolver {
         }
 
         // Build result
-        let mut result = PointsToResult::default();
-        result.points_to = self.pts.iter()
-            .map(|(k, v)| (k.clone(), v.clone()))
-            .collect();
+        let mut result = PointsToResult {
+            points_to: self.pts.clone(),
+            ..Default::default()
+        };
+
+        // Record which pointers were assigned conditionally
+        for constraint in &self.constraints {
+            if let Constraint::ConditionalAddressOf { pointer, target, .. } = constraint {
+                result
+                    .conditional_targets
+                    .entry(Self::loc_key(pointer))
+                    .or_default()
+                    .insert(Self::loc_key(target));
+            }
+        }
 
         // Extract function pointer targets
         for (loc, targets) in &self.pts {
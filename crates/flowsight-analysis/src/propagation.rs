@@ -37,9 +37,20 @@ impl ConstantPropagator {
         }
     }
 
+    /// Resolve named constants (e.g. `GFP_KERNEL`) during evaluation
+    pub fn set_constants(&mut self, constants: HashMap<String, i64>) {
+        self.evaluator.set_constants(constants);
+    }
+
+    /// Resolve object-like macros from the source (`ParseResult::defines`) too
+    pub fn add_defines(&mut self, defines: &HashMap<String, String>) {
+        self.evaluator.add_defines(defines);
+    }
+
     /// Initialize with scenario bindings
     pub fn init_from_bindings(&mut self, bindings: &[(String, SymbolicValue)]) {
         self.vars.clear();
+        self.evaluator.clear_bindings();
         for (path, value) in bindings {
             self.vars.insert(path.clone(), value.clone());
             self.evaluator.set(path, value.clone());
@@ -155,8 +166,8 @@ impl ConstantPropagator {
                 let var = cond[..pos].trim().to_string();
                 let val_str = cond[pos + op.len()..].trim();
 
-                let val = if val_str.starts_with("0x") {
-                    i64::from_str_radix(&val_str[2..], 16).ok()?
+                let val = if let Some(hex) = val_str.strip_prefix("0x") {
+                    i64::from_str_radix(hex, 16).ok()?
                 } else {
                     val_str.parse::<i64>().ok()?
                 };
@@ -212,7 +223,8 @@ impl ConstantPropagator {
     /// Restore state from a snapshot
     pub fn restore_state(&mut self, state: HashMap<String, SymbolicValue>) {
         self.vars = state.clone();
-        self.evaluator = Evaluator::with_bindings(state);
+        let constants = self.evaluator.constants().clone();
+        self.evaluator = Evaluator::with_bindings(state).with_constants(constants);
     }
 }
 
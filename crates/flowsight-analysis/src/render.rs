@@ -0,0 +1,194 @@
+//! Plain-text tree rendering
+//!
+//! Draws a `FlowNode` tree with `├──` / `└──` connectors (or their ASCII
+//! equivalents), continuing the `│` rails of every open ancestor so deep
+//! trees stay readable.
+
+use crate::classification::Confidence;
+use flowsight_core::{ConfidenceLevel, FlowNode};
+
+/// Characters used to draw the tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Charset {
+    /// `|--`, `` `-- `` and `|`
+    Ascii,
+    /// `├──`, `└──` and `│`
+    #[default]
+    Unicode,
+}
+
+/// How to render a tree
+#[derive(Debug, Clone, Default)]
+pub struct TreeStyle {
+    pub charset: Charset,
+    /// Append the source line of each node, e.g. `L42`
+    pub line_numbers: bool,
+    /// Append the execution context, e.g. `[HardIrq]`
+    pub contexts: bool,
+    /// Prefix each call with its confidence symbol
+    pub confidence: bool,
+}
+
+impl TreeStyle {
+    pub fn ascii() -> Self {
+        Self {
+            charset: Charset::Ascii,
+            ..Default::default()
+        }
+    }
+
+    pub fn unicode() -> Self {
+        Self::default()
+    }
+}
+
+/// Connector strings for one charset
+struct Glyphs {
+    branch: &'static str,
+    last: &'static str,
+    rail: &'static str,
+    blank: &'static str,
+}
+
+impl Charset {
+    fn glyphs(self) -> Glyphs {
+        match self {
+            Charset::Ascii => Glyphs {
+                branch: "|-- ",
+                last: "`-- ",
+                rail: "|   ",
+                blank: "    ",
+            },
+            Charset::Unicode => Glyphs {
+                branch: "├── ",
+                last: "└── ",
+                rail: "│   ",
+                blank: "    ",
+            },
+        }
+    }
+}
+
+/// Render `node` and its descendants, one node per line
+pub fn tree_to_string(node: &FlowNode, style: &TreeStyle) -> String {
+    let glyphs = style.charset.glyphs();
+    let mut out = String::new();
+    out.push_str(&label(node, style));
+    out.push('\n');
+    write_children(node, style, &glyphs, &mut String::new(), &mut out);
+    out
+}
+
+fn write_children(node: &FlowNode, style: &TreeStyle, glyphs: &Glyphs, prefix: &mut String, out: &mut String) {
+    for (i, child) in node.children.iter().enumerate() {
+        let is_last = i + 1 == node.children.len();
+        out.push_str(prefix);
+        out.push_str(if is_last { glyphs.last } else { glyphs.branch });
+        out.push_str(&label(child, style));
+        out.push('\n');
+
+        let len = prefix.len();
+        prefix.push_str(if is_last { glyphs.blank } else { glyphs.rail });
+        write_children(child, style, glyphs, prefix, out);
+        prefix.truncate(len);
+    }
+}
+
+fn label(node: &FlowNode, style: &TreeStyle) -> String {
+    let mut label = String::new();
+    if style.confidence {
+        if let Some(confidence) = &node.confidence {
+            let symbol = match (style.charset, confidence.level) {
+                (Charset::Unicode, ConfidenceLevel::Certain) => Confidence::Certain.symbol(),
+                (Charset::Unicode, ConfidenceLevel::Possible) => Confidence::Possible.symbol(),
+                (Charset::Unicode, ConfidenceLevel::Unknown) => Confidence::Unknown.symbol(),
+                (Charset::Ascii, ConfidenceLevel::Certain) => "+",
+                (Charset::Ascii, ConfidenceLevel::Possible) => "?",
+                (Charset::Ascii, ConfidenceLevel::Unknown) => "!",
+            };
+            label.push_str(symbol);
+            label.push(' ');
+        }
+    }
+    label.push_str(&node.display_name);
+    if style.line_numbers {
+        if let Some(loc) = &node.location {
+            label.push_str(&format!("  L{}", loc.line));
+        }
+    }
+    if style.contexts {
+        if let Some(context) = &node.execution_context {
+            label.push_str(&format!("  [{:?}]", context));
+        }
+    }
+    label
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowsight_core::{CallConfidence, ExecutionContext, FlowNodeType, Location};
+
+    fn node(name: &str, line: u32, children: Vec<FlowNode>) -> FlowNode {
+        FlowNode {
+            id: name.to_string(),
+            name: name.to_string(),
+            display_name: format!("{}()", name),
+            location: Some(Location::new("test.c", line, 0)),
+            node_type: FlowNodeType::Function,
+            children,
+            ..Default::default()
+        }
+    }
+
+    fn sample() -> FlowNode {
+        node(
+            "probe",
+            1,
+            vec![
+                node("setup", 2, vec![node("alloc", 3, vec![node("zero", 4, vec![])]), node("map", 5, vec![])]),
+                node("start", 6, vec![node("enable", 7, vec![])]),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_unicode_tree() {
+        assert_eq!(
+            tree_to_string(&sample(), &TreeStyle::unicode()),
+            "probe()\n\
+             ├── setup()\n\
+             │   ├── alloc()\n\
+             │   │   └── zero()\n\
+             │   └── map()\n\
+             └── start()\n\
+             \x20   └── enable()\n"
+        );
+    }
+
+    #[test]
+    fn test_ascii_tree_with_annotations() {
+        let mut tree = sample();
+        tree.children.truncate(1);
+        tree.children[0].children.truncate(1);
+        tree.children[0].confidence = Some(CallConfidence {
+            level: ConfidenceLevel::Possible,
+            reason: "ops table".into(),
+        });
+        tree.children[0].execution_context = Some(ExecutionContext::HardIrq);
+
+        let style = TreeStyle {
+            line_numbers: true,
+            contexts: true,
+            confidence: true,
+            ..TreeStyle::ascii()
+        };
+        assert_eq!(
+            tree_to_string(&tree, &style),
+            "probe()  L1\n\
+             `-- ? setup()  L2  [HardIrq]\n\
+             \x20   `-- alloc()  L3\n\
+             \x20       `-- zero()  L4\n"
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! SARIF 2.1.0 output
+//!
+//! Renders checker findings as a SARIF log, which GitHub code scanning and
+//! GitLab show inline on pull/merge requests.
+
+use crate::finding::{Finding, Severity, RULES};
+use flowsight_core::Location;
+use serde_json::{json, Value};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const TOOL_URI: &str = "https://github.com/TbusOS/flowsight";
+
+/// Build a SARIF log with one run containing `findings`
+pub fn to_sarif(findings: &[Finding]) -> Value {
+    let rules: Vec<Value> = RULES
+        .iter()
+        .map(|rule| {
+            json!({
+                "id": rule.id,
+                "shortDescription": { "text": rule.description },
+                "defaultConfiguration": { "level": level(rule.severity) },
+            })
+        })
+        .collect();
+
+    let results: Vec<Value> = findings
+        .iter()
+        .map(|finding| {
+            let rule = finding.rule();
+            let related: Vec<Value> = finding
+                .related_locations()
+                .iter()
+                .enumerate()
+                .map(|(i, (loc, message))| {
+                    let mut value = physical_location(loc);
+                    value["id"] = json!(i);
+                    value["message"] = json!({ "text": message });
+                    value
+                })
+                .collect();
+            let mut result = json!({
+                "ruleId": rule.id,
+                "ruleIndex": RULES.iter().position(|r| r.id == rule.id),
+                "level": level(rule.severity),
+                "message": { "text": finding.message() },
+                "locations": [physical_location(&finding.location())],
+                "relatedLocations": related,
+            });
+            if let Some(suggestion) = finding.suggestion() {
+                // Code scanning UIs only show the message, so repeat it there
+                result["message"]["text"] = json!(format!("{}\nSuggestion: {}", finding.message(), suggestion));
+                result["properties"] = json!({ "suggestion": suggestion });
+            }
+            result
+        })
+        .collect();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "flowsight",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": TOOL_URI,
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }]
+    })
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+    }
+}
+
+/// SARIF `location` object; columns are 1-based and omitted when unknown
+fn physical_location(loc: &Location) -> Value {
+    let mut region = json!({ "startLine": loc.line });
+    if loc.column > 0 {
+        region["startColumn"] = json!(loc.column + 1);
+    }
+    json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": loc.file.replace('\\', "/") },
+            "region": region,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_check::UncheckedAllocation;
+
+    #[test]
+    fn test_sarif_output() {
+        let findings = Finding::from_unchecked(
+            "drivers/foo/foo.c",
+            vec![UncheckedAllocation {
+                api: "kzalloc".into(),
+                variable: "priv".into(),
+                function: "foo_probe".into(),
+                line: 12,
+                use_line: 13,
+            }],
+        );
+        let log = to_sarif(&findings);
+
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "unchecked-result");
+
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], "unchecked-result");
+        assert_eq!(result["ruleIndex"], 0);
+        assert_eq!(result["level"], "warning");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "drivers/foo/foo.c");
+        assert_eq!(location["region"]["startLine"], 12);
+        assert!(location["region"].get("startColumn").is_none());
+        assert_eq!(result["relatedLocations"][0]["physicalLocation"]["region"]["startLine"], 13);
+        assert_eq!(
+            result["properties"]["suggestion"],
+            "add `if (!priv) return -ENOMEM;` after the call to kzalloc()"
+        );
+
+        assert_eq!(to_sarif(&[])["runs"][0]["results"], json!([]));
+    }
+}
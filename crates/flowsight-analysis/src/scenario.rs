@@ -3,17 +3,21 @@
 //! Core feature: Execute code symbolically with user-defined parameter values
 //! to visualize execution paths and variable states.
 
-use flowsight_core::{ExecutionContext, FlowNode, FlowNodeType, Location};
+use flowsight_core::{ExecutionContext, FlowNode, FlowNodeType, FunctionDef, Location};
+use flowsight_knowledge::{AsyncTimeline, KnowledgeBase};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::io;
 use std::path::Path;
 
+use crate::callgraph::{async_pattern_name, core_context};
+use crate::evaluator::EvalResult;
 use crate::propagation::{ConstantPropagator, BranchResult};
 
 /// User-defined scenario for analysis
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Scenario {
     /// Scenario name (for saving/loading)
     pub name: String,
@@ -27,7 +31,7 @@ pub struct Scenario {
 }
 
 /// Parameter value binding
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ValueBinding {
     /// Variable path (e.g., "id->idVendor", "ptr", "dev.name")
     pub path: String,
@@ -36,7 +40,7 @@ pub struct ValueBinding {
 }
 
 /// Symbolic value types
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type", content = "value")]
 pub enum SymbolicValue {
     /// Concrete integer value (supports hex: "0x1234")
@@ -187,6 +191,26 @@ impl Scenario {
         self
     }
 
+    /// Template for `func`: one unknown binding per named parameter, hinted
+    /// with the parameter's type, ready to be filled in
+    pub fn from_function(func: &FunctionDef) -> Self {
+        let mut scenario = Self::new(&func.name, &func.name);
+        for param in func.params.iter().filter(|p| !p.name.is_empty()) {
+            scenario.bind(
+                &param.name,
+                SymbolicValue::Unknown {
+                    hint: Some(param.type_name.clone()),
+                },
+            );
+        }
+        scenario
+    }
+
+    /// Scenario as YAML
+    pub fn to_yaml(&self) -> Result<String, ScenarioError> {
+        serde_yaml::to_string(self).map_err(|e| ScenarioError::Yaml(e.to_string()))
+    }
+
     /// Save scenario to a JSON file
     pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<(), ScenarioError> {
         let json = serde_json::to_string_pretty(self)?;
@@ -203,9 +227,7 @@ impl Scenario {
 
     /// Save scenario to a YAML file
     pub fn save_yaml<P: AsRef<Path>>(&self, path: P) -> Result<(), ScenarioError> {
-        let yaml = serde_yaml::to_string(self)
-            .map_err(|e| ScenarioError::Yaml(e.to_string()))?;
-        fs::write(path, yaml)?;
+        fs::write(path, self.to_yaml()?)?;
         Ok(())
     }
 
@@ -251,7 +273,7 @@ impl Scenario {
 }
 
 /// A collection of scenarios for a project
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
 pub struct ScenarioCollection {
     /// Collection name
     pub name: String,
@@ -297,7 +319,7 @@ impl ScenarioCollection {
 }
 
 /// Scenario execution options
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ScenarioOptions {
     /// Follow async callbacks
     #[serde(default = "default_true")]
@@ -318,6 +340,17 @@ fn default_depth() -> usize {
     10
 }
 
+impl ScenarioOptions {
+    /// Whether `node` is kernel code hidden by `show_kernel_api: false`
+    ///
+    /// Covers kernel API calls and the kernel call chains leading into user
+    /// callbacks; when hiding the latter, show their children in their place.
+    pub fn hides(&self, node: &FlowNode) -> bool {
+        !self.show_kernel_api
+            && (node.is_kernel_internal || matches!(node.node_type, FlowNodeType::KernelApi))
+    }
+}
+
 impl Default for ScenarioOptions {
     fn default() -> Self {
         Self {
@@ -329,7 +362,7 @@ impl Default for ScenarioOptions {
 }
 
 /// Program state at a specific point
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ProgramState {
     /// Current location
     pub location: Location,
@@ -344,7 +377,7 @@ pub struct ProgramState {
 }
 
 /// Execution path result
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ExecutionPath {
     /// States along the path
     pub states: Vec<ProgramState>,
@@ -354,6 +387,25 @@ pub struct ExecutionPath {
     pub termination_reason: Option<String>,
     /// Flow tree with variable annotations
     pub flow_tree: Option<FlowNode>,
+    /// Phases of a flow that crosses an async boundary; empty otherwise
+    #[serde(default)]
+    pub timeline: Vec<TimelineSegment>,
+}
+
+/// One phase of an execution path, run in a single context
+///
+/// An interrupt handler that queues work splits into the hard IRQ top half
+/// and the workqueue bottom half that a kworker runs later.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TimelineSegment {
+    /// Phase name, from the knowledge base timeline when one matches (e.g. "中断上半部")
+    pub name: String,
+    /// Execution context of the phase
+    pub context: ExecutionContext,
+    /// User functions run in this phase, in path order
+    pub functions: Vec<String>,
+    /// What happens between the phase that started this one and this one
+    pub separation: Option<String>,
 }
 
 /// Scenario executor with constant propagation
@@ -364,6 +416,10 @@ pub struct ScenarioExecutor {
     path: Vec<ProgramState>,
     /// Options
     options: ScenarioOptions,
+    /// Knowledge base timelines by async pattern name (e.g. "work_struct")
+    timelines: HashMap<String, AsyncTimeline>,
+    /// Parsed functions, to bind call arguments to callee parameters
+    functions: HashMap<String, FunctionDef>,
 }
 
 impl ScenarioExecutor {
@@ -373,9 +429,42 @@ impl ScenarioExecutor {
             propagator: ConstantPropagator::new(),
             path: Vec::new(),
             options,
+            timelines: HashMap::new(),
+            functions: HashMap::new(),
         }
     }
 
+    /// Resolve named constants (e.g. `KnowledgeBase::constants`) in branch conditions
+    pub fn with_constants(mut self, constants: HashMap<String, i64>) -> Self {
+        self.propagator.set_constants(constants);
+        self
+    }
+
+    /// Resolve the source's object-like macros (`ParseResult::defines`) in
+    /// branch conditions, on top of any constants already given
+    pub fn with_defines(mut self, defines: &HashMap<String, String>) -> Self {
+        self.propagator.add_defines(defines);
+        self
+    }
+
+    /// Name and separate async phases after the timelines in `kb`
+    pub fn with_async_timelines(mut self, kb: &KnowledgeBase) -> Self {
+        self.timelines = kb
+            .async_patterns
+            .iter()
+            .filter_map(|(name, pattern)| Some((name.clone(), pattern.timeline.clone()?)))
+            .collect();
+        self
+    }
+
+    /// Carry constant arguments into callees: walking into a call, each
+    /// parameter of the callee takes the value of its argument, and the
+    /// fields bound under the argument (`id->idVendor`) move under the parameter
+    pub fn with_functions(mut self, functions: &HashMap<String, FunctionDef>) -> Self {
+        self.functions = functions.clone();
+        self
+    }
+
     /// Execute scenario on a flow tree
     pub fn execute(&mut self, scenario: &Scenario, flow_tree: &FlowNode) -> ExecutionPath {
         // Initialize propagator from scenario bindings
@@ -394,9 +483,41 @@ impl ScenarioExecutor {
             completed: true,
             termination_reason: None,
             flow_tree: Some(annotated_tree),
+            timeline: self.build_timeline(flow_tree),
         }
     }
 
+    /// Execute every scenario in `collection` on the flow tree of its entry function
+    ///
+    /// Results are keyed by scenario name, in collection order. A scenario whose
+    /// entry function has no flow tree yields an incomplete path saying so.
+    pub fn execute_collection(
+        &mut self,
+        collection: &ScenarioCollection,
+        flow_trees: &[FlowNode],
+    ) -> Vec<(String, ExecutionPath)> {
+        collection
+            .scenarios
+            .iter()
+            .map(|scenario| {
+                let path = match flow_trees.iter().find(|t| t.name == scenario.entry_function) {
+                    Some(tree) => self.execute(scenario, tree),
+                    None => ExecutionPath {
+                        states: Vec::new(),
+                        completed: false,
+                        termination_reason: Some(format!(
+                            "Entry function '{}' not found in flow trees",
+                            scenario.entry_function
+                        )),
+                        flow_tree: None,
+                        timeline: Vec::new(),
+                    },
+                };
+                (scenario.name.clone(), path)
+            })
+            .collect()
+    }
+
     fn walk_tree(&mut self, node: &FlowNode, depth: usize, reachable: bool) -> FlowNode {
         if depth > self.options.max_depth {
             return node.clone();
@@ -410,7 +531,7 @@ impl ScenarioExecutor {
             location: node.location.clone().unwrap_or_default(),
             function: node.name.clone(),
             variables,
-            branch_condition: None,
+            branch_condition: node.case.as_ref().and_then(|case| case.condition()),
             reachable,
         };
         self.path.push(state);
@@ -437,16 +558,33 @@ impl ScenarioExecutor {
             })
             .collect();
 
+        // A switch's `default` arm is dead once one of its cases surely matches
+        let case_matched = filtered_children
+            .iter()
+            .any(|child| self.case_result(child) == Some(BranchResult::AlwaysTrue));
+
         // Process children with reachability
+        let mut calls_seen: HashMap<&str, usize> = HashMap::new();
         let children: Vec<FlowNode> = filtered_children.into_iter()
             .map(|child| {
+                let nth = calls_seen.entry(child.name.as_str()).or_insert(0);
+                let caller_state = self.bind_arguments(&node.name, &child.name, *nth);
+                *nth += 1;
                 // Check if this is a conditional branch
                 let child_reachable = if reachable {
-                    self.check_branch_reachability(child)
+                    match &child.case {
+                        Some(case) if case.value.is_none() => !case_matched,
+                        Some(_) => self.case_result(child) != Some(BranchResult::AlwaysFalse),
+                        None => self.check_branch_reachability(child),
+                    }
                 } else {
                     false // Parent unreachable means children unreachable
                 };
-                self.walk_tree(child, depth + 1, child_reachable)
+                let walked = self.walk_tree(child, depth + 1, child_reachable);
+                if let Some(state) = caller_state {
+                    self.propagator.restore_state(state);
+                }
+                walked
             })
             .collect();
 
@@ -463,9 +601,172 @@ impl ScenarioExecutor {
             can_sleep: node.can_sleep,
             source_file: node.source_file.clone(),
             is_kernel_internal: node.is_kernel_internal,
+            case: node.case.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Split the flow into phases at the async callbacks it reaches
+    fn build_timeline(&self, flow_tree: &FlowNode) -> Vec<TimelineSegment> {
+        let mut segments = Vec::new();
+        self.collect_segments(flow_tree, None, 0, &mut segments);
+        if segments.len() < 2 {
+            return Vec::new();
+        }
+        segments
+    }
+
+    fn collect_segments(
+        &self,
+        node: &FlowNode,
+        current: Option<usize>,
+        depth: usize,
+        segments: &mut Vec<TimelineSegment>,
+    ) {
+        if depth > self.options.max_depth {
+            return;
+        }
+
+        // Kernel call chains above an entry point belong to no phase
+        let current = if node.is_kernel_internal {
+            current
+        } else {
+            let index = match (current, &node.node_type) {
+                (Some(parent), FlowNodeType::AsyncCallback { mechanism }) => {
+                    let segment = self.async_segment(node, mechanism, &mut segments[parent]);
+                    segments.push(segment);
+                    segments.len() - 1
+                }
+                (Some(index), _) => index,
+                (None, _) => {
+                    segments.push(TimelineSegment {
+                        name: format!("{}()", node.name),
+                        context: node.execution_context.clone().unwrap_or(ExecutionContext::Unknown),
+                        functions: Vec::new(),
+                        separation: None,
+                    });
+                    segments.len() - 1
+                }
+            };
+            let is_user_code = matches!(
+                node.node_type,
+                FlowNodeType::Function | FlowNodeType::EntryPoint | FlowNodeType::AsyncCallback { .. }
+            );
+            if is_user_code && !segments[index].functions.contains(&node.name) {
+                segments[index].functions.push(node.name.clone());
+            }
+            Some(index)
+        };
+
+        for child in &node.children {
+            self.collect_segments(child, current, depth + 1, segments);
+        }
+    }
+
+    /// Start the phase of an async callback reached from `parent`
+    ///
+    /// With a matching knowledge base timeline, the phase takes its name and
+    /// separation note, and a first phase in the timeline's first context is
+    /// renamed after it too.
+    fn async_segment(
+        &self,
+        node: &FlowNode,
+        mechanism: &flowsight_core::AsyncMechanism,
+        parent: &mut TimelineSegment,
+    ) -> TimelineSegment {
+        let context = node.execution_context.clone().unwrap_or(ExecutionContext::Unknown);
+        let timeline = async_pattern_name(mechanism, context.can_sleep())
+            .and_then(|pattern| self.timelines.get(pattern));
+        let Some(timeline) = timeline else {
+            return TimelineSegment {
+                name: format!("{}()", node.name),
+                context,
+                functions: Vec::new(),
+                separation: None,
+            };
+        };
+
+        let same_context = |a: &ExecutionContext, b: &ExecutionContext| {
+            std::mem::discriminant(a) == std::mem::discriminant(b)
+        };
+        if parent.separation.is_none() && same_context(&parent.context, &core_context(&timeline.phase1.context)) {
+            parent.name = timeline.phase1.name.clone();
+        }
+        TimelineSegment {
+            name: timeline.phase2.name.clone(),
+            context: match context {
+                ExecutionContext::Unknown => core_context(&timeline.phase2.context),
+                known => known,
+            },
+            functions: Vec::new(),
+            separation: Some(timeline.separation.clone()),
         }
     }
 
+    /// Bind the arguments of the `nth` call from `caller` to `callee` to the
+    /// callee's parameters; returns the caller's state to restore after the call
+    fn bind_arguments(&mut self, caller: &str, callee: &str, nth: usize) -> Option<HashMap<String, SymbolicValue>> {
+        let site = self.functions.get(caller)?.call_sites.iter().filter(|s| s.callee == callee).nth(nth)?;
+        let params = &self.functions.get(callee)?.params;
+
+        let mut bound = Vec::new();
+        for (param, arg) in params.iter().zip(&site.arguments) {
+            if param.name.is_empty() {
+                continue;
+            }
+            let arg = arg.trim();
+            // `&dev` passes `dev.x` on as `param->x`
+            let (base, by_address) = match arg.strip_prefix('&') {
+                Some(base) => (base.trim(), true),
+                None => (arg, false),
+            };
+            for (path, value) in self.propagator.all_vars() {
+                let Some(rest) = path.strip_prefix(base) else {
+                    continue;
+                };
+                let field = match (by_address, rest.strip_prefix('.')) {
+                    (true, Some(member)) => format!("->{}", member),
+                    (false, _) if rest.starts_with("->") || rest.starts_with('.') => rest.to_string(),
+                    _ => continue,
+                };
+                bound.push((format!("{}{}", param.name, field), value.clone()));
+            }
+            if let Some(value) = self.argument_value(arg) {
+                bound.push((param.name.clone(), value));
+            }
+        }
+        if bound.is_empty() {
+            return None;
+        }
+
+        let caller_state = self.propagator.clone_state();
+        for (path, value) in bound {
+            self.propagator.set_var(&path, value);
+        }
+        Some(caller_state)
+    }
+
+    /// Value of argument expression `arg` in the current state, if known
+    fn argument_value(&self, arg: &str) -> Option<SymbolicValue> {
+        if let Some(value) = self.propagator.get_var(arg) {
+            return Some(value.clone());
+        }
+        match self.propagator.eval_expr(arg) {
+            EvalResult::Integer(n) => Some(SymbolicValue::Integer(n)),
+            EvalResult::Bool(b) => Some(SymbolicValue::Integer(i64::from(b))),
+            EvalResult::String(s) => Some(SymbolicValue::String(s)),
+            EvalResult::Pointer { is_null } => Some(SymbolicValue::Pointer { is_null, size: None }),
+            EvalResult::Unknown => None,
+        }
+    }
+
+    /// Whether the scenario takes the switch arm `node` stands for; `None`
+    /// for nodes that are not a `case` arm
+    fn case_result(&mut self, node: &FlowNode) -> Option<BranchResult> {
+        let condition = node.case.as_ref()?.condition()?;
+        Some(self.propagator.eval_condition(&condition))
+    }
+
     /// Check if a branch is reachable based on conditions
     fn check_branch_reachability(&mut self, node: &FlowNode) -> bool {
         // Check if node name contains condition hints
@@ -547,7 +848,7 @@ pub fn annotate_flow_tree(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use flowsight_core::FlowNodeType;
+    use flowsight_core::Parameter;
 
     #[test]
     fn test_parse_integer() {
@@ -605,13 +906,9 @@ mod tests {
             display_name: "main()".to_string(),
             location: Some(Location::new("test.c", 1, 0)),
             node_type: FlowNodeType::Function,
-            children: vec![],
-            description: None,
-            confidence: None,
             execution_context: Some(ExecutionContext::Process),
             can_sleep: Some(true),
-            source_file: None,
-            is_kernel_internal: false,
+            ..Default::default()
         };
 
         let mut executor = ScenarioExecutor::new(ScenarioOptions::default());
@@ -649,22 +946,14 @@ mod tests {
                     name: "if_ptr_null".to_string(),
                     display_name: "if (ptr == NULL)".to_string(),
                     location: Some(Location::new("test.c", 2, 0)),
-                    node_type: FlowNodeType::Function,
-                    children: vec![],
-                    description: None,
-                    confidence: None,
                     execution_context: Some(ExecutionContext::Process),
                     can_sleep: Some(true),
-                    source_file: None,
-                    is_kernel_internal: false,
+                    ..Default::default()
                 },
             ],
-            description: None,
-            confidence: None,
             execution_context: Some(ExecutionContext::Process),
             can_sleep: Some(true),
-            source_file: None,
-            is_kernel_internal: false,
+            ..Default::default()
         };
 
         let mut executor = ScenarioExecutor::new(ScenarioOptions::default());
@@ -689,6 +978,27 @@ mod tests {
         assert_eq!(scenario.bindings.len(), 3);
     }
 
+    #[test]
+    fn test_scenario_from_function() {
+        let func = FunctionDef {
+            name: "usb_probe".into(),
+            return_type: "int".into(),
+            params: vec![
+                Parameter { name: "intf".into(), type_name: "struct usb_interface*".into() },
+                Parameter { name: "".into(), type_name: "int".into() },
+            ],
+            ..Default::default()
+        };
+        let scenario = Scenario::from_function(&func);
+        assert_eq!(scenario.entry_function, "usb_probe");
+        assert_eq!(scenario.bindings.len(), 1);
+        assert_eq!(scenario.bindings[0].path, "intf");
+        assert_eq!(scenario.bindings[0].value.display(), "<?:struct usb_interface*>");
+
+        let loaded: Scenario = serde_yaml::from_str(&scenario.to_yaml().unwrap()).unwrap();
+        assert_eq!(loaded.bindings[0].path, "intf");
+    }
+
     #[test]
     fn test_scenario_save_load_json() {
         let mut scenario = Scenario::new("test_scenario", "main");
@@ -780,5 +1090,164 @@ mod tests {
 
         let _ = fs::remove_file(&temp_path);
     }
-}
 
+    #[test]
+    fn test_execute_collection() {
+        fn node(name: &str, children: Vec<FlowNode>) -> FlowNode {
+            FlowNode {
+                id: name.to_string(),
+                name: name.to_string(),
+                display_name: name.to_string(),
+                location: Some(Location::new("test.c", 1, 0)),
+                node_type: FlowNodeType::Function,
+                children,
+                execution_context: Some(ExecutionContext::Process),
+                ..Default::default()
+            }
+        }
+        let trees = vec![node("check_ptr", vec![node("if_ptr_null", vec![])])];
+
+        let mut collection = ScenarioCollection::new("ptr");
+        let mut null_ptr = Scenario::new("null_ptr", "check_ptr");
+        null_ptr.bind("ptr", SymbolicValue::Pointer { is_null: true, size: None });
+        let mut valid_ptr = Scenario::new("valid_ptr", "check_ptr");
+        valid_ptr.bind("ptr", SymbolicValue::Pointer { is_null: false, size: None });
+        collection.add(null_ptr);
+        collection.add(valid_ptr);
+        // No bindings: the previous scenario's `ptr` must not carry over
+        collection.add(Scenario::new("unbound", "check_ptr"));
+        collection.add(Scenario::new("missing", "no_such_fn"));
+
+        let mut executor = ScenarioExecutor::new(ScenarioOptions::default());
+        let results = executor.execute_collection(&collection, &trees);
+
+        let names: Vec<&str> = results.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["null_ptr", "valid_ptr", "unbound", "missing"]);
+
+        let branch_reached: Vec<bool> = results[..3]
+            .iter()
+            .map(|(_, path)| path.states[1].reachable)
+            .collect();
+        assert_eq!(branch_reached, vec![true, false, true]);
+
+        let missing = &results[3].1;
+        assert!(!missing.completed);
+        assert!(missing.flow_tree.is_none());
+        assert!(missing.termination_reason.as_ref().unwrap().contains("no_such_fn"));
+    }
+
+    #[test]
+    fn test_arguments_bound_to_callee_params() {
+        fn node(name: &str, children: Vec<FlowNode>) -> FlowNode {
+            FlowNode {
+                id: name.to_string(),
+                name: name.to_string(),
+                display_name: name.to_string(),
+                node_type: FlowNodeType::Function,
+                children,
+                execution_context: Some(ExecutionContext::Process),
+                ..Default::default()
+            }
+        }
+        fn func(name: &str, params: &[&str], calls: &[(&str, &[&str])]) -> FunctionDef {
+            FunctionDef {
+                name: name.into(),
+                return_type: "int".into(),
+                params: params
+                    .iter()
+                    .map(|p| Parameter { name: p.to_string(), type_name: "int".into() })
+                    .collect(),
+                calls: calls.iter().map(|(callee, _)| callee.to_string()).collect(),
+                call_sites: calls
+                    .iter()
+                    .map(|(callee, args)| flowsight_core::CallSite {
+                        callee: callee.to_string(),
+                        line: 0,
+                        column: 0,
+                        arguments: args.iter().map(|a| a.to_string()).collect(),
+                    })
+                    .collect(),
+                ..Default::default()
+            }
+        }
+
+        let functions: HashMap<String, FunctionDef> = [
+            func("probe", &["id"], &[("match_id", &["id"]), ("set_mode", &["2"]), ("set_mode", &["id->idVendor"])]),
+            func("match_id", &["ident"], &[]),
+            func("set_mode", &["mode"], &[]),
+        ]
+        .into_iter()
+        .map(|f| (f.name.clone(), f))
+        .collect();
+        let tree = node(
+            "probe",
+            vec![node("match_id", vec![]), node("set_mode", vec![]), node("set_mode", vec![])],
+        );
+
+        let mut scenario = Scenario::new("vendor", "probe");
+        scenario.bind("id->idVendor", SymbolicValue::Integer(0x1234));
+        let mut executor = ScenarioExecutor::new(ScenarioOptions::default()).with_functions(&functions);
+        let result = executor.execute(&scenario, &tree);
+
+        let vars: Vec<_> = result.states.iter().map(|s| &s.variables).collect();
+        assert!(matches!(vars[1].get("ident->idVendor"), Some(SymbolicValue::Integer(0x1234))));
+        assert!(matches!(vars[2].get("mode"), Some(SymbolicValue::Integer(2))));
+        assert!(matches!(vars[3].get("mode"), Some(SymbolicValue::Integer(0x1234))));
+        // Parameters go out of scope when the call returns
+        assert!(!vars[2].contains_key("ident->idVendor"));
+    }
+
+    #[test]
+    fn test_async_timeline() {
+        use flowsight_parser::treesitter::TreeSitterParser;
+
+        let source = r#"
+static struct work_struct my_work;
+static void my_work_handler(struct work_struct *work) {
+    process_data();
+}
+static irqreturn_t my_irq(int irq, void *dev) {
+    ack_hw();
+    schedule_work(&my_work);
+    return IRQ_HANDLED;
+}
+static int my_probe(struct platform_device *pdev) {
+    INIT_WORK(&my_work, my_work_handler);
+    return request_irq(10, my_irq, 0, "my", NULL);
+}
+static void ack_hw(void) { }
+static void process_data(void) { }
+"#;
+        let mut parse_result = TreeSitterParser::new().parse_source(source, "irq.c").unwrap();
+        let analysis = crate::Analyzer::new().analyze(source, &mut parse_result).unwrap();
+        let kb = KnowledgeBase::builtin();
+        // Entry trees are rooted at the injected kernel call chain
+        fn find<'a>(node: &'a FlowNode, name: &str) -> Option<&'a FlowNode> {
+            if node.name == name {
+                return Some(node);
+            }
+            node.children.iter().find_map(|c| find(c, name))
+        }
+        let tree = |name: &str| analysis.flow_trees.iter().find_map(|t| find(t, name)).unwrap();
+
+        let mut executor = ScenarioExecutor::new(ScenarioOptions::default()).with_async_timelines(&kb);
+        let path = executor.execute(&Scenario::new("irq", "my_irq"), tree("my_irq"));
+        let timeline = kb.get_async_timeline("work_struct").unwrap();
+
+        assert_eq!(path.timeline.len(), 2);
+        let (top, bottom) = (&path.timeline[0], &path.timeline[1]);
+        assert_eq!(top.name, timeline.phase1.name);
+        assert!(matches!(top.context, ExecutionContext::HardIrq));
+        assert_eq!(top.functions, vec!["my_irq", "ack_hw"]);
+        assert!(top.separation.is_none());
+        assert_eq!(bottom.name, timeline.phase2.name);
+        assert!(matches!(bottom.context, ExecutionContext::Process));
+        assert_eq!(bottom.functions, vec!["my_work_handler", "process_data"]);
+        assert_eq!(bottom.separation.as_deref(), Some(timeline.separation.as_str()));
+
+        // A flow that stays in one context has no phases
+        let handler = tree("my_irq").children.iter().find(|c| c.name == "my_work_handler").unwrap();
+        let path = executor.execute(&Scenario::new("work", "my_work_handler"), handler);
+        assert!(path.timeline.is_empty());
+    }
+}
@@ -0,0 +1,46 @@
+//! JSON Schemas for FlowSight's JSON output
+//!
+//! Generated from the serde types themselves, so the schemas cannot drift
+//! from what `--format json` actually prints.
+
+use crate::scenario::Scenario;
+use crate::AnalysisResult;
+use flowsight_core::FlowNode;
+use flowsight_parser::ParseResult;
+use schemars::schema::RootSchema;
+use schemars::schema_for;
+
+/// Types with a published schema
+pub const SCHEMA_TYPES: &[&str] = &["ParseResult", "AnalysisResult", "FlowNode", "Scenario"];
+
+/// JSON Schema for one of [`SCHEMA_TYPES`]
+pub fn schema(type_name: &str) -> Option<RootSchema> {
+    match type_name {
+        "ParseResult" => Some(schema_for!(ParseResult)),
+        "AnalysisResult" => Some(schema_for!(AnalysisResult)),
+        "FlowNode" => Some(schema_for!(FlowNode)),
+        "Scenario" => Some(schema_for!(Scenario)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schemas() {
+        for name in SCHEMA_TYPES {
+            let schema = serde_json::to_value(schema(name).unwrap()).unwrap();
+            assert_eq!(schema["title"], *name);
+        }
+        assert!(schema("Nope").is_none());
+
+        // FlowNode is recursive, so its children refer back to the definition
+        let flow = serde_json::to_value(schema("FlowNode").unwrap()).unwrap();
+        assert_eq!(flow["properties"]["children"]["items"]["$ref"], "#/definitions/FlowNode");
+
+        let analysis = serde_json::to_value(schema("AnalysisResult").unwrap()).unwrap();
+        assert!(analysis["definitions"]["AsyncBinding"].is_object());
+    }
+}
@@ -1,14 +1,14 @@
 //! Tests for the FlowSight analysis engine
 
 use super::*;
+use flowsight_core::{CallKind, CallType, Confidence};
 use flowsight_parser::treesitter::TreeSitterParser;
 
 /// Test basic analyzer creation
 #[test]
 fn test_analyzer_creation() {
     let analyzer = Analyzer::new();
-    assert!(true); // Analyzer created successfully
-    drop(analyzer);
+    assert!(!analyzer.knowledge_base.frameworks.is_empty());
 }
 
 /// Test async tracking for INIT_WORK pattern
@@ -123,6 +123,97 @@ static void caller(void) {
     assert!(edge.is_some(), "Should find caller->helper edge");
 }
 
+/// Test that progress is reported once per entry point, in order
+#[test]
+fn test_analyze_with_progress() {
+    let source = r#"
+static void my_work_handler(struct work_struct *work) {
+    helper();
+}
+static int __init my_init(void) {
+    INIT_WORK(&my_work, my_work_handler);
+    return 0;
+}
+static void __exit my_exit(void) {
+}
+module_init(my_init);
+module_exit(my_exit);
+"#;
+    let mut parser = TreeSitterParser::new();
+    let mut parse_result = parser.parse_source(source, "test.c").unwrap();
+    let mut reported = Vec::new();
+    let result = Analyzer::new()
+        .analyze_with_progress(source, &mut parse_result, |p| {
+            reported.push((p.current, p.total, p.entry.to_string()))
+        })
+        .unwrap();
+
+    assert_eq!(reported.len(), result.entry_points.len());
+    assert_eq!(reported[0], (1, 3, "my_init".to_string()));
+    assert_eq!(reported[2].0, 3);
+}
+
+/// Test that parallel analysis matches analyzing each file on its own
+#[cfg(feature = "parallel")]
+#[test]
+fn test_analyze_many() {
+    let sources = [
+        r#"
+static void my_work_handler(struct work_struct *work) {
+    helper();
+}
+static int __init my_init(void) {
+    INIT_WORK(&my_work, my_work_handler);
+    return 0;
+}
+module_init(my_init);
+"#,
+        r#"
+static int my_probe(struct platform_device *pdev) {
+    return setup(pdev);
+}
+static struct platform_driver my_driver = {
+    .probe = my_probe,
+};
+"#,
+    ];
+    let mut parser = TreeSitterParser::new();
+    let mut files: Vec<(String, ParseResult)> = sources
+        .iter()
+        .enumerate()
+        .map(|(i, source)| {
+            let parse_result = parser.parse_source(source, &format!("f{}.c", i)).unwrap();
+            (source.to_string(), parse_result)
+        })
+        .collect();
+    let mut serial_files = files.clone();
+
+    let mut analyzer = Analyzer::new();
+    let parallel = analyzer.analyze_many(&mut files);
+    assert_eq!(parallel.len(), sources.len());
+
+    for ((par, (_, par_parse)), (source, parse_result)) in
+        parallel.iter().zip(&files).zip(serial_files.iter_mut())
+    {
+        let serial = analyzer.analyze(source, parse_result).unwrap();
+        let mut par_entries = par.entry_points.clone();
+        let mut serial_entries = serial.entry_points.clone();
+        par_entries.sort();
+        serial_entries.sort();
+        assert_eq!(par_entries, serial_entries);
+        assert_eq!(par.call_edges.len(), serial.call_edges.len());
+        assert_eq!(par.async_bindings.len(), serial.async_bindings.len());
+        assert_eq!(par.flow_trees.len(), serial.flow_trees.len());
+        for (name, func) in &parse_result.functions {
+            assert_eq!(par_parse.functions[name].callback_context, func.callback_context);
+        }
+    }
+    assert_eq!(
+        files[1].1.functions["my_probe"].callback_context.as_deref(),
+        Some("my_driver.probe")
+    );
+}
+
 /// Test complete USB driver analysis
 #[test]
 fn test_usb_driver_analysis() {
@@ -169,3 +260,742 @@ module_init(my_probe);
         "work_handler should be marked as callback"
     );
 }
+
+/// Test that flow trees are truncated by AnalysisConfig limits
+#[test]
+fn test_flow_tree_limits() {
+    let source = r#"
+static void leaf(void) {
+    a(); b(); c(); d(); e();
+}
+
+static void mid(void) {
+    leaf();
+}
+
+static int top(void) {
+    mid();
+    return 0;
+}
+module_init(top);
+"#;
+    let mut parser = TreeSitterParser::new();
+    let mut parse_result = parser.parse_source(source, "test.c").unwrap();
+
+    let config = AnalysisConfig {
+        max_flow_depth: 1,
+        max_children_per_node: 2,
+    };
+    let mut analyzer = Analyzer::new();
+    let result = analyzer
+        .analyze_with_config(source, &mut parse_result, &config)
+        .unwrap();
+
+    let top = result.flow_trees.iter().find(|t| t.name == "top").unwrap();
+    let mid = &top.children[0];
+    assert_eq!(mid.name, "mid");
+    assert_eq!(mid.children.len(), 1);
+    assert_eq!(mid.children[0].display_name, "... 1 more");
+
+    let mut parse_result = parser.parse_source(source, "test.c").unwrap();
+    let config = AnalysisConfig {
+        max_flow_depth: 20,
+        max_children_per_node: 2,
+    };
+    let result = analyzer
+        .analyze_with_config(source, &mut parse_result, &config)
+        .unwrap();
+    let top = result.flow_trees.iter().find(|t| t.name == "top").unwrap();
+    let leaf = &top.children[0].children[0];
+    assert_eq!(leaf.children.len(), 3);
+    assert_eq!(leaf.children[2].display_name, "... 3 more");
+}
+
+/// Calls through a function pointer variable become indirect edges, one per
+/// target, with branch assignments only `Medium`
+#[test]
+fn test_pointer_variable_call_edges() {
+    let source = r#"
+static void handler_a(void) {}
+static void handler_b(void) {}
+static void handler_c(void) {}
+
+static void run(int x)
+{
+    void (*fp)(void);
+    void (*gp)(void);
+
+    if (x)
+        fp = handler_a;
+    else
+        fp = handler_b;
+    fp();
+    gp = handler_c;
+    gp();
+}
+"#;
+    let mut parser = TreeSitterParser::new();
+    let mut parse_result = parser.parse_source(source, "test.c").unwrap();
+    let mut analyzer = Analyzer::new();
+    let result = analyzer.analyze(source, &mut parse_result).unwrap();
+
+    let edge = |callee: &str| {
+        result
+            .call_edges
+            .iter()
+            .find(|e| e.caller == "run" && e.callee == callee)
+            .unwrap()
+    };
+    for callee in ["handler_a", "handler_b"] {
+        assert_eq!(edge(callee).call_kind, CallKind::Indirect);
+        assert_eq!(edge(callee).location.as_ref().unwrap().line, 15);
+        assert!(matches!(
+            edge(callee).call_type,
+            CallType::Indirect {
+                confidence: Confidence::Medium
+            }
+        ));
+    }
+    assert_eq!(edge("handler_c").location.as_ref().unwrap().line, 17);
+    assert!(matches!(
+        edge("handler_c").call_type,
+        CallType::Indirect {
+            confidence: Confidence::High
+        }
+    ));
+}
+
+/// Test CONFIG_* variant comparison
+#[test]
+fn test_config_variants() {
+    let source = r#"
+static void my_work_handler(struct work_struct *work) {
+}
+
+#ifdef CONFIG_PM
+static void my_pm_handler(struct work_struct *work) {
+}
+
+static int my_suspend(struct device *dev) {
+    return 0;
+}
+#endif
+
+static int probe(struct usb_interface *intf) {
+    struct my_device *dev = get_dev(intf);
+    INIT_WORK(&dev->work, my_work_handler);
+#if IS_ENABLED(CONFIG_PM)
+    INIT_WORK(&dev->pm_work, my_pm_handler);
+#endif
+    return 0;
+}
+"#;
+    let mut analyzer = Analyzer::new();
+    let variants = analyzer
+        .analyze_config_variants(source, "test.c", "CONFIG_PM", &HashMap::new())
+        .unwrap();
+
+    assert!(variants.is_config_dependent());
+    assert_eq!(variants.enabled_only_functions, vec!["my_pm_handler", "my_suspend"]);
+    assert!(variants.disabled_only_functions.is_empty());
+    assert_eq!(variants.enabled_only_bindings.len(), 1);
+    assert_eq!(variants.enabled_only_bindings[0].handler, "my_pm_handler");
+    assert_eq!(variants.disabled.analysis.async_bindings.len(), 1);
+
+    // Locations stay aligned with the original source
+    let probe = &variants.enabled.parse_result.functions["probe"];
+    assert_eq!(probe.location.as_ref().unwrap().line, 14);
+}
+
+/// Test call kinds on call edges
+#[test]
+fn test_call_edge_kinds() {
+    let source = r#"
+static int my_open(struct inode *i, struct file *f) { return 0; }
+
+static const struct file_operations my_fops = {
+    .open = my_open,
+};
+
+static void helper(void) {}
+
+static int do_open(struct file *filp) {
+    struct my_dev *dev = container_of(filp, struct my_dev, file);
+    helper();
+    INIT_WORK(&dev->work, NULL);
+    return filp->f_op->open(NULL, filp);
+}
+"#;
+    let mut parser = TreeSitterParser::new();
+    let mut parse_result = parser.parse_source(source, "test.c").unwrap();
+    let mut analyzer = Analyzer::new();
+    let result = analyzer.analyze(source, &mut parse_result).unwrap();
+
+    let kind = |callee: &str| {
+        result
+            .call_edges
+            .iter()
+            .find(|e| e.caller == "do_open" && e.callee == callee)
+            .map(|e| e.call_kind)
+    };
+    assert_eq!(kind("helper"), Some(CallKind::Direct));
+    assert_eq!(kind("container_of"), Some(CallKind::Macro));
+    assert_eq!(kind("INIT_WORK"), Some(CallKind::Macro));
+    assert_eq!(kind("my_open"), Some(CallKind::Indirect));
+
+    let edge = result.call_edges.iter().find(|e| e.callee == "my_open").unwrap();
+    assert_eq!(edge.location.as_ref().unwrap().line, 14);
+    // `f_op` is only matched on the field name
+    assert!(matches!(
+        edge.call_type,
+        CallType::Indirect {
+            confidence: Confidence::Medium
+        }
+    ));
+}
+
+/// Ops table calls are matched on the receiver's variable or struct type,
+/// never inside comments or strings
+#[test]
+fn test_ops_table_call_edges() {
+    let source = r#"
+static int my_open(struct inode *i, struct file *f) { return 0; }
+static int blk_open(struct block_device *b, int mode) { return 0; }
+
+static const struct file_operations my_fops = {
+    .open = my_open,
+};
+
+static const struct block_device_operations my_bops = {
+    .open = blk_open,
+};
+
+static int via_table(void) {
+    return my_fops.open(NULL, NULL);
+}
+
+static int via_typed(const struct file_operations *fops) {
+    return fops->open(NULL, NULL);
+}
+
+static int in_comment(void) {
+    /* fops->open(NULL, NULL) */
+    pr_info("->open(");
+    return 0;
+}
+"#;
+    let mut parser = TreeSitterParser::new();
+    let mut parse_result = parser.parse_source(source, "test.c").unwrap();
+    let mut analyzer = Analyzer::new();
+    let result = analyzer.analyze(source, &mut parse_result).unwrap();
+
+    let callees = |caller: &str| {
+        result
+            .call_edges
+            .iter()
+            .filter(|e| e.caller == caller)
+            .filter_map(|e| match e.call_type {
+                CallType::Indirect { confidence } => Some((e.callee.as_str(), format!("{:?}", confidence))),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    let high = |callee| vec![(callee, "High".to_string())];
+    assert_eq!(callees("via_table"), high("my_open"));
+    assert_eq!(callees("via_typed"), high("my_open"));
+    assert!(callees("in_comment").is_empty());
+}
+
+/// Reverse flow tree links an async handler back to its trigger and registration
+#[test]
+fn test_reverse_flow_tree() {
+    let source = r#"
+static void do_rx(struct my_dev *dev) {}
+
+static void rx_work(struct work_struct *work) {
+    do_rx(NULL);
+}
+
+static irqreturn_t my_irq(int irq, void *data) {
+    struct my_dev *dev = data;
+    schedule_work(&dev->work);
+    return IRQ_HANDLED;
+}
+
+static int my_probe(struct platform_device *pdev) {
+    struct my_dev *dev = devm_kzalloc(&pdev->dev, sizeof(*dev), GFP_KERNEL);
+    INIT_WORK(&dev->work, rx_work);
+    return request_irq(dev->irq, my_irq, 0, "my", dev);
+}
+"#;
+    let mut parser = TreeSitterParser::new();
+    let mut parse_result = parser.parse_source(source, "test.c").unwrap();
+    let mut analyzer = Analyzer::new();
+    let result = analyzer.analyze(source, &mut parse_result).unwrap();
+
+    let tree = callgraph::build_reverse_flow_tree(
+        "do_rx",
+        &parse_result,
+        &result.async_bindings,
+        &mut std::collections::HashSet::new(),
+        0,
+        &AnalysisConfig::default(),
+    )
+    .unwrap();
+
+    assert_eq!(tree.name, "do_rx");
+    let work = &tree.children[0];
+    assert_eq!(work.name, "rx_work");
+
+    let names: Vec<&str> = work.children.iter().map(|c| c.name.as_str()).collect();
+    assert!(names.contains(&"my_irq"), "{:?}", names);
+    assert!(names.contains(&"my_probe"), "{:?}", names);
+    let irq = work.children.iter().find(|c| c.name == "my_irq").unwrap();
+    assert!(irq.display_name.contains("triggers rx_work()"), "{}", irq.display_name);
+    // The irq handler leads back to the probe that requested it
+    assert!(irq.children.iter().any(|c| c.name == "my_probe"));
+
+    assert!(callgraph::build_reverse_flow_tree(
+        "missing",
+        &parse_result,
+        &result.async_bindings,
+        &mut std::collections::HashSet::new(),
+        0,
+        &AnalysisConfig::default(),
+    )
+    .is_none());
+}
+
+/// Flow trees follow calls and async handlers into other files of the module
+#[test]
+fn test_analyze_files() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let core = dir.path().join("core.c");
+    let hw = dir.path().join("hw.c");
+    std::fs::write(
+        &core,
+        r#"
+static int my_probe(struct platform_device *pdev) {
+    INIT_WORK(&priv->work, my_work_fn);
+    return my_hw_init(pdev);
+}
+
+static struct platform_driver my_driver = {
+    .probe = my_probe,
+};
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        &hw,
+        r#"
+int my_hw_init(struct platform_device *pdev) {
+    return my_hw_reset(pdev);
+}
+
+int my_hw_reset(struct platform_device *pdev) { return 0; }
+
+void my_work_fn(struct work_struct *work) {}
+"#,
+    )
+    .unwrap();
+
+    let mut analyzer = Analyzer::new();
+    let module = analyzer
+        .analyze_files(&[core, hw], &AnalysisConfig::default())
+        .unwrap();
+
+    fn find<'a>(node: &'a FlowNode, name: &str) -> Option<&'a FlowNode> {
+        if node.name == name {
+            return Some(node);
+        }
+        node.children.iter().find_map(|c| find(c, name))
+    }
+
+    let probe = module
+        .analysis
+        .flow_trees
+        .iter()
+        .find_map(|t| find(t, "my_probe"))
+        .unwrap();
+    let init = find(probe, "my_hw_init").unwrap();
+    assert!(matches!(init.node_type, flowsight_core::FlowNodeType::Function));
+    assert!(find(init, "my_hw_reset").is_some());
+
+    // Handler registered in core.c, defined in hw.c
+    assert!(module.analysis.entry_points.contains(&"my_work_fn".to_string()));
+    assert!(module.parse_result.functions["my_work_fn"].is_callback);
+    let binding = &module.analysis.async_bindings[0];
+    assert!(binding.bind_location.as_ref().unwrap().file.ends_with("core.c"));
+    assert_eq!(module.parse_result.functions["my_hw_reset"].called_by, vec!["my_hw_init"]);
+}
+
+/// Flow node ids depend on the call path, not on line numbers or build order
+#[test]
+fn test_stable_flow_node_ids() {
+    let build = |source: &str| {
+        let mut parser = TreeSitterParser::new();
+        let parse_result = parser.parse_source(source, "test.c").unwrap();
+        callgraph::build_flow_tree(
+            "my_probe",
+            &parse_result,
+            &[],
+            &mut std::collections::HashSet::new(),
+            0,
+            &AnalysisConfig::default(),
+        )
+        .unwrap()
+    };
+    fn ids(node: &FlowNode, out: &mut Vec<String>) {
+        out.push(node.id.clone());
+        for child in &node.children {
+            ids(child, out);
+        }
+    }
+
+    let before = build(
+        r#"
+static void helper(void) { kfree(NULL); }
+static int my_probe(void) { helper(); kfree(NULL); return 0; }
+"#,
+    );
+    let after = build(
+        r#"
+/* unrelated edit shifts every line */
+static int unused(void) { return 1; }
+
+static void helper(void) { kfree(NULL); }
+static int my_probe(void) { helper(); kfree(NULL); return 0; }
+"#,
+    );
+
+    let (mut a, mut b) = (Vec::new(), Vec::new());
+    ids(&before, &mut a);
+    ids(&after, &mut b);
+    assert_eq!(a, b);
+
+    // kfree() appears under both probe and helper but keeps distinct ids
+    let unique: std::collections::HashSet<&String> = a.iter().collect();
+    assert_eq!(unique.len(), a.len(), "{:?}", a);
+    assert!(before.id.starts_with("my_probe-"));
+}
+
+/// Callees inherit their entry's context; async callbacks switch to their own
+#[test]
+fn test_execution_context_propagation() {
+    let source = r#"
+static void log_event(struct my_dev *dev) {
+    dev->count++;
+}
+
+static void my_work_handler(struct work_struct *work) {
+    log_event(NULL);
+}
+
+static irqreturn_t my_irq_handler(int irq, void *data) {
+    struct my_dev *dev = data;
+    log_event(dev);
+    schedule_work(&dev->work);
+    return IRQ_HANDLED;
+}
+
+static int my_probe(struct platform_device *pdev) {
+    struct my_dev *dev = get_dev(pdev);
+    INIT_WORK(&dev->work, my_work_handler);
+    request_irq(dev->irq, my_irq_handler, 0, "my", dev);
+    return 0;
+}
+"#;
+    let mut parser = TreeSitterParser::new();
+    let mut parse_result = parser.parse_source(source, "test.c").unwrap();
+    let result = Analyzer::new().analyze(source, &mut parse_result).unwrap();
+
+    fn find<'a>(node: &'a FlowNode, name: &str) -> Option<&'a FlowNode> {
+        if node.name == name {
+            return Some(node);
+        }
+        node.children.iter().find_map(|c| find(c, name))
+    }
+    let irq = result
+        .flow_trees
+        .iter()
+        .find_map(|t| find(t, "my_irq_handler"))
+        .expect("irq handler tree");
+    assert!(matches!(irq.execution_context, Some(flowsight_core::ExecutionContext::HardIrq)));
+
+    let helper = find(irq, "log_event").unwrap();
+    assert!(matches!(helper.execution_context, Some(flowsight_core::ExecutionContext::HardIrq)));
+    assert_eq!(helper.can_sleep, Some(false));
+
+    // The work queued from the handler runs later, in process context
+    let work = find(irq, "my_work_handler").unwrap();
+    assert!(matches!(work.execution_context, Some(flowsight_core::ExecutionContext::Process)));
+    assert_eq!(find(work, "log_event").unwrap().can_sleep, Some(true));
+}
+
+/// Calls after `goto err_*` labels hang off branch nodes, not the normal path
+#[test]
+fn test_goto_unwind_branches() {
+    let source = r#"
+static int my_probe(struct device *dev) {
+    struct my_priv *priv;
+    int ret;
+
+    priv = kzalloc(sizeof(*priv), GFP_KERNEL);
+    if (!priv)
+        return -ENOMEM;
+    ret = clk_prepare_enable(priv->clk);
+    if (ret)
+        goto err_free;
+    ret = request_irq(priv->irq, my_irq, 0, "my", priv);
+    if (ret)
+        goto err_clk;
+    mutex_lock(&priv->lock);
+    ret = 0;
+out:
+    mutex_unlock(&priv->lock);
+    return ret;
+
+err_clk:
+    clk_disable_unprepare(priv->clk);
+err_free:
+    kfree(priv);
+    return ret;
+}
+"#;
+    let mut parser = TreeSitterParser::new();
+    let parse_result = parser.parse_source(source, "test.c").unwrap();
+
+    let probe = &parse_result.functions["my_probe"];
+    let labels: Vec<(&str, bool, &[u32])> = probe
+        .labels
+        .iter()
+        .map(|l| (l.name.as_str(), l.fallthrough, l.gotos.as_slice()))
+        .collect();
+    assert_eq!(
+        labels,
+        vec![("out", true, &[][..]), ("err_clk", false, &[14][..]), ("err_free", true, &[11][..])]
+    );
+    assert_eq!(probe.unwind_path("err_clk"), vec!["clk_disable_unprepare", "kfree"]);
+
+    let tree = callgraph::build_flow_tree(
+        "my_probe",
+        &parse_result,
+        &[],
+        &mut std::collections::HashSet::new(),
+        0,
+        &AnalysisConfig::default(),
+    )
+    .unwrap();
+    let names: Vec<&str> = tree.children.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "clk_prepare_enable",
+            "kzalloc",
+            "mutex_lock",
+            "mutex_unlock",
+            "request_irq",
+            "goto err_clk",
+            "goto err_free",
+        ]
+    );
+
+    let err_clk = &tree.children[5];
+    let unwind: Vec<&str> = err_clk.children.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(unwind, vec!["clk_disable_unprepare", "kfree"]);
+    assert_eq!(err_clk.location.as_ref().unwrap().line, 21);
+    assert!(matches!(
+        err_clk.confidence.as_ref().unwrap().level,
+        flowsight_core::ConfidenceLevel::Possible
+    ));
+    let unwind: Vec<&str> = tree.children[6].children.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(unwind, vec!["kfree"]);
+}
+
+/// Switch arms become case branches that scenarios can rule out
+#[test]
+fn test_switch_case_branches() {
+    use crate::scenario::{Scenario, ScenarioExecutor, ScenarioOptions, SymbolicValue};
+
+    let source = r#"
+static long my_ioctl(struct file *f, unsigned int cmd, unsigned long arg) {
+    struct my_dev *dev = f->private_data;
+    mutex_lock(&dev->lock);
+    switch (cmd) {
+    case MY_RESET:
+        my_reset(dev);
+        break;
+    case MY_START:
+        my_prepare(dev);
+        /* fallthrough */
+    case MY_RESTART:
+        my_start(dev);
+        break;
+    default:
+        mutex_unlock(&dev->lock);
+        return -ENOTTY;
+    }
+    mutex_unlock(&dev->lock);
+    return 0;
+}
+"#;
+    let mut parser = TreeSitterParser::new();
+    let parse_result = parser.parse_source(source, "test.c").unwrap();
+
+    let ioctl = &parse_result.functions["my_ioctl"];
+    assert_eq!(ioctl.switches.len(), 1);
+    let switch = &ioctl.switches[0];
+    assert_eq!((switch.discriminant.as_str(), switch.line, switch.end_line), ("cmd", 5, 18));
+    let arms: Vec<(Option<&str>, bool)> = switch
+        .cases
+        .iter()
+        .map(|c| (c.value.as_deref(), c.fallthrough))
+        .collect();
+    assert_eq!(
+        arms,
+        vec![(Some("MY_RESET"), false), (Some("MY_START"), false), (Some("MY_RESTART"), true), (None, false)]
+    );
+    assert_eq!(switch.arm_calls(1), vec!["my_prepare", "my_start"]);
+
+    let tree = callgraph::build_flow_tree(
+        "my_ioctl",
+        &parse_result,
+        &[],
+        &mut std::collections::HashSet::new(),
+        0,
+        &AnalysisConfig::default(),
+    )
+    .unwrap();
+    let names: Vec<&str> = tree.children.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["mutex_lock", "mutex_unlock", "switch (cmd)"]);
+
+    let cases = &tree.children[2].children;
+    let names: Vec<&str> = cases.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["case MY_RESET", "case MY_START", "case MY_RESTART", "default"]);
+    let start: Vec<&str> = cases[1].children.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(start, vec!["my_prepare", "my_start"]);
+    assert_eq!(cases[1].case.as_ref().unwrap().value.as_deref(), Some("MY_START"));
+    assert_eq!(cases[3].location.as_ref().unwrap().line, 15);
+
+    let mut scenario = Scenario::new("start", "my_ioctl");
+    scenario.bind("cmd", SymbolicValue::Integer(2));
+    let constants = [("MY_RESET", 1), ("MY_START", 2), ("MY_RESTART", 3)]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+    let mut executor = ScenarioExecutor::new(ScenarioOptions::default()).with_constants(constants);
+    let annotated = executor.execute(&scenario, &tree).flow_tree.unwrap();
+    let unreachable: Vec<bool> = annotated.children[2]
+        .children
+        .iter()
+        .map(|c| c.description.as_deref().unwrap_or("").starts_with("[unreachable]"))
+        .collect();
+    assert_eq!(unreachable, vec![true, false, true, true]);
+}
+
+/// Functions called in a switch arm sit one level below the switch's
+/// function, like its other callees, when counting against the depth limit
+#[test]
+fn test_switch_arm_depth_limit() {
+    let source = r#"
+static void helper(void) {}
+
+static void my_reset(void) {
+    helper();
+}
+
+static long my_ioctl(unsigned int cmd) {
+    my_reset();
+    switch (cmd) {
+    case MY_RESET:
+        my_reset();
+        break;
+    }
+    return 0;
+}
+"#;
+    let mut parser = TreeSitterParser::new();
+    let parse_result = parser.parse_source(source, "test.c").unwrap();
+    let config = AnalysisConfig {
+        max_flow_depth: 1,
+        ..AnalysisConfig::default()
+    };
+    let tree = callgraph::build_flow_tree(
+        "my_ioctl",
+        &parse_result,
+        &[],
+        &mut std::collections::HashSet::new(),
+        0,
+        &config,
+    )
+    .unwrap();
+
+    let direct = &tree.children[0];
+    let arm = &tree.children[1].children[0];
+    assert_eq!(direct.name, "my_reset");
+    assert_eq!(arm.name, "case MY_RESET");
+    assert_eq!(arm.children.len(), 1);
+    assert_eq!(arm.children[0].name, "my_reset");
+    assert_eq!(arm.children[0].children.len(), direct.children.len());
+}
+
+/// One module tree: every entry point once, shared helpers expanded once
+#[test]
+fn test_build_forest() {
+    let source = r#"
+static struct work_struct my_work;
+
+static void helper(void) {
+    do_thing();
+}
+
+static void my_work_handler(struct work_struct *work) {
+    helper();
+}
+
+static int setup(void) {
+    helper();
+    INIT_WORK(&my_work, my_work_handler);
+    return 0;
+}
+
+static int __init my_init(void) {
+    return setup();
+}
+
+static void __exit my_exit(void) {
+    helper();
+}
+
+module_init(my_init);
+module_exit(my_exit);
+"#;
+    let mut parser = TreeSitterParser::new();
+    let mut parse_result = parser.parse_source(source, "test.c").unwrap();
+    let result = Analyzer::new().analyze(source, &mut parse_result).unwrap();
+    let forest = callgraph::build_forest(
+        &result.entry_points,
+        &parse_result,
+        &result.async_bindings,
+        &AnalysisConfig::default(),
+    );
+
+    assert_eq!(forest.name, "module");
+    let roots: Vec<&str> = forest.children.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(&roots[..2], &["my_init", "my_exit"]);
+    assert!(roots.contains(&"my_work_handler"));
+
+    fn collect<'a>(node: &'a FlowNode, name: &str, out: &mut Vec<&'a FlowNode>) {
+        if node.name == name {
+            out.push(node);
+        }
+        for child in &node.children {
+            collect(child, name, out);
+        }
+    }
+    let mut helpers = Vec::new();
+    collect(&forest, "helper", &mut helpers);
+    assert_eq!(helpers.len(), 3);
+    assert_eq!(helpers.iter().filter(|h| !h.children.is_empty()).count(), 1);
+}
@@ -77,7 +77,11 @@ impl TypeDatabase {
     }
 
     /// Check if a function is compatible with a function pointer type
-    pub fn is_compatible(&self, func_name: &str, type_name: &str) -> bool {
+    ///
+    /// A trailing `...` in the pointer type accepts any number of extra
+    /// arguments. With `strict_const`, const qualifiers must match exactly
+    /// (kernel ops tables); otherwise they are ignored (loosely typed callbacks).
+    pub fn is_compatible(&self, func_name: &str, type_name: &str, strict_const: bool) -> bool {
         let fp_type = match self.func_ptr_types.get(type_name) {
             Some(t) => t,
             None => return false,
@@ -89,18 +93,26 @@ impl TypeDatabase {
         };
 
         // Check return type compatibility
-        if !self.types_compatible(&func_sig.return_type, &fp_type.return_type) {
+        if !self.types_compatible(&func_sig.return_type, &fp_type.return_type, strict_const) {
             return false;
         }
 
         // Check parameter count
-        if func_sig.param_types.len() != fp_type.param_types.len() {
+        let (fixed_params, variadic) = match fp_type.param_types.split_last() {
+            Some((last, rest)) if last == "..." => (rest, true),
+            _ => (fp_type.param_types.as_slice(), false),
+        };
+        if variadic {
+            if func_sig.param_types.len() < fixed_params.len() {
+                return false;
+            }
+        } else if func_sig.param_types.len() != fixed_params.len() {
             return false;
         }
 
         // Check each parameter type
-        for (func_param, fp_param) in func_sig.param_types.iter().zip(&fp_type.param_types) {
-            if !self.types_compatible(func_param, fp_param) {
+        for (func_param, fp_param) in func_sig.param_types.iter().zip(fixed_params) {
+            if !self.types_compatible(func_param, fp_param, strict_const) {
                 return false;
             }
         }
@@ -109,13 +121,13 @@ impl TypeDatabase {
     }
 
     /// Build compatibility map for all types and functions
-    pub fn build_compatibility_map(&mut self) {
+    pub fn build_compatibility_map(&mut self, strict_const: bool) {
         self.compatible_funcs.clear();
 
         for type_name in self.func_ptr_types.keys() {
             let mut compatible = HashSet::new();
             for func_name in self.function_sigs.keys() {
-                if self.is_compatible(func_name, type_name) {
+                if self.is_compatible(func_name, type_name, strict_const) {
                     compatible.insert(func_name.clone());
                 }
             }
@@ -131,7 +143,11 @@ impl TypeDatabase {
     }
 
     /// Check if two types are compatible (simplified comparison)
-    fn types_compatible(&self, t1: &str, t2: &str) -> bool {
+    fn types_compatible(&self, t1: &str, t2: &str, strict_const: bool) -> bool {
+        if strict_const && const_count(t1) != const_count(t2) {
+            return false;
+        }
+
         let t1_norm = self.normalize_type(t1);
         let t2_norm = self.normalize_type(t2);
 
@@ -140,9 +156,24 @@ impl TypeDatabase {
             return true;
         }
 
-        // void* is compatible with any pointer
-        if (t1_norm == "void*" && t2_norm.ends_with('*')) ||
-           (t2_norm == "void*" && t1_norm.ends_with('*')) {
+        // Callbacks taking callbacks: compare the function pointer types part by part
+        match (self.funcptr_signature(&t1_norm), self.funcptr_signature(&t2_norm)) {
+            (Some((ret1, depth1, params1)), Some((ret2, depth2, params2))) => {
+                return depth1 == depth2
+                    && params1.len() == params2.len()
+                    && self.types_compatible(&ret1, &ret2, strict_const)
+                    && params1
+                        .iter()
+                        .zip(&params2)
+                        .all(|(p1, p2)| self.types_compatible(p1, p2, strict_const));
+            }
+            (None, None) => {}
+            _ => return false,
+        }
+
+        // void* converts to and from any object pointer, but `void *` is not `void **`
+        if (t1_norm == "void*" && pointer_depth(&t2_norm) == 1) ||
+           (t2_norm == "void*" && pointer_depth(&t1_norm) == 1) {
             return true;
         }
 
@@ -163,20 +194,55 @@ impl TypeDatabase {
             .replace("struct ", "")
             .replace("  ", " ")
             .trim()
-            .to_string()
+            // "char *" and "char*" are the same type, as are "void **" and "void**"
+            .replace(" *", "*")
     }
+
+    /// Return type, pointer depth and parameter types of a normalized
+    /// function pointer type: written out (`int(*)(file*)` has depth 1) or a
+    /// typedef name (`fault_fn`, or `fault_fn*` with depth 2)
+    fn funcptr_signature(&self, t: &str) -> Option<(String, usize, Vec<String>)> {
+        if let Some((ret, depth, params)) = split_funcptr_type(t) {
+            let params = params.into_iter().map(str::to_string).collect();
+            return Some((ret.to_string(), depth, params));
+        }
+        let name = t.trim_end_matches('*');
+        let typedef = self
+            .func_ptr_types
+            .get(name)
+            .filter(|fp| fp.definition_kind == FuncPtrDefKind::Typedef)?;
+        Some((typedef.return_type.clone(), 1 + pointer_depth(t), typedef.param_types.clone()))
+    }
+}
+
+/// Number of `const` qualifiers in a type string
+fn const_count(t: &str) -> usize {
+    t.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| *word == "const")
+        .count()
 }
 
 /// Type analyzer for C code
 pub struct TypeAnalyzer {
     /// Collected types
     database: TypeDatabase,
+    /// Require matching const qualifiers when building the compatibility map
+    strict_const: bool,
 }
 
 impl TypeAnalyzer {
     pub fn new() -> Self {
         Self {
             database: TypeDatabase::new(),
+            strict_const: false,
+        }
+    }
+
+    /// Create an analyzer that respects const-correctness when matching
+    pub fn with_strict_const(strict_const: bool) -> Self {
+        Self {
+            database: TypeDatabase::new(),
+            strict_const,
         }
     }
 
@@ -191,10 +257,11 @@ impl TypeAnalyzer {
             self.collect_typedefs(tree.root_node(), source);
             self.collect_struct_fields(tree.root_node(), source);
             self.collect_function_params(tree.root_node(), source);
+            self.collect_global_funcptrs(tree.root_node(), source);
             self.collect_function_signatures(tree.root_node(), source);
         }
 
-        self.database.build_compatibility_map();
+        self.database.build_compatibility_map(self.strict_const);
         &self.database
     }
 
@@ -228,20 +295,13 @@ impl TypeAnalyzer {
 
     fn parse_funcptr_typedef(&self, text: &str, line: u32) -> Option<FuncPtrType> {
         // Pattern: typedef <return_type> (*<name>)(<params>);
-        let re = regex::Regex::new(r"typedef\s+(\w+)\s*\(\s*\*\s*(\w+)\s*\)\s*\(([^)]*)\)").ok()?;
+        let re = regex::Regex::new(r"typedef\s+([\w\s\*]+?)\s*\(\s*\*\s*(\w+)\s*\)\s*\(").ok()?;
 
         if let Some(caps) = re.captures(text) {
-            let return_type = caps.get(1)?.as_str().to_string();
+            let return_type = text_return_type(caps.get(1)?.as_str());
             let name = caps.get(2)?.as_str().to_string();
-            let params_str = caps.get(3)?.as_str();
-
-            let param_types: Vec<String> = if params_str.trim().is_empty() || params_str.trim() == "void" {
-                Vec::new()
-            } else {
-                params_str.split(',')
-                    .map(|p| self.simplify_type(p.trim()))
-                    .collect()
-            };
+            let params_str = paren_contents(text, caps.get(0)?.end() - 1)?;
+            let param_types = self.text_param_types(params_str);
 
             return Some(FuncPtrType {
                 name,
@@ -255,6 +315,17 @@ impl TypeAnalyzer {
         None
     }
 
+    /// Parameter types of a parameter list written out as text
+    fn text_param_types(&self, params: &str) -> Vec<String> {
+        if params.trim().is_empty() || params.trim() == "void" {
+            return Vec::new();
+        }
+        split_top_level(params)
+            .into_iter()
+            .map(|p| self.simplify_type(p))
+            .collect()
+    }
+
     fn simplify_type(&self, param: &str) -> String {
         // Simplify type: "int arg" -> "int", "const void *ptr" -> "const void *"
         let param = param.trim();
@@ -262,6 +333,25 @@ impl TypeAnalyzer {
             return String::new();
         }
 
+        // Function pointer: "int (*fault)(struct vm_fault *vmf)" -> "int (*)(struct vm_fault *)"
+        if let Some(open) = param.find('(') {
+            let close = open + param[open..].find(')').unwrap_or(0);
+            // An array of function pointers is passed as a pointer to one
+            let depth = param[open..close].matches('*').count() + param[open..close].matches('[').count();
+            let params = param[close..]
+                .find('(')
+                .and_then(|i| paren_contents(param, close + i));
+            if let Some(params) = params.filter(|_| depth > 0) {
+                let params = self.text_param_types(params);
+                return format!(
+                    "{} ({})({})",
+                    text_return_type(&param[..open]),
+                    "*".repeat(depth),
+                    params.join(", ")
+                );
+            }
+        }
+
         // If ends with identifier (no * or &), remove it
         let parts: Vec<&str> = param.split_whitespace().collect();
         if parts.len() > 1 {
@@ -269,6 +359,12 @@ impl TypeAnalyzer {
             if !last.contains('*') && !last.contains('&') && last.chars().all(|c| c.is_alphanumeric() || c == '_') {
                 return parts[..parts.len()-1].join(" ");
             }
+            // "struct device *dev" -> "struct device *"
+            let stars = last.len() - last.trim_start_matches('*').len();
+            let ident = &last[stars..];
+            if stars > 0 && !ident.is_empty() && ident.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return format!("{} {}", parts[..parts.len()-1].join(" "), &last[..stars]);
+            }
         }
         param.to_string()
     }
@@ -345,6 +441,8 @@ impl TypeAnalyzer {
                 if !param_type.is_empty() {
                     params.push(param_type);
                 }
+            } else if child.kind() == "variadic_parameter" {
+                params.push("...".to_string());
             }
         }
         params
@@ -356,20 +454,59 @@ impl TypeAnalyzer {
 
         for child in node.children(&mut cursor) {
             match child.kind() {
-                "primitive_type" | "type_identifier" | "sized_type_specifier" => {
+                "type_qualifier" | "primitive_type" | "type_identifier" | "sized_type_specifier" => {
                     type_parts.push(self.node_text(child, source));
                 }
                 "struct_specifier" => {
                     type_parts.push(format!("struct {}", self.extract_struct_name(child, source).unwrap_or_default()));
                 }
-                "pointer_declarator" | "abstract_pointer_declarator" => {
-                    type_parts.push("*".to_string());
-                }
                 _ => {}
             }
         }
 
-        type_parts.join(" ").trim().to_string()
+        let base = type_parts.join(" ").trim().to_string();
+        match node.child_by_field_name("declarator") {
+            Some(declarator) => self.declarator_type(&base, declarator, source),
+            None => base,
+        }
+    }
+
+    /// Type declared by `declarator` on `base`: `**out` gives "void **", and
+    /// `(*fault)(struct vm_fault *)` gives "int (*)(struct vm_fault *)"
+    ///
+    /// Array parameters are pointers, so `*argv[]` is "char **" too.
+    fn declarator_type(&self, base: &str, declarator: Node, source: &str) -> String {
+        let mut node = declarator;
+        let mut depth = 0;
+        loop {
+            match node.kind() {
+                "pointer_declarator" | "abstract_pointer_declarator" | "array_declarator"
+                | "abstract_array_declarator" => {
+                    depth += 1;
+                    match node.child_by_field_name("declarator") {
+                        Some(inner) => node = inner,
+                        None => break,
+                    }
+                }
+                "function_declarator" | "abstract_function_declarator" => {
+                    let params = node
+                        .child_by_field_name("parameters")
+                        .map(|p| self.extract_param_types(p, source))
+                        .unwrap_or_default();
+                    let inner_depth = node
+                        .child_by_field_name("declarator")
+                        .map_or(1, |inner| funcptr_depth(inner).max(1));
+                    return format!(
+                        "{} ({})({})",
+                        pointer_type(base, depth),
+                        "*".repeat(inner_depth),
+                        params.join(", ")
+                    );
+                }
+                _ => break,
+            }
+        }
+        pointer_type(base, depth)
     }
 
     fn extract_struct_name(&self, node: Node, source: &str) -> Option<String> {
@@ -421,21 +558,14 @@ impl TypeAnalyzer {
     }
 
     fn parse_funcptr_field(&self, struct_name: &str, text: &str, line: u32) -> Option<FuncPtrType> {
-        // Pattern: <return_type> (*<name>)(<params>);
-        let re = regex::Regex::new(r"(\w+)\s*\(\s*\*\s*(\w+)\s*\)\s*\(([^)]*)\)").ok()?;
+        // Pattern: <return_type> (*<name>)(<params>); or an array of them, (*<name>[N])
+        let re = regex::Regex::new(r"^\s*([\w\s\*]+?)\s*\(\s*\*\s*(\w+)\s*(?:\[[^\]]*\]\s*)?\)\s*\(").ok()?;
 
         if let Some(caps) = re.captures(text) {
-            let return_type = caps.get(1)?.as_str().to_string();
+            let return_type = text_return_type(caps.get(1)?.as_str());
             let field_name = caps.get(2)?.as_str().to_string();
-            let params_str = caps.get(3)?.as_str();
-
-            let param_types: Vec<String> = if params_str.trim().is_empty() || params_str.trim() == "void" {
-                Vec::new()
-            } else {
-                params_str.split(',')
-                    .map(|p| self.simplify_type(p.trim()))
-                    .collect()
-            };
+            let params_str = paren_contents(text, caps.get(0)?.end() - 1)?;
+            let param_types = self.text_param_types(params_str);
 
             return Some(FuncPtrType {
                 name: format!("{}.{}", struct_name, field_name),
@@ -462,12 +592,14 @@ impl TypeAnalyzer {
     }
 
     fn extract_funcptr_params(&mut self, node: Node, source: &str) {
-        // Find function declarator and its parameter list
-        let func_name = self.find_function_name(node, source);
-
         let mut cursor = node.walk();
         for child in node.children(&mut cursor) {
             if child.kind() == "function_declarator" {
+                // `int a(void (*cb)(void)), b(...);` - each prototype has its own name
+                let func_name = self
+                    .extract_func_declarator(child, source)
+                    .map(|(name, _)| name)
+                    .unwrap_or_else(|| "unknown".to_string());
                 let mut inner_cursor = child.walk();
                 for inner_child in child.children(&mut inner_cursor) {
                     if inner_child.kind() == "parameter_list" {
@@ -478,18 +610,6 @@ impl TypeAnalyzer {
         }
     }
 
-    fn find_function_name(&self, node: Node, source: &str) -> String {
-        let mut cursor = node.walk();
-        for child in node.children(&mut cursor) {
-            if child.kind() == "function_declarator" {
-                if let Some((name, _)) = self.extract_func_declarator(child, source) {
-                    return name;
-                }
-            }
-        }
-        "unknown".to_string()
-    }
-
     fn process_params_for_funcptrs(&mut self, func_name: &str, node: Node, source: &str) {
         let mut cursor = node.walk();
         let mut param_index = 0;
@@ -549,6 +669,70 @@ impl TypeAnalyzer {
         })
     }
 
+    /// Collect file-scope function pointer variables
+    ///
+    /// Every declarator of `void (*f)(void), (*g)(int);` declares its own variable.
+    fn collect_global_funcptrs(&mut self, root: Node, source: &str) {
+        let mut cursor = root.walk();
+        for decl in root.children(&mut cursor) {
+            if decl.kind() != "declaration" {
+                continue;
+            }
+            let base_type = decl
+                .child_by_field_name("type")
+                .map(|t| self.node_text(t, source))
+                .unwrap_or_default();
+
+            let mut decl_cursor = decl.walk();
+            for declarator in decl.children_by_field_name("declarator", &mut decl_cursor) {
+                if let Some(fp_type) = self.global_funcptr(&base_type, declarator, source) {
+                    self.database.add_type(fp_type);
+                }
+            }
+        }
+    }
+
+    fn global_funcptr(&self, base_type: &str, declarator: Node, source: &str) -> Option<FuncPtrType> {
+        let mut node = declarator;
+        if node.kind() == "init_declarator" {
+            node = node.child_by_field_name("declarator")?;
+        }
+
+        // `int *(*f)(void)` returns `int *`
+        let mut depth = 0;
+        while node.kind() == "pointer_declarator" {
+            depth += 1;
+            node = node.child_by_field_name("declarator")?;
+        }
+        if node.kind() != "function_declarator" {
+            return None;
+        }
+
+        // A plain prototype has an identifier here; a pointer has `(*name)`
+        let inner = node.child_by_field_name("declarator")?;
+        if inner.kind() != "parenthesized_declarator" {
+            return None;
+        }
+        let mut inner_cursor = inner.walk();
+        let pointer = inner
+            .named_children(&mut inner_cursor)
+            .find(|c| c.kind() == "pointer_declarator")?;
+        let name = self.extract_declarator_name(pointer, source)?;
+
+        let param_types = node
+            .child_by_field_name("parameters")
+            .map(|p| self.extract_param_types(p, source))
+            .unwrap_or_default();
+
+        Some(FuncPtrType {
+            name,
+            return_type: pointer_type(base_type, depth),
+            param_types,
+            location: Some(format!("line:{}", declarator.start_position().row + 1)),
+            definition_kind: FuncPtrDefKind::GlobalVar,
+        })
+    }
+
     /// Collect all function signatures
     fn collect_function_signatures(&mut self, node: Node, source: &str) {
         if node.kind() == "function_definition" {
@@ -567,34 +751,53 @@ impl TypeAnalyzer {
         let mut cursor = node.walk();
         let children: Vec<Node> = node.children(&mut cursor).collect();
 
-        let mut return_type = String::new();
-        let mut func_name = None;
-        let mut param_types = Vec::new();
+        let mut base_type = String::new();
+        let mut declarator = None;
 
         for child in &children {
             match child.kind() {
-                "primitive_type" | "type_identifier" => {
-                    if return_type.is_empty() {
-                        return_type = self.node_text(*child, source);
-                    }
-                }
-                "pointer_declarator" => {
-                    // Return type is a pointer
-                    if return_type.is_empty() {
-                        return_type = "void*".to_string();
+                "primitive_type" | "type_identifier" | "sized_type_specifier"
+                    if base_type.is_empty() => {
+                        base_type = self.node_text(*child, source);
                     }
-                }
-                "function_declarator" => {
-                    if let Some((name, params)) = self.extract_func_declarator(*child, source) {
-                        func_name = Some(name);
-                        param_types = params;
+                "struct_specifier" | "union_specifier" | "enum_specifier"
+                    if base_type.is_empty() => {
+                        let keyword = child.kind().trim_end_matches("_specifier");
+                        let name = self.extract_struct_name(*child, source).unwrap_or_default();
+                        base_type = format!("{} {}", keyword, name);
                     }
+                "function_declarator" | "pointer_declarator" => {
+                    declarator = Some(*child);
                 }
                 _ => {}
             }
         }
 
-        let name = func_name?;
+        // Walk the pointer_declarator chain: `struct device **foo(...)` has depth 2
+        let mut declarator = declarator?;
+        let mut depth = 0;
+        while declarator.kind() == "pointer_declarator" {
+            depth += 1;
+            declarator = declarator.child_by_field_name("declarator")?;
+        }
+        if declarator.kind() != "function_declarator" {
+            return None;
+        }
+        let return_type = pointer_type(&base_type, depth);
+
+        // `void (*get_handler(int irq))(int)` returns a function pointer: the
+        // outer declarator holds the returned type's params, the inner one the function
+        if let Some(inner) = self.returned_funcptr_declarator(declarator) {
+            let (name, param_types) = self.extract_func_declarator(inner, source)?;
+            let (_, ret_params) = self.extract_func_declarator(declarator, source)?;
+            return Some(FunctionSignature {
+                name,
+                return_type: format!("{} (*)({})", return_type, ret_params.join(", ")),
+                param_types,
+            });
+        }
+
+        let (name, param_types) = self.extract_func_declarator(declarator, source)?;
 
         Some(FunctionSignature {
             name,
@@ -603,11 +806,124 @@ impl TypeAnalyzer {
         })
     }
 
+    /// Inner function_declarator of `(*name(params))`, if `node` declares a
+    /// function returning a function pointer
+    fn returned_funcptr_declarator<'a>(&self, node: Node<'a>) -> Option<Node<'a>> {
+        let paren = node.child_by_field_name("declarator")?;
+        if paren.kind() != "parenthesized_declarator" {
+            return None;
+        }
+        let mut cursor = paren.walk();
+        let mut inner = paren
+            .named_children(&mut cursor)
+            .find(|c| c.kind() == "pointer_declarator")?;
+        while inner.kind() == "pointer_declarator" {
+            inner = inner.child_by_field_name("declarator")?;
+        }
+        (inner.kind() == "function_declarator").then_some(inner)
+    }
+
     fn node_text(&self, node: Node, source: &str) -> String {
         node.utf8_text(source.as_bytes()).unwrap_or("").to_string()
     }
 }
 
+/// Append pointer depth to a base type: ("struct device", 1) -> "struct device *"
+fn pointer_type(base: &str, depth: usize) -> String {
+    if depth == 0 {
+        base.to_string()
+    } else {
+        format!("{} {}", base, "*".repeat(depth))
+    }
+}
+
+/// Pointers (and arrays) in the `(*name)` part of a function pointer declarator:
+/// 1 for `(*fn)`, 2 for `(**fn)` or an array of function pointers `(*fns[])`
+fn funcptr_depth(node: Node) -> usize {
+    let own = usize::from(matches!(
+        node.kind(),
+        "pointer_declarator" | "abstract_pointer_declarator" | "array_declarator" | "abstract_array_declarator"
+    ));
+    let mut cursor = node.walk();
+    let inner = node
+        .named_children(&mut cursor)
+        .filter(|c| c.kind().ends_with("declarator"))
+        .map(funcptr_depth)
+        .max()
+        .unwrap_or(0);
+    own + inner
+}
+
+/// Pointer depth of a normalized type: "void**" -> 2
+fn pointer_depth(t: &str) -> usize {
+    t.len() - t.trim_end_matches('*').len()
+}
+
+/// Return type, pointer depth and parameters of a written-out function
+/// pointer type: "int (*)(file*, int)" -> ("int", 1, ["file*", "int"])
+fn split_funcptr_type(t: &str) -> Option<(&str, usize, Vec<&str>)> {
+    let open = t.find("(*")?;
+    let close = open + t[open..].find(')')?;
+    let stars = &t[open + 1..close];
+    if !stars.chars().all(|c| c == '*') {
+        return None;
+    }
+    let rest = t[close + 1..].trim_start();
+    let params = paren_contents(rest, 0)?;
+    let params = match params.trim() {
+        "" | "void" => Vec::new(),
+        _ => split_top_level(params),
+    };
+    Some((t[..open].trim(), stars.len(), params))
+}
+
+/// Text between the parenthesis at byte `open` of `text` and its match
+fn paren_contents(text: &str, open: usize) -> Option<&str> {
+    if !text[open..].starts_with('(') {
+        return None;
+    }
+    let mut depth = 0;
+    for (i, c) in text[open..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&text[open + 1..open + i]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split on commas outside parentheses: "int a, void (*cb)(int, int)" gives two parts
+fn split_top_level(list: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in list.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(list[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(list[start..].trim());
+    parts
+}
+
+/// Normalize a return type captured from text: "struct page*" -> "struct page *"
+fn text_return_type(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let base = text.trim_end_matches(['*', ' ']);
+    pointer_type(base, text.matches('*').count())
+}
+
 impl Default for TypeAnalyzer {
     fn default() -> Self {
         Self::new()
@@ -650,6 +966,27 @@ struct file_operations {
         assert!(db.func_ptr_types.contains_key("file_operations.release"), "Should find release field");
     }
 
+    #[test]
+    fn test_multi_declarator_global_funcptrs() {
+        let source = r#"
+static void (*on_open)(struct inode *), (*on_close)(int fd);
+static int count, *(*alloc_fn)(size_t);
+"#;
+        let mut analyzer = TypeAnalyzer::new();
+        analyzer.analyze(source);
+
+        let db = analyzer.database();
+        let on_open = &db.func_ptr_types["on_open"];
+        assert_eq!(on_open.definition_kind, FuncPtrDefKind::GlobalVar);
+        assert_eq!(on_open.param_types, vec!["struct inode *"]);
+        let on_close = &db.func_ptr_types["on_close"];
+        assert_eq!(on_close.return_type, "void");
+        assert_eq!(on_close.param_types, vec!["int"]);
+
+        assert_eq!(db.func_ptr_types["alloc_fn"].return_type, "int *");
+        assert!(!db.func_ptr_types.contains_key("count"));
+    }
+
     #[test]
     fn test_function_param_funcptr() {
         let source = r#"
@@ -752,4 +1089,145 @@ static void my_disconnect(struct usb_interface *intf) {
         assert!(db.function_sigs.contains_key("my_probe"), "Should find my_probe");
         assert!(db.function_sigs.contains_key("my_disconnect"), "Should find my_disconnect");
     }
+
+    #[test]
+    fn test_pointer_return_types() {
+        let source = r#"
+typedef void (*irq_handler_t)(int irq);
+typedef struct page *(*alloc_fn)(struct device *dev, size_t size);
+
+struct dma_ops {
+    struct page *(*alloc)(struct device *dev, size_t size);
+    char **(*names)(struct device *dev);
+    void (*free)(struct page *page);
+};
+
+static struct page *my_alloc(struct device *dev, size_t size) {
+    return NULL;
+}
+
+static char **my_names(struct device *dev) {
+    return NULL;
+}
+
+static void my_free(struct page *page) {
+}
+
+static irq_handler_t pick_handler(int irq) {
+    return NULL;
+}
+
+static void (*lookup_handler(int irq))(int) {
+    return NULL;
+}
+"#;
+        let mut analyzer = TypeAnalyzer::new();
+        analyzer.analyze(source);
+
+        let db = analyzer.database();
+
+        assert_eq!(db.func_ptr_types["dma_ops.alloc"].return_type, "struct page *");
+        assert_eq!(db.func_ptr_types["dma_ops.names"].return_type, "char **");
+        assert_eq!(db.func_ptr_types["alloc_fn"].return_type, "struct page *");
+
+        assert_eq!(db.function_sigs["my_alloc"].return_type, "struct page *");
+        assert_eq!(db.function_sigs["my_alloc"].param_types.len(), 2);
+        assert_eq!(db.function_sigs["my_names"].return_type, "char **");
+        assert_eq!(db.function_sigs["pick_handler"].return_type, "irq_handler_t");
+        assert_eq!(db.function_sigs["lookup_handler"].return_type, "void (*)(int)");
+        assert_eq!(db.function_sigs["lookup_handler"].param_types, vec!["int"]);
+
+        assert!(db.is_compatible("my_alloc", "dma_ops.alloc", false));
+        assert!(db.is_compatible("my_alloc", "alloc_fn", false));
+        assert!(db.is_compatible("my_names", "dma_ops.names", false));
+        assert!(db.is_compatible("my_free", "dma_ops.free", false));
+        assert!(!db.is_compatible("my_names", "dma_ops.alloc", false));
+    }
+
+    #[test]
+    fn test_variadic_and_const_compatibility() {
+        let source = r#"
+typedef int (*log_fn)(const char *fmt, ...);
+typedef void (*release_fn)(void *data);
+
+int my_log(const char *fmt, ...) { return 0; }
+int my_log_level(const char *fmt, int level, ...) { return 0; }
+int my_puts(const char *s) { return 0; }
+int my_log_mutable(char *fmt, ...) { return 0; }
+
+void my_release(void *data) {}
+void my_release_const(const void *data) {}
+"#;
+        let mut lenient = TypeAnalyzer::new();
+        lenient.analyze(source);
+        let db = lenient.database();
+
+        assert_eq!(db.function_sigs["my_log"].param_types.last().unwrap(), "...");
+        assert!(db.is_compatible("my_log", "log_fn", false));
+        assert!(db.is_compatible("my_log_level", "log_fn", false));
+        assert!(db.is_compatible("my_puts", "log_fn", false));
+        assert!(!db.is_compatible("my_log", "release_fn", false));
+
+        // const is ignored unless strict
+        assert!(db.is_compatible("my_release_const", "release_fn", false));
+        assert!(!db.is_compatible("my_release_const", "release_fn", true));
+        assert!(db.is_compatible("my_release", "release_fn", true));
+        assert!(db.is_compatible("my_log", "log_fn", true));
+        assert!(!db.is_compatible("my_log_mutable", "log_fn", true));
+
+        let mut strict = TypeAnalyzer::with_strict_const(true);
+        strict.analyze(source);
+        let release = strict.database().get_compatible_functions("release_fn").unwrap();
+        assert!(release.contains("my_release"));
+        assert!(!release.contains("my_release_const"));
+        assert!(lenient
+            .database()
+            .get_compatible_functions("release_fn")
+            .unwrap()
+            .contains("my_release_const"));
+    }
+
+    #[test]
+    fn test_pointer_depth_and_funcptr_params() {
+        let source = r#"
+typedef int (*fault_fn)(struct vm_fault *vmf);
+
+struct vm_ops {
+    int (*mmap)(struct file *file, int (*fault)(struct vm_fault *));
+    int (*get)(void **out);
+    void (*handlers[4])(int irq);
+};
+
+static int my_mmap(struct file *file, int (*fault)(struct vm_fault *vmf)) { return 0; }
+static int typed_mmap(struct file *file, fault_fn fault) { return 0; }
+static int wrong_mmap(struct file *file, void (*fault)(struct vm_fault *vmf)) { return 0; }
+static int flat_mmap(struct file *file, int fault) { return 0; }
+static int my_get(void **out) { return 0; }
+static int flat_get(void *out) { return 0; }
+static void my_handler(int irq) {}
+"#;
+        let mut analyzer = TypeAnalyzer::new();
+        analyzer.analyze(source);
+        let db = analyzer.database();
+
+        assert_eq!(
+            db.func_ptr_types["vm_ops.mmap"].param_types,
+            vec!["struct file *", "int (*)(struct vm_fault *)"]
+        );
+        assert_eq!(db.func_ptr_types["vm_ops.get"].param_types, vec!["void **"]);
+        assert_eq!(db.function_sigs["my_mmap"].param_types[1], "int (*)(struct vm_fault *)");
+        assert_eq!(db.function_sigs["my_get"].param_types, vec!["void **"]);
+        assert_eq!(db.function_sigs["flat_get"].param_types, vec!["void *"]);
+
+        assert!(db.is_compatible("my_mmap", "vm_ops.mmap", false));
+        assert!(db.is_compatible("typed_mmap", "vm_ops.mmap", false));
+        assert!(!db.is_compatible("wrong_mmap", "vm_ops.mmap", false));
+        assert!(!db.is_compatible("flat_mmap", "vm_ops.mmap", false));
+
+        // void * converts to any object pointer, but is not void **
+        assert!(db.is_compatible("my_get", "vm_ops.get", true));
+        assert!(!db.is_compatible("flat_get", "vm_ops.get", false));
+
+        assert!(db.is_compatible("my_handler", "vm_ops.handlers", false));
+    }
 }
@@ -0,0 +1,118 @@
+//! Config-dependent analysis
+//!
+//! Analyzes a source file twice, once with a `CONFIG_*` option enabled and
+//! once with it disabled, and reports what only exists in one variant.
+
+use crate::{AnalysisResult, Analyzer};
+use flowsight_core::{AsyncBinding, Result};
+use flowsight_parser::preprocessor::resolve_conditionals;
+use flowsight_parser::{get_parser, ParseResult};
+use std::collections::{HashMap, HashSet};
+
+/// One side of a config comparison
+#[derive(Debug, Default)]
+pub struct ConfigVariant {
+    pub parse_result: ParseResult,
+    pub analysis: AnalysisResult,
+}
+
+/// Analysis of the same source with a config option on and off
+#[derive(Debug, Default)]
+pub struct ConfigVariants {
+    /// The toggled option, e.g. "CONFIG_PM"
+    pub config: String,
+    pub enabled: ConfigVariant,
+    pub disabled: ConfigVariant,
+    /// Functions present only when the option is enabled
+    pub enabled_only_functions: Vec<String>,
+    /// Functions present only when the option is disabled
+    pub disabled_only_functions: Vec<String>,
+    /// Async bindings present only when the option is enabled
+    pub enabled_only_bindings: Vec<AsyncBinding>,
+    /// Async bindings present only when the option is disabled
+    pub disabled_only_bindings: Vec<AsyncBinding>,
+}
+
+impl ConfigVariants {
+    /// Whether toggling the option changes any function or binding
+    pub fn is_config_dependent(&self) -> bool {
+        !(self.enabled_only_functions.is_empty()
+            && self.disabled_only_functions.is_empty()
+            && self.enabled_only_bindings.is_empty()
+            && self.disabled_only_bindings.is_empty())
+    }
+}
+
+impl Analyzer {
+    /// Analyze `source` with `config` enabled and disabled on top of `defines`
+    ///
+    /// Conditional blocks are resolved before parsing, so only the active
+    /// branches of `#ifdef`/`#if` groups are seen by the parser.
+    pub fn analyze_config_variants(
+        &mut self,
+        source: &str,
+        filename: &str,
+        config: &str,
+        defines: &HashMap<String, String>,
+    ) -> Result<ConfigVariants> {
+        let mut enabled_defines = defines.clone();
+        enabled_defines.insert(config.to_string(), "1".to_string());
+        let mut disabled_defines = defines.clone();
+        disabled_defines.remove(config);
+
+        let enabled = self.analyze_variant(source, filename, &enabled_defines)?;
+        let disabled = self.analyze_variant(source, filename, &disabled_defines)?;
+
+        Ok(ConfigVariants {
+            config: config.to_string(),
+            enabled_only_functions: function_difference(&enabled, &disabled),
+            disabled_only_functions: function_difference(&disabled, &enabled),
+            enabled_only_bindings: binding_difference(&enabled, &disabled),
+            disabled_only_bindings: binding_difference(&disabled, &enabled),
+            enabled,
+            disabled,
+        })
+    }
+
+    fn analyze_variant(
+        &mut self,
+        source: &str,
+        filename: &str,
+        defines: &HashMap<String, String>,
+    ) -> Result<ConfigVariant> {
+        let source = resolve_conditionals(source, defines);
+        let mut parse_result = get_parser().parse(&source, filename)?;
+        let analysis = self.analyze(&source, &mut parse_result)?;
+        Ok(ConfigVariant {
+            parse_result,
+            analysis,
+        })
+    }
+}
+
+fn function_difference(a: &ConfigVariant, b: &ConfigVariant) -> Vec<String> {
+    let mut names: Vec<String> = a
+        .parse_result
+        .functions
+        .keys()
+        .filter(|name| !b.parse_result.functions.contains_key(*name))
+        .cloned()
+        .collect();
+    names.sort();
+    names
+}
+
+fn binding_difference(a: &ConfigVariant, b: &ConfigVariant) -> Vec<AsyncBinding> {
+    let other: HashSet<(&str, &str)> = b
+        .analysis
+        .async_bindings
+        .iter()
+        .map(|binding| (binding.handler.as_str(), binding.variable.as_str()))
+        .collect();
+    a.analysis
+        .async_bindings
+        .iter()
+        .filter(|binding| !other.contains(&(binding.handler.as_str(), binding.variable.as_str())))
+        .cloned()
+        .collect()
+}
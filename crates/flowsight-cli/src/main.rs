@@ -4,14 +4,43 @@
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use flowsight_analysis::Analyzer;
-use flowsight_parser::get_parser;
-use std::path::PathBuf;
+use flowsight_analysis::async_tracker::AsyncTracker;
+use flowsight_analysis::atomic_check::AtomicChecker;
+use flowsight_analysis::classification::{Confidence, ResultClassifier};
+use flowsight_analysis::control_flow::ControlFlowChecker;
+use flowsight_analysis::driver_check::find_missing_callbacks;
+use flowsight_analysis::error_check::ErrorChecker;
+use flowsight_analysis::finding::Finding;
+use flowsight_analysis::funcptr::FuncPtrResolver;
+use flowsight_analysis::irq_check::{IrqChecker, IrqViolation};
+use flowsight_analysis::lock_balance::LockBalanceChecker;
+use flowsight_analysis::module::ModuleAnalysis;
+use flowsight_analysis::render::{tree_to_string, TreeStyle};
+use flowsight_analysis::scenario::{Scenario, ScenarioOptions};
+use flowsight_analysis::{sarif, schema, AnalysisConfig, AnalysisResult, Analyzer};
+use flowsight_core::{AsyncBinding, ExecutionContext, FunctionDef, OpsAssignment};
+use flowsight_index::{IgnoreRules, IndexStorage, SymbolIndex};
+use flowsight_knowledge::KnowledgeBase;
+use flowsight_parser::parallel::ParallelParser;
+use flowsight_parser::preprocessor::HeaderResolver;
+use flowsight_parser::{get_parser, get_parser_for, ParseResult};
+use flowsight_query::{closest_names, QueryEngine};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "flowsight")]
 #[command(author, version, about = "Code flow analysis tool", long_about = None)]
 struct Cli {
+    /// Only log errors
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Log more detail (-vv for trace)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -28,35 +57,78 @@ enum Commands {
         #[arg(short, long)]
         output: Option<PathBuf>,
 
-        /// Output format (json, text)
+        /// Output format (json, jsonl, dot, text)
         #[arg(short, long, default_value = "text")]
         format: String,
+
+        /// Inline the file's local `#include "..."` headers and analyze them
+        /// together as one unit
+        #[arg(long)]
+        flatten: bool,
     },
 
-    /// Show execution flow for a function
+    /// Show execution flow for a function, or for the whole module
     Flow {
-        /// Source file
-        #[arg(value_name = "FILE")]
-        file: PathBuf,
+        /// Source files or directories, analyzed as one module
+        #[arg(value_name = "FILE", required = true, num_args = 1..)]
+        files: Vec<PathBuf>,
 
-        /// Function name
-        #[arg(value_name = "FUNCTION")]
-        function: String,
+        /// Function to show; without it, every entry point is shown
+        #[arg(short, long)]
+        function: Option<String>,
+
+        /// Skip paths matching GLOB under directory arguments, on top of
+        /// their .flowsightignore (repeatable)
+        #[arg(long, value_name = "GLOB")]
+        ignore: Vec<String>,
+
+        /// Expand at most N levels of callees
+        #[arg(long, value_name = "N")]
+        depth: Option<usize>,
+
+        /// Hide kernel APIs and kernel call chains
+        #[arg(long)]
+        no_kernel: bool,
+
+        /// Only show paths leading to async callbacks
+        #[arg(long)]
+        only_async: bool,
     },
     
     /// Show execution flow in ftrace style
     Trace {
-        /// Source file
-        #[arg(value_name = "FILE")]
-        file: PathBuf,
+        /// Source files or directories, analyzed as one module
+        #[arg(value_name = "FILE", required = true, num_args = 1..)]
+        files: Vec<PathBuf>,
 
         /// Function name
         #[arg(value_name = "FUNCTION")]
         function: String,
         
-        /// Output format (ftrace, markdown, json)
+        /// Output format (ftrace, markdown, json, dot, mermaid)
         #[arg(short, long, default_value = "ftrace")]
         format: String,
+
+        /// `function,count` CSV used to weight hot paths
+        #[arg(long, value_name = "CSV")]
+        weights: Option<PathBuf>,
+
+        /// Skip paths matching GLOB under directory arguments, on top of
+        /// their .flowsightignore (repeatable)
+        #[arg(long, value_name = "GLOB")]
+        ignore: Vec<String>,
+
+        /// Expand at most N levels of callees
+        #[arg(long, value_name = "N")]
+        depth: Option<usize>,
+
+        /// Hide kernel APIs and kernel call chains
+        #[arg(long)]
+        no_kernel: bool,
+
+        /// Only show paths leading to async callbacks
+        #[arg(long)]
+        only_async: bool,
     },
     
     /// Show who calls a function
@@ -70,275 +142,1929 @@ enum Commands {
         function: String,
     },
     
+    /// Show every path that can reach a function, including async links
+    Reaches {
+        /// Source file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Function name
+        #[arg(value_name = "FUNCTION")]
+        function: String,
+    },
+
     /// Show what a function calls
     Callees {
         /// Source file
         #[arg(value_name = "FILE")]
         file: PathBuf,
 
-        /// Function name
-        #[arg(value_name = "FUNCTION")]
-        function: String,
-    },
+        /// Function name
+        #[arg(value_name = "FUNCTION")]
+        function: String,
+    },
+
+    /// List all async handlers
+    Async {
+        /// Source file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+    },
+
+    /// List all callbacks
+    Callbacks {
+        /// Source file or directory
+        #[arg(value_name = "PATH")]
+        path: PathBuf,
+
+        /// Output format (text, csv)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+
+        /// Only callbacks run in this context (process, softirq, hardirq)
+        #[arg(long, value_name = "CONTEXT")]
+        context: Option<String>,
+
+        /// Only async handlers of this mechanism, e.g. "work queue" or "threaded_irq"
+        #[arg(long, value_name = "KIND")]
+        mechanism: Option<String>,
+    },
+
+    /// Explain a callback: what invokes it, from where, in which context, and what it does
+    Explain {
+        /// Source file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Function name
+        #[arg(value_name = "FUNCTION")]
+        function: String,
+    },
+
+    /// Find every implementation of an ops-table field (e.g. file_operations.read)
+    Implementations {
+        /// Ops type and field, as `ops_type.field`
+        #[arg(value_name = "OPS.FIELD")]
+        target: String,
+
+        /// Directory to search
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+    },
+
+    /// List call sites whose target is not certain, to annotate
+    Uncertain {
+        /// Directory to analyze
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+    },
+
+    /// Shortest call path from one function to another, across async hand-offs
+    Path {
+        /// Source file or directory
+        #[arg(value_name = "PATH")]
+        path: PathBuf,
+
+        /// Function the path starts at
+        from: String,
+
+        /// Function the path ends at
+        to: String,
+    },
+
+    /// Summarize functions, async handlers and callbacks under a directory
+    Stats {
+        /// Source file or directory
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+    },
+
+    /// List the most complex functions under a directory
+    Metrics {
+        /// Directory to scan
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+
+        /// Number of functions to show
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+    },
+
+    /// List functions that access MMIO registers, with the registers they use
+    Io {
+        /// Directory to scan
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+    },
+
+    /// Show which files depend on which, through calls and includes
+    Deps {
+        /// Directory to scan
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+
+        /// Skip paths matching GLOB, on top of the directory's .flowsightignore (repeatable)
+        #[arg(long, value_name = "GLOB")]
+        ignore: Vec<String>,
+
+        /// Output format (text, dot)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// List the functions structurally most similar to one, to find copy-pasted code
+    Similar {
+        /// Directory to scan
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+
+        /// Function name
+        #[arg(value_name = "FUNCTION")]
+        function: String,
+
+        /// Number of functions to show
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+
+    /// List functions taking a parameter of the given type
+    #[command(name = "by-param")]
+    ByParam {
+        /// Directory to scan
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+
+        /// Parameter type, e.g. "struct usb_interface *"
+        #[arg(value_name = "TYPE")]
+        type_name: String,
+    },
+
+    /// Run the checkers over source files or directories
+    Check {
+        /// Source files or directories (with --changed: only changes under these)
+        #[arg(value_name = "PATH", required_unless_present = "changed", num_args = 1..)]
+        paths: Vec<PathBuf>,
+
+        /// Only check changed code: files listed on stdin, else `git diff HEAD`
+        #[arg(long)]
+        changed: bool,
+
+        /// Index directory: saved by a full check, loaded by --changed to
+        /// follow calls into unchanged files
+        #[arg(long, value_name = "DIR")]
+        index: Option<PathBuf>,
+
+        /// Skip paths matching GLOB under directory arguments, on top of
+        /// their .flowsightignore (repeatable)
+        #[arg(long, value_name = "GLOB")]
+        ignore: Vec<String>,
+
+        /// Output format (text, json, sarif)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+
+        /// Output file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Write a self-contained HTML report: index stats, callbacks, findings and flow diagrams
+    Report {
+        /// Directory to analyze
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+
+        /// Skip paths matching GLOB, on top of the directory's .flowsightignore (repeatable)
+        #[arg(long, value_name = "GLOB")]
+        ignore: Vec<String>,
+
+        /// Output file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Check that hard IRQ handlers never sleep or recurse
+    #[command(name = "check-irq")]
+    CheckIrq {
+        /// Source file registering the handlers
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Report symbol-level changes between two index databases
+    Diff {
+        /// Index database before the change
+        #[arg(value_name = "OLD")]
+        old: PathBuf,
+
+        /// Index database after the change
+        #[arg(value_name = "NEW")]
+        new: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Work with scenarios
+    Scenario {
+        #[command(subcommand)]
+        command: ScenarioCommands,
+    },
+
+    /// Print the JSON Schema of the JSON output types
+    Schema {
+        /// ParseResult, AnalysisResult, FlowNode or Scenario (default: all, keyed by name)
+        #[arg(value_name = "TYPE")]
+        type_name: Option<String>,
+    },
+
+    /// Draw an async pattern's two-phase timeline as a Mermaid diagram
+    Timeline {
+        /// Async pattern name, e.g. work_struct
+        #[arg(value_name = "PATTERN")]
+        pattern: String,
+    },
+
+    /// Index a directory once and answer JSON-RPC requests, one per line, on stdio
+    Serve {
+        /// Project directory
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ScenarioCommands {
+    /// Print a YAML scenario for a function, one binding per parameter to fill in
+    Template {
+        /// Source file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Function name
+        #[arg(value_name = "FUNCTION")]
+        function: String,
+    },
+}
+
+impl Commands {
+    /// Whether the selected output format is meant for other programs
+    fn machine_output(&self) -> bool {
+        let format = match self {
+            Commands::Analyze { format, .. }
+            | Commands::Trace { format, .. }
+            | Commands::Callbacks { format, .. }
+            | Commands::Check { format, .. }
+            | Commands::CheckIrq { format, .. }
+            | Commands::Diff { format, .. }
+            | Commands::Deps { format, .. } => format,
+            _ => return false,
+        };
+        matches!(format.as_str(), "json" | "jsonl" | "sarif" | "csv" | "dot" | "mermaid")
+    }
+}
+
+/// Log to stderr so stdout only carries the result
+///
+/// Progress lines are logged at info level: shown by default, hidden by
+/// `--quiet` and whenever the output format is machine-readable.
+/// `RUST_LOG` overrides the level.
+fn init_logging(cli: &Cli) {
+    let level = match (cli.quiet, cli.verbose) {
+        (true, _) => "error",
+        (false, 0) if cli.command.machine_output() => "warn",
+        (false, 0) => "info",
+        (false, 1) => "debug",
+        (false, _) => "trace",
+    };
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(level));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .without_time()
+        .with_target(false)
+        .init();
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    init_logging(&cli);
+
+    match cli.command {
+        Commands::Analyze {
+            file,
+            output,
+            format,
+            flatten,
+        } => {
+            cmd_analyze(&file, output.as_deref(), &format, flatten)?;
+        }
+        Commands::Flow {
+            files,
+            function,
+            ignore,
+            depth,
+            no_kernel,
+            only_async,
+        } => {
+            // The function only comes from --function; a name among the
+            // paths is pointed there instead of being guessed at
+            if let Some(missing) = files.iter().find(|f| !f.exists()) {
+                let name = missing.to_string_lossy();
+                if function.is_none() && is_identifier(&name) {
+                    anyhow::bail!("{}: no such file or directory (to show a function, use --function {})", name, name);
+                }
+                anyhow::bail!("{}: no such file or directory", name);
+            }
+            cmd_flow(&files, function.as_deref(), &ignore, &FlowFilter::new(depth, no_kernel, only_async))?;
+        }
+        Commands::Trace {
+            files,
+            function,
+            format,
+            weights,
+            ignore,
+            depth,
+            no_kernel,
+            only_async,
+        } => {
+            let filter = FlowFilter::new(depth, no_kernel, only_async);
+            cmd_trace(
+                &files,
+                &function,
+                &format,
+                weights.as_deref(),
+                &ignore,
+                &filter,
+            )?;
+        }
+        Commands::Callers { file, function } => {
+            cmd_callers(&file, &function)?;
+        }
+        Commands::Reaches { file, function } => {
+            cmd_reaches(&file, &function)?;
+        }
+        Commands::Callees { file, function } => {
+            cmd_callees(&file, &function)?;
+        }
+        Commands::Async { file } => {
+            cmd_async(&file)?;
+        }
+        Commands::Callbacks {
+            path,
+            format,
+            context,
+            mechanism,
+        } => {
+            cmd_callbacks(&path, &format, context.as_deref(), mechanism.as_deref())?;
+        }
+        Commands::Path { path, from, to } => {
+            cmd_path(&path, &from, &to)?;
+        }
+        Commands::Explain { file, function } => {
+            cmd_explain(&file, &function)?;
+        }
+        Commands::Implementations { target, dir } => {
+            cmd_implementations(&target, &dir)?;
+        }
+        Commands::Uncertain { dir } => {
+            cmd_uncertain(&dir)?;
+        }
+        Commands::Scenario {
+            command: ScenarioCommands::Template { file, function },
+        } => {
+            cmd_scenario_template(&file, &function)?;
+        }
+        Commands::Stats { dir } => {
+            cmd_stats(&dir)?;
+        }
+        Commands::Metrics { dir, top } => {
+            cmd_metrics(&dir, top)?;
+        }
+        Commands::Io { dir } => {
+            cmd_io(&dir)?;
+        }
+        Commands::Deps {
+            dir,
+            ignore,
+            format,
+        } => {
+            cmd_deps(&dir, &ignore, &format)?;
+        }
+        Commands::Similar { dir, function, top } => {
+            cmd_similar(&dir, &function, top)?;
+        }
+        Commands::ByParam { dir, type_name } => {
+            cmd_by_param(&dir, &type_name)?;
+        }
+        Commands::Check {
+            paths,
+            changed,
+            index,
+            ignore,
+            format,
+            output,
+        } => {
+            if changed {
+                cmd_check_changed(&paths, &ignore, index.as_deref(), &format, output.as_deref())?;
+            } else {
+                cmd_check(&paths, &ignore, index.as_deref(), &format, output.as_deref())?;
+            }
+        }
+        Commands::Report { dir, ignore, output } => {
+            cmd_report(&dir, &ignore, output.as_deref())?;
+        }
+        Commands::CheckIrq { file, format } => {
+            cmd_check_irq(&file, &format)?;
+        }
+        Commands::Diff { old, new, format } => {
+            cmd_diff(&old, &new, &format)?;
+        }
+        Commands::Schema { type_name } => {
+            cmd_schema(type_name.as_deref())?;
+        }
+        Commands::Timeline { pattern } => {
+            cmd_timeline(&pattern)?;
+        }
+        Commands::Serve { dir } => {
+            cmd_serve(&dir)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_analyze(file: &Path, output: Option<&Path>, format: &str, flatten: bool) -> Result<()> {
+    tracing::info!("📂 Analyzing: {}", file.display());
+
+    let filename = file.to_string_lossy();
+    let (source, mut parse_result) = match flatten {
+        true => {
+            let root = file.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            let source = HeaderResolver::for_project(root).flatten(file)?;
+            let parse_result = flowsight_parser::preprocessed::parse(&source, &filename)?;
+            (source, parse_result)
+        }
+        false => {
+            let source = std::fs::read_to_string(file)?;
+            let parse_result = get_parser_for(file)?.parse(&source, &filename)?;
+            (source, parse_result)
+        }
+    };
+
+    tracing::info!(
+        "Found {} functions, {} structs",
+        parse_result.functions.len(),
+        parse_result.structs.len()
+    );
+    if !parse_result.errors.is_empty() {
+        tracing::warn!(
+            "{} syntax errors, results may be incomplete (first at {})",
+            parse_result.errors.len(),
+            parse_result.errors[0]
+        );
+    }
+
+    let mut analyzer = Analyzer::new();
+    let analysis = analyzer.analyze(&source, &mut parse_result)?;
+
+    tracing::info!(
+        "Found {} async handlers, {} entry points",
+        analysis.async_bindings.len(),
+        analysis.entry_points.len()
+    );
+
+    if format == "json" {
+        let result = serde_json::json!({
+            "file": file.to_string_lossy(),
+            "functions": parse_result.functions.len(),
+            "structs": parse_result.structs.len(),
+            "async_bindings": analysis.async_bindings.len(),
+            "entry_points": analysis.entry_points,
+            "flow_trees": analysis.flow_trees,
+        });
+
+        let json = serde_json::to_string_pretty(&result)?;
+
+        if let Some(out_path) = output {
+            std::fs::write(out_path, &json)?;
+            tracing::info!("Output written to: {}", out_path.display());
+        } else {
+            println!("{}", json);
+        }
+    } else if format == "dot" {
+        let dot = flowsight_analysis::export::call_edges_to_dot(&analysis.call_edges);
+        if let Some(out_path) = output {
+            std::fs::write(out_path, &dot)?;
+            tracing::info!("Output written to: {}", out_path.display());
+        } else {
+            print!("{}", dot);
+        }
+    } else if format == "jsonl" {
+        let mut out: Box<dyn Write> = match output {
+            Some(out_path) => Box::new(BufWriter::new(std::fs::File::create(out_path)?)),
+            None => Box::new(BufWriter::new(std::io::stdout().lock())),
+        };
+        write_jsonl(&mut out, &parse_result, &analysis)?;
+        out.flush()?;
+
+        if let Some(out_path) = output {
+            tracing::info!("Output written to: {}", out_path.display());
+        }
+    } else {
+        println!("📊 Summary:");
+        println!("   Functions: {}", parse_result.functions.len());
+        println!("   Structs: {}", parse_result.structs.len());
+        println!("   Async handlers: {}", analysis.async_bindings.len());
+        println!("   Entry points: {:?}", analysis.entry_points);
+
+        if !analysis.flow_trees.is_empty() {
+            println!("\n🎯 Flow confidence (1.00 = every edge certain):");
+            let classifier = ResultClassifier::new();
+            for tree in &analysis.flow_trees {
+                let summary = classifier.summarize_tree(tree);
+                println!(
+                    "   {}(): {:.2} ({} certain, {} possible, {} unknown)",
+                    tree.name,
+                    summary.weighted_score,
+                    summary.certain_count,
+                    summary.possible_count,
+                    summary.unknown_count
+                );
+            }
+        }
+
+        if !analysis.unchecked_allocations.is_empty() {
+            println!("\n⚠️  Unchecked failable calls:");
+            for finding in &analysis.unchecked_allocations {
+                println!(
+                    "   {}:{} {} = {}() used at line {} without a NULL/IS_ERR check",
+                    finding.function, finding.line, finding.variable, finding.api, finding.use_line
+                );
+            }
+        }
+
+        if !analysis.unchecked_user_copies.is_empty() {
+            println!("\n⚠️  Unchecked user copies:");
+            for finding in &analysis.unchecked_user_copies {
+                println!(
+                    "   {}:{} {}() result ignored; non-zero means the copy failed",
+                    finding.function, finding.line, finding.api
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write one JSON object per function, then one per async binding
+fn write_jsonl(
+    out: &mut dyn Write,
+    parse_result: &ParseResult,
+    analysis: &AnalysisResult,
+) -> Result<()> {
+    // Emit functions in source order so the stream is stable across runs
+    let mut functions: Vec<_> = parse_result.functions.values().collect();
+    functions.sort_by_key(|f| {
+        let line = f.location.as_ref().map(|l| l.line).unwrap_or(u32::MAX);
+        (line, &f.name)
+    });
+
+    for func in functions {
+        let record = serde_json::json!({
+            "kind": "function",
+            "name": func.name,
+            "location": func.location,
+            "is_callback": func.is_callback,
+            "calls": func.calls,
+            "callback_context": func.callback_context,
+        });
+        serde_json::to_writer(&mut *out, &record)?;
+        writeln!(out)?;
+    }
+
+    for binding in &analysis.async_bindings {
+        let record = serde_json::json!({
+            "kind": "async_binding",
+            "handler": binding.handler,
+            "mechanism": binding.mechanism,
+            "variable": binding.variable,
+            "context": binding.context,
+            "bind_location": binding.bind_location,
+            "trigger_locations": binding.trigger_locations,
+        });
+        serde_json::to_writer(&mut *out, &record)?;
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Display-time filters over an already built flow tree
+struct FlowFilter {
+    /// Levels of callees to expand below the root
+    depth: Option<usize>,
+    /// Only `show_kernel_api` is used
+    options: ScenarioOptions,
+    only_async: bool,
+}
+
+impl FlowFilter {
+    fn new(depth: Option<usize>, no_kernel: bool, only_async: bool) -> Self {
+        Self {
+            depth,
+            options: ScenarioOptions {
+                show_kernel_api: !no_kernel,
+                ..Default::default()
+            },
+            only_async,
+        }
+    }
+
+    fn apply(&self, mut tree: flowsight_core::FlowNode) -> flowsight_core::FlowNode {
+        let children = std::mem::take(&mut tree.children);
+        tree.children = self.filter_children(children, 1);
+        tree
+    }
+
+    /// Filter nodes at `level` (the root's children are level 1)
+    fn filter_children(&self, nodes: Vec<flowsight_core::FlowNode>, level: usize) -> Vec<flowsight_core::FlowNode> {
+        let mut kept = Vec::new();
+        for mut node in nodes {
+            // Hidden kernel nodes are replaced by their children
+            if self.options.hides(&node) {
+                kept.extend(self.filter_children(node.children, level));
+                continue;
+            }
+            if self.only_async && !reaches_async(&node) {
+                continue;
+            }
+            let children = std::mem::take(&mut node.children);
+            if self.depth.is_none_or(|depth| level < depth) {
+                node.children = self.filter_children(children, level + 1);
+            }
+            kept.push(node);
+        }
+        kept
+    }
+}
+
+/// Whether `node` is or leads to an async callback
+fn reaches_async(node: &flowsight_core::FlowNode) -> bool {
+    matches!(node.node_type, flowsight_core::FlowNodeType::AsyncCallback { .. })
+        || node.children.iter().any(reaches_async)
+}
+
+fn cmd_flow(files: &[PathBuf], function: Option<&str>, ignore: &[String], filter: &FlowFilter) -> Result<()> {
+    let module = analyze_module(files, ignore)?;
+
+    // Without a function, show every entry point in one tree
+    let Some(function) = function else {
+        let forest = flowsight_analysis::callgraph::build_forest(
+            &module.analysis.entry_points,
+            &module.parse_result,
+            &module.analysis.async_bindings,
+            &AnalysisConfig::default(),
+        );
+        print_flow_tree(&filter.apply(forest));
+        return Ok(());
+    };
+
+    // Find the flow tree for the specified function
+    if let Some(tree) = module.analysis.flow_trees.into_iter().find(|t| t.name == function) {
+        print_flow_tree(&filter.apply(tree));
+        return Ok(());
+    }
+
+    // If not found in flow trees, try to build one
+    if let Some(func) = module.parse_result.functions.get(function) {
+        println!("{}()", function);
+        for (i, callee) in func.calls.iter().enumerate() {
+            let connector = if i + 1 == func.calls.len() { "└── " } else { "├── " };
+            println!("{}{}()", connector, callee);
+        }
+    } else {
+        println!("{}", not_found(function, &module.parse_result.functions));
+    }
+
+    Ok(())
+}
+
+/// Whether `name` can be a C or Rust function name
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// "Function 'x' not found", suggesting the closest known names
+fn not_found(function: &str, functions: &HashMap<String, FunctionDef>) -> String {
+    let suggestions = closest_names(function, functions.keys().map(String::as_str), 3);
+    if suggestions.is_empty() {
+        format!("Function '{}' not found", function)
+    } else {
+        format!("Function '{}' not found. Did you mean: {}?", function, suggestions.join(", "))
+    }
+}
+
+/// Expand directories into their C and Rust sources
+///
+/// Paths under a directory matching `ignore` or its `.flowsightignore` are skipped.
+fn collect_sources(paths: &[PathBuf], ignore: &[String]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let rules = IgnoreRules::for_project(path, ignore)?;
+            let mut found: Vec<PathBuf> = rules
+                .walk(path)
+                .filter(|e| e.file_type().is_file())
+                .map(|e| e.into_path())
+                .filter(|p| flowsight_core::Language::from_path(p).is_some())
+                .collect();
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+/// Parse and analyze `paths` as one module
+///
+/// Paths under a directory matching `ignore` or its `.flowsightignore` are skipped.
+fn analyze_module(paths: &[PathBuf], ignore: &[String]) -> Result<ModuleAnalysis> {
+    let files = collect_sources(paths, ignore)?;
+    if files.is_empty() {
+        anyhow::bail!("no sources found");
+    }
+    let mut analyzer = Analyzer::new();
+    Ok(analyzer.analyze_files(&files, &AnalysisConfig::default())?)
+}
+
+fn cmd_reaches(file: &Path, function: &str) -> Result<()> {
+    let parser = get_parser();
+    let mut parse_result = parser.parse_file(file)?;
+
+    let source = std::fs::read_to_string(file)?;
+    let mut analyzer = Analyzer::new();
+    let analysis = analyzer.analyze(&source, &mut parse_result)?;
+
+    let tree = flowsight_analysis::callgraph::build_reverse_flow_tree(
+        function,
+        &parse_result,
+        &analysis.async_bindings,
+        &mut std::collections::HashSet::new(),
+        0,
+        &AnalysisConfig::default(),
+    );
+    match tree {
+        Some(tree) => {
+            println!("🔙 Paths reaching {}():", function);
+            println!();
+            print_flow_tree(&tree);
+        }
+        None => println!("{}", not_found(function, &parse_result.functions)),
+    }
+
+    Ok(())
+}
+
+fn print_flow_tree(node: &flowsight_core::FlowNode) {
+    print!("{}", tree_to_string(node, &TreeStyle::unicode()));
+}
+
+fn cmd_async(file: &Path) -> Result<()> {
+    let parser = get_parser();
+    let mut parse_result = parser.parse_file(file)?;
+
+    let source = std::fs::read_to_string(file)?;
+    let mut analyzer = Analyzer::new();
+    let analysis = analyzer.analyze(&source, &mut parse_result)?;
+
+    println!("🔄 Async Handlers in {}:", file.display());
+    println!();
+
+    for binding in &analysis.async_bindings {
+        let mechanism = format!("{:?}", binding.mechanism);
+        let context = if binding.context.can_sleep() {
+            "can sleep"
+        } else {
+            "cannot sleep"
+        };
+
+        println!(
+            "  {} {}()",
+            match &binding.mechanism {
+                flowsight_core::AsyncMechanism::WorkQueue { .. } => "⚙️ ",
+                flowsight_core::AsyncMechanism::Timer { .. } => "⏲️ ",
+                flowsight_core::AsyncMechanism::Interrupt { .. } => "⚡",
+                flowsight_core::AsyncMechanism::Tasklet => "🔄",
+                flowsight_core::AsyncMechanism::KThread => "🧵",
+                _ => "📍",
+            },
+            binding.handler
+        );
+        println!("     Type: {}", mechanism);
+        println!("     Context: {}", context);
+        if !binding.variable.is_empty() {
+            println!("     Variable: {}", binding.variable);
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+/// `context` and `mechanism` filter the text listing
+fn cmd_callbacks(path: &Path, format: &str, context: Option<&str>, mechanism: Option<&str>) -> Result<()> {
+    let context: Option<ExecutionContext> = context.map(str::parse).transpose()?;
+    let ModuleAnalysis {
+        parse_result,
+        analysis,
+    } = analyze_module(&[path.to_path_buf()], &[])?;
+    let mut engine = module_engine(parse_result, analysis.async_bindings);
+
+    if format == "csv" {
+        let resolver = FuncPtrResolver::new();
+        let index = engine.index_mut();
+        for file in collect_sources(&[path.to_path_buf()], &[])? {
+            if let Ok(source) = std::fs::read_to_string(&file) {
+                for assignment in resolver.find_ops_assignments(&source, &file.to_string_lossy()) {
+                    index.add_ops_assignment(assignment);
+                }
+            }
+        }
+        let stdout = std::io::stdout();
+        engine.export_callbacks_csv(&KnowledgeBase::builtin(), BufWriter::new(stdout.lock()))?;
+        return Ok(());
+    }
+
+    println!("🔌 Callbacks in {}:", path.display());
+    println!();
+
+    let mut callbacks = engine.get_callbacks();
+    if let Some(context) = &context {
+        let keep: HashSet<&str> = engine.find_by_context(context).iter().map(|f| f.name.as_str()).collect();
+        callbacks.retain(|f| keep.contains(f.name.as_str()));
+    }
+    if let Some(kind) = mechanism {
+        let keep: HashSet<&str> = engine.find_by_mechanism(kind).iter().map(|f| f.name.as_str()).collect();
+        callbacks.retain(|f| keep.contains(f.name.as_str()));
+    }
+    callbacks.sort_by(|a, b| a.name.cmp(&b.name));
+    for func in callbacks {
+        println!("  {}()", func.name);
+        println!("     Context: {}", func.callback_context.as_deref().unwrap_or("unknown"));
+        if let Some(runs_in) = &func.execution_context {
+            let sleep = if runs_in.can_sleep() { "can sleep" } else { "cannot sleep" };
+            println!("     Runs in: {:?} ({})", runs_in, sleep);
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+/// Query engine over one analyzed module's functions and async bindings
+fn module_engine(parse_result: ParseResult, bindings: Vec<AsyncBinding>) -> QueryEngine {
+    let mut engine = QueryEngine::new();
+    let index = engine.index_mut();
+    for binding in bindings {
+        // The function whose body holds the registration
+        let registered_by = binding.bind_location.as_ref().and_then(|bind| {
+            parse_result.functions.values().find_map(|f| {
+                let loc = f.location.as_ref()?;
+                (loc.file == bind.file && loc.line <= bind.line && bind.line <= loc.end_line).then(|| f.name.clone())
+            })
+        });
+        index.add_async_binding(binding, registered_by);
+    }
+    for func in parse_result.functions.into_values() {
+        let file = func.location.as_ref().map(|l| PathBuf::from(&l.file)).unwrap_or_default();
+        index.add_function(func, &file);
+    }
+    for st in parse_result.structs.into_values() {
+        index.add_struct(st);
+    }
+    engine
+}
+
+fn cmd_stats(dir: &Path) -> Result<()> {
+    let ModuleAnalysis {
+        parse_result,
+        analysis,
+    } = analyze_module(&[dir.to_path_buf()], &[])?;
+    let stats = module_engine(parse_result, analysis.async_bindings).index().stats();
+
+    println!("📊 {}:", dir.display());
+    println!("   Files: {}", stats.total_files);
+    println!("   Functions: {}", stats.total_functions);
+    println!("   Structs: {}", stats.total_structs);
+    println!("   Async handlers: {}", stats.async_handlers);
+    for (mechanism, count) in &stats.async_by_mechanism {
+        println!("     {:<14} {}", mechanism, count);
+    }
+    println!("   Callbacks: {}", stats.callbacks);
+    Ok(())
+}
+
+fn cmd_path(path: &Path, from: &str, to: &str) -> Result<()> {
+    let ModuleAnalysis {
+        parse_result,
+        analysis,
+    } = analyze_module(&[path.to_path_buf()], &[])?;
+    for function in [from, to] {
+        if !parse_result.functions.contains_key(function) {
+            anyhow::bail!("{}", not_found(function, &parse_result.functions));
+        }
+    }
+    let engine = module_engine(parse_result, analysis.async_bindings);
+
+    let Some(chain) = engine.call_path(from, to) else {
+        println!("No call path from {}() to {}()", from, to);
+        return Ok(());
+    };
+    println!("🧭 {}() → {}():", from, to);
+    println!();
+    println!("  {}()", chain[0]);
+    for hop in chain.windows(2) {
+        let direct = engine.get_function(&hop[0]).is_some_and(|f| f.calls.contains(&hop[1]));
+        match engine.async_edge(&hop[0], &hop[1]).filter(|_| !direct) {
+            Some(binding) => println!(
+                "  ⇢ {}()  [async: {} {}]",
+                hop[1],
+                flowsight_query::mechanism_name(&binding.mechanism),
+                binding.variable
+            ),
+            None => println!("  → {}()", hop[1]),
+        }
+    }
+    Ok(())
+}
+
+fn cmd_scenario_template(file: &Path, function: &str) -> Result<()> {
+    let parse_result = get_parser_for(file)?.parse_file(file)?;
+    let Some(func) = parse_result.functions.get(function) else {
+        anyhow::bail!("{} in {}", not_found(function, &parse_result.functions), file.display());
+    };
+    print!("{}", Scenario::from_function(func).to_yaml()?);
+    Ok(())
+}
+
+fn cmd_explain(file: &Path, function: &str) -> Result<()> {
+    let parser = get_parser_for(file)?;
+    let mut parse_result = parser.parse_file(file)?;
+
+    let source = std::fs::read_to_string(file)?;
+    let mut analyzer = Analyzer::new();
+    let analysis = analyzer.analyze(&source, &mut parse_result)?;
+
+    let Some(func) = parse_result.functions.get(function) else {
+        anyhow::bail!("{} in {}", not_found(function, &parse_result.functions), file.display());
+    };
+    let kb = KnowledgeBase::builtin();
+    let binding = analysis.async_bindings.iter().find(|b| b.handler == function);
+
+    println!("📖 {}()", function);
+    println!();
+
+    // Ops-table context names the exact callback; otherwise fall back to name matching
+    let identified = func
+        .callback_context
+        .as_deref()
+        .and_then(|ctx| ctx.split_once('.'))
+        .and_then(|(fw, cb)| kb.get_callback(fw, cb).map(|callback| (fw, cb, callback)))
+        .or_else(|| kb.identify_callback(function, &source));
+
+    let mut context = None;
+    if let Some((framework, cb, callback)) = identified {
+        println!("🔌 Implements {}.{}: {}", framework, cb, callback.description);
+        println!("   Triggered by: {}", callback.trigger);
+        if let Some(signature) = &callback.signature {
+            println!("   Signature: {}", signature);
+        }
+        context = Some((callback.context.description().to_string(), callback.context.can_sleep()));
+    } else if let Some(binding) = binding {
+        println!("⏱️  Async handler ({:?}) bound to {}", binding.mechanism, binding.variable);
+        if let Some(loc) = &binding.bind_location {
+            println!("   Bound at line {}", loc.line);
+        }
+        for loc in &binding.trigger_locations {
+            println!("   Triggered at line {}", loc.line);
+        }
+        context = Some((format!("{:?}", binding.context), binding.context.can_sleep()));
+    } else {
+        println!("❔ Not identified as a callback of a known framework or async mechanism");
+    }
+    println!();
+
+    let chain = flowsight_analysis::callgraph::kernel_call_chain(function, &parse_result, &analysis.async_bindings, &kb)
+        .or_else(|| identified.and_then(|(_, _, callback)| callback.call_chain.clone()));
+    if let Some(chain) = chain {
+        println!("🧭 Kernel call chain: {}", chain.name);
+        println!("   🎯 {}", chain.trigger_source);
+        for node in &chain.nodes {
+            let file = node.file.as_deref().map(|f| format!("  ({})", f)).unwrap_or_default();
+            println!("   → {}{}  [{:?}]", node.function, file, node.context);
+        }
+        println!("   → {}()", function);
+        println!();
+    }
+
+    if let Some((description, can_sleep)) = context {
+        println!("⚙️  Context: {}", description);
+        println!("   Can sleep: {}", if can_sleep { "yes" } else { "no" });
+        println!();
+    }
+
+    let mut visited = std::collections::HashSet::new();
+    let config = AnalysisConfig::default();
+    let tree = flowsight_analysis::callgraph::build_flow_tree(
+        function,
+        &parse_result,
+        &analysis.async_bindings,
+        &mut visited,
+        0,
+        &config,
+    );
+    if let Some(mut tree) = tree {
+        flowsight_analysis::callgraph::propagate_execution_context(&mut tree, &analysis.async_bindings);
+        println!("🌳 Flow:");
+        let style = TreeStyle {
+            line_numbers: true,
+            ..TreeStyle::unicode()
+        };
+        for line in tree_to_string(&tree, &style).lines() {
+            println!("   {}", line);
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_implementations(target: &str, dir: &Path) -> Result<()> {
+    let Some((ops_type, field)) = target.rsplit_once('.') else {
+        anyhow::bail!("expected OPS.FIELD, e.g. file_operations.read");
+    };
+
+    let resolver = FuncPtrResolver::new();
+    let mut engine = QueryEngine::new();
+    let files = collect_sources(&[dir.to_path_buf()], &[])?;
+    for (file, result) in ParallelParser::new().parse_files(&files) {
+        let Ok(parse_result) = result else {
+            continue;
+        };
+        let index = engine.index_mut();
+        for func in parse_result.functions.into_values() {
+            index.add_function(func, &file);
+        }
+        if let Ok(source) = std::fs::read_to_string(&file) {
+            for assignment in resolver.find_ops_assignments(&source, &file.to_string_lossy()) {
+                index.add_ops_assignment(assignment);
+            }
+        }
+    }
+
+    let implementations = engine.find_callback_implementations(ops_type, field);
+    println!("🔎 Implementations of {}.{}: {}", ops_type, field, implementations.len());
+    println!();
+
+    for func in implementations {
+        match &func.location {
+            Some(loc) => println!("  {}()  {}:{}", func.name, loc.file, loc.line),
+            None => println!("  {}()", func.name),
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_uncertain(dir: &Path) -> Result<()> {
+    let ModuleAnalysis {
+        parse_result,
+        analysis,
+    } = analyze_module(&[dir.to_path_buf()], &[])?;
+
+    let classifier = ResultClassifier::new();
+    let mut engine = QueryEngine::new();
+    let index = engine.index_mut();
+    for edge in classifier.classify_flow_edges(&analysis.flow_trees, &parse_result.functions) {
+        let file = parse_result
+            .functions
+            .get(&edge.caller)
+            .and_then(|f| f.location.as_ref())
+            .map(|l| l.file.clone())
+            .unwrap_or_default();
+        for call in edge.to_calls(&file) {
+            index.add_classified_call(call);
+        }
+    }
+
+    let uncertain: Vec<_> = engine
+        .edges_by_confidence(Confidence::Unknown)
+        .into_iter()
+        .filter(|e| e.overall_confidence != Confidence::Certain)
+        .collect();
+    println!("❓ Uncertain call sites in {}: {}", dir.display(), uncertain.len());
+    println!();
+
+    for edge in &uncertain {
+        let file = engine
+            .index()
+            .get_classified_calls(&edge.caller)
+            .first()
+            .map(|c| c.file.as_str())
+            .unwrap_or_default();
+        println!("  {} {}()  {} {}", edge.overall_confidence.symbol(), edge.caller, file, edge.call_site);
+        for target in &edge.targets {
+            println!("     → {}()  [{}] {}", target.name, target.confidence, target.reason);
+        }
+    }
+    if !uncertain.is_empty() {
+        println!();
+        println!("💡 Add a user annotation for a call site to pin down its targets");
+    }
+
+    Ok(())
+}
+
+/// Index of every function in the C and Rust sources under `dir`
+///
+/// Paths matching the directory's `.flowsightignore` are skipped.
+fn directory_index(dir: &Path) -> Result<SymbolIndex> {
+    let mut index = SymbolIndex::with_root(dir);
+    let files = collect_sources(&[dir.to_path_buf()], &[])?;
+    for (file, result) in ParallelParser::new().parse_files(&files) {
+        let Ok(parse_result) = result else {
+            continue;
+        };
+        for func in parse_result.functions.into_values() {
+            index.add_function(func, &file);
+        }
+    }
+    Ok(index)
+}
+
+/// `file:line` of `loc`, the file relative to the index root when under it
+fn display_location(index: &SymbolIndex, loc: &flowsight_core::Location) -> String {
+    let file = index
+        .relative_path(Path::new(&loc.file))
+        .unwrap_or_else(|| PathBuf::from(&loc.file));
+    format!("{}:{}", file.display(), loc.line)
+}
+
+fn cmd_metrics(dir: &Path, top: usize) -> Result<()> {
+    let index = directory_index(dir)?;
 
-    /// List all async handlers
-    Async {
-        /// Source file
-        #[arg(value_name = "FILE")]
-        file: PathBuf,
-    },
+    println!("{:>10}  {:>7}  FUNCTION", "COMPLEXITY", "NESTING");
+    for func in index.most_complex(top) {
+        let location = func
+            .location
+            .as_ref()
+            .map(|loc| format!("  {}", display_location(&index, loc)))
+            .unwrap_or_default();
+        println!("{:>10}  {:>7}  {}(){}", func.complexity, func.max_nesting, func.name, location);
+    }
 
-    /// List all callbacks
-    Callbacks {
-        /// Source file
-        #[arg(value_name = "FILE")]
-        file: PathBuf,
-    },
+    Ok(())
 }
 
-fn main() -> Result<()> {
-    // Initialize logging
-    tracing_subscriber::fmt::init();
-
-    let cli = Cli::parse();
+fn cmd_io(dir: &Path) -> Result<()> {
+    let index = directory_index(dir)?;
+    let functions = index.io_functions();
+    if functions.is_empty() {
+        println!("No functions access MMIO registers");
+        return Ok(());
+    }
 
-    match cli.command {
-        Commands::Analyze {
-            file,
-            output,
-            format,
-        } => {
-            cmd_analyze(&file, output.as_deref(), &format)?;
-        }
-        Commands::Flow { file, function } => {
-            cmd_flow(&file, &function)?;
-        }
-        Commands::Trace { file, function, format } => {
-            cmd_trace(&file, &function, &format)?;
-        }
-        Commands::Callers { file, function } => {
-            cmd_callers(&file, &function)?;
-        }
-        Commands::Callees { file, function } => {
-            cmd_callees(&file, &function)?;
-        }
-        Commands::Async { file } => {
-            cmd_async(&file)?;
-        }
-        Commands::Callbacks { file } => {
-            cmd_callbacks(&file)?;
+    println!("🔌 Functions accessing MMIO registers ({}):", functions.len());
+    println!();
+    for func in functions {
+        let location = func
+            .location
+            .as_ref()
+            .map(|loc| format!("  {}", display_location(&index, loc)))
+            .unwrap_or_default();
+        println!("  {}(){}", func.name, location);
+        for register in &func.io_registers {
+            println!("      {}", register);
         }
     }
 
     Ok(())
 }
 
-fn cmd_analyze(file: &PathBuf, output: Option<&std::path::Path>, format: &str) -> Result<()> {
-    println!("📂 Analyzing: {}", file.display());
+fn cmd_deps(dir: &Path, ignore: &[String], format: &str) -> Result<()> {
+    let project = flowsight::Project::open_ignoring(dir, ignore)?;
+    let engine = project.query();
+    match format {
+        "dot" => print!("{}", engine.file_dependency_dot()),
+        "text" => {
+            let edges = engine.file_dependency_graph();
+            if edges.is_empty() {
+                println!("No dependencies between files");
+                return Ok(());
+            }
+            let mut current: Option<&str> = None;
+            for (from, to) in &edges {
+                if current != Some(from.as_str()) {
+                    println!("{}", from);
+                    current = Some(from.as_str());
+                }
+                println!("  → {}", to);
+            }
+        }
+        other => anyhow::bail!("unknown format: {} (expected text or dot)", other),
+    }
+    Ok(())
+}
 
-    let parser = get_parser();
-    let mut parse_result = parser.parse_file(file)?;
+fn cmd_similar(dir: &Path, function: &str, top: usize) -> Result<()> {
+    let engine = QueryEngine::with_index(directory_index(dir)?);
+    if engine.get_function(function).is_none() {
+        anyhow::bail!("{}", not_found(function, &engine.index().functions));
+    }
 
-    println!(
-        "   Found {} functions, {} structs",
-        parse_result.functions.len(),
-        parse_result.structs.len()
-    );
+    println!("🔍 Functions similar to {}():", function);
+    println!();
+    for (func, score) in engine.most_similar(function, top) {
+        let location = func
+            .location
+            .as_ref()
+            .map(|loc| format!("  {}", display_location(engine.index(), loc)))
+            .unwrap_or_default();
+        println!("  {:.2}  {}(){}", score, func.name, location);
+    }
 
-    let source = std::fs::read_to_string(file)?;
-    let mut analyzer = Analyzer::new();
-    let analysis = analyzer.analyze(&source, &mut parse_result)?;
+    Ok(())
+}
 
-    println!(
-        "   Found {} async handlers, {} entry points",
-        analysis.async_bindings.len(),
-        analysis.entry_points.len()
-    );
+fn cmd_by_param(dir: &Path, type_name: &str) -> Result<()> {
+    let engine = QueryEngine::with_index(directory_index(dir)?);
+    let functions = engine.find_by_param_type(type_name);
+    if functions.is_empty() {
+        println!("No functions take a `{}` parameter", type_name);
+        return Ok(());
+    }
 
-    if format == "json" {
-        let result = serde_json::json!({
-            "file": file.to_string_lossy(),
-            "functions": parse_result.functions.len(),
-            "structs": parse_result.structs.len(),
-            "async_bindings": analysis.async_bindings.len(),
-            "entry_points": analysis.entry_points,
-            "flow_trees": analysis.flow_trees,
-        });
+    println!("🔍 Functions taking `{}`:", type_name);
+    println!();
+    for func in functions {
+        let params: Vec<String> = func
+            .params
+            .iter()
+            .map(|p| format!("{} {}", p.type_name, p.name).trim().to_string())
+            .collect();
+        let location = func
+            .location
+            .as_ref()
+            .map(|loc| format!("  {}", display_location(engine.index(), loc)))
+            .unwrap_or_default();
+        println!("  {}({}){}", func.name, params.join(", "), location);
+    }
 
-        let json = serde_json::to_string_pretty(&result)?;
+    Ok(())
+}
 
-        if let Some(out_path) = output {
-            std::fs::write(out_path, &json)?;
-            println!("   Output written to: {}", out_path.display());
-        } else {
-            println!("{}", json);
+fn cmd_check(
+    paths: &[PathBuf],
+    ignore: &[String],
+    index_dir: Option<&Path>,
+    format: &str,
+    output: Option<&Path>,
+) -> Result<()> {
+    let files = collect_sources(paths, ignore)?;
+    if files.is_empty() {
+        anyhow::bail!("no sources found");
+    }
+
+    // Each file is checked on its own, so same-named statics don't collide
+    let kb = KnowledgeBase::builtin();
+    let checkers = Checkers::new(&kb);
+    let mut index = SymbolIndex::new();
+    let mut findings = Vec::new();
+    for file in &files {
+        let source = std::fs::read_to_string(file)?;
+        let parse_result = get_parser_for(file)?.parse(&source, &file.to_string_lossy())?;
+        let checked = checkers.run_checkers(file, &source, &parse_result.functions);
+        findings.extend(checked.findings);
+
+        if index_dir.is_some() {
+            for binding in checked.bindings {
+                index.add_async_binding(binding, None);
+            }
+            for func in parse_result.functions.into_values() {
+                index.add_function(func, file);
+            }
         }
-    } else {
-        println!("\n📊 Summary:");
-        println!("   Functions: {}", parse_result.functions.len());
-        println!("   Structs: {}", parse_result.structs.len());
-        println!("   Async handlers: {}", analysis.async_bindings.len());
-        println!("   Entry points: {:?}", analysis.entry_points);
+    }
+    if let Some(dir) = index_dir {
+        IndexStorage::open(dir)?.save_index(&index)?;
     }
 
+    write_findings(findings, files.len(), format, output)
+}
+
+fn cmd_report(dir: &Path, ignore: &[String], output: Option<&Path>) -> Result<()> {
+    let files = collect_sources(&[dir.to_path_buf()], ignore)?;
+    if files.is_empty() {
+        anyhow::bail!("no sources found");
+    }
+    tracing::info!("📂 Analyzing {} files under {}", files.len(), dir.display());
+    let ModuleAnalysis {
+        parse_result,
+        analysis,
+    } = Analyzer::new().analyze_files(&files, &AnalysisConfig::default())?;
+
+    // Checked file by file, as `check` does, so same-named statics don't collide
+    let kb = KnowledgeBase::builtin();
+    let checkers = Checkers::new(&kb);
+    let mut findings = Vec::new();
+    let mut assignments = Vec::new();
+    for file in &files {
+        let source = std::fs::read_to_string(file)?;
+        let file_result = get_parser_for(file)?.parse(&source, &file.to_string_lossy())?;
+        let checked = checkers.run_checkers(file, &source, &file_result.functions);
+        findings.extend(checked.findings);
+        assignments.extend(checked.assignments);
+    }
+    findings.sort_by_key(|f| {
+        let loc = f.location();
+        (loc.file, loc.line)
+    });
+
+    let mut engine = module_engine(parse_result, analysis.async_bindings);
+    for assignment in assignments {
+        engine.index_mut().add_ops_assignment(assignment);
+    }
+    let html = engine.html_report(&dir.display().to_string(), &kb, &findings, &analysis.flow_trees);
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, html)?;
+            tracing::info!("Report written to: {}", path.display());
+        }
+        None => print!("{}", html),
+    }
     Ok(())
 }
 
-fn cmd_flow(file: &PathBuf, function: &str) -> Result<()> {
-    let parser = get_parser();
-    let mut parse_result = parser.parse_file(file)?;
+/// Check only changed code, following its calls through a saved index
+///
+/// Every function in a listed file is checked; with `git diff` only those
+/// overlapping a changed hunk. IRQ handlers anywhere in the index are
+/// reported when a call path from them runs through a checked function.
+fn cmd_check_changed(
+    paths: &[PathBuf],
+    ignore: &[String],
+    index_dir: Option<&Path>,
+    format: &str,
+    output: Option<&Path>,
+) -> Result<()> {
+    let under_paths = |file: &Path| {
+        let file = flowsight_index::normalize_path(file);
+        paths.is_empty() || paths.iter().any(|p| file.starts_with(flowsight_index::normalize_path(p)))
+    };
+    // Same rules as a full check of the directory arguments, or of the
+    // working tree without any
+    let roots: Vec<&Path> = match paths {
+        [] => vec![Path::new(".")],
+        paths => paths.iter().filter(|p| p.is_dir()).map(PathBuf::as_path).collect(),
+    };
+    let mut rules = Vec::new();
+    for root in roots {
+        rules.push((flowsight_index::normalize_path(root), IgnoreRules::for_project(root, ignore)?));
+    }
+    let ignored = |file: &Path| {
+        let file = flowsight_index::normalize_path(file);
+        rules
+            .iter()
+            .any(|(root, rules)| file.strip_prefix(root).is_ok_and(|relative| rules.excludes(relative)))
+    };
+    let changes: Vec<ChangedFile> = changed_files()?
+        .into_iter()
+        .filter(|(file, _)| file.is_file() && flowsight_core::Language::from_path(file).is_some())
+        .filter(|(file, _)| under_paths(file) && !ignored(file))
+        .collect();
 
-    let source = std::fs::read_to_string(file)?;
-    let mut analyzer = Analyzer::new();
-    let analysis = analyzer.analyze(&source, &mut parse_result)?;
+    let mut index = match index_dir {
+        Some(dir) => IndexStorage::open(dir)?.load_index()?,
+        None => SymbolIndex::new(),
+    };
 
-    // Find the flow tree for the specified function
-    for tree in &analysis.flow_trees {
-        if tree.name == function {
-            print_flow_tree(tree, 0);
-            return Ok(());
+    // Changed files replace their stale entries in the index
+    let kb = KnowledgeBase::builtin();
+    let mut parsed = Vec::new();
+    let mut focus = HashSet::new();
+    for (file, hunks) in &changes {
+        let source = std::fs::read_to_string(file)?;
+        let parse_result = get_parser_for(file)?.parse(&source, &file.to_string_lossy())?;
+        let changed: HashSet<String> = parse_result
+            .functions
+            .values()
+            .filter(|func| match (hunks, &func.location) {
+                (None, _) => true,
+                (Some(hunks), Some(loc)) => hunks.iter().any(|&(start, end)| start <= loc.end_line && loc.line <= end),
+                (Some(_), None) => false,
+            })
+            .map(|func| func.name.clone())
+            .collect();
+        focus.extend(changed.iter().cloned());
+
+        index.remove_file(file);
+        for func in parse_result.functions.values() {
+            index.add_function(func.clone(), file);
         }
+        parsed.push((file, source, parse_result, changed));
     }
 
-    // If not found in flow trees, try to build one
-    if let Some(func) = parse_result.functions.get(function) {
-        println!("{}()", function);
-        for callee in &func.calls {
-            println!("  └── {}()", callee);
+    let mut findings = Vec::new();
+    let mut changed_files = HashSet::new();
+    for (file, source, parse_result, changed) in &parsed {
+        let filename = file.to_string_lossy();
+        let checkers = Checkers::new(&kb).with_focus(changed);
+        let assignments = checkers.resolver.find_ops_assignments(source, &filename);
+        findings.extend(checkers.check_file(file, source, &parse_result.functions, &assignments));
+        let irq_checker = IrqChecker::new().with_focus(focus.clone());
+        findings.extend(Finding::from_irq_reports(
+            &filename,
+            irq_checker.check_file(&filename, source, &index.functions, &kb),
+            &index.functions,
+        ));
+        changed_files.insert(flowsight_index::normalize_path(file));
+    }
+
+    // Handlers registered in unchanged files may call into the changed code
+    let mut by_file: HashMap<String, Vec<AsyncBinding>> = HashMap::new();
+    for indexed in index.async_bindings.values().flatten() {
+        let Some(loc) = &indexed.binding.bind_location else {
+            continue;
+        };
+        if !changed_files.contains(&flowsight_index::normalize_path(Path::new(&loc.file))) {
+            by_file.entry(loc.file.clone()).or_default().push(indexed.binding.clone());
         }
-    } else {
-        println!("Function '{}' not found", function);
+    }
+    let irq_checker = IrqChecker::new().with_focus(focus);
+    for (file, bindings) in by_file {
+        findings.extend(Finding::from_irq_reports(
+            &file,
+            irq_checker.check_bindings(bindings, &index.functions, &kb),
+            &index.functions,
+        ));
     }
 
-    Ok(())
+    write_findings(findings, changes.len(), format, output)
 }
 
-fn print_flow_tree(node: &flowsight_core::FlowNode, indent: usize) {
-    let prefix = "  ".repeat(indent);
-    println!("{}{}", prefix, node.display_name);
+/// A changed file and its changed line ranges; `None` for the whole file
+type ChangedFile = (PathBuf, Option<Vec<(u32, u32)>>);
+
+/// Files changed since `HEAD`, with the new-side line ranges of their hunks
+///
+/// Files listed on stdin (one per line) are taken instead when stdin is not
+/// a terminal; those are checked as a whole.
+fn changed_files() -> Result<Vec<ChangedFile>> {
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        let listed: Vec<ChangedFile> = std::io::read_to_string(stdin)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| (PathBuf::from(line), None))
+            .collect();
+        if !listed.is_empty() {
+            return Ok(listed);
+        }
+    }
 
-    for (i, child) in node.children.iter().enumerate() {
-        let is_last = i == node.children.len() - 1;
-        let connector = if is_last { "└── " } else { "├── " };
-        print!("{}{}", prefix, connector);
-        print_flow_tree(child, indent + 1);
+    let output = std::process::Command::new("git")
+        .args([
+            "diff",
+            "--relative",
+            "--unified=0",
+            "--no-color",
+            "--no-ext-diff",
+            "--src-prefix=a/",
+            "--dst-prefix=b/",
+            "HEAD",
+        ])
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!("git diff failed: {}", String::from_utf8_lossy(&output.stderr).trim());
     }
+    Ok(parse_diff_hunks(&String::from_utf8_lossy(&output.stdout))
+        .into_iter()
+        .map(|(file, hunks)| (file, Some(hunks)))
+        .collect())
 }
 
-fn cmd_async(file: &PathBuf) -> Result<()> {
-    let parser = get_parser();
-    let mut parse_result = parser.parse_file(file)?;
+/// Files of a `--unified=0` diff with the new-side line ranges of their hunks
+///
+/// A pure deletion counts as a change to the line it follows; deleted files
+/// are left out.
+fn parse_diff_hunks(diff: &str) -> Vec<(PathBuf, Vec<(u32, u32)>)> {
+    let mut files: Vec<(PathBuf, Vec<(u32, u32)>)> = Vec::new();
+    let mut in_file = false;
+    for line in diff.lines() {
+        if line.starts_with("diff ") {
+            in_file = false;
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            in_file = false;
+            if let Some(path) = path.strip_prefix("b/") {
+                files.push((PathBuf::from(path), Vec::new()));
+                in_file = true;
+            }
+        } else if let (true, Some(hunk)) = (in_file, line.strip_prefix("@@ ")) {
+            // "@@ -12,3 +12,4 @@ context"
+            let Some(new) = hunk.split_whitespace().find_map(|part| part.strip_prefix('+')) else {
+                continue;
+            };
+            let (start, count) = match new.split_once(',') {
+                Some((start, count)) => (start.parse::<u32>(), count.parse::<u32>().unwrap_or(1)),
+                None => (new.parse::<u32>(), 1),
+            };
+            if let (Ok(start), Some((_, hunks))) = (start, files.last_mut()) {
+                hunks.push((start, start + count.saturating_sub(1)));
+            }
+        }
+    }
+    files
+}
 
-    let source = std::fs::read_to_string(file)?;
-    let mut analyzer = Analyzer::new();
-    let analysis = analyzer.analyze(&source, &mut parse_result)?;
+/// The checkers `check`, `report` and `check --changed` run on each file
+struct Checkers<'kb> {
+    kb: &'kb KnowledgeBase,
+    errors: ErrorChecker,
+    loops: ControlFlowChecker,
+    locks: LockBalanceChecker,
+    irq: IrqChecker,
+    atomic: AtomicChecker,
+    tracker: AsyncTracker,
+    resolver: FuncPtrResolver,
+}
 
-    println!("🔄 Async Handlers in {}:", file.display());
-    println!();
+/// Findings of one file, with the async bindings and ops assignments found
+/// on the way
+struct FileCheck {
+    findings: Vec<Finding>,
+    bindings: Vec<AsyncBinding>,
+    assignments: Vec<OpsAssignment>,
+}
 
-    for binding in &analysis.async_bindings {
-        let mechanism = format!("{:?}", binding.mechanism);
-        let context = if binding.context.can_sleep() {
-            "can sleep"
-        } else {
-            "cannot sleep"
-        };
+impl<'kb> Checkers<'kb> {
+    fn new(kb: &'kb KnowledgeBase) -> Self {
+        Self {
+            kb,
+            errors: ErrorChecker::new(),
+            loops: ControlFlowChecker::new(),
+            locks: LockBalanceChecker::new(),
+            irq: IrqChecker::new(),
+            atomic: AtomicChecker::new(),
+            tracker: AsyncTracker::with_knowledge(kb),
+            resolver: FuncPtrResolver::new(),
+        }
+    }
 
-        println!(
-            "  {} {}()",
-            match &binding.mechanism {
-                flowsight_core::AsyncMechanism::WorkQueue { .. } => "⚙️ ",
-                flowsight_core::AsyncMechanism::Timer { .. } => "⏲️ ",
-                flowsight_core::AsyncMechanism::Interrupt { .. } => "⚡",
-                flowsight_core::AsyncMechanism::Tasklet => "🔄",
-                flowsight_core::AsyncMechanism::KThread => "🧵",
-                _ => "📍",
-            },
-            binding.handler
-        );
-        println!("     Type: {}", mechanism);
-        println!("     Context: {}", context);
-        if !binding.variable.is_empty() {
-            println!("     Variable: {}", binding.variable);
+    /// Only report the per-file findings inside `functions`
+    fn with_focus(mut self, functions: &HashSet<String>) -> Self {
+        self.errors = self.errors.with_focus(functions.clone());
+        self.loops = self.loops.with_focus(functions.clone());
+        self.locks = self.locks.with_focus(functions.clone());
+        self
+    }
+
+    /// Every checker over `functions` of `file`, IRQ and atomic context
+    /// included
+    fn run_checkers(&self, file: &Path, source: &str, functions: &HashMap<String, FunctionDef>) -> FileCheck {
+        let filename = file.to_string_lossy();
+        let assignments = self.resolver.find_ops_assignments(source, &filename);
+        let mut findings = self.check_file(file, source, functions, &assignments);
+        findings.extend(Finding::from_irq_reports(
+            &filename,
+            self.irq.check(source, functions, self.kb),
+            functions,
+        ));
+        let bindings = self.tracker.analyze(source, functions);
+        findings.extend(Finding::from_atomic_sleeps(
+            &filename,
+            self.atomic.check(functions, &bindings, &assignments, self.kb),
+            functions,
+        ));
+        FileCheck {
+            findings,
+            bindings,
+            assignments,
         }
-        println!();
     }
 
+    /// The checkers that only look at `file` itself, given its ops `assignments`
+    fn check_file(
+        &self,
+        file: &Path,
+        source: &str,
+        functions: &HashMap<String, FunctionDef>,
+        assignments: &[OpsAssignment],
+    ) -> Vec<Finding> {
+        let filename = file.to_string_lossy();
+        let mut findings = Finding::from_unchecked(&filename, self.errors.check(source, functions, self.kb));
+        findings.extend(Finding::from_user_copies(
+            &filename,
+            self.errors.check_user_copies(source, functions),
+        ));
+        if flowsight_core::Language::from_path(file) == Some(flowsight_core::Language::C) {
+            findings.extend(Finding::from_infinite_loops(
+                &filename,
+                self.loops.find_infinite_loops(source),
+            ));
+            findings.extend(Finding::from_unbalanced_locks(
+                &filename,
+                self.locks.find_unbalanced(source),
+            ));
+            findings.extend(Finding::from_missing_callbacks(
+                &filename,
+                find_missing_callbacks(assignments, self.kb),
+            ));
+        }
+        findings
+    }
+}
+
+/// Print or save `findings` from `checked` files in `format`
+fn write_findings(mut findings: Vec<Finding>, checked: usize, format: &str, output: Option<&Path>) -> Result<()> {
+    findings.sort_by_key(|f| {
+        let loc = f.location();
+        (loc.file, loc.line)
+    });
+
+    let report = match format {
+        "sarif" => serde_json::to_string_pretty(&sarif::to_sarif(&findings))?,
+        "json" => {
+            let mut values = Vec::new();
+            for finding in &findings {
+                let mut value = serde_json::to_value(finding)?;
+                if let Some(suggestion) = finding.suggestion() {
+                    value["suggestion"] = serde_json::Value::String(suggestion);
+                }
+                values.push(value);
+            }
+            serde_json::to_string_pretty(&values)?
+        }
+        _ => {
+            let mut text = String::new();
+            for finding in &findings {
+                let loc = finding.location();
+                text.push_str(&format!(
+                    "{}:{}: {} [{}]\n",
+                    loc.file,
+                    loc.line,
+                    finding.message(),
+                    finding.rule().id
+                ));
+                if let Some(suggestion) = finding.suggestion() {
+                    text.push_str(&format!("    suggestion: {}\n", suggestion));
+                }
+            }
+            text.push_str(&format!("{} findings in {} files\n", findings.len(), checked));
+            text
+        }
+    };
+
+    match output {
+        Some(path) => std::fs::write(path, report)?,
+        None => print!("{}", report),
+    }
     Ok(())
 }
 
-fn cmd_callbacks(file: &PathBuf) -> Result<()> {
-    let parser = get_parser();
-    let mut parse_result = parser.parse_file(file)?;
+fn cmd_schema(type_name: Option<&str>) -> Result<()> {
+    let json = match type_name {
+        Some(name) => match schema::schema(name) {
+            Some(schema) => serde_json::to_value(schema)?,
+            None => anyhow::bail!(
+                "unknown type '{}' (expected one of: {})",
+                name,
+                schema::SCHEMA_TYPES.join(", ")
+            ),
+        },
+        None => {
+            let mut all = serde_json::Map::new();
+            for name in schema::SCHEMA_TYPES {
+                let schema = schema::schema(name).expect("listed type has a schema");
+                all.insert(name.to_string(), serde_json::to_value(schema)?);
+            }
+            serde_json::Value::Object(all)
+        }
+    };
+    println!("{}", serde_json::to_string_pretty(&json)?);
+    Ok(())
+}
+
+fn cmd_timeline(pattern: &str) -> Result<()> {
+    let kb = KnowledgeBase::builtin();
+    let Some(timeline) = kb.get_async_timeline(pattern) else {
+        let mut known: Vec<&str> = kb
+            .async_patterns
+            .iter()
+            .filter(|(_, p)| p.timeline.is_some())
+            .map(|(name, _)| name.as_str())
+            .collect();
+        known.sort_unstable();
+        anyhow::bail!("no timeline for '{}' (expected one of: {})", pattern, known.join(", "));
+    };
+    print!("{}", flowsight_knowledge::timeline_to_mermaid(timeline));
+    Ok(())
+}
+
+/// Serve `dir` to editor plugins and scripts; see `flowsight::rpc` for the methods
+fn cmd_serve(dir: &Path) -> Result<()> {
+    let project = flowsight::Project::open(dir)?;
+    tracing::info!(
+        "Indexed {} files under {}, serving JSON-RPC on stdio",
+        project.files().len(),
+        dir.display()
+    );
+    let server = flowsight::rpc::Server::new(project);
+    server.serve(std::io::stdin().lock(), BufWriter::new(std::io::stdout().lock()))?;
+    Ok(())
+}
 
+fn cmd_check_irq(file: &Path, format: &str) -> Result<()> {
     let source = std::fs::read_to_string(file)?;
-    let mut analyzer = Analyzer::new();
-    let _ = analyzer.analyze(&source, &mut parse_result)?;
+    let filename = file.to_string_lossy();
+    let parse_result = get_parser().parse(&source, &filename)?;
+    let reports = IrqChecker::new().check(&source, &parse_result.functions, &KnowledgeBase::builtin());
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+        return Ok(());
+    }
+
+    if reports.is_empty() {
+        println!("No hard IRQ handlers registered in {}", filename);
+        return Ok(());
+    }
+    for report in &reports {
+        let registered = report
+            .registered_at
+            .map(|line| format!(" (registered at {}:{})", filename, line))
+            .unwrap_or_default();
+        if report.is_clean() {
+            println!("✅ {}(){}: never sleeps or recurses", report.handler, registered);
+            continue;
+        }
+        println!("❌ {}(){}:", report.handler, registered);
+        for violation in &report.violations {
+            match violation {
+                IrqViolation::SleepingCall { api, path, line } => {
+                    let at = line.map(|l| format!(" at line {}", l)).unwrap_or_default();
+                    println!("  sleeps: {} → {}(){}", path.join(" → "), api, at);
+                }
+                IrqViolation::Recursion { path } => {
+                    println!("  recurses: {}", path.join(" → "));
+                }
+            }
+        }
+    }
 
-    println!("🔌 Callbacks in {}:", file.display());
+    let failed = reports.iter().filter(|r| !r.is_clean()).count();
     println!();
+    println!("{} of {} handlers have violations", failed, reports.len());
+    Ok(())
+}
 
-    for (name, func) in &parse_result.functions {
-        if func.is_callback {
-            let context = func.callback_context.as_deref().unwrap_or("unknown");
-            println!("  {}()", name);
-            println!("     Context: {}", context);
-            println!();
+fn cmd_diff(old: &Path, new: &Path, format: &str) -> Result<()> {
+    let old_index = IndexStorage::open(old)?.load_index()?;
+    let new_index = IndexStorage::open(new)?.load_index()?;
+    let diff = flowsight_index::diff(&old_index, &new_index);
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+        return Ok(());
+    }
+
+    if diff.is_empty() {
+        println!("No symbol changes");
+        return Ok(());
+    }
+
+    for name in &diff.added_functions {
+        println!("+ {}()", name);
+    }
+    for name in &diff.removed_functions {
+        println!("- {}()", name);
+    }
+    for change in &diff.modified_functions {
+        println!("~ {}()", change.name);
+        if let (Some(old_sig), Some(new_sig)) = (&change.old_signature, &change.new_signature) {
+            println!("    signature: {} -> {}", old_sig, new_sig);
+        }
+        for callee in &change.added_calls {
+            println!("    + calls {}()", callee);
         }
+        for callee in &change.removed_calls {
+            println!("    - calls {}()", callee);
+        }
+    }
+    for entry in &diff.added_async_bindings {
+        let by = entry.registered_by.as_deref().unwrap_or("?");
+        println!("+ async {}() via {} (registered by {})", entry.binding.handler, entry.binding.variable, by);
     }
+    for entry in &diff.removed_async_bindings {
+        let by = entry.registered_by.as_deref().unwrap_or("?");
+        println!("- async {}() via {} (registered by {})", entry.binding.handler, entry.binding.variable, by);
+    }
+    println!();
+    println!(
+        "{} added, {} removed, {} modified functions; {} call edges added, {} removed",
+        diff.added_functions.len(),
+        diff.removed_functions.len(),
+        diff.modified_functions.len(),
+        diff.added_edges.len(),
+        diff.removed_edges.len()
+    );
 
     Ok(())
 }
 
 /// Print execution flow in ftrace style
-fn cmd_trace(file: &PathBuf, function: &str, format: &str) -> Result<()> {
-    let parser = get_parser();
-    let mut parse_result = parser.parse_file(file)?;
-
-    let source = std::fs::read_to_string(file)?;
-    let mut analyzer = Analyzer::new();
-    let analysis = analyzer.analyze(&source, &mut parse_result)?;
+fn cmd_trace(
+    files: &[PathBuf],
+    function: &str,
+    format: &str,
+    weights: Option<&Path>,
+    ignore: &[String],
+    filter: &FlowFilter,
+) -> Result<()> {
+    let ModuleAnalysis {
+        parse_result,
+        analysis,
+    } = analyze_module(files, ignore)?;
 
     // Find the flow tree for the specified function
-    let tree = analysis.flow_trees.iter().find(|t| t.name == function);
-    
-    match format {
-        "ftrace" => {
-            if let Some(tree) = tree {
-                print_ftrace_tree(tree, 0, &parse_result.functions);
-            } else {
-                println!("Function '{}' not found in entry points", function);
-            }
+    let Some(mut tree) = analysis.flow_trees.into_iter().find(|t| t.name == function) else {
+        if parse_result.functions.contains_key(function) {
+            anyhow::bail!("Function '{}' not found in entry points", function);
         }
+        anyhow::bail!("{}", not_found(function, &parse_result.functions));
+    };
+    if let Some(path) = weights {
+        let weights = flowsight_analysis::callgraph::load_weights_csv(path)?;
+        flowsight_analysis::callgraph::apply_weights(&mut tree, &weights);
+    }
+    let tree = filter.apply(tree);
+
+    match format {
+        "ftrace" => print_ftrace_tree(&tree, 0, &parse_result.functions),
         "markdown" => {
             println!("# Execution Flow: {}()", function);
             println!();
             println!("```");
-            if let Some(tree) = tree {
-                print_ftrace_tree(tree, 0, &parse_result.functions);
-            }
+            print_ftrace_tree(&tree, 0, &parse_result.functions);
             println!("```");
         }
         "json" => {
-            if let Some(tree) = tree {
-                let json = serde_json::to_string_pretty(tree)?;
-                println!("{}", json);
-            }
-        }
-        _ => {
-            println!("Unknown format: {}", format);
+            let json = serde_json::to_string_pretty(&tree)?;
+            println!("{}", json);
         }
+        "dot" => print!("{}", flowsight_analysis::export::flow_tree_to_dot(&tree)),
+        "mermaid" => print!("{}", flowsight_analysis::export::flow_tree_to_mermaid(&tree)),
+        other => anyhow::bail!(
+            "unknown format: {} (expected ftrace, markdown, json, dot or mermaid)",
+            other
+        ),
     }
 
     Ok(())
 }
 
 fn print_ftrace_tree(node: &flowsight_core::FlowNode, depth: usize, functions: &std::collections::HashMap<String, flowsight_core::FunctionDef>) {
+    print_ftrace_node(node, depth, functions, None, None);
+}
+
+/// Print `node` at `call_line` (where `caller` calls it) when known, else at
+/// its definition
+fn print_ftrace_node(
+    node: &flowsight_core::FlowNode,
+    depth: usize,
+    functions: &std::collections::HashMap<String, flowsight_core::FunctionDef>,
+    caller: Option<&flowsight_core::FunctionDef>,
+    call_line: Option<u32>,
+) {
     let indent = "  ".repeat(depth);
     let cpu = " 0)";
     
     // Get line number info
-    let line_info = if let Some(loc) = &node.location {
+    let line_info = if let Some(line) = call_line {
+        format!("L{:<4}", line)
+    } else if let Some(loc) = &node.location {
         format!("L{:<4}", loc.line)
     } else if let Some(func) = functions.get(&node.name) {
         if let Some(loc) = &func.location {
@@ -362,6 +2088,7 @@ fn print_ftrace_tree(node: &flowsight_core::FlowNode, depth: usize, functions: &
                 _ => " [A]",
             }
         }
+        _ if flowsight_analysis::callgraph::is_macro_call(&node.name) => " [M]",
         flowsight_core::FlowNodeType::KernelApi => " [K]",
         flowsight_core::FlowNodeType::External => " [E]",
         _ => "",
@@ -371,15 +2098,33 @@ fn print_ftrace_tree(node: &flowsight_core::FlowNode, depth: usize, functions: &
         println!("{}{} {} |{}{}();{}", cpu, line_info, indent, indent, node.name, async_tag);
     } else {
         println!("{}{} {} |{}{}() {{{}", cpu, line_info, indent, indent, node.name, async_tag);
+        // Switch and case nodes aren't functions; their calls are made by the caller
+        let (caller, from) = match functions.get(&node.name) {
+            Some(func) => (Some(func), 0),
+            None => (caller, node.location.as_ref().map_or(0, |loc| loc.line)),
+        };
+        // Repeated calls to one callee take successive call sites
+        let mut last_site: HashMap<&str, u32> = HashMap::new();
         for child in &node.children {
-            print_ftrace_tree(child, depth + 1, functions);
+            let site = caller.and_then(|caller| {
+                let after = last_site.get(child.name.as_str()).copied();
+                caller
+                    .call_sites
+                    .iter()
+                    .filter(|site| site.callee == child.name)
+                    .find(|site| after.map_or(site.line >= from, |after| site.line > after))
+            });
+            if let Some(site) = site {
+                last_site.insert(&child.name, site.line);
+            }
+            print_ftrace_node(child, depth + 1, functions, caller, site.map(|site| site.line));
         }
         println!("{}{} {} |{}}}", cpu, line_info, indent, indent);
     }
 }
 
 /// Show who calls a function
-fn cmd_callers(file: &PathBuf, function: &str) -> Result<()> {
+fn cmd_callers(file: &Path, function: &str) -> Result<()> {
     let parser = get_parser();
     let mut parse_result = parser.parse_file(file)?;
 
@@ -397,7 +2142,10 @@ fn cmd_callers(file: &PathBuf, function: &str) -> Result<()> {
         if func.calls.contains(&function.to_string()) {
             found = true;
             let loc = func.location.as_ref()
-                .map(|l| format!("{}:{}", l.file.split('/').last().unwrap_or(&l.file), l.line))
+                .map(|l| {
+                    let name = Path::new(&l.file).file_name().map(|n| n.to_string_lossy());
+                    format!("{}:{}", name.unwrap_or_else(|| l.file.as_str().into()), l.line)
+                })
                 .unwrap_or_default();
             println!("  → {}() [Direct]", name);
             if !loc.is_empty() {
@@ -426,7 +2174,7 @@ fn cmd_callers(file: &PathBuf, function: &str) -> Result<()> {
 }
 
 /// Show what a function calls
-fn cmd_callees(file: &PathBuf, function: &str) -> Result<()> {
+fn cmd_callees(file: &Path, function: &str) -> Result<()> {
     let parser = get_parser();
     let parse_result = parser.parse_file(file)?;
 
@@ -447,12 +2195,25 @@ fn cmd_callees(file: &PathBuf, function: &str) -> Result<()> {
                 } else {
                     " [External]"
                 };
+
+                // Every line calling it, e.g. "L12, L30"
+                let lines: Vec<String> = func
+                    .call_sites
+                    .iter()
+                    .filter(|site| &site.callee == callee)
+                    .map(|site| format!("L{}", site.line))
+                    .collect();
+                let lines = if lines.is_empty() {
+                    String::new()
+                } else {
+                    format!("  {}", lines.join(", "))
+                };
                 
-                println!("  {}{}(){}", prefix, callee, suffix);
+                println!("  {}{}(){}{}", prefix, callee, suffix, lines);
             }
         }
     } else {
-        println!("  Function '{}' not found", function);
+        println!("  {}", not_found(function, &parse_result.functions));
     }
 
     Ok(())
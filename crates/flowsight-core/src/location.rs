@@ -1,9 +1,10 @@
 //! Source code location types
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Represents a location in source code
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema, Default)]
 pub struct Location {
     /// File path
     pub file: String,
@@ -15,6 +16,10 @@ pub struct Location {
     pub end_line: u32,
     /// End column (0-based)
     pub end_column: u32,
+    /// Macro whose expansion generated the code here, if any
+    /// (e.g. "DEFINE_SIMPLE_ATTRIBUTE")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expanded_from: Option<String>,
 }
 
 impl Location {
@@ -26,6 +31,7 @@ impl Location {
             column,
             end_line: line,
             end_column: column,
+            expanded_from: None,
         }
     }
 
@@ -43,6 +49,7 @@ impl Location {
             column,
             end_line,
             end_column,
+            expanded_from: None,
         }
     }
 }
@@ -1,10 +1,12 @@
 //! Core type definitions
 
 use crate::location::Location;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// Function definition
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct FunctionDef {
     /// Function name
     pub name: String,
@@ -24,10 +26,203 @@ pub struct FunctionDef {
     pub callback_context: Option<String>,
     /// Attributes (static, inline, __init, etc.)
     pub attributes: Vec<String>,
+    /// McCabe cyclomatic complexity (1 for straight-line code, 0 if unknown)
+    #[serde(default)]
+    pub complexity: u32,
+    /// Deepest brace nesting inside the body (0 for a flat body)
+    #[serde(default)]
+    pub max_nesting: u32,
+    /// `goto` targets at the top level of the body, in source order
+    #[serde(default)]
+    pub labels: Vec<GotoLabel>,
+    /// Every call in the body in source order, repeats included
+    /// (empty when the parser does not record call positions)
+    #[serde(default)]
+    pub call_sites: Vec<CallSite>,
+    /// Outermost `switch` statements of the body, in source order
+    #[serde(default)]
+    pub switches: Vec<SwitchDispatch>,
+    /// Async mechanism the function is bound to as a handler
+    #[serde(default)]
+    pub async_mechanism: Option<AsyncMechanism>,
+    /// Context the kernel calls the function in, when it is a known callback
+    #[serde(default)]
+    pub execution_context: Option<ExecutionContext>,
+    /// Whether the body calls an MMIO accessor (`readl`, `iowrite32`, ...)
+    #[serde(default)]
+    pub io_access: bool,
+    /// Register address expressions passed to those accessors, deduplicated
+    /// in source order, e.g. `["dev->regs + REG_CTRL"]`
+    #[serde(default)]
+    pub io_registers: Vec<String>,
+}
+
+/// One call expression in a function body
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct CallSite {
+    pub callee: String,
+    /// 1-based line of the call
+    pub line: u32,
+    /// 0-based column of the callee name
+    pub column: u32,
+    /// Argument expressions as written, e.g. `["udev", "id->idVendor"]`
+    #[serde(default)]
+    pub arguments: Vec<String>,
+}
+
+/// A label in a function body and the `goto`s jumping to it
+///
+/// Kernel functions unwind errors through a cascade of labels
+/// (`err_clk: clk_put(); err_free: kfree(); return ret;`), so each label's
+/// block runs into the next one unless it ends in `return` or `goto`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct GotoLabel {
+    pub name: String,
+    pub line: u32,
+    /// Lines of the `goto` statements targeting this label
+    pub gotos: Vec<u32>,
+    /// Whether the statement before the label can run into it
+    pub fallthrough: bool,
+    /// Calls between this label and the next one, in order of appearance
+    pub calls: Vec<String>,
+}
+
+/// A `switch` in a function body, e.g. an ioctl handler dispatching on `cmd`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SwitchDispatch {
+    /// Switched-on expression as written, without parentheses (e.g. "cmd")
+    pub discriminant: String,
+    pub line: u32,
+    pub end_line: u32,
+    /// `case` and `default` arms in source order
+    pub cases: Vec<SwitchCase>,
+}
+
+/// One `case` (or `default`) arm of a switch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SwitchCase {
+    /// Label value as written (e.g. "FOO_IOC_RESET"); `None` for `default`
+    pub value: Option<String>,
+    pub line: u32,
+    pub end_line: u32,
+    /// Whether the previous arm runs into this one (no `break`/`return`)
+    pub fallthrough: bool,
+    /// Calls in this arm, in order of appearance
+    pub calls: Vec<String>,
+}
+
+impl CallSite {
+    /// Whether the call is an MMIO register accessor
+    pub fn is_mmio(&self) -> bool {
+        mmio_address_index(&self.callee).is_some()
+    }
+
+    /// Register address expression of an MMIO accessor call, if recorded
+    pub fn mmio_register(&self) -> Option<&str> {
+        let index = mmio_address_index(&self.callee)?;
+        self.arguments.get(index).map(String::as_str)
+    }
+}
+
+/// Position of the address argument of MMIO accessor `name`
+///
+/// Covers `read*`/`write*` with their `_relaxed` and `__raw_` forms, the
+/// string variants (`readsl`, `writesl`) and `ioread*`/`iowrite*`. Single
+/// writes take the value first (`writel(val, addr)`); repeated accesses
+/// take the address first (`iowrite32_rep(addr, buf, count)`).
+pub fn mmio_address_index(name: &str) -> Option<usize> {
+    let base = name.strip_prefix("__raw_").unwrap_or(name);
+    let base = base.strip_suffix("_relaxed").unwrap_or(base);
+    match base {
+        "readb" | "readw" | "readl" | "readq" => Some(0),
+        "writeb" | "writew" | "writel" | "writeq" => Some(1),
+        "readsb" | "readsw" | "readsl" | "readsq" | "writesb" | "writesw" | "writesl" | "writesq" => Some(0),
+        "ioread8" | "ioread16" | "ioread32" | "ioread64" | "ioread16be" | "ioread32be" | "ioread64be" => Some(0),
+        "iowrite8" | "iowrite16" | "iowrite32" | "iowrite64" | "iowrite16be" | "iowrite32be" | "iowrite64be" => {
+            Some(1)
+        }
+        "ioread8_rep" | "ioread16_rep" | "ioread32_rep" | "iowrite8_rep" | "iowrite16_rep" | "iowrite32_rep" => {
+            Some(0)
+        }
+        _ => None,
+    }
+}
+
+impl SwitchDispatch {
+    /// Calls made when arm `index` is taken: its own and those of the arms it runs into
+    pub fn arm_calls(&self, index: usize) -> Vec<&str> {
+        let mut calls: Vec<&str> = Vec::new();
+        for (i, arm) in self.cases.iter().enumerate().skip(index) {
+            if i > index && !arm.fallthrough {
+                break;
+            }
+            for call in &arm.calls {
+                if !calls.contains(&call.as_str()) {
+                    calls.push(call);
+                }
+            }
+        }
+        calls
+    }
+}
+
+impl FunctionDef {
+    /// Whether the function carries `attr` (e.g. "__init", "static")
+    pub fn has_attribute(&self, attr: &str) -> bool {
+        self.attributes.iter().any(|a| a == attr)
+    }
+
+    /// Execution context implied by section annotations, if any
+    ///
+    /// `__init`/`__exit` code runs from module load/unload in process context;
+    /// `__irq_entry`/`__softirq_entry` mark interrupt entry code.
+    pub fn annotated_context(&self) -> Option<ExecutionContext> {
+        self.attributes.iter().find_map(|a| match a.as_str() {
+            "__irq_entry" => Some(ExecutionContext::HardIrq),
+            "__softirq_entry" => Some(ExecutionContext::SoftIrq),
+            "__init" | "__exit" | "__sched" => Some(ExecutionContext::Process),
+            _ => None,
+        })
+    }
+
+    /// Labels only reachable through a forward `goto`, i.e. error-unwind code
+    ///
+    /// A label is on the normal path if a backward `goto` targets it (a retry
+    /// loop) or the normal path runs into it; everything else is unwind code.
+    pub fn unwind_labels(&self) -> Vec<&GotoLabel> {
+        let mut normal = true;
+        let mut unwind = Vec::new();
+        for label in &self.labels {
+            normal = label.gotos.iter().any(|&line| line > label.line) || (normal && label.fallthrough);
+            if !normal {
+                unwind.push(label);
+            }
+        }
+        unwind
+    }
+
+    /// Calls made after `goto label`: its block and the blocks it runs into
+    pub fn unwind_path(&self, label: &str) -> Vec<&str> {
+        let Some(start) = self.labels.iter().position(|l| l.name == label) else {
+            return Vec::new();
+        };
+        let mut calls: Vec<&str> = Vec::new();
+        for (i, block) in self.labels[start..].iter().enumerate() {
+            if i > 0 && !block.fallthrough {
+                break;
+            }
+            for call in &block.calls {
+                if !calls.contains(&call.as_str()) {
+                    calls.push(call);
+                }
+            }
+        }
+        calls
+    }
 }
 
 /// Function parameter
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Parameter {
     /// Parameter name
     pub name: String,
@@ -36,7 +231,7 @@ pub struct Parameter {
 }
 
 /// Struct definition
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct StructDef {
     /// Struct name
     pub name: String,
@@ -49,7 +244,7 @@ pub struct StructDef {
 }
 
 /// Struct field
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct StructField {
     /// Field name
     pub name: String,
@@ -61,12 +256,138 @@ pub struct StructField {
     pub is_function_ptr: bool,
     /// Function pointer signature (if applicable)
     pub func_ptr_signature: Option<String>,
-    /// Array size (if applicable)
+    /// Array dimensions as written, joined by `][` (empty for a flexible array)
     pub array_size: Option<String>,
+    /// Typedef name as written when `type_name` was resolved through a typedef
+    #[serde(default)]
+    pub typedef_name: Option<String>,
+}
+
+/// Key in a type size table holding the target's pointer size
+pub const POINTER_SIZE_KEY: &str = "void *";
+
+/// Memory layout of a struct, see [`StructDef::compute_layout`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct StructLayout {
+    pub fields: Vec<FieldLayout>,
+    /// Total size, including tail padding
+    pub size: usize,
+    pub align: usize,
+    /// Bytes lost to padding between fields and at the end
+    pub padding: usize,
+    /// Fields whose type size is unknown; they are laid out as zero-sized
+    pub unknown: Vec<String>,
+}
+
+/// Placement of one struct field
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct FieldLayout {
+    pub name: String,
+    pub offset: usize,
+    pub size: usize,
+}
+
+impl StructDef {
+    /// Lay out the fields with natural alignment and padding
+    ///
+    /// `type_sizes` maps C type names (`int`, `u64`, `struct foo`, ...) to
+    /// sizes in bytes; pointers take the size under [`POINTER_SIZE_KEY`]
+    /// (8 if absent). A type is aligned to its size rounded down to a power of
+    /// two and capped at the pointer size, which matches scalars on common
+    /// ABIs but only approximates nested structs.
+    pub fn compute_layout(&self, type_sizes: &HashMap<String, usize>) -> StructLayout {
+        let word = type_sizes.get(POINTER_SIZE_KEY).copied().unwrap_or(8);
+        let mut offset: usize = 0;
+        let mut align = 1;
+        let mut padding = 0;
+        let mut fields = Vec::new();
+        let mut unknown = Vec::new();
+
+        for field in &self.fields {
+            let elem = if field.is_pointer || field.is_function_ptr {
+                Some(word)
+            } else {
+                type_size(&field.type_name, type_sizes)
+            };
+            let count = match &field.array_size {
+                Some(n) => parse_array_len(n),
+                None => Some(1),
+            };
+            let (size, field_align) = match (elem, count) {
+                (Some(elem), Some(count)) => (elem * count, natural_align(elem, word)),
+                _ => {
+                    unknown.push(field.name.clone());
+                    (0, 1)
+                }
+            };
+
+            let aligned = offset.next_multiple_of(field_align);
+            padding += aligned - offset;
+            fields.push(FieldLayout {
+                name: field.name.clone(),
+                offset: aligned,
+                size,
+            });
+            offset = aligned + size;
+            align = align.max(field_align);
+        }
+
+        let size = offset.next_multiple_of(align);
+        StructLayout {
+            fields,
+            size,
+            align,
+            padding: padding + size - offset,
+            unknown,
+        }
+    }
+}
+
+/// Size of a (non-pointer) field type, ignoring qualifiers and signedness
+fn type_size(type_name: &str, type_sizes: &HashMap<String, usize>) -> Option<usize> {
+    let words: Vec<&str> = type_name
+        .split_whitespace()
+        .filter(|w| !matches!(*w, "const" | "volatile" | "signed" | "unsigned"))
+        .collect();
+    let name = match words.join(" ") {
+        // `unsigned` alone is `unsigned int`
+        n if n.is_empty() && !type_name.trim().is_empty() => "int".to_string(),
+        n => n,
+    };
+    type_sizes.get(&name).copied().or_else(|| {
+        // Also accept the bare tag for `struct foo` / `union foo`
+        let tag = name.strip_prefix("struct ").or_else(|| name.strip_prefix("union "))?;
+        type_sizes.get(tag).copied()
+    })
+}
+
+/// Element count of `n` or `n][m...`, when every dimension is a literal
+///
+/// A flexible array member (`""`) has no elements.
+fn parse_array_len(size: &str) -> Option<usize> {
+    size.split("][")
+        .map(|dim| {
+            let dim = dim.trim();
+            if dim.is_empty() {
+                return Some(0);
+            }
+            match dim.strip_prefix("0x").or_else(|| dim.strip_prefix("0X")) {
+                Some(hex) => usize::from_str_radix(hex, 16).ok(),
+                None => dim.trim_end_matches(['u', 'U', 'l', 'L']).parse().ok(),
+            }
+        })
+        .product()
+}
+
+fn natural_align(size: usize, word: usize) -> usize {
+    match size {
+        0 => 1,
+        n => (1 << n.ilog2()).min(word),
+    }
 }
 
 /// Call edge in call graph
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CallEdge {
     /// Caller function
     pub caller: String,
@@ -76,10 +397,27 @@ pub struct CallEdge {
     pub location: Option<Location>,
     /// Call type
     pub call_type: CallType,
+    /// How the call is made; coarser than `call_type`, used for rendering
+    #[serde(default)]
+    pub call_kind: CallKind,
+}
+
+/// How a call is made, from most to least certain
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub enum CallKind {
+    /// Plain call to a named function
+    #[default]
+    Direct,
+    /// Call through a function pointer (ops table, callback field)
+    Indirect,
+    /// Deferred invocation (work queue, timer, IRQ, ...)
+    Async,
+    /// Macro invocation that looks like a call (e.g. `container_of`, `INIT_WORK`)
+    Macro,
 }
 
 /// Type of function call
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum CallType {
     /// Direct function call
     Direct,
@@ -90,7 +428,7 @@ pub enum CallType {
 }
 
 /// Confidence level for indirect call resolution
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
 pub enum Confidence {
     High,
     Medium,
@@ -98,7 +436,7 @@ pub enum Confidence {
 }
 
 /// Async mechanism type
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum AsyncMechanism {
     WorkQueue { delayed: bool },
     Timer { high_resolution: bool },
@@ -111,8 +449,25 @@ pub enum AsyncMechanism {
     Custom(String),
 }
 
+impl AsyncMechanism {
+    /// Mechanism family, ignoring variants (e.g. "timer" for both timer kinds)
+    pub fn family(&self) -> &str {
+        match self {
+            AsyncMechanism::WorkQueue { .. } => "work queue",
+            AsyncMechanism::Timer { .. } => "timer",
+            AsyncMechanism::Interrupt { .. } => "interrupt",
+            AsyncMechanism::Tasklet => "tasklet",
+            AsyncMechanism::Softirq => "softirq",
+            AsyncMechanism::KThread => "kthread",
+            AsyncMechanism::RcuCallback => "rcu callback",
+            AsyncMechanism::Notifier => "notifier",
+            AsyncMechanism::Custom(name) => name,
+        }
+    }
+}
+
 /// Execution context
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum ExecutionContext {
     /// Process context, can sleep
     Process,
@@ -131,8 +486,25 @@ impl ExecutionContext {
     }
 }
 
+impl std::str::FromStr for ExecutionContext {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace(['-', '_'], "").as_str() {
+            "process" => Ok(ExecutionContext::Process),
+            "softirq" => Ok(ExecutionContext::SoftIrq),
+            "hardirq" | "irq" => Ok(ExecutionContext::HardIrq),
+            "unknown" => Ok(ExecutionContext::Unknown),
+            _ => Err(crate::Error::Config(format!(
+                "unknown execution context '{}' (expected process, softirq or hardirq)",
+                s
+            ))),
+        }
+    }
+}
+
 /// Async binding information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AsyncBinding {
     /// Type of async mechanism
     pub mechanism: AsyncMechanism,
@@ -148,8 +520,72 @@ pub struct AsyncBinding {
     pub context: ExecutionContext,
 }
 
+/// A function stored into a field of an ops table initializer
+///
+/// e.g. `.read = my_read` inside `static struct file_operations my_fops = { ... }`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct OpsAssignment {
+    /// Struct type of the table, without the `struct` keyword (e.g. "file_operations")
+    pub ops_type: String,
+    /// Variable holding the table (e.g. "my_fops")
+    pub variable: String,
+    /// Field assigned (e.g. "read")
+    pub field: String,
+    /// Function assigned to the field
+    pub function: String,
+    /// Location of the field assignment
+    pub location: Option<Location>,
+}
+
+/// One target of a classified call site, as stored in the index
+///
+/// A call through a pointer with several candidate targets is one row per target.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ClassifiedCall {
+    pub caller: String,
+    /// Call site within the caller (e.g. "L42")
+    pub call_site: String,
+    pub target: String,
+    pub confidence: ConfidenceLevel,
+    /// Why the call got this confidence
+    pub reason: String,
+    /// Source file of the caller
+    pub file: String,
+}
+
+/// How an identifier occurrence uses the symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub enum OccurrenceKind {
+    /// Name of a function definition
+    Definition,
+    /// Name in a prototype
+    Declaration,
+    /// Callee of a direct call
+    Call,
+    /// Any other use, e.g. `.read = my_read` or `&my_fn`
+    Reference,
+}
+
+/// One textual occurrence of a symbol, with its exact byte span
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Occurrence {
+    /// Symbol name
+    pub name: String,
+    /// File path
+    pub file: String,
+    /// Byte offset of the first character
+    pub byte_start: usize,
+    /// Byte offset just past the last character
+    pub byte_end: usize,
+    /// Line (1-based)
+    pub line: u32,
+    /// Column in bytes (0-based)
+    pub column: u32,
+    pub kind: OccurrenceKind,
+}
+
 /// Flow node for visualization
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct FlowNode {
     /// Unique ID
     pub id: String,
@@ -175,10 +611,34 @@ pub struct FlowNode {
     pub source_file: Option<String>,
     /// ⭐ 是否是内核调用链的一部分（而非用户代码）
     pub is_kernel_internal: bool,
+    /// Execution frequency from external profiling data (perf, ftrace)
+    #[serde(default)]
+    pub weight: Option<u64>,
+    /// The switch arm this node stands for, when it is a `case` branch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub case: Option<CaseBranch>,
+}
+
+/// A `case` branch of a switch in a flow tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct CaseBranch {
+    /// Switched-on expression (e.g. "cmd")
+    pub discriminant: String,
+    /// Case label value as written; `None` for `default`
+    pub value: Option<String>,
+}
+
+impl CaseBranch {
+    /// Condition under which the arm is taken, e.g. "cmd == (FOO_IOC_RESET)";
+    /// `None` for `default`
+    pub fn condition(&self) -> Option<String> {
+        let value = self.value.as_ref()?;
+        Some(format!("{} == ({})", self.discriminant, value))
+    }
 }
 
 /// Call confidence information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CallConfidence {
     /// Confidence level
     pub level: ConfidenceLevel,
@@ -187,7 +647,7 @@ pub struct CallConfidence {
 }
 
 /// Confidence level enum for classification
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum ConfidenceLevel {
     /// 100% certain - direct call or known binding
     Certain,
@@ -198,9 +658,10 @@ pub enum ConfidenceLevel {
 }
 
 /// Type of flow node
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub enum FlowNodeType {
     /// Normal function call
+    #[default]
     Function,
     /// Entry point (callback)
     EntryPoint,
@@ -211,3 +672,24 @@ pub enum FlowNodeType {
     /// External function
     External,
 }
+
+/// Source language of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    C,
+    /// Rust-for-Linux sources
+    Rust,
+}
+
+impl Language {
+    /// Language of `path`, judged by its extension
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            // `.i`: already preprocessed (`gcc -E` output)
+            "c" | "h" | "i" => Some(Language::C),
+            "rs" => Some(Language::Rust),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Symbol-level diff between two index snapshots
+//!
+//! Compares a before/after pair of `SymbolIndex`es and reports which
+//! functions, call edges and async bindings a change introduced or removed.
+
+use crate::{IndexedAsyncBinding, SymbolIndex};
+use flowsight_core::FunctionDef;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+
+/// A caller -> callee edge
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct EdgeChange {
+    pub caller: String,
+    pub callee: String,
+}
+
+/// A function present in both snapshots whose signature or calls changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionChange {
+    pub name: String,
+    /// Old signature, set only when the signature changed
+    pub old_signature: Option<String>,
+    /// New signature, set only when the signature changed
+    pub new_signature: Option<String>,
+    /// Callees that were added
+    pub added_calls: Vec<String>,
+    /// Callees that were removed
+    pub removed_calls: Vec<String>,
+}
+
+/// Differences between two index snapshots
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexDiff {
+    pub added_functions: Vec<String>,
+    pub removed_functions: Vec<String>,
+    pub modified_functions: Vec<FunctionChange>,
+    /// Call edges only in the new snapshot
+    pub added_edges: Vec<EdgeChange>,
+    /// Call edges only in the old snapshot
+    pub removed_edges: Vec<EdgeChange>,
+    /// Async bindings only in the new snapshot
+    pub added_async_bindings: Vec<IndexedAsyncBinding>,
+    /// Async bindings only in the old snapshot
+    pub removed_async_bindings: Vec<IndexedAsyncBinding>,
+}
+
+impl IndexDiff {
+    /// Whether the snapshots are symbol-equivalent
+    pub fn is_empty(&self) -> bool {
+        self.added_functions.is_empty()
+            && self.removed_functions.is_empty()
+            && self.modified_functions.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+            && self.added_async_bindings.is_empty()
+            && self.removed_async_bindings.is_empty()
+    }
+}
+
+/// Compare `old` and `new`; all result lists are sorted
+pub fn diff(old: &SymbolIndex, new: &SymbolIndex) -> IndexDiff {
+    let mut result = IndexDiff {
+        added_functions: sorted_difference(&new.functions, &old.functions),
+        removed_functions: sorted_difference(&old.functions, &new.functions),
+        ..Default::default()
+    };
+
+    let mut common: Vec<&String> = old
+        .functions
+        .keys()
+        .filter(|name| new.functions.contains_key(*name))
+        .collect();
+    common.sort();
+    for name in common {
+        if let Some(change) = function_change(&old.functions[name], &new.functions[name]) {
+            result.modified_functions.push(change);
+        }
+    }
+
+    let old_edges = edges(old);
+    let new_edges = edges(new);
+    result.added_edges = new_edges.difference(&old_edges).cloned().collect();
+    result.removed_edges = old_edges.difference(&new_edges).cloned().collect();
+
+    result.added_async_bindings = binding_difference(new, old);
+    result.removed_async_bindings = binding_difference(old, new);
+
+    result
+}
+
+/// Human-readable signature, ignoring parameter names
+pub fn signature(func: &FunctionDef) -> String {
+    let params: Vec<&str> = func.params.iter().map(|p| p.type_name.as_str()).collect();
+    format!("{} {}({})", func.return_type, func.name, params.join(", "))
+}
+
+fn function_change(old: &FunctionDef, new: &FunctionDef) -> Option<FunctionChange> {
+    let old_calls: BTreeSet<&String> = old.calls.iter().collect();
+    let new_calls: BTreeSet<&String> = new.calls.iter().collect();
+    let added_calls: Vec<String> = new_calls.difference(&old_calls).map(|s| s.to_string()).collect();
+    let removed_calls: Vec<String> = old_calls.difference(&new_calls).map(|s| s.to_string()).collect();
+
+    let (old_sig, new_sig) = (signature(old), signature(new));
+    let signature_changed = old_sig != new_sig;
+
+    if !signature_changed && added_calls.is_empty() && removed_calls.is_empty() {
+        return None;
+    }
+    Some(FunctionChange {
+        name: new.name.clone(),
+        old_signature: signature_changed.then_some(old_sig),
+        new_signature: signature_changed.then_some(new_sig),
+        added_calls,
+        removed_calls,
+    })
+}
+
+fn sorted_difference<V>(a: &std::collections::HashMap<String, V>, b: &std::collections::HashMap<String, V>) -> Vec<String> {
+    let mut names: Vec<String> = a.keys().filter(|name| !b.contains_key(*name)).cloned().collect();
+    names.sort();
+    names
+}
+
+fn edges(index: &SymbolIndex) -> BTreeSet<EdgeChange> {
+    index
+        .functions
+        .values()
+        .flat_map(|func| {
+            func.calls.iter().map(|callee| EdgeChange {
+                caller: func.name.clone(),
+                callee: callee.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Bindings are matched by handler, variable and registering function
+fn binding_key(entry: &IndexedAsyncBinding) -> (&str, &str, Option<&str>) {
+    (
+        entry.binding.handler.as_str(),
+        entry.binding.variable.as_str(),
+        entry.registered_by.as_deref(),
+    )
+}
+
+fn binding_difference(a: &SymbolIndex, b: &SymbolIndex) -> Vec<IndexedAsyncBinding> {
+    let other: HashSet<_> = b.async_bindings.values().flatten().map(binding_key).collect();
+    let mut only: Vec<IndexedAsyncBinding> = a
+        .async_bindings
+        .values()
+        .flatten()
+        .filter(|entry| !other.contains(&binding_key(entry)))
+        .cloned()
+        .collect();
+    only.sort_by(|x, y| binding_key(x).cmp(&binding_key(y)));
+    only
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowsight_core::{AsyncBinding, AsyncMechanism, ExecutionContext, Parameter};
+    use std::path::Path;
+
+    fn func(name: &str, params: &[&str], calls: &[&str]) -> FunctionDef {
+        FunctionDef {
+            name: name.into(),
+            return_type: "int".into(),
+            params: params
+                .iter()
+                .map(|t| Parameter {
+                    name: "arg".into(),
+                    type_name: t.to_string(),
+                })
+                .collect(),
+            calls: calls.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn binding(handler: &str) -> AsyncBinding {
+        AsyncBinding {
+            mechanism: AsyncMechanism::WorkQueue { delayed: false },
+            variable: "priv->work".into(),
+            handler: handler.into(),
+            bind_location: None,
+            trigger_locations: vec![],
+            context: ExecutionContext::Process,
+        }
+    }
+
+    #[test]
+    fn test_index_diff() {
+        let file = Path::new("drv.c");
+        let mut old = SymbolIndex::new();
+        old.add_function(func("probe", &["struct device *"], &["kzalloc"]), file);
+        old.add_function(func("remove", &["struct device *"], &[]), file);
+        old.add_function(func("helper", &["int"], &[]), file);
+
+        let mut new = SymbolIndex::new();
+        new.add_function(func("probe", &["struct device *"], &["kzalloc", "INIT_WORK"]), file);
+        new.add_function(func("helper", &["int", "bool"], &[]), file);
+        new.add_function(func("work_fn", &["struct work_struct *"], &[]), file);
+        new.add_async_binding(binding("work_fn"), Some("probe".into()));
+
+        let d = diff(&old, &new);
+        assert_eq!(d.added_functions, vec!["work_fn"]);
+        assert_eq!(d.removed_functions, vec!["remove"]);
+
+        assert_eq!(d.modified_functions.len(), 2);
+        let helper = &d.modified_functions[0];
+        assert_eq!(helper.name, "helper");
+        assert_eq!(helper.old_signature.as_deref(), Some("int helper(int)"));
+        assert_eq!(helper.new_signature.as_deref(), Some("int helper(int, bool)"));
+        let probe = &d.modified_functions[1];
+        assert_eq!(probe.old_signature, None);
+        assert_eq!(probe.added_calls, vec!["INIT_WORK"]);
+
+        assert_eq!(
+            d.added_edges,
+            vec![EdgeChange {
+                caller: "probe".into(),
+                callee: "INIT_WORK".into()
+            }]
+        );
+        assert!(d.removed_edges.is_empty());
+        assert_eq!(d.added_async_bindings.len(), 1);
+        assert_eq!(d.added_async_bindings[0].binding.handler, "work_fn");
+
+        assert!(diff(&new, &new).is_empty());
+    }
+}
@@ -0,0 +1,117 @@
+//! Paths to leave out of a project scan
+//!
+//! Patterns are globs matched against paths relative to the project root.
+//! A pattern without a `/` matches a file or directory of that name at any
+//! depth (`samples`, `*.mod.c`); one with a `/` is anchored at the root
+//! (`arch/x86`, `drivers/*/tests`). A trailing `/` is allowed and ignored.
+//! An ignored directory is skipped with everything under it.
+//!
+//! Besides patterns passed in, a project can list them one per line in a
+//! `.flowsightignore` file at its root; blank lines and `#` comments are
+//! skipped.
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::Path;
+use walkdir::{DirEntry, WalkDir};
+
+/// Ignore file read from the project root
+pub const IGNORE_FILE: &str = ".flowsightignore";
+
+/// Compiled ignore patterns
+#[derive(Debug, Clone)]
+pub struct IgnoreRules {
+    globs: GlobSet,
+}
+
+impl IgnoreRules {
+    /// Compile `patterns`
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Result<Self, globset::Error> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let pattern = pattern.as_ref().trim().trim_end_matches('/');
+            if pattern.is_empty() {
+                continue;
+            }
+            let glob = match pattern.strip_prefix('/') {
+                Some(anchored) => anchored.to_string(),
+                None if pattern.contains('/') => pattern.to_string(),
+                None => format!("**/{}", pattern),
+            };
+            builder.add(GlobBuilder::new(&glob).literal_separator(true).build()?);
+        }
+        Ok(Self { globs: builder.build()? })
+    }
+
+    /// `extra` patterns plus those in `root`'s ignore file, if it has one
+    pub fn for_project<S: AsRef<str>>(root: &Path, extra: &[S]) -> Result<Self, globset::Error> {
+        let file = std::fs::read_to_string(root.join(IGNORE_FILE)).unwrap_or_default();
+        let mut patterns: Vec<&str> = file
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+        patterns.extend(extra.iter().map(|p| p.as_ref()));
+        Self::new(&patterns)
+    }
+
+    /// Whether `relative`, a path under the project root, is ignored
+    pub fn is_ignored(&self, relative: &Path) -> bool {
+        self.globs.is_match(relative)
+    }
+
+    /// Whether `relative` or a directory it is under is ignored, i.e. a
+    /// [`Self::walk`] from the project root would not reach it
+    pub fn excludes(&self, relative: &Path) -> bool {
+        relative
+            .ancestors()
+            .any(|p| !p.as_os_str().is_empty() && self.is_ignored(p))
+    }
+
+    /// Every entry under `root` that is not ignored
+    pub fn walk<'a>(&'a self, root: &'a Path) -> impl Iterator<Item = DirEntry> + 'a {
+        WalkDir::new(root)
+            .into_iter()
+            .filter_entry(move |entry| match entry.path().strip_prefix(root) {
+                Ok(relative) => !self.is_ignored(relative),
+                Err(_) => true,
+            })
+            .filter_map(|e| e.ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignore_patterns() {
+        let rules = IgnoreRules::new(&["Documentation/", "/arch/x86", "*.mod.c"]).unwrap();
+        assert!(rules.is_ignored(Path::new("Documentation")));
+        assert!(rules.is_ignored(Path::new("drivers/Documentation")));
+        assert!(rules.is_ignored(Path::new("arch/x86")));
+        assert!(!rules.is_ignored(Path::new("drivers/arch/x86")));
+        assert!(rules.is_ignored(Path::new("drivers/net/foo.mod.c")));
+        assert!(!rules.is_ignored(Path::new("drivers/net/foo.c")));
+        assert!(!rules.is_ignored(Path::new("arch/x86/boot.c")));
+        assert!(rules.excludes(Path::new("arch/x86/boot.c")));
+        assert!(!rules.excludes(Path::new("arch/arm/boot.c")));
+    }
+
+    #[test]
+    fn test_walk_skips_ignored_dirs() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("drivers/net")).unwrap();
+        std::fs::create_dir_all(root.path().join("samples/bpf")).unwrap();
+        std::fs::write(root.path().join("drivers/net/e1000.c"), "").unwrap();
+        std::fs::write(root.path().join("samples/bpf/sample.c"), "").unwrap();
+        std::fs::write(root.path().join(IGNORE_FILE), "# not needed\nsamples\n").unwrap();
+
+        let rules = IgnoreRules::for_project(root.path(), &[] as &[&str]).unwrap();
+        let files: Vec<_> = rules
+            .walk(root.path())
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "c"))
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(files, vec!["e1000.c"]);
+    }
+}
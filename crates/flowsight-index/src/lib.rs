@@ -3,19 +3,25 @@
 //! Provides persistent indexing for code symbols and call graphs.
 //! Supports incremental updates for large codebases.
 
-use flowsight_core::{FunctionDef, StructDef};
+use flowsight_core::{AsyncBinding, ClassifiedCall, FunctionDef, Occurrence, OpsAssignment, StructDef};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
 use std::time::SystemTime;
 
 mod batch_indexer;
+mod diff;
 mod file_tracker;
+mod ignore;
+#[cfg(feature = "storage")]
 mod storage;
 mod tree_cache;
 
 pub use batch_indexer::BatchIndexer;
+pub use diff::{diff, signature, EdgeChange, FunctionChange, IndexDiff};
 pub use file_tracker::FileVersionTracker;
+pub use ignore::{IgnoreRules, IGNORE_FILE};
+#[cfg(feature = "storage")]
 pub use storage::{IndexStorage, StorageError};
 pub use tree_cache::TreeCache;
 
@@ -52,6 +58,45 @@ mod system_time_serde {
     }
 }
 
+/// An async binding together with the function that registers it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedAsyncBinding {
+    /// The binding itself (mechanism, handler, variable, locations)
+    pub binding: AsyncBinding,
+    /// Function containing the bind site (e.g. the probe calling INIT_WORK)
+    pub registered_by: Option<String>,
+}
+
+/// Key under which ops assignments are stored, e.g. "file_operations.read"
+pub fn ops_key(ops_type: &str, field: &str) -> String {
+    let ops_type = ops_type.trim();
+    let ops_type = ops_type.strip_prefix("struct ").unwrap_or(ops_type).trim();
+    format!("{}.{}", ops_type, field)
+}
+
+/// Absolute, lexically normalized form of `path`
+///
+/// Relative paths are resolved against the current directory and `.`/`..`
+/// components are folded, so `./drivers/x.c` and `/src/linux/drivers/x.c`
+/// map to the same key. Symlinks are left alone.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    fold_components(&std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()))
+}
+
+fn fold_components(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
 /// Symbol index containing all indexed information
 #[derive(Debug, Default)]
 pub struct SymbolIndex {
@@ -63,6 +108,20 @@ pub struct SymbolIndex {
     pub functions_by_file: HashMap<PathBuf, Vec<String>>,
     /// File versions for incremental updates
     pub file_versions: HashMap<PathBuf, FileVersion>,
+    /// Async bindings indexed by handler name
+    pub async_bindings: HashMap<String, Vec<IndexedAsyncBinding>>,
+    /// Ops table assignments indexed by "ops_type.field"
+    pub ops_assignments: HashMap<String, Vec<OpsAssignment>>,
+    /// Byte-precise symbol occurrences indexed by name
+    pub occurrences: HashMap<String, Vec<Occurrence>>,
+    /// Classified call sites indexed by caller
+    pub classified_calls: HashMap<String, Vec<ClassifiedCall>>,
+    /// Headers each file `#include`s (resolved paths)
+    pub includes: HashMap<PathBuf, Vec<PathBuf>>,
+    /// Project-relative path of each indexed file under the root
+    pub relative_paths: HashMap<PathBuf, PathBuf>,
+    /// Project root that relative paths are reported against
+    pub root: Option<PathBuf>,
 }
 
 impl SymbolIndex {
@@ -71,15 +130,40 @@ impl SymbolIndex {
         Self::default()
     }
 
+    /// Create an empty index for the project at `root`
+    pub fn with_root(root: &Path) -> Self {
+        Self {
+            root: Some(normalize_path(root)),
+            ..Self::default()
+        }
+    }
+
+    /// `file` relative to the project root, if it lies under it
+    pub fn relative_path(&self, file: &Path) -> Option<PathBuf> {
+        let normalized = normalize_path(file);
+        if let Some(relative) = self.relative_paths.get(&normalized) {
+            return Some(relative.clone());
+        }
+        let root = self.root.as_ref()?;
+        normalized.strip_prefix(root).ok().map(Path::to_path_buf)
+    }
+
     /// Add a function to the index
+    ///
+    /// `file` is normalized (see [`normalize_path`]), so the same file reached
+    /// through different spellings is only recorded once.
     pub fn add_function(&mut self, func: FunctionDef, file: &Path) {
         let name = func.name.clone();
         self.functions.insert(name.clone(), func);
 
-        self.functions_by_file
-            .entry(file.to_path_buf())
-            .or_default()
-            .push(name);
+        let file = normalize_path(file);
+        if let Some(relative) = self.root.as_ref().and_then(|root| file.strip_prefix(root).ok()) {
+            self.relative_paths.insert(file.clone(), relative.to_path_buf());
+        }
+        let names = self.functions_by_file.entry(file).or_default();
+        if !names.contains(&name) {
+            names.push(name);
+        }
     }
 
     /// Add a struct to the index
@@ -87,14 +171,174 @@ impl SymbolIndex {
         self.structs.insert(st.name.clone(), st);
     }
 
+    /// Add an async binding registered by `registered_by`
+    pub fn add_async_binding(&mut self, binding: AsyncBinding, registered_by: Option<String>) {
+        self.async_bindings
+            .entry(binding.handler.clone())
+            .or_default()
+            .push(IndexedAsyncBinding {
+                binding,
+                registered_by,
+            });
+    }
+
+    /// Get async bindings whose handler is `handler`
+    pub fn get_async_bindings(&self, handler: &str) -> &[IndexedAsyncBinding] {
+        self.async_bindings
+            .get(handler)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Add an ops table field assignment
+    pub fn add_ops_assignment(&mut self, assignment: OpsAssignment) {
+        self.ops_assignments
+            .entry(ops_key(&assignment.ops_type, &assignment.field))
+            .or_default()
+            .push(assignment);
+    }
+
+    /// Get all assignments to `ops_type.field`
+    pub fn get_ops_assignments(&self, ops_type: &str, field: &str) -> &[OpsAssignment] {
+        self.ops_assignments
+            .get(&ops_key(ops_type, field))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Add a classified call site
+    pub fn add_classified_call(&mut self, call: ClassifiedCall) {
+        self.classified_calls
+            .entry(call.caller.clone())
+            .or_default()
+            .push(call);
+    }
+
+    /// Get the classified call sites of `caller`
+    pub fn get_classified_calls(&self, caller: &str) -> &[ClassifiedCall] {
+        self.classified_calls
+            .get(caller)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Add a symbol occurrence
+    pub fn add_occurrence(&mut self, occurrence: Occurrence) {
+        self.occurrences
+            .entry(occurrence.name.clone())
+            .or_default()
+            .push(occurrence);
+    }
+
+    /// Get all occurrences of `name`
+    pub fn get_occurrences(&self, name: &str) -> &[Occurrence] {
+        self.occurrences
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Record the headers `file` includes; both sides are normalized
+    pub fn set_includes(&mut self, file: &Path, headers: Vec<PathBuf>) {
+        let headers = headers.iter().map(|h| normalize_path(h)).collect();
+        self.includes.insert(normalize_path(file), headers);
+    }
+
+    /// Headers directly included by `file`
+    pub fn get_includes(&self, file: &Path) -> &[PathBuf] {
+        self.includes
+            .get(&normalize_path(file))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Headers reachable from `file` through nested includes
+    pub fn visible_headers(&self, file: &Path) -> Vec<PathBuf> {
+        let file = normalize_path(file);
+        let mut visible: Vec<PathBuf> = Vec::new();
+        let mut pending: Vec<&Path> = vec![&file];
+        while let Some(current) = pending.pop() {
+            for header in self.get_includes(current) {
+                if *header != file && !visible.contains(header) {
+                    visible.push(header.clone());
+                    pending.push(header);
+                }
+            }
+        }
+        visible
+    }
+
+    /// Struct `name` if it is defined in `file` or a header visible from it
+    pub fn resolve_struct(&self, name: &str, file: &Path) -> Option<&StructDef> {
+        let name = name.trim();
+        let name = name.strip_prefix("struct ").unwrap_or(name).trim_end_matches('*').trim();
+        let st = self.structs.get(name)?;
+        let defined_in = normalize_path(Path::new(&st.location.as_ref()?.file));
+        (defined_in == normalize_path(file) || self.visible_headers(file).contains(&defined_in)).then_some(st)
+    }
+
+    /// Struct types in `func`'s signature, each with the struct resolved
+    /// through the includes of the function's file when possible
+    pub fn function_struct_types(&self, func: &str) -> Vec<(String, Option<&StructDef>)> {
+        let Some(f) = self.functions.get(func) else {
+            return Vec::new();
+        };
+        let file = f.location.as_ref().map(|l| Path::new(l.file.as_str()));
+        let mut types: Vec<(String, Option<&StructDef>)> = Vec::new();
+        for type_name in std::iter::once(&f.return_type).chain(f.params.iter().map(|p| &p.type_name)) {
+            let Some(rest) = type_name.trim().strip_prefix("struct ") else {
+                continue;
+            };
+            let name = rest.trim_end_matches('*').trim().to_string();
+            if types.iter().any(|(n, _)| *n == name) {
+                continue;
+            }
+            let def = file.and_then(|file| self.resolve_struct(&name, file));
+            types.push((name, def));
+        }
+        types
+    }
+
     /// Remove all symbols from a file
     pub fn remove_file(&mut self, file: &Path) {
-        if let Some(func_names) = self.functions_by_file.remove(file) {
+        let normalized = normalize_path(file);
+        if let Some(func_names) = self.functions_by_file.remove(&normalized) {
             for name in func_names {
                 self.functions.remove(&name);
             }
         }
-        self.file_versions.remove(file);
+        // Locations keep the spelling the parser saw; compare them normalized
+        let other_file = |location_file: &str| normalize_path(Path::new(location_file)) != normalized;
+        self.async_bindings.retain(|_, bindings| {
+            bindings.retain(|b| {
+                b.binding
+                    .bind_location
+                    .as_ref()
+                    .map(|l| other_file(&l.file))
+                    .unwrap_or(true)
+            });
+            !bindings.is_empty()
+        });
+        self.ops_assignments.retain(|_, assignments| {
+            assignments.retain(|a| {
+                a.location
+                    .as_ref()
+                    .map(|l| other_file(&l.file))
+                    .unwrap_or(true)
+            });
+            !assignments.is_empty()
+        });
+        self.occurrences.retain(|_, occurrences| {
+            occurrences.retain(|o| other_file(&o.file));
+            !occurrences.is_empty()
+        });
+        self.classified_calls.retain(|_, calls| {
+            calls.retain(|c| other_file(&c.file));
+            !calls.is_empty()
+        });
+        self.includes.remove(&normalized);
+        self.file_versions.remove(&normalized);
+        self.relative_paths.remove(&normalized);
     }
 
     /// Get function by name
@@ -110,14 +354,57 @@ impl SymbolIndex {
     /// Get all functions in a file
     pub fn get_functions_in_file(&self, file: &Path) -> Vec<&FunctionDef> {
         self.functions_by_file
-            .get(file)
+            .get(&normalize_path(file))
             .map(|names| names.iter().filter_map(|n| self.functions.get(n)).collect())
             .unwrap_or_default()
     }
 
+    /// Up to `limit` functions starting at `offset`, ordered by name
+    ///
+    /// With a `filter`, only functions whose name contains it are paged.
+    pub fn functions_page(&self, offset: usize, limit: usize, filter: Option<&str>) -> Vec<&FunctionDef> {
+        let mut funcs: Vec<&FunctionDef> = self
+            .functions
+            .values()
+            .filter(|f| filter.is_none_or(|pattern| f.name.contains(pattern)))
+            .collect();
+        funcs.sort_by(|a, b| a.name.cmp(&b.name));
+        funcs.into_iter().skip(offset).take(limit).collect()
+    }
+
+    /// Number of functions [`functions_page`](Self::functions_page) pages over
+    pub fn count_functions(&self, filter: Option<&str>) -> usize {
+        match filter {
+            Some(pattern) => self.functions.keys().filter(|name| name.contains(pattern)).count(),
+            None => self.functions.len(),
+        }
+    }
+
+    /// The `limit` functions with the highest cyclomatic complexity
+    ///
+    /// Ties are broken by nesting depth, then by name.
+    pub fn most_complex(&self, limit: usize) -> Vec<&FunctionDef> {
+        let mut funcs: Vec<&FunctionDef> = self.functions.values().collect();
+        funcs.sort_by(|a, b| {
+            b.complexity
+                .cmp(&a.complexity)
+                .then(b.max_nesting.cmp(&a.max_nesting))
+                .then(a.name.cmp(&b.name))
+        });
+        funcs.truncate(limit);
+        funcs
+    }
+
+    /// Functions that access MMIO registers, by file then line
+    pub fn io_functions(&self) -> Vec<&FunctionDef> {
+        let mut funcs: Vec<&FunctionDef> = self.functions.values().filter(|f| f.io_access).collect();
+        funcs.sort_by_key(|f| f.location.as_ref().map(|loc| (loc.file.clone(), loc.line)));
+        funcs
+    }
+
     /// Check if a file needs reindexing
     pub fn needs_reindex(&self, file: &Path, current_mtime: SystemTime) -> bool {
-        match self.file_versions.get(file) {
+        match self.file_versions.get(&normalize_path(file)) {
             Some(version) => version.mtime != current_mtime,
             None => true,
         }
@@ -125,10 +412,11 @@ impl SymbolIndex {
 
     /// Update file version
     pub fn update_file_version(&mut self, file: &Path, hash: u64, mtime: SystemTime) {
+        let file = normalize_path(file);
         self.file_versions.insert(
-            file.to_path_buf(),
+            file.clone(),
             FileVersion {
-                path: file.to_path_buf(),
+                path: file,
                 hash,
                 mtime,
                 indexed_at: SystemTime::now(),
@@ -138,10 +426,24 @@ impl SymbolIndex {
 
     /// Get statistics
     pub fn stats(&self) -> IndexStats {
+        let mut by_mechanism = BTreeMap::new();
+        for bindings in self.async_bindings.values() {
+            let families: HashSet<&str> = bindings.iter().map(|b| b.binding.mechanism.family()).collect();
+            for family in families {
+                *by_mechanism.entry(family.to_string()).or_insert(0) += 1;
+            }
+        }
         IndexStats {
             total_functions: self.functions.len(),
             total_structs: self.structs.len(),
             total_files: self.functions_by_file.len(),
+            async_handlers: self.async_bindings.len(),
+            callbacks: self
+                .functions
+                .values()
+                .filter(|f| f.is_callback && !self.async_bindings.contains_key(&f.name))
+                .count(),
+            async_by_mechanism: by_mechanism,
         }
     }
 }
@@ -152,6 +454,12 @@ pub struct IndexStats {
     pub total_functions: usize,
     pub total_structs: usize,
     pub total_files: usize,
+    /// Functions bound as async handlers
+    pub async_handlers: usize,
+    /// Other callbacks (ops tables, registration macros)
+    pub callbacks: usize,
+    /// Async handlers per mechanism family, e.g. "timer" → 3
+    pub async_by_mechanism: BTreeMap<String, usize>,
 }
 
 /// Index manager for handling indexing operations
@@ -227,7 +535,7 @@ impl Default for IndexManager {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use flowsight_core::Location;
+    use flowsight_core::{AsyncMechanism, Location};
 
     #[test]
     fn test_symbol_index() {
@@ -236,18 +544,133 @@ mod tests {
         let func = FunctionDef {
             name: "my_func".into(),
             return_type: "int".into(),
-            params: vec![],
             location: Some(Location::new("test.c", 10, 0)),
-            calls: vec![],
-            called_by: vec![],
-            is_callback: false,
-            callback_context: None,
-            attributes: vec![],
+            ..Default::default()
         };
 
-        index.add_function(func, Path::new("test.c"));
+        index.add_function(func.clone(), Path::new("test.c"));
 
         assert!(index.get_function("my_func").is_some());
         assert_eq!(index.stats().total_functions, 1);
+
+        let tangled = FunctionDef {
+            name: "tangled".into(),
+            complexity: 7,
+            max_nesting: 3,
+            ..func
+        };
+        index.add_function(tangled, Path::new("test.c"));
+        let names: Vec<&str> = index.most_complex(1).iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["tangled"]);
+
+        let page = |offset, limit, filter| -> Vec<&str> {
+            index.functions_page(offset, limit, filter).iter().map(|f| f.name.as_str()).collect()
+        };
+        assert_eq!(page(0, 10, None), vec!["my_func", "tangled"]);
+        assert_eq!(page(1, 10, None), vec!["tangled"]);
+        assert_eq!(page(0, 1, Some("ang")), vec!["tangled"]);
+        assert!(page(5, 10, None).is_empty());
+        assert_eq!(index.count_functions(None), 2);
+        assert_eq!(index.count_functions(Some("func")), 1);
+
+        let binding = |mechanism, handler: &str| AsyncBinding {
+            mechanism,
+            variable: "priv->x".into(),
+            handler: handler.into(),
+            bind_location: None,
+            trigger_locations: vec![],
+            context: flowsight_core::ExecutionContext::Process,
+        };
+        index.add_async_binding(binding(AsyncMechanism::Timer { high_resolution: false }, "my_func"), None);
+        index.add_async_binding(binding(AsyncMechanism::Timer { high_resolution: true }, "my_func"), None);
+        index.add_async_binding(binding(AsyncMechanism::WorkQueue { delayed: true }, "my_work"), None);
+        index.add_function(
+            FunctionDef {
+                name: "my_open".into(),
+                is_callback: true,
+                ..index.get_function("my_func").unwrap().clone()
+            },
+            Path::new("test.c"),
+        );
+        let stats = index.stats();
+        assert_eq!(stats.async_handlers, 2);
+        assert_eq!(stats.callbacks, 1);
+        assert_eq!(stats.async_by_mechanism["timer"], 1);
+        assert_eq!(stats.async_by_mechanism["work queue"], 1);
+    }
+
+    #[test]
+    fn test_paths_are_normalized() {
+        let cwd = std::env::current_dir().unwrap();
+        let mut index = SymbolIndex::with_root(&cwd);
+        let func = |name: &str| FunctionDef {
+            name: name.into(),
+            return_type: "int".into(),
+            location: Some(Location::new("drivers/x.c", 1, 0)),
+            ..Default::default()
+        };
+
+        index.add_function(func("x_probe"), Path::new("./drivers/x.c"));
+        index.add_function(func("x_probe"), &cwd.join("drivers/../drivers/x.c"));
+        index.add_function(func("x_remove"), &cwd.join("drivers/x.c"));
+
+        assert_eq!(index.stats().total_files, 1);
+        let absolute = cwd.join("drivers").join("x.c");
+        assert_eq!(index.functions_by_file[&absolute], vec!["x_probe", "x_remove"]);
+        assert_eq!(index.get_functions_in_file(Path::new("drivers/x.c")).len(), 2);
+        assert_eq!(index.relative_path(&absolute), Some(Path::new("drivers").join("x.c")));
+        assert_eq!(index.relative_paths[&absolute], Path::new("drivers").join("x.c"));
+        assert_eq!(index.relative_path(Path::new("/elsewhere/y.c")), None);
+
+        let mtime = SystemTime::now();
+        index.update_file_version(Path::new("./drivers/x.c"), 1, mtime);
+        assert!(!index.needs_reindex(&absolute, mtime));
+        index.set_includes(Path::new("drivers/x.c"), vec![PathBuf::from("./include/x.h")]);
+        assert_eq!(index.get_includes(&absolute), [cwd.join("include").join("x.h")]);
+
+        index.remove_file(Path::new("drivers/./x.c"));
+        assert_eq!(index.stats().total_functions, 0);
+        assert_eq!(index.stats().total_files, 0);
+        assert!(index.file_versions.is_empty());
+        assert!(index.includes.is_empty());
+        assert!(index.relative_paths.is_empty());
+    }
+
+    #[test]
+    fn test_struct_resolution_through_includes() {
+        let mut index = SymbolIndex::new();
+        index.add_struct(StructDef {
+            name: "my_dev".into(),
+            fields: vec![],
+            location: Some(Location::new("inc/my_dev.h", 1, 0)),
+            referenced_structs: vec![],
+        });
+        index.add_function(
+            FunctionDef {
+                name: "probe".into(),
+                return_type: "int".into(),
+                params: vec![flowsight_core::Parameter {
+                    name: "dev".into(),
+                    type_name: "struct my_dev*".into(),
+                }],
+                location: Some(Location::new("drv.c", 3, 0)),
+                ..Default::default()
+            },
+            Path::new("drv.c"),
+        );
+        index.set_includes(Path::new("drv.c"), vec![PathBuf::from("inc/common.h")]);
+        index.set_includes(Path::new("inc/common.h"), vec![PathBuf::from("inc/my_dev.h")]);
+
+        assert_eq!(
+            index.visible_headers(Path::new("drv.c")),
+            vec![normalize_path(Path::new("inc/common.h")), normalize_path(Path::new("inc/my_dev.h"))]
+        );
+        assert!(index.resolve_struct("struct my_dev", Path::new("drv.c")).is_some());
+        assert!(index.resolve_struct("my_dev", Path::new("other.c")).is_none());
+
+        let types = index.function_struct_types("probe");
+        assert_eq!(types.len(), 1);
+        assert_eq!(types[0].0, "my_dev");
+        assert!(types[0].1.is_some());
     }
 }
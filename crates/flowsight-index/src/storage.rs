@@ -2,8 +2,8 @@
 //!
 //! Uses sled for fast key-value storage with automatic persistence.
 
-use crate::{FileVersion, SymbolIndex};
-use flowsight_core::{FunctionDef, StructDef};
+use crate::{normalize_path, ops_key, FileVersion, IndexedAsyncBinding, SymbolIndex};
+use flowsight_core::{ClassifiedCall, FunctionDef, Occurrence, OpsAssignment, StructDef};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use thiserror::Error;
@@ -27,12 +27,29 @@ pub struct IndexStorage {
     structs_tree: sled::Tree,
     files_tree: sled::Tree,
     versions_tree: sled::Tree,
+    async_bindings_tree: sled::Tree,
+    ops_assignments_tree: sled::Tree,
+    occurrences_tree: sled::Tree,
+    classified_calls_tree: sled::Tree,
+    includes_tree: sled::Tree,
+    meta_tree: sled::Tree,
 }
 
 /// Serializable wrapper for file-to-functions mapping
 #[derive(Serialize, Deserialize)]
 struct FileFunctions {
     functions: Vec<String>,
+    /// Path relative to the project root, when the file lies under it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    relative_path: Option<PathBuf>,
+}
+
+/// Key of the project root in the meta tree
+const ROOT_KEY: &[u8] = b"root";
+
+/// Storage key of `path`, normalized like the in-memory index keys
+fn path_key(path: &Path) -> String {
+    normalize_path(path).to_string_lossy().into_owned()
 }
 
 impl IndexStorage {
@@ -43,6 +60,12 @@ impl IndexStorage {
         let structs_tree = db.open_tree("structs")?;
         let files_tree = db.open_tree("files")?;
         let versions_tree = db.open_tree("versions")?;
+        let async_bindings_tree = db.open_tree("async_bindings")?;
+        let ops_assignments_tree = db.open_tree("ops_assignments")?;
+        let occurrences_tree = db.open_tree("occurrences")?;
+        let classified_calls_tree = db.open_tree("classified_calls")?;
+        let includes_tree = db.open_tree("includes")?;
+        let meta_tree = db.open_tree("meta")?;
 
         Ok(Self {
             db,
@@ -50,6 +73,12 @@ impl IndexStorage {
             structs_tree,
             files_tree,
             versions_tree,
+            async_bindings_tree,
+            ops_assignments_tree,
+            occurrences_tree,
+            classified_calls_tree,
+            includes_tree,
+            meta_tree,
         })
     }
 
@@ -60,6 +89,12 @@ impl IndexStorage {
         let structs_tree = db.open_tree("structs")?;
         let files_tree = db.open_tree("files")?;
         let versions_tree = db.open_tree("versions")?;
+        let async_bindings_tree = db.open_tree("async_bindings")?;
+        let ops_assignments_tree = db.open_tree("ops_assignments")?;
+        let occurrences_tree = db.open_tree("occurrences")?;
+        let classified_calls_tree = db.open_tree("classified_calls")?;
+        let includes_tree = db.open_tree("includes")?;
+        let meta_tree = db.open_tree("meta")?;
 
         Ok(Self {
             db,
@@ -67,9 +102,29 @@ impl IndexStorage {
             structs_tree,
             files_tree,
             versions_tree,
+            async_bindings_tree,
+            ops_assignments_tree,
+            occurrences_tree,
+            classified_calls_tree,
+            includes_tree,
+            meta_tree,
         })
     }
 
+    /// Set the project root that stored files are made relative to
+    pub fn set_root(&self, root: &Path) -> Result<()> {
+        self.meta_tree.insert(ROOT_KEY, path_key(root).as_bytes())?;
+        Ok(())
+    }
+
+    /// Get the project root, if one was set
+    pub fn root(&self) -> Result<Option<PathBuf>> {
+        Ok(self
+            .meta_tree
+            .get(ROOT_KEY)?
+            .map(|bytes| PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())))
+    }
+
     /// Store a function
     pub fn store_function(&self, func: &FunctionDef, file: &Path) -> Result<()> {
         let key = func.name.as_bytes();
@@ -77,14 +132,19 @@ impl IndexStorage {
         self.functions_tree.insert(key, value)?;
 
         // Update file->functions mapping
-        let file_key = file.to_string_lossy().into_owned();
         let mut file_funcs = self.get_file_functions(file)?;
-        if !file_funcs.contains(&func.name) {
-            file_funcs.push(func.name.clone());
-            let value = serde_json::to_vec(&FileFunctions {
-                functions: file_funcs,
-            })?;
-            self.files_tree.insert(file_key.as_bytes(), value)?;
+        if !file_funcs.functions.contains(&func.name) {
+            file_funcs.functions.push(func.name.clone());
+            if file_funcs.relative_path.is_none() {
+                if let Some(root) = self.root()? {
+                    file_funcs.relative_path = normalize_path(file)
+                        .strip_prefix(&root)
+                        .ok()
+                        .map(Path::to_path_buf);
+                }
+            }
+            let value = serde_json::to_vec(&file_funcs)?;
+            self.files_tree.insert(path_key(file).as_bytes(), value)?;
         }
 
         Ok(())
@@ -98,6 +158,76 @@ impl IndexStorage {
         Ok(())
     }
 
+    /// Store an async binding, appending to any existing bindings of its handler
+    pub fn store_async_binding(&self, entry: &IndexedAsyncBinding) -> Result<()> {
+        let mut entries = self.get_async_bindings(&entry.binding.handler)?;
+        entries.push(entry.clone());
+        let value = serde_json::to_vec(&entries)?;
+        self.async_bindings_tree
+            .insert(entry.binding.handler.as_bytes(), value)?;
+        Ok(())
+    }
+
+    /// Get async bindings for a handler
+    pub fn get_async_bindings(&self, handler: &str) -> Result<Vec<IndexedAsyncBinding>> {
+        match self.async_bindings_tree.get(handler.as_bytes())? {
+            Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Store an ops table assignment, appending to others for the same field
+    pub fn store_ops_assignment(&self, assignment: &OpsAssignment) -> Result<()> {
+        let mut entries = self.get_ops_assignments(&assignment.ops_type, &assignment.field)?;
+        entries.push(assignment.clone());
+        let value = serde_json::to_vec(&entries)?;
+        self.ops_assignments_tree
+            .insert(ops_key(&assignment.ops_type, &assignment.field).as_bytes(), value)?;
+        Ok(())
+    }
+
+    /// Get assignments to `ops_type.field`
+    pub fn get_ops_assignments(&self, ops_type: &str, field: &str) -> Result<Vec<OpsAssignment>> {
+        match self.ops_assignments_tree.get(ops_key(ops_type, field).as_bytes())? {
+            Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Store a symbol occurrence, appending to others for the same name
+    pub fn store_occurrence(&self, occurrence: &Occurrence) -> Result<()> {
+        let mut entries = self.get_occurrences(&occurrence.name)?;
+        entries.push(occurrence.clone());
+        let value = serde_json::to_vec(&entries)?;
+        self.occurrences_tree.insert(occurrence.name.as_bytes(), value)?;
+        Ok(())
+    }
+
+    /// Get occurrences of `name`
+    pub fn get_occurrences(&self, name: &str) -> Result<Vec<Occurrence>> {
+        match self.occurrences_tree.get(name.as_bytes())? {
+            Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Store a classified call site, appending to others of the same caller
+    pub fn store_classified_call(&self, call: &ClassifiedCall) -> Result<()> {
+        let mut entries = self.get_classified_calls(&call.caller)?;
+        entries.push(call.clone());
+        let value = serde_json::to_vec(&entries)?;
+        self.classified_calls_tree.insert(call.caller.as_bytes(), value)?;
+        Ok(())
+    }
+
+    /// Get the classified call sites of `caller`
+    pub fn get_classified_calls(&self, caller: &str) -> Result<Vec<ClassifiedCall>> {
+        match self.classified_calls_tree.get(caller.as_bytes())? {
+            Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
     /// Get a function by name
     pub fn get_function(&self, name: &str) -> Result<Option<FunctionDef>> {
         match self.functions_tree.get(name.as_bytes())? {
@@ -115,32 +245,99 @@ impl IndexStorage {
     }
 
     /// Get all functions from a file
-    fn get_file_functions(&self, file: &Path) -> Result<Vec<String>> {
-        let file_key = file.to_string_lossy();
-        match self.files_tree.get(file_key.as_bytes())? {
-            Some(bytes) => {
-                let ff: FileFunctions = serde_json::from_slice(&bytes)?;
-                Ok(ff.functions)
-            }
-            None => Ok(Vec::new()),
+    fn get_file_functions(&self, file: &Path) -> Result<FileFunctions> {
+        match self.files_tree.get(path_key(file).as_bytes())? {
+            Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            None => Ok(FileFunctions {
+                functions: Vec::new(),
+                relative_path: None,
+            }),
         }
     }
 
     /// Remove all symbols from a file
     pub fn remove_file(&self, file: &Path) -> Result<()> {
         let file_funcs = self.get_file_functions(file)?;
-        for func_name in file_funcs {
+        for func_name in file_funcs.functions {
             self.functions_tree.remove(func_name.as_bytes())?;
         }
-        let file_key = file.to_string_lossy();
+        let normalized = normalize_path(file);
+        let file_key = normalized.to_string_lossy();
+        let other_file = |location_file: &str| normalize_path(Path::new(location_file)) != normalized;
         self.files_tree.remove(file_key.as_bytes())?;
         self.versions_tree.remove(file_key.as_bytes())?;
+        self.includes_tree.remove(file_key.as_bytes())?;
+
+        // Drop async bindings registered in this file
+        for item in self.async_bindings_tree.iter() {
+            let (key, value) = item?;
+            let mut entries: Vec<IndexedAsyncBinding> = serde_json::from_slice(&value)?;
+            let before = entries.len();
+            entries.retain(|e| {
+                e.binding
+                    .bind_location
+                    .as_ref()
+                    .map(|l| other_file(&l.file))
+                    .unwrap_or(true)
+            });
+            if entries.is_empty() {
+                self.async_bindings_tree.remove(key)?;
+            } else if entries.len() != before {
+                self.async_bindings_tree
+                    .insert(key, serde_json::to_vec(&entries)?)?;
+            }
+        }
+
+        // Drop ops assignments made in this file
+        for item in self.ops_assignments_tree.iter() {
+            let (key, value) = item?;
+            let mut entries: Vec<OpsAssignment> = serde_json::from_slice(&value)?;
+            let before = entries.len();
+            entries.retain(|a| {
+                a.location
+                    .as_ref()
+                    .map(|l| other_file(&l.file))
+                    .unwrap_or(true)
+            });
+            if entries.is_empty() {
+                self.ops_assignments_tree.remove(key)?;
+            } else if entries.len() != before {
+                self.ops_assignments_tree
+                    .insert(key, serde_json::to_vec(&entries)?)?;
+            }
+        }
+
+        // Drop occurrences in this file
+        for item in self.occurrences_tree.iter() {
+            let (key, value) = item?;
+            let mut entries: Vec<Occurrence> = serde_json::from_slice(&value)?;
+            let before = entries.len();
+            entries.retain(|o| other_file(&o.file));
+            if entries.is_empty() {
+                self.occurrences_tree.remove(key)?;
+            } else if entries.len() != before {
+                self.occurrences_tree.insert(key, serde_json::to_vec(&entries)?)?;
+            }
+        }
+
+        // Drop classified calls made from this file
+        for item in self.classified_calls_tree.iter() {
+            let (key, value) = item?;
+            let mut entries: Vec<ClassifiedCall> = serde_json::from_slice(&value)?;
+            let before = entries.len();
+            entries.retain(|c| other_file(&c.file));
+            if entries.is_empty() {
+                self.classified_calls_tree.remove(key)?;
+            } else if entries.len() != before {
+                self.classified_calls_tree.insert(key, serde_json::to_vec(&entries)?)?;
+            }
+        }
         Ok(())
     }
 
     /// Store file version
     pub fn store_file_version(&self, version: &FileVersion) -> Result<()> {
-        let key = version.path.to_string_lossy();
+        let key = path_key(&version.path);
         let value = serde_json::to_vec(version)?;
         self.versions_tree.insert(key.as_bytes(), value)?;
         Ok(())
@@ -148,7 +345,7 @@ impl IndexStorage {
 
     /// Get file version
     pub fn get_file_version(&self, path: &Path) -> Result<Option<FileVersion>> {
-        let key = path.to_string_lossy();
+        let key = path_key(path);
         match self.versions_tree.get(key.as_bytes())? {
             Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
             None => Ok(None),
@@ -202,6 +399,7 @@ impl IndexStorage {
     /// Load entire index into memory
     pub fn load_index(&self) -> Result<SymbolIndex> {
         let mut index = SymbolIndex::new();
+        index.root = self.root()?;
 
         // Load all functions
         for item in self.functions_tree.iter() {
@@ -222,14 +420,58 @@ impl IndexStorage {
             let (key, value) = item?;
             let path = PathBuf::from(String::from_utf8_lossy(&key).into_owned());
             let ff: FileFunctions = serde_json::from_slice(&value)?;
+            if let Some(relative) = ff.relative_path {
+                index.relative_paths.insert(path.clone(), relative);
+            }
             index.functions_by_file.insert(path, ff.functions);
         }
 
         // Load file versions
         for item in self.versions_tree.iter() {
-            let (_, value) = item?;
+            let (key, value) = item?;
+            let path = PathBuf::from(String::from_utf8_lossy(&key).into_owned());
             let version: FileVersion = serde_json::from_slice(&value)?;
-            index.file_versions.insert(version.path.clone(), version);
+            index.file_versions.insert(path, version);
+        }
+
+        // Load async bindings
+        for item in self.async_bindings_tree.iter() {
+            let (key, value) = item?;
+            let handler = String::from_utf8_lossy(&key).into_owned();
+            let entries: Vec<IndexedAsyncBinding> = serde_json::from_slice(&value)?;
+            index.async_bindings.insert(handler, entries);
+        }
+
+        // Load ops assignments
+        for item in self.ops_assignments_tree.iter() {
+            let (key, value) = item?;
+            let key = String::from_utf8_lossy(&key).into_owned();
+            let entries: Vec<OpsAssignment> = serde_json::from_slice(&value)?;
+            index.ops_assignments.insert(key, entries);
+        }
+
+        // Load include map
+        for item in self.includes_tree.iter() {
+            let (key, value) = item?;
+            let file = PathBuf::from(String::from_utf8_lossy(&key).into_owned());
+            let headers: Vec<PathBuf> = serde_json::from_slice(&value)?;
+            index.includes.insert(file, headers);
+        }
+
+        // Load occurrences
+        for item in self.occurrences_tree.iter() {
+            let (key, value) = item?;
+            let name = String::from_utf8_lossy(&key).into_owned();
+            let entries: Vec<Occurrence> = serde_json::from_slice(&value)?;
+            index.occurrences.insert(name, entries);
+        }
+
+        // Load classified calls
+        for item in self.classified_calls_tree.iter() {
+            let (key, value) = item?;
+            let caller = String::from_utf8_lossy(&key).into_owned();
+            let entries: Vec<ClassifiedCall> = serde_json::from_slice(&value)?;
+            index.classified_calls.insert(caller, entries);
         }
 
         Ok(index)
@@ -242,6 +484,16 @@ impl IndexStorage {
         self.structs_tree.clear()?;
         self.files_tree.clear()?;
         self.versions_tree.clear()?;
+        self.async_bindings_tree.clear()?;
+        self.ops_assignments_tree.clear()?;
+        self.occurrences_tree.clear()?;
+        self.classified_calls_tree.clear()?;
+        self.includes_tree.clear()?;
+        self.meta_tree.clear()?;
+
+        if let Some(root) = &index.root {
+            self.set_root(root)?;
+        }
 
         // Store functions
         for func in index.functions.values() {
@@ -259,20 +511,50 @@ impl IndexStorage {
 
         // Store file mappings
         for (path, funcs) in &index.functions_by_file {
-            let key = path.to_string_lossy();
             let value = serde_json::to_vec(&FileFunctions {
                 functions: funcs.clone(),
+                relative_path: index.relative_paths.get(path).cloned(),
             })?;
-            self.files_tree.insert(key.as_bytes(), value)?;
+            self.files_tree.insert(path_key(path).as_bytes(), value)?;
         }
 
         // Store file versions
-        for version in index.file_versions.values() {
-            let key = version.path.to_string_lossy();
+        for (path, version) in &index.file_versions {
+            let key = path_key(path);
             let value = serde_json::to_vec(version)?;
             self.versions_tree.insert(key.as_bytes(), value)?;
         }
 
+        // Store async bindings
+        for (handler, entries) in &index.async_bindings {
+            let value = serde_json::to_vec(entries)?;
+            self.async_bindings_tree.insert(handler.as_bytes(), value)?;
+        }
+
+        // Store ops assignments
+        for (key, entries) in &index.ops_assignments {
+            let value = serde_json::to_vec(entries)?;
+            self.ops_assignments_tree.insert(key.as_bytes(), value)?;
+        }
+
+        // Store include map
+        for (file, headers) in &index.includes {
+            let value = serde_json::to_vec(headers)?;
+            self.includes_tree.insert(path_key(file).as_bytes(), value)?;
+        }
+
+        // Store occurrences
+        for (name, entries) in &index.occurrences {
+            let value = serde_json::to_vec(entries)?;
+            self.occurrences_tree.insert(name.as_bytes(), value)?;
+        }
+
+        // Store classified calls
+        for (caller, entries) in &index.classified_calls {
+            let value = serde_json::to_vec(entries)?;
+            self.classified_calls_tree.insert(caller.as_bytes(), value)?;
+        }
+
         // Flush to disk
         self.db.flush()?;
 
@@ -315,13 +597,8 @@ mod tests {
         let func = FunctionDef {
             name: "test_func".into(),
             return_type: "int".into(),
-            params: vec![],
             location: Some(Location::new("test.c", 1, 0)),
-            calls: vec![],
-            called_by: vec![],
-            is_callback: false,
-            callback_context: None,
-            attributes: vec![],
+            ..Default::default()
         };
 
         storage.store_function(&func, Path::new("test.c")).unwrap();
@@ -339,13 +616,8 @@ mod tests {
             let func = FunctionDef {
                 name: (*name).into(),
                 return_type: "int".into(),
-                params: vec![],
                 location: Some(Location::new("test.c", 1, 0)),
-                calls: vec![],
-                called_by: vec![],
-                is_callback: false,
-                callback_context: None,
-                attributes: vec![],
+                ..Default::default()
             };
             storage.store_function(&func, Path::new("test.c")).unwrap();
         }
@@ -353,4 +625,55 @@ mod tests {
         let results = storage.search_functions("driver").unwrap();
         assert_eq!(results.len(), 2);
     }
+
+    #[test]
+    fn test_paths_are_normalized() {
+        let cwd = std::env::current_dir().unwrap();
+        let mut index = SymbolIndex::with_root(&cwd);
+        let func = FunctionDef {
+            name: "x_probe".into(),
+            return_type: "int".into(),
+            location: Some(Location::new("drivers/x.c", 1, 0)),
+            ..Default::default()
+        };
+        index.add_function(func, Path::new("./drivers/x.c"));
+        index.update_file_version(Path::new("drivers/x.c"), 1, std::time::SystemTime::now());
+        index.set_includes(Path::new("drivers/x.c"), vec![PathBuf::from("include/x.h")]);
+
+        let storage = IndexStorage::in_memory().unwrap();
+        storage.save_index(&index).unwrap();
+        let loaded = storage.load_index().unwrap();
+        let absolute = cwd.join("drivers").join("x.c");
+        assert_eq!(loaded.root.as_deref(), Some(cwd.as_path()));
+        assert_eq!(loaded.relative_paths[&absolute], Path::new("drivers").join("x.c"));
+        assert_eq!(loaded.relative_path(&absolute), Some(Path::new("drivers").join("x.c")));
+        assert!(storage.get_file_version(Path::new("./drivers/x.c")).unwrap().is_some());
+
+        storage.remove_file(Path::new("drivers/../drivers/x.c")).unwrap();
+        let loaded = storage.load_index().unwrap();
+        assert!(loaded.functions.is_empty());
+        assert!(loaded.file_versions.is_empty());
+        assert!(loaded.includes.is_empty());
+    }
+
+    #[test]
+    fn test_store_function_records_relative_path() {
+        let cwd = std::env::current_dir().unwrap();
+        let storage = IndexStorage::in_memory().unwrap();
+        storage.set_root(&cwd).unwrap();
+        let func = FunctionDef {
+            name: "x_probe".into(),
+            return_type: "int".into(),
+            location: Some(Location::new("drivers/x.c", 1, 0)),
+            ..Default::default()
+        };
+        storage.store_function(&func, Path::new("./drivers/x.c")).unwrap();
+
+        let loaded = storage.load_index().unwrap();
+        assert_eq!(loaded.root.as_deref(), Some(cwd.as_path()));
+        assert_eq!(
+            loaded.relative_paths[&cwd.join("drivers").join("x.c")],
+            Path::new("drivers").join("x.c")
+        );
+    }
 }
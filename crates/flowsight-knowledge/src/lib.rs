@@ -26,6 +26,13 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
+
+mod timeline;
+mod validate;
+
+pub use timeline::timeline_to_mermaid;
+pub use validate::{ChainIssue, ChainIssueKind};
 
 /// 调用链中的一个节点
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,6 +47,16 @@ pub struct CallChainNode {
     pub description: Option<String>,
     /// 是否是用户代码入口点 (如 drv->probe 就是用户代码入口)
     pub is_user_entry: bool,
+    /// 适用的内核版本范围 [from, to)，空字符串表示不限；None 表示所有版本
+    #[serde(default)]
+    pub kernel_version_range: Option<(String, String)>,
+}
+
+impl CallChainNode {
+    /// Whether this node exists in the given kernel version
+    pub fn supports_version(&self, version: &str) -> bool {
+        version_in_range(version, self.kernel_version_range.as_ref())
+    }
 }
 
 /// 完整的调用链
@@ -51,6 +68,54 @@ pub struct CallChain {
     pub trigger_source: String,
     /// 调用链节点 (从触发源到用户代码)
     pub nodes: Vec<CallChainNode>,
+    /// 适用的内核版本范围 [from, to)，空字符串表示不限；None 表示所有版本
+    #[serde(default)]
+    pub kernel_version_range: Option<(String, String)>,
+}
+
+impl CallChain {
+    /// Whether this chain applies to the given kernel version
+    pub fn supports_version(&self, version: &str) -> bool {
+        version_in_range(version, self.kernel_version_range.as_ref())
+    }
+
+    /// Copy of this chain with nodes absent from `version` removed
+    pub fn for_version(&self, version: &str) -> CallChain {
+        CallChain {
+            nodes: self
+                .nodes
+                .iter()
+                .filter(|n| n.supports_version(version))
+                .cloned()
+                .collect(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Compare dotted kernel versions numerically ("5.10" > "5.9")
+fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    let parse = |v: &str| -> Vec<u32> {
+        v.split(['.', '-'])
+            .map_while(|part| part.parse().ok())
+            .collect()
+    };
+    let (a, b) = (parse(a), parse(b));
+    let len = a.len().max(b.len());
+    let pad = |v: &[u32], i: usize| v.get(i).copied().unwrap_or(0);
+    (0..len)
+        .map(|i| pad(&a, i).cmp(&pad(&b, i)))
+        .find(|o| o.is_ne())
+        .unwrap_or(std::cmp::Ordering::Equal)
+}
+
+/// Check `version` against a half-open range [from, to); empty bounds are open
+fn version_in_range(version: &str, range: Option<&(String, String)>) -> bool {
+    let Some((from, to)) = range else {
+        return true;
+    };
+    (from.is_empty() || compare_versions(version, from).is_ge())
+        && (to.is_empty() || compare_versions(version, to).is_lt())
 }
 
 /// 异步时间线关系
@@ -121,6 +186,9 @@ pub struct FrameworkCallback {
     pub signature: Option<String>,
     /// ⭐ 完整的内核调用链！
     pub call_chain: Option<CallChain>,
+    /// 按内核版本区分的其他调用链 (YAML 中可为一个回调提供多条)
+    #[serde(default)]
+    pub call_chain_variants: Vec<CallChain>,
 }
 
 /// Framework definition
@@ -166,15 +234,40 @@ pub struct KernelApi {
     pub params: Option<Vec<String>>,
 }
 
+/// Maps a project's symbol names to the plain names the naming heuristics
+/// expect, e.g. `mydrv_probe` to `probe` for a driver prefixing everything
+pub type NameNormalizer = Box<NormalizeFn>;
+
+type NormalizeFn = dyn Fn(&str) -> String + Send + Sync;
+
+/// A [`NameNormalizer`] shared between clones of the knowledge base
+#[derive(Clone, Default)]
+struct SharedNormalizer(Option<Arc<NormalizeFn>>);
+
+impl std::fmt::Debug for SharedNormalizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.0.is_some() { "Some(<fn>)" } else { "None" })
+    }
+}
+
 /// Knowledge base
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct KnowledgeBase {
     /// Framework definitions
+    #[serde(default)]
     pub frameworks: HashMap<String, Framework>,
     /// Async patterns
+    #[serde(default)]
     pub async_patterns: HashMap<String, AsyncPattern>,
     /// Kernel API info
+    #[serde(default)]
     pub kernel_apis: HashMap<String, KernelApi>,
+    /// Named integer constants (GFP_*, IRQF_*, O_*, ...) for the evaluator
+    #[serde(default)]
+    pub constants: HashMap<String, i64>,
+    /// Applied to function names before matching them against callback names
+    #[serde(skip)]
+    name_normalizer: SharedNormalizer,
 }
 
 impl KnowledgeBase {
@@ -202,9 +295,149 @@ impl KnowledgeBase {
         let mut kb = Self::new();
         kb.load_builtin_frameworks();
         kb.load_builtin_apis();
+        kb.load_builtin_constants();
         kb
     }
 
+    /// Overlay `other` onto this knowledge base
+    ///
+    /// Entries in `other` replace entries with the same name; everything else is kept.
+    pub fn merge(&mut self, other: KnowledgeBase) {
+        self.frameworks.extend(other.frameworks);
+        self.async_patterns.extend(other.async_patterns);
+        self.kernel_apis.extend(other.kernel_apis);
+        self.constants.extend(other.constants);
+    }
+
+    /// Overlay definitions from a YAML file (see [`merge`](Self::merge))
+    ///
+    /// Sections the file leaves out are untouched.
+    pub fn merge_yaml(&mut self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let other = Self::load_yaml(path)?;
+        self.merge(other);
+        Ok(())
+    }
+
+    /// Overlay definitions from a JSON file (see [`merge`](Self::merge))
+    pub fn merge_json(&mut self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let other = Self::load_json(path)?;
+        self.merge(other);
+        Ok(())
+    }
+
+    /// Overlay every `.yaml`/`.yml` file in `dir`, in file name order
+    ///
+    /// Later files override earlier ones. Returns the number of files merged.
+    pub fn load_dir(&mut self, dir: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut files: Vec<_> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.is_file() && path.extension().is_some_and(|ext| ext == "yaml" || ext == "yml")
+            })
+            .collect();
+        files.sort();
+
+        for file in &files {
+            self.merge_yaml(file)
+                .map_err(|e| format!("{}: {}", file.display(), e))?;
+        }
+        Ok(files.len())
+    }
+
+    /// Load constants from a YAML map (`NAME: value`), overriding existing entries
+    ///
+    /// Returns the number of constants loaded.
+    pub fn load_constants_yaml(&mut self, path: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let constants: HashMap<String, i64> = serde_yaml::from_str(&content)?;
+        let count = constants.len();
+        self.constants.extend(constants);
+        Ok(count)
+    }
+
+    /// Look up a named constant
+    pub fn get_constant(&self, name: &str) -> Option<i64> {
+        self.constants.get(name).copied()
+    }
+
+    fn load_builtin_constants(&mut self) {
+        // include/linux/gfp_types.h (6.x bit layout)
+        let gfp_dma = 0x01;
+        let gfp_highmem = 0x02;
+        let gfp_dma32 = 0x04;
+        let gfp_movable = 0x08;
+        let gfp_reclaimable = 0x10;
+        let gfp_high = 0x20;
+        let gfp_io = 0x40;
+        let gfp_fs = 0x80;
+        let gfp_zero = 0x100;
+        let gfp_direct_reclaim = 0x400;
+        let gfp_kswapd_reclaim = 0x800;
+        let gfp_write = 0x1000;
+        let gfp_nowarn = 0x2000;
+        let gfp_retry_mayfail = 0x4000;
+        let gfp_nofail = 0x8000;
+        let gfp_noretry = 0x10000;
+        let gfp_comp = 0x40000;
+        let gfp_hardwall = 0x100000;
+        let gfp_account = 0x400000;
+        let gfp_reclaim = gfp_direct_reclaim | gfp_kswapd_reclaim;
+
+        let constants: &[(&str, i64)] = &[
+            ("__GFP_DMA", gfp_dma),
+            ("__GFP_HIGHMEM", gfp_highmem),
+            ("__GFP_DMA32", gfp_dma32),
+            ("__GFP_MOVABLE", gfp_movable),
+            ("__GFP_RECLAIMABLE", gfp_reclaimable),
+            ("__GFP_HIGH", gfp_high),
+            ("__GFP_IO", gfp_io),
+            ("__GFP_FS", gfp_fs),
+            ("__GFP_ZERO", gfp_zero),
+            ("__GFP_DIRECT_RECLAIM", gfp_direct_reclaim),
+            ("__GFP_KSWAPD_RECLAIM", gfp_kswapd_reclaim),
+            ("__GFP_RECLAIM", gfp_reclaim),
+            ("__GFP_WRITE", gfp_write),
+            ("__GFP_NOWARN", gfp_nowarn),
+            ("__GFP_RETRY_MAYFAIL", gfp_retry_mayfail),
+            ("__GFP_NOFAIL", gfp_nofail),
+            ("__GFP_NORETRY", gfp_noretry),
+            ("__GFP_COMP", gfp_comp),
+            ("__GFP_HARDWALL", gfp_hardwall),
+            ("__GFP_ACCOUNT", gfp_account),
+            ("GFP_ATOMIC", gfp_high | gfp_kswapd_reclaim),
+            ("GFP_KERNEL", gfp_reclaim | gfp_io | gfp_fs),
+            ("GFP_KERNEL_ACCOUNT", gfp_reclaim | gfp_io | gfp_fs | gfp_account),
+            ("GFP_NOWAIT", gfp_kswapd_reclaim | gfp_nowarn),
+            ("GFP_NOIO", gfp_reclaim),
+            ("GFP_NOFS", gfp_reclaim | gfp_io),
+            ("GFP_USER", gfp_reclaim | gfp_io | gfp_fs | gfp_hardwall),
+            ("GFP_DMA", gfp_dma),
+            ("GFP_DMA32", gfp_dma32),
+            ("GFP_HIGHUSER", gfp_reclaim | gfp_io | gfp_fs | gfp_hardwall | gfp_highmem),
+            // include/linux/interrupt.h
+            ("IRQF_TRIGGER_RISING", 0x1),
+            ("IRQF_TRIGGER_FALLING", 0x2),
+            ("IRQF_TRIGGER_HIGH", 0x4),
+            ("IRQF_TRIGGER_LOW", 0x8),
+            ("IRQF_SHARED", 0x80),
+            ("IRQF_ONESHOT", 0x2000),
+            ("IRQF_NO_SUSPEND", 0x4000),
+            // include/uapi/asm-generic/fcntl.h
+            ("O_RDONLY", 0o0),
+            ("O_WRONLY", 0o1),
+            ("O_RDWR", 0o2),
+            ("O_CREAT", 0o100),
+            ("O_EXCL", 0o200),
+            ("O_TRUNC", 0o1000),
+            ("O_APPEND", 0o2000),
+            ("O_NONBLOCK", 0o4000),
+            ("O_SYNC", 0o4010000),
+        ];
+        for (name, value) in constants {
+            self.constants.insert(name.to_string(), *value);
+        }
+    }
+
     fn load_builtin_frameworks(&mut self) {
         // USB driver framework - 带完整调用链
         let mut usb_callbacks = HashMap::new();
@@ -213,6 +446,7 @@ impl KnowledgeBase {
         let usb_probe_chain = CallChain {
             name: "USB probe 调用链".into(),
             trigger_source: "USB 设备插入".into(),
+            kernel_version_range: None,
             nodes: vec![
                 CallChainNode {
                     function: "usb_hub_port_connect".into(),
@@ -220,6 +454,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("USB hub 检测到端口连接".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "usb_new_device".into(),
@@ -227,6 +462,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("创建新 USB 设备".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "device_add".into(),
@@ -234,6 +470,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("添加设备到设备模型".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "bus_probe_device".into(),
@@ -241,6 +478,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("总线层探测设备".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "__device_attach".into(),
@@ -248,6 +486,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("尝试将设备与驱动匹配".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "driver_probe_device".into(),
@@ -255,6 +494,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("驱动探测设备".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "really_probe".into(),
@@ -262,6 +502,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("实际执行探测".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "usb_probe_interface".into(),
@@ -269,6 +510,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("USB 接口探测".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "drv->probe()".into(),
@@ -276,6 +518,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("调用驱动的 probe 回调".into()),
                     is_user_entry: true, // ⭐ 这是用户代码入口
+                    kernel_version_range: None,
                 },
             ],
         };
@@ -290,6 +533,7 @@ impl KnowledgeBase {
                     "int (*)(struct usb_interface *, const struct usb_device_id *)".into(),
                 ),
                 call_chain: Some(usb_probe_chain),
+                call_chain_variants: Vec::new(),
             },
         );
         
@@ -297,6 +541,7 @@ impl KnowledgeBase {
         let usb_disconnect_chain = CallChain {
             name: "USB disconnect 调用链".into(),
             trigger_source: "USB 设备拔出或驱动卸载".into(),
+            kernel_version_range: None,
             nodes: vec![
                 CallChainNode {
                     function: "usb_hub_port_disconnect".into(),
@@ -304,6 +549,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("USB hub 检测到端口断开".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "usb_disconnect".into(),
@@ -311,6 +557,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("USB 断开处理".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "device_del".into(),
@@ -318,6 +565,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("从设备模型删除".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "__device_release_driver".into(),
@@ -325,6 +573,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("释放驱动".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "usb_unbind_interface".into(),
@@ -332,6 +581,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("解绑 USB 接口".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "drv->disconnect()".into(),
@@ -339,6 +589,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("调用驱动的 disconnect 回调".into()),
                     is_user_entry: true,
+                    kernel_version_range: None,
                 },
             ],
         };
@@ -351,6 +602,7 @@ impl KnowledgeBase {
                 context: ExecutionContext::Process,
                 signature: Some("void (*)(struct usb_interface *)".into()),
                 call_chain: Some(usb_disconnect_chain),
+                call_chain_variants: Vec::new(),
             },
         );
 
@@ -369,6 +621,7 @@ impl KnowledgeBase {
         let fops_open_chain = CallChain {
             name: "file open 调用链".into(),
             trigger_source: "用户空间 open() 系统调用".into(),
+            kernel_version_range: None,
             nodes: vec![
                 CallChainNode {
                     function: "sys_open / sys_openat".into(),
@@ -376,6 +629,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("系统调用入口".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "do_sys_open".into(),
@@ -383,6 +637,15 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("处理 open 系统调用".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
+                },
+                CallChainNode {
+                    function: "do_sys_openat2".into(),
+                    file: Some("fs/open.c".into()),
+                    context: ExecutionContext::Process,
+                    description: Some("openat2 公共路径 (5.6 起)".into()),
+                    is_user_entry: false,
+                    kernel_version_range: Some(("5.6".into(), String::new())),
                 },
                 CallChainNode {
                     function: "do_filp_open".into(),
@@ -390,6 +653,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("打开文件路径".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "vfs_open".into(),
@@ -397,6 +661,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("VFS 层打开".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "do_dentry_open".into(),
@@ -404,6 +669,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("打开目录项".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "f->f_op->open()".into(),
@@ -411,6 +677,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("调用文件操作的 open".into()),
                     is_user_entry: true,
+                    kernel_version_range: None,
                 },
             ],
         };
@@ -423,6 +690,7 @@ impl KnowledgeBase {
                 context: ExecutionContext::Process,
                 signature: Some("int (*)(struct inode *, struct file *)".into()),
                 call_chain: Some(fops_open_chain),
+                call_chain_variants: Vec::new(),
             },
         );
         fops_callbacks.insert(
@@ -435,6 +703,7 @@ impl KnowledgeBase {
                     "ssize_t (*)(struct file *, char __user *, size_t, loff_t *)".into(),
                 ),
                 call_chain: None, // 可以后续添加
+                call_chain_variants: Vec::new(),
             },
         );
         fops_callbacks.insert(
@@ -447,6 +716,7 @@ impl KnowledgeBase {
                     "ssize_t (*)(struct file *, const char __user *, size_t, loff_t *)".into(),
                 ),
                 call_chain: None,
+                call_chain_variants: Vec::new(),
             },
         );
 
@@ -469,6 +739,7 @@ impl KnowledgeBase {
         let workqueue_handler_chain = CallChain {
             name: "WorkQueue handler 调用链".into(),
             trigger_source: "内核 kworker 线程被调度".into(),
+            kernel_version_range: None,
             nodes: vec![
                 CallChainNode {
                     function: "kworker/xxx (内核线程)".into(),
@@ -476,6 +747,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("工作队列内核线程".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "worker_thread".into(),
@@ -483,6 +755,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("工作线程主循环".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "process_one_work".into(),
@@ -490,6 +763,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("处理单个工作项".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "worker->current_func".into(),
@@ -497,6 +771,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("调用工作函数".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "work->func()".into(),
@@ -504,6 +779,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::Process,
                     description: Some("用户的工作处理函数".into()),
                     is_user_entry: true,
+                    kernel_version_range: None,
                 },
             ],
         };
@@ -517,6 +793,7 @@ impl KnowledgeBase {
                 call_chain: CallChain {
                     name: "中断处理".into(),
                     trigger_source: "硬件中断".into(),
+                    kernel_version_range: None,
                     nodes: vec![
                         CallChainNode {
                             function: "do_IRQ".into(),
@@ -524,6 +801,7 @@ impl KnowledgeBase {
                             context: ExecutionContext::HardIrq,
                             description: Some("中断入口".into()),
                             is_user_entry: false,
+                            kernel_version_range: None,
                         },
                         CallChainNode {
                             function: "handle_irq".into(),
@@ -531,6 +809,7 @@ impl KnowledgeBase {
                             context: ExecutionContext::HardIrq,
                             description: Some("处理 IRQ".into()),
                             is_user_entry: false,
+                            kernel_version_range: None,
                         },
                         CallChainNode {
                             function: "irq_handler()".into(),
@@ -538,6 +817,7 @@ impl KnowledgeBase {
                             context: ExecutionContext::HardIrq,
                             description: Some("用户的中断处理函数".into()),
                             is_user_entry: true,
+                            kernel_version_range: None,
                         },
                     ],
                 },
@@ -574,6 +854,7 @@ impl KnowledgeBase {
         let timer_handler_chain = CallChain {
             name: "Timer handler 调用链".into(),
             trigger_source: "定时器到期".into(),
+            kernel_version_range: None,
             nodes: vec![
                 CallChainNode {
                     function: "timer interrupt (时钟中断)".into(),
@@ -581,6 +862,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::SoftIrq,
                     description: Some("时钟中断触发".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "run_timer_softirq".into(),
@@ -588,6 +870,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::SoftIrq,
                     description: Some("定时器软中断".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "__run_timers".into(),
@@ -595,6 +878,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::SoftIrq,
                     description: Some("运行到期的定时器".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "call_timer_fn".into(),
@@ -602,6 +886,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::SoftIrq,
                     description: Some("调用定时器函数".into()),
                     is_user_entry: false,
+                    kernel_version_range: None,
                 },
                 CallChainNode {
                     function: "timer->function()".into(),
@@ -609,6 +894,7 @@ impl KnowledgeBase {
                     context: ExecutionContext::SoftIrq,
                     description: Some("用户的定时器回调 (软中断上下文，不可睡眠!)".into()),
                     is_user_entry: true,
+                    kernel_version_range: None,
                 },
             ],
         };
@@ -631,6 +917,256 @@ impl KnowledgeBase {
                 handler_call_chain: Some(timer_handler_chain),
             },
         );
+
+        // 通知链调用链: blocking 链在进程上下文, atomic 链不可睡眠
+        let notifier_chain = |call_chain_fn: &str, context: ExecutionContext| CallChain {
+            name: format!("{} 调用链", call_chain_fn),
+            trigger_source: "内核发布通知事件".into(),
+            kernel_version_range: None,
+            nodes: vec![
+                CallChainNode {
+                    function: call_chain_fn.into(),
+                    file: Some("kernel/notifier.c".into()),
+                    context: context.clone(),
+                    description: Some("遍历通知链".into()),
+                    is_user_entry: false,
+                    kernel_version_range: None,
+                },
+                CallChainNode {
+                    function: "notifier_call_chain".into(),
+                    file: Some("kernel/notifier.c".into()),
+                    context: context.clone(),
+                    description: Some("按优先级依次调用每个 notifier_block".into()),
+                    is_user_entry: false,
+                    kernel_version_range: None,
+                },
+                CallChainNode {
+                    function: "nb->notifier_call()".into(),
+                    file: None,
+                    context,
+                    description: Some("用户的通知回调".into()),
+                    is_user_entry: true,
+                    kernel_version_range: None,
+                },
+            ],
+        };
+
+        self.async_patterns.insert(
+            "notifier_block".into(),
+            AsyncPattern {
+                description: "阻塞通知链 (可睡眠)".into(),
+                context: ExecutionContext::Process,
+                bind_patterns: vec![
+                    r"blocking_notifier_chain_register\s*\(\s*&?([\w\.\->]+)\s*,\s*&?([\w\.\->]+)\s*\)".into(),
+                    r"register_reboot_notifier\s*\(\s*&?([\w\.\->]+)\s*\)".into(),
+                ],
+                trigger_patterns: vec![r"blocking_notifier_call_chain\s*\(".into()],
+                handler_signature: Some("int (*)(struct notifier_block *, unsigned long, void *)".into()),
+                timeline: None,
+                handler_call_chain: Some(notifier_chain("blocking_notifier_call_chain", ExecutionContext::Process)),
+            },
+        );
+
+        self.async_patterns.insert(
+            "atomic_notifier".into(),
+            AsyncPattern {
+                description: "原子通知链 (不可睡眠)".into(),
+                context: ExecutionContext::HardIrq,
+                bind_patterns: vec![
+                    r"atomic_notifier_chain_register\s*\(\s*&?([\w\.\->]+)\s*,\s*&?([\w\.\->]+)\s*\)".into(),
+                ],
+                trigger_patterns: vec![r"atomic_notifier_call_chain\s*\(".into()],
+                handler_signature: Some("int (*)(struct notifier_block *, unsigned long, void *)".into()),
+                timeline: None,
+                handler_call_chain: Some(notifier_chain("atomic_notifier_call_chain", ExecutionContext::HardIrq)),
+            },
+        );
+
+        // 中断调用链: 主处理函数在硬中断上下文，线程化处理函数在 irq/N 内核线程中
+        let irq_handler_chain = CallChain {
+            name: "中断 handler 调用链".into(),
+            trigger_source: "硬件中断".into(),
+            kernel_version_range: None,
+            nodes: vec![
+                CallChainNode {
+                    function: "handle_irq".into(),
+                    file: Some("kernel/irq/handle.c".into()),
+                    context: ExecutionContext::HardIrq,
+                    description: Some("处理 IRQ".into()),
+                    is_user_entry: false,
+                    kernel_version_range: None,
+                },
+                CallChainNode {
+                    function: "handle_irq_event".into(),
+                    file: Some("kernel/irq/handle.c".into()),
+                    context: ExecutionContext::HardIrq,
+                    description: Some("遍历该中断线上注册的 irqaction".into()),
+                    is_user_entry: false,
+                    kernel_version_range: None,
+                },
+                CallChainNode {
+                    function: "__handle_irq_event_percpu".into(),
+                    file: Some("kernel/irq/handle.c".into()),
+                    context: ExecutionContext::HardIrq,
+                    description: Some("调用主处理函数，返回 IRQ_WAKE_THREAD 时唤醒中断线程".into()),
+                    is_user_entry: false,
+                    kernel_version_range: None,
+                },
+                CallChainNode {
+                    function: "action->handler()".into(),
+                    file: None,
+                    context: ExecutionContext::HardIrq,
+                    description: Some("用户的中断处理函数 (硬中断上下文，不可睡眠!)".into()),
+                    is_user_entry: true,
+                    kernel_version_range: None,
+                },
+            ],
+        };
+
+        let threaded_irq_chain = CallChain {
+            name: "线程化中断 handler 调用链".into(),
+            trigger_source: "主处理函数返回 IRQ_WAKE_THREAD".into(),
+            kernel_version_range: None,
+            nodes: vec![
+                CallChainNode {
+                    function: "irq/N-name (内核线程)".into(),
+                    file: Some("kernel/irq/manage.c".into()),
+                    context: ExecutionContext::Process,
+                    description: Some("每个线程化中断专属的内核线程".into()),
+                    is_user_entry: false,
+                    kernel_version_range: None,
+                },
+                CallChainNode {
+                    function: "irq_thread".into(),
+                    file: Some("kernel/irq/manage.c".into()),
+                    context: ExecutionContext::Process,
+                    description: Some("中断线程主循环，等待被唤醒".into()),
+                    is_user_entry: false,
+                    kernel_version_range: None,
+                },
+                CallChainNode {
+                    function: "irq_thread_fn".into(),
+                    file: Some("kernel/irq/manage.c".into()),
+                    context: ExecutionContext::Process,
+                    description: Some("调用线程化处理函数".into()),
+                    is_user_entry: false,
+                    kernel_version_range: None,
+                },
+                CallChainNode {
+                    function: "action->thread_fn()".into(),
+                    file: None,
+                    context: ExecutionContext::Process,
+                    description: Some("用户的线程化中断处理函数 (进程上下文，可睡眠)".into()),
+                    is_user_entry: true,
+                    kernel_version_range: None,
+                },
+            ],
+        };
+
+        let threaded_irq_timeline = AsyncTimeline {
+            name: "线程化中断时间线".into(),
+            phase1: TimelinePhase {
+                name: "主处理函数".into(),
+                context: ExecutionContext::HardIrq,
+                call_chain: irq_handler_chain.clone(),
+            },
+            separation: "主处理函数返回 IRQ_WAKE_THREAD → 中断返回 → 调度器运行 irq/N 线程 (IRQF_ONESHOT 时中断线保持屏蔽)".into(),
+            phase2: TimelinePhase {
+                name: "线程化处理函数".into(),
+                context: ExecutionContext::Process,
+                call_chain: threaded_irq_chain.clone(),
+            },
+        };
+
+        // request_threaded_irq 注册两个处理函数，由 tracker 分别识别
+        self.async_patterns.insert(
+            "irq".into(),
+            AsyncPattern {
+                description: "中断处理函数 (硬中断上下文，不可睡眠)".into(),
+                context: ExecutionContext::HardIrq,
+                bind_patterns: vec![],
+                trigger_patterns: vec![],
+                handler_signature: Some("irqreturn_t (*)(int, void *)".into()),
+                timeline: None,
+                handler_call_chain: Some(irq_handler_chain),
+            },
+        );
+
+        self.async_patterns.insert(
+            "threaded_irq".into(),
+            AsyncPattern {
+                description: "线程化中断处理函数 (进程上下文，可睡眠)".into(),
+                context: ExecutionContext::Process,
+                bind_patterns: vec![],
+                trigger_patterns: vec![],
+                handler_signature: Some("irqreturn_t (*)(int, void *)".into()),
+                timeline: Some(threaded_irq_timeline),
+                handler_call_chain: Some(threaded_irq_chain),
+            },
+        );
+
+        // DMA 完成回调: 控制器中断 → 完成 tasklet → desc->callback
+        let dma_callback_chain = CallChain {
+            name: "DMA 完成回调调用链".into(),
+            trigger_source: "DMA 传输完成中断".into(),
+            kernel_version_range: None,
+            nodes: vec![
+                CallChainNode {
+                    function: "dma_irq_handler()".into(),
+                    file: None,
+                    context: ExecutionContext::HardIrq,
+                    description: Some("DMA 控制器驱动的中断处理，调度完成 tasklet".into()),
+                    is_user_entry: false,
+                    kernel_version_range: None,
+                },
+                CallChainNode {
+                    function: "tasklet_action".into(),
+                    file: Some("kernel/softirq.c".into()),
+                    context: ExecutionContext::SoftIrq,
+                    description: Some("软中断中执行 tasklet".into()),
+                    is_user_entry: false,
+                    kernel_version_range: None,
+                },
+                CallChainNode {
+                    function: "vchan_complete".into(),
+                    file: Some("drivers/dma/virt-dma.c".into()),
+                    context: ExecutionContext::SoftIrq,
+                    description: Some("取出已完成的描述符".into()),
+                    is_user_entry: false,
+                    kernel_version_range: None,
+                },
+                CallChainNode {
+                    function: "dmaengine_desc_callback_invoke".into(),
+                    file: Some("drivers/dma/dmaengine.h".into()),
+                    context: ExecutionContext::SoftIrq,
+                    description: Some("以 callback_param 为参数调用回调".into()),
+                    is_user_entry: false,
+                    kernel_version_range: None,
+                },
+                CallChainNode {
+                    function: "desc->callback()".into(),
+                    file: None,
+                    context: ExecutionContext::SoftIrq,
+                    description: Some("用户的 DMA 完成回调 (软中断上下文，不可睡眠!)".into()),
+                    is_user_entry: true,
+                    kernel_version_range: None,
+                },
+            ],
+        };
+
+        // Bindings need the descriptor to be submitted, so the tracker finds them itself
+        self.async_patterns.insert(
+            "dma_callback".into(),
+            AsyncPattern {
+                description: "DMA 引擎完成回调 (软中断上下文，不可睡眠)".into(),
+                context: ExecutionContext::SoftIrq,
+                bind_patterns: vec![],
+                trigger_patterns: vec![r"dmaengine_submit\s*\(".into()],
+                handler_signature: Some("void (*)(void *)".into()),
+                timeline: None,
+                handler_call_chain: Some(dma_callback_chain),
+            },
+        );
     }
 
     fn load_builtin_apis(&mut self) {
@@ -683,6 +1219,75 @@ impl KnowledgeBase {
                 params: Some(vec!["fmt".into(), "...".into()]),
             },
         );
+
+        // Allocation / lookup APIs whose result must be checked (NULL or ERR_PTR)
+        let failable: &[(&str, &str, bool)] = &[
+            ("kmalloc", "Allocate kernel memory", true),
+            ("kcalloc", "Allocate a zeroed kernel array", true),
+            ("kmalloc_array", "Allocate a kernel array", true),
+            ("vmalloc", "Allocate virtually contiguous memory", true),
+            ("vzalloc", "Allocate zeroed virtually contiguous memory", true),
+            ("devm_kzalloc", "Allocate zeroed device-managed memory", true),
+            ("devm_kmalloc", "Allocate device-managed memory", true),
+            ("kmem_cache_alloc", "Allocate an object from a slab cache", true),
+            ("kstrdup", "Duplicate a string into kernel memory", true),
+            ("alloc_skb", "Allocate a socket buffer", true),
+            ("usb_alloc_urb", "Allocate a USB request block", true),
+            ("ioremap", "Map device memory into the kernel address space", true),
+            ("devm_ioremap", "Map device memory (device-managed)", true),
+            ("devm_ioremap_resource", "Map a platform resource (returns ERR_PTR)", true),
+            ("devm_platform_ioremap_resource", "Map a platform resource by index (returns ERR_PTR)", true),
+            ("clk_get", "Look up a clock (returns ERR_PTR)", true),
+            ("devm_clk_get", "Look up a device-managed clock (returns ERR_PTR)", true),
+            ("regulator_get", "Look up a regulator (returns ERR_PTR)", true),
+            ("devm_regulator_get", "Look up a device-managed regulator (returns ERR_PTR)", true),
+            ("platform_get_resource", "Get a platform device resource", false),
+        ];
+        for (name, description, can_sleep) in failable {
+            self.kernel_apis.insert(
+                name.to_string(),
+                KernelApi {
+                    description: description.to_string(),
+                    can_sleep: *can_sleep,
+                    can_fail: true,
+                    params: None,
+                },
+            );
+        }
+
+        // APIs that may block and must not be called from atomic context
+        let sleeping: &[(&str, &str)] = &[
+            ("msleep", "Sleep for at least the given milliseconds"),
+            ("msleep_interruptible", "Interruptible millisecond sleep"),
+            ("ssleep", "Sleep for the given seconds"),
+            ("usleep_range", "Sleep for a range of microseconds"),
+            ("schedule", "Yield the CPU to the scheduler"),
+            ("schedule_timeout", "Sleep until a timeout expires"),
+            ("mutex_lock_interruptible", "Acquire a mutex, interruptible"),
+            ("down", "Acquire a semaphore"),
+            ("down_interruptible", "Acquire a semaphore, interruptible"),
+            ("wait_for_completion", "Wait for a completion"),
+            ("wait_for_completion_timeout", "Wait for a completion with a timeout"),
+            ("copy_from_user", "Copy a buffer from user space"),
+            ("copy_to_user", "Copy a buffer to user space"),
+            ("vfree", "Free virtually contiguous memory"),
+            ("synchronize_rcu", "Wait for an RCU grace period"),
+            ("flush_work", "Wait for a work item to finish"),
+            ("cancel_work_sync", "Cancel a work item and wait for it"),
+            ("del_timer_sync", "Deactivate a timer and wait for its handler"),
+            ("request_firmware", "Load a firmware image"),
+        ];
+        for (name, description) in sleeping {
+            self.kernel_apis.insert(
+                name.to_string(),
+                KernelApi {
+                    description: description.to_string(),
+                    can_sleep: true,
+                    can_fail: false,
+                    params: None,
+                },
+            );
+        }
     }
 
     /// Get framework info
@@ -705,6 +1310,45 @@ impl KnowledgeBase {
         self.get_callback(framework, callback)?.call_chain.as_ref()
     }
     
+    /// ⭐ 获取指定内核版本下框架回调的调用链
+    ///
+    /// 优先选择版本范围匹配的变体，其次是不限版本的调用链；
+    /// 返回的调用链已去掉该版本中不存在的节点。
+    pub fn get_callback_call_chain_for_version(
+        &self,
+        framework: &str,
+        callback: &str,
+        version: &str,
+    ) -> Option<CallChain> {
+        let cb = self.get_callback(framework, callback)?;
+        let chains = || cb.call_chain.iter().chain(&cb.call_chain_variants);
+
+        chains()
+            .find(|c| c.kernel_version_range.is_some() && c.supports_version(version))
+            .or_else(|| chains().find(|c| c.kernel_version_range.is_none()))
+            .map(|c| c.for_version(version))
+    }
+
+    /// Add a version-specific call chain variant for a framework callback
+    pub fn add_callback_call_chain_variant(
+        &mut self,
+        framework: &str,
+        callback: &str,
+        chain: CallChain,
+    ) -> bool {
+        match self
+            .frameworks
+            .get_mut(framework)
+            .and_then(|fw| fw.callbacks.get_mut(callback))
+        {
+            Some(cb) => {
+                cb.call_chain_variants.push(chain);
+                true
+            }
+            None => false,
+        }
+    }
+
     /// ⭐ 获取异步模式的 handler 调用链
     pub fn get_async_handler_chain(&self, pattern_name: &str) -> Option<&CallChain> {
         self.async_patterns.get(pattern_name)?.handler_call_chain.as_ref()
@@ -715,14 +1359,27 @@ impl KnowledgeBase {
         self.async_patterns.get(pattern_name)?.timeline.as_ref()
     }
     
+    /// Map function names through `normalizer` before matching them against
+    /// callback names in [`identify_callback`](Self::identify_callback)
+    ///
+    /// e.g. stripping a project-wide prefix, so that with a driver prefix of
+    /// `uprobe_` not every function looks like a `probe` callback.
+    pub fn set_name_normalizer(&mut self, normalizer: NameNormalizer) {
+        self.name_normalizer = SharedNormalizer(Some(Arc::from(normalizer)));
+    }
+
     /// ⭐ 根据函数名查找其所属的框架和回调类型
     /// 例如：my_probe 函数可能被识别为 usb_driver 的 probe 回调
     pub fn identify_callback(&self, function_name: &str, code_context: &str) -> Option<(&str, &str, &FrameworkCallback)> {
+        let normalized = match &self.name_normalizer.0 {
+            Some(normalize) => normalize(function_name),
+            None => function_name.to_string(),
+        };
         // 检查代码上下文中是否有框架注册
         for (fw_name, framework) in &self.frameworks {
             for (cb_name, callback) in &framework.callbacks {
                 // 简单匹配：函数名包含回调名，或代码中有赋值
-                if function_name.contains(cb_name) {
+                if normalized.contains(cb_name) {
                     return Some((fw_name, cb_name, callback));
                 }
                 // 检查是否是 ops 表赋值
@@ -740,3 +1397,152 @@ impl KnowledgeBase {
         self.async_patterns.get(name)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_ranges() {
+        assert!(compare_versions("5.10", "5.9").is_gt());
+        assert!(compare_versions("6.1.0", "6.1").is_eq());
+        assert!(compare_versions("4.19-rc3", "5.0").is_lt());
+
+        let range = ("5.6".to_string(), String::new());
+        assert!(version_in_range("5.6", Some(&range)));
+        assert!(version_in_range("6.8", Some(&range)));
+        assert!(!version_in_range("5.4", Some(&range)));
+        assert!(version_in_range("2.6", None));
+    }
+
+    #[test]
+    fn test_callback_call_chain_for_version() {
+        let mut kb = KnowledgeBase::builtin();
+
+        // Node-level ranges: do_sys_openat2 only exists since 5.6
+        let has_openat2 = |chain: &CallChain| chain.nodes.iter().any(|n| n.function == "do_sys_openat2");
+        let old = kb.get_callback_call_chain_for_version("file_operations", "open", "4.19").unwrap();
+        let new = kb.get_callback_call_chain_for_version("file_operations", "open", "6.1").unwrap();
+        assert!(!has_openat2(&old));
+        assert!(has_openat2(&new));
+
+        // Chain-level variants take precedence when their range matches
+        let legacy = CallChain {
+            name: "legacy probe".into(),
+            trigger_source: "hotplug".into(),
+            nodes: vec![],
+            kernel_version_range: Some((String::new(), "2.6.30".into())),
+        };
+        assert!(kb.add_callback_call_chain_variant("usb_driver", "probe", legacy));
+        let chain = kb.get_callback_call_chain_for_version("usb_driver", "probe", "2.6.18").unwrap();
+        assert_eq!(chain.name, "legacy probe");
+        let chain = kb.get_callback_call_chain_for_version("usb_driver", "probe", "6.1").unwrap();
+        assert_ne!(chain.name, "legacy probe");
+    }
+
+    #[test]
+    fn test_yaml_call_chain_variants() {
+        let yaml = r#"
+frameworks:
+  file_operations:
+    description: "fops"
+    header: null
+    callbacks:
+      open:
+        description: "open"
+        trigger: "open()"
+        context: Process
+        signature: null
+        call_chain_variants:
+          - name: "old open"
+            trigger_source: "open()"
+            kernel_version_range: ["", "5.6"]
+            nodes:
+              - function: "do_sys_open"
+                file: "fs/open.c"
+                context: Process
+                description: null
+                is_user_entry: false
+          - name: "new open"
+            trigger_source: "open()"
+            kernel_version_range: ["5.6", ""]
+            nodes:
+              - function: "do_sys_openat2"
+                file: "fs/open.c"
+                context: Process
+                description: null
+                is_user_entry: false
+async_patterns: {}
+kernel_apis: {}
+"#;
+        let kb: KnowledgeBase = serde_yaml::from_str(yaml).unwrap();
+        let chain = kb.get_callback_call_chain_for_version("file_operations", "open", "5.4").unwrap();
+        assert_eq!(chain.name, "old open");
+        let chain = kb.get_callback_call_chain_for_version("file_operations", "open", "5.15").unwrap();
+        assert_eq!(chain.name, "new open");
+    }
+
+    #[test]
+    fn test_builtin_constants() {
+        let kb = KnowledgeBase::builtin();
+        let gfp_kernel = kb.get_constant("GFP_KERNEL").unwrap();
+        assert_eq!(gfp_kernel & kb.get_constant("__GFP_FS").unwrap(), 0x80);
+        assert_eq!(gfp_kernel & kb.get_constant("__GFP_ZERO").unwrap(), 0);
+        assert_eq!(kb.get_constant("O_RDWR"), Some(2));
+
+        let yaml = "frameworks: {}\nasync_patterns: {}\nkernel_apis: {}\nconstants:\n  MY_FLAG: 16\n";
+        let kb: KnowledgeBase = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(kb.get_constant("MY_FLAG"), Some(16));
+    }
+
+    #[test]
+    fn test_name_normalizer() {
+        let mut kb = KnowledgeBase::builtin();
+        let (_, callback, _) = kb.identify_callback("uprobe_init_hw", "").unwrap();
+        assert_eq!(callback, "probe");
+
+        kb.set_name_normalizer(Box::new(|name: &str| name.trim_start_matches("uprobe_").to_string()));
+        assert!(kb.identify_callback("uprobe_init_hw", "").is_none());
+        let clone = kb.clone();
+        let (_, callback, _) = clone.identify_callback("uprobe_disconnect", "").unwrap();
+        assert_eq!(callback, "disconnect");
+    }
+
+    #[test]
+    fn test_merge_user_knowledge() {
+        let dir = std::env::temp_dir().join(format!("flowsight-kb-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("10-team.yaml"),
+            r#"
+frameworks:
+  my_bus_driver:
+    description: "Team bus"
+    header: "my_bus.h"
+    callbacks: {}
+kernel_apis:
+  kfree:
+    description: "Free kernel memory (team notes)"
+    can_sleep: false
+    can_fail: false
+    params: null
+"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("20-consts.yml"), "constants:\n  MY_FLAG: 4\n").unwrap();
+        std::fs::write(dir.join("README.md"), "not knowledge").unwrap();
+
+        let mut kb = KnowledgeBase::builtin();
+        assert_eq!(kb.load_dir(&dir).unwrap(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // User entries are added or override...
+        assert!(kb.get_framework("my_bus_driver").is_some());
+        assert_eq!(kb.get_api("kfree").unwrap().description, "Free kernel memory (team notes)");
+        assert_eq!(kb.get_constant("MY_FLAG"), Some(4));
+        // ...and builtin knowledge survives
+        assert!(kb.get_framework("usb_driver").is_some());
+        assert!(kb.get_api("kzalloc").is_some());
+        assert!(kb.get_constant("GFP_KERNEL").is_some());
+    }
+}
@@ -0,0 +1,80 @@
+//! Mermaid rendering of async timelines
+//!
+//! Draws the two phases of an [`AsyncTimeline`] as subgraphs, each with its
+//! call chain and execution context, joined through the separation note.
+
+use crate::{AsyncTimeline, TimelinePhase};
+use std::fmt::Write;
+
+/// Render `timeline` as a Mermaid flowchart
+pub fn timeline_to_mermaid(timeline: &AsyncTimeline) -> String {
+    let mut out = String::from("flowchart TD\n");
+    let _ = writeln!(out, "    %% {}", timeline.name);
+    let (_, phase1_exit) = write_phase(&mut out, "p1", &timeline.phase1);
+    let (phase2_entry, _) = write_phase(&mut out, "p2", &timeline.phase2);
+    let _ = writeln!(out, "    sep{{{{\"{}\"}}}}", escape(&timeline.separation));
+    let _ = writeln!(out, "    {} -.-> sep", phase1_exit);
+    let _ = writeln!(out, "    sep -.-> {}", phase2_entry);
+    out
+}
+
+/// Write `phase` as subgraph `id`; returns the ids of its first and last nodes
+fn write_phase(out: &mut String, id: &str, phase: &TimelinePhase) -> (String, String) {
+    let chain = &phase.call_chain;
+    let _ = writeln!(
+        out,
+        "    subgraph {}[\"{} · {}\"]",
+        id,
+        escape(&phase.name),
+        escape(phase.context.description())
+    );
+    let trigger = format!("{}_src", id);
+    let _ = writeln!(out, "        {}([\"{}\"])", trigger, escape(&chain.trigger_source));
+
+    let mut previous = trigger.clone();
+    for (i, node) in chain.nodes.iter().enumerate() {
+        let node_id = format!("{}_{}", id, i);
+        let mut label = escape(&node.function);
+        if node.context != phase.context {
+            label.push_str(&format!("<br/>{}", escape(node.context.description())));
+        }
+        // User code gets the subroutine shape so it stands out from the kernel
+        match node.is_user_entry {
+            true => {
+                let _ = writeln!(out, "        {}[[\"{}\"]]", node_id, label);
+            }
+            false => {
+                let _ = writeln!(out, "        {}[\"{}\"]", node_id, label);
+            }
+        }
+        let _ = writeln!(out, "        {} --> {}", previous, node_id);
+        previous = node_id;
+    }
+    let _ = writeln!(out, "    end");
+    (trigger, previous)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KnowledgeBase;
+
+    #[test]
+    fn test_timeline_to_mermaid() {
+        let kb = KnowledgeBase::builtin();
+        let timeline = kb.get_async_timeline("work_struct").unwrap();
+        let mermaid = timeline_to_mermaid(timeline);
+
+        assert!(mermaid.starts_with("flowchart TD\n"));
+        assert!(mermaid.contains("subgraph p1[\"中断上半部 · 硬中断上下文 (不可睡眠)\"]"));
+        assert!(mermaid.contains("subgraph p2[\"WorkQueue 执行 · 进程上下文 (可睡眠)\"]"));
+        assert!(mermaid.contains("p1_src --> p1_0"));
+        assert!(mermaid.contains("p1_2[[\"irq_handler()\"]]"));
+        assert!(mermaid.contains("p1_2 -.-> sep"));
+        assert!(mermaid.contains("sep -.-> p2_src"));
+    }
+}
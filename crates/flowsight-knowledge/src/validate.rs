@@ -0,0 +1,239 @@
+//! Checking hand-written call chains against an indexed kernel tree
+//!
+//! Call chains in the knowledge base are written by hand and drift as the
+//! kernel changes. Pointing [`KnowledgeBase::validate_against_index`] at an
+//! index of a real checkout reports functions that moved or disappeared and
+//! consecutive nodes that no longer call each other.
+
+use crate::{CallChain, CallChainNode, KnowledgeBase};
+use flowsight_index::SymbolIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A problem found in one call chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainIssue {
+    /// Where the chain lives, e.g. `usb_driver.probe` or `workqueue`
+    pub owner: String,
+    /// Name of the chain
+    pub chain: String,
+    pub kind: ChainIssueKind,
+}
+
+/// What is wrong with a chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChainIssueKind {
+    /// The function is not defined anywhere in the index
+    MissingFunction { function: String, file: String },
+    /// The function exists, but not in the file the chain claims
+    WrongFile {
+        function: String,
+        file: String,
+        found_in: Vec<String>,
+    },
+    /// `caller` does not call `callee` directly
+    BrokenLink { caller: String, callee: String },
+}
+
+impl KnowledgeBase {
+    /// Check every call chain against an index of kernel sources
+    ///
+    /// Only nodes naming a kernel function with a source file are checked;
+    /// user entry points (`drv->probe()`) and descriptive nodes are skipped.
+    pub fn validate_against_index(&self, index: &SymbolIndex) -> Vec<ChainIssue> {
+        let mut files_by_function: HashMap<&str, Vec<&PathBuf>> = HashMap::new();
+        for (file, names) in &index.functions_by_file {
+            for name in names {
+                files_by_function.entry(name.as_str()).or_default().push(file);
+            }
+        }
+
+        let mut issues = Vec::new();
+        for (owner, chain) in self.all_call_chains() {
+            validate_chain(&owner, chain, index, &files_by_function, &mut issues);
+        }
+        issues
+    }
+
+    /// Every call chain with the framework callback or async pattern that owns it
+    fn all_call_chains(&self) -> Vec<(String, &CallChain)> {
+        let mut chains = Vec::new();
+
+        let mut frameworks: Vec<_> = self.frameworks.iter().collect();
+        frameworks.sort_by_key(|(name, _)| *name);
+        for (fw_name, fw) in frameworks {
+            let mut callbacks: Vec<_> = fw.callbacks.iter().collect();
+            callbacks.sort_by_key(|(name, _)| *name);
+            for (cb_name, cb) in callbacks {
+                let owner = format!("{}.{}", fw_name, cb_name);
+                for chain in cb.call_chain.iter().chain(&cb.call_chain_variants) {
+                    chains.push((owner.clone(), chain));
+                }
+            }
+        }
+
+        let mut patterns: Vec<_> = self.async_patterns.iter().collect();
+        patterns.sort_by_key(|(name, _)| *name);
+        for (name, pattern) in patterns {
+            if let Some(chain) = &pattern.handler_call_chain {
+                chains.push((name.clone(), chain));
+            }
+            if let Some(timeline) = &pattern.timeline {
+                chains.push((name.clone(), &timeline.phase1.call_chain));
+                chains.push((name.clone(), &timeline.phase2.call_chain));
+            }
+        }
+
+        chains
+    }
+}
+
+fn validate_chain(
+    owner: &str,
+    chain: &CallChain,
+    index: &SymbolIndex,
+    files_by_function: &HashMap<&str, Vec<&PathBuf>>,
+    issues: &mut Vec<ChainIssue>,
+) {
+    let mut issue = |kind| {
+        issues.push(ChainIssue {
+            owner: owner.to_string(),
+            chain: chain.name.clone(),
+            kind,
+        })
+    };
+
+    // Previous kernel node that exists in the index
+    let mut previous: Option<&str> = None;
+    for node in &chain.nodes {
+        let Some(file) = kernel_source_file(node) else {
+            previous = None;
+            continue;
+        };
+
+        let found_in = files_by_function.get(node.function.as_str());
+        let Some(found_in) = found_in else {
+            issue(ChainIssueKind::MissingFunction {
+                function: node.function.clone(),
+                file: file.to_string(),
+            });
+            previous = None;
+            continue;
+        };
+        if !found_in.iter().any(|f| f.ends_with(Path::new(file))) {
+            let mut found_in: Vec<String> = found_in.iter().map(|f| f.display().to_string()).collect();
+            found_in.sort();
+            issue(ChainIssueKind::WrongFile {
+                function: node.function.clone(),
+                file: file.to_string(),
+                found_in,
+            });
+        }
+
+        if let Some(caller) = previous {
+            let calls = index
+                .functions
+                .get(caller)
+                .is_some_and(|f| f.calls.iter().any(|c| c == &node.function));
+            if !calls {
+                issue(ChainIssueKind::BrokenLink {
+                    caller: caller.to_string(),
+                    callee: node.function.clone(),
+                });
+            }
+        }
+        previous = Some(node.function.as_str());
+    }
+}
+
+/// Source file of a node naming a plain kernel function
+fn kernel_source_file(node: &CallChainNode) -> Option<&str> {
+    let is_identifier = !node.function.is_empty()
+        && node
+            .function
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if node.is_user_entry || !is_identifier {
+        return None;
+    }
+    node.file.as_deref()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowsight_core::{FunctionDef, Location};
+
+    fn func(name: &str, file: &str, calls: &[&str]) -> FunctionDef {
+        FunctionDef {
+            name: name.into(),
+            return_type: "int".into(),
+            location: Some(Location::new(file, 1, 0)),
+            calls: calls.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_validate_against_index() {
+        let yaml = r#"
+frameworks:
+  file_operations:
+    description: "fops"
+    header: null
+    callbacks:
+      open:
+        description: "open"
+        trigger: "open()"
+        context: Process
+        signature: null
+        call_chain:
+          name: "open chain"
+          trigger_source: "open()"
+          nodes:
+            - { function: "do_sys_open", file: "fs/open.c", context: Process, description: null, is_user_entry: false }
+            - { function: "do_filp_open", file: "fs/open.c", context: Process, description: null, is_user_entry: false }
+            - { function: "path_openat", file: "fs/namei.c", context: Process, description: null, is_user_entry: false }
+            - { function: "vfs_open", file: "fs/open.c", context: Process, description: null, is_user_entry: false }
+            - { function: "f->f_op->open()", file: null, context: Process, description: null, is_user_entry: true }
+async_patterns: {}
+kernel_apis: {}
+"#;
+        let kb: KnowledgeBase = serde_yaml::from_str(yaml).unwrap();
+
+        let mut index = SymbolIndex::new();
+        for (f, file) in [
+            (func("do_sys_open", "/linux/fs/open.c", &["do_filp_open"]), "/linux/fs/open.c"),
+            (func("do_filp_open", "/linux/fs/namei.c", &["set_nameidata"]), "/linux/fs/namei.c"),
+            (func("path_openat", "/linux/fs/namei.c", &["do_open"]), "/linux/fs/namei.c"),
+        ] {
+            index.add_function(f, Path::new(file));
+        }
+
+        let kinds: Vec<ChainIssueKind> = kb
+            .validate_against_index(&index)
+            .into_iter()
+            .inspect(|issue| assert_eq!(issue.owner, "file_operations.open"))
+            .map(|issue| issue.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ChainIssueKind::WrongFile {
+                    function: "do_filp_open".into(),
+                    file: "fs/open.c".into(),
+                    found_in: vec!["/linux/fs/namei.c".into()],
+                },
+                ChainIssueKind::BrokenLink {
+                    caller: "do_filp_open".into(),
+                    callee: "path_openat".into(),
+                },
+                ChainIssueKind::MissingFunction {
+                    function: "vfs_open".into(),
+                    file: "fs/open.c".into(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! FlowSight LSP server
+//!
+//! Serves go-to-definition, find-references and workspace symbols over stdio,
+//! backed by a persistent symbol index. Unlike clangd, references include
+//! async registrations (INIT_WORK, request_irq, ...) recorded in the index.
+
+mod server;
+mod transport;
+
+use anyhow::Result;
+use clap::Parser;
+use flowsight_index::{IndexStorage, SymbolIndex};
+use serde_json::json;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use transport::Frame;
+
+#[derive(Parser)]
+#[command(name = "flowsight-lsp")]
+#[command(author, version, about = "FlowSight language server", long_about = None)]
+struct Cli {
+    /// Index database directory (built from the workspace root when empty)
+    #[arg(long, value_name = "DIR")]
+    index: Option<PathBuf>,
+}
+
+fn main() -> Result<()> {
+    // stdout carries the protocol, so log to stderr
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
+
+    let cli = Cli::parse();
+
+    let (index, storage) = match &cli.index {
+        Some(path) => {
+            let storage = IndexStorage::open(path)?;
+            (storage.load_index()?, Some(storage))
+        }
+        None => (SymbolIndex::new(), None),
+    };
+    tracing::info!("Loaded {} functions", index.stats().total_functions);
+
+    let mut server = server::Server::new(index, storage);
+    let mut input = BufReader::new(std::io::stdin().lock());
+    let mut output = BufWriter::new(std::io::stdout().lock());
+
+    while let Some(frame) = transport::read_message(&mut input)? {
+        let message = match frame {
+            Frame::Message(message) => message,
+            Frame::Malformed(error) => {
+                tracing::warn!("Malformed message: {}", error);
+                let response = json!({
+                    "jsonrpc": "2.0",
+                    "id": null,
+                    "error": {"code": server::PARSE_ERROR, "message": error}
+                });
+                transport::write_message(&mut output, &response)?;
+                continue;
+            }
+        };
+        if message.get("method").and_then(|m| m.as_str()) == Some("exit") {
+            break;
+        }
+        if let Some(response) = server.handle(&message) {
+            transport::write_message(&mut output, &response)?;
+        }
+    }
+
+    Ok(())
+}
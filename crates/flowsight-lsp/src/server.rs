@@ -0,0 +1,642 @@
+//! Request dispatch for the LSP subset FlowSight supports
+//!
+//! - `textDocument/definition`: the function's `Location` from the index
+//! - `textDocument/references`: call sites of direct callers, plus async registrations
+//! - `workspace/symbol`: substring search over indexed functions and structs
+
+use flowsight_analysis::async_tracker::AsyncTracker;
+use flowsight_analysis::funcptr::FuncPtrResolver;
+use flowsight_core::{CallType, Location};
+use flowsight_index::{IndexStorage, SymbolIndex};
+use flowsight_parser::cache::{PersistentCache, DEFAULT_CACHE_DIR};
+use flowsight_parser::parallel::ParallelParser;
+use flowsight_parser::preprocessor::HeaderResolver;
+use flowsight_query::{QueryEngine, SearchMode};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use url::Url;
+use walkdir::WalkDir;
+
+/// LSP `SymbolKind` values
+const SYMBOL_KIND_FUNCTION: u32 = 12;
+const SYMBOL_KIND_STRUCT: u32 = 23;
+
+/// JSON-RPC error codes
+pub const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Unit the client counts `Position.character` in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PositionEncoding {
+    Utf8,
+    /// The LSP default when the client offers nothing else
+    Utf16,
+}
+
+impl PositionEncoding {
+    /// UTF-8 when the client offers it, since locations are byte columns
+    fn negotiate(params: &Value) -> Self {
+        let offered = params
+            .pointer("/capabilities/general/positionEncodings")
+            .and_then(Value::as_array);
+        match offered.is_some_and(|encodings| encodings.iter().any(|e| e == "utf-8")) {
+            true => PositionEncoding::Utf8,
+            false => PositionEncoding::Utf16,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            PositionEncoding::Utf8 => "utf-8",
+            PositionEncoding::Utf16 => "utf-16",
+        }
+    }
+}
+
+/// LSP server state
+pub struct Server {
+    engine: QueryEngine,
+    storage: Option<IndexStorage>,
+    /// Text of open documents by URI (full sync)
+    documents: HashMap<String, String>,
+    position_encoding: PositionEncoding,
+}
+
+impl Server {
+    /// Create a server over an index, persisting rebuilt indexes to `storage`
+    pub fn new(index: SymbolIndex, storage: Option<IndexStorage>) -> Self {
+        Self {
+            engine: QueryEngine::with_index(index),
+            storage,
+            documents: HashMap::new(),
+            position_encoding: PositionEncoding::Utf16,
+        }
+    }
+
+    /// Handle a request or notification, returning the response for requests
+    pub fn handle(&mut self, message: &Value) -> Option<Value> {
+        let method = message.get("method")?.as_str()?;
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        let Some(id) = message.get("id").cloned() else {
+            self.handle_notification(method, &params);
+            return None;
+        };
+
+        let response = match self.handle_request(method, &params) {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, msg)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {"code": code, "message": msg}
+            }),
+        };
+        Some(response)
+    }
+
+    fn handle_request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        match method {
+            "initialize" => {
+                self.position_encoding = PositionEncoding::negotiate(params);
+                self.initialize(params);
+                Ok(json!({
+                    "capabilities": {
+                        "positionEncoding": self.position_encoding.name(),
+                        "textDocumentSync": 1,
+                        "definitionProvider": true,
+                        "referencesProvider": true,
+                        "workspaceSymbolProvider": true
+                    },
+                    "serverInfo": {"name": "flowsight-lsp", "version": env!("CARGO_PKG_VERSION")}
+                }))
+            }
+            "shutdown" => Ok(Value::Null),
+            "textDocument/definition" => {
+                let name = self.symbol_at(params).ok_or_else(invalid_params)?;
+                Ok(self
+                    .definition(&name)
+                    .and_then(|loc| self.lsp_locations([&loc]).pop())
+                    .unwrap_or(Value::Null))
+            }
+            "textDocument/references" => {
+                let name = self.symbol_at(params).ok_or_else(invalid_params)?;
+                let include_declaration = params
+                    .pointer("/context/includeDeclaration")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false);
+                let mut locations = Vec::new();
+                if include_declaration {
+                    locations.extend(self.definition(&name));
+                }
+                locations.extend(self.references(&name));
+                Ok(Value::Array(self.lsp_locations(&locations)))
+            }
+            "workspace/symbol" => {
+                let query = params.get("query").and_then(Value::as_str).unwrap_or("");
+                Ok(Value::Array(self.workspace_symbols(query)))
+            }
+            _ => Err((
+                METHOD_NOT_FOUND,
+                format!("Method not supported: {}", method),
+            )),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) {
+        let uri = params
+            .pointer("/textDocument/uri")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        match (method, uri) {
+            ("textDocument/didOpen", Some(uri)) => {
+                if let Some(text) = params.pointer("/textDocument/text").and_then(Value::as_str) {
+                    self.documents.insert(uri, text.to_string());
+                }
+            }
+            ("textDocument/didChange", Some(uri)) => {
+                // Full sync: the last change holds the whole document
+                if let Some(text) = params
+                    .get("contentChanges")
+                    .and_then(Value::as_array)
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(Value::as_str)
+                {
+                    self.documents.insert(uri, text.to_string());
+                }
+            }
+            ("textDocument/didClose", Some(uri)) => {
+                self.documents.remove(&uri);
+            }
+            _ => {}
+        }
+    }
+
+    /// Index the workspace root if the persistent index is empty
+    fn initialize(&mut self, params: &Value) {
+        if !self.engine.index().functions.is_empty() {
+            return;
+        }
+        let root = params
+            .get("rootUri")
+            .and_then(Value::as_str)
+            .and_then(uri_to_path)
+            .or_else(|| {
+                params
+                    .get("rootPath")
+                    .and_then(Value::as_str)
+                    .map(PathBuf::from)
+            });
+        let Some(root) = root else {
+            return;
+        };
+
+        tracing::info!("Indexing {}", root.display());
+        let index = build_index(&root);
+        if let Some(storage) = &self.storage {
+            if let Err(e) = storage.save_index(&index) {
+                tracing::warn!("Failed to persist index: {}", e);
+            }
+        }
+        self.engine = QueryEngine::with_index(index);
+    }
+
+    fn definition(&self, name: &str) -> Option<Location> {
+        self.engine
+            .get_function(name)
+            .and_then(|f| f.location.clone())
+            .or_else(|| {
+                self.engine
+                    .get_struct(name)
+                    .and_then(|s| s.location.clone())
+            })
+    }
+
+    /// Call sites of `name`: direct calls inside each caller, and async bind sites
+    fn references(&self, name: &str) -> Vec<Location> {
+        let mut locations = Vec::new();
+
+        for edge in self.engine.get_caller_edges(name) {
+            match edge.call_type {
+                CallType::Async { .. } => {
+                    locations.extend(
+                        self.engine
+                            .index()
+                            .get_async_bindings(name)
+                            .iter()
+                            .filter(|b| b.registered_by.as_deref() == Some(edge.caller.as_str()))
+                            .filter_map(|b| b.binding.bind_location.clone()),
+                    );
+                }
+                _ => {
+                    let Some(caller_loc) = self
+                        .engine
+                        .get_function(&edge.caller)
+                        .and_then(|f| f.location.as_ref())
+                    else {
+                        continue;
+                    };
+                    // A recursive function names itself on its definition line
+                    let skip_definition = edge.caller == name;
+                    let sites = self.call_sites(caller_loc, name, skip_definition);
+                    if sites.is_empty() {
+                        locations.push(caller_loc.clone());
+                    } else {
+                        locations.extend(sites);
+                    }
+                }
+            }
+        }
+
+        // One edge per binding may repeat the same bind sites
+        let mut seen = HashSet::new();
+        locations.retain(|loc| seen.insert(loc.clone()));
+        locations
+    }
+
+    /// Find occurrences of `name` within the span of `func_loc`
+    fn call_sites(&self, func_loc: &Location, name: &str, skip_definition: bool) -> Vec<Location> {
+        let Some(text) = self.document_text(&func_loc.file) else {
+            return Vec::new();
+        };
+
+        let first = func_loc.line.saturating_sub(1) as usize;
+        let last = func_loc.end_line.max(func_loc.line) as usize;
+        text.lines()
+            .enumerate()
+            .skip(first)
+            .take(last - first)
+            .filter(|(row, _)| !(skip_definition && *row == first))
+            .filter_map(|(row, line)| {
+                find_identifier(line, name).map(|col| {
+                    Location::with_range(
+                        func_loc.file.clone(),
+                        row as u32 + 1,
+                        col as u32,
+                        row as u32 + 1,
+                        (col + name.len()) as u32,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn workspace_symbols(&self, query: &str) -> Vec<Value> {
+        let mut symbols: Vec<(&str, u32, &Location)> = self
+            .engine
+            .search_functions(query, SearchMode::Substring)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|f| Some((f.name.as_str(), SYMBOL_KIND_FUNCTION, f.location.as_ref()?)))
+            .collect();
+        symbols.extend(
+            self.engine
+                .index()
+                .structs
+                .values()
+                .filter(|s| s.name.contains(query))
+                .filter_map(|s| Some((s.name.as_str(), SYMBOL_KIND_STRUCT, s.location.as_ref()?))),
+        );
+        symbols.sort_by(|a, b| a.0.cmp(b.0));
+
+        let locations = self.lsp_locations(symbols.iter().map(|(_, _, loc)| *loc));
+        symbols
+            .into_iter()
+            .zip(locations)
+            .map(|((name, kind, _), location)| {
+                json!({
+                    "name": name,
+                    "kind": kind,
+                    "location": location
+                })
+            })
+            .collect()
+    }
+
+    /// LSP locations for `locations`, with columns in the negotiated encoding
+    fn lsp_locations<'a>(&self, locations: impl IntoIterator<Item = &'a Location>) -> Vec<Value> {
+        let mut texts: HashMap<&str, Option<String>> = HashMap::new();
+        locations
+            .into_iter()
+            .map(|loc| {
+                let text = match self.position_encoding {
+                    PositionEncoding::Utf8 => None,
+                    PositionEncoding::Utf16 => texts
+                        .entry(loc.file.as_str())
+                        .or_insert_with(|| self.document_text(&loc.file))
+                        .as_deref(),
+                };
+                to_lsp_location(loc, text)
+            })
+            .collect()
+    }
+
+    /// Identifier under the cursor of a `TextDocumentPositionParams`
+    fn symbol_at(&self, params: &Value) -> Option<String> {
+        let uri = params.pointer("/textDocument/uri")?.as_str()?;
+        let line = params.pointer("/position/line")?.as_u64()? as usize;
+        let character = params.pointer("/position/character")?.as_u64()? as usize;
+
+        let text = match self.documents.get(uri) {
+            Some(text) => text.clone(),
+            None => std::fs::read_to_string(uri_to_path(uri)?).ok()?,
+        };
+        let line = text.lines().nth(line)?;
+        let column = match self.position_encoding {
+            PositionEncoding::Utf8 => character,
+            PositionEncoding::Utf16 => utf16_to_byte(line, character),
+        };
+        identifier_at(line, column)
+    }
+
+    fn document_text(&self, file: &str) -> Option<String> {
+        let uri = path_to_uri(Path::new(file));
+        match self.documents.get(&uri) {
+            Some(text) => Some(text.clone()),
+            None => std::fs::read_to_string(file).ok(),
+        }
+    }
+}
+
+fn invalid_params() -> (i64, String) {
+    (INVALID_PARAMS, "No symbol at position".to_string())
+}
+
+/// Parse all C sources under `root` into a fresh index
+pub fn build_index(root: &Path) -> SymbolIndex {
+    let files: Vec<PathBuf> = WalkDir::new(root)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.path()
+                .extension()
+                .map(|ext| ext == "c" || ext == "h")
+                .unwrap_or(false)
+        })
+        .map(|e| e.path().to_path_buf())
+        .collect();
+
+    let async_tracker = AsyncTracker::new();
+    let funcptr_resolver = FuncPtrResolver::new();
+    let mut index = SymbolIndex::with_root(root);
+    let mut parser = ParallelParser::new();
+    if let Ok(cache) = PersistentCache::new(root.join(DEFAULT_CACHE_DIR)) {
+        parser = parser.with_persistent_cache(cache);
+    }
+    parser = parser.with_header_resolver(HeaderResolver::for_project(root));
+    for (file, result) in parser.parse_files(&files) {
+        let Ok(parse_result) = result else {
+            continue;
+        };
+        for func in parse_result.functions.values() {
+            index.add_function(func.clone(), &file);
+        }
+        for st in parse_result.structs.values() {
+            index.add_struct(st.clone());
+        }
+        for occurrence in &parse_result.occurrences {
+            index.add_occurrence(occurrence.clone());
+        }
+        if let Ok(source) = std::fs::read_to_string(&file) {
+            for binding in async_tracker.analyze(&source, &parse_result.functions) {
+                let registered_by = binding.bind_location.as_ref().and_then(|loc| {
+                    parse_result
+                        .functions
+                        .values()
+                        .find(|f| {
+                            f.location
+                                .as_ref()
+                                .is_some_and(|l| l.line <= loc.line && loc.line <= l.end_line)
+                        })
+                        .map(|f| f.name.clone())
+                });
+                index.add_async_binding(binding, registered_by);
+            }
+            for assignment in funcptr_resolver.find_ops_assignments(&source, &file.to_string_lossy()) {
+                index.add_ops_assignment(assignment);
+            }
+        }
+    }
+    for (file, headers) in parser.include_map() {
+        index.set_includes(&file, headers);
+    }
+    index
+}
+
+/// Convert a FlowSight location (1-based lines) to an LSP location (0-based)
+///
+/// Byte columns are converted to UTF-16 through the lines of `text` when it
+/// is given, and passed through otherwise.
+fn to_lsp_location(loc: &Location, text: Option<&str>) -> Value {
+    let end_line = loc.end_line.max(loc.line);
+    let column = |line: u32, byte: u32| {
+        match text.and_then(|text| text.lines().nth(line.saturating_sub(1) as usize)) {
+            Some(line) => byte_to_utf16(line, byte as usize) as u32,
+            None => byte,
+        }
+    };
+    json!({
+        "uri": path_to_uri(Path::new(&loc.file)),
+        "range": {
+            "start": {"line": loc.line.saturating_sub(1), "character": column(loc.line, loc.column)},
+            "end": {"line": end_line.saturating_sub(1), "character": column(end_line, loc.end_column)}
+        }
+    })
+}
+
+/// UTF-16 code units in `line` before byte offset `byte`
+fn byte_to_utf16(line: &str, byte: usize) -> usize {
+    line.char_indices()
+        .take_while(|(i, _)| *i < byte)
+        .map(|(_, c)| c.len_utf16())
+        .sum()
+}
+
+/// Byte offset of the character `units` UTF-16 code units into `line`
+fn utf16_to_byte(line: &str, units: usize) -> usize {
+    let mut count = 0;
+    for (i, c) in line.char_indices() {
+        if count >= units {
+            return i;
+        }
+        count += c.len_utf16();
+    }
+    line.len()
+}
+
+/// `file://` URI of `path`, percent-encoded
+fn path_to_uri(path: &Path) -> String {
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    Url::from_file_path(&path)
+        .map(String::from)
+        .unwrap_or_else(|()| format!("file://{}", path.to_string_lossy()))
+}
+
+/// Local path of a `file://` URI, percent-decoded
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    Url::parse(uri).ok()?.to_file_path().ok()
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Identifier containing (or ending right before) byte offset `column`
+fn identifier_at(line: &str, column: usize) -> Option<String> {
+    // Identifiers are ASCII, so their bounds are always char boundaries
+    let is_ident = |i: usize| line.as_bytes().get(i).is_some_and(|&b| is_ident_char(b as char));
+    let mut pos = column.min(line.len());
+    if !is_ident(pos) {
+        pos = pos.checked_sub(1)?;
+        if !is_ident(pos) {
+            return None;
+        }
+    }
+
+    let start = (0..=pos).rev().take_while(|&i| is_ident(i)).last()?;
+    let end = (pos..line.len()).take_while(|&i| is_ident(i)).last()?;
+    Some(line[start..=end].to_string())
+}
+
+/// Byte column of the first whole-word occurrence of `name` in `line`
+fn find_identifier(line: &str, name: &str) -> Option<usize> {
+    line.match_indices(name).map(|(i, _)| i).find(|&i| {
+        let before = line[..i].chars().next_back();
+        let after = line[i + name.len()..].chars().next();
+        !before.is_some_and(is_ident_char) && !after.is_some_and(is_ident_char)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowsight_core::FunctionDef;
+
+    fn func(name: &str, file: &str, line: u32, end_line: u32, calls: &[&str]) -> FunctionDef {
+        FunctionDef {
+            name: name.into(),
+            return_type: "int".into(),
+            location: Some(Location::with_range(file, line, 0, end_line, 1)),
+            calls: calls.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_identifier_at() {
+        assert_eq!(
+            identifier_at("    ret = do_probe(dev);", 12),
+            Some("do_probe".into())
+        );
+        assert_eq!(
+            identifier_at("    ret = do_probe(dev);", 18),
+            Some("do_probe".into())
+        );
+        assert_eq!(identifier_at("a + b", 2), None);
+        assert_eq!(find_identifier("foo_bar(); foo();", "foo"), Some(11));
+    }
+
+    #[test]
+    fn test_definition_and_references() {
+        let source =
+            "int helper(void)\n{\n\treturn 0;\n}\n\nint probe(void)\n{\n\treturn helper();\n}\n";
+        let file = "/nonexistent/drv.c";
+        let uri = "file:///nonexistent/drv.c";
+
+        let mut index = SymbolIndex::new();
+        index.add_function(func("helper", file, 1, 4, &[]), Path::new(file));
+        index.add_function(func("probe", file, 6, 9, &["helper"]), Path::new(file));
+
+        let mut server = Server::new(index, None);
+        server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {"textDocument": {"uri": uri, "text": source}}
+        }));
+
+        let position =
+            json!({"textDocument": {"uri": uri}, "position": {"line": 7, "character": 10}});
+        let def = server
+            .handle(&json!({"jsonrpc": "2.0", "id": 1, "method": "textDocument/definition", "params": position}))
+            .unwrap();
+        assert_eq!(def["result"]["range"]["start"]["line"], 0);
+
+        let refs = server
+            .handle(&json!({"jsonrpc": "2.0", "id": 2, "method": "textDocument/references", "params": position}))
+            .unwrap();
+        let refs = refs["result"].as_array().unwrap();
+        assert_eq!(refs.len(), 1);
+        assert_eq!(refs[0]["range"]["start"]["line"], 7);
+        assert_eq!(refs[0]["range"]["start"]["character"], 8);
+
+        let symbols = server
+            .handle(&json!({"jsonrpc": "2.0", "id": 3, "method": "workspace/symbol", "params": {"query": "prob"}}))
+            .unwrap();
+        assert_eq!(symbols["result"][0]["name"], "probe");
+        assert_eq!(symbols["result"][0]["kind"], SYMBOL_KIND_FUNCTION);
+    }
+
+    #[test]
+    fn test_position_encoding() {
+        // "é" is two bytes but one UTF-16 unit, "🦀" four bytes but two units
+        let source = "int helper(void)\n{\n\treturn 0;\n}\n\nint probe(void)\n{\n\t/* é🦀 */ return helper();\n}\n";
+        let file = "/nonexistent/drv.c";
+        let uri = "file:///nonexistent/drv.c";
+        let line = source.lines().nth(7).unwrap();
+        let byte = line.find("helper").unwrap();
+        let utf16 = byte_to_utf16(line, byte);
+        assert_eq!((byte, utf16), (21, 18));
+        assert_eq!(utf16_to_byte(line, utf16), byte);
+
+        let server = |offered: Value| {
+            let mut index = SymbolIndex::new();
+            index.add_function(func("helper", file, 1, 4, &[]), Path::new(file));
+            index.add_function(func("probe", file, 6, 9, &["helper"]), Path::new(file));
+            let mut server = Server::new(index, None);
+            let init = server
+                .handle(&json!({"jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {
+                    "capabilities": {"general": {"positionEncodings": offered}}
+                }}))
+                .unwrap();
+            server.handle(&json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didOpen",
+                "params": {"textDocument": {"uri": uri, "text": source}}
+            }));
+            (server, init["result"]["capabilities"]["positionEncoding"].clone())
+        };
+        let references = |server: &mut Server, character: usize| {
+            let params = json!({"textDocument": {"uri": uri}, "position": {"line": 7, "character": character}});
+            server
+                .handle(&json!({"jsonrpc": "2.0", "id": 1, "method": "textDocument/references", "params": params}))
+                .unwrap()["result"]
+                .clone()
+        };
+
+        let (mut utf16_server, encoding) = server(json!(["utf-16"]));
+        assert_eq!(encoding, "utf-16");
+        let refs = references(&mut utf16_server, utf16 + 1);
+        assert_eq!(refs[0]["range"]["start"]["character"], utf16);
+        assert_eq!(refs[0]["range"]["end"]["character"], utf16 + "helper".len());
+
+        let (mut utf8_server, encoding) = server(json!(["utf-8", "utf-16"]));
+        assert_eq!(encoding, "utf-8");
+        let refs = references(&mut utf8_server, byte + 1);
+        assert_eq!(refs[0]["range"]["start"]["character"], byte);
+    }
+    #[test]
+    fn test_uri_conversion() {
+        let path = Path::new("/nonexistent/my drivers/#1/é.c");
+        let uri = path_to_uri(path);
+        assert_eq!(uri, "file:///nonexistent/my%20drivers/%231/%C3%A9.c");
+        assert_eq!(uri_to_path(&uri).as_deref(), Some(path));
+        assert_eq!(
+            uri_to_path("file:///nonexistent/a%2Bb%5Bc%5D.c").as_deref(),
+            Some(Path::new("/nonexistent/a+b[c].c"))
+        );
+        assert_eq!(uri_to_path("untitled:Untitled-1"), None);
+    }
+}
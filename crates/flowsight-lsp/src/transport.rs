@@ -0,0 +1,92 @@
+//! JSON-RPC framing over stdio (`Content-Length` headers)
+
+use anyhow::Result;
+use serde_json::Value;
+use std::io::{BufRead, Write};
+
+/// One frame read from the client
+#[derive(Debug, PartialEq)]
+pub enum Frame {
+    /// A well-formed JSON message
+    Message(Value),
+    /// A frame whose header or body could not be decoded
+    Malformed(String),
+}
+
+/// Read one frame; returns `None` on EOF
+///
+/// Only I/O errors are returned as errors: a bad header or body yields
+/// [`Frame::Malformed`] and leaves the reader at the next frame.
+pub fn read_message(input: &mut impl BufRead) -> Result<Option<Frame>> {
+    let mut content_length = None;
+
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        // The body of a frame without a length runs into the next header line
+        if let Some((_, value)) = header.split_once("Content-Length:") {
+            content_length = Some(value.trim().parse::<usize>().map_err(|_| value.trim().to_string()));
+        }
+    }
+
+    let len = match content_length {
+        Some(Ok(len)) => len,
+        Some(Err(value)) => return Ok(Some(Frame::Malformed(format!("bad Content-Length: {}", value)))),
+        None => return Ok(Some(Frame::Malformed("missing Content-Length header".to_string()))),
+    };
+
+    let mut body = vec![0u8; len];
+    input.read_exact(&mut body)?;
+    Ok(Some(match serde_json::from_slice(&body) {
+        Ok(message) => Frame::Message(message),
+        Err(e) => Frame::Malformed(e.to_string()),
+    }))
+}
+
+/// Write one message
+pub fn write_message(output: &mut impl Write, message: &Value) -> Result<()> {
+    let body = serde_json::to_string(message)?;
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_roundtrip() {
+        let msg = json!({"jsonrpc": "2.0", "id": 1, "method": "shutdown"});
+        let mut buf = Vec::new();
+        write_message(&mut buf, &msg).unwrap();
+
+        let mut input = Cursor::new(buf);
+        assert_eq!(read_message(&mut input).unwrap(), Some(Frame::Message(msg)));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+
+    #[test]
+    fn test_malformed_frames() {
+        let msg = json!({"jsonrpc": "2.0", "id": 1, "method": "shutdown"});
+        let mut buf = b"Content-Length: 5\r\n\r\n{oops".to_vec();
+        buf.extend_from_slice(b"Content-Length: x\r\n\r\n");
+        buf.extend_from_slice(b"\r\n{}");
+        write_message(&mut buf, &msg).unwrap();
+
+        let mut input = Cursor::new(buf);
+        let malformed = |frame| matches!(frame, Some(Frame::Malformed(_)));
+        assert!(malformed(read_message(&mut input).unwrap()));
+        assert!(malformed(read_message(&mut input).unwrap()));
+        assert!(malformed(read_message(&mut input).unwrap()));
+        assert_eq!(read_message(&mut input).unwrap(), Some(Frame::Message(msg)));
+    }
+}
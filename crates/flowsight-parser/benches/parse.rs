@@ -0,0 +1,294 @@
+//! Parse throughput benchmarks
+//!
+//! Measures parsing plus symbol extraction in MB/s over a synthetic corpus
+//! of driver-shaped C files, and how [`ParallelParser`] scales with thread
+//! count. The corpus is generated from a fixed seed, so runs compare on any
+//! machine without shipping kernel sources.
+//!
+//! ```text
+//! cargo bench -p flowsight-parser --bench parse -- --save-baseline main
+//! cargo bench -p flowsight-parser --bench parse -- --baseline main
+//! ```
+//!
+//! See `docs/developer/BENCHMARKS.md` for the baseline and how to compare.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use flowsight_parser::parallel::ParallelParser;
+use std::fmt::Write;
+use std::hint::black_box;
+use std::path::PathBuf;
+
+/// Files in the corpus
+const CORPUS_FILES: usize = 64;
+/// Functions per corpus file; a mid-sized driver
+const CORPUS_FUNCTIONS: usize = 60;
+
+/// Small deterministic generator (xorshift), so the corpus is reproducible
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+/// A driver-shaped C file with `functions` functions
+///
+/// Mixes what real drivers are made of: ops structs and tables, probe with
+/// a goto unwind cascade, an ioctl switch, IRQ and work handlers, locking,
+/// `#ifdef CONFIG_*` blocks and comments.
+fn synthetic_driver(seed: u64, functions: usize) -> String {
+    let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1);
+    let p = format!("drv{}", seed);
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        r#"// SPDX-License-Identifier: GPL-2.0
+/*
+ * Synthetic driver {p} for parser benchmarks
+ */
+#include <linux/module.h>
+#include <linux/interrupt.h>
+#include <linux/workqueue.h>
+#include <linux/slab.h>
+#include "{p}.h"
+
+#define {P}_MAX_QUEUE 64
+#define {P}_REG(n) (0x100 + (n) * 4)
+
+struct {p}_ops {{
+	int (*start)(struct {p}_dev *dev);
+	void (*stop)(struct {p}_dev *dev);
+	long (*ioctl)(struct {p}_dev *dev, unsigned int cmd, unsigned long arg);
+}};
+
+struct {p}_dev {{
+	struct device *dev;
+	void __iomem *regs;
+	spinlock_t lock;
+	struct mutex io_lock;
+	struct work_struct reset_work;
+	const struct {p}_ops *ops;
+	u32 queue[{P}_MAX_QUEUE];
+	int irq;
+}};
+
+"#,
+        p = p,
+        P = p.to_uppercase()
+    );
+
+    for i in 0..functions {
+        match rng.below(4) {
+            0 => {
+                let _ = write!(
+                    out,
+                    r#"static int {p}_helper{i}(struct {p}_dev *dev, int val)
+{{
+	int ret = 0;
+	unsigned long flags;
+
+	spin_lock_irqsave(&dev->lock, flags);
+	if (val > {v})
+		ret = -EINVAL;
+	else
+		dev->queue[val % {P}_MAX_QUEUE] = readl(dev->regs + {P}_REG(val));
+	spin_unlock_irqrestore(&dev->lock, flags);
+	return ret;
+}}
+
+"#,
+                    p = p,
+                    P = p.to_uppercase(),
+                    i = i,
+                    v = rng.below(1000)
+                );
+            }
+            1 => {
+                let _ = write!(
+                    out,
+                    r#"static long {p}_ioctl{i}(struct {p}_dev *dev, unsigned int cmd, unsigned long arg)
+{{
+	switch (cmd) {{
+	case 0x{a:x}:
+		return {p}_start(dev);
+	case 0x{b:x}:
+		{p}_stop(dev);
+		break;
+	case 0x{c:x}:
+		if (copy_to_user((void __user *)arg, dev->queue, sizeof(dev->queue)))
+			return -EFAULT;
+		break;
+	default:
+		return -ENOTTY;
+	}}
+	return 0;
+}}
+
+"#,
+                    p = p,
+                    i = i,
+                    a = rng.below(0x1000),
+                    b = rng.below(0x1000) + 0x1000,
+                    c = rng.below(0x1000) + 0x2000
+                );
+            }
+            2 => {
+                let _ = write!(
+                    out,
+                    r#"static irqreturn_t {p}_irq{i}(int irq, void *data)
+{{
+	struct {p}_dev *dev = data;
+	u32 status = readl(dev->regs + {P}_REG({r}));
+
+	if (!status)
+		return IRQ_NONE;
+	writel(status, dev->regs + {P}_REG({r}));
+	schedule_work(&dev->reset_work);
+	return IRQ_HANDLED;
+}}
+
+#ifdef CONFIG_{P}_DEBUG
+static void {p}_dump{i}(struct {p}_dev *dev)
+{{
+	dev_dbg(dev->dev, "queue head %u\n", dev->queue[0]);
+}}
+#endif
+
+"#,
+                    p = p,
+                    P = p.to_uppercase(),
+                    i = i,
+                    r = rng.below(32)
+                );
+            }
+            _ => {
+                let _ = write!(
+                    out,
+                    r#"static int {p}_setup{i}(struct {p}_dev *dev)
+{{
+	void *buf;
+	int ret;
+
+	buf = kzalloc({n}, GFP_KERNEL);
+	if (!buf)
+		return -ENOMEM;
+
+	ret = request_irq(dev->irq, {p}_isr, IRQF_SHARED, "{p}", dev);
+	if (ret)
+		goto err_free;
+
+	INIT_WORK(&dev->reset_work, {p}_reset_work);
+	mutex_lock(&dev->io_lock);
+	ret = dev->ops->start(dev);
+	mutex_unlock(&dev->io_lock);
+	if (ret)
+		goto err_irq;
+	return 0;
+
+err_irq:
+	free_irq(dev->irq, dev);
+err_free:
+	kfree(buf);
+	return ret;
+}}
+
+"#,
+                    p = p,
+                    i = i,
+                    n = 64 << rng.below(6)
+                );
+            }
+        }
+    }
+
+    let _ = write!(
+        out,
+        r#"static const struct file_operations {p}_fops = {{
+	.owner = THIS_MODULE,
+	.open = {p}_open,
+	.release = {p}_release,
+	.unlocked_ioctl = {p}_unlocked_ioctl,
+}};
+
+module_platform_driver({p}_driver);
+MODULE_LICENSE("GPL");
+"#,
+        p = p
+    );
+    out
+}
+
+/// The benchmark corpus, as (file name, source)
+fn corpus() -> Vec<(String, String)> {
+    (0..CORPUS_FILES as u64)
+        .map(|seed| (format!("drv{}.c", seed), synthetic_driver(seed, CORPUS_FUNCTIONS)))
+        .collect()
+}
+
+fn bench_single_file(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_file");
+    for functions in [20, 200, 2000] {
+        let source = synthetic_driver(functions as u64, functions);
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(functions), &source, |b, source| {
+            let parser = flowsight_parser::get_parser();
+            b.iter(|| parser.parse(black_box(source), "drv.c").unwrap());
+        });
+    }
+    group.finish();
+}
+
+fn bench_corpus(c: &mut Criterion) {
+    let files = corpus();
+    let bytes: usize = files.iter().map(|(_, source)| source.len()).sum();
+    let mut group = c.benchmark_group("parse_corpus");
+    group.throughput(Throughput::Bytes(bytes as u64));
+    group.bench_function("sequential", |b| {
+        let parser = flowsight_parser::get_parser();
+        b.iter(|| {
+            for (name, source) in &files {
+                black_box(parser.parse(source, name).unwrap());
+            }
+        });
+    });
+    group.finish();
+}
+
+fn bench_parallel_scaling(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let mut paths: Vec<PathBuf> = Vec::new();
+    let mut bytes = 0;
+    for (name, source) in corpus() {
+        let path = dir.path().join(name);
+        std::fs::write(&path, &source).unwrap();
+        bytes += source.len();
+        paths.push(path);
+    }
+
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut group = c.benchmark_group("parallel_parser");
+    group.throughput(Throughput::Bytes(bytes as u64));
+    for threads in [1, 2, 4, 8, 16].into_iter().filter(|&n| n <= cores.max(1)) {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        group.bench_with_input(BenchmarkId::new("threads", threads), &paths, |b, paths| {
+            // A fresh parser each time, so its cache never answers
+            b.iter_batched(
+                ParallelParser::new,
+                |parser| pool.install(|| parser.parse_files(paths)),
+                BatchSize::SmallInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_single_file, bench_corpus, bench_parallel_scaling);
+criterion_main!(benches);
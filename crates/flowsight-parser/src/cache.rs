@@ -1,9 +1,13 @@
-//! LRU Cache for parsed syntax trees
+//! Caches for parsed syntax trees
 //!
-//! Provides memory-efficient caching of parsed trees with automatic eviction.
+//! `ParseCache` is an in-memory LRU cache with automatic eviction.
+//! `PersistentCache` keeps serialized parse results on disk so unchanged
+//! files are not reparsed across runs.
 
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 
 /// Cache entry with metadata
@@ -143,6 +147,109 @@ pub fn hash_content(content: &str) -> u64 {
     hasher.finish()
 }
 
+/// Default on-disk cache location, relative to the indexed root
+pub const DEFAULT_CACHE_DIR: &str = ".flowsight/cache";
+
+/// Identity of one version of a file on disk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheKey {
+    pub path: PathBuf,
+    /// Modification time in seconds since the epoch
+    pub mtime: u64,
+    /// File size in bytes
+    pub size: u64,
+    /// Hash of the file content
+    pub content_hash: u64,
+}
+
+impl CacheKey {
+    /// Build the key for `path` whose current content is `content`
+    pub fn for_file(path: &Path, content: &str) -> Self {
+        let metadata = fs::metadata(path).ok();
+        let mtime = metadata
+            .as_ref()
+            .and_then(|m| m.modified().ok())
+            .map(|t| t.duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs())
+            .unwrap_or(0);
+        Self {
+            path: path.to_path_buf(),
+            mtime,
+            size: metadata.map(|m| m.len()).unwrap_or(content.len() as u64),
+            content_hash: hash_content(content),
+        }
+    }
+}
+
+/// On-disk entry: the key it was stored under plus the result
+#[derive(Serialize, Deserialize)]
+struct PersistentEntry {
+    key: CacheKey,
+    result: crate::ParseResult,
+}
+
+/// Disk-backed cache of parse results, one JSON file per source file
+pub struct PersistentCache {
+    dir: PathBuf,
+}
+
+impl PersistentCache {
+    /// Open (creating if needed) a cache in `dir`
+    pub fn new(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Cache directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get the stored result if it was saved under exactly `key`
+    pub fn get(&self, key: &CacheKey) -> Option<crate::ParseResult> {
+        let data = fs::read(self.entry_path(&key.path)).ok()?;
+        let entry: PersistentEntry = serde_json::from_slice(&data).ok()?;
+        (entry.key == *key).then_some(entry.result)
+    }
+
+    /// Store `result` under `key`, replacing any older version of the file
+    pub fn put(&self, key: &CacheKey, result: &crate::ParseResult) -> flowsight_core::Result<()> {
+        let entry = PersistentEntry {
+            key: key.clone(),
+            result: result.clone(),
+        };
+        let data = serde_json::to_vec(&entry).map_err(|e| flowsight_core::Error::Other(e.to_string()))?;
+
+        // Write then rename so concurrent readers never see a partial entry
+        let path = self.entry_path(&key.path);
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Remove the entry for `path`
+    pub fn invalidate(&self, path: &Path) {
+        let _ = fs::remove_file(self.entry_path(path));
+    }
+
+    /// Remove all entries
+    pub fn clear(&self) -> std::io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn entry_path(&self, path: &Path) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.json", hash_content(&path.to_string_lossy())))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -177,4 +284,38 @@ mod tests {
         let path2 = PathBuf::from("/test/file2.c");
         assert!(cache.get(&path2, 2).is_some());
     }
+
+    #[test]
+    fn test_persistent_cache() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("a.c");
+        fs::write(&file, "int a(void) { return 0; }\n").unwrap();
+
+        let cache = PersistentCache::new(dir.path().join("cache")).unwrap();
+        let key = CacheKey::for_file(&file, "int a(void) { return 0; }\n");
+        assert!(cache.get(&key).is_none());
+
+        let mut result = crate::ParseResult::default();
+        result.errors.push(crate::ParseDiagnostic {
+            file: "a.c".into(),
+            message: "marker".into(),
+            line: 1,
+            column: 1,
+        });
+        cache.put(&key, &result).unwrap();
+
+        // A fresh handle on the same directory sees the entry
+        let reopened = PersistentCache::new(cache.dir()).unwrap();
+        assert_eq!(reopened.get(&key).unwrap().errors, result.errors);
+
+        // Any change to the key is a miss
+        let changed = CacheKey {
+            content_hash: key.content_hash + 1,
+            ..key.clone()
+        };
+        assert!(reopened.get(&changed).is_none());
+
+        reopened.invalidate(&file);
+        assert!(reopened.get(&key).is_none());
+    }
 }
@@ -7,29 +7,146 @@
 //!
 //! - `treesitter` - Fast incremental parsing using tree-sitter
 //! - `preprocessor` - C preprocessor integration using Clang
+//! - `preprocessed` - Already-preprocessed `.i` files, mapped back via line markers
 //! - `ast` - AST types and utilities
-//! - `cache` - LRU cache for parsed syntax trees
-//! - `parallel` - Parallel file parsing using rayon
+//! - `cache` - LRU and on-disk caches for parse results
+//! - `parallel` - Parallel file parsing using rayon (`parallel` feature)
+//! - `rust` - Rust-for-Linux sources (functions and trait impls) using tree-sitter
 
 pub mod ast;
 pub mod cache;
+#[cfg(feature = "parallel")]
 pub mod parallel;
+pub mod preprocessed;
 pub mod preprocessor;
+pub mod rust;
 pub mod treesitter;
 
-use flowsight_core::{FunctionDef, Result, StructDef};
+use flowsight_core::{Error, FunctionDef, Language, Occurrence, Result, StructDef};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+/// A syntax problem found while parsing (tree-sitter ERROR or MISSING node)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ParseDiagnostic {
+    /// File the problem is in, as named to the parser; results of several
+    /// files are merged, so each diagnostic carries its own
+    pub file: String,
+    /// What went wrong, e.g. "missing `;`"
+    pub message: String,
+    /// 1-based line
+    pub line: u32,
+    /// 0-based column
+    pub column: u32,
+}
+
+impl std::fmt::Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message)
+    }
+}
 
 /// Parse result containing extracted information
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ParseResult {
     /// Functions found in the source
     pub functions: HashMap<String, FunctionDef>,
     /// Structs found in the source
     pub structs: HashMap<String, StructDef>,
-    /// Parse errors (non-fatal)
-    pub errors: Vec<String>,
+    /// Syntax diagnostics (non-fatal); results may be partial when non-empty
+    pub errors: Vec<ParseDiagnostic>,
+    /// Byte-precise occurrences of function names (definitions, calls, references)
+    pub occurrences: Vec<Occurrence>,
+    /// Headers named by `#include` directives, as written (e.g. "linux/usb.h")
+    pub includes: Vec<String>,
+    /// Struct typedefs: alias -> aliased type as written (e.g. "struct foo" or another alias)
+    #[serde(default)]
+    pub typedefs: HashMap<String, String>,
+    /// Object-like macros: name -> value as written (e.g. "MAX_BUF" -> "256")
+    #[serde(default)]
+    pub defines: HashMap<String, String>,
+}
+
+impl ParseResult {
+    /// Merge `other` (e.g. another file of the same module) into `self`
+    ///
+    /// A function defined in both keeps `other`'s definition. Afterwards
+    /// `called_by` lists every known function calling it, across files.
+    pub fn merge(&mut self, other: ParseResult) {
+        self.functions.extend(other.functions);
+        self.structs.extend(other.structs);
+        self.errors.extend(other.errors);
+        self.occurrences.extend(other.occurrences);
+        self.typedefs.extend(other.typedefs);
+        self.defines.extend(other.defines);
+        for include in other.includes {
+            if !self.includes.contains(&include) {
+                self.includes.push(include);
+            }
+        }
+
+        let edges: Vec<(String, String)> = self
+            .functions
+            .values()
+            .flat_map(|f| f.calls.iter().map(move |callee| (f.name.clone(), callee.clone())))
+            .collect();
+        for (caller, callee) in edges {
+            if let Some(target) = self.functions.get_mut(&callee) {
+                if !target.called_by.contains(&caller) {
+                    target.called_by.push(caller);
+                }
+            }
+        }
+        self.resolve_typedefs();
+    }
+
+    /// Struct named by typedef `alias`, following alias chains
+    pub fn resolve_typedef(&self, alias: &str) -> Option<&str> {
+        let mut current = self.typedefs.get(alias)?;
+        // Bounded in case of a (malformed) typedef cycle
+        for _ in 0..8 {
+            if let Some(name) = current.strip_prefix("struct ") {
+                return Some(name);
+            }
+            current = self.typedefs.get(current)?;
+        }
+        None
+    }
+
+    /// Rewrite struct fields typed via a typedef to the underlying struct
+    ///
+    /// The typedef name is kept in `StructField::typedef_name` and the struct
+    /// is added to `referenced_structs`. Fields already resolved are skipped.
+    pub fn resolve_typedefs(&mut self) {
+        if self.typedefs.is_empty() {
+            return;
+        }
+        let mut resolved: Vec<(String, usize, String)> = Vec::new();
+        for st in self.structs.values() {
+            for (i, field) in st.fields.iter().enumerate() {
+                if field.typedef_name.is_some() || field.is_function_ptr {
+                    continue;
+                }
+                if let Some(target) = self.resolve_typedef(&field.type_name) {
+                    resolved.push((st.name.clone(), i, target.to_string()));
+                }
+            }
+        }
+
+        for (struct_name, i, target) in resolved {
+            let Some(st) = self.structs.get_mut(&struct_name) else {
+                continue;
+            };
+            let field = &mut st.fields[i];
+            field.typedef_name = Some(std::mem::replace(&mut field.type_name, format!("struct {}", target)));
+            if !st.referenced_structs.contains(&target) {
+                st.referenced_structs.push(target);
+                st.referenced_structs.sort();
+            }
+        }
+    }
 }
 
 /// Parser trait for different backends
@@ -44,6 +161,14 @@ pub trait Parser: Send + Sync {
         self.parse(&source, &filename)
     }
 
+    /// Parse several files, keeping each file's outcome
+    fn parse_many(&self, files: &[PathBuf]) -> Vec<(PathBuf, Result<ParseResult>)> {
+        files
+            .iter()
+            .map(|file| (file.clone(), self.parse_file(file)))
+            .collect()
+    }
+
     /// Get parser name
     fn name(&self) -> &str;
 
@@ -56,6 +181,18 @@ pub fn get_parser() -> Box<dyn Parser> {
     Box::new(treesitter::TreeSitterParser::new())
 }
 
+/// Extensions of the files [`get_parser_for`] has a parser for
+pub const SUPPORTED_EXTENSIONS: &[&str] = &["c", "h", "i", "rs"];
+
+/// Get the parser for `path`'s language
+pub fn get_parser_for(path: &Path) -> Result<Box<dyn Parser>> {
+    match Language::from_path(path) {
+        Some(Language::C) => Ok(get_parser()),
+        Some(Language::Rust) => Ok(Box::new(rust::RustParser::new())),
+        None => Err(Error::UnsupportedLanguage(path.display().to_string())),
+    }
+}
+
 #[cfg(test)]
 mod tests;
 
@@ -68,4 +205,12 @@ mod basic_tests {
         let parser = get_parser();
         assert!(parser.is_available());
     }
+
+    #[test]
+    fn test_supported_extensions() {
+        for ext in SUPPORTED_EXTENSIONS {
+            assert!(get_parser_for(Path::new(&format!("file.{}", ext))).is_ok());
+        }
+        assert!(get_parser_for(Path::new("file.py")).is_err());
+    }
 }
@@ -2,14 +2,20 @@
 //!
 //! Provides efficient multi-file parsing with progress reporting.
 
-use crate::cache::{hash_content, ParseCache};
+use crate::cache::{CacheKey, ParseCache, PersistentCache};
+use crate::preprocessed;
+use crate::preprocessor::HeaderResolver;
+use crate::rust::RustParser;
 use crate::treesitter::TreeSitterParser;
 use crate::ParseResult;
-use flowsight_core::Result;
+use flowsight_core::{Language, Result};
 use rayon::prelude::*;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
 use tracing::{debug, info};
 use walkdir::WalkDir;
 
@@ -32,12 +38,72 @@ pub enum ProgressPhase {
     Parsing,
     Indexing,
     Complete,
+    Cancelled,
+}
+
+/// Timing and size of one parsed file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct FileStats {
+    /// Wall time spent parsing (or loading from cache), in milliseconds
+    pub parse_ms: f64,
+    /// Wall time the caller spent analyzing the result, in milliseconds
+    /// (filled in by indexers; 0 from the parser)
+    pub analyze_ms: f64,
+    /// Source size
+    pub bytes: u64,
+    /// Functions found
+    pub functions: usize,
+}
+
+impl FileStats {
+    /// Parse plus analyze time
+    pub fn total_ms(&self) -> f64 {
+        self.parse_ms + self.analyze_ms
+    }
+}
+
+/// Totals over many files, with the slowest ones
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TimingSummary {
+    pub files: usize,
+    pub bytes: u64,
+    pub functions: usize,
+    pub parse_ms: f64,
+    pub analyze_ms: f64,
+    /// Slowest files by total time, slowest first
+    pub slowest: Vec<(PathBuf, FileStats)>,
+}
+
+impl TimingSummary {
+    /// Sum `stats`, keeping the `slowest` files that took longest
+    pub fn new<'a>(stats: impl IntoIterator<Item = (&'a PathBuf, &'a FileStats)>, slowest: usize) -> Self {
+        let mut summary = Self::default();
+        let mut all = Vec::new();
+        for (path, file) in stats {
+            summary.files += 1;
+            summary.bytes += file.bytes;
+            summary.functions += file.functions;
+            summary.parse_ms += file.parse_ms;
+            summary.analyze_ms += file.analyze_ms;
+            all.push((path.clone(), *file));
+        }
+        all.sort_by(|a, b| b.1.total_ms().total_cmp(&a.1.total_ms()).then_with(|| a.0.cmp(&b.0)));
+        all.truncate(slowest);
+        summary.slowest = all;
+        summary
+    }
 }
 
 /// Parallel parser with caching support
 pub struct ParallelParser {
     cache: Arc<ParseCache>,
+    persistent: Option<Arc<PersistentCache>>,
+    header_resolver: Option<HeaderResolver>,
+    /// Resolved `#include`s per parsed file (only with a header resolver)
+    includes: RwLock<HashMap<PathBuf, Vec<PathBuf>>>,
     progress_callback: Option<Arc<ProgressCallback>>,
+    /// Set from another thread to stop parsing between files
+    cancel: Option<Arc<AtomicBool>>,
 }
 
 impl ParallelParser {
@@ -45,7 +111,11 @@ impl ParallelParser {
     pub fn new() -> Self {
         Self {
             cache: Arc::new(ParseCache::default()),
+            persistent: None,
+            header_resolver: None,
+            includes: RwLock::new(HashMap::new()),
             progress_callback: None,
+            cancel: None,
         }
     }
 
@@ -53,10 +123,26 @@ impl ParallelParser {
     pub fn with_cache_capacity(capacity: usize) -> Self {
         Self {
             cache: Arc::new(ParseCache::new(capacity)),
+            persistent: None,
+            header_resolver: None,
+            includes: RwLock::new(HashMap::new()),
             progress_callback: None,
+            cancel: None,
         }
     }
 
+    /// Check an on-disk cache before reparsing, and store new results in it
+    pub fn with_persistent_cache(mut self, cache: PersistentCache) -> Self {
+        self.persistent = Some(Arc::new(cache));
+        self
+    }
+
+    /// Resolve each file's `#include`s to header paths while parsing
+    pub fn with_header_resolver(mut self, resolver: HeaderResolver) -> Self {
+        self.header_resolver = Some(resolver);
+        self
+    }
+
     /// Set progress callback
     pub fn with_progress<F>(mut self, callback: F) -> Self
     where
@@ -66,8 +152,30 @@ impl ParallelParser {
         self
     }
 
+    /// Stop parsing once `flag` is set
+    ///
+    /// Files already being parsed finish; the rest are skipped and left out
+    /// of the results.
+    pub fn with_cancel_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Whether the cancel flag has been set
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
     /// Parse multiple files in parallel
     pub fn parse_files(&self, paths: &[PathBuf]) -> Vec<(PathBuf, Result<ParseResult>)> {
+        self.parse_each(paths, |path| self.parse_file_cached(path))
+    }
+
+    /// Run `parse` over `paths` in parallel, reporting progress and honouring cancellation
+    fn parse_each<F>(&self, paths: &[PathBuf], parse: F) -> Vec<(PathBuf, Result<ParseResult>)>
+    where
+        F: Fn(&PathBuf) -> Result<ParseResult> + Sync,
+    {
         let total = paths.len();
         let processed = AtomicUsize::new(0);
 
@@ -75,8 +183,11 @@ impl ParallelParser {
 
         let results: Vec<_> = paths
             .par_iter()
-            .map(|path| {
-                let result = self.parse_file_cached(path);
+            .filter_map(|path| {
+                if self.is_cancelled() {
+                    return None;
+                }
+                let result = parse(path);
 
                 let current = processed.fetch_add(1, Ordering::SeqCst) + 1;
                 if current % 10 == 0 || current == total {
@@ -88,12 +199,55 @@ impl ParallelParser {
                     );
                 }
 
-                (path.clone(), result)
+                Some((path.clone(), result))
             })
             .collect();
 
-        self.emit_progress(ProgressPhase::Complete, total, total, "Parsing complete");
+        if self.is_cancelled() {
+            self.emit_progress(ProgressPhase::Cancelled, results.len(), total, "Parsing cancelled");
+        } else {
+            self.emit_progress(ProgressPhase::Complete, total, total, "Parsing complete");
+        }
+        results
+    }
+
+    /// Parse multiple files in parallel, timing each
+    ///
+    /// Files that fail to parse are left out. Times are wall-clock per file,
+    /// so with several threads they add up to more than the elapsed time.
+    pub fn parse_files_timed(&self, paths: &[PathBuf]) -> Vec<(PathBuf, ParseResult, FileStats)> {
+        let timings: RwLock<HashMap<PathBuf, (f64, u64)>> = RwLock::new(HashMap::new());
+        let results = self.parse_each(paths, |path| {
+            let start = Instant::now();
+            let result = self.parse_file_cached(path);
+            let parse_ms = start.elapsed().as_secs_f64() * 1000.0;
+            let bytes = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            if let Ok(mut timings) = timings.write() {
+                timings.insert(path.clone(), (parse_ms, bytes));
+            }
+            result
+        });
+        let timings = timings.into_inner().unwrap_or_default();
+
         results
+            .into_iter()
+            .filter_map(|(path, result)| match result {
+                Ok(result) => {
+                    let (parse_ms, bytes) = timings.get(&path).copied().unwrap_or_default();
+                    let stats = FileStats {
+                        parse_ms,
+                        analyze_ms: 0.0,
+                        bytes,
+                        functions: result.functions.len(),
+                    };
+                    Some((path, result, stats))
+                }
+                Err(e) => {
+                    debug!("Failed to parse {:?}: {}", path, e);
+                    None
+                }
+            })
+            .collect()
     }
 
     /// Parse a directory recursively
@@ -129,34 +283,73 @@ impl ParallelParser {
     /// Parse a single file with caching
     pub fn parse_file_cached(&self, path: &Path) -> Result<ParseResult> {
         let content = std::fs::read_to_string(path)?;
-        let hash = hash_content(&content);
+        let key = CacheKey::for_file(path, &content);
         let path_buf = path.to_path_buf();
 
         // Check cache
-        if let Some(cached) = self.cache.get(&path_buf, hash) {
+        if let Some(cached) = self.cache.get(&path_buf, key.content_hash) {
             debug!("Cache hit for {:?}", path);
+            self.record_includes(path, &cached);
             return Ok((*cached).clone());
         }
+        if let Some(result) = self.persistent.as_ref().and_then(|p| p.get(&key)) {
+            debug!("Persistent cache hit for {:?}", path);
+            self.record_includes(path, &result);
+            self.cache.insert(path_buf, key.content_hash, key.mtime, result.clone());
+            return Ok(result);
+        }
 
         // Parse
         debug!("Parsing {:?}", path);
-        let mut parser = TreeSitterParser::new();
         let filename = path.to_string_lossy();
-        let result = parser.parse_source(&content, &filename)?;
+        let result = if Language::from_path(path) == Some(Language::Rust) {
+            RustParser::new().parse_source(&content, &filename)?
+        } else if preprocessed::is_preprocessed(path) {
+            preprocessed::parse(&content, &filename)?
+        } else {
+            TreeSitterParser::new().parse_source(&content, &filename)?
+        };
 
         // Cache result
-        let mtime = std::fs::metadata(path)
-            .and_then(|m| m.modified())
-            .map(|t| t.duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs())
-            .unwrap_or(0);
-        self.cache.insert(path_buf, hash, mtime, result.clone());
+        if let Some(persistent) = &self.persistent {
+            if let Err(e) = persistent.put(&key, &result) {
+                debug!("Failed to persist {:?}: {}", path, e);
+            }
+        }
+        self.record_includes(path, &result);
+        self.cache.insert(path_buf, key.content_hash, key.mtime, result.clone());
 
         Ok(result)
     }
 
+    /// Headers each parsed file includes, as resolved by the header resolver
+    ///
+    /// Includes that cannot be resolved (e.g. missing system headers) are left out.
+    pub fn include_map(&self) -> HashMap<PathBuf, Vec<PathBuf>> {
+        self.includes.read().map(|m| m.clone()).unwrap_or_default()
+    }
+
+    fn record_includes(&self, path: &Path, result: &ParseResult) {
+        let Some(resolver) = &self.header_resolver else {
+            return;
+        };
+        let headers: Vec<PathBuf> = result
+            .includes
+            .iter()
+            .filter_map(|header| resolver.resolve(header, Some(path)))
+            .map(|header| normalize_path(&header))
+            .collect();
+        if let Ok(mut includes) = self.includes.write() {
+            includes.insert(path.to_path_buf(), headers);
+        }
+    }
+
     /// Invalidate cache for a file
     pub fn invalidate(&self, path: &Path) {
         self.cache.invalidate(&path.to_path_buf());
+        if let Some(persistent) = &self.persistent {
+            persistent.invalidate(path);
+        }
     }
 
     /// Clear all cache
@@ -187,14 +380,28 @@ impl Default for ParallelParser {
     }
 }
 
+/// Lexically drop `.` and fold `..` so `dir/../inc/a.h` matches walked paths
+fn normalize_path(path: &Path) -> PathBuf {
+    use std::path::Component;
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized.components().next_back(), Some(Component::Normal(_))) => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
 /// Merge multiple parse results into one
 pub fn merge_results(results: Vec<ParseResult>) -> ParseResult {
     let mut merged = ParseResult::default();
 
     for result in results {
-        merged.functions.extend(result.functions);
-        merged.structs.extend(result.structs);
-        merged.errors.extend(result.errors);
+        merged.merge(result);
     }
 
     merged
@@ -226,6 +433,52 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cancel_flag() {
+        let dir = TempDir::new().unwrap();
+        let paths: Vec<PathBuf> = (0..5)
+            .map(|i| {
+                let path = dir.path().join(format!("test{}.c", i));
+                std::fs::write(&path, format!("void func{}(void) {{}}", i)).unwrap();
+                path
+            })
+            .collect();
+
+        let flag = Arc::new(AtomicBool::new(false));
+        let phases = Arc::new(RwLock::new(Vec::new()));
+        let seen = phases.clone();
+        let parser = ParallelParser::new()
+            .with_cancel_flag(flag.clone())
+            .with_progress(move |event| seen.write().unwrap().push(event.phase));
+
+        assert_eq!(parser.parse_files(&paths).len(), 5);
+
+        flag.store(true, Ordering::Relaxed);
+        assert!(parser.parse_files(&paths).is_empty());
+        assert_eq!(phases.read().unwrap().last(), Some(&ProgressPhase::Cancelled));
+    }
+
+    #[test]
+    fn test_parse_files_timed() {
+        let dir = TempDir::new().unwrap();
+        let small = dir.path().join("small.c");
+        let big = dir.path().join("big.c");
+        std::fs::write(&small, "void one(void) {}").unwrap();
+        std::fs::write(&big, "void a(void) {}\nvoid b(void) {}\nvoid c(void) {}").unwrap();
+        let missing = dir.path().join("missing.c");
+
+        let timed = ParallelParser::new().parse_files_timed(&[small.clone(), big.clone(), missing]);
+        assert_eq!(timed.len(), 2);
+        let stats: HashMap<&PathBuf, &FileStats> = timed.iter().map(|(p, _, s)| (p, s)).collect();
+        assert_eq!((stats[&small].functions, stats[&small].bytes), (1, 17));
+        assert_eq!(stats[&big].functions, 3);
+        assert!(stats.values().all(|s| s.parse_ms >= 0.0 && s.analyze_ms == 0.0));
+
+        let summary = TimingSummary::new(stats.iter().map(|(p, s)| (*p, *s)), 1);
+        assert_eq!((summary.files, summary.functions), (2, 4));
+        assert_eq!(summary.slowest.len(), 1);
+    }
+
     #[test]
     fn test_cache_hit() {
         let dir = TempDir::new().unwrap();
@@ -244,4 +497,53 @@ mod tests {
         let stats2 = parser.cache_stats();
         assert_eq!(stats2.entries, 1); // Still 1, cache hit
     }
+
+    #[test]
+    fn test_persistent_cache_across_runs() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.c");
+        std::fs::write(&path, "void test(void) {}").unwrap();
+        let cache_dir = dir.path().join("cache");
+
+        let first = ParallelParser::new().with_persistent_cache(PersistentCache::new(&cache_dir).unwrap());
+        let result = first.parse_file_cached(&path).unwrap();
+        assert!(result.functions.contains_key("test"));
+
+        // Tag the stored entry so a second run proves it came from disk
+        let cache = PersistentCache::new(&cache_dir).unwrap();
+        let key = CacheKey::for_file(&path, "void test(void) {}");
+        let mut tagged = cache.get(&key).unwrap();
+        tagged.functions.clear();
+        cache.put(&key, &tagged).unwrap();
+
+        let second = ParallelParser::new().with_persistent_cache(PersistentCache::new(&cache_dir).unwrap());
+        assert!(second.parse_file_cached(&path).unwrap().functions.is_empty());
+
+        // Changing the file invalidates the entry
+        std::fs::write(&path, "void test(void) {}\nvoid other(void) {}").unwrap();
+        let third = ParallelParser::new().with_persistent_cache(PersistentCache::new(&cache_dir).unwrap());
+        assert!(third.parse_file_cached(&path).unwrap().functions.contains_key("other"));
+    }
+
+    #[test]
+    fn test_include_resolution() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("include")).unwrap();
+        std::fs::create_dir_all(dir.path().join("drivers")).unwrap();
+        std::fs::write(dir.path().join("include/my_dev.h"), "struct my_dev { int irq; };\n").unwrap();
+        let source = dir.path().join("drivers/drv.c");
+        std::fs::write(
+            &source,
+            "#include <linux/module.h>\n#include \"../include/my_dev.h\"\nint probe(struct my_dev *dev) { return 0; }\n",
+        )
+        .unwrap();
+
+        let parser = ParallelParser::new().with_header_resolver(HeaderResolver::new(dir.path().to_path_buf()));
+        let result = parser.parse_file_cached(&source).unwrap();
+        assert_eq!(result.includes, vec!["linux/module.h", "../include/my_dev.h"]);
+
+        // The system header is not in the tree, so only the local one resolves
+        let includes = parser.include_map();
+        assert_eq!(includes[&source], vec![dir.path().join("include/my_dev.h")]);
+    }
 }
@@ -0,0 +1,95 @@
+//! Already-preprocessed C (`gcc -E` output, `.i` files)
+//!
+//! The build system has expanded every macro and resolved every `#if`, so
+//! the code parses as written. Its `# 42 "drivers/foo.c"` line markers map
+//! each symbol back to the file and line it was written in.
+
+use std::path::Path;
+
+use flowsight_core::Result;
+
+use crate::preprocessor::clang::{line_marker, map_lines};
+use crate::preprocessor::PreprocessResult;
+use crate::treesitter::TreeSitterParser;
+use crate::ParseResult;
+
+/// Whether `path` holds preprocessed C
+pub fn is_preprocessed(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "i")
+}
+
+/// Parse preprocessed `source`, locating symbols in the original sources
+///
+/// Occurrences keep their byte spans in `source` and so stay in `filename`.
+pub fn parse(source: &str, filename: &str) -> Result<ParseResult> {
+    // Line markers aren't C; blank them so line numbers stay put
+    let code = source
+        .lines()
+        .map(|line| if line_marker(line).is_some() { "" } else { line })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut result = TreeSitterParser::new().parse_source(&code, filename)?;
+
+    let preprocessed = PreprocessResult {
+        code,
+        included_files: Vec::new(),
+        warnings: Vec::new(),
+        line_origins: map_lines(source, None),
+        expansions: Vec::new(),
+    };
+    preprocessed.relocate(&mut result);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_preprocessed() {
+        let source = r#"# 1 "drivers/foo.c"
+# 1 "<built-in>" 1
+# 1 "<command-line>" 1
+# 1 "./include/linux/kernel.h" 1 3 4
+static inline int helper(int x)
+{
+ return x + 1;
+}
+# 2 "drivers/foo.c" 2
+
+struct foo_dev {
+ int irq;
+};
+
+static int foo_probe(int dev)
+{
+ int ret = helper(dev);
+ if (ret)
+  goto err;
+ return 0;
+err:
+ return ret;
+}
+"#;
+        let result = parse(source, "foo.i").unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        let helper = result.functions["helper"].location.as_ref().unwrap();
+        assert_eq!((helper.file.as_str(), helper.line, helper.end_line), ("./include/linux/kernel.h", 1, 4));
+
+        let probe = &result.functions["foo_probe"];
+        let loc = probe.location.as_ref().unwrap();
+        assert_eq!((loc.file.as_str(), loc.line, loc.end_line), ("drivers/foo.c", 7, 15));
+        assert_eq!(probe.call_sites[0].line, 9);
+        assert_eq!((probe.labels[0].line, probe.labels[0].gotos.as_slice()), (13, &[11][..]));
+
+        let st = result.structs["foo_dev"].location.as_ref().unwrap();
+        assert_eq!((st.file.as_str(), st.line), ("drivers/foo.c", 3));
+    }
+
+    #[test]
+    fn test_is_preprocessed() {
+        assert!(is_preprocessed(Path::new("drivers/foo.i")));
+        assert!(!is_preprocessed(Path::new("drivers/foo.c")));
+    }
+}
@@ -3,12 +3,19 @@
 //! Wraps the Clang preprocessor for accurate C code preprocessing,
 //! handling macros, conditional compilation, and header file inclusion.
 
+use std::collections::HashMap;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use tracing::{debug, warn};
 
 use super::config::{Architecture, MacroDefinition};
+use crate::ParseResult;
+use flowsight_core::Location;
+use regex::Regex;
 
 /// Errors that can occur during preprocessing
 #[derive(Debug, Error)]
@@ -24,6 +31,9 @@ pub enum PreprocessError {
 
     #[error("Invalid source file: {0}")]
     InvalidSource(String),
+
+    #[error("Preprocessing timed out after {0:?}")]
+    Timeout(Duration),
 }
 
 /// Options for preprocessing
@@ -43,6 +53,10 @@ pub struct PreprocessOptions {
     pub keep_comments: bool,
     /// Generate line markers
     pub line_markers: bool,
+    /// Kill clang if it runs longer than this (`None` waits forever)
+    pub timeout: Option<Duration>,
+    /// Cap clang's virtual memory, in MiB (Unix only, applied via `ulimit -v`)
+    pub memory_limit_mb: Option<u64>,
 }
 
 impl Default for PreprocessOptions {
@@ -55,6 +69,8 @@ impl Default for PreprocessOptions {
             extra_args: Vec::new(),
             keep_comments: false,
             line_markers: true,
+            timeout: Some(Duration::from_secs(30)),
+            memory_limit_mb: None,
         }
     }
 }
@@ -91,6 +107,7 @@ impl PreprocessOptions {
             ],
             keep_comments: false,
             line_markers: true,
+            ..Default::default()
         }
     }
 }
@@ -104,6 +121,105 @@ pub struct PreprocessResult {
     pub included_files: Vec<PathBuf>,
     /// Warnings generated during preprocessing
     pub warnings: Vec<String>,
+    /// Origin of each line of `code` (0-based index); `None` for line
+    /// markers, or everywhere when they are turned off
+    pub line_origins: Vec<Option<LineOrigin>>,
+    /// Macro invocations in the main source file that were expanded
+    pub expansions: Vec<MacroExpansion>,
+}
+
+/// Source line a line of preprocessed output came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineOrigin {
+    pub file: String,
+    /// 1-based
+    pub line: u32,
+}
+
+/// A macro invocation in the main source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MacroExpansion {
+    /// Macro name (e.g. "DEFINE_SIMPLE_ATTRIBUTE")
+    pub name: String,
+    /// Line of the invocation (1-based)
+    pub line: u32,
+    /// The invocation line as written
+    pub source: String,
+}
+
+impl PreprocessResult {
+    /// Where 1-based `line` of the preprocessed code came from
+    pub fn origin(&self, line: u32) -> Option<&LineOrigin> {
+        self.line_origins.get((line as usize).checked_sub(1)?)?.as_ref()
+    }
+
+    /// Move the locations of symbols parsed from [`Self::code`] back to the
+    /// original source
+    ///
+    /// A symbol whose original line is a macro invocation not naming it was
+    /// generated by that macro, and its location records the macro in
+    /// `expanded_from`.
+    pub fn relocate(&self, result: &mut ParseResult) {
+        let main_file = self.line_origins.iter().flatten().next().map(|o| o.file.clone());
+        for func in result.functions.values_mut() {
+            if let Some(loc) = &mut func.location {
+                self.relocate_location(loc, &func.name, main_file.as_deref());
+            }
+            for site in &mut func.call_sites {
+                if let Some(origin) = self.origin(site.line) {
+                    site.line = origin.line;
+                }
+            }
+            let label_lines = func
+                .labels
+                .iter_mut()
+                .flat_map(|label| std::iter::once(&mut label.line).chain(&mut label.gotos));
+            let switch_lines = func.switches.iter_mut().flat_map(|switch| {
+                [&mut switch.line, &mut switch.end_line].into_iter().chain(
+                    switch
+                        .cases
+                        .iter_mut()
+                        .flat_map(|case| [&mut case.line, &mut case.end_line]),
+                )
+            });
+            for line in label_lines.chain(switch_lines) {
+                if let Some(origin) = self.origin(*line) {
+                    *line = origin.line;
+                }
+            }
+        }
+        for st in result.structs.values_mut() {
+            if let Some(loc) = &mut st.location {
+                self.relocate_location(loc, &st.name, main_file.as_deref());
+            }
+        }
+        for diagnostic in &mut result.errors {
+            if let Some(origin) = self.origin(diagnostic.line) {
+                diagnostic.file = origin.file.clone();
+                diagnostic.line = origin.line;
+            }
+        }
+    }
+
+    fn relocate_location(&self, loc: &mut Location, name: &str, main_file: Option<&str>) {
+        let Some(start) = self.origin(loc.line).cloned() else {
+            return;
+        };
+        let end = self.origin(loc.end_line).filter(|o| o.file == start.file);
+        loc.end_line = end.map_or(start.line, |o| o.line.max(start.line));
+        loc.line = start.line;
+        loc.file = start.file.clone();
+
+        if main_file != Some(start.file.as_str()) {
+            return;
+        }
+        loc.expanded_from = self
+            .expansions
+            .iter()
+            .find(|e| e.line == start.line)
+            .filter(|e| !has_word(&e.source, name))
+            .map(|e| e.name.clone());
+    }
 }
 
 /// Clang preprocessor wrapper
@@ -184,12 +300,14 @@ impl ClangPreprocessor {
         let args = self.build_args(options);
         debug!("Preprocessing {:?} with args: {:?}", source_path, args);
 
-        let output = Command::new(&self.clang_path)
-            .args(&args)
+        let mut cmd = self.command(options);
+        cmd.args(&args)
             .arg(source_path)
+            .stdin(Stdio::null())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()?;
+            .stderr(Stdio::piped());
+
+        let output = wait_with_deadline(cmd.spawn()?, None, options.timeout)?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -199,11 +317,16 @@ impl ClangPreprocessor {
         let code = String::from_utf8_lossy(&output.stdout).to_string();
         let warnings = self.parse_warnings(&output.stderr);
         let included_files = self.extract_included_files(&code);
+        let line_origins = map_lines(&code, None);
+        let source = std::fs::read_to_string(source_path).unwrap_or_default();
+        let expansions = find_expansions(&source, &code, &line_origins);
 
         Ok(PreprocessResult {
             code,
             included_files,
             warnings,
+            line_origins,
+            expansions,
         })
     }
 
@@ -216,7 +339,7 @@ impl ClangPreprocessor {
     ) -> Result<PreprocessResult, PreprocessError> {
         let args = self.build_args(options);
 
-        let mut cmd = Command::new(&self.clang_path);
+        let mut cmd = self.command(options);
         cmd.args(&args)
             .arg("-x").arg("c")  // Treat input as C
             .arg("-")           // Read from stdin
@@ -227,15 +350,7 @@ impl ClangPreprocessor {
         // Set filename for error messages
         cmd.arg(format!("-ffile-prefix-map=-={}", filename));
 
-        let mut child = cmd.spawn()?;
-
-        // Write source to stdin
-        if let Some(mut stdin) = child.stdin.take() {
-            use std::io::Write;
-            stdin.write_all(source.as_bytes())?;
-        }
-
-        let output = child.wait_with_output()?;
+        let output = wait_with_deadline(cmd.spawn()?, Some(source), options.timeout)?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -245,14 +360,32 @@ impl ClangPreprocessor {
         let code = String::from_utf8_lossy(&output.stdout).to_string();
         let warnings = self.parse_warnings(&output.stderr);
         let included_files = self.extract_included_files(&code);
+        let line_origins = map_lines(&code, Some(filename));
+        let expansions = find_expansions(source, &code, &line_origins);
 
         Ok(PreprocessResult {
             code,
             included_files,
             warnings,
+            line_origins,
+            expansions,
         })
     }
 
+    /// Base command for clang, wrapped in a shell when a memory limit is set
+    fn command(&self, options: &PreprocessOptions) -> Command {
+        match options.memory_limit_mb {
+            Some(limit) if cfg!(unix) => {
+                let mut cmd = Command::new("sh");
+                cmd.arg("-c")
+                    .arg(format!("ulimit -v {} && exec \"$0\" \"$@\"", limit * 1024))
+                    .arg(&self.clang_path);
+                cmd
+            }
+            _ => Command::new(&self.clang_path),
+        }
+    }
+
     /// Build clang command line arguments
     fn build_args(&self, options: &PreprocessOptions) -> Vec<String> {
         let mut args = vec![
@@ -324,6 +457,147 @@ impl ClangPreprocessor {
     }
 }
 
+/// Origin of each line of preprocessed `code`, from its `# N "file"` line markers
+///
+/// The first marker names the main file; it is renamed to `main_file` if
+/// given (clang calls stdin "<stdin>").
+pub(crate) fn map_lines(code: &str, main_file: Option<&str>) -> Vec<Option<LineOrigin>> {
+    let mut origins = Vec::new();
+    let mut main: Option<String> = None;
+    let mut current: Option<LineOrigin> = None;
+    for line in code.lines() {
+        if let Some((number, file)) = line_marker(line) {
+            let main = main.get_or_insert_with(|| file.to_string());
+            let file = match main_file {
+                Some(name) if file == main.as_str() => name.to_string(),
+                _ => file.to_string(),
+            };
+            current = Some(LineOrigin { file, line: number });
+            origins.push(None);
+            continue;
+        }
+        origins.push(current.clone());
+        if let Some(origin) = &mut current {
+            origin.line += 1;
+        }
+    }
+    origins
+}
+
+/// Line number and file of a `# 42 "file.h" 1` (or `#line 42 "file.h"`) line marker
+pub(crate) fn line_marker(line: &str) -> Option<(u32, &str)> {
+    let rest = line.strip_prefix("# ").or_else(|| line.strip_prefix("#line "))?;
+    let (number, rest) = rest.split_once(' ')?;
+    let number = number.parse().ok()?;
+    let file = rest.strip_prefix('"')?;
+    Some((number, &file[..file.find('"')?]))
+}
+
+/// Macro invocations in the main file `source`
+///
+/// A name called on a line of `source` is a macro when the code that line
+/// was preprocessed into no longer mentions it.
+fn find_expansions(source: &str, code: &str, origins: &[Option<LineOrigin>]) -> Vec<MacroExpansion> {
+    let Some(main_file) = origins.iter().flatten().next().map(|o| o.file.as_str()) else {
+        return Vec::new();
+    };
+    let mut output: HashMap<u32, String> = HashMap::new();
+    for (text, origin) in code.lines().zip(origins) {
+        if let Some(origin) = origin.as_ref().filter(|o| o.file == main_file) {
+            let out = output.entry(origin.line).or_default();
+            out.push_str(text);
+            out.push(' ');
+        }
+    }
+
+    let call = Regex::new(r"\b([A-Za-z_]\w*)\s*\(").unwrap();
+    let mut expansions = Vec::new();
+    for (i, text) in source.lines().enumerate() {
+        let line = i as u32 + 1;
+        let Some(out) = output.get(&line).filter(|out| !out.trim().is_empty()) else {
+            continue;
+        };
+        let code = text.trim_start();
+        if code.starts_with('#') || code.starts_with("//") || code.starts_with('*') || code.starts_with("/*") {
+            continue;
+        }
+        for caps in call.captures_iter(text) {
+            let name = &caps[1];
+            if !has_word(out, name) && !expansions.iter().any(|e: &MacroExpansion| e.line == line && e.name == name) {
+                expansions.push(MacroExpansion {
+                    name: name.to_string(),
+                    line,
+                    source: text.to_string(),
+                });
+            }
+        }
+    }
+    expansions
+}
+
+/// Whether `text` has `word` as a whole identifier
+fn has_word(text: &str, word: &str) -> bool {
+    text.match_indices(word).any(|(i, _)| {
+        let ident = |c: char| c.is_alphanumeric() || c == '_';
+        !text[..i].ends_with(ident) && !text[i + word.len()..].starts_with(ident)
+    })
+}
+
+/// Feed `input` to the child and collect its output, killing it at the deadline
+///
+/// stdout/stderr are drained on helper threads so a chatty child can't
+/// block on a full pipe while we wait.
+fn wait_with_deadline(
+    mut child: Child,
+    input: Option<&str>,
+    timeout: Option<Duration>,
+) -> Result<Output, PreprocessError> {
+    let stdin = child.stdin.take();
+    let input = input.map(|s| s.to_string());
+    let writer = thread::spawn(move || {
+        if let (Some(mut stdin), Some(input)) = (stdin, input) {
+            // A child killed on timeout closes the pipe; that's not an error here
+            let _ = stdin.write_all(input.as_bytes());
+        }
+    });
+    let stdout = child.stdout.take().map(spawn_reader);
+    let stderr = child.stderr.take().map(spawn_reader);
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if let Some(limit) = timeout {
+            if start.elapsed() >= limit {
+                warn!("clang exceeded {:?}, killing it", limit);
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(PreprocessError::Timeout(limit));
+            }
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    let _ = writer.join();
+    let join = |reader: Option<thread::JoinHandle<Vec<u8>>>| {
+        reader.and_then(|r| r.join().ok()).unwrap_or_default()
+    };
+    Ok(Output {
+        status,
+        stdout: join(stdout),
+        stderr: join(stderr),
+    })
+}
+
+fn spawn_reader(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        buf
+    })
+}
+
 impl Default for ClangPreprocessor {
     fn default() -> Self {
         Self::new().unwrap_or_else(|_| {
@@ -369,6 +643,54 @@ mod tests {
         assert!(files.contains(&PathBuf::from("/usr/include/bits/types.h")));
     }
 
+    #[test]
+    fn test_macro_expansion_provenance() {
+        let source = "#define DEFINE_SHOW(name) \\
+static int name##_show(int x) { return x; }
+
+DEFINE_SHOW(foo);
+
+static int my_probe(int dev)
+{
+\treturn foo_show(dev);
+}
+";
+        let code = r#"# 1 "<stdin>"
+# 1 "<built-in>" 1
+# 1 "<stdin>" 2
+
+
+
+static int foo_show(int x) { return x; };
+
+static int my_probe(int dev)
+{
+ return foo_show(dev);
+}
+"#;
+        let line_origins = map_lines(code, Some("m.c"));
+        let expansions = find_expansions(source, code, &line_origins);
+        assert_eq!(expansions.iter().map(|e| (e.name.as_str(), e.line)).collect::<Vec<_>>(), vec![("DEFINE_SHOW", 4)]);
+
+        let result = PreprocessResult {
+            code: code.to_string(),
+            included_files: Vec::new(),
+            warnings: Vec::new(),
+            line_origins,
+            expansions,
+        };
+        let mut parsed = crate::treesitter::TreeSitterParser::new().parse_source(code, "m.c").unwrap();
+        result.relocate(&mut parsed);
+
+        let show = parsed.functions["foo_show"].location.as_ref().unwrap();
+        assert_eq!((show.file.as_str(), show.line), ("m.c", 4));
+        assert_eq!(show.expanded_from.as_deref(), Some("DEFINE_SHOW"));
+
+        let probe = parsed.functions["my_probe"].location.as_ref().unwrap();
+        assert_eq!((probe.line, probe.end_line), (6, 9));
+        assert_eq!(probe.expanded_from, None);
+    }
+
     #[test]
     fn test_kernel_options() {
         let kernel_root = PathBuf::from("/usr/src/linux");
@@ -377,5 +699,30 @@ mod tests {
         assert!(options.defines.iter().any(|m| m.name == "__KERNEL__"));
         assert!(options.defines.iter().any(|m| m.name == "__x86_64__"));
         assert!(options.includes.iter().any(|p| p.ends_with("include")));
+        assert!(options.timeout.is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_preprocess_timeout() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // A fake clang that never finishes
+        let dir = tempfile::tempdir().unwrap();
+        let fake = dir.path().join("clang");
+        std::fs::write(&fake, "#!/bin/sh\nexec sleep 10\n").unwrap();
+        std::fs::set_permissions(&fake, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let preprocessor = ClangPreprocessor::with_path(fake);
+        let options = PreprocessOptions {
+            timeout: Some(Duration::from_millis(200)),
+            memory_limit_mb: Some(512),
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        let result = preprocessor.preprocess_string("int x;", "test.c", &options);
+        assert!(matches!(result, Err(PreprocessError::Timeout(_))));
+        assert!(start.elapsed() < Duration::from_secs(5));
     }
 }
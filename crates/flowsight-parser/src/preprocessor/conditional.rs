@@ -0,0 +1,361 @@
+//! Conditional Compilation
+//!
+//! Lightweight `#if`/`#ifdef` resolution without invoking clang. Inactive
+//! branches are blanked out line by line, so locations in the result still
+//! match the original source.
+
+use std::collections::HashMap;
+
+/// State of one `#if ... #endif` group
+struct Frame {
+    /// Whether the enclosing group is active
+    parent_active: bool,
+    /// Whether some branch of this group has already been taken
+    taken: bool,
+    /// Whether the current branch is active
+    active: bool,
+}
+
+/// Keep only the branches selected by `defines`; everything else becomes blank lines
+///
+/// Macros absent from `defines` are treated as undefined. Preprocessor
+/// directive lines themselves are blanked as well.
+pub fn resolve_conditionals(source: &str, defines: &HashMap<String, String>) -> String {
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut out = String::with_capacity(source.len());
+    let mut continued = false;
+
+    for line in source.split_inclusive('\n') {
+        let newline = if line.ends_with('\n') { "\n" } else { "" };
+        let active = stack.last().map(|f| f.active).unwrap_or(true);
+
+        // Skip continuation lines of a multi-line directive
+        if continued {
+            continued = line.trim_end().ends_with('\\');
+            out.push_str(newline);
+            continue;
+        }
+
+        let trimmed = line.trim_start();
+        let Some(directive) = trimmed.strip_prefix('#') else {
+            out.push_str(if active { line } else { newline });
+            continue;
+        };
+        continued = line.trim_end().ends_with('\\');
+
+        let directive = directive.trim_start();
+        let keyword_len = directive
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(directive.len());
+        let (keyword, rest) = directive.split_at(keyword_len);
+        let rest = strip_comment(rest.trim_end().trim_end_matches('\\'));
+
+        match keyword {
+            "ifdef" | "ifndef" | "if" => {
+                let cond = match keyword {
+                    "ifdef" => defines.contains_key(rest.trim()),
+                    "ifndef" => !defines.contains_key(rest.trim()),
+                    _ => eval_condition(rest, defines),
+                };
+                stack.push(Frame {
+                    parent_active: active,
+                    taken: cond,
+                    active: active && cond,
+                });
+            }
+            "elif" | "elifdef" | "elifndef" => {
+                if let Some(frame) = stack.last_mut() {
+                    let cond = !frame.taken
+                        && match keyword {
+                            "elifdef" => defines.contains_key(rest.trim()),
+                            "elifndef" => !defines.contains_key(rest.trim()),
+                            _ => eval_condition(rest, defines),
+                        };
+                    frame.taken |= cond;
+                    frame.active = frame.parent_active && cond;
+                }
+            }
+            "else" => {
+                if let Some(frame) = stack.last_mut() {
+                    frame.active = frame.parent_active && !frame.taken;
+                    frame.taken = true;
+                }
+            }
+            "endif" => {
+                stack.pop();
+            }
+            _ => {}
+        }
+
+        out.push_str(newline);
+    }
+
+    out
+}
+
+fn strip_comment(s: &str) -> &str {
+    let end = [s.find("//"), s.find("/*")]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap_or(s.len());
+    &s[..end]
+}
+
+/// Evaluate an `#if` expression; unknown macros evaluate to 0
+pub fn eval_condition(expr: &str, defines: &HashMap<String, String>) -> bool {
+    let tokens = tokenize(expr);
+    let mut parser = ExprParser {
+        tokens: &tokens,
+        pos: 0,
+        defines,
+    };
+    parser.parse_or() != 0
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(i64),
+    Op(&'static str),
+}
+
+fn tokenize(expr: &str) -> Vec<Token> {
+    const OPS: [&str; 14] = [
+        "&&", "||", "==", "!=", "<=", ">=", "!", "<", ">", "(", ")", "+", "-", ",",
+    ];
+
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim_start();
+    while !rest.is_empty() {
+        let c = rest.chars().next().unwrap_or(' ');
+        if c.is_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            tokens.push(Token::Number(parse_number(&rest[..end]).unwrap_or(0)));
+            rest = &rest[end..];
+        } else if let Some(op) = OPS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            rest = &rest[c.len_utf8()..];
+        }
+        rest = rest.trim_start();
+    }
+    tokens
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let text = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Recursive-descent evaluator over `||`, `&&`, comparisons, `+`/`-` and unary `!`
+struct ExprParser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    defines: &'a HashMap<String, String>,
+}
+
+impl ExprParser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Op(o)) if *o == op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_or(&mut self) -> i64 {
+        let mut value = self.parse_and();
+        while self.eat("||") {
+            let rhs = self.parse_and();
+            value = ((value != 0) || (rhs != 0)) as i64;
+        }
+        value
+    }
+
+    fn parse_and(&mut self) -> i64 {
+        let mut value = self.parse_compare();
+        while self.eat("&&") {
+            let rhs = self.parse_compare();
+            value = ((value != 0) && (rhs != 0)) as i64;
+        }
+        value
+    }
+
+    fn parse_compare(&mut self) -> i64 {
+        let mut value = self.parse_additive();
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op(op @ ("==" | "!=" | "<" | ">" | "<=" | ">="))) => *op,
+                _ => return value,
+            };
+            self.pos += 1;
+            let rhs = self.parse_additive();
+            value = match op {
+                "==" => value == rhs,
+                "!=" => value != rhs,
+                "<" => value < rhs,
+                ">" => value > rhs,
+                "<=" => value <= rhs,
+                _ => value >= rhs,
+            } as i64;
+        }
+    }
+
+    fn parse_additive(&mut self) -> i64 {
+        let mut value = self.parse_unary();
+        loop {
+            if self.eat("+") {
+                value = value.wrapping_add(self.parse_unary());
+            } else if self.eat("-") {
+                value = value.wrapping_sub(self.parse_unary());
+            } else {
+                return value;
+            }
+        }
+    }
+
+    fn parse_unary(&mut self) -> i64 {
+        if self.eat("!") {
+            return (self.parse_unary() == 0) as i64;
+        }
+        if self.eat("-") {
+            return self.parse_unary().wrapping_neg();
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> i64 {
+        match self.peek().cloned() {
+            Some(Token::Number(n)) => {
+                self.pos += 1;
+                n
+            }
+            Some(Token::Op("(")) => {
+                self.pos += 1;
+                let value = self.parse_or();
+                self.eat(")");
+                value
+            }
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                match name.as_str() {
+                    "defined" => {
+                        let paren = self.eat("(");
+                        let defined = match self.peek() {
+                            Some(Token::Ident(m)) => self.defines.contains_key(m),
+                            _ => false,
+                        };
+                        self.pos += 1;
+                        if paren {
+                            self.eat(")");
+                        }
+                        defined as i64
+                    }
+                    // Kernel helpers from <linux/kconfig.h>
+                    "IS_ENABLED" | "IS_BUILTIN" | "IS_REACHABLE" | "IS_MODULE" => {
+                        let args = self.call_args();
+                        args.first()
+                            .map(|m| self.macro_value(m) != 0)
+                            .unwrap_or(false) as i64
+                    }
+                    _ if self.peek() == Some(&Token::Op("(")) => {
+                        // Unknown function-like macro
+                        self.call_args();
+                        0
+                    }
+                    _ => self.macro_value(&name),
+                }
+            }
+            _ => {
+                self.pos += 1;
+                0
+            }
+        }
+    }
+
+    /// Consume a parenthesized argument list, returning identifier arguments
+    fn call_args(&mut self) -> Vec<String> {
+        let mut args = Vec::new();
+        if !self.eat("(") {
+            return args;
+        }
+        let mut depth = 1;
+        while let Some(token) = self.peek().cloned() {
+            self.pos += 1;
+            match token {
+                Token::Op("(") => depth += 1,
+                Token::Op(")") => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                Token::Ident(name) if depth == 1 => args.push(name),
+                _ => {}
+            }
+        }
+        args
+    }
+
+    fn macro_value(&self, name: &str) -> i64 {
+        match self.defines.get(name) {
+            Some(value) if value.trim().is_empty() => 1,
+            Some(value) => parse_number(value.trim()).unwrap_or(1),
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defines(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_ifdef_branches() {
+        let source = "a\n#ifdef CONFIG_PM\nb\n#else\nc\n#endif\nd\n";
+
+        let on = resolve_conditionals(source, &defines(&[("CONFIG_PM", "1")]));
+        assert_eq!(on, "a\n\nb\n\n\n\nd\n");
+
+        let off = resolve_conditionals(source, &HashMap::new());
+        assert_eq!(off, "a\n\n\n\nc\n\nd\n");
+    }
+
+    #[test]
+    fn test_if_expressions() {
+        let d = defines(&[("CONFIG_PM", "1"), ("CONFIG_HZ", "250")]);
+        assert!(eval_condition("IS_ENABLED(CONFIG_PM)", &d));
+        assert!(eval_condition("defined(CONFIG_PM) && !defined CONFIG_DEBUG", &d));
+        assert!(eval_condition("CONFIG_HZ >= 100 || 0", &d));
+        assert!(!eval_condition("IS_ENABLED(CONFIG_DEBUG)", &d));
+        assert!(!eval_condition("0", &d));
+
+        let source = "#if 0\nx\n#elif IS_ENABLED(CONFIG_PM)\ny\n#else\nz\n#endif\n";
+        assert_eq!(resolve_conditionals(source, &d), "\n\n\ny\n\n\n\n");
+    }
+}
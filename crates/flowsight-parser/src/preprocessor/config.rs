@@ -47,6 +47,41 @@ impl Architecture {
         }
     }
 
+    /// Size of a pointer (and of `long`) in bytes
+    pub fn pointer_size(&self) -> usize {
+        match self {
+            Architecture::X86_64 | Architecture::Arm64 | Architecture::Riscv64 | Architecture::PowerPC => 8,
+            Architecture::I386 | Architecture::Arm | Architecture::Riscv32 | Architecture::Mips => 4,
+        }
+    }
+
+    /// Sizes of the C and kernel scalar types, for `StructDef::compute_layout`
+    pub fn type_sizes(&self) -> HashMap<String, usize> {
+        let word = self.pointer_size();
+        let fixed = [
+            ("char", 1), ("_Bool", 1), ("bool", 1), ("short", 2), ("int", 4), ("long long", 8),
+            ("float", 4), ("double", 8),
+            ("u8", 1), ("s8", 1), ("__u8", 1), ("__s8", 1), ("uint8_t", 1), ("int8_t", 1),
+            ("u16", 2), ("s16", 2), ("__u16", 2), ("__s16", 2), ("uint16_t", 2), ("int16_t", 2),
+            ("__le16", 2), ("__be16", 2),
+            ("u32", 4), ("s32", 4), ("__u32", 4), ("__s32", 4), ("uint32_t", 4), ("int32_t", 4),
+            ("__le32", 4), ("__be32", 4), ("atomic_t", 4),
+            ("u64", 8), ("s64", 8), ("__u64", 8), ("__s64", 8), ("uint64_t", 8), ("int64_t", 8),
+            ("__le64", 8), ("__be64", 8),
+        ];
+        let word_sized = [
+            "long", "size_t", "ssize_t", "uintptr_t", "ptrdiff_t", "phys_addr_t", "dma_addr_t",
+        ];
+
+        let mut sizes: HashMap<String, usize> = fixed.iter().map(|&(t, n)| (t.to_string(), n)).collect();
+        sizes.extend(word_sized.iter().map(|t| (t.to_string(), word)));
+        sizes.insert(flowsight_core::POINTER_SIZE_KEY.into(), word);
+        sizes.insert("short int".into(), 2);
+        sizes.insert("long int".into(), word);
+        sizes.insert("long long int".into(), 8);
+        sizes
+    }
+
     /// Get architecture-specific predefined macros
     pub fn predefined_macros(&self) -> Vec<MacroDefinition> {
         match self {
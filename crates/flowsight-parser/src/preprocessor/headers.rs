@@ -2,6 +2,8 @@
 //!
 //! Resolves header file paths for Linux kernel and other C projects.
 
+use std::collections::HashSet;
+use std::fmt::Write;
 use std::path::{Path, PathBuf};
 use tracing::debug;
 
@@ -48,6 +50,17 @@ impl HeaderResolver {
         }
     }
 
+    /// Resolver for an indexed project: kernel include layout for kernel
+    /// trees, otherwise the root plus its `include/` directory
+    pub fn for_project(root: &Path) -> Self {
+        let mut resolver = Self::new(root.to_path_buf());
+        if resolver.is_kernel_source() {
+            return Self::for_kernel(root, Architecture::X86_64);
+        }
+        resolver.add_include_path(root.join("include"));
+        resolver
+    }
+
     /// Add an include path
     pub fn add_include_path(&mut self, path: PathBuf) {
         if !self.include_paths.contains(&path) {
@@ -105,6 +118,53 @@ impl HeaderResolver {
         None
     }
 
+    /// `main_file` with its local (`"..."`) includes inlined, recursively,
+    /// as one translation unit
+    ///
+    /// System (`<...>`) includes and headers that don't resolve are left as
+    /// written, and each header is inlined only at its first include, as its
+    /// include guard would have it. `# N "file"` line markers around every
+    /// inlined header keep symbols in the file and on the line they were
+    /// written on when the result is parsed with
+    /// [`preprocessed::parse`](crate::preprocessed::parse).
+    pub fn flatten(&self, main_file: &Path) -> std::io::Result<String> {
+        let source = std::fs::read_to_string(main_file)?;
+        let mut seen = HashSet::new();
+        seen.insert(main_file.canonicalize().unwrap_or_else(|_| main_file.to_path_buf()));
+        let mut out = String::new();
+        self.flatten_into(main_file, &source, &mut seen, &mut out);
+        Ok(out)
+    }
+
+    fn flatten_into(&self, file: &Path, source: &str, seen: &mut HashSet<PathBuf>, out: &mut String) {
+        let name = file.display().to_string();
+        let _ = writeln!(out, "# 1 \"{}\"", name);
+        for (i, line) in source.lines().enumerate() {
+            let header = local_include(line).and_then(|header| self.resolve(header, Some(file)));
+            let inlined = header.and_then(|path| {
+                let key = path.canonicalize().unwrap_or_else(|_| path.clone());
+                if seen.contains(&key) {
+                    return None;
+                }
+                let text = std::fs::read_to_string(&path).ok()?;
+                seen.insert(key);
+                Some((path, text))
+            });
+            match inlined {
+                Some((path, text)) => {
+                    debug!("Inlining {:?} into {}", path, name);
+                    self.flatten_into(&path, &text, seen, out);
+                    // Back in `file`, on the line after the include
+                    let _ = writeln!(out, "# {} \"{}\"", i + 2, name);
+                }
+                None => {
+                    out.push_str(line);
+                    out.push('\n');
+                }
+            }
+        }
+    }
+
     /// Check if this looks like a Linux kernel source tree
     pub fn is_kernel_source(&self) -> bool {
         // Check for characteristic kernel files
@@ -165,6 +225,13 @@ impl HeaderResolver {
     }
 }
 
+/// Header named by a local `#include "header.h"` line
+fn local_include(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start().strip_prefix("include")?;
+    let rest = rest.trim_start().strip_prefix('"')?;
+    Some(&rest[..rest.find('"')?])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -212,6 +279,52 @@ mod tests {
         assert!(resolved.unwrap().ends_with("include/linux/kernel.h"));
     }
 
+    #[test]
+    fn test_flatten() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("include")).unwrap();
+        fs::write(
+            root.join("include/regs.h"),
+            "#ifndef REGS_H\n#define REGS_H\n#define CTRL 0x10\n#endif\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("drv.h"),
+            "#include <linux/types.h>\n#include \"regs.h\"\nstruct drv_priv {\n\tint irq;\n};\n",
+        )
+        .unwrap();
+        let main = root.join("drv.c");
+        fs::write(
+            &main,
+            "#include \"drv.h\"\n#include \"regs.h\"\n#include \"missing.h\"\n\nint drv_probe(struct drv_priv *p)\n{\n\treturn p->irq;\n}\n",
+        )
+        .unwrap();
+
+        let resolver = HeaderResolver::for_project(root);
+        let flat = resolver.flatten(&main).unwrap();
+        let (c, h, regs) = (
+            main.display().to_string(),
+            root.join("drv.h").display().to_string(),
+            root.join("include/regs.h").display().to_string(),
+        );
+        let expected = format!(
+            "# 1 \"{c}\"\n\
+             # 1 \"{h}\"\n#include <linux/types.h>\n\
+             # 1 \"{regs}\"\n#ifndef REGS_H\n#define REGS_H\n#define CTRL 0x10\n#endif\n\
+             # 3 \"{h}\"\nstruct drv_priv {{\n\tint irq;\n}};\n\
+             # 2 \"{c}\"\n#include \"regs.h\"\n#include \"missing.h\"\n\n\
+             int drv_probe(struct drv_priv *p)\n{{\n\treturn p->irq;\n}}\n"
+        );
+        assert_eq!(flat, expected);
+
+        let result = crate::preprocessed::parse(&flat, &c).unwrap();
+        let probe = result.functions["drv_probe"].location.as_ref().unwrap();
+        assert_eq!((probe.file.as_str(), probe.line), (c.as_str(), 5));
+        let priv_struct = result.structs["drv_priv"].location.as_ref().unwrap();
+        assert_eq!((priv_struct.file.as_str(), priv_struct.line), (h.as_str(), 3));
+    }
+
     #[test]
     fn test_existing_include_paths() {
         let temp = create_test_kernel_tree();
@@ -4,11 +4,13 @@
 //! C code analysis, handling macros, conditional compilation, and header files.
 
 pub mod config;
+pub mod conditional;
 pub mod clang;
 pub mod headers;
 pub mod cache;
 
+pub use conditional::resolve_conditionals;
 pub use config::{ConfigExtractor, MacroDefinition, Architecture};
-pub use clang::{ClangPreprocessor, PreprocessOptions, PreprocessResult};
+pub use clang::{ClangPreprocessor, LineOrigin, MacroExpansion, PreprocessOptions, PreprocessResult};
 pub use headers::HeaderResolver;
 pub use cache::PreprocessorCache;
@@ -0,0 +1,301 @@
+//! Rust-for-Linux source parsing
+//!
+//! Parses kernel Rust drivers with tree-sitter-rust and extracts `fn` items
+//! and `impl` blocks. Methods of a trait impl (`impl Driver for MyDriver`)
+//! are the callbacks the kernel's Rust abstractions call into, so they are
+//! marked as callbacks with the trait as their context.
+//!
+//! Methods are named `Type::method`, so impls of the same trait for
+//! different types don't collide.
+
+use crate::ParseResult;
+use flowsight_core::{FunctionDef, Location, Parameter, Result};
+use std::collections::HashMap;
+use tree_sitter::{Node, Parser as TSParser};
+
+/// Tree-sitter based parser for Rust sources
+pub struct RustParser {
+    parser: TSParser,
+}
+
+/// The `impl` block a function is defined in
+struct ImplBlock {
+    /// Implemented trait, `None` for inherent impls
+    trait_name: Option<String>,
+    self_type: String,
+}
+
+impl RustParser {
+    /// Create a new Tree-sitter parser for Rust
+    pub fn new() -> Self {
+        let mut parser = TSParser::new();
+        parser
+            .set_language(&tree_sitter_rust::language())
+            .expect("Failed to load Rust grammar");
+        Self { parser }
+    }
+
+    /// Parse `source` and extract its functions
+    pub fn parse_source(&mut self, source: &str, filename: &str) -> Result<ParseResult> {
+        let tree = self
+            .parser
+            .parse(source, None)
+            .ok_or_else(|| flowsight_core::Error::Parse("Failed to parse source".into()))?;
+
+        let mut functions = HashMap::new();
+        collect_functions(tree.root_node(), source, filename, None, &mut functions);
+
+        // Who calls whom, within this file
+        let edges: Vec<(String, String)> = functions
+            .values()
+            .flat_map(|f| f.calls.iter().map(move |c| (f.name.clone(), c.clone())))
+            .collect();
+        for (caller, callee) in edges {
+            if let Some(target) = functions.get_mut(&callee) {
+                if !target.called_by.contains(&caller) {
+                    target.called_by.push(caller);
+                }
+            }
+        }
+
+        Ok(ParseResult {
+            functions,
+            ..Default::default()
+        })
+    }
+}
+
+impl Default for RustParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl crate::Parser for RustParser {
+    fn parse(&self, source: &str, filename: &str) -> Result<ParseResult> {
+        RustParser::new().parse_source(source, filename)
+    }
+
+    fn name(&self) -> &str {
+        "rust"
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+}
+
+/// Add every `fn` with a body under `node`, qualified by its enclosing impl
+fn collect_functions(
+    node: Node,
+    source: &str,
+    filename: &str,
+    owner: Option<&ImplBlock>,
+    functions: &mut HashMap<String, FunctionDef>,
+) {
+    match node.kind() {
+        "impl_item" => {
+            let Some(body) = node.child_by_field_name("body") else {
+                return;
+            };
+            let block = ImplBlock {
+                trait_name: node.child_by_field_name("trait").map(|t| type_name(t, source)),
+                self_type: node
+                    .child_by_field_name("type")
+                    .map(|t| last_segment(&type_name(t, source)).to_string())
+                    .unwrap_or_default(),
+            };
+            collect_functions(body, source, filename, Some(&block), functions);
+            return;
+        }
+        "function_item" => {
+            if let Some(func) = extract_function(node, source, filename, owner) {
+                functions.insert(func.name.clone(), func);
+            }
+        }
+        _ => {}
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_functions(child, source, filename, owner, functions);
+    }
+}
+
+fn extract_function(node: Node, source: &str, filename: &str, owner: Option<&ImplBlock>) -> Option<FunctionDef> {
+    let name = text(node.child_by_field_name("name")?, source);
+    let body = node.child_by_field_name("body")?;
+    let self_type = owner.map(|o| o.self_type.as_str());
+
+    let mut attributes = Vec::new();
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        match child.kind() {
+            "visibility_modifier" => attributes.push(text(child, source)),
+            "function_modifiers" => {
+                let mut inner = child.walk();
+                attributes.extend(child.children(&mut inner).map(|m| text(m, source)));
+            }
+            _ => {}
+        }
+    }
+
+    let mut calls = Vec::new();
+    collect_calls(body, source, self_type, &mut calls);
+
+    Some(FunctionDef {
+        name: match self_type {
+            Some(ty) => format!("{}::{}", ty, name),
+            None => name,
+        },
+        return_type: node
+            .child_by_field_name("return_type")
+            .map(|t| text(t, source))
+            .unwrap_or_else(|| "()".to_string()),
+        params: node
+            .child_by_field_name("parameters")
+            .map(|p| parameters(p, source))
+            .unwrap_or_default(),
+        location: Some(Location::with_range(
+            filename,
+            node.start_position().row as u32 + 1,
+            node.start_position().column as u32,
+            node.end_position().row as u32 + 1,
+            node.end_position().column as u32,
+        )),
+        calls,
+        is_callback: owner.is_some_and(|o| o.trait_name.is_some()),
+        callback_context: owner.and_then(|o| o.trait_name.clone()),
+        attributes,
+        complexity: 1 + decision_points(body, source),
+        max_nesting: block_nesting(body),
+        ..Default::default()
+    })
+}
+
+fn parameters(node: Node, source: &str) -> Vec<Parameter> {
+    let mut cursor = node.walk();
+    node.named_children(&mut cursor)
+        .filter_map(|param| match param.kind() {
+            "parameter" => {
+                let pattern = text(param.child_by_field_name("pattern")?, source);
+                Some(Parameter {
+                    name: pattern.trim_start_matches("mut ").to_string(),
+                    type_name: text(param.child_by_field_name("type")?, source),
+                })
+            }
+            // self, mut self, &self, &mut self
+            "self_parameter" => {
+                let param = text(param, source);
+                Some(Parameter {
+                    name: "self".into(),
+                    type_name: param.strip_prefix("mut ").unwrap_or(&param).replace("self", "Self"),
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Functions called under `node`, in order of first appearance
+///
+/// `Self::f()` and `self.f()` resolve to `Type::f` inside an impl; tuple
+/// struct and enum variant constructors (`Some(..)`, `Ok(..)`) are skipped.
+/// Method calls on anything but `self` have an unknown receiver type.
+fn collect_calls(node: Node, source: &str, self_type: Option<&str>, calls: &mut Vec<String>) {
+    if node.kind() == "call_expression" {
+        if let Some(name) = node
+            .child_by_field_name("function")
+            .and_then(|function| callee(function, source, self_type))
+        {
+            if !calls.contains(&name) {
+                calls.push(name);
+            }
+        }
+    }
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_calls(child, source, self_type, calls);
+    }
+}
+
+fn callee(function: Node, source: &str, self_type: Option<&str>) -> Option<String> {
+    match function.kind() {
+        "identifier" | "scoped_identifier" => {
+            let path = text(function, source);
+            if last_segment(&path).starts_with(|c: char| c.is_ascii_uppercase()) {
+                return None;
+            }
+            match (path.strip_prefix("Self::"), self_type) {
+                (Some(rest), Some(ty)) => Some(format!("{}::{}", ty, rest)),
+                _ => Some(path),
+            }
+        }
+        // `f::<T>()`
+        "generic_function" => callee(function.child_by_field_name("function")?, source, self_type),
+        "field_expression" => {
+            let receiver = function.child_by_field_name("value")?;
+            let method = function.child_by_field_name("field")?;
+            match (receiver.kind(), self_type) {
+                ("self", Some(ty)) => Some(format!("{}::{}", ty, text(method, source))),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// if/while/for + `&&`/`||` + match arms beyond the first
+fn decision_points(node: Node, source: &str) -> u32 {
+    let own: i64 = match node.kind() {
+        "if_expression" | "while_expression" | "for_expression" | "match_arm" => 1,
+        "match_expression" => -1,
+        "binary_expression" => node
+            .child_by_field_name("operator")
+            .is_some_and(|op| matches!(op.utf8_text(source.as_bytes()), Ok("&&" | "||")))
+            as i64,
+        _ => 0,
+    };
+    let mut cursor = node.walk();
+    let nested: i64 = node
+        .children(&mut cursor)
+        .map(|c| decision_points(c, source) as i64)
+        .sum();
+    (own + nested).max(0) as u32
+}
+
+/// Deepest block nested inside `body`, not counting `body` itself
+fn block_nesting(body: Node) -> u32 {
+    let mut cursor = body.walk();
+    body.children(&mut cursor).map(block_depth).max().unwrap_or(0)
+}
+
+fn block_depth(node: Node) -> u32 {
+    let mut cursor = node.walk();
+    let inner = node.children(&mut cursor).map(block_depth).max().unwrap_or(0);
+    inner + matches!(node.kind(), "block" | "match_block") as u32
+}
+
+/// A type as written, without generic arguments (`Foo<T>` -> `Foo`)
+fn type_name(node: Node, source: &str) -> String {
+    match node.kind() {
+        "generic_type" => match node.child_by_field_name("type") {
+            Some(inner) => type_name(inner, source),
+            None => text(node, source),
+        },
+        _ => text(node, source),
+    }
+}
+
+fn last_segment(path: &str) -> &str {
+    path.rsplit("::").next().unwrap_or(path).trim()
+}
+
+/// Source text of `node` with whitespace runs collapsed
+fn text(node: Node, source: &str) -> String {
+    node.utf8_text(source.as_bytes())
+        .unwrap_or("")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -76,6 +76,188 @@ struct file_operations {
     assert_eq!(ops.fields.len(), 2);
 }
 
+/// Every declarator of a comma-separated field declaration becomes a field
+#[test]
+fn test_multi_declarator_fields() {
+    let source = r#"
+struct my_dev {
+    int irq, *counts, regs[4];
+    struct device *parent, *child;
+};
+"#;
+    let mut parser = TreeSitterParser::new();
+    let result = parser.parse_source(source, "test.c").unwrap();
+
+    let dev = result.structs.get("my_dev").unwrap();
+    let names: Vec<&str> = dev.fields.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, vec!["irq", "counts", "regs", "parent", "child"]);
+    assert!(dev.fields[1].is_pointer);
+    assert_eq!(dev.fields[2].array_size.as_deref(), Some("4"));
+    assert_eq!(dev.fields[4].type_name, "struct device");
+    assert_eq!(dev.referenced_structs, vec!["device"]);
+}
+
+#[test]
+fn test_call_sites() {
+    let source = r#"
+static int reset(struct my_dev *dev) {
+    writel(0, dev->regs);
+    udelay(10);
+    writel(1, dev->regs);
+    return check(readl(dev->regs));
+}
+"#;
+    let mut parser = TreeSitterParser::new();
+    let result = parser.parse_source(source, "test.c").unwrap();
+    let reset = &result.functions["reset"];
+
+    let sites: Vec<(&str, u32, u32)> = reset
+        .call_sites
+        .iter()
+        .map(|s| (s.callee.as_str(), s.line, s.column))
+        .collect();
+    assert_eq!(
+        sites,
+        [("writel", 3, 4), ("udelay", 4, 4), ("writel", 5, 4), ("check", 6, 11), ("readl", 6, 17)]
+    );
+    assert_eq!(reset.calls, vec!["check", "readl", "udelay", "writel"]);
+}
+
+#[test]
+fn test_typedef_struct_fields() {
+    let source = r#"
+struct my_dev {
+    ctrl_t ctrl;
+    regs_t *regs;
+    u32 flags;
+    alias_t other;
+};
+
+typedef struct controller {
+    int id;
+} ctrl_t;
+
+typedef struct {
+    u32 base;
+} regs_t;
+
+typedef ctrl_t alias_t;
+"#;
+    let mut parser = TreeSitterParser::new();
+    let result = parser.parse_source(source, "test.c").unwrap();
+
+    assert!(result.structs.contains_key("controller"));
+    assert!(result.structs.contains_key("regs_t"));
+    let dev = result.structs.get("my_dev").unwrap();
+    let types: Vec<(&str, Option<&str>)> = dev
+        .fields
+        .iter()
+        .map(|f| (f.type_name.as_str(), f.typedef_name.as_deref()))
+        .collect();
+    assert_eq!(
+        types,
+        [
+            ("struct controller", Some("ctrl_t")),
+            ("struct regs_t", Some("regs_t")),
+            ("u32", None),
+            ("struct controller", Some("alias_t")),
+        ]
+    );
+    assert!(dev.fields[1].is_pointer);
+    assert_eq!(dev.referenced_structs, vec!["controller", "regs_t"]);
+
+    // A typedef from another file resolves on merge
+    let mut header = parser
+        .parse_source("typedef struct bus { int n; } bus_t;", "bus.h")
+        .unwrap();
+    let user = parser
+        .parse_source("struct user { bus_t *bus; };", "user.c")
+        .unwrap();
+    assert_eq!(user.structs["user"].fields[0].type_name, "bus_t");
+    header.merge(user);
+    let user = &header.structs["user"];
+    assert_eq!(user.fields[0].type_name, "struct bus");
+    assert_eq!(user.referenced_structs, vec!["bus"]);
+}
+
+#[test]
+fn test_object_like_defines() {
+    let source = r#"
+#define MAX_BUF 256
+#define HDR_LEN (MAX_BUF / 4) /* header */
+#define NAME "my//dev" // driver name
+#define MASK (0xff00 | \
+    0x00ff)
+#define EMPTY
+#define MIN(a, b) ((a) < (b) ? (a) : (b))
+"#;
+    let mut parser = TreeSitterParser::new();
+    let result = parser.parse_source(source, "test.c").unwrap();
+
+    let mut defines: Vec<(&str, &str)> = result.defines.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    defines.sort();
+    assert_eq!(
+        defines,
+        [("HDR_LEN", "(MAX_BUF / 4)"), ("MASK", "(0xff00 | 0x00ff)"), ("MAX_BUF", "256"), ("NAME", "\"my//dev\"")]
+    );
+}
+
+#[test]
+fn test_struct_layout() {
+    use crate::preprocessor::config::Architecture;
+
+    let source = r#"
+struct pkt {
+    u8 type;
+    u32 len;
+    char *names[2];
+    u16 flags[2][3];
+    unsigned long stamp;
+    struct opaque o;
+    u8 data[];
+};
+"#;
+    let mut parser = TreeSitterParser::new();
+    let result = parser.parse_source(source, "test.c").unwrap();
+    let pkt = result.structs.get("pkt").unwrap();
+    assert!(pkt.fields[2].is_pointer);
+    assert_eq!(pkt.fields[3].array_size.as_deref(), Some("2][3"));
+    assert_eq!(pkt.fields[6].array_size.as_deref(), Some(""));
+
+    let placement = |arch: Architecture| {
+        let layout = pkt.compute_layout(&arch.type_sizes());
+        let fields: Vec<(String, usize, usize)> = layout
+            .fields
+            .iter()
+            .map(|f| (f.name.clone(), f.offset, f.size))
+            .collect();
+        (fields, layout.size, layout.align, layout.padding)
+    };
+
+    let (fields, size, align, padding) = placement(Architecture::X86_64);
+    assert_eq!(
+        fields,
+        [
+            ("type", 0, 1),
+            ("len", 4, 4),
+            ("names", 8, 16),
+            ("flags", 24, 12),
+            ("stamp", 40, 8),
+            ("o", 48, 0),
+            ("data", 48, 0),
+        ]
+        .map(|(name, offset, size)| (name.to_string(), offset, size))
+    );
+    assert_eq!((size, align, padding), (48, 8, 7));
+
+    let (fields, size, align, padding) = placement(Architecture::I386);
+    assert_eq!(fields[4], ("stamp".to_string(), 28, 4));
+    assert_eq!((size, align, padding), (32, 4, 3));
+
+    let layout = pkt.compute_layout(&Architecture::X86_64.type_sizes());
+    assert_eq!(layout.unknown, vec!["o"]);
+}
+
 /// Test parsing of USB driver structure definition
 #[test]
 fn test_usb_driver_structure() {
@@ -193,3 +375,266 @@ static int driver_init(struct platform_device *pdev) {
     assert!(init.calls.contains(&"clk_prepare_enable".to_string()));
     assert!(init.calls.contains(&"platform_set_drvdata".to_string()));
 }
+
+/// Test that functions record their real end line
+#[test]
+fn test_function_end_line() {
+    let source = r#"
+static int short_fn(void) { return 0; }
+
+static int long_fn(struct device *dev)
+{
+    DEFINE_FOO(bar, 1);
+    if (!dev)
+        return -EINVAL;
+
+    dev_info(dev, "ok\n");
+    return 0;
+}
+"#;
+    let mut parser = TreeSitterParser::new();
+    let result = parser.parse_source(source, "test.c").unwrap();
+
+    let short_loc = result.functions["short_fn"].location.clone().unwrap();
+    assert_eq!((short_loc.line, short_loc.end_line), (2, 2));
+
+    let long_loc = result.functions["long_fn"].location.clone().unwrap();
+    assert_eq!((long_loc.line, long_loc.end_line), (4, 12));
+}
+
+/// Test that syntax errors are reported as diagnostics with locations
+#[test]
+fn test_syntax_errors_reported() {
+    let source = r#"
+static int ok_fn(void) { return 0; }
+
+static int broken_fn(void)
+{
+    int x = (1 + ;
+    return x
+}
+"#;
+    let mut parser = TreeSitterParser::new();
+    let result = parser.parse_source(source, "broken.c").unwrap();
+
+    assert!(result.functions.contains_key("ok_fn"));
+    assert!(!result.errors.is_empty());
+    assert!(result.errors.iter().any(|e| e.line == 6), "{:?}", result.errors);
+
+    let missing = parser
+        .parse_source("int f(void)\n{\n    return 0\n}\n", "missing.c")
+        .unwrap();
+    assert_eq!(missing.errors.len(), 1, "{:?}", missing.errors);
+    assert_eq!(missing.errors[0].message, "missing `;`");
+    assert_eq!(missing.errors[0].line, 3);
+    assert_eq!(missing.errors[0].file, "missing.c");
+
+    // Merged results keep telling files apart
+    let mut merged = result;
+    merged.merge(missing);
+    let files: Vec<&str> = merged.errors.iter().map(|e| e.file.as_str()).collect();
+    assert!(files.contains(&"broken.c") && files.contains(&"missing.c"), "{:?}", files);
+
+    let clean = parser.parse_source("int f(void) { return 0; }", "ok.c").unwrap();
+    assert!(clean.errors.is_empty());
+}
+
+/// Test parse_many keeps per-file outcomes
+#[test]
+fn test_parse_many() {
+    use std::io::Write;
+
+    let dir = tempfile::tempdir().unwrap();
+    let good = dir.path().join("good.c");
+    std::fs::File::create(&good)
+        .unwrap()
+        .write_all(b"int good(void) { return 0; }\n")
+        .unwrap();
+    let missing = dir.path().join("missing.c");
+
+    let parser = crate::get_parser();
+    let results = parser.parse_many(&[good.clone(), missing.clone()]);
+
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].0, good);
+    assert!(results[0].1.as_ref().unwrap().functions.contains_key("good"));
+    assert_eq!(results[1].0, missing);
+    assert!(results[1].1.is_err());
+}
+
+#[test]
+fn test_function_annotations() {
+    let source = r#"
+static int __init my_init(void) { return 0; }
+static void __exit my_exit(void) {}
+__attribute__((cold)) static int __must_check my_read(char __user *buf) { return 0; }
+"#;
+    let mut parser = TreeSitterParser::new();
+    let result = parser.parse_source(source, "test.c").unwrap();
+
+    let init = result.functions.get("my_init").unwrap();
+    assert_eq!(init.attributes, vec!["static", "__init"]);
+    assert_eq!(init.return_type, "int");
+    assert!(matches!(init.annotated_context(), Some(flowsight_core::ExecutionContext::Process)));
+
+    assert!(result.functions.get("my_exit").unwrap().has_attribute("__exit"));
+
+    let read = result.functions.get("my_read").unwrap();
+    assert!(read.has_attribute("__attribute__((cold))"));
+    assert!(read.has_attribute("__must_check"));
+
+    // Annotation macros are not reported as syntax errors
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+}
+
+#[test]
+fn test_merge_resolves_cross_file_calls() {
+    let mut parser = TreeSitterParser::new();
+    let mut core = parser
+        .parse_source("int my_probe(void) { return my_hw_init(); }\n", "core.c")
+        .unwrap();
+    let hw = parser
+        .parse_source("int my_hw_init(void) { return 0; }\n", "hw.c")
+        .unwrap();
+
+    core.merge(hw);
+
+    assert_eq!(core.functions.len(), 2);
+    let init = core.functions.get("my_hw_init").unwrap();
+    assert_eq!(init.called_by, vec!["my_probe"]);
+    assert_eq!(init.location.as_ref().unwrap().file, "hw.c");
+}
+
+#[test]
+fn test_occurrences_have_byte_spans() {
+    use flowsight_core::OccurrenceKind;
+
+    let source = r#"static int my_read(char *buf);
+static int my_read(char *buf) { return 0; }
+static const struct file_operations my_fops = { .read = my_read };
+static int my_open(void) {
+    int (*fn)(char *) = &my_read;
+    return my_read(NULL);
+}
+"#;
+    let mut parser = TreeSitterParser::new();
+    let result = parser.parse_source(source, "test.c").unwrap();
+
+    let occurrences: Vec<_> = result.occurrences.iter().filter(|o| o.name == "my_read").collect();
+    let kinds: Vec<OccurrenceKind> = occurrences.iter().map(|o| o.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            OccurrenceKind::Declaration,
+            OccurrenceKind::Definition,
+            OccurrenceKind::Reference,
+            OccurrenceKind::Reference,
+            OccurrenceKind::Call,
+        ]
+    );
+    for occ in &occurrences {
+        assert_eq!(&source[occ.byte_start..occ.byte_end], "my_read");
+    }
+    assert_eq!(occurrences[4].line, 6);
+    // `fn` is a local variable, not a function name
+    assert!(!result.occurrences.iter().any(|o| o.name == "fn"));
+}
+
+/// Cyclomatic complexity counts branches and short-circuit operators
+#[test]
+fn test_complexity_metrics() {
+    let source = r#"
+static int flat(void) { return 0; }
+
+static int busy(int a, int b) {
+    if (a && b) {
+        for (;;) {
+            while (a) { a--; }
+        }
+    }
+    switch (b) {
+    case 1: return a ? 1 : 2;
+    case 2: break;
+    default: break;
+    }
+    return a || b;
+}
+"#;
+    let mut parser = TreeSitterParser::new();
+    let result = parser.parse_source(source, "test.c").unwrap();
+
+    let flat = &result.functions["flat"];
+    assert_eq!((flat.complexity, flat.max_nesting), (1, 0));
+
+    // if, &&, for, while, case 1, ?:, case 2, || -> 8 decisions
+    let busy = &result.functions["busy"];
+    assert_eq!(busy.complexity, 9);
+    assert_eq!(busy.max_nesting, 3);
+}
+
+#[test]
+fn test_rust_driver_functions() {
+    let source = r#"
+use kernel::prelude::*;
+
+struct MyDriver;
+
+impl platform::Driver for MyDriver {
+    fn probe(pdev: &mut platform::Device, info: Option<&Self::IdInfo>) -> Result<Self::Data> {
+        // probe() { comment braces are ignored
+        let data = Self::setup(pdev)?;
+        pr_info!("probed {}\n", "}");
+        Ok(data)
+    }
+
+    fn remove(data: &Self::Data) {
+        if data.ready && !data.busy {
+            teardown(data);
+        }
+    }
+}
+
+impl MyDriver {
+    fn setup(pdev: &mut platform::Device) -> Result<Box<Data>> {
+        match pdev.id() {
+            0 => helper(),
+            _ => Ok(Box::new(Data::default())),
+        }
+    }
+}
+
+fn helper() -> Result<Box<Data>> { todo!() }
+fn teardown(data: &Data) {}
+"#;
+    let parser = get_parser_for(Path::new("my_driver.rs")).unwrap();
+    let result = parser.parse(source, "my_driver.rs").unwrap();
+
+    let mut names: Vec<&str> = result.functions.keys().map(String::as_str).collect();
+    names.sort();
+    assert_eq!(
+        names,
+        vec!["MyDriver::probe", "MyDriver::remove", "MyDriver::setup", "helper", "teardown"]
+    );
+
+    let probe = &result.functions["MyDriver::probe"];
+    assert!(probe.is_callback);
+    assert_eq!(probe.callback_context.as_deref(), Some("platform::Driver"));
+    assert_eq!(probe.return_type, "Result<Self::Data>");
+    assert_eq!(probe.params.len(), 2);
+    assert_eq!(probe.params[0].name, "pdev");
+    assert_eq!(probe.params[0].type_name, "&mut platform::Device");
+    assert_eq!(probe.calls, vec!["MyDriver::setup"]);
+    let loc = probe.location.as_ref().unwrap();
+    assert_eq!((loc.line, loc.end_line), (7, 12));
+
+    let remove = &result.functions["MyDriver::remove"];
+    assert_eq!(remove.calls, vec!["teardown"]);
+    assert_eq!((remove.complexity, remove.max_nesting), (3, 1));
+
+    // Inherent methods are not callbacks
+    let setup = &result.functions["MyDriver::setup"];
+    assert!(!setup.is_callback);
+    assert_eq!(setup.calls, vec!["helper", "Box::new", "Data::default"]);
+    assert_eq!(setup.called_by, vec!["MyDriver::probe"]);
+    assert_eq!(setup.complexity, 2);
+}
@@ -2,11 +2,16 @@
 //!
 //! Provides fast incremental parsing using tree-sitter.
 
-use flowsight_core::{FunctionDef, Location, Parameter, Result, StructDef, StructField};
+use flowsight_core::{
+    CallSite, FunctionDef, GotoLabel, Location, Occurrence, OccurrenceKind, Parameter, Result, StructDef,
+    StructField, SwitchCase, SwitchDispatch,
+};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use tracing::debug;
 use tree_sitter::{Node, Parser as TSParser, Tree};
 
-use crate::ParseResult;
+use crate::{ParseDiagnostic, ParseResult};
 
 /// Tree-sitter based parser
 pub struct TreeSitterParser {
@@ -25,6 +30,7 @@ impl TreeSitterParser {
 
     /// Parse source code and extract information
     pub fn parse_source(&mut self, source: &str, filename: &str) -> Result<ParseResult> {
+        let source = &*mask_inline_asm(source);
         let tree = self
             .parser
             .parse(source, None)
@@ -42,6 +48,7 @@ impl TreeSitterParser {
         old_tree: Option<&Tree>,
         filename: &str,
     ) -> Result<(ParseResult, Tree)> {
+        let source = &*mask_inline_asm(source);
         let tree = self
             .parser
             .parse(source, old_tree)
@@ -61,6 +68,51 @@ impl TreeSitterParser {
     ) {
         let root = tree.root_node();
         self.visit_node(root, source, filename, result);
+        result.resolve_typedefs();
+        self.collect_occurrences(root, source, filename, result);
+        if root.has_error() {
+            self.collect_syntax_errors(root, source, filename, result);
+        }
+    }
+
+    /// Report ERROR and MISSING nodes (outermost only) with their positions
+    fn collect_syntax_errors(
+        &self,
+        node: Node,
+        source: &str,
+        filename: &str,
+        result: &mut ParseResult,
+    ) {
+        let pos = node.start_position();
+        let message = if node.is_missing() {
+            Some(format!("missing `{}`", node.kind()))
+        } else if node.is_error() && !self.kernel_annotations(node, source).is_empty() {
+            // e.g. `static int __init foo(void)` - not a real syntax error
+            return;
+        } else if node.is_error() {
+            let text = self.node_text(node, source);
+            let snippet: String = text.lines().next().unwrap_or("").trim().chars().take(40).collect();
+            Some(format!("syntax error near `{}`", snippet))
+        } else {
+            None
+        };
+        if let Some(message) = message {
+            result.errors.push(ParseDiagnostic {
+                file: filename.to_string(),
+                message,
+                line: pos.row as u32 + 1,
+                column: pos.column as u32,
+            });
+            return;
+        }
+        if !node.has_error() {
+            return;
+        }
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.collect_syntax_errors(child, source, filename, result);
+        }
     }
 
     fn visit_node(&self, node: Node, source: &str, filename: &str, result: &mut ParseResult) {
@@ -72,11 +124,32 @@ impl TreeSitterParser {
                 }
             }
             "struct_specifier" => {
-                if let Some(st) = self.extract_struct(node, source, filename) {
+                if let Some(st) = self.extract_struct(node, source, filename, None) {
                     debug!("Found struct: {}", st.name);
                     result.structs.insert(st.name.clone(), st);
                 }
             }
+            "type_definition" => self.extract_typedef(node, source, filename, result),
+            // Function-like macros are `preproc_function_def` and left out
+            "preproc_def" => {
+                if let (Some(name), Some(value)) =
+                    (node.child_by_field_name("name"), node.child_by_field_name("value"))
+                {
+                    let value = macro_value(&self.node_text(value, source));
+                    if !value.is_empty() {
+                        result.defines.insert(self.node_text(name, source), value);
+                    }
+                }
+            }
+            "preproc_include" => {
+                if let Some(path) = node.child_by_field_name("path") {
+                    let text = self.node_text(path, source);
+                    let header = text.trim_matches(|c| c == '"' || c == '<' || c == '>');
+                    if !header.is_empty() {
+                        result.includes.push(header.to_string());
+                    }
+                }
+            }
             _ => {}
         }
 
@@ -87,12 +160,108 @@ impl TreeSitterParser {
         }
     }
 
+    /// Record every use of a function name known to this file, with byte spans
+    ///
+    /// Known names are functions defined or prototyped here and every callee.
+    fn collect_occurrences(&self, root: Node, source: &str, filename: &str, result: &mut ParseResult) {
+        let mut names: HashSet<String> = result.functions.keys().cloned().collect();
+        names.extend(result.functions.values().flat_map(|f| f.calls.iter().cloned()));
+        self.collect_prototype_names(root, source, &mut names);
+
+        let mut occurrences = Vec::new();
+        self.visit_occurrences(root, source, filename, &names, &mut occurrences);
+        result.occurrences = occurrences;
+    }
+
+    fn collect_prototype_names(&self, node: Node, source: &str, names: &mut HashSet<String>) {
+        if node.kind() == "function_declarator" {
+            if let Some(declarator) = node.child_by_field_name("declarator") {
+                if declarator.kind() == "identifier" {
+                    names.insert(self.node_text(declarator, source));
+                }
+            }
+        }
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.collect_prototype_names(child, source, names);
+        }
+    }
+
+    fn visit_occurrences(
+        &self,
+        node: Node,
+        source: &str,
+        filename: &str,
+        names: &HashSet<String>,
+        occurrences: &mut Vec<Occurrence>,
+    ) {
+        if node.kind() == "identifier" {
+            let name = self.node_text(node, source);
+            if names.contains(&name) {
+                if let Some(kind) = self.occurrence_kind(node) {
+                    let pos = node.start_position();
+                    occurrences.push(Occurrence {
+                        name,
+                        file: filename.to_string(),
+                        byte_start: node.start_byte(),
+                        byte_end: node.end_byte(),
+                        line: pos.row as u32 + 1,
+                        column: pos.column as u32,
+                        kind,
+                    });
+                }
+            }
+            return;
+        }
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.visit_occurrences(child, source, filename, names, occurrences);
+        }
+    }
+
+    /// Classify an identifier by its syntactic position; `None` for variables
+    /// that merely shadow a function name
+    fn occurrence_kind(&self, node: Node) -> Option<OccurrenceKind> {
+        let parent = node.parent()?;
+        let is_field = |field: &str| parent.child_by_field_name(field).is_some_and(|n| n.id() == node.id());
+
+        match parent.kind() {
+            "function_declarator" if is_field("declarator") => {
+                // Walk out of pointer/parenthesized declarators to the owner
+                let mut owner = parent.parent();
+                while let Some(n) = owner {
+                    match n.kind() {
+                        "pointer_declarator" | "parenthesized_declarator" | "function_declarator" => {
+                            owner = n.parent()
+                        }
+                        _ => break,
+                    }
+                }
+                match owner?.kind() {
+                    "function_definition" => Some(OccurrenceKind::Definition),
+                    "declaration" | "init_declarator" => Some(OccurrenceKind::Declaration),
+                    _ => None,
+                }
+            }
+            "call_expression" if is_field("function") => Some(OccurrenceKind::Call),
+            "init_declarator" if is_field("declarator") => None,
+            "declaration" | "parameter_declaration" | "field_declaration" | "pointer_declarator"
+            | "array_declarator" | "parenthesized_declarator" | "function_declarator" => None,
+            _ => Some(OccurrenceKind::Reference),
+        }
+    }
+
     fn extract_function(&self, node: Node, source: &str, filename: &str) -> Option<FunctionDef> {
         let mut name = String::new();
         let mut return_type = String::new();
         let mut params = Vec::new();
         let mut calls = Vec::new();
+        let mut call_sites = Vec::new();
         let mut attributes = Vec::new();
+        let (mut complexity, mut max_nesting) = (0, 0);
+        let mut labels = Vec::new();
+        let mut switches = Vec::new();
 
         let mut cursor = node.walk();
         for child in node.children(&mut cursor) {
@@ -101,6 +270,20 @@ impl TreeSitterParser {
                     let attr = self.node_text(child, source);
                     attributes.push(attr);
                 }
+                "attribute_specifier" => {
+                    // __attribute__((cold)) - keep as written, minus whitespace
+                    let attr: String = self
+                        .node_text(child, source)
+                        .chars()
+                        .filter(|c| !c.is_whitespace())
+                        .collect();
+                    attributes.push(attr);
+                }
+                "ERROR" => {
+                    // Kernel annotation macros (__init, __must_check, ...) are
+                    // unknown to the grammar and surface as ERROR nodes
+                    attributes.extend(self.kernel_annotations(child, source));
+                }
                 "primitive_type" | "type_identifier" | "sized_type_specifier" => {
                     return_type = self.node_text(child, source);
                 }
@@ -110,7 +293,14 @@ impl TreeSitterParser {
                 }
                 "compound_statement" => {
                     // Extract function calls from body
-                    calls = self.extract_calls(child, source);
+                    call_sites = self.extract_call_sites(child, source);
+                    calls = call_sites.iter().map(|site| site.callee.clone()).collect();
+                    calls.sort();
+                    calls.dedup();
+                    complexity = 1 + self.decision_points(child, source);
+                    max_nesting = self.brace_nesting(child);
+                    labels = self.extract_labels(child, source);
+                    self.collect_switches(child, source, &mut switches);
                 }
                 _ => {}
             }
@@ -120,23 +310,210 @@ impl TreeSitterParser {
             return None;
         }
 
+        let io_access = call_sites.iter().any(CallSite::is_mmio);
+        let mut io_registers: Vec<String> = Vec::new();
+        for register in call_sites.iter().filter_map(CallSite::mmio_register) {
+            if !io_registers.iter().any(|r| r == register) {
+                io_registers.push(register.to_string());
+            }
+        }
+
         Some(FunctionDef {
             name,
             return_type,
             params,
-            location: Some(Location::new(
+            // Use the node's own span even when macros confuse the body parse
+            location: Some(Location::with_range(
                 filename,
                 node.start_position().row as u32 + 1,
                 node.start_position().column as u32,
+                node.end_position().row as u32 + 1,
+                node.end_position().column as u32,
             )),
             calls,
-            called_by: Vec::new(),
-            is_callback: false,
-            callback_context: None,
             attributes,
+            complexity,
+            max_nesting,
+            labels,
+            call_sites,
+            switches,
+            io_access,
+            io_registers,
+            ..Default::default()
         })
     }
 
+    /// Labels at the top level of a function body, with their blocks' calls
+    fn extract_labels(&self, body: Node, source: &str) -> Vec<GotoLabel> {
+        let mut gotos: HashMap<String, Vec<u32>> = HashMap::new();
+        self.collect_gotos(body, source, &mut gotos);
+
+        let mut labels: Vec<GotoLabel> = Vec::new();
+        let mut falls_through = true;
+        let mut cursor = body.walk();
+        for mut stmt in body.named_children(&mut cursor) {
+            if stmt.kind() == "comment" {
+                continue;
+            }
+            // `err_a: err_b: stmt;` nests one labeled_statement per label
+            while stmt.kind() == "labeled_statement" {
+                let Some(label) = stmt.child_by_field_name("label") else {
+                    break;
+                };
+                let name = self.node_text(label, source);
+                labels.push(GotoLabel {
+                    gotos: gotos.remove(&name).unwrap_or_default(),
+                    name,
+                    line: stmt.start_position().row as u32 + 1,
+                    fallthrough: falls_through,
+                    calls: Vec::new(),
+                });
+                falls_through = true;
+                match stmt.named_child(stmt.named_child_count().saturating_sub(1)) {
+                    Some(inner) if inner.id() != label.id() => stmt = inner,
+                    _ => break,
+                }
+            }
+
+            if let Some(label) = labels.last_mut() {
+                let mut sites = Vec::new();
+                self.collect_calls(stmt, source, &mut sites);
+                for site in sites {
+                    if !label.calls.contains(&site.callee) {
+                        label.calls.push(site.callee);
+                    }
+                }
+            }
+            falls_through = !matches!(stmt.kind(), "return_statement" | "goto_statement");
+        }
+        labels
+    }
+
+    fn collect_gotos(&self, node: Node, source: &str, gotos: &mut HashMap<String, Vec<u32>>) {
+        if node.kind() == "goto_statement" {
+            if let Some(label) = node.child_by_field_name("label") {
+                gotos
+                    .entry(self.node_text(label, source))
+                    .or_default()
+                    .push(node.start_position().row as u32 + 1);
+            }
+        }
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.collect_gotos(child, source, gotos);
+        }
+    }
+
+    /// Outermost `switch` statements under `node`; nested ones count toward their arm
+    fn collect_switches(&self, node: Node, source: &str, switches: &mut Vec<SwitchDispatch>) {
+        if node.kind() == "switch_statement" {
+            switches.extend(self.switch_dispatch(node, source));
+            return;
+        }
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.collect_switches(child, source, switches);
+        }
+    }
+
+    fn switch_dispatch(&self, node: Node, source: &str) -> Option<SwitchDispatch> {
+        let condition = self.node_text(node.child_by_field_name("condition")?, source);
+        let condition = condition.trim();
+        let discriminant = condition
+            .strip_prefix('(')
+            .and_then(|c| c.strip_suffix(')'))
+            .unwrap_or(condition)
+            .trim()
+            .to_string();
+        let body = node.child_by_field_name("body")?;
+
+        let mut cases: Vec<SwitchCase> = Vec::new();
+        let mut falls_through = false;
+        let mut cursor = body.walk();
+        for arm in body.named_children(&mut cursor) {
+            if arm.kind() != "case_statement" {
+                continue;
+            }
+            let value = arm.child_by_field_name("value");
+            let mut calls = Vec::new();
+            let mut last = None;
+            let mut stmts = arm.walk();
+            for stmt in arm.named_children(&mut stmts) {
+                if value.is_some_and(|v| v.id() == stmt.id()) || stmt.kind() == "comment" {
+                    continue;
+                }
+                let mut sites = Vec::new();
+                self.collect_calls(stmt, source, &mut sites);
+                for site in sites {
+                    if !calls.contains(&site.callee) {
+                        calls.push(site.callee);
+                    }
+                }
+                last = Some(stmt);
+            }
+            cases.push(SwitchCase {
+                value: value.map(|v| self.node_text(v, source)),
+                line: arm.start_position().row as u32 + 1,
+                end_line: arm.end_position().row as u32 + 1,
+                fallthrough: falls_through,
+                calls,
+            });
+            falls_through = !last.is_some_and(ends_arm);
+        }
+
+        Some(SwitchDispatch {
+            discriminant,
+            line: node.start_position().row as u32 + 1,
+            end_line: node.end_position().row as u32 + 1,
+            cases,
+        })
+    }
+
+    /// Branches that add a path through the code: `if`, loops, `case`, `?:`, `&&`, `||`
+    fn decision_points(&self, node: Node, source: &str) -> u32 {
+        let own = match node.kind() {
+            "if_statement" | "for_statement" | "while_statement" | "do_statement" | "conditional_expression" => 1,
+            // `default:` has no value and adds no path of its own
+            "case_statement" => node.child_by_field_name("value").is_some() as u32,
+            "binary_expression" => node
+                .child_by_field_name("operator")
+                .is_some_and(|op| matches!(op.utf8_text(source.as_bytes()), Ok("&&" | "||"))) as u32,
+            _ => 0,
+        };
+        let mut cursor = node.walk();
+        let nested: u32 = node.children(&mut cursor).map(|c| self.decision_points(c, source)).sum();
+        own + nested
+    }
+
+    /// Deepest block nested inside `body`, not counting `body` itself
+    fn brace_nesting(&self, body: Node) -> u32 {
+        let mut cursor = body.walk();
+        body.children(&mut cursor)
+            .map(|child| self.block_depth(child))
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn block_depth(&self, node: Node) -> u32 {
+        let mut cursor = node.walk();
+        let inner = node.children(&mut cursor).map(|c| self.block_depth(c)).max().unwrap_or(0);
+        inner + (node.kind() == "compound_statement") as u32
+    }
+
+    /// Identifiers in an ERROR node if they are all kernel annotation macros
+    fn kernel_annotations(&self, node: Node, source: &str) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            let text = self.node_text(child, source);
+            if child.kind() != "identifier" || !is_kernel_annotation(&text) {
+                return Vec::new();
+            }
+            names.push(text);
+        }
+        names
+    }
+
     fn extract_function_name(&self, node: Node, source: &str) -> String {
         let mut cursor = node.walk();
         for child in node.children(&mut cursor) {
@@ -185,16 +562,23 @@ impl TreeSitterParser {
         let mut cursor = node.walk();
         for child in node.children(&mut cursor) {
             match child.kind() {
-                "primitive_type" | "type_identifier" | "sized_type_specifier" => {
-                    if type_name.is_empty() {
-                        type_name = self.node_text(child, source);
-                    }
+                "primitive_type" | "type_identifier" | "sized_type_specifier"
+                    if type_name.is_empty() =>
+                {
+                    type_name = self.node_text(child, source);
                 }
                 "struct_specifier" => {
                     type_name = format!("struct {}", self.get_struct_name(child, source));
                 }
                 "pointer_declarator" => {
-                    type_name = format!("{}*", type_name);
+                    // `**pp` nests one declarator per star
+                    let mut depth = 0;
+                    let mut declarator = Some(child);
+                    while let Some(d) = declarator.filter(|d| d.kind() == "pointer_declarator") {
+                        depth += 1;
+                        declarator = d.child_by_field_name("declarator");
+                    }
+                    type_name = format!("{}{}", type_name, "*".repeat(depth));
                     name = self.extract_identifier(child, source);
                 }
                 "identifier" => {
@@ -239,20 +623,34 @@ impl TreeSitterParser {
         String::new()
     }
 
-    fn extract_calls(&self, node: Node, source: &str) -> Vec<String> {
-        let mut calls = Vec::new();
-        self.collect_calls(node, source, &mut calls);
-        calls.sort();
-        calls.dedup();
-        calls
+    /// Calls in `node`, in source order
+    fn extract_call_sites(&self, node: Node, source: &str) -> Vec<CallSite> {
+        let mut sites = Vec::new();
+        self.collect_calls(node, source, &mut sites);
+        sites
     }
 
-    fn collect_calls(&self, node: Node, source: &str, calls: &mut Vec<String>) {
+    fn collect_calls(&self, node: Node, source: &str, sites: &mut Vec<CallSite>) {
         if node.kind() == "call_expression" {
             let mut cursor = node.walk();
             for child in node.children(&mut cursor) {
                 if child.kind() == "identifier" {
-                    calls.push(self.node_text(child, source));
+                    let arguments = match node.child_by_field_name("arguments") {
+                        Some(args) => {
+                            let mut cursor = args.walk();
+                            args.named_children(&mut cursor)
+                                .filter(|arg| arg.kind() != "comment")
+                                .map(|arg| self.node_text(arg, source))
+                                .collect()
+                        }
+                        None => Vec::new(),
+                    };
+                    sites.push(CallSite {
+                        callee: self.node_text(child, source),
+                        line: child.start_position().row as u32 + 1,
+                        column: child.start_position().column as u32,
+                        arguments,
+                    });
                     break;
                 }
             }
@@ -260,12 +658,58 @@ impl TreeSitterParser {
 
         let mut cursor = node.walk();
         for child in node.children(&mut cursor) {
-            self.collect_calls(child, source, calls);
+            self.collect_calls(child, source, sites);
         }
     }
 
-    fn extract_struct(&self, node: Node, source: &str, filename: &str) -> Option<StructDef> {
-        let mut name = String::new();
+    /// Record `typedef struct foo foo_t;` style aliases
+    ///
+    /// An anonymous `typedef struct { ... } foo_t;` is recorded as struct `foo_t`.
+    fn extract_typedef(&self, node: Node, source: &str, filename: &str, result: &mut ParseResult) {
+        let Some(ty) = node.child_by_field_name("type") else {
+            return;
+        };
+        let mut cursor = node.walk();
+        let aliases: Vec<String> = node
+            .children_by_field_name("declarator", &mut cursor)
+            .filter(|d| d.kind() == "type_identifier")
+            .map(|d| self.node_text(d, source))
+            .collect();
+        let Some(first) = aliases.first() else {
+            return;
+        };
+
+        let target = match ty.kind() {
+            "struct_specifier" => {
+                let name = self.get_struct_name(ty, source);
+                if !name.is_empty() {
+                    format!("struct {}", name)
+                } else if let Some(st) = self.extract_struct(ty, source, filename, Some(first)) {
+                    debug!("Found struct: {}", st.name);
+                    let target = format!("struct {}", st.name);
+                    result.structs.insert(st.name.clone(), st);
+                    target
+                } else {
+                    return;
+                }
+            }
+            "type_identifier" => self.node_text(ty, source),
+            _ => return,
+        };
+        for alias in aliases {
+            result.typedefs.insert(alias, target.clone());
+        }
+    }
+
+    /// `anonymous_name` names a struct without a tag (from its typedef)
+    fn extract_struct(
+        &self,
+        node: Node,
+        source: &str,
+        filename: &str,
+        anonymous_name: Option<&str>,
+    ) -> Option<StructDef> {
+        let mut name = anonymous_name.unwrap_or_default().to_string();
         let mut fields = Vec::new();
         let mut referenced_structs = Vec::new();
         let mut has_body = false;
@@ -310,12 +754,17 @@ impl TreeSitterParser {
         let mut cursor = node.walk();
         for child in node.children(&mut cursor) {
             if child.kind() == "field_declaration" {
-                if let Some((field, maybe_ref)) = self.extract_field(child, source) {
-                    fields.push(field);
-                    if let Some(r) = maybe_ref {
-                        refs.push(r);
+                let (type_name, maybe_ref) = self.field_base_type(child, source);
+                // `int a, *b;` declares one field per declarator
+                let mut decl_cursor = child.walk();
+                for declarator in child.children_by_field_name("declarator", &mut decl_cursor) {
+                    if let Some(field) = self.extract_field(declarator, &type_name, source) {
+                        fields.push(field);
                     }
                 }
+                if let Some(r) = maybe_ref {
+                    refs.push(r);
+                }
             }
         }
 
@@ -324,14 +773,10 @@ impl TreeSitterParser {
         (fields, refs)
     }
 
-    fn extract_field(&self, node: Node, source: &str) -> Option<(StructField, Option<String>)> {
-        let mut name = String::new();
+    /// Type shared by every declarator of a field declaration, and the struct it names
+    fn field_base_type(&self, node: Node, source: &str) -> (String, Option<String>) {
         let mut type_name = String::new();
-        let mut is_pointer = false;
-        let mut is_function_ptr = false;
-        let mut func_ptr_signature: Option<String> = None;
-        let mut array_size: Option<String> = None;
-        let mut referenced_struct: Option<String> = None;
+        let mut referenced_struct = None;
 
         let mut cursor = node.walk();
         for child in node.children(&mut cursor) {
@@ -344,43 +789,63 @@ impl TreeSitterParser {
                     type_name = format!("struct {}", struct_name);
                     referenced_struct = Some(struct_name);
                 }
-                "field_identifier" => {
-                    name = self.node_text(child, source);
-                }
-                "pointer_declarator" => {
-                    is_pointer = true;
-                    name = self.extract_field_identifier(child, source);
-                }
-                "array_declarator" => {
-                    let (arr_name, arr_size) = self.extract_array_info(child, source);
-                    name = arr_name;
-                    array_size = arr_size;
-                }
-                "function_declarator" => {
-                    is_function_ptr = true;
-                    is_pointer = true;
-                    name = self.extract_function_name(child, source);
-                    func_ptr_signature = Some(self.node_text(child, source));
-                }
                 _ => {}
             }
         }
 
+        (type_name, referenced_struct)
+    }
+
+    fn extract_field(&self, declarator: Node, type_name: &str, source: &str) -> Option<StructField> {
+        let mut name = String::new();
+        let mut is_pointer = false;
+        let mut is_function_ptr = false;
+        let mut func_ptr_signature: Option<String> = None;
+        let mut array_size: Option<String> = None;
+
+        match declarator.kind() {
+            "field_identifier" => {
+                name = self.node_text(declarator, source);
+            }
+            "pointer_declarator" => {
+                is_pointer = true;
+                // `*names[4]` is an array of pointers
+                match declarator.child_by_field_name("declarator") {
+                    Some(inner) if inner.kind() == "array_declarator" => {
+                        let (arr_name, arr_size) = self.extract_array_info(inner, source);
+                        name = arr_name;
+                        array_size = arr_size;
+                    }
+                    _ => name = self.extract_field_identifier(declarator, source),
+                }
+            }
+            "array_declarator" => {
+                let (arr_name, arr_size) = self.extract_array_info(declarator, source);
+                name = arr_name;
+                array_size = arr_size;
+            }
+            "function_declarator" => {
+                is_function_ptr = true;
+                is_pointer = true;
+                name = self.extract_function_name(declarator, source);
+                func_ptr_signature = Some(self.node_text(declarator, source));
+            }
+            _ => {}
+        }
+
         if name.is_empty() {
             return None;
         }
 
-        Some((
-            StructField {
-                name,
-                type_name,
-                is_pointer,
-                is_function_ptr,
-                func_ptr_signature,
-                array_size,
-            },
-            referenced_struct,
-        ))
+        Some(StructField {
+            name,
+            type_name: type_name.to_string(),
+            is_pointer,
+            is_function_ptr,
+            func_ptr_signature,
+            array_size,
+            typedef_name: None,
+        })
     }
 
     fn extract_field_identifier(&self, node: Node, source: &str) -> String {
@@ -399,33 +864,249 @@ impl TreeSitterParser {
         String::new()
     }
 
+    /// Name and dimensions of an array field
+    ///
+    /// Dimensions are kept as written and joined by `][`, so `a[2][3]` gives
+    /// `"2][3"` and a flexible `data[]` gives `""`.
     fn extract_array_info(&self, node: Node, source: &str) -> (String, Option<String>) {
-        let mut name = String::new();
-        let mut size = None;
+        let size = node
+            .child_by_field_name("size")
+            .map(|n| self.node_text(n, source))
+            .unwrap_or_default();
+        match node.child_by_field_name("declarator") {
+            Some(inner) if inner.kind() == "array_declarator" => {
+                let (name, dims) = self.extract_array_info(inner, source);
+                (name, dims.map(|d| format!("{}][{}", d, size)))
+            }
+            Some(inner) => (self.node_text(inner, source), Some(size)),
+            None => (String::new(), Some(size)),
+        }
+    }
 
-        let mut cursor = node.walk();
-        for child in node.children(&mut cursor) {
-            match child.kind() {
-                "field_identifier" | "identifier" => {
-                    if name.is_empty() {
-                        name = self.node_text(child, source);
+    fn node_text(&self, node: Node, source: &str) -> String {
+        node.utf8_text(source.as_bytes()).unwrap_or("").to_string()
+    }
+}
+
+/// Body of an object-like macro, without line continuations or a trailing comment
+fn macro_value(text: &str) -> String {
+    let mut value = String::new();
+    let mut in_string = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => in_string = !in_string,
+            '\\' if in_string => {
+                // Keep escapes like `\"` whole
+                value.push(c);
+                value.extend(chars.next());
+                continue;
+            }
+            '\\' if matches!(chars.peek(), Some('\n' | '\r')) => {
+                value.push(' ');
+                continue;
+            }
+            '/' if !in_string && matches!(chars.peek(), Some('/' | '*')) => break,
+            _ => {}
+        }
+        value.push(c);
+    }
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Keywords starting an inline assembly statement (`asm_inline` is the kernel's macro for `asm __inline`)
+const ASM_KEYWORDS: &[&str] = &["asm", "__asm__", "__asm", "asm_inline"];
+/// Qualifiers allowed between the keyword and the operands
+const ASM_QUALIFIERS: &[&str] = &[
+    "volatile",
+    "__volatile__",
+    "__volatile",
+    "inline",
+    "__inline",
+    "__inline__",
+    "goto",
+];
+
+/// `source` with every inline assembly statement blanked out
+///
+/// The grammar's error recovery on forms like `__asm__ __volatile__(...)`
+/// or `asm goto(...)` with macro operands can swallow the rest of the
+/// function, dropping the calls after the block and cutting its end line
+/// short. Each `asm (...)` is replaced by spaces, newlines and line
+/// continuations kept, so every position in the file stays the same.
+fn mask_inline_asm(source: &str) -> Cow<'_, str> {
+    let bytes = source.as_bytes();
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'/' if matches!(bytes.get(i + 1), Some(b'/' | b'*')) => i = skip_comment(bytes, i),
+            b'"' | b'\'' => i = skip_literal(bytes, i),
+            c if is_ident_byte(c) => {
+                let end = ident_end(bytes, i);
+                if ASM_KEYWORDS.contains(&&source[i..end]) {
+                    if let Some(close) = asm_operands_end(bytes, end) {
+                        spans.push((i, close));
+                        i = close;
+                        continue;
                     }
                 }
-                "number_literal" => {
-                    size = Some(self.node_text(child, source));
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+    if spans.is_empty() {
+        return Cow::Borrowed(source);
+    }
+
+    let mut masked = bytes.to_vec();
+    for (start, end) in spans {
+        for k in start..end {
+            let continuation = bytes[k] == b'\\' && matches!(bytes.get(k + 1), Some(b'\n' | b'\r'));
+            if !matches!(bytes[k], b'\n' | b'\r') && !continuation {
+                masked[k] = b' ';
+            }
+        }
+    }
+    // Spans start and end on ASCII bytes, so only whole characters are replaced
+    Cow::Owned(String::from_utf8(masked).expect("blanking keeps UTF-8 valid"))
+}
+
+/// `source` with the contents of comments and string/character literals
+/// blanked out
+///
+/// For text searches over source that must not match inside comments or
+/// strings. Newlines are kept, so line numbers and byte offsets stay the
+/// same.
+pub fn mask_comments_and_literals(source: &str) -> String {
+    let bytes = source.as_bytes();
+    let mut masked = bytes.to_vec();
+    let mut i = 0;
+    while i < bytes.len() {
+        let end = match bytes[i] {
+            b'/' if matches!(bytes.get(i + 1), Some(b'/' | b'*')) => skip_comment(bytes, i),
+            b'"' | b'\'' => skip_literal(bytes, i),
+            c if is_ident_byte(c) => {
+                i = ident_end(bytes, i);
+                continue;
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        for byte in &mut masked[i..end] {
+            if !matches!(*byte, b'\n' | b'\r') {
+                *byte = b' ';
+            }
+        }
+        i = end;
+    }
+    // Whole spans are blanked, multi-byte characters included
+    String::from_utf8(masked).expect("blanking keeps UTF-8 valid")
+}
+
+/// End of the parenthesized operands following an `asm` keyword at `i`,
+/// past any qualifiers; `None` if no `(` follows
+fn asm_operands_end(bytes: &[u8], mut i: usize) -> Option<usize> {
+    loop {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'\\') {
+            i += 1;
+        }
+        match *bytes.get(i)? {
+            b'(' => break,
+            c if is_ident_byte(c) => {
+                let end = ident_end(bytes, i);
+                let word = std::str::from_utf8(&bytes[i..end]).ok()?;
+                if !ASM_QUALIFIERS.contains(&word) {
+                    return None;
+                }
+                i = end;
+            }
+            _ => return None,
+        }
+    }
+
+    let mut depth = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'/' if matches!(bytes.get(i + 1), Some(b'/' | b'*')) => {
+                i = skip_comment(bytes, i);
+                continue;
+            }
+            b'"' | b'\'' => {
+                i = skip_literal(bytes, i);
+                continue;
+            }
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
                 }
-                _ => {}
             }
+            _ => {}
         }
+        i += 1;
+    }
+    None
+}
+
+fn is_ident_byte(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+fn ident_end(bytes: &[u8], start: usize) -> usize {
+    start + bytes[start..].iter().take_while(|&&c| is_ident_byte(c)).count()
+}
+
+/// Position after the comment starting at `start`
+fn skip_comment(bytes: &[u8], start: usize) -> usize {
+    let rest = &bytes[start + 2..];
+    let len = match bytes[start + 1] {
+        b'/' => rest.iter().position(|&c| c == b'\n').unwrap_or(rest.len()),
+        _ => rest.windows(2).position(|w| w == b"*/").map_or(rest.len(), |p| p + 2),
+    };
+    start + 2 + len
+}
 
-        (name, size)
+/// Position after the string or character literal starting at `start`
+fn skip_literal(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'\n' => return i,
+            c if c == quote => return i + 1,
+            _ => i += 1,
+        }
     }
+    bytes.len()
+}
 
-    fn node_text(&self, node: Node, source: &str) -> String {
-        node.utf8_text(source.as_bytes()).unwrap_or("").to_string()
+/// Whether a switch arm ending in `stmt` leaves the switch rather than
+/// running into the next arm
+fn ends_arm(stmt: Node) -> bool {
+    match stmt.kind() {
+        "break_statement" | "return_statement" | "goto_statement" | "continue_statement" => true,
+        // `case X: { ...; break; }`
+        "compound_statement" => {
+            let mut cursor = stmt.walk();
+            let last = stmt.named_children(&mut cursor).filter(|s| s.kind() != "comment").last();
+            last.is_some_and(ends_arm)
+        }
+        _ => false,
     }
 }
 
+/// Kernel annotation macros that decorate declarations (`__init`, `__user`, `asmlinkage`, ...)
+fn is_kernel_annotation(name: &str) -> bool {
+    const BARE: &[&str] = &["asmlinkage", "noinline", "notrace", "noinstr", "inline"];
+    (name.starts_with("__") && name.len() > 2) || BARE.contains(&name)
+}
+
 impl Default for TreeSitterParser {
     fn default() -> Self {
         Self::new()
@@ -434,6 +1115,9 @@ impl Default for TreeSitterParser {
 
 impl crate::Parser for TreeSitterParser {
     fn parse(&self, source: &str, filename: &str) -> Result<ParseResult> {
+        if crate::preprocessed::is_preprocessed(std::path::Path::new(filename)) {
+            return crate::preprocessed::parse(source, filename);
+        }
         let mut parser = TreeSitterParser::new();
         parser.parse_source(source, filename)
     }
@@ -508,4 +1192,98 @@ static int my_probe(struct usb_interface *intf) {
         assert!(result.functions.contains_key("my_work_handler"));
         assert!(result.functions.contains_key("my_probe"));
     }
+
+    #[test]
+    fn test_parse_mmio_access() {
+        let source = r#"
+static void my_reset(struct my_dev *dev) {
+    u32 ctrl = readl(dev->regs + REG_CTRL);
+    writel(ctrl | CTRL_RESET, dev->regs + REG_CTRL);
+    iowrite32_rep(dev->fifo, buf, len);
+    writel_relaxed(0, dev->regs + REG_IRQ);
+}
+
+static int my_helper(int x) {
+    return x + 1;
+}
+"#;
+        let mut parser = TreeSitterParser::new();
+        let result = parser.parse_source(source, "test.c").unwrap();
+
+        let reset = &result.functions["my_reset"];
+        assert!(reset.io_access);
+        assert_eq!(reset.io_registers, vec!["dev->regs + REG_CTRL", "dev->fifo", "dev->regs + REG_IRQ"]);
+        let helper = &result.functions["my_helper"];
+        assert!(!helper.io_access);
+        assert!(helper.io_registers.is_empty());
+    }
+
+    #[test]
+    fn test_parse_inline_asm() {
+        let source = r#"
+static void my_sync(struct my_dev *dev)
+{
+    unsigned long flags;
+
+    prepare(dev);
+    __asm__ __volatile__("pushf ; pop %0" : "=rm" (flags) : : "memory");
+    asm volatile("1: rep; nop\n\t"
+                 "jmp 1b /* ) */"
+                 : /* no outputs ) */
+                 : "r" (dev->base), "i" (MY_OFFSET(3))
+                 : "memory");
+    asm goto("jmp %l[out]" : : : : out);
+    notify(dev);
+    return;
+out:
+    finish(dev);
+}
+
+static int my_after(void)
+{
+    return helper();
+}
+"#;
+        let mut parser = TreeSitterParser::new();
+        let result = parser.parse_source(source, "test.c").unwrap();
+
+        let func = &result.functions["my_sync"];
+        for call in ["prepare", "notify", "finish"] {
+            assert!(func.calls.iter().any(|c| c == call), "missing {}", call);
+        }
+        let last_line = source.lines().position(|l| l == "}").unwrap() as u32 + 1;
+        assert_eq!(func.location.as_ref().unwrap().end_line, last_line);
+        assert_eq!(result.functions["my_after"].calls, vec!["helper"]);
+    }
+
+    #[test]
+    fn test_mask_inline_asm() {
+        let source = "#include <asm/io.h>\n\
+                      #define barrier() \\\n  asm volatile(\"\" ::: \"memory\")\n\
+                      const char *s = \"asm(\";\n\
+                      int asmlinkage_x = asm_count(1);\n";
+        let masked = mask_inline_asm(source);
+        assert_eq!(masked.len(), source.len());
+        assert_eq!(masked.lines().count(), source.lines().count());
+        let lines: Vec<&str> = masked.lines().collect();
+        assert_eq!(lines[0], "#include <asm/io.h>");
+        assert_eq!(lines[1], "#define barrier() \\");
+        assert!(lines[2].trim().is_empty());
+        assert_eq!(lines[3], "const char *s = \"asm(\";");
+        assert_eq!(lines[4], "int asmlinkage_x = asm_count(1);");
+
+        assert!(matches!(mask_inline_asm("int x = 1;"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_mask_comments_and_literals() {
+        let source = "a->open(x); // b->open(\n\
+                      /* c->open(\n d->open( */ s = \"e->open(\";\n\
+                      c = '\\''; f->open(y);\n";
+        let masked = mask_comments_and_literals(source);
+        assert_eq!(masked.len(), source.len());
+        assert_eq!(masked.matches("->open(").count(), 2);
+        assert_eq!(masked.lines().count(), source.lines().count());
+        assert!(masked.lines().last().unwrap().ends_with("f->open(y);"));
+    }
 }
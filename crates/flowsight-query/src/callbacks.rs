@@ -0,0 +1,169 @@
+//! Flat report of every callback in the index
+//!
+//! Each callback is joined with what the knowledge base says about it: the
+//! framework and callback it implements, the context the kernel calls it in
+//! and whether it may sleep. The CSV form is meant for spreadsheets.
+
+use crate::QueryEngine;
+use flowsight_core::{AsyncMechanism, FunctionDef, Result};
+use flowsight_knowledge::{FrameworkCallback, KnowledgeBase};
+use std::io::Write;
+
+/// Header row of [`QueryEngine::export_callbacks_csv`]
+const CSV_HEADER: &str = "function,file,line,framework,callback_kind,context,can_sleep";
+
+/// One callback and what it implements
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallbackInfo {
+    pub function: String,
+    pub file: String,
+    pub line: u32,
+    /// Framework or ops struct (e.g. "usb_driver"), or async mechanism (e.g. "work_struct")
+    pub framework: String,
+    /// Callback slot (e.g. "probe"), or "handler" for async handlers
+    pub callback_kind: String,
+    /// Execution context (e.g. "Process", "HardIrq"); empty if unknown
+    pub context: String,
+    pub can_sleep: Option<bool>,
+}
+
+impl QueryEngine {
+    /// Every callback in the index, sorted by file, line and name
+    ///
+    /// An ops-table slot known to the knowledge base wins, then an ops
+    /// assignment of another struct, then an async binding, then
+    /// [`KnowledgeBase::identify_callback`] by name.
+    pub fn callback_report(&self, kb: &KnowledgeBase) -> Vec<CallbackInfo> {
+        let mut rows: Vec<CallbackInfo> = self
+            .get_callbacks()
+            .into_iter()
+            .map(|func| self.describe_callback(func, kb))
+            .collect();
+        rows.sort_by(|a, b| (&a.file, a.line, &a.function).cmp(&(&b.file, b.line, &b.function)));
+        rows
+    }
+
+    /// Write [`Self::callback_report`] as CSV, one row per callback after a header
+    pub fn export_callbacks_csv<W: Write>(&self, kb: &KnowledgeBase, mut writer: W) -> Result<()> {
+        writeln!(writer, "{}", CSV_HEADER)?;
+        for row in self.callback_report(kb) {
+            let line = row.line.to_string();
+            let can_sleep = row.can_sleep.map(|s| s.to_string()).unwrap_or_default();
+            let fields = [
+                row.function.as_str(),
+                &row.file,
+                &line,
+                &row.framework,
+                &row.callback_kind,
+                &row.context,
+                &can_sleep,
+            ];
+            let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+            writeln!(writer, "{}", fields.join(","))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn describe_callback(&self, func: &FunctionDef, kb: &KnowledgeBase) -> CallbackInfo {
+        let (file, line) = func
+            .location
+            .as_ref()
+            .map(|l| (l.file.clone(), l.line))
+            .unwrap_or_default();
+        let mut info = CallbackInfo {
+            function: func.name.clone(),
+            file,
+            line,
+            framework: String::new(),
+            callback_kind: String::new(),
+            context: String::new(),
+            can_sleep: None,
+        };
+        let known = |info: &mut CallbackInfo, framework: &str, kind: &str, callback: &FrameworkCallback| {
+            info.framework = framework.to_string();
+            info.callback_kind = kind.to_string();
+            info.context = format!("{:?}", callback.context);
+            info.can_sleep = Some(callback.context.can_sleep());
+        };
+
+        // "usb_driver.probe" from the ops table the function sits in
+        let slot = func.callback_context.as_deref().and_then(|ctx| ctx.split_once('.'));
+        if let Some((framework, kind)) = slot {
+            if let Some(callback) = kb.get_callback(framework, kind) {
+                known(&mut info, framework, kind, callback);
+                return info;
+            }
+        }
+
+        // The context may name the table variable ("my_driver.probe"); its type is in the index
+        let assignment = self
+            .index
+            .ops_assignments
+            .values()
+            .flatten()
+            .filter(|a| a.function == func.name)
+            .min_by(|a, b| (&a.ops_type, &a.field).cmp(&(&b.ops_type, &b.field)));
+        if let Some(assignment) = assignment {
+            match kb.get_callback(&assignment.ops_type, &assignment.field) {
+                Some(callback) => known(&mut info, &assignment.ops_type, &assignment.field, callback),
+                None => {
+                    info.framework = assignment.ops_type.clone();
+                    info.callback_kind = assignment.field.clone();
+                }
+            }
+            return info;
+        }
+
+        if let Some(indexed) = self.index.get_async_bindings(&func.name).first() {
+            let binding = &indexed.binding;
+            info.framework = mechanism_name(&binding.mechanism);
+            info.callback_kind = "handler".to_string();
+            info.context = format!("{:?}", binding.context);
+            info.can_sleep = Some(binding.context.can_sleep());
+            return info;
+        }
+
+        if let Some((framework, kind, callback)) = kb.identify_callback(&func.name, "") {
+            known(&mut info, framework, kind, callback);
+            return info;
+        }
+
+        // Unknown to the knowledge base; keep whatever the analysis recorded
+        if let Some((framework, kind)) = slot {
+            info.framework = framework.to_string();
+            info.callback_kind = kind.to_string();
+        } else if let Some(ctx) = &func.callback_context {
+            info.callback_kind = ctx.clone();
+        }
+        info
+    }
+}
+
+/// Kernel type behind an async mechanism (e.g. "work_struct")
+pub fn mechanism_name(mechanism: &AsyncMechanism) -> String {
+    let name = match mechanism {
+        AsyncMechanism::WorkQueue { delayed: false } => "work_struct",
+        AsyncMechanism::WorkQueue { delayed: true } => "delayed_work",
+        AsyncMechanism::Timer { high_resolution: false } => "timer_list",
+        AsyncMechanism::Timer { high_resolution: true } => "hrtimer",
+        AsyncMechanism::Interrupt { threaded: false } => "irq",
+        AsyncMechanism::Interrupt { threaded: true } => "threaded_irq",
+        AsyncMechanism::Tasklet => "tasklet",
+        AsyncMechanism::Softirq => "softirq",
+        AsyncMechanism::KThread => "kthread",
+        AsyncMechanism::RcuCallback => "rcu",
+        AsyncMechanism::Notifier => "notifier_block",
+        AsyncMechanism::Custom(name) => name,
+    };
+    name.to_string()
+}
+
+/// Quote a CSV field if it holds a comma, quote or line break (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
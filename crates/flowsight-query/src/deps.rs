@@ -0,0 +1,94 @@
+//! File-level dependency graph
+//!
+//! File A depends on file B when a function defined in A calls one defined
+//! in B, or A includes B. A callee is only linked when exactly one file
+//! defines it, since static functions in different files may share a name.
+//! Files are named relative to the project root when they lie under it.
+
+use crate::QueryEngine;
+use flowsight_analysis::export::file_graph_to_dot;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+impl QueryEngine {
+    /// Edges `(from, to)` of the file dependency graph, sorted and without self-loops
+    pub fn file_dependency_graph(&self) -> Vec<(String, String)> {
+        let mut defined_in: HashMap<&str, Vec<&PathBuf>> = HashMap::new();
+        for (file, names) in &self.index.functions_by_file {
+            for name in names {
+                defined_in.entry(name.as_str()).or_default().push(file);
+            }
+        }
+
+        let mut edges: BTreeSet<(String, String)> = BTreeSet::new();
+        for (file, names) in &self.index.functions_by_file {
+            let callees = names
+                .iter()
+                .filter_map(|name| self.index.functions.get(name))
+                .flat_map(|func| func.calls.iter());
+            for callee in callees {
+                let Some(files) = defined_in.get(callee.as_str()) else {
+                    continue;
+                };
+                if let [target] = files.as_slice() {
+                    edges.insert((self.file_name(file), self.file_name(target)));
+                }
+            }
+        }
+        for (file, headers) in &self.index.includes {
+            for header in headers {
+                edges.insert((self.file_name(file), self.file_name(header)));
+            }
+        }
+
+        edges.into_iter().filter(|(from, to)| from != to).collect()
+    }
+
+    /// The file dependency graph as a Graphviz digraph
+    pub fn file_dependency_dot(&self) -> String {
+        file_graph_to_dot(&self.file_dependency_graph())
+    }
+
+    fn file_name(&self, file: &Path) -> String {
+        self.index
+            .relative_path(file)
+            .unwrap_or_else(|| file.to_path_buf())
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowsight_core::FunctionDef;
+    use flowsight_index::SymbolIndex;
+
+    #[test]
+    fn test_file_dependency_graph() {
+        let func = |name: &str, calls: &[&str]| FunctionDef {
+            name: name.into(),
+            return_type: "int".into(),
+            calls: calls.iter().map(|c| c.to_string()).collect(),
+            complexity: 1,
+            ..Default::default()
+        };
+        let mut index = SymbolIndex::with_root(Path::new("/src/drv"));
+        let main = Path::new("/src/drv/main.c");
+        let hw = Path::new("/src/drv/hw.c");
+        index.add_function(func("drv_probe", &["hw_init", "kzalloc", "drv_local"]), main);
+        index.add_function(func("drv_local", &[]), main);
+        index.add_function(func("hw_init", &["hw_reset", "drv_local"]), hw);
+        index.add_function(func("hw_reset", &[]), hw);
+        index.set_includes(main, vec![PathBuf::from("/src/drv/hw.h")]);
+        index.set_includes(hw, vec![PathBuf::from("/src/drv/hw.h")]);
+        let engine = QueryEngine::with_index(index);
+
+        let edge = |from: &str, to: &str| (from.to_string(), to.to_string());
+        assert_eq!(
+            engine.file_dependency_graph(),
+            vec![edge("hw.c", "hw.h"), edge("hw.c", "main.c"), edge("main.c", "hw.c"), edge("main.c", "hw.h")]
+        );
+        assert!(engine.file_dependency_dot().starts_with("digraph deps {"));
+    }
+}
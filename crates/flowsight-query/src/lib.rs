@@ -2,34 +2,82 @@
 //!
 //! High-level query interface for code analysis.
 
-use flowsight_core::FunctionDef;
+use flowsight_analysis::classification::{ClassifiedEdge, ClassifiedTarget, Confidence};
+use flowsight_core::{AsyncMechanism, CallType, ExecutionContext, FunctionDef, Occurrence, Result, StructDef};
 use flowsight_index::SymbolIndex;
+use flowsight_knowledge::NameNormalizer;
+
+mod callbacks;
+mod deps;
+mod path;
+mod report;
+mod search;
+mod signature;
+mod similarity;
+
+pub use callbacks::{mechanism_name, CallbackInfo};
+pub use search::{closest_names, SearchMode, SymbolMatcher};
+
+/// An incoming edge to a function
+#[derive(Debug, Clone)]
+pub struct CallerEdge {
+    /// Calling (or registering) function
+    pub caller: String,
+    /// Direct call, or async invocation via the registered mechanism
+    pub call_type: CallType,
+}
 
 /// Query engine
 pub struct QueryEngine {
     index: SymbolIndex,
+    /// Also matched on in searches, e.g. to see past a project-wide prefix
+    normalizer: Option<NameNormalizer>,
 }
 
 impl QueryEngine {
     /// Create a new query engine with in-memory index
     pub fn new() -> Self {
+        Self::with_index(SymbolIndex::new())
+    }
+
+    /// Create a query engine over an existing index (e.g. loaded from storage)
+    pub fn with_index(index: SymbolIndex) -> Self {
         Self {
-            index: SymbolIndex::new(),
+            index,
+            normalizer: None,
         }
     }
 
+    /// Match searches against function names mapped through `normalizer` as
+    /// well as the names themselves
+    ///
+    /// e.g. a normalizer dropping `mydriver_` lets a glob search for `probe`
+    /// find `mydriver_probe`.
+    pub fn set_name_normalizer(&mut self, normalizer: NameNormalizer) {
+        self.normalizer = Some(normalizer);
+    }
+
     /// Get mutable access to index for adding symbols
     pub fn index_mut(&mut self) -> &mut SymbolIndex {
         &mut self.index
     }
 
-    /// Search for functions by pattern (simple substring match)
-    pub fn search_functions(&self, pattern: &str) -> Vec<&FunctionDef> {
-        self.index
+    /// Search for functions whose name matches `pattern` under `mode`
+    pub fn search_functions(&self, pattern: &str, mode: SearchMode) -> Result<Vec<&FunctionDef>> {
+        let matcher = SymbolMatcher::new(pattern, mode)?;
+        Ok(self
+            .index
             .functions
             .values()
-            .filter(|f| f.name.contains(pattern))
-            .collect()
+            .filter(|f| {
+                matcher.is_match(&f.name) || self.normalizer.as_ref().is_some_and(|n| matcher.is_match(&n(&f.name)))
+            })
+            .collect())
+    }
+
+    /// Up to `n` known function names closest to `name`, for "did you mean"
+    pub fn suggest(&self, name: &str, n: usize) -> Vec<String> {
+        closest_names(name, self.index.functions.keys().map(String::as_str), n)
     }
 
     /// Get function by name
@@ -37,6 +85,40 @@ impl QueryEngine {
         self.index.get_function(name)
     }
 
+    /// Get struct by name
+    pub fn get_struct(&self, name: &str) -> Option<&StructDef> {
+        self.index.get_struct(name)
+    }
+
+    /// Find all function-pointer fields, as (struct, field) pairs sorted by name
+    pub fn find_structs_with_funcptr_field(&self) -> Vec<(String, String)> {
+        let mut pairs: Vec<(String, String)> = self
+            .index
+            .structs
+            .values()
+            .flat_map(|st| {
+                st.fields
+                    .iter()
+                    .filter(|f| f.is_function_ptr)
+                    .map(move |f| (st.name.clone(), f.name.clone()))
+            })
+            .collect();
+        pairs.sort();
+        pairs
+    }
+
+    /// Get functions carrying an attribute (e.g. "__init"), sorted by name
+    pub fn find_functions_with_attribute(&self, attr: &str) -> Vec<&FunctionDef> {
+        let mut funcs: Vec<&FunctionDef> = self
+            .index
+            .functions
+            .values()
+            .filter(|f| f.has_attribute(attr))
+            .collect();
+        funcs.sort_by(|a, b| a.name.cmp(&b.name));
+        funcs
+    }
+
     /// Get all callback functions
     pub fn get_callbacks(&self) -> Vec<&FunctionDef> {
         self.index
@@ -46,14 +128,106 @@ impl QueryEngine {
             .collect()
     }
 
-    /// Get callers of a function
+    /// Callbacks the kernel calls in `context`, sorted by name
+    ///
+    /// Uses the context analysis recorded on the function, else that of an
+    /// async binding of it in the index.
+    pub fn find_by_context(&self, context: &ExecutionContext) -> Vec<&FunctionDef> {
+        let mut funcs: Vec<&FunctionDef> = self
+            .index
+            .functions
+            .values()
+            .filter(|f| {
+                f.execution_context.as_ref() == Some(context)
+                    || (f.execution_context.is_none()
+                        && self.index.get_async_bindings(&f.name).iter().any(|b| &b.binding.context == context))
+            })
+            .collect();
+        funcs.sort_by(|a, b| a.name.cmp(&b.name));
+        funcs
+    }
+
+    /// Async handlers bound through a mechanism of `kind`, sorted by name
+    ///
+    /// `kind` is a mechanism family ("work queue", "interrupt") or the kernel
+    /// type behind one ("delayed_work", "threaded_irq"), see [`mechanism_name`].
+    pub fn find_by_mechanism(&self, kind: &str) -> Vec<&FunctionDef> {
+        let matches = |m: &AsyncMechanism| m.family() == kind || mechanism_name(m) == kind;
+        let mut funcs: Vec<&FunctionDef> = self
+            .index
+            .functions
+            .values()
+            .filter(|f| {
+                f.async_mechanism.as_ref().is_some_and(matches)
+                    || self.index.get_async_bindings(&f.name).iter().any(|b| matches(&b.binding.mechanism))
+            })
+            .collect();
+        funcs.sort_by(|a, b| a.name.cmp(&b.name));
+        funcs
+    }
+
+    /// Find every function assigned to `ops_type.field` anywhere in the index
+    ///
+    /// e.g. `find_callback_implementations("file_operations", "read")`
+    pub fn find_callback_implementations(&self, ops_type: &str, field: &str) -> Vec<&FunctionDef> {
+        let mut implementations: Vec<&FunctionDef> = Vec::new();
+        for assignment in self.index.get_ops_assignments(ops_type, field) {
+            if let Some(func) = self.index.get_function(&assignment.function) {
+                if !implementations.iter().any(|f| f.name == func.name) {
+                    implementations.push(func);
+                }
+            }
+        }
+        implementations.sort_by(|a, b| a.name.cmp(&b.name));
+        implementations
+    }
+
+    /// Every textual occurrence of `name`, ordered by file and byte offset
+    ///
+    /// Spans are exact, so renaming is a matter of replacing each range.
+    pub fn find_occurrences(&self, name: &str) -> Vec<Occurrence> {
+        let mut occurrences = self.index.get_occurrences(name).to_vec();
+        occurrences.sort_by(|a, b| (&a.file, a.byte_start).cmp(&(&b.file, b.byte_start)));
+        occurrences.dedup();
+        occurrences
+    }
+
+    /// Get callers of a function, including functions that register it as an async handler
     pub fn get_callers(&self, name: &str) -> Vec<String> {
-        self.index
+        let mut callers: Vec<String> = Vec::new();
+        for edge in self.get_caller_edges(name) {
+            if !callers.contains(&edge.caller) {
+                callers.push(edge.caller);
+            }
+        }
+        callers
+    }
+
+    /// Get incoming edges of a function: direct calls plus async registrations
+    pub fn get_caller_edges(&self, name: &str) -> Vec<CallerEdge> {
+        let mut edges: Vec<CallerEdge> = self
+            .index
             .functions
             .values()
-            .filter(|f| f.calls.contains(&name.to_string()))
-            .map(|f| f.name.clone())
-            .collect()
+            .filter(|f| f.calls.iter().any(|c| c == name))
+            .map(|f| CallerEdge {
+                caller: f.name.clone(),
+                call_type: CallType::Direct,
+            })
+            .collect();
+
+        for entry in self.index.get_async_bindings(name) {
+            if let Some(registrar) = &entry.registered_by {
+                edges.push(CallerEdge {
+                    caller: registrar.clone(),
+                    call_type: CallType::Async {
+                        mechanism: entry.binding.mechanism.clone(),
+                    },
+                });
+            }
+        }
+
+        edges
     }
 
     /// Get callees of a function
@@ -64,6 +238,49 @@ impl QueryEngine {
             .unwrap_or_default()
     }
 
+    /// Classified call sites whose overall confidence is at least `min`
+    ///
+    /// Rows of the same call site are merged into one edge whose confidence
+    /// is its least certain target. Sorted by caller, then call site.
+    pub fn edges_by_confidence(&self, min: Confidence) -> Vec<ClassifiedEdge> {
+        let mut callers: Vec<&String> = self.index.classified_calls.keys().collect();
+        callers.sort();
+
+        let mut edges: Vec<ClassifiedEdge> = Vec::new();
+        for caller in callers {
+            let start = edges.len();
+            for call in self.index.get_classified_calls(caller) {
+                let target = ClassifiedTarget {
+                    name: call.target.clone(),
+                    confidence: call.confidence.into(),
+                    reason: call.reason.clone(),
+                };
+                match edges[start..].iter_mut().find(|e| e.call_site == call.call_site) {
+                    Some(edge) => {
+                        if !target.confidence.is_at_least(edge.overall_confidence) {
+                            edge.overall_confidence = target.confidence;
+                        }
+                        edge.targets.push(target);
+                    }
+                    None => edges.push(ClassifiedEdge {
+                        caller: caller.clone(),
+                        call_site: call.call_site.clone(),
+                        overall_confidence: target.confidence,
+                        targets: vec![target],
+                    }),
+                }
+            }
+            // "L9" before "L10"
+            edges[start..].sort_by_key(|e| {
+                let line = e.call_site.strip_prefix('L').and_then(|l| l.parse::<u32>().ok());
+                (line, e.call_site.clone())
+            });
+        }
+
+        edges.retain(|e| e.overall_confidence.is_at_least(min));
+        edges
+    }
+
     /// Get the index (for direct access)
     pub fn index(&self) -> &SymbolIndex {
         &self.index
@@ -75,3 +292,462 @@ impl Default for QueryEngine {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowsight_core::{
+        AsyncBinding, AsyncMechanism, ClassifiedCall, ConfidenceLevel, ExecutionContext, Location,
+        OccurrenceKind, OpsAssignment, StructField,
+    };
+    use flowsight_index::{IndexStorage, IndexedAsyncBinding};
+    use std::path::Path;
+
+    fn func(name: &str) -> FunctionDef {
+        FunctionDef {
+            name: name.into(),
+            return_type: "int".into(),
+            ..Default::default()
+        }
+    }
+
+    fn func_at(name: &str, file: &str, line: u32) -> FunctionDef {
+        FunctionDef {
+            location: Some(Location::new(file, line, 0)),
+            ..func(name)
+        }
+    }
+
+    fn field(name: &str, is_function_ptr: bool) -> StructField {
+        StructField {
+            name: name.into(),
+            type_name: "int".into(),
+            is_pointer: is_function_ptr,
+            is_function_ptr,
+            func_ptr_signature: None,
+            array_size: None,
+            typedef_name: None,
+        }
+    }
+
+    #[test]
+    fn test_funcptr_fields_survive_storage() {
+        let st = StructDef {
+            name: "my_ops".into(),
+            fields: vec![field("open", true), field("count", false), field("release", true)],
+            location: None,
+            referenced_structs: vec![],
+        };
+
+        let storage = IndexStorage::in_memory().unwrap();
+        storage.store_struct(&st).unwrap();
+
+        let engine = QueryEngine::with_index(storage.load_index().unwrap());
+        assert_eq!(engine.get_struct("my_ops").unwrap().fields.len(), 3);
+        assert_eq!(
+            engine.find_structs_with_funcptr_field(),
+            vec![
+                ("my_ops".to_string(), "open".to_string()),
+                ("my_ops".to_string(), "release".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_async_handler_callers() {
+        let storage = IndexStorage::in_memory().unwrap();
+        storage
+            .store_async_binding(&IndexedAsyncBinding {
+                binding: AsyncBinding {
+                    mechanism: AsyncMechanism::WorkQueue { delayed: false },
+                    variable: "priv->work".into(),
+                    handler: "my_work_handler".into(),
+                    bind_location: Some(Location::new("drv.c", 42, 4)),
+                    trigger_locations: vec![],
+                    context: ExecutionContext::Process,
+                },
+                registered_by: Some("my_probe".into()),
+            })
+            .unwrap();
+
+        let engine = QueryEngine::with_index(storage.load_index().unwrap());
+        assert_eq!(engine.get_callers("my_work_handler"), vec!["my_probe"]);
+
+        let edges = engine.get_caller_edges("my_work_handler");
+        assert_eq!(edges.len(), 1);
+        assert!(matches!(
+            edges[0].call_type,
+            CallType::Async {
+                mechanism: AsyncMechanism::WorkQueue { delayed: false }
+            }
+        ));
+
+        storage.remove_file(Path::new("drv.c")).unwrap();
+        assert!(storage.get_async_bindings("my_work_handler").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_callback_implementations() {
+        let assign = |variable: &str, field: &str, function: &str, file: &str| OpsAssignment {
+            ops_type: "file_operations".into(),
+            variable: variable.into(),
+            field: field.into(),
+            function: function.into(),
+            location: Some(Location::new(file, 10, 4)),
+        };
+
+        let storage = IndexStorage::in_memory().unwrap();
+        storage.store_function(&func_at("foo_read", "foo.c", 1), Path::new("foo.c")).unwrap();
+        storage.store_function(&func_at("bar_read", "bar.c", 1), Path::new("bar.c")).unwrap();
+        storage.store_ops_assignment(&assign("foo_fops", "read", "foo_read", "foo.c")).unwrap();
+        storage.store_ops_assignment(&assign("bar_fops", "read", "bar_read", "bar.c")).unwrap();
+        storage.store_ops_assignment(&assign("bar_fops", "owner", "THIS_MODULE", "bar.c")).unwrap();
+
+        let engine = QueryEngine::with_index(storage.load_index().unwrap());
+        let names: Vec<&str> = engine
+            .find_callback_implementations("struct file_operations", "read")
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["bar_read", "foo_read"]);
+        assert!(engine.find_callback_implementations("file_operations", "owner").is_empty());
+
+        storage.remove_file(Path::new("bar.c")).unwrap();
+        assert_eq!(storage.get_ops_assignments("file_operations", "read").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_find_occurrences() {
+        let occ = |file: &str, byte_start: usize, kind| Occurrence {
+            name: "my_read".into(),
+            file: file.into(),
+            byte_start,
+            byte_end: byte_start + "my_read".len(),
+            line: 1,
+            column: 0,
+            kind,
+        };
+
+        let storage = IndexStorage::in_memory().unwrap();
+        storage.store_occurrence(&occ("b.c", 40, OccurrenceKind::Reference)).unwrap();
+        storage.store_occurrence(&occ("a.c", 90, OccurrenceKind::Call)).unwrap();
+        storage.store_occurrence(&occ("a.c", 11, OccurrenceKind::Definition)).unwrap();
+
+        let engine = QueryEngine::with_index(storage.load_index().unwrap());
+        let found: Vec<(String, usize, OccurrenceKind)> = engine
+            .find_occurrences("my_read")
+            .into_iter()
+            .map(|o| (o.file, o.byte_start, o.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("a.c".to_string(), 11, OccurrenceKind::Definition),
+                ("a.c".to_string(), 90, OccurrenceKind::Call),
+                ("b.c".to_string(), 40, OccurrenceKind::Reference),
+            ]
+        );
+        assert!(engine.find_occurrences("other").is_empty());
+
+        storage.remove_file(Path::new("a.c")).unwrap();
+        assert_eq!(storage.get_occurrences("my_read").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_edges_by_confidence() {
+        let call = |caller: &str, call_site: &str, target: &str, confidence, file: &str| ClassifiedCall {
+            caller: caller.into(),
+            call_site: call_site.into(),
+            target: target.into(),
+            confidence,
+            reason: "test".into(),
+            file: file.into(),
+        };
+
+        let storage = IndexStorage::in_memory().unwrap();
+        for row in [
+            call("probe", "L12", "setup", ConfidenceLevel::Certain, "drv.c"),
+            call("probe", "L9", "fast_read", ConfidenceLevel::Possible, "drv.c"),
+            call("probe", "L9", "slow_read", ConfidenceLevel::Possible, "drv.c"),
+            call("irq", "L30", "handler", ConfidenceLevel::Unknown, "irq.c"),
+        ] {
+            storage.store_classified_call(&row).unwrap();
+        }
+
+        let engine = QueryEngine::with_index(storage.load_index().unwrap());
+        let edges = |min| -> Vec<(String, String, usize, Confidence)> {
+            engine
+                .edges_by_confidence(min)
+                .into_iter()
+                .map(|e| (e.caller, e.call_site, e.targets.len(), e.overall_confidence))
+                .collect()
+        };
+        assert_eq!(
+            edges(Confidence::Unknown),
+            vec![
+                ("irq".to_string(), "L30".to_string(), 1, Confidence::Unknown),
+                ("probe".to_string(), "L9".to_string(), 2, Confidence::Possible),
+                ("probe".to_string(), "L12".to_string(), 1, Confidence::Certain),
+            ]
+        );
+        assert_eq!(edges(Confidence::Certain).len(), 1);
+
+        storage.remove_file(Path::new("irq.c")).unwrap();
+        assert!(storage.get_classified_calls("irq").unwrap().is_empty());
+        assert_eq!(storage.get_classified_calls("probe").unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_export_callbacks_csv() {
+        let callback = |name: &str, file: &str, line: u32, context: &str| FunctionDef {
+            is_callback: true,
+            callback_context: Some(context.into()),
+            ..func_at(name, file, line)
+        };
+
+        let mut engine = QueryEngine::new();
+        let index = engine.index_mut();
+        index.add_function(callback("my_probe", "usb,drv.c", 20, "my_drv.probe"), Path::new("usb,drv.c"));
+        index.add_function(callback("my_work", "usb,drv.c", 5, "async_WorkQueue"), Path::new("usb,drv.c"));
+        index.add_function(callback("odd_frob", "odd.c", 3, "odd_table.frob"), Path::new("odd.c"));
+        index.add_function(func_at("helper", "odd.c", 1), Path::new("odd.c"));
+        index.add_ops_assignment(OpsAssignment {
+            ops_type: "usb_driver".into(),
+            variable: "my_drv".into(),
+            field: "probe".into(),
+            function: "my_probe".into(),
+            location: None,
+        });
+        index.add_async_binding(
+            AsyncBinding {
+                mechanism: AsyncMechanism::WorkQueue { delayed: true },
+                variable: "priv->dwork".into(),
+                handler: "my_work".into(),
+                bind_location: None,
+                trigger_locations: vec![],
+                context: ExecutionContext::Process,
+            },
+            None,
+        );
+
+        let mut out = Vec::new();
+        engine
+            .export_callbacks_csv(&flowsight_knowledge::KnowledgeBase::builtin(), &mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "function,file,line,framework,callback_kind,context,can_sleep\n\
+             odd_frob,odd.c,3,odd_table,frob,,\n\
+             my_work,\"usb,drv.c\",5,delayed_work,handler,Process,true\n\
+             my_probe,\"usb,drv.c\",20,usb_driver,probe,Process,true\n"
+        );
+    }
+
+    #[test]
+    fn test_find_by_context_and_mechanism() {
+        let callback = |name: &str, mechanism: Option<AsyncMechanism>, context: Option<ExecutionContext>| FunctionDef {
+            is_callback: true,
+            async_mechanism: mechanism,
+            execution_context: context,
+            ..func(name)
+        };
+        let mut engine = QueryEngine::new();
+        let index = engine.index_mut();
+        let work = AsyncMechanism::WorkQueue { delayed: true };
+        index.add_function(callback("my_work", Some(work), Some(ExecutionContext::Process)), Path::new("drv.c"));
+        index.add_function(callback("my_probe", None, Some(ExecutionContext::Process)), Path::new("drv.c"));
+        // Indexed before the structured fields existed: only the binding knows
+        index.add_function(callback("my_timer", None, None), Path::new("drv.c"));
+        index.add_async_binding(
+            AsyncBinding {
+                mechanism: AsyncMechanism::Timer { high_resolution: false },
+                variable: "priv->timer".into(),
+                handler: "my_timer".into(),
+                bind_location: None,
+                trigger_locations: vec![],
+                context: ExecutionContext::SoftIrq,
+            },
+            None,
+        );
+
+        let names = |funcs: Vec<&FunctionDef>| -> Vec<String> { funcs.iter().map(|f| f.name.clone()).collect() };
+        assert_eq!(names(engine.find_by_context(&ExecutionContext::Process)), vec!["my_probe", "my_work"]);
+        assert_eq!(names(engine.find_by_context(&ExecutionContext::SoftIrq)), vec!["my_timer"]);
+        assert!(engine.find_by_context(&ExecutionContext::HardIrq).is_empty());
+        assert_eq!(names(engine.find_by_mechanism("work queue")), vec!["my_work"]);
+        assert_eq!(names(engine.find_by_mechanism("delayed_work")), vec!["my_work"]);
+        assert_eq!(names(engine.find_by_mechanism("timer_list")), vec!["my_timer"]);
+        assert!(engine.find_by_mechanism("tasklet").is_empty());
+
+        assert_eq!("softirq".parse::<ExecutionContext>().unwrap(), ExecutionContext::SoftIrq);
+        assert_eq!("hard_irq".parse::<ExecutionContext>().unwrap(), ExecutionContext::HardIrq);
+        assert!("nmi".parse::<ExecutionContext>().is_err());
+    }
+
+    #[test]
+    fn test_name_normalizer() {
+        let mut engine = QueryEngine::new();
+        for name in ["mydriver_probe", "mydriver_read", "probe_helper"] {
+            engine.index_mut().add_function(func(name), Path::new("drv.c"));
+        }
+        let names = |engine: &QueryEngine| -> Vec<String> {
+            let mut names: Vec<String> = engine
+                .search_functions("probe", SearchMode::Glob)
+                .unwrap()
+                .iter()
+                .map(|f| f.name.clone())
+                .collect();
+            names.sort();
+            names
+        };
+        assert!(names(&engine).is_empty());
+
+        engine.set_name_normalizer(Box::new(|name: &str| name.trim_start_matches("mydriver_").to_string()));
+        assert_eq!(names(&engine), vec!["mydriver_probe"]);
+    }
+
+    #[test]
+    fn test_html_report() {
+        let mut engine = QueryEngine::new();
+        engine.index_mut().add_function(
+            FunctionDef {
+                is_callback: true,
+                ..func_at("my_irq", "drv.c", 7)
+            },
+            Path::new("drv.c"),
+        );
+        engine.index_mut().add_async_binding(
+            AsyncBinding {
+                mechanism: AsyncMechanism::Interrupt { threaded: false },
+                variable: String::new(),
+                handler: "my_irq".into(),
+                bind_location: None,
+                trigger_locations: vec![],
+                context: ExecutionContext::HardIrq,
+            },
+            None,
+        );
+        let findings = vec![flowsight_analysis::finding::Finding::IrqSleep {
+            file: "drv.c".into(),
+            line: 9,
+            handler: "my_irq".into(),
+            api: "msleep".into(),
+            path: vec!["my_irq".into()],
+        }];
+        let tree = flowsight_core::FlowNode {
+            id: "my_irq".into(),
+            name: "my_irq".into(),
+            display_name: "my_irq()".into(),
+            node_type: flowsight_core::FlowNodeType::EntryPoint,
+            ..Default::default()
+        };
+
+        let kb = flowsight_knowledge::KnowledgeBase::builtin();
+        let html = engine.html_report("drivers/<x>", &kb, &findings, &[tree]);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h1>FlowSight report: drivers/&lt;x&gt;</h1>"));
+        assert!(html.contains("<tr><td>Async handlers</td><td>1</td></tr>"), "{}", html);
+        assert!(html.contains(
+            "<td><code>my_irq()</code></td><td>drv.c:7</td><td>irq</td><td>handler</td><td>HardIrq</td>"
+        ));
+        assert!(html.contains("<tr data-severity=\"error\">"));
+        assert!(html.contains("<summary>my_irq()</summary>\n<div class=\"diagram\">\n<svg"));
+        // Nothing is loaded from elsewhere
+        assert!(!html.contains("src=") && !html.contains("<link"));
+    }
+
+    #[test]
+    fn test_call_path_across_async() {
+        let calling = |name: &str, lines: (u32, u32), calls: &[&str]| FunctionDef {
+            location: Some(Location::with_range("drv.c", lines.0, 0, lines.1, 1)),
+            calls: calls.iter().map(|c| c.to_string()).collect(),
+            ..func(name)
+        };
+        let mut index = SymbolIndex::new();
+        for f in [
+            calling("my_probe", (1, 5), &["INIT_WORK"]),
+            calling("my_irq", (10, 14), &["schedule_work"]),
+            calling("my_work", (20, 24), &["my_helper"]),
+            calling("my_helper", (30, 32), &[]),
+        ] {
+            index.add_function(f, Path::new("drv.c"));
+        }
+        index.add_async_binding(
+            AsyncBinding {
+                mechanism: AsyncMechanism::WorkQueue { delayed: false },
+                variable: "priv->work".into(),
+                handler: "my_work".into(),
+                bind_location: Some(Location::new("drv.c", 3, 4)),
+                trigger_locations: vec![Location::new("drv.c", 12, 4)],
+                context: ExecutionContext::Process,
+            },
+            Some("my_probe".into()),
+        );
+        let engine = QueryEngine::with_index(index);
+
+        // The work runs once my_irq schedules it, not when my_probe sets it up
+        assert_eq!(engine.call_path("my_irq", "my_helper").unwrap(), vec!["my_irq", "my_work", "my_helper"]);
+        assert_eq!(engine.call_path("my_probe", "my_helper"), None);
+        assert!(engine.async_edge("my_irq", "my_work").is_some());
+        assert!(engine.async_edge("my_work", "my_helper").is_none());
+        assert_eq!(engine.call_path("my_helper", "my_helper").unwrap(), vec!["my_helper"]);
+    }
+
+    #[test]
+    fn test_similarity() {
+        let calling = |name: &str, calls: &[&str], complexity: u32| FunctionDef {
+            calls: calls.iter().map(|c| c.to_string()).collect(),
+            complexity,
+            ..func(name)
+        };
+        let mut index = SymbolIndex::new();
+        for f in [
+            calling("a_open", &["mutex_lock", "kzalloc", "mutex_unlock"], 3),
+            calling("b_open", &["mutex_lock", "kzalloc", "mutex_unlock"], 3),
+            calling("c_open", &["mutex_lock", "kmalloc", "mutex_unlock"], 2),
+            calling("irq", &["readl", "writel"], 6),
+        ] {
+            index.add_function(f, Path::new("drv.c"));
+        }
+        let engine = QueryEngine::with_index(index);
+
+        assert_eq!(engine.similarity("a_open", "b_open"), 1.0);
+        assert!(engine.similarity("a_open", "c_open") > engine.similarity("a_open", "irq"));
+        assert_eq!(engine.similarity("a_open", "missing"), 0.0);
+
+        let names: Vec<&str> = engine.most_similar("a_open", 2).iter().map(|(f, _)| f.name.as_str()).collect();
+        assert_eq!(names, vec!["b_open", "c_open"]);
+    }
+
+    #[test]
+    fn test_find_by_param_type() {
+        let taking = |name: &str, params: &[&str]| FunctionDef {
+            params: params
+                .iter()
+                .map(|t| flowsight_core::Parameter {
+                    name: "arg".into(),
+                    type_name: t.to_string(),
+                })
+                .collect(),
+            ..func(name)
+        };
+        let mut index = SymbolIndex::new();
+        for f in [
+            taking("my_probe", &["struct usb_interface*", "const struct usb_device_id*"]),
+            taking("my_disconnect", &["struct usb_interface *"]),
+            taking("my_lookup", &["struct usb_interface**"]),
+            taking("my_irq", &["int", "void*"]),
+        ] {
+            index.add_function(f, Path::new("drv.c"));
+        }
+        let engine = QueryEngine::with_index(index);
+
+        let names = |type_name: &str| -> Vec<String> {
+            engine.find_by_param_type(type_name).iter().map(|f| f.name.clone()).collect()
+        };
+        assert_eq!(names("struct usb_interface *"), vec!["my_disconnect", "my_probe"]);
+        assert_eq!(names("struct usb_device_id *"), vec!["my_probe"]);
+        assert_eq!(names("struct usb_interface **"), vec!["my_lookup"]);
+        assert!(names("struct usb_interface").is_empty());
+    }
+}
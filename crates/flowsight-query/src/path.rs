@@ -0,0 +1,73 @@
+//! Shortest call paths, across async hand-offs
+//!
+//! Direct edges come from each function's calls. An async binding adds an
+//! edge to its handler from every function that triggers it, or from the
+//! function that registered it when no trigger is known (e.g. `request_irq`).
+
+use crate::QueryEngine;
+use flowsight_analysis::graph::CallGraph;
+use flowsight_core::{AsyncBinding, Location};
+use flowsight_index::IndexedAsyncBinding;
+use std::path::Path;
+
+impl QueryEngine {
+    /// Shortest chain of functions from `from` to `to`, both included
+    pub fn call_path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let graph = self.call_graph();
+        let path = graph.shortest_path(from, to)?;
+        Some(path.into_iter().map(String::from).collect())
+    }
+
+    /// Call graph of the index, with an edge from each async trigger to its handler
+    pub fn call_graph(&self) -> CallGraph {
+        let mut graph = CallGraph::new();
+        for func in self.index.functions.values() {
+            graph.add_function(&func.name);
+            for callee in &func.calls {
+                graph.add_edge(&func.name, callee);
+            }
+        }
+        for indexed in self.index.async_bindings.values().flatten() {
+            for caller in self.starters(indexed) {
+                graph.add_edge(&caller, &indexed.binding.handler);
+            }
+        }
+        graph
+    }
+
+    /// The async binding through which `caller` starts `handler`, if any
+    pub fn async_edge(&self, caller: &str, handler: &str) -> Option<&AsyncBinding> {
+        self.index
+            .get_async_bindings(handler)
+            .iter()
+            .find(|indexed| self.starters(indexed).iter().any(|f| f == caller))
+            .map(|indexed| &indexed.binding)
+    }
+
+    /// Functions containing a trigger of the binding, else the one registering it
+    fn starters(&self, indexed: &IndexedAsyncBinding) -> Vec<String> {
+        let triggers: Vec<String> = indexed
+            .binding
+            .trigger_locations
+            .iter()
+            .filter_map(|loc| self.enclosing_function(loc))
+            .collect();
+        if triggers.is_empty() {
+            indexed.registered_by.iter().cloned().collect()
+        } else {
+            triggers
+        }
+    }
+
+    /// Function whose body contains `loc`
+    fn enclosing_function(&self, loc: &Location) -> Option<String> {
+        if loc.file.is_empty() {
+            return None;
+        }
+        self.index
+            .get_functions_in_file(Path::new(&loc.file))
+            .into_iter()
+            .find(|f| f.location.as_ref().is_some_and(|l| l.line <= loc.line && loc.line <= l.end_line))
+            .map(|f| f.name.clone())
+    }
+}
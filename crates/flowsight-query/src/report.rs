@@ -0,0 +1,204 @@
+//! Self-contained HTML report of an indexed project
+//!
+//! One page with the index statistics, the callback table, checker findings
+//! and a diagram per entry point. Styles and the small script that filters
+//! findings are inlined and diagrams embedded as SVG, so the file opens
+//! offline and can be attached to a review as is.
+
+use crate::QueryEngine;
+use flowsight_analysis::export::{escape_xml, flow_tree_to_mermaid, flow_tree_to_svg};
+use flowsight_analysis::finding::{Finding, Severity};
+use flowsight_core::FlowNode;
+use flowsight_knowledge::KnowledgeBase;
+use std::fmt::Write;
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 0 auto; max-width: 1200px; padding: 0 24px 48px; color: #222; }
+h1 { border-bottom: 2px solid #ddd; padding-bottom: 8px; }
+h2 { margin-top: 40px; }
+nav a { margin-right: 16px; }
+table { border-collapse: collapse; width: 100%; font-size: 14px; }
+th, td { border: 1px solid #ddd; padding: 4px 8px; text-align: left; vertical-align: top; }
+th { background: #f4f4f4; }
+code, pre { font-family: ui-monospace, monospace; }
+.stats td:last-child { text-align: right; }
+.stats { width: auto; }
+.error { color: #b00020; font-weight: bold; }
+.warning { color: #a65e00; font-weight: bold; }
+.note { color: #555; }
+.sleep { color: #2e7d32; }
+.atomic { color: #b00020; }
+.suggestion { color: #555; font-size: 13px; }
+.filters { margin-bottom: 8px; }
+details { margin: 8px 0; }
+summary { cursor: pointer; font-family: ui-monospace, monospace; }
+.diagram { overflow-x: auto; border: 1px solid #eee; padding: 8px; }
+"#;
+
+const SCRIPT: &str = r#"
+const text = document.getElementById('finding-text');
+const severity = document.getElementById('finding-severity');
+function filterFindings() {
+  const needle = text.value.toLowerCase();
+  for (const row of document.querySelectorAll('#findings tbody tr')) {
+    row.hidden = !row.textContent.toLowerCase().includes(needle)
+      || (severity.value !== '' && row.dataset.severity !== severity.value);
+  }
+}
+if (text) {
+  text.addEventListener('input', filterFindings);
+  severity.addEventListener('change', filterFindings);
+}
+function toggleFlows(open) {
+  for (const d of document.querySelectorAll('#flows > details')) d.open = open;
+}
+"#;
+
+impl QueryEngine {
+    /// Render the index, `findings` and one diagram per tree of `flow_trees`
+    /// as a single HTML page headed `title`
+    pub fn html_report(
+        &self,
+        title: &str,
+        kb: &KnowledgeBase,
+        findings: &[Finding],
+        flow_trees: &[FlowNode],
+    ) -> String {
+        let title = escape_xml(title);
+        let mut out = String::new();
+        let _ = writeln!(out, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">");
+        let _ = writeln!(out, "<title>FlowSight report: {}</title>", title);
+        let _ = writeln!(out, "<style>{}</style>\n</head>\n<body>", STYLE);
+        let _ = writeln!(out, "<h1>FlowSight report: {}</h1>", title);
+        out.push_str(
+            "<nav><a href=\"#stats\">Index</a><a href=\"#callbacks\">Callbacks</a>\
+             <a href=\"#findings\">Findings</a><a href=\"#flows\">Flows</a></nav>\n",
+        );
+
+        self.write_stats(&mut out);
+        self.write_callbacks(&mut out, kb);
+        write_findings(&mut out, findings);
+        write_flows(&mut out, flow_trees);
+
+        let _ = writeln!(out, "<script>{}</script>\n</body>\n</html>", SCRIPT);
+        out
+    }
+
+    fn write_stats(&self, out: &mut String) {
+        let stats = self.index.stats();
+        out.push_str("<h2 id=\"stats\">Index</h2>\n<table class=\"stats\">\n");
+        let mut row = |label: &str, value: usize| {
+            let _ = writeln!(out, "<tr><td>{}</td><td>{}</td></tr>", escape_xml(label), value);
+        };
+        row("Files", stats.total_files);
+        row("Functions", stats.total_functions);
+        row("Structs", stats.total_structs);
+        row("Async handlers", stats.async_handlers);
+        for (mechanism, count) in &stats.async_by_mechanism {
+            row(&format!("\u{2003}{}", mechanism), *count);
+        }
+        row("Callbacks", stats.callbacks);
+        out.push_str("</table>\n");
+    }
+
+    fn write_callbacks(&self, out: &mut String, kb: &KnowledgeBase) {
+        let callbacks = self.callback_report(kb);
+        let _ = writeln!(out, "<h2 id=\"callbacks\">Callbacks ({})</h2>", callbacks.len());
+        if callbacks.is_empty() {
+            out.push_str("<p>No callbacks found.</p>\n");
+            return;
+        }
+        out.push_str(
+            "<table>\n<thead><tr><th>Function</th><th>Location</th><th>Framework</th>\
+             <th>Callback</th><th>Context</th><th>Can sleep</th></tr></thead>\n<tbody>\n",
+        );
+        for cb in &callbacks {
+            let can_sleep = match cb.can_sleep {
+                Some(true) => "<span class=\"sleep\">yes</span>",
+                Some(false) => "<span class=\"atomic\">no</span>",
+                None => "",
+            };
+            let _ = writeln!(
+                out,
+                "<tr><td><code>{}()</code></td><td>{}:{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape_xml(&cb.function),
+                escape_xml(&cb.file),
+                cb.line,
+                escape_xml(&cb.framework),
+                escape_xml(&cb.callback_kind),
+                escape_xml(&cb.context),
+                can_sleep
+            );
+        }
+        out.push_str("</tbody>\n</table>\n");
+    }
+}
+
+fn write_findings(out: &mut String, findings: &[Finding]) {
+    let _ = writeln!(out, "<h2>Findings ({})</h2>", findings.len());
+    if findings.is_empty() {
+        out.push_str("<p id=\"findings\">No findings.</p>\n");
+        return;
+    }
+    out.push_str(
+        "<div class=\"filters\"><input id=\"finding-text\" type=\"search\" placeholder=\"Filter\">\n\
+         <select id=\"finding-severity\"><option value=\"\">All severities</option>\
+         <option value=\"error\">Error</option><option value=\"warning\">Warning</option>\
+         <option value=\"note\">Note</option></select></div>\n\
+         <table id=\"findings\">\n<thead><tr><th>Severity</th><th>Rule</th><th>Location</th>\
+         <th>Message</th></tr></thead>\n<tbody>\n",
+    );
+    for finding in findings {
+        let severity = severity_name(finding.rule().severity);
+        let loc = finding.location();
+        let mut message = escape_xml(&finding.message());
+        if let Some(suggestion) = finding.suggestion() {
+            let _ = write!(message, "<div class=\"suggestion\">{}</div>", escape_xml(&suggestion));
+        }
+        let _ = writeln!(
+            out,
+            "<tr data-severity=\"{s}\"><td class=\"{s}\">{s}</td><td><code>{}</code></td>\
+             <td>{}:{}</td><td>{}</td></tr>",
+            finding.rule().id,
+            escape_xml(&loc.file),
+            loc.line,
+            message,
+            s = severity
+        );
+    }
+    out.push_str("</tbody>\n</table>\n");
+}
+
+fn write_flows(out: &mut String, flow_trees: &[FlowNode]) {
+    let _ = writeln!(out, "<h2>Flows ({})</h2>", flow_trees.len());
+    if flow_trees.is_empty() {
+        out.push_str("<p id=\"flows\">No entry points found.</p>\n");
+        return;
+    }
+    out.push_str(
+        "<p><button onclick=\"toggleFlows(true)\">Expand all</button> \
+         <button onclick=\"toggleFlows(false)\">Collapse all</button></p>\n<div id=\"flows\">\n",
+    );
+    for tree in flow_trees {
+        let _ = writeln!(
+            out,
+            "<details>\n<summary>{}</summary>\n<div class=\"diagram\">\n{}</div>",
+            escape_xml(&tree.display_name),
+            flow_tree_to_svg(tree)
+        );
+        let _ = writeln!(
+            out,
+            "<details><summary>Mermaid source</summary><pre>{}</pre></details>\n</details>",
+            escape_xml(&flow_tree_to_mermaid(tree))
+        );
+    }
+    out.push_str("</div>\n");
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+    }
+}
@@ -0,0 +1,174 @@
+//! Symbol name matching for searches
+//!
+//! Patterns are plain substrings, regexes (`^ext4_.*_iomap$`) or globs
+//! (`usb_*_probe`). Regexes run on the `regex` crate, which matches in linear
+//! time, so a hostile pattern cannot backtrack forever; the compiled size is
+//! capped as well so it cannot exhaust memory either.
+//!
+//! Names that match nothing get "did you mean" suggestions by edit distance.
+
+use flowsight_core::{Error, Result};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Upper bound on a compiled pattern (program and lazy DFA), in bytes
+const PATTERN_SIZE_LIMIT: usize = 1 << 20;
+
+/// How a search pattern is interpreted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// Name contains the pattern
+    #[default]
+    Substring,
+    /// Pattern is a regular expression, matched anywhere unless anchored
+    Regex,
+    /// Whole name matches a glob: `*` any run, `?` one character
+    Glob,
+}
+
+/// A compiled search pattern
+#[derive(Debug, Clone)]
+pub enum SymbolMatcher {
+    Substring { pattern: String, ignore_case: bool },
+    Pattern(Regex),
+}
+
+impl SymbolMatcher {
+    /// Compile a case-sensitive matcher
+    pub fn new(pattern: &str, mode: SearchMode) -> Result<Self> {
+        Self::with_case(pattern, mode, false)
+    }
+
+    /// Compile a matcher, optionally ignoring case
+    pub fn with_case(pattern: &str, mode: SearchMode, ignore_case: bool) -> Result<Self> {
+        let source = match mode {
+            SearchMode::Substring => {
+                let pattern = if ignore_case {
+                    pattern.to_lowercase()
+                } else {
+                    pattern.to_string()
+                };
+                return Ok(Self::Substring {
+                    pattern,
+                    ignore_case,
+                });
+            }
+            SearchMode::Regex => pattern.to_string(),
+            SearchMode::Glob => glob_to_regex(pattern),
+        };
+
+        RegexBuilder::new(&source)
+            .case_insensitive(ignore_case)
+            .size_limit(PATTERN_SIZE_LIMIT)
+            .dfa_size_limit(PATTERN_SIZE_LIMIT)
+            .build()
+            .map(Self::Pattern)
+            .map_err(|e| Error::Query(format!("invalid pattern `{}`: {}", pattern, e)))
+    }
+
+    /// Whether `name` matches
+    pub fn is_match(&self, name: &str) -> bool {
+        match self {
+            Self::Substring {
+                pattern,
+                ignore_case: true,
+            } => name.to_lowercase().contains(pattern.as_str()),
+            Self::Substring { pattern, .. } => name.contains(pattern.as_str()),
+            Self::Pattern(re) => re.is_match(name),
+        }
+    }
+}
+
+/// Translate a glob into an anchored regex
+fn glob_to_regex(glob: &str) -> String {
+    let mut out = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => out.push_str(".*"),
+            '?' => out.push('.'),
+            c => out.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    out.push('$');
+    out
+}
+
+/// Up to `n` of `candidates` closest to `name` by edit distance, closest first
+///
+/// A candidate starting with something close to `name` counts too, one edit
+/// further away, so `usb_prob` suggests `usb_probe_interface`. Candidates
+/// more than a third of `name`'s length away are left out.
+pub fn closest_names<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>, n: usize) -> Vec<String> {
+    let wanted: Vec<char> = name.chars().collect();
+    let limit = (wanted.len() / 3).max(1);
+    let mut scored: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .filter_map(|candidate| {
+            let chars: Vec<char> = candidate.chars().collect();
+            let mut score = edit_distance(&wanted, &chars);
+            if chars.len() > wanted.len() {
+                score = score.min(edit_distance(&wanted, &chars[..wanted.len()]) + 1);
+            }
+            (score <= limit).then_some((score, candidate))
+        })
+        .collect();
+    scored.sort();
+    scored.dedup();
+    scored.into_iter().take(n).map(|(_, name)| name.to_string()).collect()
+}
+
+/// Levenshtein distance between `a` and `b`
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_modes() {
+        let glob = SymbolMatcher::new("usb_*_probe", SearchMode::Glob).unwrap();
+        assert!(glob.is_match("usb_storage_probe"));
+        assert!(!glob.is_match("my_usb_storage_probe"));
+
+        let re = SymbolMatcher::new("^ext4_.*_iomap$", SearchMode::Regex).unwrap();
+        assert!(re.is_match("ext4_file_iomap"));
+        assert!(!re.is_match("ext4_iomap_begin"));
+
+        let sub = SymbolMatcher::with_case("PROBE", SearchMode::Substring, true).unwrap();
+        assert!(sub.is_match("usb_probe"));
+        assert!(!SymbolMatcher::new("PROBE", SearchMode::Substring)
+            .unwrap()
+            .is_match("usb_probe"));
+
+        let err = SymbolMatcher::new("ext4_(", SearchMode::Regex).unwrap_err();
+        assert!(err.to_string().contains("invalid pattern"), "{}", err);
+
+        // Huge repetitions are rejected instead of compiling for ages
+        assert!(SymbolMatcher::new("(a{1000}){1000}", SearchMode::Regex).is_err());
+    }
+
+    #[test]
+    fn test_closest_names() {
+        let names = ["usb_probe", "usb_probe_interface", "usb_disconnect", "pci_probe"];
+        assert_eq!(
+            closest_names("usb_prob", names, 2),
+            vec!["usb_probe", "usb_probe_interface"]
+        );
+        assert_eq!(closest_names("usb_disconect", names, 5), vec!["usb_disconnect"]);
+        assert!(closest_names("ext4_fill_super", names, 5).is_empty());
+    }
+}
@@ -0,0 +1,40 @@
+//! Finding functions by the types of their parameters
+//!
+//! Types are compared without qualifiers (`const`, `__user`, ...) and with
+//! whitespace around `*` ignored, so `const struct usb_interface *` and
+//! `struct usb_interface*` match. Pointer depth must agree.
+
+use crate::QueryEngine;
+use flowsight_core::FunctionDef;
+
+/// Words that don't change which type a parameter has
+const QUALIFIERS: &[&str] = &[
+    "const", "volatile", "restrict", "__restrict", "__user", "__iomem", "__rcu", "__percpu", "__force",
+];
+
+impl QueryEngine {
+    /// Functions with a parameter of type `type_name`, by name
+    pub fn find_by_param_type(&self, type_name: &str) -> Vec<&FunctionDef> {
+        let wanted = normalize_type(type_name);
+        let mut found: Vec<&FunctionDef> = self
+            .index
+            .functions
+            .values()
+            .filter(|f| f.params.iter().any(|p| normalize_type(&p.type_name) == wanted))
+            .collect();
+        found.sort_by(|a, b| a.name.cmp(&b.name));
+        found
+    }
+}
+
+/// Base type without qualifiers, and the pointer depth
+fn normalize_type(type_name: &str) -> (String, usize) {
+    let depth = type_name.matches('*').count();
+    let base = type_name
+        .replace('*', " ")
+        .split_whitespace()
+        .filter(|word| !QUALIFIERS.contains(word))
+        .collect::<Vec<_>>()
+        .join(" ");
+    (base, depth)
+}
@@ -0,0 +1,83 @@
+//! Structural similarity between functions, to spot copy-pasted handlers
+//!
+//! Two functions are compared by what they call, counting repeats, and by
+//! the shape of their control flow (complexity and nesting depth).
+
+use crate::QueryEngine;
+use flowsight_core::FunctionDef;
+use std::collections::HashMap;
+
+/// Share of the score carried by the calls; the rest is control-flow shape
+const CALLS_WEIGHT: f64 = 0.7;
+
+impl QueryEngine {
+    /// Similarity of functions `a` and `b`, from 0.0 (unrelated or unknown) to 1.0
+    pub fn similarity(&self, a: &str, b: &str) -> f64 {
+        match (self.index.get_function(a), self.index.get_function(b)) {
+            (Some(a), Some(b)) => function_similarity(a, b),
+            _ => 0.0,
+        }
+    }
+
+    /// Up to `n` other functions most similar to `name`, best first
+    pub fn most_similar(&self, name: &str, n: usize) -> Vec<(&FunctionDef, f64)> {
+        let Some(target) = self.index.get_function(name) else {
+            return Vec::new();
+        };
+        let mut scored: Vec<(&FunctionDef, f64)> = self
+            .index
+            .functions
+            .values()
+            .filter(|f| f.name != name)
+            .map(|f| (f, function_similarity(target, f)))
+            .filter(|&(_, score)| score > 0.0)
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.name.cmp(&b.0.name)));
+        scored.truncate(n);
+        scored
+    }
+}
+
+fn function_similarity(a: &FunctionDef, b: &FunctionDef) -> f64 {
+    let shape = (closeness(a.complexity, b.complexity) + closeness(a.max_nesting, b.max_nesting)) / 2.0;
+    CALLS_WEIGHT * calls_similarity(a, b) + (1.0 - CALLS_WEIGHT) * shape
+}
+
+/// Weighted Jaccard index of the two call multisets
+fn calls_similarity(a: &FunctionDef, b: &FunctionDef) -> f64 {
+    let (a, b) = (call_counts(a), call_counts(b));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let (mut shared, mut total) = (0, 0);
+    for callee in a.keys().chain(b.keys().filter(|c| !a.contains_key(*c))) {
+        let (x, y) = (a.get(callee).copied().unwrap_or(0), b.get(callee).copied().unwrap_or(0));
+        shared += x.min(y);
+        total += x.max(y);
+    }
+    shared as f64 / total as f64
+}
+
+/// How often each function is called; once each when call sites aren't recorded
+fn call_counts(func: &FunctionDef) -> HashMap<&str, usize> {
+    let mut counts = HashMap::new();
+    if func.call_sites.is_empty() {
+        for callee in &func.calls {
+            counts.insert(callee.as_str(), 1);
+        }
+    } else {
+        for site in &func.call_sites {
+            *counts.entry(site.callee.as_str()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// 1.0 for equal metrics, falling towards 0.0 as they diverge
+fn closeness(a: u32, b: u32) -> f64 {
+    if a == b {
+        1.0
+    } else {
+        f64::from(a.min(b)) / f64::from(a.max(b))
+    }
+}
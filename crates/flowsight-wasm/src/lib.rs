@@ -0,0 +1,70 @@
+//! FlowSight for the browser
+//!
+//! Thin `wasm-bindgen` entry points over the parser and analyzer, for a
+//! zero-install web demo. Build with
+//! `wasm-pack build crates/flowsight-wasm --target web`; no index database,
+//! thread pool or filesystem is involved.
+//!
+//! The tree-sitter runtime and the C grammar are C code, so the build needs
+//! a clang that can target wasm32 (`CC_wasm32_unknown_unknown=clang`). The
+//! parser and analyzer still link `std::fs` for their header and cache
+//! helpers; on wasm32-unknown-unknown those calls compile but fail at run
+//! time, which is why the entry points here only take in-memory buffers.
+
+use flowsight_analysis::{AnalysisResult, Analyzer};
+use flowsight_core::Result;
+use flowsight_parser::treesitter::TreeSitterParser;
+use flowsight_parser::ParseResult;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+/// Name given to the analyzed buffer in locations
+const SOURCE_NAME: &str = "input.c";
+
+/// Everything known about one source buffer
+#[derive(Debug, Serialize)]
+pub struct SourceAnalysis {
+    pub parse: ParseResult,
+    pub analysis: AnalysisResult,
+}
+
+/// Parse and analyze one C source buffer
+pub fn analyze(source: &str) -> Result<SourceAnalysis> {
+    let mut parse = TreeSitterParser::new().parse_source(source, SOURCE_NAME)?;
+    let analysis = Analyzer::new().analyze(source, &mut parse)?;
+    Ok(SourceAnalysis { parse, analysis })
+}
+
+/// Parse and analyze `source`, returning `{ parse, analysis }` as a JS object
+///
+/// The shapes follow the `ParseResult` and `AnalysisResult` JSON Schemas
+/// (`flowsight schema`).
+#[wasm_bindgen(js_name = analyzeSource)]
+pub fn analyze_source(source: &str) -> std::result::Result<JsValue, JsError> {
+    let result = analyze(source).map_err(|e| JsError::new(&e.to_string()))?;
+    let json = serde_json::to_string(&result)?;
+    js_sys::JSON::parse(&json).map_err(|_| JsError::new("failed to convert analysis to a JS value"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze() {
+        let source = r#"
+static void my_work_fn(struct work_struct *work) { }
+static int my_probe(void) {
+    INIT_WORK(&priv->work, my_work_fn);
+    return 0;
+}
+"#;
+        let result = analyze(source).unwrap();
+        assert!(result.parse.functions.contains_key("my_probe"));
+        assert_eq!(result.analysis.async_bindings[0].handler, "my_work_fn");
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["parse"]["functions"]["my_probe"]["location"]["file"], SOURCE_NAME);
+        assert!(json["analysis"]["flow_trees"].is_array());
+    }
+}
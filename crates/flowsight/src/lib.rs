@@ -0,0 +1,293 @@
+//! FlowSight
+//!
+//! The entry point for embedding FlowSight. A [`Project`] indexes a source
+//! tree once and owns the index and knowledge base, so analyzing a file,
+//! querying symbols or running the checkers takes a single call:
+//!
+//! ```no_run
+//! let project = flowsight::Project::open("drivers/net/ethernet/intel/e1000")?;
+//! let analysis = project.analyze_file("e1000_main.c")?;
+//! println!("{} entry points", analysis.analysis.entry_points.len());
+//! for finding in project.check()? {
+//!     println!("{}", finding.message());
+//! }
+//! # Ok::<(), flowsight::Error>(())
+//! ```
+//!
+//! The individual crates stay available below for finer control, and
+//! [`rpc`] serves a project to other processes over JSON-RPC.
+
+pub mod rpc;
+
+use flowsight_analysis::async_tracker::AsyncTracker;
+use flowsight_analysis::atomic_check::AtomicChecker;
+use flowsight_analysis::control_flow::ControlFlowChecker;
+use flowsight_analysis::driver_check::find_missing_callbacks;
+use flowsight_analysis::error_check::ErrorChecker;
+use flowsight_analysis::funcptr::FuncPtrResolver;
+use flowsight_analysis::irq_check::IrqChecker;
+use flowsight_analysis::lock_balance::LockBalanceChecker;
+use flowsight_index::{IgnoreRules, SymbolIndex};
+use flowsight_parser::parallel::ParallelParser;
+use flowsight_parser::preprocessor::HeaderResolver;
+use std::path::{Path, PathBuf};
+
+pub use flowsight_analysis::finding::Finding;
+pub use flowsight_analysis::AnalysisResult;
+pub use flowsight_core::{Error, Result};
+pub use flowsight_knowledge::KnowledgeBase;
+pub use flowsight_parser::ParseResult;
+pub use flowsight_query::QueryEngine;
+
+pub use flowsight_analysis as analysis;
+pub use flowsight_index as index;
+pub use flowsight_knowledge as knowledge;
+pub use flowsight_parser as parser;
+pub use flowsight_query as query;
+pub use flowsight_core as types;
+
+/// A parsed and analyzed source file
+#[derive(Debug)]
+pub struct FileAnalysis {
+    pub parse_result: ParseResult,
+    pub analysis: AnalysisResult,
+}
+
+/// An indexed source tree
+pub struct Project {
+    root: PathBuf,
+    files: Vec<PathBuf>,
+    knowledge_base: KnowledgeBase,
+    engine: QueryEngine,
+}
+
+impl Project {
+    /// Index the C and Rust sources under `dir` with the built-in knowledge base
+    ///
+    /// Paths listed in the directory's `.flowsightignore` are skipped.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        Self::open_with_knowledge_base(dir, KnowledgeBase::builtin())
+    }
+
+    /// Like [`Project::open`], also skipping paths matching the `ignore` globs
+    pub fn open_ignoring<S: AsRef<str>>(dir: impl AsRef<Path>, ignore: &[S]) -> Result<Self> {
+        Self::open_with(dir, KnowledgeBase::builtin(), ignore)
+    }
+
+    /// Index the C and Rust sources under `dir`, using `kb` for analysis and checks
+    pub fn open_with_knowledge_base(dir: impl AsRef<Path>, kb: KnowledgeBase) -> Result<Self> {
+        Self::open_with(dir, kb, &[] as &[&str])
+    }
+
+    fn open_with<S: AsRef<str>>(dir: impl AsRef<Path>, kb: KnowledgeBase, ignore: &[S]) -> Result<Self> {
+        let root = dir.as_ref().to_path_buf();
+        if !root.is_dir() {
+            return Err(Error::FileNotFound(root.display().to_string()));
+        }
+        let ignore = IgnoreRules::for_project(&root, ignore).map_err(|e| Error::Config(e.to_string()))?;
+        let mut files: Vec<PathBuf> = ignore
+            .walk(&root)
+            .filter(|e| e.file_type().is_file())
+            .map(|e| e.into_path())
+            .filter(|p| {
+                p.extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| flowsight_parser::SUPPORTED_EXTENSIONS.contains(&ext))
+            })
+            .collect();
+        files.sort();
+
+        let parser = ParallelParser::new().with_header_resolver(HeaderResolver::for_project(&root));
+        let async_tracker = AsyncTracker::with_knowledge(&kb);
+        let funcptr_resolver = FuncPtrResolver::new();
+        let mut index = SymbolIndex::with_root(&root);
+        for (file, result) in parser.parse_files(&files) {
+            let Ok(parse_result) = result else {
+                continue;
+            };
+            for func in parse_result.functions.values() {
+                index.add_function(func.clone(), &file);
+            }
+            for st in parse_result.structs.values() {
+                index.add_struct(st.clone());
+            }
+            for occurrence in &parse_result.occurrences {
+                index.add_occurrence(occurrence.clone());
+            }
+            if let Ok(source) = std::fs::read_to_string(&file) {
+                for binding in async_tracker.analyze(&source, &parse_result.functions) {
+                    let registered_by = binding
+                        .bind_location
+                        .as_ref()
+                        .and_then(|loc| enclosing_function(&parse_result, loc.line));
+                    index.add_async_binding(binding, registered_by);
+                }
+                for assignment in funcptr_resolver.find_ops_assignments(&source, &file.to_string_lossy()) {
+                    index.add_ops_assignment(assignment);
+                }
+            }
+        }
+        for (file, headers) in parser.include_map() {
+            index.set_includes(&file, headers);
+        }
+
+        Ok(Self {
+            root,
+            files,
+            knowledge_base: kb,
+            engine: QueryEngine::with_index(index),
+        })
+    }
+
+    /// Directory the project was opened on
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Source files indexed, in path order
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Knowledge base used for analysis and checks
+    pub fn knowledge_base(&self) -> &KnowledgeBase {
+        &self.knowledge_base
+    }
+
+    /// Symbol queries over the whole project
+    pub fn query(&self) -> &QueryEngine {
+        &self.engine
+    }
+
+    /// Parse and analyze one file; a relative `path` is taken from the project root
+    pub fn analyze_file(&self, path: impl AsRef<Path>) -> Result<FileAnalysis> {
+        let path = self.resolve(path.as_ref());
+        let source = std::fs::read_to_string(&path)?;
+        let mut parse_result = flowsight_parser::get_parser_for(&path)?.parse(&source, &path.to_string_lossy())?;
+        let mut analyzer = flowsight_analysis::Analyzer::with_knowledge_base(self.knowledge_base.clone());
+        let analysis = analyzer.analyze(&source, &mut parse_result)?;
+        Ok(FileAnalysis {
+            parse_result,
+            analysis,
+        })
+    }
+
+    /// Run every checker over every source file of the project but headers
+    ///
+    /// IRQ handlers are followed into functions of other files through the
+    /// index, and sleeping calls in atomic context are looked for over the
+    /// whole project.
+    pub fn check(&self) -> Result<Vec<Finding>> {
+        let checker = ErrorChecker::new();
+        let loop_checker = ControlFlowChecker::new();
+        let lock_checker = LockBalanceChecker::new();
+        let irq_checker = IrqChecker::new();
+        let resolver = FuncPtrResolver::new();
+        let kb = &self.knowledge_base;
+        let functions = &self.engine.index().functions;
+
+        let mut findings = Vec::new();
+        for file in self.files.iter().filter(|f| f.extension().and_then(|ext| ext.to_str()) != Some("h")) {
+            let source = std::fs::read_to_string(file)?;
+            let filename = file.to_string_lossy();
+            let parse_result = flowsight_parser::get_parser_for(file)?.parse(&source, &filename)?;
+            let local = &parse_result.functions;
+
+            findings.extend(Finding::from_unchecked(&filename, checker.check(&source, local, kb)));
+            findings.extend(Finding::from_user_copies(&filename, checker.check_user_copies(&source, local)));
+            findings.extend(Finding::from_infinite_loops(&filename, loop_checker.find_infinite_loops(&source)));
+            findings.extend(Finding::from_unbalanced_locks(&filename, lock_checker.find_unbalanced(&source)));
+            let assignments = resolver.find_ops_assignments(&source, &filename);
+            findings.extend(Finding::from_missing_callbacks(&filename, find_missing_callbacks(&assignments, kb)));
+            findings.extend(Finding::from_irq_reports(
+                &filename,
+                irq_checker.check(&source, functions, kb),
+                functions,
+            ));
+        }
+
+        let index = self.engine.index();
+        let bindings: Vec<_> = index.async_bindings.values().flatten().map(|b| b.binding.clone()).collect();
+        let assignments: Vec<_> = index.ops_assignments.values().flatten().cloned().collect();
+        let root = self.root.to_string_lossy();
+        findings.extend(Finding::from_atomic_sleeps(
+            &root,
+            AtomicChecker::new().check(functions, &bindings, &assignments, kb),
+            functions,
+        ));
+        Ok(findings)
+    }
+
+    fn resolve(&self, path: &Path) -> PathBuf {
+        match path.is_relative() {
+            true => self.root.join(path),
+            false => path.to_path_buf(),
+        }
+    }
+}
+
+/// Name of the function of `parse_result` spanning `line`
+fn enclosing_function(parse_result: &ParseResult, line: u32) -> Option<String> {
+    parse_result
+        .functions
+        .values()
+        .find(|f| f.location.as_ref().is_some_and(|l| l.line <= line && line <= l.end_line))
+        .map(|f| f.name.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(
+            root.path().join("drv.c"),
+            r#"
+static int helper(int x)
+{
+    return x + 1;
+}
+
+static int drv_probe(struct usb_interface *intf, const struct usb_device_id *id)
+{
+    struct drv_priv *priv = kzalloc(sizeof(*priv), GFP_KERNEL);
+    priv->count = helper(0);
+    return 0;
+}
+
+static struct usb_driver drv_driver = {
+    .name = "drv",
+    .probe = drv_probe,
+};
+"#,
+        )
+        .unwrap();
+
+        let project = Project::open(root.path()).unwrap();
+        assert_eq!(project.files().len(), 1);
+        assert!(project.query().index().get_function("helper").is_some());
+
+        let analysis = project.analyze_file("drv.c").unwrap();
+        assert!(analysis.parse_result.functions.contains_key("drv_probe"));
+
+        let findings = project.check().unwrap();
+        assert!(findings.iter().any(|f| f.rule().id == "unchecked-result"));
+        assert!(findings.iter().any(|f| f.rule().id == "missing-callback"));
+
+        assert!(Project::open(root.path().join("missing")).is_err());
+
+        std::fs::create_dir(root.path().join("tests")).unwrap();
+        std::fs::write(root.path().join("tests/stub.c"), "int stub(void) { return 0; }\n").unwrap();
+        assert_eq!(Project::open(root.path()).unwrap().files().len(), 2);
+        let project = Project::open_ignoring(root.path(), &["tests"]).unwrap();
+        assert_eq!(project.files().len(), 1);
+        assert!(project.query().index().get_function("stub").is_none());
+
+        std::fs::write(root.path().join("drv.rs"), "fn rust_helper() {}\n").unwrap();
+        let project = Project::open(root.path()).unwrap();
+        assert!(project.query().index().get_function("rust_helper").is_some());
+        assert!(project.check().is_ok());
+    }
+}
@@ -0,0 +1,434 @@
+//! JSON-RPC server over stdio
+//!
+//! A lighter integration point than the LSP server for editor plugins and
+//! scripts: `flowsight serve <dir>` indexes the project once, then answers
+//! JSON-RPC 2.0 requests read one per line from stdin with one response per
+//! line on stdout, so each query costs no startup or indexing.
+//!
+//! | Method | Params | Result |
+//! |--------|--------|--------|
+//! | `analyze` | [`FileParams`] | [`AnalysisResult`] of the file |
+//! | `search` | [`SearchParams`] | [`SymbolInfo`] of each matching function |
+//! | `callers` | [`FunctionParams`] | [`CallerInfo`] of each direct or async caller |
+//! | `callees` | [`FunctionParams`] | [`SymbolInfo`] of each callee, without location if not indexed |
+//! | `flow` | [`FunctionParams`] | [`FlowNode`] rooted at the function |
+//! | `scenario` | [`Scenario`] | [`ExecutionPath`] of the scenario |
+//!
+//! Relative file paths are taken from the project root. A request without
+//! an `id` is a notification and gets no response; `shutdown` answers
+//! `null` and stops the server.
+//!
+//! ```text
+//! → {"jsonrpc": "2.0", "id": 1, "method": "callers", "params": {"function": "my_work"}}
+//! ← {"jsonrpc":"2.0","id":1,"result":[{"caller":"my_probe","call_type":{"Async":...}}]}
+//! ```
+//!
+//! [`AnalysisResult`]: crate::AnalysisResult
+
+use crate::Project;
+use flowsight_analysis::callgraph;
+use flowsight_analysis::scenario::{ExecutionPath, Scenario, ScenarioExecutor};
+use flowsight_analysis::AnalysisConfig;
+use flowsight_core::{CallType, FlowNode, Location};
+use flowsight_query::SearchMode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+/// JSON-RPC error codes
+pub const PARSE_ERROR: i64 = -32700;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+/// A request, or a notification when `id` is absent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Request {
+    #[serde(default)]
+    pub jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+/// Reply to a request: `result` on success, `error` otherwise
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+/// Error object of a failed request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+/// Params of `analyze`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileParams {
+    pub file: PathBuf,
+}
+
+/// Params of `search`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchParams {
+    pub pattern: String,
+    /// `substring` (default), `regex` or `glob`
+    #[serde(default)]
+    pub mode: SearchMode,
+}
+
+/// Params of `callers`, `callees` and `flow`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionParams {
+    pub function: String,
+}
+
+/// A function and where it is defined
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SymbolInfo {
+    pub name: String,
+    pub location: Option<Location>,
+}
+
+/// A function calling, or registering as an async handler, the queried one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallerInfo {
+    pub caller: String,
+    pub call_type: CallType,
+}
+
+/// Answers requests from a project held in memory
+pub struct Server {
+    project: Project,
+}
+
+impl Server {
+    pub fn new(project: Project) -> Self {
+        Self { project }
+    }
+
+    /// The project requests are answered from
+    pub fn project(&self) -> &Project {
+        &self.project
+    }
+
+    /// Answer requests read line by line from `input` until EOF or `shutdown`
+    ///
+    /// Blank lines are skipped; a line that is not a request gets a parse
+    /// error with a `null` id.
+    pub fn serve(&self, input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (response, shutdown) = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => (self.handle(&request), request.method == "shutdown"),
+                Err(e) => (Some(error_response(Value::Null, PARSE_ERROR, e.to_string())), false),
+            };
+            if let Some(response) = response {
+                serde_json::to_writer(&mut output, &response)?;
+                writeln!(output)?;
+                output.flush()?;
+            }
+            if shutdown {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Run `request`, returning its response unless it is a notification
+    pub fn handle(&self, request: &Request) -> Option<Response> {
+        let result = self.dispatch(&request.method, &request.params);
+        let id = request.id.clone()?;
+        Some(match result {
+            Ok(result) => Response {
+                jsonrpc: "2.0".to_string(),
+                id,
+                result: Some(result),
+                error: None,
+            },
+            Err(error) => error_response(id, error.code, error.message),
+        })
+    }
+
+    fn dispatch(&self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        match method {
+            "analyze" => {
+                let params: FileParams = parse_params(params)?;
+                let analysis = self.project.analyze_file(&params.file).map_err(internal_error)?;
+                to_value(&analysis.analysis)
+            }
+            "search" => {
+                let params: SearchParams = parse_params(params)?;
+                let functions = self
+                    .project
+                    .query()
+                    .search_functions(&params.pattern, params.mode)
+                    .map_err(|e| invalid_params(e.to_string()))?;
+                let mut symbols: Vec<SymbolInfo> = functions
+                    .into_iter()
+                    .map(|f| SymbolInfo {
+                        name: f.name.clone(),
+                        location: f.location.clone(),
+                    })
+                    .collect();
+                symbols.sort_by(|a, b| a.name.cmp(&b.name));
+                to_value(&symbols)
+            }
+            "callers" => {
+                let params: FunctionParams = parse_params(params)?;
+                let callers: Vec<CallerInfo> = self
+                    .project
+                    .query()
+                    .get_caller_edges(&params.function)
+                    .into_iter()
+                    .map(|edge| CallerInfo {
+                        caller: edge.caller,
+                        call_type: edge.call_type,
+                    })
+                    .collect();
+                to_value(&callers)
+            }
+            "callees" => {
+                let params: FunctionParams = parse_params(params)?;
+                let engine = self.project.query();
+                if engine.get_function(&params.function).is_none() {
+                    return Err(self.not_found(&params.function));
+                }
+                let callees: Vec<SymbolInfo> = engine
+                    .get_callees(&params.function)
+                    .into_iter()
+                    .map(|name| SymbolInfo {
+                        location: engine.get_function(&name).and_then(|f| f.location.clone()),
+                        name,
+                    })
+                    .collect();
+                to_value(&callees)
+            }
+            "flow" => {
+                let params: FunctionParams = parse_params(params)?;
+                let (tree, _) = self.flow(&params.function)?;
+                to_value(&tree)
+            }
+            "scenario" => {
+                let scenario: Scenario = parse_params(params)?;
+                to_value(&self.run_scenario(&scenario)?)
+            }
+            "shutdown" => Ok(Value::Null),
+            _ => Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("Method not supported: {}", method),
+            }),
+        }
+    }
+
+    /// Flow tree rooted at `function`, with the analysis of its file
+    ///
+    /// Entry points get the tree the analyzer built, kernel call chain
+    /// included; any other function gets one built on demand.
+    fn flow(&self, function: &str) -> Result<(FlowNode, crate::FileAnalysis), RpcError> {
+        let file = self
+            .project
+            .query()
+            .get_function(function)
+            .and_then(|f| f.location.as_ref())
+            .map(|loc| PathBuf::from(&loc.file))
+            .ok_or_else(|| self.not_found(function))?;
+        let mut analysis = self.project.analyze_file(&file).map_err(internal_error)?;
+
+        if let Some(i) = analysis.analysis.flow_trees.iter().position(|t| t.name == function) {
+            let tree = analysis.analysis.flow_trees.swap_remove(i);
+            return Ok((tree, analysis));
+        }
+        let bindings = &analysis.analysis.async_bindings;
+        let mut tree = callgraph::build_full_flow_tree(
+            function,
+            &analysis.parse_result,
+            bindings,
+            self.project.knowledge_base(),
+            &AnalysisConfig::default(),
+        )
+        .ok_or_else(|| self.not_found(function))?;
+        callgraph::propagate_execution_context(&mut tree, bindings);
+        Ok((tree, analysis))
+    }
+
+    fn run_scenario(&self, scenario: &Scenario) -> Result<ExecutionPath, RpcError> {
+        let (tree, analysis) = self.flow(&scenario.entry_function)?;
+        let kb = self.project.knowledge_base();
+        let mut executor = ScenarioExecutor::new(scenario.options.clone())
+            .with_async_timelines(kb)
+            .with_constants(kb.constants.clone())
+            .with_defines(&analysis.parse_result.defines)
+            .with_functions(&analysis.parse_result.functions);
+        Ok(executor.execute(scenario, &tree))
+    }
+
+    fn not_found(&self, function: &str) -> RpcError {
+        let suggestions = self.project.query().suggest(function, 3);
+        let message = match suggestions.is_empty() {
+            true => format!("Function '{}' not found", function),
+            false => format!("Function '{}' not found. Did you mean: {}?", function, suggestions.join(", ")),
+        };
+        invalid_params(message)
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: &Value) -> Result<T, RpcError> {
+    T::deserialize(params).map_err(|e| invalid_params(e.to_string()))
+}
+
+fn to_value<T: Serialize>(value: &T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(internal_error)
+}
+
+fn invalid_params(message: String) -> RpcError {
+    RpcError {
+        code: INVALID_PARAMS,
+        message,
+    }
+}
+
+fn internal_error(e: impl std::fmt::Display) -> RpcError {
+    RpcError {
+        code: INTERNAL_ERROR,
+        message: e.to_string(),
+    }
+}
+
+fn error_response(id: Value, code: i64, message: String) -> Response {
+    Response {
+        jsonrpc: "2.0".to_string(),
+        id,
+        result: None,
+        error: Some(RpcError { code, message }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_serve() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(
+            root.path().join("drv.c"),
+            r#"
+static int helper(int x)
+{
+    return x + 1;
+}
+
+static int drv_probe(struct usb_interface *intf, const struct usb_device_id *id)
+{
+    if (id->idVendor == 0x1234)
+        return helper(1);
+    return helper(0);
+}
+
+static struct usb_driver drv_driver = {
+    .name = "drv",
+    .probe = drv_probe,
+};
+"#,
+        )
+        .unwrap();
+        let server = Server::new(Project::open(root.path()).unwrap());
+
+        let input = [
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "search", "params": {"pattern": "*probe", "mode": "glob"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "callers", "params": {"function": "helper"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "callees", "params": {"function": "drv_probe"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 4, "method": "flow", "params": {"function": "helper"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 5, "method": "analyze", "params": {"file": "drv.c"}}"#,
+            r#"{"jsonrpc": "2.0", "method": "callers", "params": {"function": "helper"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 6, "method": "callees", "params": {"function": "drv_prob"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 7, "method": "frobnicate"}"#,
+            "not json",
+            r#"{"jsonrpc": "2.0", "id": 8, "method": "shutdown"}"#,
+            r#"{"jsonrpc": "2.0", "id": 9, "method": "search", "params": {"pattern": "helper"}}"#,
+        ]
+        .join("\n");
+        let mut output = Vec::new();
+        server.serve(input.as_bytes(), &mut output).unwrap();
+
+        let responses: Vec<Response> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        // The notification gets no response, and nothing is read after shutdown
+        let ids: Vec<Value> = responses.iter().map(|r| r.id.clone()).collect();
+        assert_eq!(
+            ids,
+            vec![json!(1), json!(2), json!(3), json!(4), json!(5), json!(6), json!(7), Value::Null, json!(8)]
+        );
+
+        let result = |i: usize| responses[i].result.clone().unwrap();
+        let names = |value: Value| -> Vec<String> {
+            let symbols: Vec<SymbolInfo> = serde_json::from_value(value).unwrap();
+            symbols.into_iter().map(|s| s.name).collect()
+        };
+        assert_eq!(names(result(0)), vec!["drv_probe"]);
+        let callers: Vec<CallerInfo> = serde_json::from_value(result(1)).unwrap();
+        assert_eq!(callers.len(), 1);
+        assert_eq!(callers[0].caller, "drv_probe");
+        assert_eq!(names(result(2)), vec!["helper"]);
+        assert_eq!(result(3)["name"], "helper");
+        assert!(result(4)["entry_points"].as_array().unwrap().iter().any(|e| e == "drv_probe"));
+
+        let error = |i: usize| responses[i].error.clone().unwrap();
+        assert_eq!(error(5).code, INVALID_PARAMS);
+        assert!(error(5).message.contains("drv_probe"));
+        assert_eq!(error(6).code, METHOD_NOT_FOUND);
+        assert_eq!(error(7).code, PARSE_ERROR);
+        // `"result": null` reads back as `None`
+        assert!(responses[8].error.is_none());
+    }
+
+    #[test]
+    fn test_scenario() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(
+            root.path().join("drv.c"),
+            r#"
+static int drv_probe(struct usb_interface *intf, const struct usb_device_id *id)
+{
+    if (id->idVendor == 0x1234)
+        return setup_a(intf);
+    return setup_b(intf);
+}
+"#,
+        )
+        .unwrap();
+        let server = Server::new(Project::open(root.path()).unwrap());
+
+        let request: Request = serde_json::from_str(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "scenario", "params": {
+                "name": "vendor",
+                "entry_function": "drv_probe",
+                "bindings": [{"path": "id->idVendor", "value": {"type": "Integer", "value": 4660}}]
+            }}"#,
+        )
+        .unwrap();
+        let response = server.handle(&request).unwrap();
+        let path: ExecutionPath = serde_json::from_value(response.result.unwrap()).unwrap();
+        assert!(path.completed);
+        assert!(path.flow_tree.is_some());
+    }
+}